@@ -0,0 +1,253 @@
+//! Action hints: what an owner can do right now on each chain variant.
+//!
+//! Every hint is evaluated with the same predicates the contract checks before acting, so a
+//! hint is offered exactly when its operation would be taken. Each hint carries what its
+//! operation needs beyond the owner's own choices (stakes, stances, bet amounts).
+
+use async_graphql::{SimpleObject, Union};
+use linera_sdk::{
+    linera_base_types::{AccountOwner, ChainId, Timestamp},
+    views::ViewError,
+};
+use majorules::{
+    schedule::{ScheduleLimits, SchedulePhase},
+    QueueMode, ATTESTATION_WINDOW,
+};
+
+use crate::state::{BattleState, LobbyState, PlayerState};
+
+/// Most hints listed per kind, e.g. characters or open markets
+pub const MAX_HINTS_PER_KIND: usize = 20;
+
+/// `JoinQueue` for a character in a mode, with the reason it would be refused if it would be
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanQueue {
+    pub character_id: String,
+    pub mode: QueueMode,
+    pub blocked_by: Option<String>,
+}
+
+/// `SubmitTurn` for a turn of the current round not yet submitted
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct MustSubmitTurn {
+    pub round: u8,
+    pub turn: u8,
+    pub deadline: Option<Timestamp>,
+}
+
+/// `RevealTurn` for a sealed turn both fighters locked in
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct MustRevealTurn {
+    pub round: u8,
+    pub turn: u8,
+    pub closes_at: Timestamp,
+}
+
+/// `ClaimForfeit` on a turn the opponent left sealed past its reveal window, or that an enemy
+/// team left unsubmitted past the round deadline
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanClaimForfeit {
+    pub round: u8,
+    pub turn: u8,
+}
+
+/// `AttestResult` on the finalized outcome
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanAttest {
+    pub closes_at: Timestamp,
+}
+
+/// `Crank` with deferred work waiting; `estimated_work` is the steps it would take
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanCrank {
+    pub estimated_work: u32,
+}
+
+/// `PlaceBet` on an open market, by its external id
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanPlaceBet {
+    pub market_id: u64,
+    pub player1_chain: ChainId,
+    pub player2_chain: ChainId,
+}
+
+/// `LeaveQueue` releasing a queued or held character; it releases the owner's others with it
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct CanLeaveQueue {
+    pub character_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Union)]
+pub enum PlayerAction {
+    CanQueue(CanQueue),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Union)]
+pub enum BattleAction {
+    MustSubmitTurn(MustSubmitTurn),
+    MustRevealTurn(MustRevealTurn),
+    CanClaimForfeit(CanClaimForfeit),
+    CanAttest(CanAttest),
+}
+
+#[allow(clippy::enum_variant_names)] // Named for the hints, as in the schema
+#[derive(Debug, Clone, PartialEq, Eq, Union)]
+pub enum LobbyAction {
+    CanCrank(CanCrank),
+    CanPlaceBet(CanPlaceBet),
+    CanLeaveQueue(CanLeaveQueue),
+}
+
+/// Queue hints for each of the chain owner's characters; other signers cannot act here
+pub async fn player_actions(player: &PlayerState, owner: AccountOwner) -> Result<Vec<PlayerAction>, ViewError> {
+    if *player.owner.get() != Some(owner) {
+        return Ok(Vec::new());
+    }
+    let mut actions = Vec::new();
+    for character_id in player.characters.indices().await?.into_iter().take(MAX_HINTS_PER_KIND) {
+        for mode in QueueMode::ALL {
+            let blocked_by = player.queue_check(&character_id, mode).await.err();
+            actions.push(PlayerAction::CanQueue(CanQueue { character_id: character_id.clone(), mode, blocked_by }));
+        }
+    }
+    Ok(actions)
+}
+
+/// Open turns, pending reveals, forfeit claims and attestation for `owner` at `now`
+pub async fn battle_actions(battle: &BattleState, owner: AccountOwner, now: Timestamp) -> Vec<BattleAction> {
+    let mut actions = Vec::new();
+    let round = *battle.current_round.get();
+    // Team fighters submit in the clear while they stand; nothing is sealed or attested
+    if let Some((side, seat)) = battle.team_roster.get().as_ref().and_then(|roster| roster.seat_of(owner)) {
+        if battle.teams.get()[side][seat].current_hp > 0 {
+            for turn in 0..battle.turns_per_round() {
+                if battle.turn_check(owner, round, turn).await.is_ok() {
+                    actions.push(BattleAction::MustSubmitTurn(MustSubmitTurn { round, turn, deadline: *battle.round_deadline.get() }));
+                }
+            }
+        }
+        let turn = *battle.resolved_turns.get();
+        if battle.team_forfeit_check(owner, round, turn, now).await.is_ok() {
+            actions.push(BattleAction::CanClaimForfeit(CanClaimForfeit { round, turn }));
+        }
+        return actions;
+    }
+    if battle.roster.get().as_ref().and_then(|roster| roster.opponent_of(owner)).is_none() {
+        return actions;
+    }
+    for turn in 0..battle.turns_per_round() {
+        if battle.turn_check(owner, round, turn).await.is_ok() {
+            actions.push(BattleAction::MustSubmitTurn(MustSubmitTurn { round, turn, deadline: *battle.round_deadline.get() }));
+        }
+        if battle.reveal_check(owner, round, turn, now).await.is_ok() {
+            if let Ok(Some(closes_at)) = battle.reveal_deadlines.get(&turn).await {
+                actions.push(BattleAction::MustRevealTurn(MustRevealTurn { round, turn, closes_at }));
+            }
+        }
+        if battle.forfeit_check(owner, round, turn, now).await.is_ok() {
+            actions.push(BattleAction::CanClaimForfeit(CanClaimForfeit { round, turn }));
+        }
+    }
+    if battle.attest_check(owner, now).await.is_ok() {
+        let completed_at = battle.completed_at.get().expect("Attestable battles have completed");
+        actions.push(BattleAction::CanAttest(CanAttest { closes_at: completed_at.saturating_add(ATTESTATION_WINDOW) }));
+    }
+    actions
+}
+
+/// Leaving the queue, betting on open markets and cranking pending maintenance at `now`
+pub async fn lobby_actions(lobby: &LobbyState, owner: AccountOwner, now: Timestamp) -> Result<Vec<LobbyAction>, ViewError> {
+    let mut actions = Vec::new();
+
+    let mut queued: Vec<String> = lobby.queued_entries(owner).await?
+        .into_iter()
+        .map(|entry| entry.character_id)
+        .collect();
+    lobby.held_releases.for_each_index(|(held_by, character_id)| {
+        if held_by == owner {
+            queued.push(character_id);
+        }
+        Ok(())
+    }).await?;
+    for character_id in queued {
+        actions.push(LobbyAction::CanLeaveQueue(CanLeaveQueue { character_id }));
+    }
+
+    // Open markets are exactly those with a deadline
+    let mut open = Vec::new();
+    lobby.market_deadlines.for_each_index_while(|market_id| {
+        open.push(market_id);
+        Ok(open.len() < MAX_HINTS_PER_KIND)
+    }).await?;
+    for market_id in open {
+        let Some(market) = lobby.prediction_markets.get(&market_id).await? else {
+            continue;
+        };
+        if market.accepts_bets() {
+            actions.push(LobbyAction::CanPlaceBet(CanPlaceBet {
+                market_id: lobby.id_codec.get().encode(market_id),
+                player1_chain: market.player1_chain,
+                player2_chain: market.player2_chain,
+            }));
+        }
+    }
+
+    let estimated_work = maintenance_backlog(lobby, now).await?;
+    if estimated_work > 0 {
+        actions.push(LobbyAction::CanCrank(CanCrank { estimated_work: u32::try_from(estimated_work).unwrap_or(u32::MAX) }));
+    }
+    Ok(actions)
+}
+
+/// Steps lobby maintenance would take at `now` with an unlimited budget: one per summary to
+/// send, bet to pay out, overdue market to close or void, stale queue entry or private battle to release
+/// and tournament to start or cancel
+pub async fn maintenance_backlog(lobby: &LobbyState, now: Timestamp) -> Result<u64, ViewError> {
+    let mut work = 0u64;
+    lobby.notification_outbox.for_each_index_value(|_, pending| {
+        work += pending.recipients.len() as u64;
+        Ok(())
+    }).await?;
+
+    let mut settlements = Vec::new();
+    lobby.pending_settlements.for_each_index_value(|market_id, pending| {
+        settlements.push((market_id, pending.next_bettor));
+        Ok(())
+    }).await?;
+    for (market_id, next_bettor) in settlements {
+        let bettors = lobby.market_bettors.get(&market_id).await?.unwrap_or_default();
+        work += (bettors.len() as u64).saturating_sub(next_bettor as u64);
+    }
+
+    lobby.market_deadlines.for_each_index_value(|_, deadline| {
+        work += (*deadline <= now) as u64;
+        Ok(())
+    }).await?;
+    lobby.result_deadlines.for_each_index_value(|_, deadline| {
+        work += (*deadline <= now) as u64;
+        Ok(())
+    }).await?;
+    lobby.waiting_players.for_each_index_value(|_, entry| {
+        work += entry.expired(now) as u64;
+        Ok(())
+    }).await?;
+    lobby.pending_private_battles.for_each_index_value(|_, host| {
+        work += host.expired(now) as u64;
+        Ok(())
+    }).await?;
+
+    let mut due = Vec::new();
+    lobby.tournament_starts.for_each_index_value(|tournament_id, start_time| {
+        if *start_time <= now {
+            due.push(tournament_id);
+        }
+        Ok(())
+    }).await?;
+    for tournament_id in due {
+        if let Some(tournament) = lobby.tournaments.get(&tournament_id).await? {
+            let phase = tournament.schedule.phase(now, tournament.entrants, &ScheduleLimits::default());
+            work += matches!(phase, SchedulePhase::ReadyToStart | SchedulePhase::Expired) as u64;
+        }
+    }
+    Ok(work)
+}
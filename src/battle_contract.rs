@@ -1,31 +1,129 @@
-use crate::state::{BattleState, BattleStatus, BattleParticipant, CombatStats, Stance, TurnSubmission, RoundResult, CombatAction};
-use crate::{Message, Operation};
-use crate::random::random_value;
+use crate::state::{record_rejection, special_cooldown_of, special_plan_start, ActiveEffect, BattleState, StatusEffect, BattleStatus, BattleParticipant, BattlePhase, CombatStats, ReplayTurn, Roster, Stance, TeamRoster, TurnEmote, TurnSubmission, RoundResult, CombatAction, TimingInfo};
+use crate::{Message, Operation, OperationResponse};
+use majorules::{
+    cooldown::{cooldown_schedule, PlanError},
+    damage, fees::{self, DustDestination, FeeBreakdown, RoundingPolicy}, practice::{self, BotView, PracticeDifficulty}, random::{derive_random_u64, turn_seed, AttackRolls, Roll},
+    throttle::RejectionKey, time, turn_commitment,
+    Attestation, BattleEndReason, Bps, BattleEvent, BattleRules, Emote, FighterResult, ItemDrop, SpecialAbility, TiebreakBy, TurnAck, BATTLE_EVENT_STREAM, MAX_TEAM_SIZE,
+};
 use linera_sdk::{
-    linera_base_types::{AccountOwner, Amount, ChainId},
+    linera_base_types::{AccountOwner, Amount, ChainId, TimeDelta},
+    views::View,
     ContractRuntime,
 };
 
-const FP_SCALE: u128 = 1_000_000;
-
-fn mul_fp(a: u128, b: u128) -> u128 {
-    (a * b) / FP_SCALE
-}
+/// Most events a battle keeps in its own log; older ones remain on the event stream
+pub const BATTLE_LOG_CAPACITY: usize = 128;
 
 pub async fn handle_battle_operation(
     operation: Operation,
     state: &mut BattleState,
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
-) {
+) -> OperationResponse {
+    // Signing blocks on this chain is not enough: ownership can change after the battle opens
+    let Some(caller) = runtime.authenticated_signer() else {
+        return OperationResponse::rejected("unauthenticated");
+    };
+    // A battle whose fighters no longer match the ones it was opened for takes no more moves
+    if !fighters_intact(state) {
+        let refused = reject(state, runtime, operation_name(&operation), "roster_mismatch", caller).await;
+        return match operation {
+            Operation::SubmitTurn { .. }
+            | Operation::SubmitTeamTurn { .. }
+            | Operation::CommitTurn { .. }
+            | Operation::RevealTurn { .. } => OperationResponse::TurnAck(TurnAck::rejected("roster_mismatch")),
+            _ => refused,
+        };
+    }
+    if state.team_roster.get().is_some() {
+        return handle_team_operation(operation, state, runtime, caller).await;
+    }
+    if acting_participant(state, caller).is_none() {
+        let refused = reject(state, runtime, operation_name(&operation), "not_a_participant", caller).await;
+        return match operation {
+            Operation::SubmitTurn { .. } | Operation::CommitTurn { .. } | Operation::RevealTurn { .. } => {
+                OperationResponse::TurnAck(TurnAck::rejected("not_a_participant"))
+            }
+            _ => refused,
+        };
+    }
+
     match operation {
-        Operation::SubmitTurn { round, turn, stance, use_special } => {
-            submit_turn(state, runtime, round, turn, stance, use_special).await;
+        Operation::SubmitTurn { round, turn, stance, use_special, emote } => {
+            return OperationResponse::TurnAck(submit_turn(state, runtime, caller, round, turn, stance, use_special, emote).await);
+        }
+        Operation::CommitTurn { .. } if state.practice.get().is_some() => {
+            // The bot answers a turn as it comes in, so there is no one to seal it from
+            reject(state, runtime, "CommitTurn", "practice_battle", caller).await;
+            return OperationResponse::TurnAck(TurnAck::rejected("practice_battle"));
+        }
+        Operation::CommitTurn { round, turn, commitment } => {
+            return OperationResponse::TurnAck(commit_turn(state, runtime, caller, round, turn, commitment).await);
+        }
+        Operation::RevealTurn { round, turn, stance, use_special, salt } => {
+            return OperationResponse::TurnAck(reveal_turn(state, runtime, caller, round, turn, stance, use_special, salt).await);
+        }
+        Operation::ClaimForfeit { round, turn } => {
+            return claim_forfeit(state, runtime, caller, round, turn).await;
         }
         Operation::ExecuteRound => {
-            execute_3_rounds(state, runtime).await;
+            return execute_3_rounds(state, runtime, caller).await;
+        }
+        Operation::AttestResult { agree } => {
+            return attest_result(state, runtime, caller, agree).await;
+        }
+        Operation::SubmitTeamTurn { .. } => {
+            return reject(state, runtime, "SubmitTeamTurn", "not_a_team_battle", caller).await;
         }
         _ => {}
     }
+    OperationResponse::Done
+}
+
+/// Name a battle operation is recorded under when refused
+fn operation_name(operation: &Operation) -> &'static str {
+    match operation {
+        Operation::SubmitTurn { .. } => "SubmitTurn",
+        Operation::SubmitTeamTurn { .. } => "SubmitTeamTurn",
+        Operation::CommitTurn { .. } => "CommitTurn",
+        Operation::RevealTurn { .. } => "RevealTurn",
+        Operation::ClaimForfeit { .. } => "ClaimForfeit",
+        Operation::ExecuteRound => "ExecuteRound",
+        Operation::AttestResult { .. } => "AttestResult",
+        _ => "BattleOperation",
+    }
+}
+
+/// Whether the seated fighters are still the ones the roster fixed at initialization, in a
+/// battle of two or of teams. A battle not yet opened has no one to compare
+fn fighters_intact(state: &BattleState) -> bool {
+    match (state.roster.get(), state.team_roster.get()) {
+        (Some(roster), _) => match (state.player1.get(), state.player2.get()) {
+            (Some(player1), Some(player2)) => roster.matches(player1, player2),
+            _ => false,
+        },
+        (None, Some(roster)) => roster.matches(state.teams.get()),
+        (None, None) => true,
+    }
+}
+
+/// The fighter `caller` acts as, with their opponent, or `None` for any other signer. Authority
+/// comes from the roster fixed at initialization, so a co-owner added to the chain later is
+/// still not a participant
+fn acting_participant(state: &BattleState, caller: AccountOwner) -> Option<(AccountOwner, AccountOwner)> {
+    let opponent = state.roster.get().as_ref()?.opponent_of(caller)?;
+    Some((caller, opponent))
+}
+
+/// Write back the fighters' combat state, returning whether it was written. Their identity is
+/// write-once: fighters changed in who they are, their chain or their stake are not stored
+fn store_fighters(state: &mut BattleState, player1: BattleParticipant, player2: BattleParticipant) -> bool {
+    let intact = state.roster.get().as_ref().is_some_and(|roster| roster.matches(&player1, &player2));
+    if intact {
+        state.player1.set(Some(player1));
+        state.player2.set(Some(player2));
+    }
+    intact
 }
 
 pub async fn handle_battle_message(
@@ -34,13 +132,25 @@ pub async fn handle_battle_message(
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
 ) {
     match message {
-        Message::InitializeBattle { player1, player2, lobby_chain_id, platform_fee_bps, treasury_owner } => {
-            initialize_battle(state, runtime, player1, player2, lobby_chain_id, platform_fee_bps, treasury_owner).await;
+        Message::InitializeBattle { player1, player2, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version } => {
+            initialize_battle(state, runtime, *player1, *player2, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version).await;
+        }
+        Message::InitializeTeamBattle { teams, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version } => {
+            initialize_team_battle(state, runtime, teams, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version);
+        }
+        Message::InitializePracticeBattle { player, difficulty, lobby_chain_id, treasury_owner, rules, rules_version } => {
+            // The bot fights a copy of the player's character from the lobby's chain, for nothing
+            let bot = majorules::BattleParticipant::new(practice::practice_bot(), lobby_chain_id, player.character.clone(), Amount::ZERO);
+            initialize_battle(state, runtime, *player, bot, lobby_chain_id, 0, treasury_owner, rules, rules_version).await;
+            if state.roster.get().as_ref().is_some_and(|roster| roster.owners[1] == practice::practice_bot()) {
+                state.practice.set(Some(difficulty));
+            }
         }
         _ => {}
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn initialize_battle(
     state: &mut BattleState,
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
@@ -49,252 +159,763 @@ async fn initialize_battle(
     lobby_chain_id: ChainId,
     platform_fee_bps: u16,
     treasury_owner: AccountOwner,
+    rules: BattleRules,
+    rules_version: u32,
 ) {
-    let sender_chain = runtime.message_origin_chain_id().expect("Message must have origin");
-    assert_eq!(sender_chain, lobby_chain_id, "Only lobby can initialize battles");
-
-    if state.player1.get().is_some() || state.player2.get().is_some() {
+    // Only the lobby opens battles
+    if runtime.message_origin_chain_id() != Some(lobby_chain_id) {
+        return;
+    }
+    if state.roster.get().is_some() || state.team_roster.get().is_some() || state.player1.get().is_some() || state.player2.get().is_some() {
         return;
     }
 
-    let convert_participant = |p: majorules::BattleParticipant| BattleParticipant {
-        owner: p.owner,
-        chain: p.chain,
-        character: crate::state::CharacterSnapshot {
-            nft_id: p.character.nft_id,
-            class: match p.character.class {
-                majorules::CharacterClass::Warrior => crate::state::CharacterClass::Warrior,
-                majorules::CharacterClass::Assassin => crate::state::CharacterClass::Assassin,
-                majorules::CharacterClass::Mage => crate::state::CharacterClass::Mage,
-                majorules::CharacterClass::Tank => crate::state::CharacterClass::Tank,
-                majorules::CharacterClass::Trickster => crate::state::CharacterClass::Trickster,
-            },
-            level: p.character.level,
-            hp_max: p.character.hp_max,
-            min_damage: p.character.min_damage,
-            max_damage: p.character.max_damage,
-            crit_chance: p.character.crit_chance,
-            crit_multiplier: p.character.crit_multiplier,
-            dodge_chance: p.character.dodge_chance,
-            defense: p.character.defense,
-            attack_bps: p.character.attack_bps,
-            defense_bps: p.character.defense_bps,
-            crit_bps: p.character.crit_bps,
-        },
-        stake: p.stake,
-        current_hp: p.character.hp_max,
-        combo_stack: 0,
-        special_cooldown: 0,
-        turns_submitted: [None, None, None],
-    };
+    let (player1, player2) = (BattleParticipant::from(player1), BattleParticipant::from(player2));
+    state.roster.set(Some(Roster::of(&player1, &player2)));
+    state.opening_fighters.set(vec![player1.clone(), player2.clone()]);
+    state.replay_turns.clear();
+    store_fighters(state, player1, player2);
+    open_battle(state, runtime, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version);
+}
 
-    state.player1.set(Some(convert_participant(player1)));
-    state.player2.set(Some(convert_participant(player2)));
+/// Start the first round under `rules`, for whichever fighters initialization just seated
+fn open_battle(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    lobby_chain_id: ChainId,
+    platform_fee_bps: u16,
+    treasury_owner: AccountOwner,
+    rules: BattleRules,
+    rules_version: u32,
+) {
     state.status.set(BattleStatus::InProgress);
     state.current_round.set(1);
-    state.max_rounds.set(10);
+    state.max_rounds.set(rules.max_rounds);
+    state.rules.set(rules);
+    state.rules_version.set(rules_version);
     state.winner.set(None);
-    state.round_results.set(Vec::new());
+    state.end_reason.set(None);
+    state.round_results.clear();
     state.lobby_chain_id.set(Some(lobby_chain_id));
     state.platform_fee_bps.set(platform_fee_bps);
     state.treasury_owner.set(Some(treasury_owner));
     state.random_counter.set(0);
     state.started_at.set(Some(runtime.system_time()));
     state.completed_at.set(None);
+    start_round_clock(state, runtime);
+}
+
+/// Publish `event` on the battle's spectator stream and keep it in the battle's own log
+fn emit(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, event: BattleEvent) {
+    let index = runtime.emit(BATTLE_EVENT_STREAM.into(), &event);
+    state.battle_log.push_back((index, event));
+    while state.battle_log.count() > BATTLE_LOG_CAPACITY {
+        state.battle_log.delete_front();
+    }
+}
+
+/// Set the deadline for the current round and reopen turn submission
+fn start_round_clock(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>) {
+    let round_duration = TimeDelta::from_secs(state.rules.get().round_duration_secs);
+    let deadline = time::deadline_after(runtime.system_time(), round_duration);
+    state.round_deadline.set(Some(deadline));
+    refresh_timing(state, runtime, BattlePhase::SubmittingTurns);
 }
 
+/// Record the current phase alongside the chain's time at write
+fn refresh_timing(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, phase: BattlePhase) {
+    state.timing.set(Some(TimingInfo {
+        current_round: *state.current_round.get(),
+        phase,
+        phase_deadline: *state.round_deadline.get(),
+        server_time_at_write: runtime.system_time(),
+    }));
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn submit_turn(
     state: &mut BattleState,
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
     round: u8,
     turn: u8,
     stance: String,
     use_special: bool,
-) {
-    if *state.status.get() != BattleStatus::InProgress || round != *state.current_round.get() || turn >= 3 {
-        return;
+    emote: Option<Emote>,
+) -> TurnAck {
+    match accept_turn(state, runtime, caller, round, turn, stance, use_special, emote).await {
+        Ok(executed) => turn_ack(state, runtime, caller, turn, executed).await,
+        Err(reason) => {
+            reject(state, runtime, "SubmitTurn", reason, caller).await;
+            TurnAck::rejected(reason)
+        }
+    }
+}
+
+/// Store a turn and execute it once both players are in.
+/// Returns whether the turn executed, or the rejection reason.
+#[allow(clippy::too_many_arguments)]
+async fn accept_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+    stance: String,
+    use_special: bool,
+    emote: Option<Emote>,
+) -> Result<bool, &'static str> {
+    state.turn_check(caller, round, turn).await?;
+    let stance = check_choice(state, caller, turn, &stance, use_special).await?;
+    report_start(state, runtime);
+    emit(state, runtime, BattleEvent::TurnSubmitted { round, turn, player: caller, sealed: false });
+    // A turn is accepted once per fighter, which holds emotes to one per turn
+    if let Some(emote) = emote {
+        emit(state, runtime, BattleEvent::Emoted { round, turn, player: caller, emote });
     }
+    Ok(store_turn(state, runtime, caller, TurnSubmission { round, turn, stance, use_special, salt: None, target: None, emote }).await)
+}
 
-    let caller = runtime.authenticated_signer().expect("Operation must be authenticated");
-    let stance = match stance.as_str() {
+/// Parse a stance and check it and any special against the rules for `caller`
+async fn check_choice(state: &BattleState, caller: AccountOwner, turn: u8, stance: &str, use_special: bool) -> Result<Stance, &'static str> {
+    let stance = match stance {
         "Balanced" => Stance::Balanced,
         "Aggressive" => Stance::Aggressive,
         "Defensive" => Stance::Defensive,
         "Berserker" => Stance::Berserker,
         "Counter" => Stance::Counter,
-        _ => return,
+        _ => return Err("unknown_stance"),
     };
 
-    let turn_key = (caller, turn);
-    
-    // Prevent double submission
-    if state.turn_submissions.contains_key(&turn_key).await.unwrap_or(false) {
-        return;
+    if state.rules.get().class_locked_stances {
+        let class = [state.player1.get(), state.player2.get()].into_iter().flatten()
+            .chain(state.teams.get().iter().flatten())
+            .find(|player| player.owner == caller)
+            .map(|player| player.character.class);
+        if let Some(class) = class {
+            if !majorules::allowed_stances(class.into()).contains(&stance.into()) {
+                return Err("stance_not_allowed");
+            }
+        }
+    }
+
+    if use_special {
+        check_special(state, caller, turn).await.map_err(|error| error.reason())?;
     }
+    Ok(stance)
+}
 
-    // Store turn submission
-    state.turn_submissions.insert(&turn_key, TurnSubmission { round, turn, stance, use_special })
+/// Store a checked turn and execute it once both players are in; returns whether it executed
+async fn store_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    submission: TurnSubmission,
+) -> bool {
+    let turn = submission.turn;
+    if let Some(difficulty) = *state.practice.get() {
+        play_bot_turn(state, runtime, difficulty, &submission).await;
+    }
+    state.turn_submissions.insert(&(caller, turn), submission)
         .expect("Failed to store turn submission");
 
     // Check if both players submitted this turn
-    let (p1, p2) = (state.player1.get().clone(), state.player2.get().clone());
-    if let (Some(player1), Some(player2)) = (p1, p2) {
-        let p1_key = (player1.owner, turn);
-        let p2_key = (player2.owner, turn);
+    let owners = (
+        state.player1.get().as_ref().map(|player| player.owner),
+        state.player2.get().as_ref().map(|player| player.owner),
+    );
+    if let (Some(p1_owner), Some(p2_owner)) = owners {
+        let p1_key = (p1_owner, turn);
+        let p2_key = (p2_owner, turn);
         
         let p1_submitted = state.turn_submissions.contains_key(&p1_key).await.unwrap_or(false);
         let p2_submitted = state.turn_submissions.contains_key(&p2_key).await.unwrap_or(false);
         
+        // Acknowledge the submission; the round awaits execution once the last turn is in
+        let last_turn = turn + 1 == state.turns_per_round();
+        let phase = if last_turn && p1_submitted && p2_submitted {
+            BattlePhase::AwaitingExecution
+        } else {
+            BattlePhase::SubmittingTurns
+        };
+        refresh_timing(state, runtime, phase);
+        
         // Auto-execute turn when both players submit
         if p1_submitted && p2_submitted {
             execute_single_turn(state, runtime, turn).await;
+            return true;
         }
+        open_reveal_window(state, runtime, caller, turn).await;
     }
+    false
 }
 
-async fn execute_single_turn(
+/// In a practice battle, submit the bot's answer to the turn its opponent just submitted. The
+/// bot rolls from the turn's block and the opponent's commit data, as the turn's seed does
+async fn play_bot_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    difficulty: PracticeDifficulty,
+    opponent: &TurnSubmission,
+) {
+    let (Some(player), Some(bot)) = (state.player1.get().clone(), state.player2.get().clone()) else {
+        return;
+    };
+    let (round, turn) = (*state.current_round.get(), opponent.turn);
+    let seed = turn_seed(runtime.chain_id(), runtime.block_height(), round, turn, [commit_data(opponent), Vec::new()]);
+    let stances: &[majorules::Stance] = if state.rules.get().class_locked_stances {
+        majorules::allowed_stances(bot.character.class.into())
+    } else {
+        &practice::STANCES
+    };
+    let view = BotView {
+        stances,
+        hp: bot.current_hp,
+        hp_max: bot.character.hp_max,
+        opponent_hp: player.current_hp,
+        opponent_hp_max: player.character.hp_max,
+        special_ready: check_special(state, bot.owner, turn).await.is_ok(),
+    };
+    let (stance, use_special) = practice::bot_choice(difficulty, derive_random_u64(&seed, 0), &view);
+    let submission = TurnSubmission { round, turn, stance: stance.into(), use_special, salt: None, target: None, emote: None };
+    state.turn_submissions.insert(&(bot.owner, turn), submission)
+        .expect("Failed to store bot turn");
+}
+
+/// Start the reveal window for `turn` once both fighters locked it in and one of them sealed it
+async fn open_reveal_window(
     state: &mut BattleState,
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
     turn: u8,
 ) {
-    if *state.status.get() != BattleStatus::InProgress {
+    let Some((_, opponent)) = acting_participant(state, caller) else {
+        return;
+    };
+    if state.reveal_deadlines.contains_key(&turn).await.unwrap_or(true) || !state.turn_locked(opponent, turn).await {
         return;
     }
+    let deadline = time::deadline_after(runtime.system_time(), state.rules.get().reveal_window());
+    state.reveal_deadlines.insert(&turn, deadline).expect("Failed to open reveal window");
+}
 
-    let (p1, p2) = (state.player1.get().clone(), state.player2.get().clone());
-    if let (Some(player1), Some(player2)) = (p1, p2) {
-        let p1_key = (player1.owner, turn);
-        let p2_key = (player2.owner, turn);
-        
-        let p1_turn = state.turn_submissions.get(&p1_key).await.ok().flatten();
-        let p2_turn = state.turn_submissions.get(&p2_key).await.ok().flatten();
-        
-        if let (Some(p1_submission), Some(p2_submission)) = (p1_turn, p2_turn) {
-            let mut p1_mut = player1.clone();
-            let mut p2_mut = player2.clone();
-            
-            // Execute combat for this turn
-            if p1_mut.current_hp > 0 && p2_mut.current_hp > 0 {
-                execute_attack(state, &mut p1_mut, &mut p2_mut, &p1_submission, p2_submission.stance).ok();
-            }
-            if p2_mut.current_hp > 0 && p1_mut.current_hp > 0 {
-                execute_attack(state, &mut p2_mut, &mut p1_mut, &p2_submission, p1_submission.stance).ok();
-            }
+/// Seal a turn behind its commitment
+async fn commit_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+    commitment: [u8; 32],
+) -> TurnAck {
+    if let Err(reason) = state.turn_check(caller, round, turn).await {
+        reject(state, runtime, "CommitTurn", reason, caller).await;
+        return TurnAck::rejected(reason);
+    }
+    report_start(state, runtime);
+    state.turn_commitments.insert(&(caller, turn), commitment)
+        .expect("Failed to store turn commitment");
+    emit(state, runtime, BattleEvent::TurnSubmitted { round, turn, player: caller, sealed: true });
+    refresh_timing(state, runtime, BattlePhase::SubmittingTurns);
+    open_reveal_window(state, runtime, caller, turn).await;
+    turn_ack(state, runtime, caller, turn, false).await
+}
+
+/// Open a sealed turn and submit it as if it had been submitted in the clear
+#[allow(clippy::too_many_arguments)]
+async fn reveal_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+    stance: String,
+    use_special: bool,
+    salt: [u8; 32],
+) -> TurnAck {
+    let now = runtime.system_time();
+    let accepted = async {
+        let commitment = state.reveal_check(caller, round, turn, now).await?;
+        let stance = check_choice(state, caller, turn, &stance, use_special).await?;
+        if turn_commitment(caller, round, turn, stance.into(), use_special, salt) != commitment {
+            return Err("commitment_mismatch");
+        }
+        Ok(stance)
+    }.await;
+    match accepted {
+        Ok(stance) => {
+            state.turn_commitments.remove(&(caller, turn)).expect("Failed to clear turn commitment");
+            let submission = TurnSubmission { round, turn, stance, use_special, salt: Some(salt), target: None, emote: None };
+            let executed = store_turn(state, runtime, caller, submission).await;
+            turn_ack(state, runtime, caller, turn, executed).await
+        }
+        Err(reason) => {
+            reject(state, runtime, "RevealTurn", reason, caller).await;
+            TurnAck::rejected(reason)
+        }
+    }
+}
+
+/// End the battle in the caller's favour when the opponent let their reveal window pass
+async fn claim_forfeit(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+) -> OperationResponse {
+    match state.forfeit_check(caller, round, turn, runtime.system_time()).await {
+        Ok(opponent) => finalize_battle(state, runtime, caller, opponent, BattleEndReason::Forfeit).await,
+        Err(reason) => return reject(state, runtime, "ClaimForfeit", reason, caller).await,
+    }
+    OperationResponse::Done
+}
 
-            // Update player states
-            state.player1.set(Some(p1_mut.clone()));
-            state.player2.set(Some(p2_mut.clone()));
+/// Check a special on `turn` against the caller's cooldown with the planner's rules,
+/// counting the specials it already queued this round
+async fn check_special(state: &BattleState, caller: AccountOwner, turn: u8) -> Result<(), PlanError> {
+    let (Some((mut start, mut planned_uses)), Some(cooldown)) =
+        (special_plan_start(state, caller).await, special_cooldown_of(state, caller))
+    else {
+        return Ok(());
+    };
+    let round = *state.current_round.get();
+    if start.round == round {
+        start.turn = start.turn.min(turn);
+    }
+    planned_uses.push((round, turn));
+    planned_uses.sort();
+    cooldown_schedule(cooldown, start, state.turns_per_round(), 1, &planned_uses).map(|_| ())
+}
 
-            // Check if battle ends
-            if p1_mut.current_hp == 0 || p2_mut.current_hp == 0 {
-                let winner = if p1_mut.current_hp > 0 { p1_mut.owner } else { p2_mut.owner };
-                let loser = if winner == p1_mut.owner { p2_mut.owner } else { p1_mut.owner };
-                finalize_battle(state, runtime, winner, loser).await;
-            }
+/// Snapshot the signer's view of the battle after a submission
+async fn turn_ack(
+    state: &BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    turn: u8,
+    executed: bool,
+) -> TurnAck {
+    let (Some(player1), Some(player2)) = (state.player1.get(), state.player2.get()) else {
+        return TurnAck { accepted: true, executed, ..timed_ack(state, runtime) };
+    };
+    let (me, opponent) = if player1.owner == caller { (player1, player2) } else { (player2, player1) };
+
+    let mut next_turn_index = None;
+    for index in 0..state.turns_per_round() {
+        if !state.turn_locked(caller, index).await {
+            next_turn_index = Some(index);
+            break;
         }
     }
+    // Executed turns were revealed, sealed or not
+    let revealed = if executed {
+        state.turn_submissions.get(&(opponent.owner, turn)).await.ok().flatten()
+    } else {
+        None
+    };
+    let battle_over = *state.status.get() != BattleStatus::InProgress;
+
+    TurnAck {
+        accepted: true,
+        reason: None,
+        executed,
+        my_hp: me.current_hp,
+        opponent_hp: opponent.current_hp,
+        my_combo: me.combo_stack,
+        my_cooldown: me.special_cooldown,
+        next_turn_index: if battle_over { None } else { next_turn_index },
+        round_complete: battle_over || (executed && turn + 1 == state.turns_per_round()),
+        opponent_stance: revealed.as_ref().map(|submission| submission.stance.into()),
+        opponent_used_special: revealed.map(|submission| submission.use_special),
+        ..timed_ack(state, runtime)
+    }
 }
 
-async fn execute_3_rounds(
+/// An acknowledgement carrying the current phase deadline and the chain's time
+fn timed_ack(state: &BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>) -> TurnAck {
+    TurnAck {
+        phase_deadline: state.timing.get().as_ref().and_then(|timing| timing.phase_deadline),
+        server_time: runtime.system_time(),
+        ..TurnAck::default()
+    }
+}
+
+/// Record a participant's verdict on the finalized outcome and tell the lobby
+/// once both agreed or as soon as one contests it
+async fn attest_result(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    agree: bool,
+) -> OperationResponse {
+    let opponent = match state.attest_check(caller, runtime.system_time()).await {
+        Ok(opponent) => opponent,
+        Err(reason) => return reject(state, runtime, "AttestResult", reason, caller).await,
+    };
+
+    let opponent_verdict = state.attestations.get(&opponent).await.ok().flatten();
+    state.attestations.insert(&caller, agree).expect("Failed to record attestation");
+    let attestation = match (agree, opponent_verdict) {
+        (true, Some(true)) => Attestation::Attested,
+        (false, None | Some(true)) => Attestation::Disputed,
+        // Waiting on the opponent, or their disagreement was already reported
+        _ => return OperationResponse::Done,
+    };
+    if let Some(lobby_chain) = *state.lobby_chain_id.get() {
+        runtime.prepare_message(Message::ResultAttestation { attestation })
+            .with_authentication()
+            .send_to(lobby_chain);
+    }
+    OperationResponse::Done
+}
+
+/// Tell the lobby the first turn is in, once, so betting on the battle closes
+fn report_start(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>) {
+    if *state.start_reported.get() {
+        return;
+    }
+    state.start_reported.set(true);
+    if let Some(lobby_chain) = *state.lobby_chain_id.get() {
+        let battle_chain = runtime.chain_id();
+        runtime.prepare_message(Message::BattleStarted { battle_chain })
+            .with_authentication()
+            .send_to(lobby_chain);
+    }
+}
+
+/// Log a rejected battle operation and answer it with the reason
+async fn reject(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    operation: &str,
+    reason: &str,
+    caller: AccountOwner,
+) -> OperationResponse {
+    let key = RejectionKey::new(operation, reason, caller);
+    record_rejection(&mut state.rejections, key, runtime.system_time()).await;
+    OperationResponse::rejected(reason)
+}
+
+async fn execute_single_turn(
     state: &mut BattleState,
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    turn: u8,
 ) {
     if *state.status.get() != BattleStatus::InProgress {
         return;
     }
 
-    let caller = runtime.authenticated_signer().expect("Operation must be authenticated");
-    let (p1, p2) = (state.player1.get().clone(), state.player2.get().clone());
-    
-    let is_participant = if let (Some(ref player1), Some(ref player2)) = (p1, p2) {
-        caller == player1.owner || caller == player2.owner
-    } else {
-        false
+    // Load both participants once, mutate locally and write back once
+    let (Some(mut player1), Some(mut player2)) = (state.player1.get().clone(), state.player2.get().clone()) else {
+        return;
+    };
+
+    let p1_turn = state.turn_submissions.get(&(player1.owner, turn)).await.ok().flatten();
+    let p2_turn = state.turn_submissions.get(&(player2.owner, turn)).await.ok().flatten();
+    let (Some(p1_submission), Some(p2_submission)) = (p1_turn, p2_turn) else {
+        return;
+    };
+
+    // Execute combat for this turn, rolling from a seed neither fighter could know alone
+    let seed = turn_seed(
+        runtime.chain_id(),
+        runtime.block_height(),
+        *state.current_round.get(),
+        turn,
+        [commit_data(&p1_submission), commit_data(&p2_submission)],
+    );
+    let mut random_counter = *state.random_counter.get();
+    let rules = state.rules.get().clone();
+    let round = *state.current_round.get();
+    let actions = resolve_turn(seed, &rules, round, &mut random_counter, &mut player1, &mut player2, &p1_submission, &p2_submission);
+    state.random_counter.set(random_counter);
+    let choices = [
+        (p1_submission.stance, p1_submission.use_special),
+        (p2_submission.stance, p2_submission.use_special),
+    ];
+    state.replay_turns.insert(&(round, turn), ReplayTurn { seed, choices, actions: actions.clone() })
+        .expect("Failed to record replay turn");
+    let emotes = [(player1.owner, &p1_submission), (player2.owner, &p2_submission)].into_iter()
+        .filter_map(|(player, submission)| Some(TurnEmote { turn, player, emote: submission.emote? }))
+        .collect();
+    record_turn(state, actions, emotes, &player1, &player2).await;
+    let resolved = BattleEvent::TurnResolved {
+        round,
+        turn,
+        choices: [
+            (p1_submission.stance.into(), p1_submission.use_special),
+            (p2_submission.stance.into(), p2_submission.use_special),
+        ],
+        player1_hp: player1.current_hp,
+        player2_hp: player2.current_hp,
     };
+    emit(state, runtime, resolved);
 
-    if !is_participant {
+    // Check if battle ends
+    let outcome = decide_ending(&player1, &player2, false).map(|(end_reason, winner)| {
+        let loser = if winner == player1.owner { player2.owner } else { player1.owner };
+        (end_reason, winner, loser)
+    });
+
+    // Update player states
+    if !store_fighters(state, player1, player2) {
         return;
     }
 
+    if let Some((end_reason, winner, loser)) = outcome {
+        finalize_battle(state, runtime, winner, loser, end_reason).await;
+    }
+}
+
+/// Fight one turn from its seed and both fighters' choices: effects tick, then each fighter
+/// still standing attacks, player 1 first. Nothing else feeds in, so a replay of the turn's
+/// inputs resolves it the same way
+#[allow(clippy::too_many_arguments)]
+fn resolve_turn(
+    seed: [u8; 32],
+    rules: &BattleRules,
+    round: u8,
+    random_counter: &mut u64,
+    player1: &mut BattleParticipant,
+    player2: &mut BattleParticipant,
+    p1_submission: &TurnSubmission,
+    p2_submission: &TurnSubmission,
+) -> Vec<CombatAction> {
+    // Effects tick before anyone attacks, and damage over time can end the fight on its own
+    let mut actions = Vec::new();
+    let p1_stunned = tick_effects(player1, player2, p1_submission.stance, &mut actions);
+    let p2_stunned = tick_effects(player2, player1, p2_submission.stance, &mut actions);
+    // A stunned fighter's lost attack still ticks both cooldowns, as the attack would have
+    if player1.current_hp > 0 && player2.current_hp > 0 {
+        if p1_stunned {
+            player1.tick_cooldown();
+            player2.tick_cooldown();
+        } else {
+            let rolls = AttackRolls::new(seed, 0);
+            if let Ok((attack, counter)) = execute_attack(rolls, random_counter, rules, round, player1, player2, p1_submission, p2_submission.stance) {
+                actions.push(attack);
+                actions.extend(counter);
+            }
+        }
+    }
+    if player2.current_hp > 0 && player1.current_hp > 0 {
+        if p2_stunned {
+            player2.tick_cooldown();
+            player1.tick_cooldown();
+        } else {
+            let rolls = AttackRolls::new(seed, 1);
+            if let Ok((attack, counter)) = execute_attack(rolls, random_counter, rules, round, player2, player1, p2_submission, p1_submission.stance) {
+                actions.push(attack);
+                actions.extend(counter);
+            }
+        }
+    }
+    actions
+}
+
+/// Close the current round once both fighters asked to and every turn of it executed:
+/// store its final HP, clear its submissions, then end the battle or start the next round
+async fn execute_3_rounds(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+) -> OperationResponse {
+    if *state.status.get() != BattleStatus::InProgress {
+        return reject(state, runtime, "ExecuteRound", "battle_not_active", caller).await;
+    }
+
+    let Some([p1_owner, p2_owner]) = state.roster.get().as_ref().map(|roster| roster.owners) else {
+        return reject(state, runtime, "ExecuteRound", "battle_not_active", caller).await;
+    };
+    if acting_participant(state, caller).is_none() {
+        return reject(state, runtime, "ExecuteRound", "not_a_participant", caller).await;
+    }
+
     let current_round = *state.current_round.get();
-    let execute_key = format!("execute_3_rounds_{}_{}", current_round, caller);
-    let mut log = state.battle_log.get().clone();
     
     // Prevent double execution
-    if log.iter().any(|entry| entry.contains(&execute_key)) {
-        return;
+    if state.execute_requests.contains_key(&(current_round, caller)).await.unwrap_or(false) {
+        return reject(state, runtime, "ExecuteRound", "duplicate_request", caller).await;
+    }
+    // Turns resolve pairwise as they come in; a round closes only once all of them have
+    if !state.round_resolved().await {
+        return reject(state, runtime, "ExecuteRound", "turns_pending", caller).await;
     }
     
-    log.push(execute_key.clone());
-    state.battle_log.set(log.clone());
-
-    // Check if both players called execute
-    let p1 = state.player1.get().clone().unwrap();
-    let p2 = state.player2.get().clone().unwrap();
-    let p1_execute_key = format!("execute_3_rounds_{}_{}", current_round, p1.owner);
-    let p2_execute_key = format!("execute_3_rounds_{}_{}", current_round, p2.owner);
-    
-    let p1_wants_execute = log.iter().any(|entry| entry.contains(&p1_execute_key));
-    let p2_wants_execute = log.iter().any(|entry| entry.contains(&p2_execute_key));
-    
+    state.execute_requests.insert(&(current_round, caller), ())
+        .expect("Failed to record execute request");
+    if state.practice.get().is_some() {
+        // The bot closes a round whenever its opponent does
+        state.execute_requests.insert(&(current_round, practice::practice_bot()), ())
+            .expect("Failed to record execute request");
+    }
+    emit(state, runtime, BattleEvent::RoundCloseRequested { round: current_round, player: caller });
+    refresh_timing(state, runtime, BattlePhase::AwaitingExecution);
+
     // Only execute when both players call it
+    let p1_wants_execute = state.execute_requests.contains_key(&(current_round, p1_owner)).await.unwrap_or(false);
+    let p2_wants_execute = state.execute_requests.contains_key(&(current_round, p2_owner)).await.unwrap_or(false);
+    
     if p1_wants_execute && p2_wants_execute {
-        // Store round result
-        let round_result = RoundResult {
-            round: current_round,
-            player1_actions: Vec::new(),
-            player2_actions: Vec::new(),
-            player1_hp: p1.current_hp,
-            player2_hp: p2.current_hp,
+        let (Some(p1), Some(p2)) = (state.player1.get().clone(), state.player2.get().clone()) else {
+            return OperationResponse::Done;
         };
-        
-        let mut results = state.round_results.get().clone();
-        results.push(round_result);
-        state.round_results.set(results);
+        // Close the round result its turns filled in with the HP the round ended on
+        let mut round_result = current_round_result(state).await;
+        (round_result.player1_hp, round_result.player2_hp) = (p1.current_hp, p2.current_hp);
+        state.round_results.insert(&current_round, round_result)
+            .expect("Failed to store round result");
+        emit(state, runtime, BattleEvent::RoundEnded { round: current_round, player1_hp: p1.current_hp, player2_hp: p2.current_hp });
 
-        // Clear turn submissions
-        for turn in 0..3 {
-            state.turn_submissions.remove(&(p1.owner, turn)).ok();
-            state.turn_submissions.remove(&(p2.owner, turn)).ok();
-        }
-
-        // Check battle completion or advance round
-        if p1.current_hp == 0 || p2.current_hp == 0 {
-            let winner = if p1.current_hp > 0 { p1.owner } else { p2.owner };
-            let loser = if winner == p1.owner { p2.owner } else { p1.owner };
-            finalize_battle(state, runtime, winner, loser).await;
-        } else if current_round >= *state.max_rounds.get() {
-            let winner = if p1.current_hp > p2.current_hp { p1.owner } else { p2.owner };
-            let loser = if winner == p1.owner { p2.owner } else { p1.owner };
-            finalize_battle(state, runtime, winner, loser).await;
+        // Clear turn submissions, with any seals left unrevealed
+        for turn in 0..state.turns_per_round() {
+            for owner in [p1_owner, p2_owner] {
+                state.turn_submissions.remove(&(owner, turn)).ok();
+                state.turn_commitments.remove(&(owner, turn)).ok();
+            }
+            state.reveal_deadlines.remove(&turn).ok();
+        }
+
+        // Check battle completion or advance round; sudden death plays on past the regulation
+        // rounds before the tiebreak
+        let last_round = state.max_rounds.get().saturating_add(state.rules.get().sudden_death_rounds);
+        let at_round_limit = current_round >= last_round;
+        if let Some((end_reason, winner)) = decide_ending(&p1, &p2, at_round_limit) {
+            let loser = if winner == p1_owner { p2_owner } else { p1_owner };
+            finalize_battle(state, runtime, winner, loser, end_reason).await;
         } else {
             state.current_round.set(current_round + 1);
+            start_round_clock(state, runtime);
         }
     }
+    OperationResponse::Done
 }
 
-fn execute_attack(
+/// Result of the current round so far, or an empty one before its first turn executes
+async fn current_round_result(state: &BattleState) -> RoundResult {
+    let round = *state.current_round.get();
+    state.round_results.get(&round).await
+        .expect("Failed to read round result")
+        .unwrap_or(RoundResult {
+            round,
+            player1_actions: Vec::new(),
+            player2_actions: Vec::new(),
+            player1_hp: 0,
+            player2_hp: 0,
+            player1_effects: Vec::new(),
+            player2_effects: Vec::new(),
+            emotes: Vec::new(),
+        })
+}
+
+/// Append an executed turn's actions to the current round's result, each under its attacker,
+/// with the emotes sent with it, and bring the result's HP and effects up to date
+async fn record_turn(
     state: &mut BattleState,
+    actions: Vec<CombatAction>,
+    emotes: Vec<TurnEmote>,
+    player1: &BattleParticipant,
+    player2: &BattleParticipant,
+) {
+    let mut round_result = current_round_result(state).await;
+    for action in actions {
+        if action.attacker == player1.owner {
+            round_result.player1_actions.push(action);
+        } else {
+            round_result.player2_actions.push(action);
+        }
+    }
+    round_result.emotes.extend(emotes);
+    (round_result.player1_hp, round_result.player2_hp) = (player1.current_hp, player2.current_hp);
+    (round_result.player1_effects, round_result.player2_effects) = (player1.effects.clone(), player2.effects.clone());
+    let round = round_result.round;
+    state.round_results.insert(&round, round_result)
+        .expect("Failed to store round result");
+}
+
+/// What a fighter committed to for a turn, as it feeds the turn's seed: the salt of a sealed
+/// turn, which stays secret until its reveal, or the choice submitted in the clear
+fn commit_data(submission: &TurnSubmission) -> Vec<u8> {
+    match submission.salt {
+        Some(salt) => salt.to_vec(),
+        None => vec![submission.stance as u8, submission.use_special as u8],
+    }
+}
+
+/// Tick `fighter`'s effects at the start of a turn, recording each burn or bleed as an action of
+/// whoever applied it, and a stun as `fighter` sitting the turn out. Fighting Defensive halves
+/// damage over time. Returns whether `fighter` is stunned for the turn
+fn tick_effects(
+    fighter: &mut BattleParticipant,
+    opponent: &BattleParticipant,
+    stance: Stance,
+    actions: &mut Vec<CombatAction>,
+) -> bool {
+    let dot_bps = if stance == Stance::Defensive { 5_000 } else { 10_000 };
+    let mut hp = fighter.current_hp;
+    let ticked = fighter.tick_effects(dot_bps);
+    let mut stunned = false;
+    for (active, damage) in ticked {
+        let (attacker, defender, damage_over_time) = match active.effect {
+            effect if effect.damages_over_time() => (active.source, fighter.owner, true),
+            StatusEffect::Stun => (fighter.owner, opponent.owner, false),
+            _ => continue,
+        };
+        stunned |= active.effect == StatusEffect::Stun;
+        hp = hp.saturating_sub(damage);
+        actions.push(CombatAction {
+            attacker,
+            defender,
+            damage,
+            was_crit: false,
+            was_dodged: false,
+            was_countered: false,
+            special_used: false,
+            special: None,
+            absorbed: 0,
+            damage_over_time,
+            counter: false,
+            effects: vec![active.effect],
+            defender_hp_remaining: if damage_over_time { hp } else { opponent.current_hp },
+        });
+    }
+    stunned
+}
+
+/// Resolve one attack in `round`, and the counter it drew if the defender answered it
+#[allow(clippy::too_many_arguments)]
+fn execute_attack(
+    rolls: AttackRolls,
+    random_counter: &mut u64,
+    rules: &BattleRules,
+    round: u8,
     attacker: &mut BattleParticipant,
     defender: &mut BattleParticipant,
     attacker_turn: &TurnSubmission,
     defender_stance: Stance,
-) -> Result<CombatAction, String> {
+) -> Result<(CombatAction, Option<CombatAction>), String> {
     let attacker_owner = attacker.owner;
     let defender_owner = defender.owner;
 
     // Use special ability
-    let special_used = if attacker_turn.use_special && attacker.special_cooldown == 0 {
-        attacker.special_cooldown = 3;
-        true
-    } else {
-        false
-    };
+    let special = (attacker_turn.use_special && attacker.special_cooldown == 0).then(|| {
+        let class = majorules::CharacterClass::from(attacker.character.class);
+        attacker.special_cooldown = rules.special_cooldown_for(class);
+        class.special()
+    });
+    let special_used = special.is_some();
 
     // Calculate damage
-    let (damage, was_crit, was_dodged) = calculate_damage(attacker, defender, attacker_turn.stance, defender_stance, special_used)?;
-
-    let mut was_countered = false;
+    let round_bps = rules.damage_bps(round);
+    let (damage, was_crit, was_dodged) = calculate_damage(rolls, attacker, defender, attacker_turn.stance, defender_stance, special, round_bps)?;
+    // Evasion only covers the one attack that comes after it
+    defender.remove_effect(StatusEffect::Evasion);
+    let mut effects = Vec::new();
+    let mut apply = |target: &mut BattleParticipant, effect, magnitude, turns_left| {
+        target.apply_effect(ActiveEffect { effect, magnitude, turns_left, source: attacker_owner });
+        effects.push(effect);
+    };
 
     // Berserker self-damage
     if attacker_turn.stance == Stance::Berserker && !was_dodged {
@@ -302,125 +923,146 @@ fn execute_attack(
     }
 
     // Apply damage
-    if !was_dodged {
-        defender.current_hp = defender.current_hp.saturating_sub(damage);
+    let absorbed = if was_dodged { 0 } else { defender.absorb_hit(damage) };
+
+    // Special effects
+    match special {
+        Some(SpecialAbility::Ignite) if !was_dodged => apply(defender, StatusEffect::Burn, (damage / 5).max(1), 2),
+        Some(SpecialAbility::Bulwark) => {
+            let shield = attacker.character.hp_max / 5;
+            apply(attacker, StatusEffect::Shield, shield, 3);
+        }
+        Some(SpecialAbility::Vanish) => apply(attacker, StatusEffect::Evasion, 5_000, 2),
+        _ => {}
     }
 
-    // Handle combos
-    if was_crit && attacker.combo_stack < 5 {
-        attacker.combo_stack += 1;
-    } else if was_dodged {
-        attacker.combo_stack = 0;
+    // Berserker crits open a wound
+    if attacker_turn.stance == Stance::Berserker && was_crit && !was_dodged {
+        apply(defender, StatusEffect::Bleed, (damage / 10).max(1), 3);
     }
 
-    // Counter-attack
-    if defender_stance == Stance::Counter && !was_dodged && defender.current_hp > 0 {
-        if random_value(0, 9999) < 4000 {
-            was_countered = true;
-            attacker.current_hp = attacker.current_hp.saturating_sub(damage * 4 / 10);
+    // Handle combos
+    attacker.record_hit(was_crit, was_dodged);
+
+    // Counter-attack, on the attack's own roll from the turn seed
+    let reflected = (defender_stance == Stance::Counter && !was_dodged && defender.current_hp > 0)
+        .then(|| {
+            let roll = rolls.roll(Roll::Counter, 0, Bps::ROLLS - 1);
+            damage::counter_damage(damage, rules.counter_chance, rules.counter_damage_bps, roll)
+        })
+        .flatten();
+    let counter = reflected.map(|reflected| {
+        let absorbed = attacker.absorb_hit(reflected);
+        // A landed counter leaves the attacker stunned for their next attack
+        attacker.apply_effect(ActiveEffect { effect: StatusEffect::Stun, magnitude: 0, turns_left: 1, source: defender_owner });
+        CombatAction {
+            attacker: defender_owner,
+            defender: attacker_owner,
+            damage: reflected,
+            was_crit: false,
+            was_dodged: false,
+            was_countered: false,
+            special_used: false,
+            special: None,
+            absorbed,
+            damage_over_time: false,
+            counter: true,
+            effects: vec![StatusEffect::Stun],
+            defender_hp_remaining: attacker.current_hp,
         }
-    }
+    });
 
     // Tick cooldowns
     if attacker.special_cooldown > 0 { attacker.special_cooldown -= 1; }
     if defender.special_cooldown > 0 { defender.special_cooldown -= 1; }
 
-    state.random_counter.set(state.random_counter.get() + 1);
+    *random_counter += 1;
 
-    Ok(CombatAction {
+    let attack = CombatAction {
         attacker: attacker_owner,
         defender: defender_owner,
         damage,
         was_crit,
         was_dodged,
-        was_countered,
+        was_countered: counter.is_some(),
         special_used,
+        special,
+        absorbed,
+        damage_over_time: false,
+        counter: false,
+        effects,
         defender_hp_remaining: defender.current_hp,
-    })
+    };
+    Ok((attack, counter))
 }
 
+/// Roll an attack's damage, crit and dodge; the arithmetic lives in `majorules::damage`
 fn calculate_damage(
+    rolls: AttackRolls,
     attacker: &BattleParticipant,
     defender: &BattleParticipant,
     attacker_stance: Stance,
     defender_stance: Stance,
-    special_used: bool,
+    special: Option<SpecialAbility>,
+    round_bps: u32,
 ) -> Result<(u32, bool, bool), String> {
     let char = &attacker.character;
-    let base_damage = random_value(char.min_damage as u64, char.max_damage as u64) as u32;
-    let mut damage = base_damage as u128 * FP_SCALE;
-
-    // Apply attack traits
-    if char.attack_bps != 0 {
-        let attack_mod = FP_SCALE as i128 + ((char.attack_bps as i128 * FP_SCALE as i128) / 10000);
-        damage = ((damage as i128 * attack_mod) / FP_SCALE as i128) as u128;
-    }
+    let base = rolls.roll(Roll::Damage, char.min_damage as u64, char.max_damage as u64) as u32;
 
-    // Stance modifiers
-    damage = match attacker_stance {
-        Stance::Balanced => damage,
-        Stance::Aggressive => mul_fp(damage, 13 * FP_SCALE / 10),
-        Stance::Defensive => mul_fp(damage, 7 * FP_SCALE / 10),
-        Stance::Berserker => mul_fp(damage, 2 * FP_SCALE),
-        Stance::Counter => mul_fp(damage, 9 * FP_SCALE / 10),
-    };
+    let crit_roll = rolls.roll(Roll::Crit, 0, Bps::ROLLS - 1);
+    let was_crit = special == Some(SpecialAbility::Assassinate) || damage::crit_chance(char.crit_chance, char.crit_bps).hits(crit_roll);
 
-    // Combo bonus
-    if attacker.combo_stack > 0 {
-        let combo_bonus = FP_SCALE + (attacker.combo_stack as u128 * FP_SCALE / 20);
-        damage = mul_fp(damage, combo_bonus);
-    }
+    // Dodge check, helped by a Vanish
+    let dodge_roll = rolls.roll(Roll::Dodge, 0, Bps::ROLLS - 1);
+    let evasion = defender.effect(StatusEffect::Evasion).map_or(0, |active| u16::try_from(active.magnitude).unwrap_or(u16::MAX));
+    let was_dodged = defender.character.dodge_chance.saturating_add(Bps(evasion)).hits(dodge_roll);
 
-    // Critical hit
-    let crit_roll = random_value(0, 9999);
-    let crit_chance = char.crit_chance + char.crit_bps.max(0) as u16;
-    let was_crit = crit_roll < crit_chance as u64;
-    if was_crit {
-        let crit_mult = char.crit_multiplier as u128 * FP_SCALE / 10000;
-        damage = mul_fp(damage, crit_mult);
-    }
+    let attack = damage::Attack {
+        base,
+        attack_bps: char.attack_bps,
+        attacker_stance: attacker_stance.into(),
+        combo_bps: attacker.combo_multiplier_bps(),
+        crit_multiplier: was_crit.then_some(char.crit_multiplier),
+        // Armor pierce hits harder and skips the defender's armor
+        pierces: special == Some(SpecialAbility::ArmorPierce),
+        dodged: was_dodged,
+        defense: defender.character.defense,
+        defender_stance: defender_stance.into(),
+        defense_bps: defender.character.defense_bps,
+        round_bps,
+    };
+    Ok((attack.damage(), was_crit, was_dodged))
+}
 
-    // Special ability
-    if special_used {
-        damage = mul_fp(damage, 15 * FP_SCALE / 10);
+/// How the fight stands after the latest exchange: its ending and winner, or `None` while it
+/// goes on. Every ending of a fought battle is decided here
+fn decide_ending(
+    p1: &BattleParticipant,
+    p2: &BattleParticipant,
+    at_round_limit: bool,
+) -> Option<(BattleEndReason, AccountOwner)> {
+    if p1.current_hp == 0 || p2.current_hp == 0 {
+        let winner = if p1.current_hp > 0 { p1.owner } else { p2.owner };
+        return Some((BattleEndReason::Knockout, winner));
     }
-
-    // Dodge check
-    let dodge_roll = random_value(0, 9999);
-    let was_dodged = dodge_roll < defender.character.dodge_chance as u64;
-    if was_dodged {
-        return Ok((0, was_crit, true));
+    if !at_round_limit {
+        return None;
     }
 
-    // Defense
-    let def_reduction = defender.character.defense as u128 * FP_SCALE / 100;
-    if def_reduction < FP_SCALE {
-        damage = mul_fp(damage, FP_SCALE - def_reduction);
+    // Compare HP shares without dividing: hp1 / max1 against hp2 / max2
+    let p1_share = u64::from(p1.current_hp) * u64::from(p2.character.hp_max);
+    let p2_share = u64::from(p2.current_hp) * u64::from(p1.character.hp_max);
+    let p1_dealt = p2.character.hp_max.saturating_sub(p2.current_hp);
+    let p2_dealt = p1.character.hp_max.saturating_sub(p1.current_hp);
+    let (by, p1_won) = if p1_share != p2_share {
+        (TiebreakBy::HpPercent, p1_share > p2_share)
+    } else if p1_dealt != p2_dealt {
+        (TiebreakBy::Damage, p1_dealt > p2_dealt)
     } else {
-        damage = FP_SCALE;
-    }
-
-    // Defender stance
-    damage = match defender_stance {
-        Stance::Balanced => damage,
-        Stance::Aggressive => mul_fp(damage, 15 * FP_SCALE / 10),
-        Stance::Defensive => mul_fp(damage, 5 * FP_SCALE / 10),
-        Stance::Berserker => damage,
-        Stance::Counter => mul_fp(damage, 6 * FP_SCALE / 10),
+        // Dead even: nobody wins, and player 1 stands in as the nominal winner
+        return Some((BattleEndReason::Draw, p1.owner));
     };
-
-    // Defense traits
-    if defender.character.defense_bps != 0 {
-        let def_mod = FP_SCALE as i128 - ((defender.character.defense_bps as i128 * FP_SCALE as i128) / 10000);
-        if def_mod > 0 {
-            damage = ((damage as i128 * def_mod) / FP_SCALE as i128) as u128;
-        } else {
-            damage = FP_SCALE;
-        }
-    }
-
-    let final_damage = ((damage / FP_SCALE) as u32).max(1);
-    Ok((final_damage, was_crit, false))
+    Some((BattleEndReason::MaxRoundsTiebreak { by }, if p1_won { p1.owner } else { p2.owner }))
 }
 
 async fn finalize_battle(
@@ -428,129 +1070,2210 @@ async fn finalize_battle(
     runtime: &mut ContractRuntime<crate::MajorulesContract>,
     winner: AccountOwner,
     loser: AccountOwner,
+    end_reason: BattleEndReason,
 ) {
-    state.winner.set(Some(winner));
-    state.status.set(BattleStatus::Completed);
-    state.completed_at.set(Some(runtime.system_time()));
+    // Results, payouts and ELO only ever go to the fighters the battle was opened for
+    let intact = match (state.roster.get(), state.player1.get(), state.player2.get()) {
+        (Some(roster), Some(p1), Some(p2)) => roster.matches(p1, p2) && roster.opponent_of(winner) == Some(loser),
+        _ => false,
+    };
+    if !intact {
+        return;
+    }
+
+    let drawn = end_reason == BattleEndReason::Draw;
+    state.winner.set((!drawn).then_some(winner));
+    state.end_reason.set(Some(end_reason));
+    state.status.set(if drawn { BattleStatus::Draw } else { BattleStatus::Completed });
+    state.completed_at.set(Some(runtime.system_time()));
+    state.round_deadline.set(None);
+    state.timing.set(None);
+    let finished = BattleEvent::BattleFinished {
+        winner: *state.winner.get(),
+        end_reason,
+        rounds_played: *state.current_round.get(),
+    };
+    emit(state, runtime, finished);
 
-    let (p1, p2) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+    let (Some(p1), Some(p2)) = (state.player1.get().as_ref(), state.player2.get().as_ref()) else {
+        return;
+    };
     let total_stake = p1.stake.saturating_add(p2.stake);
-    let platform_fee_bps = *state.platform_fee_bps.get();
-    let platform_fee_amount = (u128::from(total_stake) * platform_fee_bps as u128) / 10000;
-    let platform_fee = Amount::from_attos(platform_fee_amount);
-    let winner_payout = total_stake.saturating_sub(platform_fee);
+    let fee_bps = *state.platform_fee_bps.get();
+    let winner_payout = FeeBreakdown::compute(total_stake, fee_bps).winner_payout;
+    // A draw refunds each stake less its share of the fee; player 2's refund takes the rounding
+    // so the two add up to the pot's payout
+    let p1_refund = FeeBreakdown::compute(p1.stake, fee_bps).winner_payout.min(winner_payout);
+    let draw_refunds = (p1_refund, winner_payout.saturating_sub(p1_refund));
 
     // Calculate stats
-    let round_results = state.round_results.get().clone();
-    let (winner_stats, loser_stats) = calculate_combat_stats(&round_results, &winner);
+    let round_results: Vec<RoundResult> = state.round_results.index_values().await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, result)| result)
+        .collect();
+    let (mut winner_stats, mut loser_stats) = (combat_stats_of(&round_results, winner), combat_stats_of(&round_results, loser));
 
-    // Calculate ELO changes
-    let (winner_elo_change, loser_elo_change) = calculate_elo_changes(&p1, &p2, &winner);
+    let (winner_participant, loser_participant) = if winner == p1.owner { (p1, p2) } else { (p2, p1) };
+    let winner_character = winner_participant.character.nft_id.clone();
+    let loser_character = loser_participant.character.nft_id.clone();
+    winner_stats.longest_combo = winner_participant.longest_combo;
+    loser_stats.longest_combo = loser_participant.longest_combo;
 
     // Send results to lobby
     if let Some(lobby_chain) = state.lobby_chain_id.get().as_ref() {
-        let convert_stats = |stats: &CombatStats| majorules::CombatStats {
-            damage_dealt: stats.damage_dealt,
-            damage_taken: stats.damage_taken,
-            crits: stats.crits,
-            dodges: stats.dodges,
-            highest_crit: stats.highest_crit,
-        };
-
         let battle_chain = runtime.chain_id();
+        let rules = state.rules.get();
+        let rules_digest = rules.digest();
+        if let Some(difficulty) = *state.practice.get() {
+            // Only the player's result goes out: no payout, no drop and a share of the XP
+            let won = !drawn && winner == p1.owner;
+            let stats = if winner == p1.owner { &winner_stats } else { &loser_stats };
+            runtime.prepare_message(Message::PracticeBattleCompleted {
+                result: FighterResult {
+                    player: p1.owner,
+                    character_id: p1.character.nft_id.clone(),
+                    won,
+                    payout: Amount::ZERO,
+                    xp_gained: difficulty.scaled_xp(rules.awarded_xp(won)),
+                    elo_change: 0,
+                    item_drop: None,
+                    combat_stats: convert_stats(stats),
+                    win_streak: 0,
+                },
+            }).with_authentication().send_to(*lobby_chain);
+            return;
+        }
+        let item_drop = ItemDrop::roll(battle_chain, *state.random_counter.get());
 
-        // Winner result with ELO update
-        runtime.prepare_message(Message::BattleResultWithElo {
-            player: winner,
-            opponent: loser,
-            won: true,
-            payout: winner_payout,
-            xp_gained: 150,
-            elo_change: winner_elo_change,
-            battle_stats: convert_stats(&winner_stats),
-            battle_chain,
-        }).with_authentication().send_to(*lobby_chain);
-
-        // Loser result with ELO update
-        runtime.prepare_message(Message::BattleResultWithElo {
-            player: loser,
-            opponent: winner,
-            won: false,
-            payout: Amount::ZERO,
-            xp_gained: 50,
-            elo_change: loser_elo_change,
-            battle_stats: convert_stats(&loser_stats),
-            battle_chain,
-        }).with_authentication().send_to(*lobby_chain);
-
-        // Completion notification
+        // Both results travel with the completion, so the lobby never depends on the arrival
+        // order of separate messages. The lobby rates the battle and fills in ELO changes
+        // In a draw the nominal winner, player 1, fares like the loser apart from the refund
+        let (payouts, winner_xp, item_drop) = if drawn {
+            (draw_refunds, rules.awarded_xp(false), None)
+        } else {
+            ((winner_payout, Amount::ZERO), rules.awarded_xp(true), item_drop)
+        };
+        let results = vec![
+            FighterResult {
+                player: winner,
+                character_id: winner_character,
+                won: !drawn,
+                payout: payouts.0,
+                xp_gained: winner_xp,
+                elo_change: 0,
+                item_drop,
+                combat_stats: convert_stats(&winner_stats),
+                win_streak: 0,
+            },
+            FighterResult {
+                player: loser,
+                character_id: loser_character,
+                won: false,
+                payout: payouts.1,
+                xp_gained: rules.awarded_xp(false),
+                elo_change: 0,
+                item_drop: None,
+                combat_stats: convert_stats(&loser_stats),
+                win_streak: 0,
+            },
+        ];
         runtime.prepare_message(Message::BattleCompleted {
             winner, loser, rounds_played: *state.current_round.get(), total_stake,
             battle_stats: (convert_stats(&winner_stats), convert_stats(&loser_stats)),
+            rules_digest,
+            end_reason,
+            results,
         }).with_authentication().send_to(*lobby_chain);
+
+        // Each fighter's player chain also hears the battle's own account, for its history
+        let fighters = [
+            (winner_participant, loser_participant, payouts.0, &winner_stats),
+            (loser_participant, winner_participant, payouts.1, &loser_stats),
+        ];
+        for (fighter, opponent, payout, stats) in fighters {
+            runtime.prepare_message(Message::BattleResult {
+                battle_chain,
+                character_id: fighter.character.nft_id.clone(),
+                opponent: opponent.owner,
+                stake: fighter.stake,
+                payout,
+                rounds_played: *state.current_round.get(),
+                battle_stats: convert_stats(stats),
+                end_reason,
+            }).with_authentication().send_to(fighter.chain);
+        }
     }
 }
 
-/// Calculate ELO rating changes using standard ELO formula
-fn calculate_elo_changes(
-    p1: &BattleParticipant,
-    p2: &BattleParticipant,
-    winner: &AccountOwner,
-) -> (i32, i32) {
-    // Use character levels as proxy for ELO (in real implementation, lobby would provide actual ELO)
-    let p1_elo = 1200 + (p1.character.level as i32 * 10);
-    let p2_elo = 1200 + (p2.character.level as i32 * 10);
-    
-    let k_factor = 32; // Standard K-factor for ELO
-    
-    // Calculate expected scores
-    let rating_diff = p2_elo - p1_elo;
-    let expected_p1 = 1.0 / (1.0 + 10.0_f64.powf(rating_diff as f64 / 400.0));
-    let expected_p2 = 1.0 - expected_p1;
-    
-    // Determine actual scores
-    let (actual_p1, actual_p2) = if winner == &p1.owner {
-        (1.0, 0.0)
+/// What `fighter` dealt and took over `round_results`, from every action they were part of
+fn combat_stats_of(round_results: &[RoundResult], fighter: AccountOwner) -> CombatStats {
+    let mut stats = CombatStats { damage_dealt: 0, damage_taken: 0, crits: 0, dodges: 0, highest_crit: 0, longest_combo: 0 };
+
+    for round in round_results {
+        for action in round.player1_actions.iter().chain(&round.player2_actions) {
+            // Only what got past a shield counts
+            let hp_damage = if action.was_dodged { 0 } else { action.damage.saturating_sub(action.absorbed) as u64 };
+            if action.attacker == fighter {
+                stats.damage_dealt = stats.damage_dealt.saturating_add(hp_damage);
+                if action.was_crit {
+                    stats.crits += 1;
+                    stats.highest_crit = stats.highest_crit.max(action.damage as u64);
+                }
+            }
+            if action.defender == fighter {
+                stats.damage_taken = stats.damage_taken.saturating_add(hp_damage);
+                if action.was_dodged {
+                    stats.dodges += 1;
+                }
+            }
+        }
+    }
+    stats
+}
+
+fn convert_stats(stats: &CombatStats) -> majorules::CombatStats {
+    majorules::CombatStats {
+        damage_dealt: stats.damage_dealt,
+        damage_taken: stats.damage_taken,
+        crits: stats.crits,
+        dodges: stats.dodges,
+        highest_crit: stats.highest_crit,
+        longest_combo: stats.longest_combo,
+    }
+}
+
+// ===== TEAM BATTLES =====
+// Two sides of up to `MAX_TEAM_SIZE` fighters. Fighters submit in the clear, each picking an
+// enemy to attack; a turn resolves once every fighter still standing is in, and a round closes
+// on its own once its last turn resolved
+
+#[allow(clippy::too_many_arguments)]
+fn initialize_team_battle(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    teams: [Vec<majorules::BattleParticipant>; 2],
+    lobby_chain_id: ChainId,
+    platform_fee_bps: u16,
+    treasury_owner: AccountOwner,
+    rules: BattleRules,
+    rules_version: u32,
+) {
+    // Only the lobby opens battles, and only with one to MAX_TEAM_SIZE distinct fighters a side
+    if runtime.message_origin_chain_id() != Some(lobby_chain_id) {
+        return;
+    }
+    if state.roster.get().is_some() || state.team_roster.get().is_some() {
+        return;
+    }
+
+    let teams = teams.map(|side| side.into_iter().map(BattleParticipant::from).collect::<Vec<_>>());
+    let roster = TeamRoster::of(&teams);
+    if !roster.is_valid() {
+        return;
+    }
+    state.team_roster.set(Some(roster));
+    state.teams.set(teams);
+    state.resolved_turns.set(0);
+    open_battle(state, runtime, lobby_chain_id, platform_fee_bps, treasury_owner, rules, rules_version);
+}
+
+/// Team battles take team turns only: nothing is sealed, and rounds close without being asked to
+async fn handle_team_operation(
+    operation: Operation,
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+) -> OperationResponse {
+    let seated = state.team_roster.get().as_ref().and_then(|roster| roster.seat_of(caller)).is_some();
+    let (round, turn, stance, use_special, target, emote) = match operation {
+        Operation::SubmitTeamTurn { round, turn, choice } => (round, turn, choice.stance, choice.use_special, choice.target, choice.emote),
+        Operation::ClaimForfeit { round, turn } if seated => {
+            return claim_team_forfeit(state, runtime, caller, round, turn).await;
+        }
+        other => {
+            let reason = if seated { "not_in_team_battles" } else { "not_a_participant" };
+            let refused = reject(state, runtime, "BattleOperation", reason, caller).await;
+            return match other {
+                Operation::SubmitTurn { .. } | Operation::CommitTurn { .. } | Operation::RevealTurn { .. } => {
+                    OperationResponse::TurnAck(TurnAck::rejected(reason))
+                }
+                _ => refused,
+            };
+        }
+    };
+    let accepted = if seated {
+        accept_team_turn(state, runtime, caller, round, turn, stance, use_special, target, emote).await
     } else {
-        (0.0, 1.0)
+        Err("not_a_participant")
     };
-    
-    // Calculate ELO changes
-    let p1_change = (k_factor as f64 * (actual_p1 - expected_p1)).round() as i32;
-    let p2_change = (k_factor as f64 * (actual_p2 - expected_p2)).round() as i32;
-    
-    (p1_change, p2_change)
+    match accepted {
+        Ok(executed) => OperationResponse::TurnAck(team_turn_ack(state, runtime, caller, round, executed).await),
+        Err(reason) => {
+            reject(state, runtime, "SubmitTeamTurn", reason, caller).await;
+            OperationResponse::TurnAck(TurnAck::rejected(reason))
+        }
+    }
 }
 
-fn calculate_combat_stats(round_results: &[RoundResult], winner: &AccountOwner) -> (CombatStats, CombatStats) {
-    let mut winner_stats = CombatStats { damage_dealt: 0, damage_taken: 0, crits: 0, dodges: 0, highest_crit: 0 };
-    let mut loser_stats = CombatStats { damage_dealt: 0, damage_taken: 0, crits: 0, dodges: 0, highest_crit: 0 };
+/// Store a team turn and resolve every turn that is now complete, in order. Returns whether
+/// the submitted turn resolved, or the rejection reason
+#[allow(clippy::too_many_arguments)]
+async fn accept_team_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+    stance: String,
+    use_special: bool,
+    target: u8,
+    emote: Option<Emote>,
+) -> Result<bool, &'static str> {
+    state.turn_check(caller, round, turn).await?;
+    let Some((side, seat)) = state.team_roster.get().as_ref().and_then(|roster| roster.seat_of(caller)) else {
+        return Err("not_a_participant");
+    };
+    let teams = state.teams.get();
+    if teams[side][seat].current_hp == 0 {
+        return Err("fighter_down");
+    }
+    if teams[1 - side].get(usize::from(target)).is_none_or(|enemy| enemy.current_hp == 0) {
+        return Err("invalid_target");
+    }
+    let stance = check_choice(state, caller, turn, &stance, use_special).await?;
+    report_start(state, runtime);
+    emit(state, runtime, BattleEvent::TurnSubmitted { round, turn, player: caller, sealed: false });
+    let submission = TurnSubmission { round, turn, stance, use_special, salt: None, target: Some(target), emote };
+    state.turn_submissions.insert(&(caller, turn), submission)
+        .expect("Failed to store turn submission");
 
-    for round in round_results {
-        for actions in [&round.player1_actions, &round.player2_actions] {
-            for action in actions {
-                let (attacker_stats, defender_stats) = if &action.attacker == winner {
-                    (&mut winner_stats, &mut loser_stats)
-                } else {
-                    (&mut loser_stats, &mut winner_stats)
-                };
+    let mut executed = false;
+    while *state.status.get() == BattleStatus::InProgress && *state.current_round.get() == round {
+        let next = *state.resolved_turns.get();
+        if next >= state.turns_per_round() || !team_turn_ready(state, next).await {
+            break;
+        }
+        execute_team_turn(state, runtime, next).await;
+        executed |= next == turn;
+    }
+    if !executed {
+        refresh_timing(state, runtime, BattlePhase::SubmittingTurns);
+    }
+    Ok(executed)
+}
+
+/// Whether every fighter still standing submitted `turn`
+async fn team_turn_ready(state: &BattleState, turn: u8) -> bool {
+    state.side_submitted(0, turn).await && state.side_submitted(1, turn).await
+}
+
+/// End a team battle for the caller's side once an enemy still standing let the round's
+/// deadline pass without submitting the turn the battle waits on
+async fn claim_team_forfeit(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    turn: u8,
+) -> OperationResponse {
+    match state.team_forfeit_check(caller, round, turn, runtime.system_time()).await {
+        Ok(side) => finalize_team_battle(state, runtime, side, BattleEndReason::Forfeit).await,
+        Err(reason) => return reject(state, runtime, "ClaimForfeit", reason, caller).await,
+    }
+    OperationResponse::Done
+}
+
+/// Resolve `turn` of the current round for both sides, then end the battle or, on the round's
+/// last turn, close the round
+async fn execute_team_turn(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    turn: u8,
+) {
+    let Some(roster) = state.team_roster.get().clone() else {
+        return;
+    };
+    let mut teams = state.teams.get().clone();
+    let mut submissions: [Vec<Option<TurnSubmission>>; 2] = Default::default();
+    for (side, fighters) in teams.iter().enumerate() {
+        for fighter in fighters {
+            let submission = state.turn_submissions.get(&(fighter.owner, turn)).await.ok().flatten();
+            submissions[side].push(submission.filter(|_| fighter.current_hp > 0));
+        }
+    }
+
+    // Each side's commitments feed the seed together, seat by seat
+    let commits = submissions.each_ref().map(|side| side.iter().flatten().flat_map(commit_data).collect::<Vec<u8>>());
+    let round = *state.current_round.get();
+    let seed = turn_seed(runtime.chain_id(), runtime.block_height(), round, turn, commits);
+    let mut random_counter = *state.random_counter.get();
+    let rules = state.rules.get().clone();
+    let actions = resolve_team_turn(seed, &rules, round, &mut random_counter, &mut teams, &submissions);
+    state.random_counter.set(random_counter);
+
+    // Each side's actions go under its player slot, with the side's HP
+    let mut round_result = current_round_result(state).await;
+    for action in actions {
+        match roster.seat_of(action.attacker) {
+            Some((0, _)) => round_result.player1_actions.push(action),
+            _ => round_result.player2_actions.push(action),
+        }
+    }
+    let [hp1, hp2] = teams.each_ref().map(|side| side_hp(side));
+    (round_result.player1_hp, round_result.player2_hp) = (hp1, hp2);
+    let [effects1, effects2] = teams.each_ref().map(|side| side.iter().flat_map(|fighter| fighter.effects.clone()).collect::<Vec<_>>());
+    (round_result.player1_effects, round_result.player2_effects) = (effects1, effects2);
+    let emotes = roster.sides.iter().zip(&submissions).flat_map(|(seats, side)| seats.iter().zip(side))
+        .filter_map(|(seat, submission)| Some(TurnEmote { turn, player: seat.owner, emote: submission.as_ref()?.emote? }));
+    round_result.emotes.extend(emotes);
+    state.round_results.insert(&round, round_result)
+        .expect("Failed to store round result");
+
+    // Spectators see each side's first choice of the turn
+    let choice = |side: &Vec<Option<TurnSubmission>>| -> (majorules::Stance, bool) {
+        let first = side.iter().flatten().next();
+        first.map_or((Stance::Balanced.into(), false), |submission| (submission.stance.into(), submission.use_special))
+    };
+    let resolved = BattleEvent::TurnResolved {
+        round,
+        turn,
+        choices: [choice(&submissions[0]), choice(&submissions[1])],
+        player1_hp: hp1,
+        player2_hp: hp2,
+    };
+    emit(state, runtime, resolved);
+
+    if !roster.matches(&teams) {
+        return;
+    }
+    state.teams.set(teams);
+    state.resolved_turns.set(turn + 1);
+
+    let last_turn = turn + 1 == state.turns_per_round();
+    if let Some((end_reason, winning_side)) = decide_team_ending(state.teams.get(), false) {
+        finalize_team_battle(state, runtime, winning_side, end_reason).await;
+    } else if last_turn {
+        close_team_round(state, runtime).await;
+    } else {
+        refresh_timing(state, runtime, BattlePhase::SubmittingTurns);
+    }
+}
+
+/// Fight one team turn: effects tick for every fighter standing, then the seats attack in
+/// turn, side 1 first in each seat, each at its chosen enemy or the first one still standing
+fn resolve_team_turn(
+    seed: [u8; 32],
+    rules: &BattleRules,
+    round: u8,
+    random_counter: &mut u64,
+    teams: &mut [Vec<BattleParticipant>; 2],
+    submissions: &[Vec<Option<TurnSubmission>>; 2],
+) -> Vec<CombatAction> {
+    let mut actions = Vec::new();
+    let mut stunned = [vec![false; teams[0].len()], vec![false; teams[1].len()]];
+    for side in 0..2 {
+        for (seat, submission) in submissions[side].iter().enumerate() {
+            let Some(submission) = submission else { continue };
+            let (own, enemies) = own_and_enemies(teams, side);
+            let Some(target) = target_of(enemies, submission.target) else { continue };
+            stunned[side][seat] = tick_effects(&mut own[seat], &enemies[target], submission.stance, &mut actions);
+        }
+    }
+
+    for seat in 0..MAX_TEAM_SIZE {
+        for side in 0..2 {
+            let Some(Some(submission)) = submissions[side].get(seat) else { continue };
+            let (own, enemies) = own_and_enemies(teams, side);
+            let Some(target) = target_of(enemies, submission.target) else { continue };
+            let (attacker, defender) = (&mut own[seat], &mut enemies[target]);
+            if attacker.current_hp == 0 {
+                continue;
+            }
+            if stunned[side][seat] {
+                attacker.tick_cooldown();
+                defender.tick_cooldown();
+                continue;
+            }
+            let defender_stance = submissions[1 - side][target].as_ref().map_or(Stance::Balanced, |turn| turn.stance);
+            let rolls = AttackRolls::new(seed, (side * MAX_TEAM_SIZE + seat) as u8);
+            if let Ok((attack, counter)) = execute_attack(rolls, random_counter, rules, round, attacker, defender, submission, defender_stance) {
+                actions.push(attack);
+                actions.extend(counter);
+            }
+        }
+    }
+    actions
+}
+
+/// `side`'s fighters and their enemies
+fn own_and_enemies(teams: &mut [Vec<BattleParticipant>; 2], side: usize) -> (&mut Vec<BattleParticipant>, &mut Vec<BattleParticipant>) {
+    let [first, second] = teams;
+    if side == 0 { (first, second) } else { (second, first) }
+}
+
+/// Seat of the enemy an attack lands on: the chosen one while standing, else the first standing
+fn target_of(enemies: &[BattleParticipant], chosen: Option<u8>) -> Option<usize> {
+    chosen.map(usize::from)
+        .filter(|&seat| enemies.get(seat).is_some_and(|enemy| enemy.current_hp > 0))
+        .or_else(|| enemies.iter().position(|enemy| enemy.current_hp > 0))
+}
+
+fn side_hp(side: &[BattleParticipant]) -> u32 {
+    side.iter().fold(0, |total, fighter| total.saturating_add(fighter.current_hp))
+}
+
+/// How a team fight stands, decided as a fight between the sides' pooled HP: a side is knocked
+/// out once all its fighters are. Returns the ending and winning side; a draw names side 0
+fn decide_team_ending(teams: &[Vec<BattleParticipant>; 2], at_round_limit: bool) -> Option<(BattleEndReason, usize)> {
+    let [first, second] = teams.each_ref().map(|side| {
+        let mut pooled = side[0].clone();
+        pooled.current_hp = side_hp(side);
+        pooled.character.hp_max = side.iter().fold(0, |total, fighter| total.saturating_add(fighter.character.hp_max));
+        pooled
+    });
+    decide_ending(&first, &second, at_round_limit)
+        .map(|(end_reason, winner)| (end_reason, if winner == first.owner { 0 } else { 1 }))
+}
+
+/// Record the round's end and clear its submissions, then end the battle at the round limit or
+/// start the next round
+async fn close_team_round(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>) {
+    let round = *state.current_round.get();
+    let teams = state.teams.get().clone();
+    emit(state, runtime, BattleEvent::RoundEnded { round, player1_hp: side_hp(&teams[0]), player2_hp: side_hp(&teams[1]) });
+
+    for turn in 0..state.turns_per_round() {
+        for fighter in teams.iter().flatten() {
+            state.turn_submissions.remove(&(fighter.owner, turn)).ok();
+        }
+    }
+    state.resolved_turns.set(0);
+
+    let last_round = state.max_rounds.get().saturating_add(state.rules.get().sudden_death_rounds);
+    if let Some((end_reason, winning_side)) = decide_team_ending(&teams, round >= last_round) {
+        finalize_team_battle(state, runtime, winning_side, end_reason).await;
+    } else {
+        state.current_round.set(round + 1);
+        start_round_clock(state, runtime);
+    }
+}
+
+/// End a team battle and report every fighter's result to the lobby. The winning side splits
+/// the pot by stake and each winner rolls a drop; a draw splits it across everyone. Each side
+/// splits the XP one fighter would earn the same way, evenly if it staked nothing
+async fn finalize_team_battle(
+    state: &mut BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    winning_side: usize,
+    end_reason: BattleEndReason,
+) {
+    let Some(roster) = state.team_roster.get().clone() else {
+        return;
+    };
+    let teams = state.teams.get().clone();
+    // Results and payouts only ever go to the fighters the battle was opened for
+    if !roster.matches(&teams) {
+        return;
+    }
+
+    // The winning side's first seat stands in for the side wherever one winner is named
+    let drawn = end_reason == BattleEndReason::Draw;
+    state.winner.set((!drawn).then_some(roster.sides[winning_side][0].owner));
+    state.end_reason.set(Some(end_reason));
+    state.status.set(if drawn { BattleStatus::Draw } else { BattleStatus::Completed });
+    state.completed_at.set(Some(runtime.system_time()));
+    state.round_deadline.set(None);
+    state.timing.set(None);
+    let rounds_played = *state.current_round.get();
+    let finished = BattleEvent::BattleFinished { winner: *state.winner.get(), end_reason, rounds_played };
+    emit(state, runtime, finished);
+
+    let Some(lobby_chain) = *state.lobby_chain_id.get() else {
+        return;
+    };
+    let seats: Vec<(usize, &BattleParticipant)> = teams.iter().enumerate()
+        .flat_map(|(side, fighters)| fighters.iter().map(move |fighter| (side, fighter)))
+        .collect();
+    let total_stake = seats.iter().fold(Amount::ZERO, |total, (_, fighter)| total.saturating_add(fighter.stake));
+    let pot = FeeBreakdown::compute(total_stake, *state.platform_fee_bps.get()).winner_payout;
+    let shares_in = |side: usize| drawn || side == winning_side;
+    let weights: Vec<Amount> = seats.iter().map(|&(side, fighter)| if shares_in(side) { fighter.stake } else { Amount::ZERO }).collect();
+    let (payouts, _) = fees::split_pro_rata(pot, &weights, RoundingPolicy { dust_to: DustDestination::LargestShare });
+    let rules = state.rules.get();
+    let side_xp = |side: usize| {
+        let staked = seats.iter().any(|&(seat_side, fighter)| seat_side == side && fighter.stake > Amount::ZERO);
+        let weights: Vec<Amount> = seats.iter()
+            .map(|&(seat_side, fighter)| match (seat_side == side, staked) {
+                (false, _) => Amount::ZERO,
+                (true, true) => fighter.stake,
+                (true, false) => Amount::from_attos(1),
+            })
+            .collect();
+        let pool = Amount::from_attos(rules.awarded_xp(!drawn && side == winning_side).into());
+        fees::split_pro_rata(pool, &weights, RoundingPolicy { dust_to: DustDestination::LargestShare }).0
+    };
+    let xp = [side_xp(0), side_xp(1)];
+
+    let round_results: Vec<RoundResult> = state.round_results.index_values().await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, result)| result)
+        .collect();
+    let battle_chain = runtime.chain_id();
+    let random_counter = *state.random_counter.get();
+    let results = seats.iter().zip(payouts).enumerate().map(|(index, (&(side, fighter), payout))| {
+        let won = !drawn && side == winning_side;
+        let mut combat_stats = combat_stats_of(&round_results, fighter.owner);
+        combat_stats.longest_combo = fighter.longest_combo;
+        FighterResult {
+            player: fighter.owner,
+            character_id: fighter.character.nft_id.clone(),
+            won,
+            payout,
+            xp_gained: u128::from(xp[side][index]) as u64,
+            elo_change: 0,
+            item_drop: won.then(|| ItemDrop::roll(battle_chain, random_counter.wrapping_add(index as u64))).flatten(),
+            combat_stats: convert_stats(&combat_stats),
+            win_streak: 0,
+        }
+    }).collect();
+    runtime.prepare_message(Message::TeamBattleCompleted {
+        rounds_played,
+        total_stake,
+        rules_digest: rules.digest(),
+        end_reason,
+        results,
+    }).with_authentication().send_to(lobby_chain);
+}
+
+/// Snapshot a team fighter's view after a submission in `round`: their own HP against their
+/// enemies' total. Turns may resolve several at once, so the round is complete once it moved on
+async fn team_turn_ack(
+    state: &BattleState,
+    runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    caller: AccountOwner,
+    round: u8,
+    executed: bool,
+) -> TurnAck {
+    let Some((side, seat)) = state.team_roster.get().as_ref().and_then(|roster| roster.seat_of(caller)) else {
+        return TurnAck { accepted: true, executed, ..timed_ack(state, runtime) };
+    };
+    let teams = state.teams.get();
+    let me = &teams[side][seat];
+    let battle_over = *state.status.get() != BattleStatus::InProgress;
+    let mut next_turn_index = None;
+    for index in 0..state.turns_per_round() {
+        if !state.turn_locked(caller, index).await {
+            next_turn_index = Some(index);
+            break;
+        }
+    }
+    TurnAck {
+        accepted: true,
+        reason: None,
+        executed,
+        my_hp: me.current_hp,
+        opponent_hp: side_hp(&teams[1 - side]),
+        my_combo: me.combo_stack,
+        my_cooldown: me.special_cooldown,
+        next_turn_index: if battle_over || me.current_hp == 0 { None } else { next_turn_index },
+        round_complete: battle_over || *state.current_round.get() != round,
+        opponent_stance: None,
+        opponent_used_special: None,
+        ..timed_ack(state, runtime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        linera_base_types::{AccountOwner, Amount, BlockHeight, ChainId, CryptoHash, TimeDelta, Timestamp},
+        util::BlockingWait,
+        views::{
+            linera_views::{
+                batch::{Batch, WriteOperation},
+                context::Context as _,
+            },
+            RootView, View, ViewStorageContext,
+        },
+        ContractRuntime,
+    };
+    use majorules::{
+        cooldown::{cooldown_schedule, PlanStart},
+        practice::{practice_bot, PracticeDifficulty},
+        random::AttackRolls,
+        throttle::{RejectionKey, BACKOFF_THRESHOLD},
+        turn_commitment,
+        Attestation, BattleEndReason, BattleEvent, BattleParticipant, BattleRules, Bps, CharacterClass, CharacterSnapshot, Emote, ItemDrop, Message,
+        Operation, OperationResponse, Stance, TeamTurnChoice, TiebreakBy, TurnAck, ATTESTATION_WINDOW, BATTLE_EVENT_STREAM, REVEAL_WINDOW, TURNS_PER_ROUND,
+    };
+
+    use super::{
+        decide_ending, emit, execute_attack, finalize_battle, finalize_team_battle, handle_battle_message, handle_battle_operation,
+        resolve_turn, store_fighters, tick_effects, BATTLE_LOG_CAPACITY,
+    };
+    use crate::actions::{battle_actions, BattleAction, CanClaimForfeit, MustRevealTurn};
+    use crate::state::{
+        self, ActiveEffect, BattlePhase, BattleState, BattleStatus, CombatAction, RoundResult, StatusEffect, TurnEmote, TurnSubmission,
+    };
+
+    fn owner(name: &str) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(name))
+    }
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    fn participant(name: &str, hp_max: u32) -> BattleParticipant {
+        BattleParticipant::new(owner(name), chain(name), CharacterSnapshot {
+            nft_id: format!("{name}-character"),
+            class: CharacterClass::Warrior,
+            level: 1,
+            hp_max,
+            min_damage: 10,
+            max_damage: 20,
+            crit_chance: Bps(1000),
+            crit_multiplier: 15000,
+            dodge_chance: Bps(500),
+            defense: 5,
+            attack_bps: 0,
+            defense_bps: 0,
+            crit_bps: 0,
+        }, Amount::from_tokens(1))
+    }
+
+    fn at_secs(secs: u64) -> Timestamp {
+        Timestamp::from(secs * 1_000_000)
+    }
+
+    fn setup(hp_max: u32) -> (BattleState, ContractRuntime<crate::MajorulesContract>) {
+        setup_with_rules(hp_max, BattleRules::default())
+    }
+
+    fn setup_with_rules(hp_max: u32, rules: BattleRules) -> (BattleState, ContractRuntime<crate::MajorulesContract>) {
+        setup_with_message(Message::InitializeBattle {
+            player1: Box::new(participant("alice", hp_max)),
+            player2: Box::new(participant("bob", hp_max)),
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules,
+            rules_version: 1,
+        })
+    }
+
+    fn setup_with_message(initialize: Message) -> (BattleState, ContractRuntime<crate::MajorulesContract>) {
+        let mut runtime = ContractRuntime::new()
+            .with_chain_id(chain("battle"))
+            .with_block_height(BlockHeight(1))
+            .with_system_time(Timestamp::from(0));
+        let mut state = BattleState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+
+        runtime.set_message_origin_chain_id(chain("lobby"));
+        handle_battle_message(initialize, &mut state, &mut runtime).blocking_wait();
+        state.save().blocking_wait().expect("Failed to save battle state");
+        (state, runtime)
+    }
+
+    fn submit(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8) -> TurnAck {
+        let round = *state.current_round.get();
+        submit_in_round(state, runtime, player, round, turn)
+    }
+
+    fn submit_in_round(
+        state: &mut BattleState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        round: u8,
+        turn: u8,
+    ) -> TurnAck {
+        submit_with_special(state, runtime, player, round, turn, false)
+    }
+
+    fn submit_with_special(
+        state: &mut BattleState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        round: u8,
+        turn: u8,
+        use_special: bool,
+    ) -> TurnAck {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        let response = handle_battle_operation(Operation::SubmitTurn {
+            round,
+            turn,
+            stance: "Aggressive".to_string(),
+            use_special,
+            emote: None,
+        }, state, runtime).blocking_wait();
+        match response {
+            OperationResponse::TurnAck(ack) => ack,
+            other => panic!("Expected a turn acknowledgement, got {other:?}"),
+        }
+    }
+
+    /// Keys of the registers `state` would rewrite on save, leaving out the `timing`
+    /// acknowledgement that every accepted submission refreshes. A register is stored at its
+    /// field's own key, while collections store beneath theirs, so these are the batch's writes
+    /// at a field-length key
+    fn written_registers(state: &BattleState) -> Vec<Vec<u8>> {
+        let field_key = |context: &ViewStorageContext| context.base_key().bytes.clone();
+        let timing = field_key(state.timing.context());
+        let mut batch = Batch::new();
+        state.pre_save(&mut batch).expect("Failed to collect battle state writes");
+        batch.operations.into_iter()
+            .filter_map(|operation| match operation {
+                WriteOperation::Put { key, .. } if key.len() == timing.len() && key != timing => Some(key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn executed_turn_writes_at_most_three_registers() {
+        let (mut state, mut runtime) = setup(1_000);
+
+        let mut batch_sizes = Vec::new();
+        for turn in 0..3 {
+            submit(&mut state, &mut runtime, "alice", turn);
+            state.save().blocking_wait().expect("Failed to save battle state");
+            submit(&mut state, &mut runtime, "bob", turn);
+
+            let written = written_registers(&state);
+            // At most the two fighters and the roll counter
+            assert!((1..=3).contains(&written.len()), "turn {turn} rewrote {} registers", written.len());
+            let mut batch = Batch::new();
+            state.pre_save(&mut batch).expect("Failed to collect battle state writes");
+            batch_sizes.push(batch.operations.len());
+            state.save().blocking_wait().expect("Failed to save battle state");
+            assert!(written_registers(&state).is_empty());
+        }
+
+        // Appending a turn never rewrites the turns before it
+        assert_eq!(*state.random_counter.get(), 6);
+        assert!(batch_sizes.windows(2).all(|sizes| sizes[0] == sizes[1]), "{batch_sizes:?}");
+    }
+
+    #[test]
+    fn turns_roll_from_their_block_and_submissions() {
+        let hp_after_first_turn = |height: u64| {
+            let (mut state, mut runtime) = setup(1_000);
+            runtime.set_block_height(BlockHeight(height));
+            for player in ["alice", "bob"] {
+                runtime.set_authenticated_signer(Some(owner(player)));
+                handle_battle_operation(Operation::SubmitTurn {
+                    round: 1,
+                    turn: 0,
+                    stance: "Balanced".to_string(),
+                    use_special: false,
+                    emote: None,
+                }, &mut state, &mut runtime).blocking_wait();
+            }
+            let (alice, bob) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+            (alice.current_hp, bob.current_hp)
+        };
+
+        let rolled = hp_after_first_turn(1);
+        assert_eq!(rolled, hp_after_first_turn(1));
+        assert_ne!(rolled, hp_after_first_turn(2));
+    }
+
+    #[test]
+    fn second_submission_acks_the_executed_turn() {
+        let (mut state, mut runtime) = setup(1_000);
+
+        let first = submit(&mut state, &mut runtime, "alice", 0);
+        assert!(first.accepted && !first.executed);
+        assert_eq!((first.next_turn_index, first.opponent_stance), (Some(1), None));
+
+        let second = submit(&mut state, &mut runtime, "bob", 0);
+        let (alice, bob) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+        assert!(second.accepted && second.executed && !second.round_complete);
+        assert_eq!((second.my_hp, second.opponent_hp), (bob.current_hp, alice.current_hp));
+        assert!(second.opponent_hp < 1_000);
+        assert_eq!((second.my_combo, second.my_cooldown), (bob.combo_stack, bob.special_cooldown));
+        assert_eq!(second.next_turn_index, Some(1));
+        assert_eq!((second.opponent_stance, second.opponent_used_special), (Some(Stance::Aggressive), Some(false)));
+
+        submit(&mut state, &mut runtime, "alice", 1);
+        submit(&mut state, &mut runtime, "bob", 1);
+        submit(&mut state, &mut runtime, "alice", 2);
+        let last = submit(&mut state, &mut runtime, "bob", 2);
+        assert!(last.round_complete);
+        assert_eq!(last.next_turn_index, None);
+    }
+
+    #[test]
+    fn rejected_submission_acks_the_reason() {
+        let (mut state, mut runtime) = setup(1_000);
+
+        let ack = submit_in_round(&mut state, &mut runtime, "alice", 5, 0);
+
+        assert_eq!(ack, TurnAck::rejected("wrong_round"));
+        assert!(!ack.accepted);
+    }
+
+    /// Play full rounds until the battle completes or its last round, sudden death included,
+    /// is exhausted
+    fn play_out(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>) {
+        for _ in 0..state.rules.get().last_round().unwrap_or(u8::MAX) {
+            if *state.status.get() == BattleStatus::Completed {
+                break;
+            }
+            for turn in 0..state.turns_per_round() {
+                submit(state, runtime, "alice", turn);
+                submit(state, runtime, "bob", turn);
+            }
+            for player in ["alice", "bob"] {
+                runtime.set_authenticated_signer(Some(owner(player)));
+                handle_battle_operation(Operation::ExecuteRound, state, runtime).blocking_wait();
+            }
+        }
+    }
+
+    #[test]
+    fn replays_re_simulate_every_turn_from_the_opening_fighters() {
+        let (mut state, mut runtime) = setup(300);
+        play_out(&mut state, &mut runtime);
+        let [mut player1, mut player2] = <[_; 2]>::try_from(state.opening_fighters.get().clone()).unwrap();
+        let rules = state.rules.get().clone();
+        let mut random_counter = 0;
+
+        let turns = state.replay_turns.index_values().blocking_wait().unwrap();
+        assert!(turns.len() > TURNS_PER_ROUND as usize);
+        for ((round, turn), replayed) in turns {
+            let [first, second] = replayed.choices.map(|(stance, use_special)| TurnSubmission { round, turn, stance, use_special, salt: None, target: None, emote: None });
+            let actions = resolve_turn(replayed.seed, &rules, round, &mut random_counter, &mut player1, &mut player2, &first, &second);
+            assert_eq!(format!("{actions:?}"), format!("{:?}", replayed.actions), "round {round} turn {turn}");
+        }
+        let fought = [state.player1.get(), state.player2.get()].map(|fighter| fighter.as_ref().unwrap().current_hp);
+        assert_eq!([player1.current_hp, player2.current_hp], fought);
+    }
+
+    #[test]
+    fn rounds_close_only_once_every_turn_executed() {
+        let (mut state, mut runtime) = setup(1_000);
+        let execute_round = |state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>| {
+            for player in ["alice", "bob"] {
+                runtime.set_authenticated_signer(Some(owner(player)));
+                handle_battle_operation(Operation::ExecuteRound, state, runtime).blocking_wait();
+            }
+        };
+
+        submit(&mut state, &mut runtime, "alice", 0);
+        submit(&mut state, &mut runtime, "bob", 0);
+        submit(&mut state, &mut runtime, "alice", 1);
+        execute_round(&mut state, &mut runtime);
+        assert_eq!(*state.current_round.get(), 1);
+        let pending = RejectionKey::new("ExecuteRound", "turns_pending", owner("alice"));
+        assert!(state.rejections.contains_key(&pending).blocking_wait().unwrap());
+        let response = handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        assert_eq!(response, OperationResponse::rejected("turns_pending"));
+
+        submit(&mut state, &mut runtime, "bob", 1);
+        submit(&mut state, &mut runtime, "alice", 2);
+        submit(&mut state, &mut runtime, "bob", 2);
+        execute_round(&mut state, &mut runtime);
+        assert_eq!(*state.current_round.get(), 2);
+        let round = state.round_results.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!((round.player1_actions.len(), round.player2_actions.len()), (3, 3));
+
+        let log: Vec<BattleEvent> = state.battle_log.elements().blocking_wait().unwrap().into_iter().map(|(_, event)| event).collect();
+        let requested = log.iter()
+            .filter(|event| matches!(event, BattleEvent::RoundCloseRequested { round: 1, .. }))
+            .count();
+        assert_eq!(requested, 2);
+        assert!(matches!(log.last(), Some(BattleEvent::RoundEnded { round: 1, .. })));
+    }
+
+    #[test]
+    fn a_full_round_publishes_each_turn_then_the_close() {
+        let (mut state, mut runtime) = setup(1_000);
+        let turns = state.turns_per_round();
+        for turn in 0..turns {
+            submit(&mut state, &mut runtime, "alice", turn);
+            submit(&mut state, &mut runtime, "bob", turn);
+        }
+        for player in ["alice", "bob"] {
+            runtime.set_authenticated_signer(Some(owner(player)));
+            handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        }
+
+        let (p1, p2) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+        let (indices, log): (Vec<u32>, Vec<BattleEvent>) = state.battle_log.elements().blocking_wait().unwrap().into_iter().unzip();
+        let mut expected = Vec::new();
+        for turn in 0..turns {
+            for player in ["alice", "bob"] {
+                expected.push(BattleEvent::TurnSubmitted { round: 1, turn, player: owner(player), sealed: false });
+            }
+            // Hit points are whatever the turn left; the last turn's match the round's end
+            let Some(BattleEvent::TurnResolved { player1_hp, player2_hp, .. }) = log.get(expected.len()) else {
+                panic!("Expected turn {turn} to resolve, got {log:?}");
+            };
+            expected.push(BattleEvent::TurnResolved {
+                round: 1,
+                turn,
+                choices: [(Stance::Aggressive, false); 2],
+                player1_hp: *player1_hp,
+                player2_hp: *player2_hp,
+            });
+        }
+        for player in ["alice", "bob"] {
+            expected.push(BattleEvent::RoundCloseRequested { round: 1, player: owner(player) });
+        }
+        expected.push(BattleEvent::RoundEnded { round: 1, player1_hp: p1.current_hp, player2_hp: p2.current_hp });
+        assert_eq!(log, expected);
+        assert!(matches!(&log[log.len() - 4], BattleEvent::TurnResolved { player1_hp, player2_hp, .. }
+            if (*player1_hp, *player2_hp) == (p1.current_hp, p2.current_hp)));
+        assert!(p1.current_hp < 1_000 && p2.current_hp < 1_000);
+
+        // Every logged event went out on the stream first, at the index it was logged with, and
+        // the next one lands right after them
+        assert!(indices.into_iter().eq(0..log.len() as u32));
+        let next = runtime.emit(BATTLE_EVENT_STREAM.into(), &BattleEvent::RoundCloseRequested { round: 2, player: owner("alice") });
+        assert_eq!(next as usize, log.len());
+    }
+
+    #[test]
+    fn emotes_ride_along_with_their_turn_into_events_and_the_round_result() {
+        let (mut state, mut runtime) = setup(1_000);
+        let send = |state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, emote: Emote| {
+            runtime.set_authenticated_signer(Some(owner(player)));
+            let operation = Operation::SubmitTurn { round: 1, turn: 0, stance: "Balanced".to_string(), use_special: false, emote: Some(emote) };
+            handle_battle_operation(operation, state, runtime).blocking_wait()
+        };
+        let emoted = |state: &BattleState| -> Vec<(AccountOwner, Emote)> {
+            state.battle_log.elements().blocking_wait().unwrap().into_iter()
+                .filter_map(|(_, event)| match event {
+                    BattleEvent::Emoted { round: 1, turn: 0, player, emote } => Some((player, emote)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Spectators see an emote as soon as its turn is in
+        send(&mut state, &mut runtime, "alice", Emote::GoodLuck);
+        assert_eq!(emoted(&state), [(owner("alice"), Emote::GoodLuck)]);
+        assert!(state.round_results.get(&1).blocking_wait().unwrap().is_none());
+
+        send(&mut state, &mut runtime, "bob", Emote::Taunt);
+        let round = state.round_results.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!(round.emotes, [
+            TurnEmote { turn: 0, player: owner("alice"), emote: Emote::GoodLuck },
+            TurnEmote { turn: 0, player: owner("bob"), emote: Emote::Taunt },
+        ]);
+
+        // One turn carries one emote: resubmitting the turn to emote again is refused
+        let response = send(&mut state, &mut runtime, "alice", Emote::Oops);
+        assert!(matches!(response, OperationResponse::TurnAck(TurnAck { reason: Some(_), .. })));
+        assert_eq!(emoted(&state).len(), 2);
+    }
+
+    #[test]
+    fn practice_bots_answer_every_turn_and_pay_only_reduced_xp() {
+        let mut alice = participant("alice", 200);
+        alice.stake = Amount::ZERO;
+        let (mut state, mut runtime) = setup_with_message(Message::InitializePracticeBattle {
+            player: Box::new(alice),
+            difficulty: PracticeDifficulty::Hard,
+            lobby_chain_id: chain("lobby"),
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        });
+        let bot = state.player2.get().clone().unwrap();
+        assert_eq!((bot.owner, bot.stake, bot.character.nft_id.as_str()), (practice_bot(), Amount::ZERO, "alice-character"));
 
-                if !action.was_dodged {
-                    attacker_stats.damage_dealt += action.damage as u64;
-                    defender_stats.damage_taken += action.damage as u64;
+        // There is no one to seal a turn from
+        runtime.set_authenticated_signer(Some(owner("alice")));
+        let sealed = handle_battle_operation(Operation::CommitTurn { round: 1, turn: 0, commitment: [0; 32] }, &mut state, &mut runtime).blocking_wait();
+        assert!(matches!(sealed, OperationResponse::TurnAck(ack) if ack.reason.as_deref() == Some("practice_battle")));
+
+        // Every turn executes on the player's submission alone, and the player alone closes rounds
+        while *state.status.get() == BattleStatus::InProgress {
+            for turn in 0..state.turns_per_round() {
+                if *state.status.get() != BattleStatus::InProgress {
+                    break;
                 }
-                if action.was_crit {
-                    attacker_stats.crits += 1;
-                    if action.damage as u64 > attacker_stats.highest_crit {
-                        attacker_stats.highest_crit = action.damage as u64;
+                assert!(submit(&mut state, &mut runtime, "alice", turn).executed);
+            }
+            runtime.set_authenticated_signer(Some(owner("alice")));
+            handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        }
+
+        let requests = runtime.created_send_message_requests();
+        assert!(!requests.iter().any(|request| matches!(request.message, Message::BattleCompleted { .. })));
+        let results: Vec<_> = requests.iter()
+            .filter_map(|request| match &request.message {
+                Message::PracticeBattleCompleted { result } => Some(result.clone()),
+                _ => None,
+            })
+            .collect();
+        let [result] = results.as_slice() else {
+            panic!("Expected one practice result, got {results:?}");
+        };
+        assert_eq!((result.player, result.payout, result.item_drop.is_none()), (owner("alice"), Amount::ZERO, true));
+        assert_eq!(result.won, *state.winner.get() == Some(owner("alice")));
+        assert_eq!(result.xp_gained, PracticeDifficulty::Hard.scaled_xp(BattleRules::default().awarded_xp(result.won)));
+    }
+
+    #[test]
+    fn the_battle_log_keeps_only_the_latest_events() {
+        let (mut state, mut runtime) = setup(1_000);
+        for round in 0..BATTLE_LOG_CAPACITY + 5 {
+            emit(&mut state, &mut runtime, BattleEvent::RoundEnded { round: round as u8, player1_hp: 1, player2_hp: 1 });
+        }
+
+        assert_eq!(state.battle_log.count(), BATTLE_LOG_CAPACITY);
+        let oldest = state.battle_log.front().blocking_wait().unwrap();
+        assert!(matches!(oldest, Some((5, BattleEvent::RoundEnded { round: 5, .. }))));
+    }
+
+    #[test]
+    fn battle_runs_to_completion() {
+        let (mut state, mut runtime) = setup(60);
+
+        play_out(&mut state, &mut runtime);
+
+        assert_eq!(*state.status.get(), BattleStatus::Completed);
+        let winner = state.winner.get().expect("Battle should have a winner");
+        let (p1, p2) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+        let (winner_hp, loser_hp) = if winner == p1.owner { (p1.current_hp, p2.current_hp) } else { (p2.current_hp, p1.current_hp) };
+        assert!(winner_hp > 0 && loser_hp == 0);
+
+        // A single completion carries both fighters' results and the ending the battle recorded
+        let requests = runtime.created_send_message_requests();
+        let completions: Vec<_> = requests.iter()
+            .filter(|request| request.destination == chain("lobby"))
+            .filter_map(|request| match &request.message {
+                Message::BattleCompleted { results, end_reason, .. } => {
+                    assert_eq!(Some(*end_reason), *state.end_reason.get());
+                    Some(results)
+                }
+                Message::BattleResultWithElo { .. } => panic!("Results should travel with the completion"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(completions.len(), 1);
+        let loser = if winner == p1.owner { p2.owner } else { p1.owner };
+        assert_eq!(completions[0].iter().map(|result| (result.player, result.won)).collect::<Vec<_>>(), [(winner, true), (loser, false)]);
+
+        // Betting closed once, as the first turn came in
+        let starts = requests.iter()
+            .filter(|request| request.destination == chain("lobby"))
+            .filter(|request| matches!(request.message, Message::BattleStarted { battle_chain } if battle_chain == chain("battle")))
+            .count();
+        assert_eq!(starts, 1);
+
+        // Only the winner can receive the deterministic item drop
+        let expected_drop = ItemDrop::roll(chain("battle"), *state.random_counter.get());
+        for result in completions[0] {
+            assert_eq!(result.item_drop, if result.won { expected_drop.clone() } else { None });
+        }
+
+        // Each fighter's player chain hears the battle's account, naming the other fighter
+        let rounds = *state.current_round.get();
+        for (fighter, other) in [(&p1, &p2), (&p2, &p1)] {
+            let reports: Vec<_> = requests.iter()
+                .filter(|request| request.destination == fighter.chain)
+                .filter_map(|request| match &request.message {
+                    Message::BattleResult { opponent, stake, payout, rounds_played, .. } => Some((*opponent, *stake, *payout, *rounds_played)),
+                    _ => None,
+                })
+                .collect();
+            let payout = completions[0].iter().find(|result| result.player == fighter.owner).unwrap().payout;
+            assert_eq!(reports, [(other.owner, fighter.stake, payout, rounds)]);
+        }
+    }
+
+    #[test]
+    fn executed_turns_fill_the_round_history_the_completion_reports() {
+        let (mut state, mut runtime) = setup(60);
+
+        play_out(&mut state, &mut runtime);
+
+        let rounds: Vec<RoundResult> = state.round_results.index_values().blocking_wait().unwrap()
+            .into_iter()
+            .map(|(_, result)| result)
+            .collect();
+        let (p1, p2) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+        assert!(rounds.iter().all(|round| !round.player1_actions.is_empty() && !round.player2_actions.is_empty()));
+        assert!(rounds.iter().all(|round| {
+            round.player1_actions.iter().all(|action| action.attacker == p1.owner)
+                && round.player2_actions.iter().all(|action| action.attacker == p2.owner)
+        }));
+        let last = rounds.last().unwrap();
+        assert_eq!((last.player1_hp, last.player2_hp), (p1.current_hp, p2.current_hp));
+
+        let winner = state.winner.get().unwrap();
+        let landed = |attacker: AccountOwner| -> u64 {
+            rounds.iter()
+                .flat_map(|round| round.player1_actions.iter().chain(&round.player2_actions))
+                .filter(|action| action.attacker == attacker && !action.was_dodged)
+                .map(|action| action.damage as u64)
+                .sum()
+        };
+        let stats = runtime.created_send_message_requests().iter()
+            .find_map(|request| match &request.message {
+                Message::BattleCompleted { battle_stats, .. } => Some(battle_stats.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(stats.0.damage_dealt > 0);
+        assert_eq!((stats.0.damage_dealt, stats.1.damage_taken), (landed(winner), landed(winner)));
+    }
+
+    #[test]
+    fn co_owners_who_are_not_fighters_are_rejected() {
+        let (mut state, mut runtime) = setup(1_000);
+        runtime.set_authenticated_signer(Some(owner("carol")));
+        let operations = [
+            ("SubmitTurn", Operation::SubmitTurn { round: 1, turn: 0, stance: "Aggressive".to_string(), use_special: false, emote: None }),
+            ("ExecuteRound", Operation::ExecuteRound),
+            ("AttestResult", Operation::AttestResult { agree: false }),
+            ("BattleOperation", Operation::LeaveQueue),
+        ];
+        for (name, operation) in operations {
+            let response = handle_battle_operation(operation, &mut state, &mut runtime).blocking_wait();
+            if name == "SubmitTurn" {
+                assert_eq!(response, OperationResponse::TurnAck(TurnAck::rejected("not_a_participant")));
+            }
+            let key = RejectionKey::new(name, "not_a_participant", owner("carol"));
+            assert!(state.rejections.contains_key(&key).blocking_wait().unwrap(), "{name}");
+        }
+
+        assert!(state.turn_submissions.indices().blocking_wait().unwrap().is_empty());
+        assert!(state.execute_requests.indices().blocking_wait().unwrap().is_empty());
+        assert!(state.attestations.indices().blocking_wait().unwrap().is_empty());
+        assert_eq!(*state.status.get(), BattleStatus::InProgress);
+    }
+
+    #[test]
+    fn participants_cannot_be_replaced_after_initialization() {
+        let (mut state, mut runtime) = setup(1_000);
+        let roster = state.roster.get().clone();
+
+        handle_battle_message(Message::InitializeBattle {
+            player1: Box::new(participant("carol", 1_000)),
+            player2: Box::new(participant("dave", 1_000)),
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 0,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 2,
+        }, &mut state, &mut runtime).blocking_wait();
+
+        assert_eq!(*state.roster.get(), roster);
+        assert_eq!(state.player1.get().as_ref().map(|player| player.owner), Some(owner("alice")));
+        assert_eq!(roster.map(|roster| roster.owners), Some([owner("alice"), owner("bob")]));
+    }
+
+    #[test]
+    fn participants_with_a_rewritten_stake_cannot_fight_on() {
+        let (mut state, mut runtime) = setup(1_000);
+        let mut tampered = state.player2.get().clone().unwrap();
+        tampered.stake = Amount::from_tokens(100);
+        state.player2.set(Some(tampered.clone()));
+
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 0), TurnAck::rejected("roster_mismatch"));
+        runtime.set_authenticated_signer(Some(owner("bob")));
+        for operation in [Operation::ExecuteRound, Operation::ClaimForfeit { round: 1, turn: 0 }] {
+            let response = handle_battle_operation(operation, &mut state, &mut runtime).blocking_wait();
+            assert_eq!(response, OperationResponse::rejected("roster_mismatch"));
+        }
+        assert!(state.rejections.contains_key(&RejectionKey::new("ClaimForfeit", "roster_mismatch", owner("bob"))).blocking_wait().unwrap());
+
+        // Nothing is stored, rated or paid for fighters the battle was not opened for
+        let alice = state.player1.get().clone().unwrap();
+        assert!(!store_fighters(&mut state, alice, tampered));
+        finalize_battle(&mut state, &mut runtime, owner("alice"), owner("bob"), BattleEndReason::Knockout).blocking_wait();
+        assert_eq!((*state.status.get(), *state.winner.get()), (BattleStatus::InProgress, None));
+        assert!(runtime.created_send_message_requests().is_empty());
+    }
+
+    #[test]
+    fn endings_are_decided_by_knockout_then_hp_share_then_damage() {
+        let (state, _) = setup(100);
+        let (alice, bob) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+        let decide = |(hp1, max1), (hp2, max2), at_round_limit| {
+            let (mut p1, mut p2) = (alice.clone(), bob.clone());
+            (p1.current_hp, p1.character.hp_max, p2.current_hp, p2.character.hp_max) = (hp1, max1, hp2, max2);
+            decide_ending(&p1, &p2, at_round_limit)
+        };
+        let tiebreak = |by| BattleEndReason::MaxRoundsTiebreak { by };
+
+        assert_eq!(decide((40, 100), (30, 100), false), None);
+        assert_eq!(decide((0, 100), (30, 100), false), Some((BattleEndReason::Knockout, owner("bob"))));
+        assert_eq!(decide((40, 100), (0, 100), true), Some((BattleEndReason::Knockout, owner("alice"))));
+        // More HP left but a smaller share of it loses
+        assert_eq!(decide((60, 200), (40, 100), true), Some((tiebreak(TiebreakBy::HpPercent), owner("bob"))));
+        // Equal shares: the smaller fighter dealt more damage to lose the same share
+        assert_eq!(decide((50, 100), (100, 200), true), Some((tiebreak(TiebreakBy::Damage), owner("alice"))));
+        assert_eq!(decide((100, 200), (50, 100), true), Some((tiebreak(TiebreakBy::Damage), owner("bob"))));
+        // Nothing separates them: a draw, nominally won by player 1
+        assert_eq!(decide((50, 100), (50, 100), true), Some((BattleEndReason::Draw, owner("alice"))));
+    }
+
+    #[test]
+    fn dead_even_battles_draw_and_refund_both_stakes_less_the_fee() {
+        let (mut state, mut runtime) = setup_with_rules(1_000, BattleRules { max_rounds: 1, ..BattleRules::default() });
+        for turn in 0..3 {
+            submit(&mut state, &mut runtime, "alice", turn);
+            submit(&mut state, &mut runtime, "bob", turn);
+        }
+        for register in [&mut state.player1, &mut state.player2] {
+            let fighter = register.get().clone().unwrap();
+            register.set(Some(state::BattleParticipant { current_hp: 600, ..fighter }));
+        }
+        for player in ["alice", "bob"] {
+            runtime.set_authenticated_signer(Some(owner(player)));
+            handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        }
+
+        assert_eq!(*state.status.get(), BattleStatus::Draw);
+        assert_eq!((*state.winner.get(), *state.end_reason.get()), (None, Some(BattleEndReason::Draw)));
+        let results: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match &request.message {
+                Message::BattleCompleted { results, end_reason: BattleEndReason::Draw, .. } => Some(results.clone()),
+                _ => None,
+            })
+            .flatten()
+            .map(|result| (result.player, result.won, result.payout, result.item_drop.is_none()))
+            .collect();
+        // 1 token each, less the 3% fee
+        let refund = Amount::from_millis(970);
+        assert_eq!(results, [(owner("alice"), false, refund, true), (owner("bob"), false, refund, true)]);
+    }
+
+    #[test]
+    fn plans_the_planner_accepts_pass_turn_by_turn() {
+        let start = PlanStart { round: 1, turn: 0, ready_in: 0, specials_this_round: 0 };
+        for special_cooldown in [2, 3, 4] {
+            for (fighter, opponent) in [("alice", "bob"), ("bob", "alice")] {
+                // Every subset of the first two rounds' turns
+                for mask in 0u32..64 {
+                    let plan: Vec<(u8, u8)> = (0..6u8)
+                        .filter(|bit| mask & (1 << bit) != 0)
+                        .map(|bit| (1 + bit / TURNS_PER_ROUND, bit % TURNS_PER_ROUND))
+                        .collect();
+                    let Ok(schedule) = cooldown_schedule(special_cooldown, start, TURNS_PER_ROUND, 2, &plan) else {
+                        continue;
+                    };
+
+                    let rules = BattleRules { special_cooldown, ..BattleRules::default() };
+                    let (mut state, mut runtime) = setup_with_rules(10_000, rules);
+                    let mut scheduled = schedule.into_iter();
+                    for round in 1..=2 {
+                        for turn in 0..TURNS_PER_ROUND {
+                            let use_special = plan.contains(&(round, turn));
+                            submit_in_round(&mut state, &mut runtime, opponent, round, turn);
+                            let ack = submit_with_special(&mut state, &mut runtime, fighter, round, turn, use_special);
+                            assert!(ack.accepted, "cooldown {special_cooldown}: {fighter} rejected at {round}/{turn} of {plan:?}");
+
+                            // The special fired and left the cooldown the planner predicted
+                            if use_special {
+                                let (_, _, cooldown_after) = scheduled.next().unwrap();
+                                let next = PlanStart::live(round, turn + 1, ack.my_cooldown, fighter == "alice", 0);
+                                assert_eq!(next.ready_in, cooldown_after);
+                            }
+                        }
+                        for player in ["alice", "bob"] {
+                            runtime.set_authenticated_signer(Some(owner(player)));
+                            handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+                        }
                     }
                 }
-                if action.was_dodged {
-                    defender_stats.dodges += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn specials_on_cooldown_are_rejected_at_submission() {
+        let (mut state, mut runtime) = setup(10_000);
+
+        // Alice queues a special before Bob has played the turn, so it already counts
+        assert!(submit_with_special(&mut state, &mut runtime, "alice", 1, 0, true).accepted);
+        let ack = submit_with_special(&mut state, &mut runtime, "alice", 1, 1, true);
+        assert_eq!(ack.reason.as_deref(), Some("special_on_cooldown"));
+
+        submit(&mut state, &mut runtime, "bob", 0);
+        assert!(submit_with_special(&mut state, &mut runtime, "alice", 1, 2, true).accepted);
+        let key = RejectionKey::new("SubmitTurn", "special_on_cooldown", owner("alice"));
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+    }
+
+    fn attest(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, agree: bool) {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        handle_battle_operation(Operation::AttestResult { agree }, state, runtime).blocking_wait();
+    }
+
+    fn attestations_sent(runtime: &mut ContractRuntime<crate::MajorulesContract>) -> Vec<Attestation> {
+        runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain("lobby"))
+            .filter_map(|request| match request.message {
+                Message::ResultAttestation { attestation } => Some(attestation),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dual_agreement_attests_the_result() {
+        let (mut state, mut runtime) = setup(60);
+        play_out(&mut state, &mut runtime);
+
+        attest(&mut state, &mut runtime, "alice", true);
+        assert!(attestations_sent(&mut runtime).is_empty());
+        attest(&mut state, &mut runtime, "bob", true);
+
+        assert_eq!(attestations_sent(&mut runtime), [Attestation::Attested]);
+    }
+
+    #[test]
+    fn disagreement_is_reported_once() {
+        let (mut state, mut runtime) = setup(60);
+        play_out(&mut state, &mut runtime);
+
+        attest(&mut state, &mut runtime, "alice", false);
+        attest(&mut state, &mut runtime, "bob", false);
+
+        assert_eq!(attestations_sent(&mut runtime), [Attestation::Disputed]);
+    }
+
+    #[test]
+    fn attestations_outside_the_window_are_rejected() {
+        let (mut state, mut runtime) = setup(60);
+        attest(&mut state, &mut runtime, "alice", true);
+        let early = RejectionKey::new("AttestResult", "battle_not_completed", owner("alice"));
+        assert!(state.rejections.contains_key(&early).blocking_wait().unwrap());
+
+        play_out(&mut state, &mut runtime);
+        let completed_at = state.completed_at.get().unwrap();
+        runtime.set_system_time(completed_at.saturating_add(ATTESTATION_WINDOW).saturating_add(TimeDelta::from_secs(1)));
+        attest(&mut state, &mut runtime, "alice", false);
+
+        let late = RejectionKey::new("AttestResult", "attestation_closed", owner("alice"));
+        assert!(state.rejections.contains_key(&late).blocking_wait().unwrap());
+        assert!(attestations_sent(&mut runtime).is_empty());
+    }
+
+    fn hinted_turns(state: &BattleState, player: &str, now: Timestamp) -> Vec<u8> {
+        battle_actions(state, owner(player), now).blocking_wait().into_iter()
+            .filter_map(|action| match action {
+                BattleAction::MustSubmitTurn(hint) => Some(hint.turn),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn can_attest(state: &BattleState, player: &str, now: Timestamp) -> bool {
+        battle_actions(state, owner(player), now).blocking_wait().iter()
+            .any(|action| matches!(action, BattleAction::CanAttest(_)))
+    }
+
+    #[test]
+    fn hints_match_the_operations_a_battle_takes() {
+        let (mut state, mut runtime) = setup(60);
+        let now = at_secs(0);
+        assert_eq!(hinted_turns(&state, "alice", now), [0, 1, 2]);
+        assert!(battle_actions(&state, owner("mallory"), now).blocking_wait().is_empty());
+        assert_eq!(submit(&mut state, &mut runtime, "mallory", 0), TurnAck::rejected("not_a_participant"));
+
+        assert!(submit(&mut state, &mut runtime, "alice", 0).accepted);
+        assert_eq!(hinted_turns(&state, "alice", now), [1, 2]);
+        assert_eq!(hinted_turns(&state, "bob", now), [0, 1, 2]);
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 0), TurnAck::rejected("duplicate_turn"));
+        assert!(!can_attest(&state, "alice", now));
+
+        play_out(&mut state, &mut runtime);
+        let completed_at = state.completed_at.get().unwrap();
+        assert!(hinted_turns(&state, "alice", completed_at).is_empty());
+        assert!(can_attest(&state, "alice", completed_at) && can_attest(&state, "bob", completed_at));
+
+        attest(&mut state, &mut runtime, "alice", true);
+        assert!(!can_attest(&state, "alice", completed_at));
+        attest(&mut state, &mut runtime, "alice", true);
+        let again = RejectionKey::new("AttestResult", "already_attested", owner("alice"));
+        assert!(state.rejections.contains_key(&again).blocking_wait().unwrap());
+
+        let late = completed_at.saturating_add(ATTESTATION_WINDOW).saturating_add(TimeDelta::from_secs(1));
+        assert!(!can_attest(&state, "bob", late));
+        runtime.set_system_time(late);
+        attest(&mut state, &mut runtime, "bob", true);
+        let closed = RejectionKey::new("AttestResult", "attestation_closed", owner("bob"));
+        assert!(state.rejections.contains_key(&closed).blocking_wait().unwrap());
+    }
+
+    fn salt(player: &str) -> [u8; 32] {
+        CryptoHash::test_hash(format!("{player}-salt")).into()
+    }
+
+    fn commit(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8, stance: Stance) -> TurnAck {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        let round = *state.current_round.get();
+        let commitment = turn_commitment(owner(player), round, turn, stance, false, salt(player));
+        match handle_battle_operation(Operation::CommitTurn { round, turn, commitment }, state, runtime).blocking_wait() {
+            OperationResponse::TurnAck(ack) => ack,
+            other => panic!("Expected a turn acknowledgement, got {other:?}"),
+        }
+    }
+
+    fn reveal(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8, stance: &str) -> TurnAck {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        let round = *state.current_round.get();
+        let operation = Operation::RevealTurn { round, turn, stance: stance.to_string(), use_special: false, salt: salt(player) };
+        match handle_battle_operation(operation, state, runtime).blocking_wait() {
+            OperationResponse::TurnAck(ack) => ack,
+            other => panic!("Expected a turn acknowledgement, got {other:?}"),
+        }
+    }
+
+    fn claim_forfeit(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8) {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        let round = *state.current_round.get();
+        handle_battle_operation(Operation::ClaimForfeit { round, turn }, state, runtime).blocking_wait();
+    }
+
+    #[test]
+    fn sealed_turns_execute_once_both_are_revealed() {
+        let (mut state, mut runtime) = setup(1_000);
+
+        assert!(commit(&mut state, &mut runtime, "alice", 0, Stance::Aggressive).accepted);
+        assert_eq!(reveal(&mut state, &mut runtime, "alice", 0, "Aggressive"), TurnAck::rejected("awaiting_opponent"));
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 0), TurnAck::rejected("duplicate_turn"));
+        assert_eq!(hinted_turns(&state, "alice", at_secs(0)), [1, 2]);
+
+        // Bob seals too; neither choice is on chain until revealed
+        assert!(commit(&mut state, &mut runtime, "bob", 0, Stance::Defensive).accepted);
+        assert!(state.turn_submissions.indices().blocking_wait().unwrap().is_empty());
+        let pending = battle_actions(&state, owner("alice"), at_secs(0)).blocking_wait();
+        let closes_at = at_secs(0).saturating_add(REVEAL_WINDOW);
+        assert!(pending.contains(&BattleAction::MustRevealTurn(MustRevealTurn { round: 1, turn: 0, closes_at })));
+
+        assert_eq!(reveal(&mut state, &mut runtime, "alice", 0, "Defensive"), TurnAck::rejected("commitment_mismatch"));
+        let opened = reveal(&mut state, &mut runtime, "alice", 0, "Aggressive");
+        assert!(opened.accepted && !opened.executed);
+        let executed = reveal(&mut state, &mut runtime, "bob", 0, "Defensive");
+        assert!(executed.accepted && executed.executed);
+        assert_eq!(executed.opponent_stance, Some(Stance::Aggressive));
+        assert_eq!(*state.random_counter.get(), 2);
+    }
+
+    #[test]
+    fn unrevealed_turns_are_forfeited() {
+        let (mut state, mut runtime) = setup(1_000);
+        commit(&mut state, &mut runtime, "alice", 0, Stance::Balanced);
+        commit(&mut state, &mut runtime, "bob", 0, Stance::Berserker);
+        reveal(&mut state, &mut runtime, "alice", 0, "Balanced");
+
+        claim_forfeit(&mut state, &mut runtime, "alice", 0);
+        let early = RejectionKey::new("ClaimForfeit", "reveal_open", owner("alice"));
+        assert!(state.rejections.contains_key(&early).blocking_wait().unwrap());
+
+        let late = at_secs(0).saturating_add(REVEAL_WINDOW).saturating_add(TimeDelta::from_secs(1));
+        runtime.set_system_time(late);
+        assert_eq!(reveal(&mut state, &mut runtime, "bob", 0, "Berserker"), TurnAck::rejected("reveal_closed"));
+        claim_forfeit(&mut state, &mut runtime, "bob", 0);
+        let unrevealed = RejectionKey::new("ClaimForfeit", "not_revealed", owner("bob"));
+        assert!(state.rejections.contains_key(&unrevealed).blocking_wait().unwrap());
+        assert!(battle_actions(&state, owner("alice"), late).blocking_wait()
+            .contains(&BattleAction::CanClaimForfeit(CanClaimForfeit { round: 1, turn: 0 })));
+
+        claim_forfeit(&mut state, &mut runtime, "alice", 0);
+        assert_eq!(*state.status.get(), BattleStatus::Completed);
+        assert_eq!((*state.winner.get(), *state.end_reason.get()), (Some(owner("alice")), Some(BattleEndReason::Forfeit)));
+        assert!(runtime.created_send_message_requests().iter()
+            .any(|request| matches!(request.message, Message::BattleCompleted { end_reason: BattleEndReason::Forfeit, .. })));
+    }
+
+    #[test]
+    fn battles_report_the_rules_they_ran_under() {
+        let boosted = BattleRules { max_rounds: 1, round_duration_secs: 30, xp_boost_bps: 20_000, ..BattleRules::default() };
+        let mut outcomes = Vec::new();
+
+        for rules in [BattleRules::default(), boosted.clone()] {
+            let (mut state, mut runtime) = setup_with_rules(60, rules.clone());
+            assert_eq!(*state.rules.get(), rules);
+            assert_eq!(*state.max_rounds.get(), rules.max_rounds);
+            assert_eq!(*state.round_deadline.get(), Some(at_secs(rules.round_duration_secs)));
+
+            play_out(&mut state, &mut runtime);
+            assert_eq!(*state.status.get(), BattleStatus::Completed);
+
+            let mut digests = Vec::new();
+            let mut xp = Vec::new();
+            for request in runtime.created_send_message_requests().iter() {
+                if let Message::BattleCompleted { rules_digest, results, .. } = &request.message {
+                    digests.push(*rules_digest);
+                    xp.extend(results.iter().map(|result| (result.won, result.xp_gained)));
                 }
             }
+            assert_eq!(digests, vec![rules.digest()]);
+            outcomes.push((rules.digest(), xp));
+        }
+
+        assert_ne!(outcomes[0].0, outcomes[1].0);
+        assert!(outcomes[0].1.contains(&(true, 150)) && outcomes[0].1.contains(&(false, 50)));
+        assert!(outcomes[1].1.contains(&(true, 300)) && outcomes[1].1.contains(&(false, 100)));
+    }
+
+    #[test]
+    fn battle_formats_set_turns_reveal_windows_and_sudden_death() {
+        let format = BattleRules {
+            max_rounds: 1,
+            turns_per_round: 2,
+            reveal_window_secs: 10,
+            sudden_death_rounds: 1,
+            sudden_death_step_bps: 10_000,
+            ..BattleRules::default()
+        };
+        let (mut state, mut runtime) = setup_with_rules(1_000, format.clone());
+        assert_eq!(hinted_turns(&state, "alice", at_secs(0)), [0, 1]);
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 2), TurnAck::rejected("invalid_turn"));
+
+        commit(&mut state, &mut runtime, "alice", 0, Stance::Aggressive);
+        commit(&mut state, &mut runtime, "bob", 0, Stance::Aggressive);
+        assert_eq!(state.reveal_deadlines.get(&0).blocking_wait().unwrap(), Some(at_secs(10)));
+        reveal(&mut state, &mut runtime, "alice", 0, "Aggressive");
+        reveal(&mut state, &mut runtime, "bob", 0, "Aggressive");
+        submit(&mut state, &mut runtime, "alice", 1);
+        assert!(submit(&mut state, &mut runtime, "bob", 1).round_complete);
+
+        // Both still stand after regulation, so the fight goes on into sudden death
+        let execute_round = |state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>| {
+            for player in ["alice", "bob"] {
+                runtime.set_authenticated_signer(Some(owner(player)));
+                handle_battle_operation(Operation::ExecuteRound, state, runtime).blocking_wait();
+            }
+        };
+        execute_round(&mut state, &mut runtime);
+        assert_eq!((*state.current_round.get(), *state.status.get()), (2, BattleStatus::InProgress));
+
+        // Sudden-death attacks land for double
+        let turn = TurnSubmission { round: 2, turn: 0, stance: state::Stance::Balanced, use_special: false, salt: None, target: None, emote: None };
+        for seed in 0..20 {
+            let (attacker, defender) = (state.player1.get().clone().unwrap(), state.player2.get().clone().unwrap());
+            let hit = |round| {
+                let rolls = AttackRolls::new([seed; 32], 0);
+                execute_attack(rolls, &mut 0, &format, round, &mut attacker.clone(), &mut defender.clone(), &turn, state::Stance::Balanced).unwrap().0
+            };
+            let (regulation, sudden_death) = (hit(1), hit(2));
+            assert!(sudden_death.damage.abs_diff(regulation.damage * 2) <= 1, "seed {seed}");
+        }
+
+        for turn in 0..2 {
+            submit(&mut state, &mut runtime, "alice", turn);
+            submit(&mut state, &mut runtime, "bob", turn);
+        }
+        execute_round(&mut state, &mut runtime);
+        assert_ne!(*state.status.get(), BattleStatus::InProgress);
+        assert_eq!(state.round_results.count().blocking_wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn rounds_close_on_the_formats_last_turn() {
+        let format = BattleRules { turns_per_round: 4, ..BattleRules::default() };
+        let (mut state, mut runtime) = setup_with_rules(1_000, format);
+        for turn in 0..4 {
+            submit(&mut state, &mut runtime, "alice", turn);
+            let ack = submit(&mut state, &mut runtime, "bob", turn);
+            assert_eq!(ack.round_complete, turn == 3, "turn {turn}");
+            let phase = if turn == 3 { BattlePhase::AwaitingExecution } else { BattlePhase::SubmittingTurns };
+            assert_eq!(state.timing.get().as_ref().map(|timing| timing.phase), Some(phase));
+        }
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 4), TurnAck::rejected("invalid_turn"));
+    }
+
+    #[test]
+    fn overtime_escalates_until_someone_drops() {
+        let overtime = BattleRules { max_rounds: 1, sudden_death_rounds: 20, sudden_death_step_bps: 10_000, ..BattleRules::default() };
+        assert_eq!([1, 2, 3, 4].map(|round| overtime.damage_bps(round)), [10_000, 20_000, 30_000, 40_000]);
+
+        // Without overtime the round cap goes to the tiebreak
+        let (mut state, mut runtime) = setup_with_rules(300, BattleRules { max_rounds: 1, ..BattleRules::default() });
+        play_out(&mut state, &mut runtime);
+        assert!(matches!(*state.end_reason.get(), Some(BattleEndReason::MaxRoundsTiebreak { .. })));
+
+        let (mut state, mut runtime) = setup_with_rules(300, overtime.clone());
+        play_out(&mut state, &mut runtime);
+        assert_eq!(*state.end_reason.get(), Some(BattleEndReason::Knockout));
+        let round = *state.current_round.get();
+        assert!(round > 1 && round <= overtime.last_round().unwrap(), "ended in round {round}");
+    }
+
+    #[test]
+    fn repeated_wrong_round_turns_are_throttled() {
+        let (mut state, mut runtime) = setup(1_000);
+        let key = RejectionKey::new("SubmitTurn", "wrong_round", owner("alice"));
+        let wrong_round = |state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>| {
+            runtime.set_authenticated_signer(Some(owner("alice")));
+            handle_battle_operation(Operation::SubmitTurn {
+                round: 7,
+                turn: 0,
+                stance: "Aggressive".to_string(),
+                use_special: false,
+                emote: None,
+            }, state, runtime).blocking_wait();
+        };
+
+        for _ in 0..50 {
+            wrong_round(&mut state, &mut runtime);
+        }
+        assert_eq!(state.rejections.get(&key).blocking_wait().unwrap().unwrap().count, 50);
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 1);
+
+        submit(&mut state, &mut runtime, "alice", 5);
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 2);
+
+        for _ in 50..BACKOFF_THRESHOLD {
+            wrong_round(&mut state, &mut runtime);
+        }
+        state.save().blocking_wait().expect("Failed to save battle state");
+        wrong_round(&mut state, &mut runtime);
+        assert!(!state.has_pending_changes().blocking_wait());
+        assert_eq!(state.rejections.get(&key).blocking_wait().unwrap().unwrap().count, BACKOFF_THRESHOLD);
+    }
+
+    #[test]
+    fn every_class_has_at_least_three_stances() {
+        for class in majorules::CharacterClass::ALL {
+            assert!(majorules::allowed_stances(class).len() >= 3, "{class:?}");
+        }
+    }
+
+    #[test]
+    fn class_locked_stances_follow_the_rules_stamped_at_initialization() {
+        let casual = BattleRules { class_locked_stances: false, ..BattleRules::default() };
+        for (rules, accepted) in [(BattleRules::default(), false), (casual, true)] {
+            let (mut state, mut runtime) = setup_with_rules(1_000, rules);
+            let mut tank = state.player1.get().clone().unwrap();
+            tank.character.class = crate::state::CharacterClass::Tank;
+            state.player1.set(Some(tank));
+
+            runtime.set_authenticated_signer(Some(owner("alice")));
+            handle_battle_operation(Operation::SubmitTurn {
+                round: 1,
+                turn: 0,
+                stance: "Berserker".to_string(),
+                use_special: false,
+                emote: None,
+            }, &mut state, &mut runtime).blocking_wait();
+
+            let submitted = state.turn_submissions.contains_key(&(owner("alice"), 0)).blocking_wait().unwrap();
+            let rejected = state.rejections
+                .contains_key(&RejectionKey::new("SubmitTurn", "stance_not_allowed", owner("alice")))
+                .blocking_wait()
+                .unwrap();
+            assert_eq!((submitted, rejected), (accepted, !accepted));
         }
     }
 
-    (winner_stats, loser_stats)
-}
\ No newline at end of file
+    #[test]
+    fn timing_tracks_phase_transitions() {
+        let (mut state, mut runtime) = setup(1_000);
+        let mut last_write = Timestamp::from(0);
+        let mut expect_timing = |state: &BattleState, round: u8, phase: BattlePhase, deadline: Timestamp| {
+            let timing = state.timing.get().clone().expect("Timing should be set");
+            assert_eq!((timing.current_round, timing.phase, timing.phase_deadline), (round, phase, Some(deadline)));
+            assert!(timing.server_time_at_write >= last_write);
+            last_write = timing.server_time_at_write;
+        };
+
+        expect_timing(&state, 1, BattlePhase::SubmittingTurns, at_secs(120));
+
+        let mut now = 0;
+        for turn in 0..3 {
+            for player in ["alice", "bob"] {
+                now += 5;
+                runtime.set_system_time(at_secs(now));
+                let ack = submit(&mut state, &mut runtime, player, turn);
+                assert_eq!((ack.phase_deadline, ack.server_time), (Some(at_secs(120)), at_secs(now)));
+                let phase = if turn == 2 && player == "bob" {
+                    BattlePhase::AwaitingExecution
+                } else {
+                    BattlePhase::SubmittingTurns
+                };
+                expect_timing(&state, 1, phase, at_secs(120));
+                assert_eq!(state.timing.get().as_ref().unwrap().server_time_at_write, at_secs(now));
+            }
+        }
+
+        runtime.set_system_time(at_secs(40));
+        runtime.set_authenticated_signer(Some(owner("alice")));
+        handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        expect_timing(&state, 1, BattlePhase::AwaitingExecution, at_secs(120));
+
+        runtime.set_system_time(at_secs(45));
+        runtime.set_authenticated_signer(Some(owner("bob")));
+        handle_battle_operation(Operation::ExecuteRound, &mut state, &mut runtime).blocking_wait();
+        expect_timing(&state, 2, BattlePhase::SubmittingTurns, at_secs(165));
+    }
+
+    #[test]
+    fn battles_are_opened_only_by_the_lobby() {
+        let mut runtime = ContractRuntime::new()
+            .with_chain_id(chain("battle"))
+            .with_block_height(BlockHeight(1))
+            .with_system_time(Timestamp::from(0));
+        let mut state = BattleState::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        runtime.set_message_origin_chain_id(chain("mallory"));
+
+        handle_battle_message(Message::InitializeBattle {
+            player1: Box::new(participant("alice", 200)),
+            player2: Box::new(participant("bob", 200)),
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        }, &mut state, &mut runtime).blocking_wait();
+        handle_battle_message(Message::InitializeTeamBattle {
+            teams: [vec![participant("alice", 200)], vec![participant("bob", 200)]],
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        }, &mut state, &mut runtime).blocking_wait();
+
+        assert!(state.roster.get().is_none() && state.team_roster.get().is_none());
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 0), TurnAck::rejected("not_a_participant"));
+    }
+
+    /// Alice as `class` and Bob as a Warrior, straight out of a fresh battle
+    fn fighters(class: state::CharacterClass) -> (state::BattleParticipant, state::BattleParticipant) {
+        let (state, _runtime) = setup(200);
+        let mut attacker = state.player1.get().clone().unwrap();
+        attacker.character.class = class;
+        (attacker, state.player2.get().clone().unwrap())
+    }
+
+    /// One balanced attack rolled from `seed`
+    fn strike(
+        seed: u8,
+        attacker: &mut state::BattleParticipant,
+        defender: &mut state::BattleParticipant,
+        use_special: bool,
+    ) -> CombatAction {
+        let turn = TurnSubmission { round: 1, turn: 0, stance: state::Stance::Balanced, use_special, salt: None, target: None, emote: None };
+        let rolls = AttackRolls::new([seed; 32], 0);
+        execute_attack(rolls, &mut 0, &BattleRules::default(), 1, attacker, defender, &turn, state::Stance::Balanced).unwrap().0
+    }
+
+    /// One attack without specials rolled from `seed` under `rules`, each fighter in the given
+    /// stance, and the counter it drew
+    fn exchange(
+        seed: u8,
+        rules: &BattleRules,
+        attacker: &mut state::BattleParticipant,
+        defender: &mut state::BattleParticipant,
+        stance: state::Stance,
+        defender_stance: state::Stance,
+    ) -> (CombatAction, Option<CombatAction>) {
+        let turn = TurnSubmission { round: 1, turn: 0, stance, use_special: false, salt: None, target: None, emote: None };
+        let rolls = AttackRolls::new([seed; 32], 0);
+        execute_attack(rolls, &mut 0, rules, 1, attacker, defender, &turn, defender_stance).unwrap()
+    }
+
+    #[test]
+    fn each_class_special_restarts_its_own_cooldown() {
+        for class in majorules::CharacterClass::ALL {
+            let (mut attacker, mut defender) = fighters(class.into());
+            let action = strike(0, &mut attacker, &mut defender, true);
+            assert_eq!(action.special, Some(class.special()));
+            // The attack itself ticks the fresh cooldown once
+            assert_eq!(attacker.special_cooldown, BattleRules::default().special_cooldown_for(class) - 1);
+        }
+        let slow = BattleRules { special_cooldown: 5, ..BattleRules::default() };
+        assert_eq!(slow.special_cooldown_for(CharacterClass::Trickster), 4);
+        assert_eq!(BattleRules { special_cooldown: 1, ..slow }.special_cooldown_for(CharacterClass::Trickster), 1);
+    }
+
+    #[test]
+    fn assassinations_always_crit_and_armor_pierce_ignores_defense() {
+        for seed in 0..50 {
+            let (mut assassin, mut defender) = fighters(state::CharacterClass::Assassin);
+            assert!(strike(seed, &mut assassin, &mut defender, true).was_crit, "seed {seed}");
+
+            let (mut warrior, mut armored) = fighters(state::CharacterClass::Warrior);
+            armored.character.defense = 50;
+            armored.character.dodge_chance = Bps(0);
+            let plain = strike(seed, &mut warrior.clone(), &mut armored.clone(), false);
+            let pierce = strike(seed, &mut warrior, &mut armored, true);
+            // Half the damage of the plain hit is lost to armor, none of the pierce's
+            assert!(pierce.damage > plain.damage * 2, "seed {seed}: {} vs {}", pierce.damage, plain.damage);
+        }
+    }
+
+    #[test]
+    fn bulwark_shields_absorb_damage_before_hp() {
+        let (mut tank, mut warrior) = fighters(state::CharacterClass::Tank);
+        tank.character.dodge_chance = Bps(0);
+        strike(0, &mut tank, &mut warrior, true);
+        assert_eq!(tank.effect(StatusEffect::Shield).map(|shield| (shield.magnitude, shield.turns_left)), Some((40, 3)));
+
+        let hp = tank.current_hp;
+        let hit = strike(1, &mut warrior, &mut tank, false);
+        assert_eq!(hit.absorbed, hit.damage.min(40));
+        assert_eq!(tank.effect(StatusEffect::Shield).map_or(0, |shield| shield.magnitude), 40 - hit.absorbed);
+        assert_eq!(tank.current_hp, hp - (hit.damage - hit.absorbed));
+    }
+
+    #[test]
+    fn ignite_burns_the_target_for_two_turns_and_defending_halves_it() {
+        let (mut mage, mut target) = fighters(state::CharacterClass::Mage);
+        target.character.dodge_chance = Bps(0);
+        let hit = strike(0, &mut mage, &mut target, true);
+        assert_eq!(hit.effects, [StatusEffect::Burn]);
+        let burn = *target.effect(StatusEffect::Burn).unwrap();
+        assert_eq!((burn.magnitude, burn.turns_left, burn.source), ((hit.damage / 5).max(1), 2, owner("alice")));
+
+        let hp = target.current_hp;
+        let mut actions = Vec::new();
+        assert!(!tick_effects(&mut target, &mage, state::Stance::Balanced, &mut actions));
+        assert!(!tick_effects(&mut target, &mage, state::Stance::Defensive, &mut actions));
+        assert!(!tick_effects(&mut target, &mage, state::Stance::Balanced, &mut actions));
+        let ticks: Vec<_> = actions.iter().map(|tick| (tick.attacker, tick.damage, tick.damage_over_time)).collect();
+        assert_eq!(ticks, [(owner("alice"), burn.magnitude, true), (owner("alice"), burn.magnitude / 2, true)]);
+        assert_eq!(target.current_hp, hp - burn.magnitude - burn.magnitude / 2);
+        assert!(target.effects.is_empty());
+    }
+
+    #[test]
+    fn berserker_crits_bleed_and_landed_counters_stun() {
+        let rules = BattleRules::default();
+        let (mut crits, mut counters) = (0, 0);
+        for seed in 0..100 {
+            let (mut berserker, mut defender) = fighters(state::CharacterClass::Warrior);
+            defender.character.dodge_chance = Bps(0);
+            let (hit, _) = exchange(seed, &rules, &mut berserker, &mut defender, state::Stance::Berserker, state::Stance::Balanced);
+            assert_eq!(defender.effect(StatusEffect::Bleed).is_some(), hit.was_crit, "seed {seed}");
+            crits += hit.was_crit as u32;
+
+            let (mut attacker, mut counter) = fighters(state::CharacterClass::Warrior);
+            counter.character.dodge_chance = Bps(0);
+            let hp = attacker.current_hp;
+            let (hit, answer) = exchange(seed, &rules, &mut attacker, &mut counter, state::Stance::Balanced, state::Stance::Counter);
+            let stun = attacker.effect(StatusEffect::Stun).copied();
+            assert_eq!(stun.map(|stun| (stun.turns_left, stun.source)), hit.was_countered.then_some((1, owner("bob"))));
+            assert_eq!(answer.is_some(), hit.was_countered);
+            assert!(!hit.effects.contains(&StatusEffect::Stun));
+            if let Some(answer) = answer {
+                counters += 1;
+                // The counter is its own action, Bob's answer to Alice's attack
+                assert!(answer.counter && !hit.counter);
+                assert_eq!((answer.attacker, answer.defender, &answer.effects[..]), (owner("bob"), owner("alice"), &[StatusEffect::Stun][..]));
+                assert_eq!(answer.damage, majorules::apply_bps_u32(hit.damage, 4_000));
+                assert_eq!(answer.defender_hp_remaining, hp - answer.damage);
+                let mut actions = Vec::new();
+                assert!(tick_effects(&mut attacker, &counter, state::Stance::Balanced, &mut actions));
+                assert_eq!((actions[0].attacker, actions[0].damage, &actions[0].effects[..]), (owner("alice"), 0, &[StatusEffect::Stun][..]));
+                assert!(!tick_effects(&mut attacker, &counter, state::Stance::Balanced, &mut actions));
+            }
+        }
+        assert!(crits > 0 && counters > 0, "{crits} crits, {counters} counters");
+    }
+
+    #[test]
+    fn counter_chance_and_share_follow_the_battle_rules() {
+        let never = BattleRules { counter_chance: Bps(0), ..BattleRules::default() };
+        let always = BattleRules { counter_chance: Bps(Bps::ROLLS as u16), counter_damage_bps: 10_000, ..BattleRules::default() };
+        for seed in 0..50 {
+            let (mut attacker, mut counter) = fighters(state::CharacterClass::Warrior);
+            counter.character.dodge_chance = Bps(0);
+            let (hit, answer) = exchange(seed, &never, &mut attacker.clone(), &mut counter.clone(), state::Stance::Balanced, state::Stance::Counter);
+            assert!(!hit.was_countered && answer.is_none());
+
+            let (hit, answer) = exchange(seed, &always, &mut attacker, &mut counter, state::Stance::Balanced, state::Stance::Counter);
+            assert!(hit.was_countered);
+            assert_eq!(answer.map(|answer| answer.damage), Some(hit.damage));
+            // The same seed draws the same counter
+            let (again, _) = exchange(seed, &always, &mut fighters(state::CharacterClass::Warrior).0, &mut counter.clone(), state::Stance::Balanced, state::Stance::Counter);
+            assert_eq!(again.was_countered, hit.was_countered);
+        }
+    }
+
+    #[test]
+    fn vanish_covers_only_the_next_incoming_attack() {
+        let mut dodges = [0, 0];
+        for seed in 0..100 {
+            let (mut trickster, mut warrior) = fighters(state::CharacterClass::Trickster);
+            dodges[0] += strike(seed, &mut warrior.clone(), &mut trickster.clone(), false).was_dodged as u32;
+            strike(seed, &mut trickster, &mut warrior, true);
+            assert_eq!(trickster.effect(StatusEffect::Evasion).map(|evasion| evasion.magnitude), Some(5_000));
+            dodges[1] += strike(seed, &mut warrior, &mut trickster, false).was_dodged as u32;
+            assert!(trickster.effect(StatusEffect::Evasion).is_none());
+        }
+        // A 5% dodge chance against 55% with the boost
+        assert!(dodges[0] < 20 && dodges[1] > 35, "{dodges:?}");
+    }
+
+    #[test]
+    fn effects_tick_into_the_round_history() {
+        // The turn helpers fight Aggressive, which Mages cannot under class-locked stances
+        let rules = BattleRules { class_locked_stances: false, ..BattleRules::default() };
+        let (mut state, mut runtime) = setup_with_rules(1_000, rules);
+        let mut mage = state.player1.get().clone().unwrap();
+        mage.character.class = state::CharacterClass::Mage;
+        state.player1.set(Some(mage));
+        let mut target = state.player2.get().clone().unwrap();
+        target.character.dodge_chance = Bps(0);
+        target.apply_effect(ActiveEffect { effect: StatusEffect::Stun, magnitude: 0, turns_left: 1, source: owner("alice") });
+        state.player2.set(Some(target));
+
+        assert!(submit_with_special(&mut state, &mut runtime, "alice", 1, 0, true).accepted);
+        submit(&mut state, &mut runtime, "bob", 0);
+        let round = state.round_results.get(&1).blocking_wait().unwrap().unwrap();
+        // Bob sat the turn out stunned, and burns from Alice's Ignite
+        assert_eq!(round.player2_actions.len(), 1);
+        assert_eq!((round.player2_actions[0].damage, &round.player2_actions[0].effects[..]), (0, &[StatusEffect::Stun][..]));
+        assert_eq!(round.player1_hp, 1_000);
+        assert_eq!(round.player2_effects.iter().map(|active| active.effect).collect::<Vec<_>>(), [StatusEffect::Burn]);
+
+        for turn in 1..3 {
+            submit(&mut state, &mut runtime, "alice", turn);
+            submit(&mut state, &mut runtime, "bob", turn);
+        }
+        let round = state.round_results.get(&1).blocking_wait().unwrap().unwrap();
+        let burns: Vec<_> = round.player1_actions.iter().filter(|action| action.damage_over_time).collect();
+        assert_eq!(burns.len(), 2);
+        assert!(burns.iter().all(|burn| burn.defender == owner("bob") && burn.effects == [StatusEffect::Burn]));
+        assert_eq!(round.player1_actions.len(), 5);
+        assert!(round.player2_effects.is_empty());
+    }
+
+    #[test]
+    fn combo_state_machine_transitions() {
+        let (state, _runtime) = setup(1_000);
+        let mut fighter = state.player1.get().clone().unwrap();
+        let mut expect = |was_crit, was_dodged, stack: u8, longest: u8| {
+            fighter.record_hit(was_crit, was_dodged);
+            assert_eq!((fighter.combo_stack, fighter.longest_combo), (stack, longest));
+            assert_eq!(fighter.combo_multiplier_bps(), 10_000 + stack as u32 * 500);
+        };
+
+        // Plain hits neither start nor break a combo
+        expect(false, false, 0, 0);
+        // First crit starts the combo, further crits continue it
+        expect(true, false, 1, 1);
+        expect(false, false, 1, 1);
+        expect(true, false, 2, 2);
+        // The stack is capped
+        expect(true, false, 3, 3);
+        expect(true, false, 4, 4);
+        expect(true, false, 5, 5);
+        expect(true, false, 5, 5);
+        // A dodge breaks the combo, even when the attack rolled a crit
+        expect(true, true, 0, 5);
+        expect(false, true, 0, 5);
+        // Combos restart from one
+        expect(true, false, 1, 5);
+    }
+
+    fn setup_teams(hp_max: u32) -> (BattleState, ContractRuntime<crate::MajorulesContract>) {
+        setup_with_message(Message::InitializeTeamBattle {
+            teams: [
+                vec![participant("alice", hp_max), participant("carol", hp_max)],
+                vec![participant("bob", hp_max), participant("dave", hp_max)],
+            ],
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        })
+    }
+
+    fn submit_team(state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8, target: u8) -> TurnAck {
+        submit_team_with_emote(state, runtime, player, turn, target, None)
+    }
+
+    fn submit_team_with_emote(
+        state: &mut BattleState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        turn: u8,
+        target: u8,
+        emote: Option<Emote>,
+    ) -> TurnAck {
+        runtime.set_authenticated_signer(Some(owner(player)));
+        let round = *state.current_round.get();
+        let choice = TeamTurnChoice { stance: "Aggressive".to_string(), use_special: false, target, emote };
+        let operation = Operation::SubmitTeamTurn { round, turn, choice };
+        match handle_battle_operation(operation, state, runtime).blocking_wait() {
+            OperationResponse::TurnAck(ack) => ack,
+            other => panic!("Expected a turn acknowledgement, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn team_battles_refuse_rosters_they_cannot_seat() {
+        let (mut state, mut runtime) = setup_with_message(Message::InitializeTeamBattle {
+            teams: [vec![participant("alice", 200)], vec![participant("bob", 200), participant("alice", 200)]],
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        });
+
+        assert!(state.team_roster.get().is_none());
+        assert_ne!(*state.status.get(), BattleStatus::InProgress);
+        runtime.set_authenticated_signer(Some(owner("alice")));
+        let choice = TeamTurnChoice { stance: "Aggressive".to_string(), use_special: false, target: 0, emote: None };
+        let operation = Operation::SubmitTeamTurn { round: 1, turn: 0, choice };
+        let response = handle_battle_operation(operation, &mut state, &mut runtime).blocking_wait();
+        assert_eq!(response, OperationResponse::rejected("not_a_participant"));
+    }
+
+    #[test]
+    fn team_battles_whose_fighters_changed_refuse_every_move() {
+        let (mut state, mut runtime) = setup_teams(200);
+        let mut teams = state.teams.get().clone();
+        teams[1][1].chain = chain("mallory");
+        state.teams.set(teams);
+
+        assert_eq!(submit_team(&mut state, &mut runtime, "alice", 0, 0), TurnAck::rejected("roster_mismatch"));
+        assert!(state.rejections.contains_key(&RejectionKey::new("SubmitTeamTurn", "roster_mismatch", owner("alice"))).blocking_wait().unwrap());
+
+        finalize_team_battle(&mut state, &mut runtime, 0, BattleEndReason::Knockout).blocking_wait();
+        assert_eq!((*state.status.get(), *state.winner.get()), (BattleStatus::InProgress, None));
+        assert!(runtime.created_send_message_requests().is_empty());
+    }
+
+    #[test]
+    fn team_turns_resolve_once_every_standing_fighter_is_in() {
+        let (mut state, mut runtime) = setup_teams(200);
+
+        assert_eq!(submit_team(&mut state, &mut runtime, "erin", 0, 0), TurnAck::rejected("not_a_participant"));
+        assert_eq!(submit_team(&mut state, &mut runtime, "alice", 0, 2), TurnAck::rejected("invalid_target"));
+        assert_eq!(submit(&mut state, &mut runtime, "alice", 0), TurnAck::rejected("not_in_team_battles"));
+        let (mut one_on_one, mut one_on_one_runtime) = setup(60);
+        one_on_one_runtime.set_authenticated_signer(Some(owner("alice")));
+        let choice = TeamTurnChoice { stance: "Aggressive".to_string(), use_special: false, target: 0, emote: None };
+        let operation = Operation::SubmitTeamTurn { round: 1, turn: 0, choice };
+        let refused = handle_battle_operation(operation, &mut one_on_one, &mut one_on_one_runtime).blocking_wait();
+        assert_eq!(refused, OperationResponse::rejected("not_a_team_battle"));
+
+        // Later turns wait on turn 0, which waits on dave
+        assert!(!submit_team_with_emote(&mut state, &mut runtime, "alice", 0, 0, Some(Emote::GoodLuck)).executed);
+        for player in ["carol", "bob"] {
+            assert!(!submit_team(&mut state, &mut runtime, player, 0, 0).executed);
+        }
+        for turn in 1..TURNS_PER_ROUND {
+            for player in ["alice", "carol", "bob", "dave"] {
+                assert!(!submit_team(&mut state, &mut runtime, player, turn, 1).executed);
+            }
+        }
+        let ack = submit_team(&mut state, &mut runtime, "dave", 0, 1);
+        assert!(ack.executed && ack.round_complete);
+        assert_eq!(*state.current_round.get(), 2);
+
+        // Every turn resolved into the round history, each side's attacks under its player slot
+        let round = state.round_results.get(&1).blocking_wait().unwrap().expect("Round 1 should be recorded");
+        let side = |action: &CombatAction| [owner("alice"), owner("carol")].contains(&action.attacker);
+        assert!(round.player1_actions.iter().all(side) && round.player2_actions.iter().all(|action| !side(action)));
+        let teams = state.teams.get();
+        assert_eq!(round.player1_hp, teams[0].iter().map(|fighter| fighter.current_hp).sum::<u32>());
+        assert_eq!(round.player2_hp, teams[1].iter().map(|fighter| fighter.current_hp).sum::<u32>());
+        assert!(round.player1_hp < 400 && round.player2_hp < 400);
+        assert_eq!(round.emotes, [TurnEmote { turn: 0, player: owner("alice"), emote: Emote::GoodLuck }]);
+    }
+
+    #[test]
+    fn team_fighters_left_waiting_past_the_deadline_win_by_forfeit() {
+        let (mut state, mut runtime) = setup_teams(200);
+        for player in ["alice", "carol", "bob"] {
+            submit_team(&mut state, &mut runtime, player, 0, 0);
+        }
+        let claim = |state: &mut BattleState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, turn: u8| {
+            runtime.set_authenticated_signer(Some(owner(player)));
+            handle_battle_operation(Operation::ClaimForfeit { round: 1, turn }, state, runtime).blocking_wait()
+        };
+
+        assert_eq!(claim(&mut state, &mut runtime, "alice", 0), OperationResponse::rejected("round_open"));
+        let deadline = state.round_deadline.get().unwrap();
+        runtime.set_system_time(deadline.saturating_add(TimeDelta::from_secs(1)));
+        assert_eq!(claim(&mut state, &mut runtime, "bob", 0), OperationResponse::rejected("not_submitted"));
+        assert_eq!(claim(&mut state, &mut runtime, "alice", 1), OperationResponse::rejected("turn_not_pending"));
+        let hints = battle_actions(&state, owner("carol"), runtime.system_time()).blocking_wait();
+        assert!(hints.contains(&BattleAction::CanClaimForfeit(CanClaimForfeit { round: 1, turn: 0 })));
+
+        assert_eq!(claim(&mut state, &mut runtime, "carol", 0), OperationResponse::Done);
+        assert_eq!(*state.status.get(), BattleStatus::Completed);
+        assert_eq!((*state.winner.get(), *state.end_reason.get()), (Some(owner("alice")), Some(BattleEndReason::Forfeit)));
+        let winners: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match &request.message {
+                Message::TeamBattleCompleted { results, .. } => Some(results.iter().filter(|result| result.won).map(|result| result.player).collect::<Vec<_>>()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(winners, [vec![owner("alice"), owner("carol")]]);
+    }
+
+    #[test]
+    fn team_battles_split_the_pot_and_xp_among_the_winning_side() {
+        // Bob and Dave drop at the first hit they take, so Alice and Carol win
+        let staked = |name: &str, hp_max: u32, tokens: u128| BattleParticipant { stake: Amount::from_tokens(tokens), ..participant(name, hp_max) };
+        let (mut state, mut runtime) = setup_with_message(Message::InitializeTeamBattle {
+            teams: [
+                vec![staked("alice", 1_000, 3), staked("carol", 1_000, 1)],
+                vec![staked("bob", 1, 1), staked("dave", 1, 1)],
+            ],
+            lobby_chain_id: chain("lobby"),
+            platform_fee_bps: 300,
+            treasury_owner: owner("treasury"),
+            rules: BattleRules::default(),
+            rules_version: 1,
+        });
+
+        while *state.status.get() == BattleStatus::InProgress {
+            let round = *state.current_round.get();
+            for turn in 0..state.turns_per_round() {
+                for (player, side) in [("alice", 0), ("bob", 1), ("carol", 0), ("dave", 1)] {
+                    let teams = state.teams.get();
+                    let standing = teams[side].iter().any(|fighter| fighter.owner == owner(player) && fighter.current_hp > 0);
+                    let target = teams[1 - side].iter().position(|fighter| fighter.current_hp > 0);
+                    let live = *state.current_round.get() == round && *state.status.get() == BattleStatus::InProgress;
+                    if let (true, Some(target), true) = (standing, target, live) {
+                        assert!(submit_team(&mut state, &mut runtime, player, turn, target as u8).accepted);
+                    }
+                }
+            }
+        }
+        assert_eq!(*state.status.get(), BattleStatus::Completed);
+        assert_eq!(*state.winner.get(), Some(owner("alice")));
+
+        // Everyone's result travels in one completion
+        let results: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match &request.message {
+                Message::TeamBattleCompleted { results, total_stake, .. } => {
+                    assert_eq!(*total_stake, Amount::from_tokens(6));
+                    Some(results.clone())
+                }
+                Message::BattleCompleted { .. } => panic!("Team battles complete as team battles"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 1);
+        let results = &results[0];
+        assert_eq!(results.iter().map(|result| result.player).collect::<Vec<_>>(), ["alice", "carol", "bob", "dave"].map(owner));
+        assert_eq!(results.iter().map(|result| result.won).collect::<Vec<_>>(), [true, true, false, false]);
+
+        // The winners split the pot three to one by stake, the remainder to the larger share
+        let pot = majorules::fees::FeeBreakdown::compute(Amount::from_tokens(6), 300).winner_payout;
+        let quarter = Amount::from_attos(u128::from(pot) / 4);
+        let payouts: Vec<_> = results.iter().map(|result| result.payout).collect();
+        assert_eq!(payouts, [pot.saturating_sub(quarter), quarter, Amount::ZERO, Amount::ZERO]);
+
+        // Each side splits one fighter's XP the same way; the losers staked alike and share evenly
+        let rules = BattleRules::default();
+        let (won, lost) = (rules.awarded_xp(true), rules.awarded_xp(false));
+        let xp: Vec<_> = results.iter().map(|result| result.xp_gained).collect();
+        assert_eq!(xp, [won - won / 4, won / 4, lost - lost / 2, lost / 2]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Betting limits.
+//!
+//! The lobby caps what one bettor holds on a market and what a whole market pools, so no
+//! single whale can swing a market's odds unopposed and no market grows past what the platform
+//! is willing to settle. A bet that would cross either cap is refused whole.
+
+use async_graphql::{InputObject, SimpleObject};
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Most a bettor and a market may hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "BettingLimitsInput")]
+pub struct BettingLimits {
+    /// Most one bettor stakes on one market, top-ups included
+    pub max_stake_per_bettor: Amount,
+    /// Most one market pools across both sides
+    pub max_market_pool: Amount,
+}
+
+impl Default for BettingLimits {
+    fn default() -> Self {
+        Self {
+            max_stake_per_bettor: Amount::from_tokens(100),
+            max_market_pool: Amount::from_tokens(10_000),
+        }
+    }
+}
+
+impl BettingLimits {
+    /// Whether the limits can be met at all
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.max_stake_per_bettor == Amount::ZERO || self.max_market_pool == Amount::ZERO {
+            return Err("invalid_limits");
+        }
+        Ok(())
+    }
+
+    /// Whether a bettor may hold `stake` in a market pooling `pool`, both counting the new bet
+    pub fn check(&self, stake: Amount, pool: Amount) -> Result<(), &'static str> {
+        if stake > self.max_stake_per_bettor {
+            return Err("bet_limit_exceeded");
+        }
+        if pool > self.max_market_pool {
+            return Err("market_limit_reached");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::BettingLimits;
+
+    #[test]
+    fn bets_crossing_either_cap_are_refused() {
+        let tokens = Amount::from_tokens;
+        let limits = BettingLimits { max_stake_per_bettor: tokens(10), max_market_pool: tokens(50) };
+        assert_eq!(limits.check(tokens(10), tokens(50)), Ok(()));
+        assert_eq!(limits.check(tokens(11), tokens(20)), Err("bet_limit_exceeded"));
+        assert_eq!(limits.check(tokens(5), tokens(51)), Err("market_limit_reached"));
+        assert_eq!(BettingLimits { max_market_pool: Amount::ZERO, ..limits }.validate(), Err("invalid_limits"));
+        assert_eq!(BettingLimits::default().validate(), Ok(()));
+    }
+}
@@ -0,0 +1,141 @@
+use async_graphql::{Enum, InputObject, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{fnv1a, splitmix64};
+
+/// How entrants are ordered into seeds before round one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum Seeding {
+    /// Shuffle deterministically from the tournament id and start time
+    #[default]
+    Random,
+    /// Highest level is seed 1; ties keep registration order
+    ByLevel,
+}
+
+/// Bracket options chosen when a tournament is created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "BracketOptionsInput")]
+pub struct BracketOptions {
+    /// Refuse to start unless the entrant count is a power of two, instead of handing out byes
+    pub require_power_of_two: bool,
+    pub seeding: Seeding,
+}
+
+/// A registered fighter as the bracket sees it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct Entrant {
+    pub owner: AccountOwner,
+    pub level: u16,
+}
+
+/// A round-one match; `low` is `None` when `high` has a bye
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct Pairing {
+    pub high_seed: u32,
+    pub high: AccountOwner,
+    pub low_seed: u32,
+    pub low: Option<AccountOwner>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BracketError {
+    TooFewEntrants,
+    NotPowerOfTwo { entrants: usize },
+}
+
+/// Seed `entrants` and pair seed `i` against seed `size + 1 - i`, where `size` is the
+/// entrant count rounded up to a power of two. Missing low seeds are byes for the top seeds.
+pub fn round_one(
+    mut entrants: Vec<Entrant>,
+    options: BracketOptions,
+    tournament_id: u64,
+    start_time: Timestamp,
+) -> Result<Vec<Pairing>, BracketError> {
+    if entrants.len() < 2 {
+        return Err(BracketError::TooFewEntrants);
+    }
+    if options.require_power_of_two && !entrants.len().is_power_of_two() {
+        return Err(BracketError::NotPowerOfTwo { entrants: entrants.len() });
+    }
+
+    match options.seeding {
+        Seeding::ByLevel => entrants.sort_by(|a, b| b.level.cmp(&a.level)),
+        Seeding::Random => {
+            let mut seed = fnv1a(&[tournament_id.to_le_bytes(), start_time.micros().to_le_bytes()].concat());
+            for i in (1..entrants.len()).rev() {
+                let j = (splitmix64(&mut seed) % (i as u64 + 1)) as usize;
+                entrants.swap(i, j);
+            }
+        }
+    }
+
+    let size = entrants.len().next_power_of_two();
+    Ok((0..size / 2)
+        .map(|i| Pairing {
+            high_seed: i as u32 + 1,
+            high: entrants[i].owner,
+            low_seed: (size - i) as u32,
+            low: entrants.get(size - 1 - i).map(|entrant| entrant.owner),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{AccountOwner, CryptoHash, Timestamp};
+
+    use super::{round_one, BracketError, BracketOptions, Entrant, Seeding};
+
+    fn owner(index: u16) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(format!("entrant-{index}")))
+    }
+
+    /// Entrants registered in an order unrelated to their levels
+    fn entrants(count: u16) -> Vec<Entrant> {
+        (1..=count).map(|level| Entrant { owner: owner(level), level: (level * 7) % 13 + level * 20 }).collect()
+    }
+
+    fn by_level(require_power_of_two: bool) -> BracketOptions {
+        BracketOptions { require_power_of_two, seeding: Seeding::ByLevel }
+    }
+
+    #[test]
+    fn level_seeding_pairs_top_against_bottom() {
+        let mut registered = entrants(8);
+        registered.reverse();
+
+        let pairings = round_one(registered, by_level(true), 1, Timestamp::from(0)).unwrap();
+
+        // Levels grow with the index, so entrant 8 is seed 1
+        let seeds: Vec<_> = pairings.iter().map(|pairing| (pairing.high_seed, pairing.low_seed)).collect();
+        assert_eq!(seeds, [(1, 8), (2, 7), (3, 6), (4, 5)]);
+        let owners: Vec<_> = pairings.iter().map(|pairing| (pairing.high, pairing.low.unwrap())).collect();
+        assert_eq!(owners, [(owner(8), owner(1)), (owner(7), owner(2)), (owner(6), owner(3)), (owner(5), owner(4))]);
+    }
+
+    #[test]
+    fn power_of_two_option_blocks_uneven_brackets() {
+        assert_eq!(
+            round_one(entrants(6), by_level(true), 1, Timestamp::from(0)),
+            Err(BracketError::NotPowerOfTwo { entrants: 6 }),
+        );
+
+        // Without the option the top seeds get byes
+        let pairings = round_one(entrants(6), by_level(false), 1, Timestamp::from(0)).unwrap();
+        assert_eq!(pairings.len(), 4);
+        assert_eq!(pairings.iter().filter(|pairing| pairing.low.is_none()).count(), 2);
+        assert!(pairings[0].low.is_none() && pairings[1].low.is_none());
+    }
+
+    #[test]
+    fn random_seeding_is_deterministic() {
+        let options = BracketOptions::default();
+        let start = Timestamp::from(1_000);
+
+        let first = round_one(entrants(8), options, 7, start).unwrap();
+        assert_eq!(first, round_one(entrants(8), options, 7, start).unwrap());
+        assert_ne!(first, round_one(entrants(8), options, 8, start).unwrap());
+    }
+}
@@ -1,10 +1,12 @@
 #![cfg_attr(target_arch = "wasm32", no_main)]
 
+#[cfg(test)] // Served by the service; compiled here so tests hold hints against the contract
+mod actions;
 mod state;
-mod random;
 mod battle_contract;
 mod lobby_contract;
 mod player_contract;
+mod prediction_contract;
 
 use linera_sdk::{
     linera_base_types::{WithContractAbi, Amount},
@@ -12,95 +14,193 @@ use linera_sdk::{
     Contract, ContractRuntime,
 };
 
-use majorules::{Operation, Message, InitializationArgument, ChainVariant};
+use majorules::{fees::RoundingPolicy, idcodec::IdCodec, migrations, Operation, OperationResponse, Message, InitializationArgument, ChainVariant};
 
-use self::state::{LobbyState, PlayerState, BattleState};
-use self::lobby_contract::LobbyContract;
+use self::state::{ChainMetadata, LobbyConfig, LobbyState, MetadataView, PlayerState, BattleState, PredictionState, STATE_SCHEMA_VERSION};
+use self::lobby_contract::{LobbyContract, MAINTENANCE_BUDGET};
 use self::player_contract::PlayerContract;
+use self::prediction_contract::PredictionContract;
 
 /// Multi-variant Contract - routes to appropriate chain implementation
 pub struct MajorulesContract {
     pub variant: ChainVariant,
+    pub metadata: ChainMetadata,
     pub lobby_state: Option<LobbyState>,
     pub player_state: Option<PlayerState>,
     pub battle_state: Option<BattleState>,
+    pub prediction_state: Option<PredictionState>,
     pub runtime: ContractRuntime<Self>,
 }
 
 linera_sdk::contract!(MajorulesContract);
 
+/// Dependencies asking for ambient entropy get the same zero stream on every validator;
+/// contract logic draws from `majorules::random` seeds instead
+fn deterministic_getrandom(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    buf.fill(0);
+    Ok(())
+}
+
+getrandom::register_custom_getrandom!(deterministic_getrandom);
+
 impl WithContractAbi for MajorulesContract {
     type Abi = majorules::MajorulesAbi;
 }
 
 impl MajorulesContract {
-    /// Detect chain variant from stored state
-    async fn detect_chain_variant(runtime: &ContractRuntime<Self>) -> ChainVariant {
-        // Try to load each state type and check variant field
-        if let Ok(lobby_state) = LobbyState::load(runtime.root_view_storage_context()).await {
-            let variant_str = lobby_state.variant.get();
-            if !variant_str.is_empty() {
-                match variant_str.as_str() {
-                    "Lobby" => return ChainVariant::Lobby,
-                    "Player" => return ChainVariant::Player,
-                    "Battle" => return ChainVariant::Battle,
-                    _ => {}
-                }
+    /// Read the chain's metadata on its own, before knowing which state to load
+    async fn load_metadata(runtime: &ContractRuntime<Self>) -> ChainMetadata {
+        let header = MetadataView::load(runtime.root_view_storage_context()).await.expect("Failed to load chain metadata");
+        let metadata = header.metadata.get().clone();
+        assert!(
+            metadata.schema_version <= STATE_SCHEMA_VERSION,
+            "Chain state was written by a newer build (schema {})",
+            metadata.schema_version
+        );
+        metadata
+    }
+
+    /// Bring a chain written by an older build up to `STATE_SCHEMA_VERSION`
+    async fn migrate(&mut self) {
+        if !self.metadata.is_initialized() || self.metadata.schema_version == STATE_SCHEMA_VERSION {
+            return;
+        }
+        for version in migrations::pending(self.metadata.schema_version, STATE_SCHEMA_VERSION) {
+            self.upgrade_from(version).await;
+        }
+        self.metadata.schema_version = STATE_SCHEMA_VERSION;
+        self.write_metadata();
+    }
+
+    /// Rewrite state stored at schema `version` the way `version + 1` stores it
+    async fn upgrade_from(&mut self, version: u32) {
+        // No layout has changed since versions were first recorded; once one does, this matches
+        // on `version` and upgrades the affected views, e.g. for a character gaining a field:
+        // `migrations::upgrade_map::<_, CharacterDataV1>(&mut state.characters)`
+        panic!("No migration from schema {version}");
+    }
+
+    /// Store `self.metadata` in whichever state is loaded
+    fn write_metadata(&mut self) {
+        let metadata = self.metadata.clone();
+        if let Some(ref mut state) = self.lobby_state {
+            state.metadata.set(metadata);
+        } else if let Some(ref mut state) = self.player_state {
+            state.metadata.set(metadata);
+        } else if let Some(ref mut state) = self.battle_state {
+            state.metadata.set(metadata);
+        } else if let Some(ref mut state) = self.prediction_state {
+            state.metadata.set(metadata);
+        }
+    }
+
+    /// Swap the loaded state for the one `variant` keeps in the same storage.
+    /// Chains opened by the lobby are loaded as lobbies and only learn their variant
+    /// from the `InstantiateChain` message.
+    async fn load_variant_state(&mut self, variant: &ChainVariant) {
+        let context = self.runtime.root_view_storage_context();
+        match variant {
+            ChainVariant::Lobby if self.lobby_state.is_none() => {
+                self.lobby_state = Some(LobbyState::load(context).await.expect("Failed to load lobby state"));
+                (self.player_state, self.battle_state, self.prediction_state) = (None, None, None);
             }
+            ChainVariant::Player if self.player_state.is_none() => {
+                self.player_state = Some(PlayerState::load(context).await.expect("Failed to load player state"));
+                (self.lobby_state, self.battle_state, self.prediction_state) = (None, None, None);
+            }
+            ChainVariant::Battle if self.battle_state.is_none() => {
+                self.battle_state = Some(BattleState::load(context).await.expect("Failed to load battle state"));
+                (self.lobby_state, self.player_state, self.prediction_state) = (None, None, None);
+            }
+            ChainVariant::Prediction if self.prediction_state.is_none() => {
+                self.prediction_state = Some(PredictionState::load(context).await.expect("Failed to load prediction state"));
+                (self.lobby_state, self.player_state, self.battle_state) = (None, None, None);
+            }
+            _ => {}
         }
-        
-        // Default to Lobby for uninitialized chains
-        ChainVariant::Lobby
+    }
+
+    /// Per-deployment id obfuscation key, derived from the lobby chain and instantiation time.
+    /// Both are public, so the key only makes ids opaque; see `IdCodec`
+    fn id_key(runtime: &mut ContractRuntime<Self>) -> u64 {
+        let chain_bytes = runtime.chain_id().0.as_bytes().0;
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&chain_bytes[..8]);
+        u64::from_le_bytes(key) ^ runtime.system_time().micros()
     }
 }
 
 impl Contract for MajorulesContract {
     type Message = Message;
-    type Parameters = ();
+    type Parameters = majorules::Parameters;
     type InstantiationArgument = InitializationArgument;
-    type EventValue = ();
+    type EventValue = majorules::BattleEvent;
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
-        // Try to detect chain variant from stored state
-        let variant = Self::detect_chain_variant(&runtime).await;
+        // Uninitialized chains load as lobbies until instantiated
+        let metadata = Self::load_metadata(&runtime).await;
+        let variant = metadata.variant.unwrap_or(ChainVariant::Lobby);
 
-        // Load appropriate state, defaulting to empty state for new chains
-        match variant {
+        let mut contract = match variant {
             ChainVariant::Lobby => {
                 let lobby_state = LobbyState::load(runtime.root_view_storage_context()).await.expect("Failed to load lobby state");
-                Self { variant, lobby_state: Some(lobby_state), player_state: None, battle_state: None, runtime }
+                Self { variant, metadata, lobby_state: Some(lobby_state), player_state: None, battle_state: None, prediction_state: None, runtime }
             }
             ChainVariant::Player => {
                 let player_state = PlayerState::load(runtime.root_view_storage_context()).await.expect("Failed to load player state");
-                Self { variant, lobby_state: None, player_state: Some(player_state), battle_state: None, runtime }
+                Self { variant, metadata, lobby_state: None, player_state: Some(player_state), battle_state: None, prediction_state: None, runtime }
             }
             ChainVariant::Battle => {
                 let battle_state = BattleState::load(runtime.root_view_storage_context()).await.expect("Failed to load battle state");
-                Self { variant, lobby_state: None, player_state: None, battle_state: Some(battle_state), runtime }
+                Self { variant, metadata, lobby_state: None, player_state: None, battle_state: Some(battle_state), prediction_state: None, runtime }
             }
             ChainVariant::Prediction => {
-                // Prediction markets are handled by lobby, redirect to lobby
-                let lobby_state = LobbyState::load(runtime.root_view_storage_context()).await.expect("Failed to load lobby state");
-                Self { variant: ChainVariant::Lobby, lobby_state: Some(lobby_state), player_state: None, battle_state: None, runtime }
+                let prediction_state = PredictionState::load(runtime.root_view_storage_context()).await.expect("Failed to load prediction state");
+                Self { variant, metadata, lobby_state: None, player_state: None, battle_state: None, prediction_state: Some(prediction_state), runtime }
             }
-        }
+        };
+        contract.migrate().await;
+        contract
     }
 
     async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
         self.runtime.application_parameters();
-        
-        self.variant = argument.variant.clone();
-        
+
+        // A chain is set up once; a repeated `InstantiateChain` must not wipe its state
+        if self.metadata.is_initialized() {
+            return;
+        }
+        self.load_variant_state(&argument.variant).await;
+        self.variant = argument.variant;
+        self.metadata = ChainMetadata::new(argument.variant);
+        self.write_metadata();
+
         match argument.variant {
             ChainVariant::Lobby => {
                 if let Some(ref mut state) = self.lobby_state {
-                    state.variant.set("Lobby".to_string());
                     state.value.set(0);
                     state.treasury_owner.set(argument.treasury_owner);
-                    state.platform_fee_bps.set(argument.platform_fee_bps.unwrap_or(500));
+                    state.config.set(LobbyConfig {
+                        platform_fee_bps: argument.platform_fee_bps.unwrap_or(500),
+                        max_concurrent_battles: argument.max_concurrent_battles.unwrap_or(1),
+                        public_bettors: argument.public_bettors.unwrap_or(false),
+                        matchmaking: argument.matchmaking.unwrap_or_default(),
+                        xp_curve: Default::default(),
+                        streaks: Default::default(),
+                        market_pricing: Default::default(),
+                        betting_limits: Default::default(),
+                        matchmaking_paused: false,
+                        admin: None,
+                        progression: Default::default(),
+                    });
                     state.battle_count.set(0);
                     state.total_platform_revenue.set(Amount::ZERO);
                     state.battle_token_balance.set(Amount::ZERO);
+                    state.rules_version.set(1);
+                    state.market_rounding.set(RoundingPolicy { dust_to: argument.market_dust_to.unwrap_or_default() });
+                    if argument.obfuscated_ids.unwrap_or(false) {
+                        state.id_codec.set(IdCodec::obfuscated(Self::id_key(&mut self.runtime)));
+                    }
                     state.market_count.set(0);
                     state.total_betting_volume.set(Amount::ZERO);
                     state.betting_leaderboard.set(Vec::new());
@@ -108,25 +208,22 @@ impl Contract for MajorulesContract {
             }
             ChainVariant::Player => {
                 if let Some(ref mut state) = self.player_state {
-                    state.variant.set("Player".to_string());
                     state.value.set(0);
                     state.character_count.set(0);
                     state.battle_token_balance.set(Amount::ZERO);
-                    state.in_battle.set(false);
-                    state.current_battle_chain.set(None);
+                    state.max_concurrent_battles.set(argument.max_concurrent_battles.unwrap_or(1));
                     state.last_active.set(self.runtime.system_time());
                     state.player_stats.set(crate::state::PlayerGlobalStats::default());
                 }
             }
             ChainVariant::Battle => {
                 if let Some(ref mut state) = self.battle_state {
-                    state.variant.set("Battle".to_string());
                     state.value.set(0);
                     state.status.set(crate::state::BattleStatus::WaitingForPlayers);
                     state.current_round.set(0);
                     state.max_rounds.set(10);
                     state.winner.set(None);
-                    state.round_results.set(Vec::new());
+                    state.round_results.clear();
                     state.random_counter.set(0);
                     state.lobby_chain_id.set(None);
                     state.platform_fee_bps.set(300);
@@ -136,32 +233,49 @@ impl Contract for MajorulesContract {
                 }
             }
             ChainVariant::Prediction => {
-                // Prediction markets handled by lobby, treat as lobby
+                if let Some(ref mut state) = self.prediction_state {
+                    state.value.set(0);
+                    state.market_count.set(0);
+                    state.public_bettors.set(argument.public_bettors.unwrap_or(false));
+                    state.total_volume.set(Amount::ZERO);
+                    state.total_fees_collected.set(Amount::ZERO);
+                    state.platform_fee_bps.set(argument.platform_fee_bps.unwrap_or(0));
+                    state.treasury_owner.set(argument.treasury_owner);
+                    // Set when the lobby opened this chain by message
+                    state.lobby_chain_id.set(self.runtime.message_origin_chain_id());
+                }
             }
         }
     }
 
     async fn execute_operation(&mut self, operation: Self::Operation) -> Self::Response {
+        // Each variant refuses unsigned operations itself; a chain whose state never loaded
+        // refuses everything rather than failing the block
         match self.variant {
             ChainVariant::Lobby => {
                 if let Some(ref mut state) = self.lobby_state {
-                    LobbyContract::execute_operation(state, &mut self.runtime, operation).await;
+                    let response = LobbyContract::execute_operation(state, &mut self.runtime, operation).await;
+                    LobbyContract::run_maintenance(state, &mut self.runtime, MAINTENANCE_BUDGET).await;
+                    return response;
                 }
             }
             ChainVariant::Player => {
                 if let Some(ref mut state) = self.player_state {
-                    PlayerContract::execute_operation(state, &mut self.runtime, operation).await;
+                    return PlayerContract::execute_operation(state, &mut self.runtime, operation).await;
                 }
             }
             ChainVariant::Battle => {
                 if let Some(ref mut state) = self.battle_state {
-                    battle_contract::handle_battle_operation(operation, state, &mut self.runtime).await;
+                    return battle_contract::handle_battle_operation(operation, state, &mut self.runtime).await;
                 }
             }
             ChainVariant::Prediction => {
-                // Prediction operations handled by lobby
+                if let Some(ref mut state) = self.prediction_state {
+                    return PredictionContract::execute_operation(state, &mut self.runtime, operation).await;
+                }
             }
         }
+        OperationResponse::rejected("chain_not_initialized")
     }
 
     async fn execute_message(&mut self, message: Self::Message) {
@@ -171,6 +285,11 @@ impl Contract for MajorulesContract {
                 variant,
                 treasury_owner,
                 platform_fee_bps,
+                max_concurrent_battles: None,
+                public_bettors: None,
+                obfuscated_ids: None,
+                market_dust_to: None,
+                matchmaking: None,
             };
             self.instantiate(init_arg).await;
             return;
@@ -180,6 +299,7 @@ impl Contract for MajorulesContract {
             ChainVariant::Lobby => {
                 if let Some(ref mut state) = self.lobby_state {
                     LobbyContract::execute_message(state, &mut self.runtime, message).await;
+                    LobbyContract::run_maintenance(state, &mut self.runtime, MAINTENANCE_BUDGET).await;
                 }
             }
             ChainVariant::Player => {
@@ -193,7 +313,9 @@ impl Contract for MajorulesContract {
                 }
             }
             ChainVariant::Prediction => {
-                // Prediction messages handled by lobby
+                if let Some(ref mut state) = self.prediction_state {
+                    PredictionContract::execute_message(state, &mut self.runtime, message).await;
+                }
             }
         }
     }
@@ -208,6 +330,300 @@ impl Contract for MajorulesContract {
         if let Some(mut state) = self.battle_state {
             state.save().await.expect("Failed to save battle state");
         }
+        if let Some(mut state) = self.prediction_state {
+            state.save().await.expect("Failed to save prediction state");
+        }
+
+    }
+}
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        bcs,
+        linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+        util::BlockingWait,
+        views::{linera_views, MapView, RootView, View, ViewStorageContext},
+        Contract, ContractRuntime,
+    };
+    use majorules::{
+        migrations::{upgrade_bytes, upgrade_map, Upgrade},
+        odds::MarketPricing,
+        Bps, ChainVariant, InitializationArgument, ItemRarity, Message, Parameters, STARTING_LIVES,
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::MajorulesContract;
+    use crate::state::{ChainMetadata, CharacterClass, CharacterData, Market, MarketStatus, PlayerGlobalStats};
+
+    fn owner(name: &str) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(name))
+    }
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    /// A character as stored before rarity and lives
+    #[derive(Clone, Serialize, Deserialize)]
+    struct CharacterDataV0 {
+        nft_id: String,
+        owner: AccountOwner,
+        class: CharacterClass,
+        level: u16,
+        xp: u64,
+        hp_max: u32,
+        min_damage: u16,
+        max_damage: u16,
+        crit_chance: u16,
+        crit_multiplier: u16,
+        dodge_chance: u16,
+        defense: u16,
+        attack_bps: i16,
+        defense_bps: i16,
+        crit_bps: i16,
+        created_at: Timestamp,
+        is_active: bool,
+    }
+
+    impl Upgrade for CharacterDataV0 {
+        type Next = CharacterData;
+
+        fn upgrade(self) -> CharacterData {
+            CharacterData {
+                nft_id: self.nft_id,
+                owner: self.owner,
+                class: self.class,
+                level: self.level,
+                xp: self.xp,
+                hp_max: self.hp_max,
+                min_damage: self.min_damage,
+                max_damage: self.max_damage,
+                crit_chance: Bps(self.crit_chance),
+                crit_multiplier: self.crit_multiplier,
+                dodge_chance: Bps(self.dodge_chance),
+                defense: self.defense,
+                attack_bps: self.attack_bps,
+                defense_bps: self.defense_bps,
+                crit_bps: self.crit_bps,
+                rarity: ItemRarity::Common,
+                lives_remaining: STARTING_LIVES,
+                created_at: self.created_at,
+                is_active: self.is_active,
+            }
+        }
+    }
+
+    /// Player stats as stored before draws were counted
+    #[derive(Clone, Serialize, Deserialize)]
+    struct PlayerGlobalStatsV0 {
+        total_battles: u64,
+        wins: u64,
+        losses: u64,
+        win_rate_bps: u64,
+        elo_rating: u64,
+        total_damage_dealt: u64,
+        total_damage_taken: u64,
+        total_crits: u64,
+        total_dodges: u64,
+        highest_crit: u64,
+        total_earnings: Amount,
+        current_streak: u64,
+        best_streak: u64,
+    }
+
+    impl Upgrade for PlayerGlobalStatsV0 {
+        type Next = PlayerGlobalStats;
+
+        fn upgrade(self) -> PlayerGlobalStats {
+            PlayerGlobalStats {
+                total_battles: self.total_battles,
+                wins: self.wins,
+                losses: self.losses,
+                draws: 0,
+                win_rate_bps: self.win_rate_bps,
+                elo_rating: self.elo_rating,
+                total_damage_dealt: self.total_damage_dealt,
+                total_damage_taken: self.total_damage_taken,
+                total_crits: self.total_crits,
+                total_dodges: self.total_dodges,
+                highest_crit: self.highest_crit,
+                total_earnings: self.total_earnings,
+                current_streak: self.current_streak,
+                best_streak: self.best_streak,
+            }
+        }
+    }
 
+    /// A market as stored before visibility and fixed odds
+    #[derive(Clone, Serialize, Deserialize)]
+    struct MarketV0 {
+        market_id: u64,
+        battle_chain: ChainId,
+        player1_chain: ChainId,
+        player2_chain: ChainId,
+        status: MarketStatus,
+        total_pool: Amount,
+        player1_pool: Amount,
+        player2_pool: Amount,
+        winner_chain: Option<ChainId>,
+        created_at: Timestamp,
+        closed_at: Option<Timestamp>,
+        settled_at: Option<Timestamp>,
     }
-}
\ No newline at end of file
+
+    impl Upgrade for MarketV0 {
+        type Next = Market;
+
+        fn upgrade(self) -> Market {
+            Market {
+                market_id: self.market_id,
+                battle_chain: self.battle_chain,
+                player1_chain: self.player1_chain,
+                player2_chain: self.player2_chain,
+                status: self.status,
+                total_pool: self.total_pool,
+                player1_pool: self.player1_pool,
+                player2_pool: self.player2_pool,
+                winner_chain: self.winner_chain,
+                created_at: self.created_at,
+                closed_at: self.closed_at,
+                settled_at: self.settled_at,
+                public_bettors: false,
+                pricing: MarketPricing::PariMutuel,
+                player1_locked: Amount::ZERO,
+                player2_locked: Amount::ZERO,
+            }
+        }
+    }
+
+    fn character_v0(nft_id: &str) -> CharacterDataV0 {
+        CharacterDataV0 {
+            nft_id: nft_id.to_string(),
+            owner: owner("alice"),
+            class: CharacterClass::Warrior,
+            level: 4,
+            xp: 350,
+            hp_max: 120,
+            min_damage: 10,
+            max_damage: 20,
+            crit_chance: 1000,
+            crit_multiplier: 15000,
+            dodge_chance: 500,
+            defense: 5,
+            attack_bps: 100,
+            defense_bps: -50,
+            crit_bps: 0,
+            created_at: Timestamp::from(7),
+            is_active: true,
+        }
+    }
+
+    #[derive(RootView)]
+    #[view(context = ViewStorageContext)]
+    struct CharactersBefore {
+        characters: MapView<String, CharacterDataV0>,
+    }
+
+    #[derive(RootView)]
+    #[view(context = ViewStorageContext)]
+    struct CharactersAfter {
+        characters: MapView<String, CharacterData>,
+    }
+
+    #[test]
+    fn chains_are_instantiated_once() {
+        let runtime = ContractRuntime::new()
+            .with_chain_id(chain("player"))
+            .with_application_parameters(Parameters { dev_fixtures: false })
+            .with_system_time(Timestamp::from(0));
+        let mut contract = MajorulesContract::load(runtime).blocking_wait();
+        assert!(!contract.metadata.is_initialized());
+
+        contract.instantiate(InitializationArgument {
+            variant: ChainVariant::Player,
+            treasury_owner: None,
+            platform_fee_bps: None,
+            max_concurrent_battles: Some(3),
+            public_bettors: None,
+            obfuscated_ids: None,
+            market_dust_to: None,
+            matchmaking: None,
+        }).blocking_wait();
+        contract.execute_message(Message::InstantiateChain {
+            variant: ChainVariant::Lobby,
+            treasury_owner: Some(owner("mallory")),
+            platform_fee_bps: Some(0),
+        }).blocking_wait();
+
+        assert_eq!(contract.variant, ChainVariant::Player);
+        assert!(contract.lobby_state.is_none());
+        let state = contract.player_state.as_ref().expect("Player state stays loaded");
+        assert_eq!(*state.metadata.get(), ChainMetadata::new(ChainVariant::Player));
+        assert_eq!(*state.max_concurrent_battles.get(), 3);
+    }
+    #[test]
+    fn structs_written_by_older_builds_upgrade_with_defaults() {
+        let character = upgrade_bytes::<CharacterDataV0>(&bcs::to_bytes(&character_v0("alice-1")).unwrap()).unwrap();
+        assert_eq!((character.level, character.xp, character.defense_bps), (4, 350, -50));
+        assert_eq!((character.rarity, character.lives_remaining), (ItemRarity::Common, STARTING_LIVES));
+        assert_eq!(character.created_at, Timestamp::from(7));
+
+        let stats = PlayerGlobalStatsV0 {
+            total_battles: 9,
+            wins: 5,
+            losses: 4,
+            win_rate_bps: 5_555,
+            elo_rating: 1_230,
+            total_damage_dealt: 800,
+            total_damage_taken: 640,
+            total_crits: 6,
+            total_dodges: 3,
+            highest_crit: 45,
+            total_earnings: Amount::from_tokens(12),
+            current_streak: 2,
+            best_streak: 3,
+        };
+        let bytes = bcs::to_bytes(&stats).unwrap();
+        assert!(bcs::from_bytes::<PlayerGlobalStats>(&bytes).is_err());
+        let stats = upgrade_bytes::<PlayerGlobalStatsV0>(&bytes).unwrap();
+        assert_eq!((stats.total_battles, stats.losses, stats.draws, stats.best_streak), (9, 4, 0, 3));
+        assert_eq!(stats.total_earnings, Amount::from_tokens(12));
+
+        let market = MarketV0 {
+            market_id: 3,
+            battle_chain: chain("battle"),
+            player1_chain: chain("alice"),
+            player2_chain: chain("bob"),
+            status: MarketStatus::Settled,
+            total_pool: Amount::from_tokens(5),
+            player1_pool: Amount::from_tokens(2),
+            player2_pool: Amount::from_tokens(3),
+            winner_chain: Some(chain("bob")),
+            created_at: Timestamp::from(1),
+            closed_at: Some(Timestamp::from(2)),
+            settled_at: Some(Timestamp::from(3)),
+        };
+        let market = upgrade_bytes::<MarketV0>(&bcs::to_bytes(&market).unwrap()).unwrap();
+        assert_eq!((market.market_id, market.status, market.winner_chain), (3, MarketStatus::Settled, Some(chain("bob"))));
+        assert_eq!((market.pricing, market.player2_locked, market.public_bettors), (MarketPricing::PariMutuel, Amount::ZERO, false));
+    }
+
+    #[test]
+    fn stored_maps_are_rewritten_in_place() {
+        let runtime = ContractRuntime::<MajorulesContract>::new();
+        let mut before = CharactersBefore::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        for nft_id in ["alice-1", "alice-2"] {
+            before.characters.insert(&nft_id.to_string(), character_v0(nft_id)).unwrap();
+        }
+        before.save().blocking_wait().unwrap();
+
+        let mut after = CharactersAfter::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        assert!(after.characters.get(&"alice-1".to_string()).blocking_wait().is_err());
+        assert_eq!(upgrade_map::<_, CharacterDataV0>(&mut after.characters).blocking_wait().unwrap(), 2);
+        after.save().blocking_wait().unwrap();
+
+        let reloaded = CharactersAfter::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        let character = reloaded.characters.get(&"alice-2".to_string()).blocking_wait().unwrap().unwrap();
+        assert_eq!((character.nft_id.as_str(), character.lives_remaining), ("alice-2", STARTING_LIVES));
+    }
+}
@@ -0,0 +1,185 @@
+//! Special-ability cooldown planning. The battle chain checks each special submission with
+//! the same walk the planner query runs, so an accepted plan is also accepted turn by turn.
+
+use std::fmt;
+
+/// Most specials a fighter may spend within one round
+pub const MAX_SPECIALS_PER_ROUND: u8 = 2;
+
+/// Every turn both fighters attack once, and each attack ticks both cooldowns
+const TICKS_PER_TURN: u32 = 2;
+
+/// A fighter's special state at the first turn a plan covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanStart {
+    pub round: u8,
+    pub turn: u8,
+    /// Cooldown left when the fighter next attacks; 0 means the special is ready
+    pub ready_in: u8,
+    /// Specials already spent in `round` before `turn`
+    pub specials_this_round: u8,
+}
+
+impl PlanStart {
+    /// Start from a participant's stored cooldown. The second attacker of a turn only
+    /// acts after the first attack has ticked its cooldown once.
+    pub fn live(round: u8, turn: u8, special_cooldown: u8, attacks_first: bool, specials_this_round: u8) -> Self {
+        let ready_in = if attacks_first { special_cooldown } else { special_cooldown.saturating_sub(1) };
+        Self { round, turn, ready_in, specials_this_round }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanError {
+    InvalidTurn { round: u8, turn: u8 },
+    PastTurn { round: u8, turn: u8 },
+    BeyondLastRound { round: u8, turn: u8 },
+    /// Planned uses must be listed in turn order without repeats
+    Unordered { round: u8, turn: u8 },
+    OnCooldown { round: u8, turn: u8, ready_in: u8 },
+    RoundCapExceeded { round: u8 },
+}
+
+impl PlanError {
+    /// Rejection reason recorded when a submission fails the same check
+    pub fn reason(&self) -> &'static str {
+        match self {
+            PlanError::InvalidTurn { .. } => "invalid_turn",
+            PlanError::PastTurn { .. } => "wrong_round",
+            PlanError::BeyondLastRound { .. } => "wrong_round",
+            PlanError::Unordered { .. } => "duplicate_turn",
+            PlanError::OnCooldown { .. } => "special_on_cooldown",
+            PlanError::RoundCapExceeded { .. } => "special_cap",
+        }
+    }
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::InvalidTurn { round, turn } => write!(f, "round {round} has no turn {turn}"),
+            PlanError::PastTurn { round, turn } => write!(f, "round {round} turn {turn} has already been played"),
+            PlanError::BeyondLastRound { round, turn } => write!(f, "round {round} turn {turn} is past the last round"),
+            PlanError::Unordered { round, turn } => write!(f, "round {round} turn {turn} is out of order"),
+            PlanError::OnCooldown { round, turn, ready_in } => {
+                write!(f, "special is on cooldown at round {round} turn {turn} ({ready_in} left)")
+            }
+            PlanError::RoundCapExceeded { round } => {
+                write!(f, "round {round} exceeds {MAX_SPECIALS_PER_ROUND} specials")
+            }
+        }
+    }
+}
+
+/// Walk from `start` through the last of `rounds_remaining` rounds (counting `start.round`),
+/// spending a special on each planned `(round, turn)`. Returns each use with the cooldown
+/// left at the fighter's following turn.
+pub fn cooldown_schedule(
+    cooldown: u8,
+    start: PlanStart,
+    turns_per_round: u8,
+    rounds_remaining: u8,
+    planned_uses: &[(u8, u8)],
+) -> Result<Vec<(u8, u8, u8)>, PlanError> {
+    let index = |round: u8, turn: u8| round as u32 * turns_per_round as u32 + turn as u32;
+    let last_round = start.round as u32 + rounds_remaining as u32;
+
+    let mut cursor = index(start.round, start.turn);
+    let mut ready_in = start.ready_in;
+    let (mut cap_round, mut used) = (start.round, start.specials_this_round);
+    let mut schedule = Vec::with_capacity(planned_uses.len());
+
+    for &(round, turn) in planned_uses {
+        if turn >= turns_per_round {
+            return Err(PlanError::InvalidTurn { round, turn });
+        }
+        if (round, turn) < (start.round, start.turn) {
+            return Err(PlanError::PastTurn { round, turn });
+        }
+        if round as u32 >= last_round {
+            return Err(PlanError::BeyondLastRound { round, turn });
+        }
+        let planned = index(round, turn);
+        if planned < cursor {
+            return Err(PlanError::Unordered { round, turn });
+        }
+
+        let ready = (ready_in as u32).saturating_sub((planned - cursor) * TICKS_PER_TURN) as u8;
+        if ready > 0 {
+            return Err(PlanError::OnCooldown { round, turn, ready_in: ready });
+        }
+        if round != cap_round {
+            (cap_round, used) = (round, 0);
+        }
+        if used >= MAX_SPECIALS_PER_ROUND {
+            return Err(PlanError::RoundCapExceeded { round });
+        }
+
+        used += 1;
+        cursor = planned + 1;
+        ready_in = (cooldown as u32).saturating_sub(TICKS_PER_TURN) as u8;
+        schedule.push((round, turn, ready_in));
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cooldown_schedule, PlanError, PlanStart, MAX_SPECIALS_PER_ROUND};
+    use crate::CharacterClass;
+
+    fn fresh(round: u8) -> PlanStart {
+        PlanStart { round, turn: 0, ready_in: 0, specials_this_round: 0 }
+    }
+
+    #[test]
+    fn uses_come_back_after_the_class_cooldown() {
+        // Two ticks per turn: a cooldown of 3 or 4 skips one turn, 2 skips none
+        for (class, gap) in [(CharacterClass::Warrior, 2), (CharacterClass::Tank, 2), (CharacterClass::Trickster, 1)] {
+            let cooldown = class.special_cooldown();
+            let schedule = cooldown_schedule(cooldown, fresh(1), 3, 2, &[(1, 0), (1, gap)]).unwrap();
+            assert_eq!(schedule, [(1, 0, cooldown - 2), (1, gap, cooldown - 2)]);
+
+            if gap > 1 {
+                assert_eq!(
+                    cooldown_schedule(cooldown, fresh(1), 3, 2, &[(1, 0), (1, gap - 1)]),
+                    Err(PlanError::OnCooldown { round: 1, turn: gap - 1, ready_in: cooldown - 2 }),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn turns_per_round_sets_where_rounds_roll_over() {
+        // With five turns the cap resets only at the next round
+        let uses = [(1, 0), (1, 2), (1, 4)];
+        assert_eq!(cooldown_schedule(2, fresh(1), 5, 2, &uses), Err(PlanError::RoundCapExceeded { round: 1 }));
+        let schedule = cooldown_schedule(2, fresh(1), 5, 2, &[(1, 0), (1, 4), (2, 0)]).unwrap();
+        assert_eq!(schedule.len(), 3);
+
+        // With two turns, turn 1 of round 1 sits right before turn 0 of round 2
+        assert_eq!(
+            cooldown_schedule(4, fresh(1), 2, 2, &[(1, 1), (2, 0)]),
+            Err(PlanError::OnCooldown { round: 2, turn: 0, ready_in: 2 }),
+        );
+        assert!(cooldown_schedule(4, fresh(1), 2, 2, &[(1, 0), (2, 0)]).is_ok());
+        assert_eq!(cooldown_schedule(4, fresh(1), 2, 2, &[(1, 2)]), Err(PlanError::InvalidTurn { round: 1, turn: 2 }));
+    }
+
+    #[test]
+    fn plans_stay_within_the_remaining_turns() {
+        let start = PlanStart { round: 3, turn: 1, ready_in: 3, specials_this_round: MAX_SPECIALS_PER_ROUND };
+
+        assert_eq!(cooldown_schedule(3, start, 3, 2, &[(3, 0)]), Err(PlanError::PastTurn { round: 3, turn: 0 }));
+        assert_eq!(cooldown_schedule(3, start, 3, 2, &[(5, 0)]), Err(PlanError::BeyondLastRound { round: 5, turn: 0 }));
+        assert_eq!(cooldown_schedule(3, start, 3, 2, &[(4, 1), (4, 0)]), Err(PlanError::Unordered { round: 4, turn: 0 }));
+        // Live cooldown and specials already spent this round carry over
+        assert_eq!(cooldown_schedule(3, start, 3, 2, &[(3, 2)]), Err(PlanError::OnCooldown { round: 3, turn: 2, ready_in: 1 }));
+        assert_eq!(cooldown_schedule(0, start, 3, 2, &[(3, 2)]), Err(PlanError::OnCooldown { round: 3, turn: 2, ready_in: 1 }));
+        assert_eq!(cooldown_schedule(3, start, 3, 2, &[(4, 0), (4, 2)]).unwrap(), [(4, 0, 1), (4, 2, 1)]);
+
+        // The second attacker's cooldown has ticked once more by the time it acts
+        assert_eq!(PlanStart::live(3, 1, 3, false, 0).ready_in, 2);
+        assert_eq!(PlanStart::live(3, 1, 3, true, 0).ready_in, 3);
+    }
+}
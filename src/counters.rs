@@ -0,0 +1,134 @@
+//! Lifetime counters that detect saturation.
+//!
+//! A counter that pins at its maximum is no longer the sum it claims to be, so every pin is
+//! recorded per counter. Reports read those records to flag the figures they affect as
+//! unreliable instead of presenting them, or the invariants built on them, at face value.
+
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::{Amount, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::time;
+
+/// Lobby platform revenue, fees plus dust routed to the platform
+pub const PLATFORM_REVENUE: &str = "total_platform_revenue";
+/// Lobby betting volume across all markets
+pub const BETTING_VOLUME: &str = "total_betting_volume";
+/// Fees a prediction chain took from settled markets
+pub const PREDICTION_FEES: &str = "total_fees_collected";
+/// Stakes placed across a prediction chain's markets
+pub const PREDICTION_VOLUME: &str = "total_volume";
+/// Battle tokens the lobby burned as collected fees
+pub const TOKENS_BURNED: &str = "tokens_burned";
+/// Player winnings across all battles
+pub const EARNINGS: &str = "total_earnings";
+/// XP of one of the player's characters
+pub const CHARACTER_XP: &str = "character_xp";
+
+/// Value a lifetime counter can accumulate
+pub trait Counter: Copy {
+    const MAX: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+impl Counter for u64 {
+    const MAX: Self = u64::MAX;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        u64::checked_add(self, other)
+    }
+}
+
+impl Counter for Amount {
+    const MAX: Self = Amount::MAX;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        self.try_add(other).ok()
+    }
+}
+
+/// Add `delta` to `value`, pinning it at the maximum on overflow.
+/// Returns whether it overflowed, so the caller can record the pin.
+#[must_use]
+pub fn checked_accumulate<T: Counter>(value: &mut T, delta: T) -> bool {
+    match value.checked_add(delta) {
+        Some(sum) => {
+            *value = sum;
+            false
+        }
+        None => {
+            *value = T::MAX;
+            true
+        }
+    }
+}
+
+/// Pins recorded against one counter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct CounterOverflow {
+    /// Day of the first pin; the counter is unreliable from then on
+    pub first_day: u64,
+    pub last_day: u64,
+    /// Additions that overflowed
+    pub events: u64,
+}
+
+impl CounterOverflow {
+    /// Fold a pin at `now` into the previous record for its counter
+    pub fn observe(previous: Option<Self>, now: Timestamp) -> Self {
+        let day = time::bucket_day(now);
+        match previous {
+            Some(entry) => Self { last_day: day, events: entry.events.saturating_add(1), ..entry },
+            None => Self { first_day: day, last_day: day, events: 1 },
+        }
+    }
+
+    /// Whether figures for `day` include this counter's pinned value
+    pub fn affects(&self, day: u64) -> bool {
+        day >= self.first_day
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{Amount, Timestamp};
+
+    use super::{checked_accumulate, CounterOverflow};
+    use crate::time::MICROS_PER_DAY;
+
+    #[test]
+    fn sums_below_the_maximum_are_exact() {
+        let mut total = Amount::from_tokens(5);
+        assert!(!checked_accumulate(&mut total, Amount::from_tokens(7)));
+        assert_eq!(total, Amount::from_tokens(12));
+
+        let mut count = u64::MAX - 1;
+        assert!(!checked_accumulate(&mut count, 1));
+        assert_eq!(count, u64::MAX);
+    }
+
+    #[test]
+    fn overflow_pins_and_reports() {
+        let mut total = Amount::MAX.saturating_sub(Amount::from_tokens(1));
+        assert!(checked_accumulate(&mut total, Amount::from_tokens(2)));
+        assert_eq!(total, Amount::MAX);
+        // Once pinned, any further addition overflows again
+        assert!(checked_accumulate(&mut total, Amount::from_attos(1)));
+
+        let mut count = u64::MAX;
+        assert!(checked_accumulate(&mut count, 1));
+        assert_eq!(count, u64::MAX);
+    }
+
+    #[test]
+    fn pins_accumulate_per_counter_from_the_first_day() {
+        let first = CounterOverflow::observe(None, Timestamp::from(2 * MICROS_PER_DAY + 5));
+        let later = CounterOverflow::observe(Some(first.clone()), Timestamp::from(4 * MICROS_PER_DAY));
+
+        assert_eq!(first, CounterOverflow { first_day: 2, last_day: 2, events: 1 });
+        assert_eq!(later, CounterOverflow { first_day: 2, last_day: 4, events: 2 });
+        assert!(!later.affects(1));
+        assert!(later.affects(2) && later.affects(9));
+    }
+}
@@ -0,0 +1,276 @@
+//! Attack damage.
+//!
+//! A landed attack deals its rolled base damage times a chain of factors: the attacker's attack
+//! trait, stance, combo, crit and armor pierce, then the defender's armor, stance and defense
+//! trait, then the round's sudden-death multiplier. Every factor is a basis-point multiplier applied in fixed point, so the order they
+//! apply in only shows through truncation: any order lands within a point of any other.
+//! Factors that would turn negative (an attack trait below -100%, armor of 100 or more, a
+//! defense trait of 100% or more) stop at zero, and a landed attack still deals
+//! [`MIN_DAMAGE`]. A dodged attack deals nothing.
+//!
+//! A defender in the Counter stance may answer a landed attack they survive. Whether they do
+//! is the attack's own counter roll from the turn seed, so a counter replays like every other
+//! roll; it reflects a share of the attack's damage and stuns the attacker for their next
+//! attack. Both the chance and the share are `BattleRules` knobs.
+
+use crate::{apply_bps, apply_bps_u32, fees::BPS_DENOMINATOR, Bps, Stance, FP_SCALE};
+
+/// Least damage a landed attack deals
+pub const MIN_DAMAGE: u32 = 1;
+
+/// A factor that leaves damage as it is
+pub const UNIT_BPS: u128 = BPS_DENOMINATOR;
+
+/// Factor an armor-piercing attack hits with, on top of skipping the defender's armor and
+/// defense trait
+pub const ARMOR_PIERCE_BPS: u128 = 12_000;
+
+/// Everything one attack's damage follows from, rolls already made
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attack {
+    /// Rolled between the attacker's minimum and maximum damage
+    pub base: u32,
+    /// Attacker's attack trait, in bps of damage added
+    pub attack_bps: i16,
+    pub attacker_stance: Stance,
+    /// Attacker's combo multiplier, in bps
+    pub combo_bps: u32,
+    /// Attacker's crit multiplier in bps, when the attack crits
+    pub crit_multiplier: Option<u16>,
+    pub pierces: bool,
+    pub dodged: bool,
+    /// Defender's armor, in percent of damage stopped
+    pub defense: u16,
+    pub defender_stance: Stance,
+    /// Defender's defense trait, in bps of damage stopped
+    pub defense_bps: i16,
+    /// Multiplier of the round the attack lands in, in bps; only sudden death raises it
+    pub round_bps: u32,
+}
+
+impl Attack {
+    /// Every factor on the base damage, in bps, in the order they apply
+    pub fn factors(&self) -> [u128; 9] {
+        let shielded = !self.pierces;
+        [
+            UNIT_BPS.saturating_add_signed(self.attack_bps as i128),
+            attacker_stance_bps(self.attacker_stance),
+            self.combo_bps as u128,
+            self.crit_multiplier.map_or(UNIT_BPS, u128::from),
+            if self.pierces { ARMOR_PIERCE_BPS } else { UNIT_BPS },
+            if shielded { UNIT_BPS.saturating_sub(self.defense as u128 * 100) } else { UNIT_BPS },
+            defender_stance_bps(self.defender_stance),
+            if shielded { UNIT_BPS.saturating_add_signed(-(self.defense_bps as i128)) } else { UNIT_BPS },
+            self.round_bps as u128,
+        ]
+    }
+
+    /// Damage the attack deals
+    pub fn damage(&self) -> u32 {
+        if self.dodged {
+            return 0;
+        }
+        apply(self.base, self.factors())
+    }
+}
+
+/// `base` times each of `factors` in turn, in fixed point, no less than [`MIN_DAMAGE`]
+fn apply(base: u32, factors: impl IntoIterator<Item = u128>) -> u32 {
+    let damage = factors.into_iter().fold(base as u128 * FP_SCALE, apply_bps);
+    u32::try_from(damage / FP_SCALE).unwrap_or(u32::MAX).max(MIN_DAMAGE)
+}
+
+/// Factor an attacker's stance hits with, in bps
+pub fn attacker_stance_bps(stance: Stance) -> u128 {
+    match stance {
+        Stance::Balanced => 10_000,
+        Stance::Aggressive => 13_000,
+        Stance::Defensive => 7_000,
+        Stance::Berserker => 20_000,
+        Stance::Counter => 9_000,
+    }
+}
+
+/// Factor a defender's stance takes hits with, in bps
+pub fn defender_stance_bps(stance: Stance) -> u128 {
+    match stance {
+        Stance::Balanced => 10_000,
+        Stance::Aggressive => 15_000,
+        Stance::Defensive => 5_000,
+        Stance::Berserker => 10_000,
+        Stance::Counter => 6_000,
+    }
+}
+
+/// Crit chance with the crit trait added, a trait raising it but never lowering it
+pub fn crit_chance(crit_chance: Bps, crit_bps: i16) -> Bps {
+    crit_chance.saturating_add_signed(crit_bps.max(0))
+}
+
+/// Damage a Counter-stance defender reflects after taking `damage`, when the attack's counter
+/// `roll` (drawn from `0..Bps::ROLLS`) lands under `chance`; a counter still stuns at zero
+/// `damage_bps`
+pub fn counter_damage(damage: u32, chance: Bps, damage_bps: u16, roll: u64) -> Option<u32> {
+    chance.hits(roll).then(|| apply_bps_u32(damage, damage_bps as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{apply, attacker_stance_bps, counter_damage, crit_chance, defender_stance_bps, Attack, MIN_DAMAGE, UNIT_BPS};
+    use crate::{apply_bps, apply_bps_u32, fp_to_u64, mul_fp, mul_fp_checked, Bps, Stance, FP_SCALE};
+
+    const STANCES: [Stance; 5] = [Stance::Balanced, Stance::Aggressive, Stance::Defensive, Stance::Berserker, Stance::Counter];
+
+    fn plain(base: u32) -> Attack {
+        Attack {
+            base,
+            attack_bps: 0,
+            attacker_stance: Stance::Balanced,
+            combo_bps: 10_000,
+            crit_multiplier: None,
+            pierces: false,
+            dodged: false,
+            defense: 0,
+            defender_stance: Stance::Balanced,
+            defense_bps: 0,
+            round_bps: 10_000,
+        }
+    }
+
+    #[test]
+    fn each_factor_scales_damage_as_documented() {
+        assert_eq!(plain(100).damage(), 100);
+        assert_eq!(Attack { attack_bps: 2_500, ..plain(100) }.damage(), 125);
+        assert_eq!(Attack { combo_bps: 11_500, ..plain(100) }.damage(), 115);
+        assert_eq!(Attack { crit_multiplier: Some(15_000), ..plain(100) }.damage(), 150);
+        assert_eq!(Attack { defense: 20, ..plain(100) }.damage(), 80);
+        assert_eq!(Attack { defense_bps: 1_000, ..plain(100) }.damage(), 90);
+        assert_eq!(Attack { round_bps: 15_000, ..plain(100) }.damage(), 150);
+        // Negative defense traits let more damage through
+        assert_eq!(Attack { defense_bps: -5_000, ..plain(100) }.damage(), 150);
+        // Piercing hits harder and ignores both armor and the defense trait
+        assert_eq!(Attack { pierces: true, defense: 50, defense_bps: 5_000, ..plain(100) }.damage(), 120);
+        assert_eq!(Attack { dodged: true, crit_multiplier: Some(30_000), ..plain(100) }.damage(), 0);
+    }
+
+    #[test]
+    fn every_stance_pairing_multiplies_both_sides() {
+        for attacker_stance in STANCES {
+            for defender_stance in STANCES {
+                let attack = Attack { attacker_stance, defender_stance, ..plain(1_000) };
+                let expected = 1_000 * attacker_stance_bps(attacker_stance) * defender_stance_bps(defender_stance) / UNIT_BPS / UNIT_BPS;
+                assert_eq!(attack.damage() as u128, expected, "{attacker_stance:?} into {defender_stance:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_traits_and_armor_stop_at_minimum_damage() {
+        // Armor of 100 or more, or a defense trait of 100% or more, stops all but the minimum,
+        // whatever the defender's stance or an amplifying trait would add after it
+        for defense in [100, 101, 250, u16::MAX] {
+            let attack = Attack { defense, defender_stance: Stance::Aggressive, defense_bps: i16::MIN, ..plain(1_000) };
+            assert_eq!(attack.damage(), MIN_DAMAGE);
+        }
+        for defense_bps in [10_000, 12_000, i16::MAX] {
+            assert_eq!(Attack { defense_bps, ..plain(1_000) }.damage(), MIN_DAMAGE);
+        }
+        // An attack trait below -100% does not wrap around into a huge hit
+        for attack_bps in [-10_000, -12_000, i16::MIN] {
+            assert_eq!(Attack { attack_bps, ..plain(1_000) }.damage(), MIN_DAMAGE);
+        }
+        // The largest inputs saturate rather than overflow
+        let extreme = Attack {
+            base: u32::MAX,
+            attack_bps: i16::MAX,
+            attacker_stance: Stance::Berserker,
+            combo_bps: u32::MAX,
+            crit_multiplier: Some(u16::MAX),
+            pierces: true,
+            defense_bps: i16::MIN,
+            defender_stance: Stance::Aggressive,
+            round_bps: u32::MAX,
+            ..plain(0)
+        };
+        assert_eq!(extreme.damage(), u32::MAX);
+    }
+
+    #[test]
+    fn fixed_point_helpers_saturate_instead_of_overflowing() {
+        assert_eq!(mul_fp(3 * FP_SCALE, FP_SCALE / 2), 3 * FP_SCALE / 2);
+        assert_eq!(mul_fp_checked(u128::MAX, 2), None);
+        assert_eq!(mul_fp(u128::MAX, 2), u128::MAX);
+        assert_eq!(apply_bps(200, 2_500), 50);
+        assert_eq!(apply_bps(u128::MAX, 10_001), u128::MAX);
+        assert_eq!(apply_bps_u32(u32::MAX, 4_000), (u32::MAX as u64 * 4 / 10) as u32);
+        assert_eq!(apply_bps_u32(u32::MAX, u32::MAX), u32::MAX);
+        assert_eq!(fp_to_u64(u128::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn crit_traits_only_raise_crit_chance() {
+        assert_eq!(crit_chance(Bps(1_000), 500), Bps(1_500));
+        assert_eq!(crit_chance(Bps(1_000), -500), Bps(1_000));
+        assert_eq!(crit_chance(Bps(u16::MAX), i16::MAX), Bps(u16::MAX));
+        assert!(Bps(1_500).hits(1_499) && !Bps(1_500).hits(1_500) && !Bps(0).hits(0));
+    }
+
+    #[test]
+    fn counters_reflect_their_share_when_the_roll_lands() {
+        assert_eq!(counter_damage(100, Bps(4_000), 4_000, 3_999), Some(40));
+        assert_eq!(counter_damage(100, Bps(4_000), 4_000, 4_000), None);
+        assert_eq!(counter_damage(100, Bps(0), 10_000, 0), None);
+        assert_eq!(counter_damage(100, Bps(Bps::ROLLS as u16), 10_000, Bps::ROLLS - 1), Some(100));
+        // A zero share still counters, for the stun alone
+        assert_eq!(counter_damage(100, Bps(4_000), 0, 0), Some(0));
+        assert_eq!(counter_damage(u32::MAX, Bps(4_000), u16::MAX, 0), Some(u32::MAX));
+    }
+
+    fn stance() -> impl Strategy<Value = Stance> {
+        prop::sample::select(STANCES.to_vec())
+    }
+
+    prop_compose! {
+        fn attack()(
+            base in 0..=u16::MAX as u32,
+            attack_bps in any::<i16>(),
+            attacker_stance in stance(),
+            combo_bps in 10_000..=12_500u32,
+            crit_multiplier in prop::option::of(10_000..=30_000u16),
+            pierces in any::<bool>(),
+            dodged in any::<bool>(),
+            defense in 0..=150u16,
+            defender_stance in stance(),
+            defense_bps in any::<i16>(),
+            round_bps in 10_000..=20_000u32,
+        ) -> Attack {
+            Attack {
+                base, attack_bps, attacker_stance, combo_bps, crit_multiplier, pierces, dodged, defense, defender_stance, defense_bps, round_bps,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn landed_attacks_deal_at_least_the_minimum(attack in attack()) {
+            let attack = Attack { dodged: false, ..attack };
+            prop_assert!(attack.damage() >= MIN_DAMAGE);
+        }
+
+        #[test]
+        fn dodged_attacks_deal_nothing(attack in attack()) {
+            prop_assert_eq!(Attack { dodged: true, ..attack }.damage(), 0);
+        }
+
+        #[test]
+        fn factors_commute_up_to_truncation(attack in attack(), rotation in 0..9usize) {
+            let attack = Attack { dodged: false, ..attack };
+            let mut factors = attack.factors();
+            factors.rotate_left(rotation);
+            factors.reverse();
+            prop_assert!(attack.damage().abs_diff(apply(attack.base, factors)) <= 1);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! ELO ratings for completed battles.
+//!
+//! Expected scores come from the standard logistic curve, read from a table in basis points
+//! and interpolated, so every validator computes the same change without floating point.
+
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+
+/// Rating of an account the lobby has not rated yet
+pub const INITIAL_RATING: u64 = 1200;
+
+/// Rating gap between table entries
+const STEP: u64 = 50;
+
+/// Expected score of the higher rated side, in basis points, every `STEP` points of gap:
+/// `1 / (1 + 10^(-gap / 400))`. Gaps past the end count as the last entry
+const EXPECTED_BPS: [u64; 17] =
+    [5000, 5715, 6401, 7034, 7597, 8083, 8490, 8823, 9091, 9302, 9468, 9595, 9693, 9768, 9825, 9868, 9901];
+
+/// How ratings move after a battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "EloConfigInput")]
+pub struct EloConfig {
+    /// Most points a single battle moves a rating
+    pub k_factor: u32,
+}
+
+impl Default for EloConfig {
+    fn default() -> Self {
+        Self { k_factor: 32 }
+    }
+}
+
+/// Expected score of `rating` against `opponent`, in basis points
+pub fn expected_score_bps(rating: u64, opponent: u64) -> u64 {
+    let gap = rating.abs_diff(opponent);
+    let index = (gap / STEP) as usize;
+    let favourite = match EXPECTED_BPS.get(index + 1) {
+        Some(next) => {
+            let low = EXPECTED_BPS[index];
+            low + (next - low) * (gap % STEP) / STEP
+        }
+        None => EXPECTED_BPS[EXPECTED_BPS.len() - 1],
+    };
+    if rating >= opponent { favourite } else { 10_000 - favourite }
+}
+
+/// Changes to the winner's and loser's ratings: `K * (1 - expected)` to the nearest point,
+/// which the loser gives up, so ratings are conserved
+pub fn rating_changes(winner: u64, loser: u64, config: EloConfig) -> (i32, i32) {
+    let surprise = 10_000 - expected_score_bps(winner, loser);
+    let change = ((config.k_factor as u64 * surprise + 5_000) / 10_000) as i32;
+    (change, -change)
+}
+
+/// `rating` moved by `change`, floored at zero
+pub fn apply(rating: u64, change: i32) -> u64 {
+    rating.saturating_add_signed(change as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, expected_score_bps, rating_changes, EloConfig};
+
+    #[test]
+    fn expected_scores_follow_the_logistic_curve() {
+        assert_eq!(expected_score_bps(1200, 1200), 5000);
+        assert_eq!(expected_score_bps(1600, 1200), 9091);
+        assert_eq!(expected_score_bps(1200, 1600), 909);
+        // Between table entries, and past its end
+        assert_eq!(expected_score_bps(1225, 1200), 5357);
+        assert_eq!(expected_score_bps(3000, 1000), 9901);
+        for gap in 0..1000 {
+            assert_eq!(expected_score_bps(1000 + gap, 1000) + expected_score_bps(1000, 1000 + gap), 10_000);
+        }
+    }
+
+    #[test]
+    fn upsets_move_ratings_further_than_expected_wins() {
+        let config = EloConfig::default();
+        assert_eq!(rating_changes(1200, 1200, config), (16, -16));
+        assert_eq!(rating_changes(1600, 1200, config), (3, -3));
+        assert_eq!(rating_changes(1200, 1600, config), (29, -29));
+        assert_eq!(rating_changes(1200, 1200, EloConfig { k_factor: 0 }), (0, 0));
+    }
+
+    #[test]
+    fn ratings_never_go_negative() {
+        assert_eq!(apply(1200, 16), 1216);
+        assert_eq!(apply(10, -16), 0);
+    }
+}
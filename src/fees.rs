@@ -0,0 +1,247 @@
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::Attestation;
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Split of a battle pot between the treasury and the winner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct FeeBreakdown {
+    pub total_stake: Amount,
+    pub platform_fee_bps: u16,
+    pub platform_fee: Amount,
+    pub winner_payout: Amount,
+}
+
+impl FeeBreakdown {
+    /// Compute the fee split for a pot at the given platform fee rate
+    pub fn compute(total_stake: Amount, platform_fee_bps: u16) -> Self {
+        let fee_attos = u128::from(total_stake).saturating_mul(platform_fee_bps as u128) / BPS_DENOMINATOR;
+        let platform_fee = Amount::from_attos(fee_attos);
+        Self {
+            total_stake,
+            platform_fee_bps,
+            platform_fee,
+            winner_payout: total_stake.saturating_sub(platform_fee),
+        }
+    }
+}
+
+/// Outcome of a payout verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PayoutVerdictStatus {
+    Match,
+    Mismatch,
+    Incomplete,
+}
+
+/// A recorded amount that disagrees with the recomputed breakdown
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct PayoutFieldDiff {
+    pub field: String,
+    pub expected: Amount,
+    pub actual: Amount,
+}
+
+/// Verdict for a completed battle's payout
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct PayoutVerdict {
+    pub status: PayoutVerdictStatus,
+    pub expected: FeeBreakdown,
+    pub diffs: Vec<PayoutFieldDiff>,
+    pub missing: Vec<String>,
+    /// Participants' verdict on the outcome, independent of the amounts
+    pub attestation: Attestation,
+}
+
+/// Amounts recorded on-chain for a completed battle
+#[derive(Debug, Clone, Copy)]
+pub struct PayoutRecords {
+    /// Fee split stored in the completed battle record
+    pub recorded: FeeBreakdown,
+    /// Amount the winner's chain reported crediting
+    pub receipt: Option<Amount>,
+    /// Treasury ledger entry for the battle
+    pub treasury_entry: Option<Amount>,
+    pub attestation: Attestation,
+}
+
+/// Recompute the expected breakdown and compare it with every on-chain record
+pub fn verify_payout(records: PayoutRecords) -> PayoutVerdict {
+    let expected = FeeBreakdown::compute(records.recorded.total_stake, records.recorded.platform_fee_bps);
+    let mut diffs = Vec::new();
+    let mut missing = Vec::new();
+
+    let mut compare = |field: &str, expected: Amount, actual: Option<Amount>| match actual {
+        Some(actual) if actual != expected => diffs.push(PayoutFieldDiff {
+            field: field.to_string(),
+            expected,
+            actual,
+        }),
+        Some(_) => {}
+        None => missing.push(field.to_string()),
+    };
+
+    compare("recordPlatformFee", expected.platform_fee, Some(records.recorded.platform_fee));
+    compare("recordWinnerPayout", expected.winner_payout, Some(records.recorded.winner_payout));
+    compare("payoutReceipt", expected.winner_payout, records.receipt);
+    compare("treasuryLedger", expected.platform_fee, records.treasury_entry);
+
+    let status = if !diffs.is_empty() {
+        PayoutVerdictStatus::Mismatch
+    } else if !missing.is_empty() {
+        PayoutVerdictStatus::Incomplete
+    } else {
+        PayoutVerdictStatus::Match
+    };
+
+    PayoutVerdict { status, expected, diffs, missing, attestation: records.attestation }
+}
+
+/// Where the remainder of a pro-rata split goes once every share is rounded down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum DustDestination {
+    /// Credit the platform's revenue
+    #[default]
+    Platform,
+    /// Add to the largest share; ties go to the earliest recipient
+    LargestShare,
+    /// Add to the first recipient with a claim
+    FirstClaimer,
+}
+
+/// Rounding rules for one payout flow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct RoundingPolicy {
+    pub dust_to: DustDestination,
+}
+
+/// Remainder of a finished split and the recipient index it goes to; `None` means the platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dust {
+    pub amount: Amount,
+    pub recipient: Option<u32>,
+}
+
+/// Pro-rata split of `pool` by weight, fed one recipient at a time so it can span
+/// maintenance passes. Shares round down and `finish` accounts for what is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProRataSplit {
+    pub pool: Amount,
+    pub total_weight: Amount,
+    /// Sum of the shares handed out so far
+    pub paid: Amount,
+    pub first: Option<u32>,
+    /// Index and share of the largest recipient so far
+    pub largest: Option<(u32, Amount)>,
+}
+
+impl ProRataSplit {
+    pub fn new(pool: Amount, total_weight: Amount) -> Self {
+        Self { pool, total_weight, paid: Amount::ZERO, first: None, largest: None }
+    }
+
+    /// Floor share of recipient `index`, weighted by `weight`
+    pub fn share(&mut self, index: u32, weight: Amount) -> Amount {
+        if weight == Amount::ZERO || self.total_weight == Amount::ZERO {
+            return Amount::ZERO;
+        }
+        let share = Amount::from_attos(u128::from(weight) * u128::from(self.pool) / u128::from(self.total_weight));
+        self.paid = self.paid.saturating_add(share);
+        self.first.get_or_insert(index);
+        if self.largest.is_none_or(|(_, largest)| share > largest) {
+            self.largest = Some((index, share));
+        }
+        share
+    }
+
+    /// What the rounded-down shares left of the pool, routed by `policy`.
+    /// Without any claimant the whole remainder goes to the platform.
+    pub fn finish(&self, policy: RoundingPolicy) -> Dust {
+        let amount = self.pool.saturating_sub(self.paid);
+        let recipient = match policy.dust_to {
+            _ if amount == Amount::ZERO => None,
+            DustDestination::Platform => None,
+            DustDestination::LargestShare => self.largest.map(|(index, _)| index),
+            DustDestination::FirstClaimer => self.first,
+        };
+        Dust { amount, recipient }
+    }
+}
+
+/// Split `pool` across `weights` at once. The dust is already added to its recipient's
+/// payout; it is still reported so the caller can ledger it.
+pub fn split_pro_rata(pool: Amount, weights: &[Amount], policy: RoundingPolicy) -> (Vec<Amount>, Dust) {
+    let total_weight = weights.iter().fold(Amount::ZERO, |total, weight| total.saturating_add(*weight));
+    let mut split = ProRataSplit::new(pool, total_weight);
+    let mut payouts: Vec<Amount> = weights.iter().enumerate().map(|(index, weight)| split.share(index as u32, *weight)).collect();
+    let dust = split.finish(policy);
+    if let Some(recipient) = dust.recipient {
+        payouts[recipient as usize] = payouts[recipient as usize].saturating_add(dust.amount);
+    }
+    (payouts, dust)
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::{split_pro_rata, DustDestination, ProRataSplit, RoundingPolicy};
+    use crate::splitmix64;
+
+    const POLICIES: [DustDestination; 3] =
+        [DustDestination::Platform, DustDestination::LargestShare, DustDestination::FirstClaimer];
+
+    fn policy(dust_to: DustDestination) -> RoundingPolicy {
+        RoundingPolicy { dust_to }
+    }
+
+    #[test]
+    fn random_splits_conserve_the_pool() {
+        let mut seed = 2531;
+        for _ in 0..500 {
+            let pool = Amount::from_attos((splitmix64(&mut seed) % 1_000_000_000_000) as u128);
+            let recipients = 1 + splitmix64(&mut seed) % 12;
+            let weights: Vec<Amount> =
+                (0..recipients).map(|_| Amount::from_attos((splitmix64(&mut seed) % 1_000_000) as u128 + 1)).collect();
+
+            for dust_to in POLICIES {
+                let (payouts, dust) = split_pro_rata(pool, &weights, policy(dust_to));
+                let paid = payouts.iter().fold(Amount::ZERO, |total, payout| total.saturating_add(*payout));
+                let platform = if dust.recipient.is_none() { dust.amount } else { Amount::ZERO };
+
+                assert_eq!(paid.saturating_add(platform), pool);
+                assert!(u128::from(dust.amount) < recipients as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn dust_follows_the_policy() {
+        // 101 attos over weights 1:2:1 leaves one atto
+        let weights = [Amount::from_attos(1), Amount::from_attos(2), Amount::from_attos(1)];
+        let pool = Amount::from_attos(101);
+
+        let (platform, dust) = split_pro_rata(pool, &weights, policy(DustDestination::Platform));
+        assert_eq!(dust.amount, Amount::from_attos(1));
+        assert_eq!((platform[1], dust.recipient), (Amount::from_attos(50), None));
+
+        let (largest, dust) = split_pro_rata(pool, &weights, policy(DustDestination::LargestShare));
+        assert_eq!((largest[1], dust.recipient), (Amount::from_attos(51), Some(1)));
+
+        let (first, dust) = split_pro_rata(pool, &weights, policy(DustDestination::FirstClaimer));
+        assert_eq!((first[0], dust.recipient), (Amount::from_attos(26), Some(0)));
+    }
+
+    #[test]
+    fn unclaimed_pools_go_to_the_platform() {
+        let mut split = ProRataSplit::new(Amount::from_tokens(3), Amount::ZERO);
+        assert_eq!(split.share(0, Amount::from_tokens(1)), Amount::ZERO);
+
+        let dust = split.finish(policy(DustDestination::LargestShare));
+        assert_eq!((dust.amount, dust.recipient), (Amount::from_tokens(3), None));
+    }
+}
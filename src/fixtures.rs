@@ -0,0 +1,302 @@
+//! Deterministic fixture worlds for frontend development.
+//!
+//! A world is planned entirely from a seed and the current time, then applied by the lobby
+//! through the same write paths live traffic takes. The same seed always plans the same
+//! world, and the applied fixtures hold every invariant live data does.
+
+use linera_sdk::linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, TimeDelta, Timestamp};
+
+use crate::{splitmix64, time::MICROS_PER_DAY, BattleEndReason, CharacterClass, CharacterSnapshot, TiebreakBy, BASE_DODGE_CHANCE};
+
+/// Most players a single generation registers
+pub const MAX_FIXTURE_PLAYERS: u32 = 64;
+/// Most completed battles a single generation records
+pub const MAX_FIXTURE_BATTLES: u32 = 96;
+/// Most live markets a single generation opens
+pub const MAX_FIXTURE_MARKETS: u32 = 32;
+/// Completed battles are spread over this many days up to now
+pub const FIXTURE_DAYS: u64 = 7;
+/// Most bets placed on a single fixture market
+pub const MAX_FIXTURE_BETS: u64 = 4;
+
+const MICROS_PER_SECOND: u64 = 1_000_000;
+/// Live markets open up to this long ago, so the oldest are already overdue for closing
+const LIVE_MARKET_WINDOW: u64 = 2 * 60 * 60 * MICROS_PER_SECOND;
+
+const ADJECTIVES: [&str; 8] = ["ashen", "brisk", "crimson", "dire", "ember", "frost", "gilded", "hollow"];
+const NOUNS: [&str; 8] = ["blade", "crow", "fang", "golem", "hart", "lynx", "viper", "wyrm"];
+
+/// A registered player and their one character
+#[derive(Debug, Clone)]
+pub struct FixturePlayer {
+    pub owner: AccountOwner,
+    pub chain: ChainId,
+    pub name: String,
+    pub snapshot: CharacterSnapshot,
+    /// Rating after every completed fixture battle
+    pub elo: u64,
+}
+
+/// A bet by one of the fixture players
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureBet {
+    pub bettor: usize,
+    pub on_player1: bool,
+    pub amount: Amount,
+    pub placed_at: Timestamp,
+}
+
+/// How a completed fixture battle ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixtureOutcome {
+    pub completed_at: Timestamp,
+    pub player1_won: bool,
+    pub rounds_played: u8,
+    pub end_reason: BattleEndReason,
+    /// Rating the winner took from the loser
+    pub elo_change: u64,
+}
+
+/// Where a fixture battle and its market stand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureStage {
+    /// In progress, market still taking bets
+    Open,
+    /// In progress, market closed when the battle started
+    Closed,
+    Completed(FixtureOutcome),
+}
+
+/// A battle between two fixture players, with its market
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureBattle {
+    pub chain: ChainId,
+    pub player1: usize,
+    pub player2: usize,
+    /// Stake of each fighter
+    pub stake: Amount,
+    pub opened_at: Timestamp,
+    pub bets: Vec<FixtureBet>,
+    pub stage: FixtureStage,
+}
+
+/// Everything one generation creates: completed battles in completion order, then live ones
+#[derive(Debug, Clone)]
+pub struct FixtureWorld {
+    pub players: Vec<FixturePlayer>,
+    pub battles: Vec<FixtureBattle>,
+}
+
+impl FixtureWorld {
+    /// Plan a world of up to `players` players, `battles` completed battles and `markets`
+    /// live markets. Counts are capped; battles and markets need at least two players.
+    pub fn plan(seed: u64, players: u32, battles: u32, markets: u32, max_rounds: u8, now: Timestamp) -> Self {
+        let mut rng = seed;
+        let mut next = || splitmix64(&mut rng);
+
+        let players: Vec<FixturePlayer> = (0..players.min(MAX_FIXTURE_PLAYERS) as u64)
+            .map(|index| {
+                let class = CharacterClass::ALL[(next() % 5) as usize];
+                let level = 1 + (next() % 30) as u16;
+                let name = format!(
+                    "{}-{}-{index:02}",
+                    ADJECTIVES[(next() % 8) as usize],
+                    NOUNS[(next() % 8) as usize],
+                );
+                FixturePlayer {
+                    owner: AccountOwner::Address32(CryptoHash::from([seed, 0, index, 0])),
+                    chain: ChainId(CryptoHash::from([seed, 1, index, 0])),
+                    snapshot: snapshot(name.clone(), class, level),
+                    name,
+                    elo: 1000 + next() % 500,
+                }
+            })
+            .collect();
+        let count = players.len() as u64;
+        if count < 2 {
+            return Self { players, battles: Vec::new() };
+        }
+
+        // Fighters, stakes and bets first; outcomes follow in completion order so ratings evolve
+        let mut completed: Vec<FixtureBattle> = (0..battles.min(MAX_FIXTURE_BATTLES) as u64)
+            .map(|index| {
+                let ago = next() % (FIXTURE_DAYS * MICROS_PER_DAY);
+                let rounds_played = 3 + (next() % (max_rounds.max(3) as u64 - 2)) as u8;
+                let completed_at = Timestamp::from(now.micros().saturating_sub(ago));
+                let opened_at = completed_at.saturating_sub_micros(rounds_played as u64 * 60 * MICROS_PER_SECOND);
+                let mut battle = fixture_battle(&mut next, seed, 2, index, count, opened_at);
+                battle.stage = FixtureStage::Completed(FixtureOutcome {
+                    completed_at,
+                    player1_won: true,
+                    rounds_played,
+                    end_reason: BattleEndReason::Knockout,
+                    elo_change: 0,
+                });
+                battle
+            })
+            .collect();
+        completed.sort_by_key(|battle| match battle.stage {
+            FixtureStage::Completed(outcome) => (outcome.completed_at, battle.chain),
+            _ => unreachable!("only completed battles are sorted"),
+        });
+
+        let mut ratings: Vec<u64> = players.iter().map(|player| player.elo).collect();
+        for battle in &mut completed {
+            let FixtureStage::Completed(outcome) = &mut battle.stage else {
+                unreachable!("only completed battles are rated");
+            };
+            // The stronger fighter wins more often, never always
+            let (elo1, elo2) = (ratings[battle.player1] as i64, ratings[battle.player2] as i64);
+            let odds = (500 + (elo1 - elo2) / 2).clamp(100, 900) as u64;
+            outcome.player1_won = next() % 1000 < odds;
+            let (winner, loser) = if outcome.player1_won {
+                (battle.player1, battle.player2)
+            } else {
+                (battle.player2, battle.player1)
+            };
+            outcome.end_reason = if outcome.rounds_played < max_rounds {
+                BattleEndReason::Knockout
+            } else if next() % 4 == 0 {
+                BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::Damage }
+            } else {
+                BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::HpPercent }
+            };
+            // Upsets move ratings further than expected wins
+            let gap = ratings[loser] as i64 - ratings[winner] as i64;
+            outcome.elo_change = (16 + gap / 25).clamp(4, 28) as u64;
+            ratings[winner] += outcome.elo_change;
+            ratings[loser] = ratings[loser].saturating_sub(outcome.elo_change);
+        }
+
+        let live = (0..markets.min(MAX_FIXTURE_MARKETS) as u64).map(|index| {
+            let opened_at = Timestamp::from(now.micros().saturating_sub(next() % LIVE_MARKET_WINDOW));
+            let mut battle = fixture_battle(&mut next, seed, 3, index, count, opened_at);
+            battle.stage = if next() % 3 == 0 { FixtureStage::Closed } else { FixtureStage::Open };
+            battle
+        });
+        let battles = completed.into_iter().chain(live).collect();
+
+        let players = players
+            .into_iter()
+            .zip(ratings)
+            .map(|(player, elo)| FixturePlayer { elo, ..player })
+            .collect();
+        Self { players, battles }
+    }
+}
+
+/// Two distinct fighters out of `count` players, with bets from up to `MAX_FIXTURE_BETS`
+/// other players. The stage is left open for the caller to decide.
+fn fixture_battle(
+    next: &mut impl FnMut() -> u64,
+    seed: u64,
+    kind: u64,
+    index: u64,
+    count: u64,
+    opened_at: Timestamp,
+) -> FixtureBattle {
+    let player1 = next() % count;
+    let player2 = (player1 + 1 + next() % (count - 1)) % count;
+    let stake = Amount::from_millis(100 * (1 + (next() % 50) as u128));
+
+    let wanted = next() % (MAX_FIXTURE_BETS + 1);
+    let mut bets: Vec<FixtureBet> = Vec::new();
+    for offset in 0..count {
+        if bets.len() as u64 >= wanted {
+            break;
+        }
+        // Bettors are the players after the fighters, each betting once
+        let bettor = (player1 + 1 + offset) % count;
+        if bettor == player1 || bettor == player2 {
+            continue;
+        }
+        bets.push(FixtureBet {
+            bettor: bettor as usize,
+            on_player1: next() % 2 == 0,
+            amount: Amount::from_millis(10 * (1 + (next() % 100) as u128)),
+            placed_at: opened_at.saturating_add(TimeDelta::from_secs(bets.len() as u64 + 1)),
+        });
+    }
+
+    FixtureBattle {
+        chain: ChainId(CryptoHash::from([seed, kind, index, 0])),
+        player1: player1 as usize,
+        player2: player2 as usize,
+        stake,
+        opened_at,
+        bets,
+        stage: FixtureStage::Open,
+    }
+}
+
+/// A freshly minted character of `class`, at `level`
+fn snapshot(nft_id: String, class: CharacterClass, level: u16) -> CharacterSnapshot {
+    let (hp_max, min_damage, max_damage, crit_chance) = class.base_stats();
+    CharacterSnapshot {
+        nft_id,
+        class,
+        level,
+        hp_max,
+        min_damage,
+        max_damage,
+        crit_chance,
+        crit_multiplier: 1500,
+        dodge_chance: BASE_DODGE_CHANCE,
+        defense: 5,
+        attack_bps: 0,
+        defense_bps: 0,
+        crit_bps: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Timestamp;
+
+    use super::{FixtureStage, FixtureWorld, FIXTURE_DAYS, MAX_FIXTURE_PLAYERS};
+    use crate::time::MICROS_PER_DAY;
+
+    fn now() -> Timestamp {
+        Timestamp::from(30 * MICROS_PER_DAY)
+    }
+
+    #[test]
+    fn worlds_are_planned_from_the_seed_alone() {
+        let first = FixtureWorld::plan(7, 12, 20, 5, 10, now());
+        let again = FixtureWorld::plan(7, 12, 20, 5, 10, now());
+        let other = FixtureWorld::plan(8, 12, 20, 5, 10, now());
+
+        assert_eq!(first.battles, again.battles);
+        let names = |world: &FixtureWorld| world.players.iter().map(|player| (player.name.clone(), player.elo)).collect::<Vec<_>>();
+        assert_eq!(names(&first), names(&again));
+        assert_ne!(first.battles, other.battles);
+    }
+
+    #[test]
+    fn battles_are_recent_ordered_and_between_distinct_players() {
+        let world = FixtureWorld::plan(42, 6, 40, 8, 10, now());
+        assert_eq!(world.battles.len(), 48);
+
+        let mut last = Timestamp::from(0);
+        for battle in &world.battles {
+            assert_ne!(battle.player1, battle.player2);
+            assert!(battle.bets.iter().all(|bet| bet.bettor != battle.player1 && bet.bettor != battle.player2));
+            assert!(battle.opened_at <= now());
+            if let FixtureStage::Completed(outcome) = battle.stage {
+                assert!(outcome.completed_at >= last && outcome.completed_at <= now());
+                assert!(now().micros() - outcome.completed_at.micros() < FIXTURE_DAYS * MICROS_PER_DAY);
+                last = outcome.completed_at;
+            }
+        }
+        let completed = world.battles.iter().filter(|battle| matches!(battle.stage, FixtureStage::Completed(_))).count();
+        assert_eq!(completed, 40);
+    }
+
+    #[test]
+    fn counts_are_capped_and_lone_players_do_not_fight() {
+        assert_eq!(FixtureWorld::plan(1, u32::MAX, 0, 0, 10, now()).players.len(), MAX_FIXTURE_PLAYERS as usize);
+        let lone = FixtureWorld::plan(1, 1, 10, 10, 10, now());
+        assert_eq!(lone.players.len(), 1);
+        assert!(lone.battles.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// Feistel rounds applied to each id
+const ROUNDS: u64 = 6;
+
+/// Bits per Feistel half. The permuted domain is kept to 52 bits so encoded
+/// ids stay exact in JavaScript numbers and GraphQL `Int` inputs.
+const HALF_BITS: u32 = 26;
+const HALF_MASK: u64 = (1 << HALF_BITS) - 1;
+
+/// Sequence numbers at or above this bound are exposed unchanged
+pub const OBFUSCATED_DOMAIN: u64 = 1 << (2 * HALF_BITS);
+
+/// Maps sequential internal ids to the ids shown outside the chain.
+///
+/// Plain codecs expose the sequence number unchanged. Obfuscated codecs apply
+/// a keyed Feistel permutation over the first 2^52 sequence numbers, so exposed
+/// ids look random but still decode back to the sequence number used for storage.
+///
+/// The key is derived from public inputs and the codec is readable chain state, so
+/// encoded ids are opaque, not secret: they hide creation order from casual readers
+/// but anyone can decode them. Nothing may rely on an id being unguessable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdCodec {
+    key: Option<u64>,
+}
+
+impl IdCodec {
+    /// Codec that exposes sequence numbers as-is
+    pub fn plain() -> Self {
+        Self { key: None }
+    }
+
+    /// Codec that permutes sequence numbers with a per-deployment key
+    pub fn obfuscated(key: u64) -> Self {
+        Self { key: Some(key) }
+    }
+
+    pub fn is_obfuscated(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Turn an internal sequence number into its external id
+    pub fn encode(&self, sequence: u64) -> u64 {
+        let Some(key) = self.key.filter(|_| sequence < OBFUSCATED_DOMAIN) else {
+            return sequence;
+        };
+        let (mut left, mut right) = (sequence >> HALF_BITS, sequence & HALF_MASK);
+        for round in 0..ROUNDS {
+            (left, right) = (right, left ^ round_function(right, key, round));
+        }
+        (left << HALF_BITS) | right
+    }
+
+    /// Map an external id back to the internal sequence number
+    pub fn decode(&self, id: u64) -> u64 {
+        let Some(key) = self.key.filter(|_| id < OBFUSCATED_DOMAIN) else {
+            return id;
+        };
+        let (mut left, mut right) = (id >> HALF_BITS, id & HALF_MASK);
+        for round in (0..ROUNDS).rev() {
+            (left, right) = (right ^ round_function(left, key, round), left);
+        }
+        (left << HALF_BITS) | right
+    }
+}
+
+/// Keyed SplitMix64-style mixer used as the Feistel round function
+fn round_function(half: u64, key: u64, round: u64) -> u64 {
+    let mut x = half ^ key.wrapping_add(round.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    x & HALF_MASK
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{IdCodec, OBFUSCATED_DOMAIN};
+
+    const SAMPLES: [u64; 10] = [
+        0, 1, 2, 41, 1 << 32, OBFUSCATED_DOMAIN - 1, OBFUSCATED_DOMAIN, OBFUSCATED_DOMAIN + 1, u64::MAX - 1, u64::MAX,
+    ];
+
+    #[test]
+    fn obfuscated_ids_round_trip() {
+        let codec = IdCodec::obfuscated(0x5EED_1234_ABCD_0042);
+        let stride = u64::MAX / 10_007;
+        let spread = (0..10_007).map(|i| i * stride);
+
+        for sequence in SAMPLES.into_iter().chain(spread) {
+            assert_eq!(codec.decode(codec.encode(sequence)), sequence);
+        }
+    }
+
+    #[test]
+    fn obfuscated_ids_are_unique_and_not_sequential() {
+        let codec = IdCodec::obfuscated(7);
+        let ids: HashSet<u64> = (1..=10_000).map(|sequence| codec.encode(sequence)).collect();
+
+        assert_eq!(ids.len(), 10_000);
+        assert!((1..=10_000).filter(|sequence| ids.contains(sequence)).count() < 10);
+        assert!(ids.iter().all(|id| *id < OBFUSCATED_DOMAIN));
+    }
+
+    #[test]
+    fn keys_produce_different_ids() {
+        let (a, b) = (IdCodec::obfuscated(1), IdCodec::obfuscated(2));
+
+        assert_ne!(a.encode(1), b.encode(1));
+    }
+
+    #[test]
+    fn plain_ids_are_unchanged() {
+        let codec = IdCodec::plain();
+
+        for sequence in SAMPLES {
+            assert_eq!(codec.encode(sequence), sequence);
+            assert_eq!(codec.decode(sequence), sequence);
+        }
+        assert_eq!(IdCodec::default(), IdCodec::plain());
+    }
+}
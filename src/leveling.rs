@@ -0,0 +1,106 @@
+//! Character levels bought with battle XP.
+//!
+//! Each level costs more than the last, and every level adds its class's fixed gains, so two
+//! characters of one class and level always have the same base stats.
+
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+
+use crate::{Bps, CharacterClass};
+
+/// XP the first level-up costs; level `n` to `n + 1` costs `n * (n + 1) / 2` times this
+pub const XP_PER_LEVEL: u64 = 100;
+
+/// Highest crit or dodge chance levels can raise a character to
+pub const MAX_CHANCE_BPS: Bps = Bps(7_500);
+
+/// How far characters can level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "LevelingConfigInput")]
+pub struct LevelingConfig {
+    /// Highest level a character can reach
+    pub level_cap: u16,
+}
+
+impl Default for LevelingConfig {
+    fn default() -> Self {
+        Self { level_cap: 50 }
+    }
+}
+
+/// XP that takes a character from `level` to the next one
+pub fn xp_to_next(level: u16) -> u64 {
+    let level = level as u64;
+    XP_PER_LEVEL.saturating_mul(level * (level + 1) / 2)
+}
+
+/// Levels gained by spending at most `xp_to_spend` from `level`, stopping at `config`'s cap,
+/// and the XP those levels cost. Unspent XP stays with the character
+pub fn levels_bought(level: u16, xp_to_spend: u64, config: LevelingConfig) -> Result<(u16, u64), &'static str> {
+    if level >= config.level_cap {
+        return Err("level_cap_reached");
+    }
+    let (mut gained, mut spent) = (0u16, 0u64);
+    while level + gained < config.level_cap {
+        let cost = xp_to_next(level + gained);
+        if spent.saturating_add(cost) > xp_to_spend {
+            break;
+        }
+        spent += cost;
+        gained += 1;
+    }
+    if gained == 0 {
+        return Err("insufficient_xp");
+    }
+    Ok((gained, spent))
+}
+
+/// Stats one level adds to a character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelGains {
+    pub hp_max: u32,
+    pub min_damage: u16,
+    pub max_damage: u16,
+    /// Up to `MAX_CHANCE_BPS` in total
+    pub crit_chance: Bps,
+    /// Up to `MAX_CHANCE_BPS` in total
+    pub dodge_chance: Bps,
+    pub defense: u16,
+}
+
+impl LevelGains {
+    /// Gains per level of `class`, leaning into what the class already does best
+    pub fn of(class: CharacterClass) -> Self {
+        let (hp_max, min_damage, max_damage, crit_chance, dodge_chance, defense) = match class {
+            CharacterClass::Warrior => (12, 1, 2, 25, 10, 1),
+            CharacterClass::Assassin => (7, 1, 2, 60, 30, 0),
+            CharacterClass::Mage => (6, 2, 2, 40, 15, 0),
+            CharacterClass::Tank => (18, 1, 1, 10, 5, 2),
+            CharacterClass::Trickster => (9, 1, 2, 40, 40, 1),
+        };
+        Self { hp_max, min_damage, max_damage, crit_chance: Bps(crit_chance), dodge_chance: Bps(dodge_chance), defense }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{levels_bought, xp_to_next, LevelingConfig};
+
+    #[test]
+    fn each_level_costs_more_than_the_last() {
+        assert_eq!([1, 2, 3, 10].map(xp_to_next), [100, 300, 600, 5_500]);
+    }
+
+    #[test]
+    fn spending_buys_every_affordable_level_up_to_the_cap() {
+        let config = LevelingConfig::default();
+        assert_eq!(levels_bought(1, 100, config), Ok((1, 100)));
+        // 100 + 300 buys two levels; the remaining 50 cannot buy a third
+        assert_eq!(levels_bought(1, 450, config), Ok((2, 400)));
+        assert_eq!(levels_bought(1, 99, config), Err("insufficient_xp"));
+
+        let capped = LevelingConfig { level_cap: 3 };
+        assert_eq!(levels_bought(1, u64::MAX, capped), Ok((2, 400)));
+        assert_eq!(levels_bought(3, u64::MAX, capped), Err("level_cap_reached"));
+    }
+}
@@ -1,10 +1,35 @@
-use async_graphql::{Request, Response};
+use async_graphql::{Enum, InputObject, Object, Request, Response, SimpleObject};
 use linera_sdk::{
     graphql::GraphQLMutationRoot,
-    linera_base_types::{AccountOwner, Amount, ChainId, ContractAbi, ServiceAbi},
+    linera_base_types::{AccountOwner, Amount, BcsHashable, ChainId, ContractAbi, CryptoHash, ServiceAbi, TimeDelta, Timestamp},
 };
 use serde::{Deserialize, Serialize};
 
+pub mod betting;
+pub mod bracket;
+pub mod cooldown;
+pub mod counters;
+pub mod damage;
+pub mod elo;
+pub mod fees;
+pub mod fixtures;
+pub mod idcodec;
+pub mod leveling;
+pub mod matchmaking;
+pub mod migrations;
+pub mod minting;
+pub mod odds;
+pub mod practice;
+pub mod quests;
+pub mod random;
+pub mod rewards;
+pub mod schedule;
+pub mod season;
+pub mod series;
+pub mod throttle;
+pub mod time;
+pub mod tokens;
+
 /// Character classes with unique abilities
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CharacterClass {
@@ -15,6 +40,21 @@ pub enum CharacterClass {
     Trickster,
 }
 
+/// What a class's special does to the attack it is used on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum SpecialAbility {
+    /// Warrior: the hit ignores the defender's armor
+    ArmorPierce,
+    /// Assassin: the hit always crits
+    Assassinate,
+    /// Mage: the target burns for part of the hit over the next turns
+    Ignite,
+    /// Tank: a shield that absorbs incoming damage until it breaks or wears off
+    Bulwark,
+    /// Trickster: the next attack against the trickster is much likelier to miss
+    Vanish,
+}
+
 /// Battle stances with strategic modifiers
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Stance {
@@ -25,6 +65,49 @@ pub enum Stance {
     Counter,
 }
 
+/// Predefined emotes a fighter can send with a turn; there is no free text to moderate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum Emote {
+    GoodLuck,
+    WellPlayed,
+    Taunt,
+    Oops,
+    Thanks,
+    GoodGame,
+}
+
+/// A chance out of 10 000 rolls, in basis points: 1 500 is 15%. Every chance a character
+/// carries is one, so crit and dodge checks read rolls on a single scale. Stored as the bare
+/// number, the way chances were stored before they had a type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bps(pub u16);
+
+async_graphql::scalar!(Bps, "Bps", "A chance out of 10 000 rolls, in basis points");
+
+impl Bps {
+    /// Rolls a chance is taken out of; each roll is drawn from 0 to `ROLLS - 1`
+    pub const ROLLS: u64 = 10_000;
+
+    /// Whether `roll`, drawn from 0 to `ROLLS - 1`, lands within the chance
+    pub fn hits(self, roll: u64) -> bool {
+        roll < u64::from(self.0)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    /// The chance moved by a signed trait bonus, kept within `u16`
+    pub fn saturating_add_signed(self, bonus: i16) -> Self {
+        Self(self.0.saturating_add_signed(bonus))
+    }
+
+    pub fn saturating_mul(self, times: u16) -> Self {
+        Self(self.0.saturating_mul(times))
+    }
+}
+
 /// Character snapshot for battles
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharacterSnapshot {
@@ -34,13 +117,61 @@ pub struct CharacterSnapshot {
     pub hp_max: u32,
     pub min_damage: u16,
     pub max_damage: u16,
-    pub crit_chance: u16,
+    pub crit_chance: Bps,
+    pub crit_multiplier: u16,
+    pub dodge_chance: Bps,
+    pub defense: u16,
+    pub attack_bps: i16,
+    pub defense_bps: i16,
+    pub crit_bps: i16,
+}
+
+impl CharacterSnapshot {
+    /// Whether the modifiers could come from traits and equipment: each slot boosts one stat,
+    /// so no stat can exceed the best rarity's trait plus its item bonus
+    pub fn within_equipment_bounds(&self) -> bool {
+        let max = ItemRarity::Epic.max_bonus_bps() + minting::max_trait_bps(ItemRarity::Epic);
+        [self.attack_bps, self.defense_bps, self.crit_bps].iter().all(|bps| (0..=max).contains(bps))
+    }
+}
+
+/// Dodge chance every character is minted with
+pub const BASE_DODGE_CHANCE: Bps = Bps(500);
+
+/// Lives a character starts with, and is revived to. Every ranked defeat costs one; a character
+/// out of lives is dead and cannot fight until revived
+pub const STARTING_LIVES: u8 = 3;
+
+/// Everything a player chain keeps about a character, carried when it changes hands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterRecord {
+    pub nft_id: String,
+    pub class: CharacterClass,
+    pub level: u16,
+    pub xp: u64,
+    pub hp_max: u32,
+    pub min_damage: u16,
+    pub max_damage: u16,
+    pub crit_chance: Bps,
     pub crit_multiplier: u16,
-    pub dodge_chance: u16,
+    pub dodge_chance: Bps,
     pub defense: u16,
     pub attack_bps: i16,
     pub defense_bps: i16,
     pub crit_bps: i16,
+    pub rarity: ItemRarity,
+    pub lives_remaining: u8,
+    pub created_at: Timestamp,
+}
+
+/// Longest character id a chain mints
+pub const MAX_CHARACTER_ID_LEN: usize = 64;
+
+/// Whether `id` may name a new character: 1 to `MAX_CHARACTER_ID_LEN` ASCII letters, digits,
+/// `-` or `_`, so an id reads and compares the same on every chain that keys by it
+pub fn valid_character_id(id: &str) -> bool {
+    (1..=MAX_CHARACTER_ID_LEN).contains(&id.len())
+        && id.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
 }
 
 /// Turn submission
@@ -52,6 +183,43 @@ pub struct TurnSubmission {
     pub use_special: bool,
 }
 
+/// How long fighters have to reveal a sealed turn once both have locked it in, under the
+/// default rules
+pub const REVEAL_WINDOW: TimeDelta = TimeDelta::from_secs(60);
+
+/// A sealed turn's contents, as hashed into its commitment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TurnReveal {
+    owner: AccountOwner,
+    round: u8,
+    turn: u8,
+    stance: Stance,
+    use_special: bool,
+    salt: [u8; 32],
+}
+
+impl BcsHashable<'_> for TurnReveal {}
+
+/// Commitment `owner` submits with `CommitTurn` to seal a turn. Binding the owner keeps an
+/// opponent from copying the commitment and mirroring the reveal
+pub fn turn_commitment(owner: AccountOwner, round: u8, turn: u8, stance: Stance, use_special: bool, salt: [u8; 32]) -> [u8; 32] {
+    CryptoHash::new(&TurnReveal { owner, round, turn, stance, use_special, salt }).into()
+}
+
+/// A private battle's invite secret, as hashed into its digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PrivateInvite {
+    secret: [u8; 32],
+}
+
+impl BcsHashable<'_> for PrivateInvite {}
+
+/// Digest a host opens a private battle with. The host picks `secret` and shares it off chain
+/// with the opponent, who joins with it; only the digest is ever stored
+pub fn invite_digest(secret: [u8; 32]) -> [u8; 32] {
+    CryptoHash::new(&PrivateInvite { secret }).into()
+}
+
 /// Battle participant data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BattleParticipant {
@@ -61,18 +229,20 @@ pub struct BattleParticipant {
     pub stake: Amount,
     pub current_hp: u32,
     pub combo_stack: u8,
+    pub longest_combo: u8,
     pub special_cooldown: u8,
     pub turns_submitted: [Option<TurnSubmission>; 3],
 }
 
 /// Combat statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CombatStats {
     pub damage_dealt: u64,
     pub damage_taken: u64,
     pub crits: u64,
     pub dodges: u64,
     pub highest_crit: u64,
+    pub longest_combo: u8,
 }
 
 /// Global player statistics tracked by lobby
@@ -81,7 +251,10 @@ pub struct PlayerGlobalStats {
     pub total_battles: u64,
     pub wins: u64,
     pub losses: u64,
-    pub win_rate: f64,
+    /// Battles neither fighter won; counted in `total_battles` but not as wins or losses
+    pub draws: u64,
+    /// Wins per 10 000 battles; BCS cannot encode floats
+    pub win_rate_bps: u64,
     pub elo_rating: u64,
     pub total_damage_dealt: u64,
     pub total_damage_taken: u64,
@@ -99,7 +272,8 @@ impl Default for PlayerGlobalStats {
             total_battles: 0,
             wins: 0,
             losses: 0,
-            win_rate: 0.0,
+            draws: 0,
+            win_rate_bps: 0,
             elo_rating: 1200,
             total_damage_dealt: 0,
             total_damage_taken: 0,
@@ -113,16 +287,620 @@ impl Default for PlayerGlobalStats {
     }
 }
 
+/// How a battle was decided, without the details of its `BattleEndReason`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum ResultKind {
+    /// A fighter's HP reached zero
+    Knockout,
+    /// Both fighters lasted `max_rounds` and a tiebreak picked the winner
+    MaxRounds,
+    Forfeit,
+    Timeout,
+    MutualCancel,
+    ForceCancel,
+    Draw,
+    NoContest,
+}
+
+/// What separated two fighters who both lasted `max_rounds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum TiebreakBy {
+    /// Larger share of maximum HP left
+    HpPercent,
+    /// Equal HP shares; more damage dealt
+    Damage,
+}
+
+/// Why a battle ended. Decided once on the battle chain and carried unchanged to the lobby's
+/// record, the completion summary and the fighters' histories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BattleEndReason {
+    /// A fighter's HP reached zero
+    Knockout,
+    MaxRoundsTiebreak { by: TiebreakBy },
+    /// A fighter conceded
+    Forfeit,
+    /// A fighter let the round deadline pass
+    Timeout,
+    /// Both fighters called the battle off
+    MutualCancel,
+    /// The operator called the battle off
+    ForceCancel,
+    /// The fighters could not be separated
+    Draw,
+    /// The battle ran but its result does not count
+    NoContest,
+}
+
+impl BattleEndReason {
+    pub fn kind(self) -> ResultKind {
+        match self {
+            Self::Knockout => ResultKind::Knockout,
+            Self::MaxRoundsTiebreak { .. } => ResultKind::MaxRounds,
+            Self::Forfeit => ResultKind::Forfeit,
+            Self::Timeout => ResultKind::Timeout,
+            Self::MutualCancel => ResultKind::MutualCancel,
+            Self::ForceCancel => ResultKind::ForceCancel,
+            Self::Draw => ResultKind::Draw,
+            Self::NoContest => ResultKind::NoContest,
+        }
+    }
+
+    /// Whether markets on the battle pay out on its winner. Endings without a contested
+    /// winner void them instead; forfeits and timeouts settle normally
+    pub fn settles_market(self) -> bool {
+        !matches!(self, Self::MutualCancel | Self::ForceCancel | Self::Draw | Self::NoContest)
+    }
+}
+
+#[Object]
+impl BattleEndReason {
+    #[graphql(name = "kind")]
+    async fn result_kind(&self) -> ResultKind {
+        self.kind()
+    }
+
+    /// Set for battles decided at the round limit
+    async fn tiebreak(&self) -> Option<TiebreakBy> {
+        match self {
+            Self::MaxRoundsTiebreak { by } => Some(*by),
+            _ => None,
+        }
+    }
+}
+
+/// Which matchmaking pool a queue request targets; players are only matched within one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum QueueMode {
+    /// Open to every account; ratings are left alone
+    #[default]
+    Casual,
+    /// Rated games, gated by `RankedGates`
+    Ranked,
+    /// Unrated games for stakes of at least the queue's `min_stake`
+    HighStakes,
+    /// Unrated team battles: fighters queue one by one and the lobby drafts them into two sides
+    /// of `TEAM_SIZE`, keeping members of a party on the same side
+    Teams,
+}
+
+impl QueueMode {
+    pub const ALL: [QueueMode; 4] = [QueueMode::Casual, QueueMode::Ranked, QueueMode::HighStakes, QueueMode::Teams];
+
+    /// Whether battles matched in this queue move ratings
+    pub fn is_rated(self) -> bool {
+        self == QueueMode::Ranked
+    }
+}
+
+/// What entering a tournament costs and how its prize pool is split
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "TournamentTermsInput")]
+pub struct TournamentTerms {
+    /// Every entry fee goes into the prize pool
+    pub entry_fee: Amount,
+    pub max_entrants: u32,
+    pub options: bracket::BracketOptions,
+    /// Share of the pool left after the platform fee that goes to the runner-up; the champion
+    /// takes the rest
+    pub runner_up_share_bps: u16,
+}
+
+/// Thresholds an account must meet to queue ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "RankedGatesInput")]
+pub struct RankedGates {
+    /// Level of the character being queued
+    pub min_ranked_level: u16,
+    /// Completed battles across the account, in any queue
+    pub min_account_battles: u64,
+}
+
+impl Default for RankedGates {
+    fn default() -> Self {
+        Self { min_ranked_level: 5, min_account_battles: 10 }
+    }
+}
+
+impl RankedGates {
+    /// Rejection reason naming the first gate missed and its threshold
+    pub fn check(&self, level: u16, account_battles: u64) -> Result<(), String> {
+        if level < self.min_ranked_level {
+            return Err(format!("ranked_level_below_{}", self.min_ranked_level));
+        }
+        if account_battles < self.min_account_battles {
+            return Err(format!("ranked_battles_below_{}", self.min_account_battles));
+        }
+        Ok(())
+    }
+}
+
+/// What an account must show before staked or ranked queues take it: enough practice and
+/// casual wins, or a character of high enough level. Either threshold at zero lets everyone in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "ProgressionGatesInput")]
+pub struct ProgressionGates {
+    /// Practice and casual wins across the account
+    pub min_wins: u64,
+    /// Level of the character being queued
+    pub min_level: u16,
+}
+
+impl ProgressionGates {
+    /// Rejection reason naming both thresholds when neither is met
+    pub fn check(&self, level: u16, wins: u64) -> Result<(), String> {
+        if wins >= self.min_wins || level >= self.min_level {
+            return Ok(());
+        }
+        Err(format!("progression_wins_below_{}_or_level_below_{}", self.min_wins, self.min_level))
+    }
+
+    /// Whether a queue entry in `mode` for `stake` must clear the gates
+    pub fn applies(mode: QueueMode, stake: Amount) -> bool {
+        mode == QueueMode::Ranked || stake > Amount::ZERO
+    }
+}
+
+/// Largest BCS encoding of `PlayerPreferences` a player chain will store
+pub const MAX_PREFERENCES_BYTES: usize = 1024;
+
+/// Longest key or value of a free-form preference entry
+pub const MAX_PREFERENCE_ENTRY_LEN: usize = 64;
+
+/// Client settings kept on the player chain so they follow the player across devices
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "PlayerPreferencesInput")]
+pub struct PlayerPreferences {
+    /// Stance clients preselect for new turns
+    pub preferred_stance: Option<String>,
+    pub auto_accept_rematch: bool,
+    /// Skip storing battle summaries relayed by the lobby
+    pub mute_battle_summaries: bool,
+    pub mute_streak_bonuses: bool,
+    pub anonymized_betting: bool,
+    /// Settings this contract does not interpret, preserved for clients
+    pub extra: Vec<PreferenceEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "PreferenceEntryInput")]
+pub struct PreferenceEntry {
+    pub key: String,
+    pub value: String,
+}
+
+impl PlayerPreferences {
+    /// Rejection reason for preferences a player chain should not store
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.preferred_stance.as_deref().is_some_and(|stance| stance.parse::<Stance>().is_err()) {
+            return Err("invalid_stance");
+        }
+        if self.extra.iter().any(|entry| entry.key.len() > MAX_PREFERENCE_ENTRY_LEN || entry.value.len() > MAX_PREFERENCE_ENTRY_LEN) {
+            return Err("preference_entry_too_long");
+        }
+        let size = linera_sdk::bcs::serialized_size(self).expect("PlayerPreferences serialize to BCS");
+        if size > MAX_PREFERENCES_BYTES {
+            return Err("preferences_too_large");
+        }
+        Ok(())
+    }
+}
+
+/// Most XP one battle awards, so character XP totals stay far from overflow
+pub const MAX_XP_PER_BATTLE: u64 = 100_000;
+
+/// Fighters per side of the team battles the lobby's team queue forms
+pub const TEAM_SIZE: usize = 2;
+
+/// Most fighters a side of a team battle may field
+pub const MAX_TEAM_SIZE: usize = 4;
+
+/// Most friends, and most unanswered friend requests, a player chain keeps
+pub const MAX_FRIENDS: usize = 100;
+
+/// Most player chains in one party; only parties of up to `TEAM_SIZE` queue for team battles
+pub const MAX_PARTY_SIZE: usize = 8;
+
+/// A player chain in a party, with the account that owns it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct PartyMember {
+    pub owner: AccountOwner,
+    pub chain: ChainId,
+}
+
+/// Player chains that queue and enter tournaments together, as their leader announced it;
+/// the leader's chain names the party and is its first member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct Party {
+    pub leader: ChainId,
+    pub members: Vec<PartyMember>,
+}
+
+impl Party {
+    pub fn has(&self, chain: ChainId) -> bool {
+        self.members.iter().any(|member| member.chain == chain)
+    }
+}
+
+/// Most turns a battle format may ask for in one round
+pub const MAX_TURNS_PER_ROUND: u8 = 10;
+
+/// Combat knobs a battle runs under, stamped on the battle chain at initialization; the lobby's
+/// rules set the format of every battle it opens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "BattleRulesInput")]
+pub struct BattleRules {
+    pub max_rounds: u8,
+    /// Turns each fighter submits per round
+    pub turns_per_round: u8,
+    /// Time fighters are given to submit a round's turns. The deadline is advertised to
+    /// clients, not enforced: a fighter who stalls past it forfeits nothing
+    pub round_duration_secs: u64,
+    /// How long fighters have to reveal a sealed turn once both have locked it in
+    pub reveal_window_secs: u64,
+    /// Special cooldown of Warriors and Mages; every other class's cooldown differs from it by
+    /// as much as the two differ by default
+    pub special_cooldown: u8,
+    pub xp_for_win: u64,
+    pub xp_for_loss: u64,
+    /// XP multiplier in basis points (10000 = no boost)
+    pub xp_boost_bps: u16,
+    /// Restrict each class to its `allowed_stances`; casual lobbies may turn this off
+    pub class_locked_stances: bool,
+    /// Chance a Counter-stance defender answers a landed attack
+    pub counter_chance: Bps,
+    /// Share of the attack's damage a counter deals back, in basis points
+    pub counter_damage_bps: u16,
+    /// Overtime: rounds fought past `max_rounds` while both fighters stand, before the tiebreak
+    /// decides. Zero leaves the round cap to the tiebreak alone
+    pub sudden_death_rounds: u8,
+    /// Damage multiplier each sudden-death round adds on top of the round before, in basis
+    /// points, so overtime escalates until someone drops
+    pub sudden_death_step_bps: u16,
+}
+
+impl Default for BattleRules {
+    fn default() -> Self {
+        Self {
+            max_rounds: 10,
+            turns_per_round: TURNS_PER_ROUND,
+            round_duration_secs: 120,
+            reveal_window_secs: REVEAL_WINDOW.as_micros() / 1_000_000,
+            special_cooldown: 3,
+            xp_for_win: 150,
+            xp_for_loss: 50,
+            xp_boost_bps: 10_000,
+            class_locked_stances: true,
+            counter_chance: Bps(4_000),
+            counter_damage_bps: 4_000,
+            sudden_death_rounds: 0,
+            sudden_death_step_bps: 5_000,
+        }
+    }
+}
+
+impl BattleRules {
+    /// Rejection reason for rules no battle could be fought under
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.max_rounds == 0 {
+            return Err("no_rounds");
+        }
+        if self.turns_per_round == 0 || self.turns_per_round > MAX_TURNS_PER_ROUND {
+            return Err("invalid_turns_per_round");
+        }
+        if self.round_duration_secs == 0 || self.reveal_window_secs == 0 {
+            return Err("zero_timeout");
+        }
+        if self.last_round().is_none() {
+            return Err("too_many_rounds");
+        }
+        Ok(())
+    }
+
+    /// Last round a battle under these rules can reach, sudden death included
+    pub fn last_round(&self) -> Option<u8> {
+        self.max_rounds.checked_add(self.sudden_death_rounds)
+    }
+
+    /// Damage multiplier `round` fights under, in bps: none in regulation, then one more
+    /// `sudden_death_step_bps` for each round of overtime
+    pub fn damage_bps(&self, round: u8) -> u32 {
+        let overtime = round.saturating_sub(self.max_rounds) as u32;
+        10_000 + overtime * self.sudden_death_step_bps as u32
+    }
+
+    /// How long a sealed turn stays open for its reveal
+    pub fn reveal_window(&self) -> TimeDelta {
+        TimeDelta::from_secs(self.reveal_window_secs)
+    }
+
+    /// Cooldown of `class`'s special under these rules, never below one attack
+    pub fn special_cooldown_for(&self, class: CharacterClass) -> u8 {
+        let default = Self::default().special_cooldown;
+        class.special_cooldown().saturating_add(self.special_cooldown).saturating_sub(default).max(1)
+    }
+
+    /// Compact FNV-1a digest of the BCS encoding, stable across builds
+    pub fn digest(&self) -> u64 {
+        fnv1a(&linera_sdk::bcs::to_bytes(self).expect("BattleRules serialize to BCS"))
+    }
+
+    /// XP awarded for a result after applying the boost, capped at `MAX_XP_PER_BATTLE`; the base
+    /// the lobby's `rewards::XpCurve` scales
+    pub fn awarded_xp(&self, won: bool) -> u64 {
+        let base = if won { self.xp_for_win } else { self.xp_for_loss };
+        (base.saturating_mul(self.xp_boost_bps as u64) / 10_000).min(MAX_XP_PER_BATTLE)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// SplitMix64 step: advance `seed` and return the next deterministic value
+fn splitmix64(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut x = *seed;
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Chance in basis points that a battle's winner receives an item
+pub const ITEM_DROP_CHANCE_BPS: u64 = 2_500;
+
+/// Equipment slot; a character holds at most one item per slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum ItemSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum ItemRarity {
+    Common,
+    Rare,
+    Epic,
+}
+
+impl ItemRarity {
+    /// Drop weights in basis points, rarest last
+    const TABLE: [(ItemRarity, u64); 3] = [(Self::Common, 7_000), (Self::Rare, 2_500), (Self::Epic, 500)];
+
+    /// Largest bonus an item of this rarity can grant to its stat
+    pub fn max_bonus_bps(self) -> i16 {
+        match self {
+            Self::Common => 200,
+            Self::Rare => 500,
+            Self::Epic => 1_000,
+        }
+    }
+
+    /// Rarity drawn by `roll`, a value below 10 000 weighted by `TABLE`
+    fn from_roll(mut roll: u64) -> Self {
+        for (candidate, weight) in Self::TABLE {
+            if roll < weight {
+                return candidate;
+            }
+            roll -= weight;
+        }
+        Self::Common
+    }
+}
+
+/// Stat modifiers in basis points, as carried by snapshots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct PassiveMods {
+    pub attack_bps: i16,
+    pub defense_bps: i16,
+    pub crit_bps: i16,
+}
+
+impl PassiveMods {
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self {
+            attack_bps: self.attack_bps.saturating_add(other.attack_bps),
+            defense_bps: self.defense_bps.saturating_add(other.defense_bps),
+            crit_bps: self.crit_bps.saturating_add(other.crit_bps),
+        }
+    }
+}
+
+/// How long after finalization participants may attest a battle's outcome
+pub const ATTESTATION_WINDOW: TimeDelta = TimeDelta::from_secs(24 * 3600);
+
+/// Participants' verdict on a finalized battle. Outcomes stand either way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum Attestation {
+    #[default]
+    Unattested,
+    /// Both participants agreed with the outcome
+    Attested,
+    /// A participant contested the outcome; flagged for operator review
+    Disputed,
+}
+
+/// Compact battle result pushed to subscribed observers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct BattleResultSummary {
+    pub battle_chain: ChainId,
+    pub winner: AccountOwner,
+    pub rounds_played: u8,
+    pub total_stake: Amount,
+    pub rules_digest: u64,
+    pub end_reason: BattleEndReason,
+    pub completed_at: Timestamp,
+}
+
+/// One fighter's outcome, relayed by the lobby to the fighter's player chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FighterResult {
+    pub player: AccountOwner,
+    pub character_id: String,
+    pub won: bool,
+    pub payout: Amount,
+    pub xp_gained: u64,
+    pub elo_change: i32,
+    pub item_drop: Option<ItemDrop>,
+    /// What the fighter dealt and took; player chains advance their quests with it
+    pub combat_stats: CombatStats,
+    /// Wins in a row the fighter came out of the battle with; filled in by the lobby, which
+    /// keeps streaks
+    pub win_streak: u64,
+}
+
+/// Name of the stream every battle chain publishes its `BattleEvent`s on
+pub const BATTLE_EVENT_STREAM: &[u8] = b"battle";
+
+/// What a battle chain publishes as it plays out, so spectators and indexers can follow it
+/// live instead of polling. Choices stay hidden until the turn they belong to resolves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BattleEvent {
+    /// A fighter locked in a turn, in the clear or sealed behind a commitment
+    TurnSubmitted {
+        round: u8,
+        turn: u8,
+        player: AccountOwner,
+        sealed: bool,
+    },
+    /// A fighter sent an emote with a turn submitted in the clear; at most one per fighter
+    /// per turn
+    Emoted {
+        round: u8,
+        turn: u8,
+        player: AccountOwner,
+        emote: Emote,
+    },
+    /// Both fighters' choices for a turn met and it executed
+    TurnResolved {
+        round: u8,
+        turn: u8,
+        /// Each fighter's stance and whether they asked for their special, player 1 first
+        choices: [(Stance, bool); 2],
+        player1_hp: u32,
+        player2_hp: u32,
+    },
+    /// A fighter asked to close the round; it closes once both have
+    RoundCloseRequested {
+        round: u8,
+        player: AccountOwner,
+    },
+    /// Both fighters closed a round; `BattleFinished` follows if it was the last
+    RoundEnded {
+        round: u8,
+        player1_hp: u32,
+        player2_hp: u32,
+    },
+    /// The battle is over; no winner for a draw
+    BattleFinished {
+        winner: Option<AccountOwner>,
+        end_reason: BattleEndReason,
+        rounds_played: u8,
+    },
+}
+
+/// Item awarded by a battle, delivered to the winner's player chain, or forged on a player chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct ItemDrop {
+    pub item_id: String,
+    pub slot: ItemSlot,
+    pub rarity: ItemRarity,
+    pub bonuses: PassiveMods,
+}
+
+impl ItemDrop {
+    /// Roll the drop for a battle from its entropy; every input yields the same result on replay
+    pub fn roll(battle_chain: ChainId, entropy: u64) -> Option<Self> {
+        let mut seed = fnv1a(battle_chain.to_string().as_bytes()) ^ entropy;
+        let mut next = || splitmix64(&mut seed);
+
+        if next() % 10_000 >= ITEM_DROP_CHANCE_BPS {
+            return None;
+        }
+        let slot = [ItemSlot::Weapon, ItemSlot::Armor, ItemSlot::Trinket][(next() % 3) as usize];
+        let (rarity, bonuses) = Self::roll_bonuses(slot, &mut next);
+
+        Some(Self {
+            item_id: format!("item-{:016x}", next()),
+            slot,
+            rarity,
+            bonuses,
+        })
+    }
+
+    /// Forge `item_id` for `slot` on `player_chain`, rolling its rarity and bonus as a drop
+    /// would; every input yields the same result on replay
+    pub fn forge(player_chain: ChainId, item_id: &str, slot: ItemSlot, entropy: u64) -> Self {
+        let mut seed = fnv1a(player_chain.to_string().as_bytes()) ^ fnv1a(item_id.as_bytes()).rotate_left(32) ^ entropy;
+        let mut next = || splitmix64(&mut seed);
+        let (rarity, bonuses) = Self::roll_bonuses(slot, &mut next);
+        Self { item_id: item_id.to_string(), slot, rarity, bonuses }
+    }
+
+    /// Rarity and bonus to the stat `slot` improves
+    fn roll_bonuses(slot: ItemSlot, next: &mut impl FnMut() -> u64) -> (ItemRarity, PassiveMods) {
+        let rarity = ItemRarity::from_roll(next() % 10_000);
+        let bonus = 1 + (next() % rarity.max_bonus_bps() as u64) as i16;
+        let bonuses = match slot {
+            ItemSlot::Weapon => PassiveMods { attack_bps: bonus, ..PassiveMods::default() },
+            ItemSlot::Armor => PassiveMods { defense_bps: bonus, ..PassiveMods::default() },
+            ItemSlot::Trinket => PassiveMods { crit_bps: bonus, ..PassiveMods::default() },
+        };
+        (rarity, bonuses)
+    }
+}
+
+/// Application parameters, fixed for every chain of a deployment
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Parameters {
+    /// Accept `GenerateFixtures`; never set on mainnet deployments
+    pub dev_fixtures: bool,
+}
+
 /// Initialization argument for different chain types
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InitializationArgument {
     pub variant: ChainVariant,
     pub treasury_owner: Option<AccountOwner>,
     pub platform_fee_bps: Option<u16>,
+    /// Concurrent queue/battle engagements allowed per player chain (defaults to 1)
+    pub max_concurrent_battles: Option<u8>,
+    /// Whether new prediction markets list individual bettors (defaults to false)
+    pub public_bettors: Option<bool>,
+    /// Expose market and private battle ids as keyed permutations of their sequence numbers,
+    /// opaque but decodable by anyone (defaults to false)
+    pub obfuscated_ids: Option<bool>,
+    /// Where rounding dust from market payouts goes (defaults to the platform)
+    pub market_dust_to: Option<fees::DustDestination>,
+    /// How widely the lobby matches players by rating (defaults to `MatchmakingConfig::default()`)
+    pub matchmaking: Option<matchmaking::MatchmakingConfig>,
 }
 
 /// Chain variant type
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ChainVariant {
     Lobby,
     Battle,
@@ -134,7 +912,74 @@ pub struct MajorulesAbi;
 
 impl ContractAbi for MajorulesAbi {
     type Operation = Operation;
-    type Response = ();
+    type Response = OperationResponse;
+}
+
+/// What an operation reports back to its signer. Refusals carry the same snake_case reason
+/// the chain logs with its rejections; callers that are not allowed to act at all, such as a
+/// non-admin changing lobby settings, still fail the whole block
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperationResponse {
+    /// Applied, or nothing for this chain to do
+    #[default]
+    Done,
+    TurnAck(TurnAck),
+    /// The signer's player chain, opened by this operation or registered earlier
+    PlayerChain { chain_id: ChainId },
+    /// The operation was refused and did not take effect
+    Rejected { reason: String },
+}
+
+impl OperationResponse {
+    pub fn rejected(reason: &str) -> Self {
+        Self::Rejected { reason: reason.to_string() }
+    }
+}
+
+/// Battle HUD snapshot returned by `SubmitTurn`, taken after any turn the submission executed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnAck {
+    pub accepted: bool,
+    /// Rejection reason when not accepted
+    pub reason: Option<String>,
+    /// Whether this submission completed the turn and it was executed
+    pub executed: bool,
+    pub my_hp: u32,
+    pub opponent_hp: u32,
+    pub my_combo: u8,
+    pub my_cooldown: u8,
+    /// Next turn of the current round the signer has yet to submit
+    pub next_turn_index: Option<u8>,
+    /// All turns of the round have executed, or the battle ended
+    pub round_complete: bool,
+    /// Opponent's choices for the executed turn; `None` when not executed or hidden
+    pub opponent_stance: Option<Stance>,
+    pub opponent_used_special: Option<bool>,
+    /// Deadline of the battle's current phase, if it has one
+    pub phase_deadline: Option<Timestamp>,
+    /// Chain time the acknowledgement was taken at, so clients can correct the countdown to
+    /// `phase_deadline` for their clock's drift
+    pub server_time: Timestamp,
+}
+
+impl TurnAck {
+    pub fn rejected(reason: &str) -> Self {
+        Self {
+            reason: Some(reason.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+/// A team fighter's choices for one turn
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, InputObject)]
+pub struct TeamTurnChoice {
+    pub stance: String,
+    pub use_special: bool,
+    /// Enemy seat to attack; the first enemy still standing takes it when that one is down
+    pub target: u8,
+    /// Sent with the turn for both sides and spectators to see
+    pub emote: Option<Emote>,
 }
 
 impl ServiceAbi for MajorulesAbi {
@@ -150,35 +995,205 @@ pub enum Operation {
     Increment { value: u64 },
 
     // ========== LOBBY OPERATIONS ==========
-    /// Join matchmaking queue with character and stake (auto-matches when 2 players)
+    /// Join matchmaking queue with character and stake (auto-matches when 2 players); the
+    /// stake stays locked on the player chain until the entry is matched or released
     JoinQueue { 
         character_id: String, 
-        stake: Amount 
+        stake: Amount,
+        mode: QueueMode,
+        /// Games of the series to play, 1 for a single battle; only equal lengths are matched
+        best_of: u8,
     },
     
-    /// Leave matchmaking queue
+    /// Leave matchmaking queue with every queued character, unlocking their stakes on the
+    /// player chain
     LeaveQueue,
     
-    /// Create private battle and return battle ID
+    /// Create private battle and return battle ID. `invite` is the `invite_digest` of a secret
+    /// the host shares with the opponent they mean to fight
     CreatePrivateBattle { 
         character_id: String, 
-        stake: Amount 
+        stake: Amount,
+        invite: [u8; 32],
     },
     
-    /// Join existing private battle by ID
+    /// Join existing private battle by ID with the invite secret its host shared
     JoinPrivateBattle { 
         battle_id: u64,
+        invite_secret: [u8; 32],
         character_id: String, 
         stake: Amount 
     },
+
+    /// Fight a bot at `difficulty` with a character, for no stake and a share of the usual XP
+    StartPracticeBattle {
+        character_id: String,
+        difficulty: practice::PracticeDifficulty,
+    },
     
+    /// Enter a lobby tournament with a character, paying `entry_fee` from the chain's balance;
+    /// the fee must match the tournament's and is refunded if the lobby refuses the entry
+    JoinTournament {
+        tournament_id: u64,
+        character_id: String,
+        entry_fee: Amount,
+    },
+
+    /// Open a single-elimination tournament that starts itself at `start_time` (treasury only).
+    /// A tournament for `party`, named by its leader's chain, only takes that party's members
+    CreateTournament {
+        name: String,
+        terms: TournamentTerms,
+        start_time: Timestamp,
+        registration_closes_at: Option<Timestamp>,
+        party: Option<ChainId>,
+    },
+
     /// Update global leaderboard for specific player
     UpdateLeaderboard { 
         player: AccountOwner 
     },
     
-    /// Create player chain for user
+    /// Have the summary of an active battle pushed to the caller's player chain
+    SubscribeToBattle {
+        battle_chain: ChainId,
+    },
+    
+    /// Create player chain for user; one already registered is returned instead
     CreatePlayerChain,
+
+    /// Open a new player chain in place of the caller's lost one, keeping their lobby records.
+    /// The old chain and the characters on it are abandoned. Refused while the caller is queued
+    /// or fighting, so no stake, payout or result is ever left to forward from the old chain
+    ReplacePlayerChain,
+    
+    /// Replace the rules new battles run under and bump the rules version (treasury or admin)
+    UpdateBattleRules {
+        rules: BattleRules,
+    },
+    
+    /// Set the per-owner daily creation limits (treasury or admin)
+    SetCreationCaps {
+        player_chains: u32,
+        private_battles: u32,
+        queue_joins: u32,
+    },
+    
+    /// Set the thresholds for entering the ranked queue (treasury or admin)
+    SetRankedGates {
+        gates: RankedGates,
+    },
+
+    /// Set what an account must show before staked or ranked queues take it, for the lobby and
+    /// player chains created from now on (treasury or admin)
+    SetProgressionGates {
+        gates: ProgressionGates,
+    },
+
+    /// Set how far battles move ratings (treasury or admin)
+    SetEloConfig {
+        config: elo::EloConfig,
+    },
+
+    /// Set how far characters can level on player chains created from now on (treasury or admin)
+    SetLevelingConfig {
+        config: leveling::LevelingConfig,
+    },
+
+    /// Set what minting a character takes on player chains created from now on (treasury or admin)
+    SetMintTerms {
+        terms: minting::MintTerms,
+    },
+    
+    /// Exempt an owner from the daily creation limits, e.g. bots and tournament organizers (treasury or admin)
+    SetCreationExemption {
+        owner: AccountOwner,
+        exempt: bool,
+    },
+
+    /// Set the fee on battles opened and markets settled from now on (treasury or admin)
+    UpdatePlatformFee {
+        platform_fee_bps: u16,
+    },
+
+    /// Set how matchmaking's rating windows open up (treasury or admin)
+    UpdateMatchmakingConfig {
+        config: matchmaking::MatchmakingConfig,
+    },
+
+    /// Set the minimum stake and matchmaking settings of one queue (treasury or admin)
+    SetQueueTerms {
+        mode: QueueMode,
+        terms: matchmaking::QueueTerms,
+    },
+
+    /// Set how battle XP scales with the opponent, the battle and the streak (treasury or admin)
+    UpdateXpCurve {
+        curve: rewards::XpCurve,
+    },
+
+    /// Set streak token bonuses, the streak insurance price and which streaks are announced
+    /// (treasury or admin)
+    UpdateStreakTerms {
+        terms: rewards::StreakTerms,
+    },
+
+    /// Set whether markets opened from now on pay pari-mutuel or at locked odds (treasury or
+    /// admin)
+    SetMarketPricing {
+        pricing: odds::MarketPricing,
+    },
+
+    /// Cap what one bettor stakes and what one market pools; bets already placed stand
+    /// (treasury or admin)
+    SetBettingLimits {
+        limits: betting::BettingLimits,
+    },
+
+    /// Stop or resume pairing queued players; the queue stays open (treasury or admin)
+    PauseMatchmaking {
+        paused: bool,
+    },
+
+    /// Hand the treasury to another owner (treasury only)
+    SetTreasuryOwner {
+        owner: AccountOwner,
+    },
+
+    /// Name the owner who manages lobby settings alongside the treasury, or nobody (treasury only)
+    SetLobbyAdmin {
+        admin: Option<AccountOwner>,
+    },
+
+    /// Run up to `max_work` steps of lobby maintenance, earning a small bounty per step done
+    Crank {
+        max_work: u32,
+    },
+
+    /// Open a ranked season; each rating is soft-reset the first time it is read in the
+    /// season (treasury only)
+    StartSeason {
+        name: String,
+        terms: season::SeasonTerms,
+    },
+
+    /// Close the running season, freezing its standings and minting its rewards to the top
+    /// rated players (treasury only)
+    EndSeason,
+
+    /// Add to the community pool crank bounties are paid from (treasury only)
+    FundCommunityPool {
+        amount: Amount,
+    },
+
+    /// Populate the lobby with a deterministic world of players, completed battles and live
+    /// markets planned from `seed` (treasury only, and only where the parameters allow fixtures)
+    GenerateFixtures {
+        seed: u64,
+        players: u32,
+        battles: u32,
+        markets: u32,
+    },
     
     // ========== BATTLE OPERATIONS ==========
     /// Submit turn for current round
@@ -186,14 +1201,51 @@ pub enum Operation {
         round: u8, 
         turn: u8, 
         stance: String, 
-        use_special: bool 
+        use_special: bool,
+        /// Sent with the turn for the opponent and spectators to see
+        emote: Option<Emote>,
     },
     
+    /// Seal a turn of the current round; the opponent only learns it once it is revealed
+    CommitTurn {
+        round: u8,
+        turn: u8,
+        commitment: [u8; 32],
+    },
+
+    /// Open a sealed turn once both fighters locked it in, before the reveal window closes
+    RevealTurn {
+        round: u8,
+        turn: u8,
+        stance: String,
+        use_special: bool,
+        salt: [u8; 32],
+    },
+
+    /// Win by forfeit when the opponent left a sealed turn unrevealed past its reveal window
+    ClaimForfeit {
+        round: u8,
+        turn: u8,
+    },
+
+    /// Submit a turn of a team battle in the clear
+    SubmitTeamTurn {
+        round: u8,
+        turn: u8,
+        choice: TeamTurnChoice,
+    },
+
     /// Execute current round when all turns submitted (auto-executed)
     ExecuteRound,
+
+    /// Co-sign (or contest) a finalized battle's outcome within the attestation window
+    AttestResult {
+        agree: bool,
+    },
     
     // ========== PLAYER OPERATIONS ==========
-    /// Mint new character NFT
+    /// Mint new character NFT, within the chain's roster cap and cooldown; mints past the free
+    /// ones are paid from the battle token balance
     MintCharacter { 
         character_id: String, 
         class: String 
@@ -205,10 +1257,100 @@ pub enum Operation {
         xp_to_spend: u64 
     },
     
+    /// Bring a dead character back with `STARTING_LIVES`, for what a paid mint costs
+    ReviveCharacter {
+        character_id: String,
+    },
+
     /// Set active character for battles
     SetActiveCharacter { 
         character_id: String 
     },
+
+    /// Forge a new item for `slot` under `item_id`, paying the mint terms' item cost
+    MintItem {
+        item_id: String,
+        slot: ItemSlot,
+    },
+
+    /// Insure the owner's win streak against their next defeat, paying the lobby's insurance
+    /// price out of at most `max_price`; the rest is returned
+    BuyStreakInsurance {
+        max_price: Amount,
+    },
+
+    /// Collect a completed daily quest's reward: its XP goes to `character_id`,
+    /// its battle tokens are minted by the lobby
+    ClaimQuestReward {
+        quest_id: String,
+        character_id: String,
+    },
+
+    /// Equip an owned item on a character, one item per slot
+    EquipItem {
+        character_id: String,
+        item_id: String,
+    },
+
+    /// Take an item off the character wearing it
+    UnequipItem {
+        item_id: String,
+    },
+
+    /// Replace the player's stored client preferences
+    SetPreferences {
+        prefs: PlayerPreferences,
+    },
+
+    /// Give a character to `to_owner` on their player chain
+    TransferCharacter {
+        character_id: String,
+        to_owner: AccountOwner,
+        to_chain: ChainId,
+    },
+
+    /// Offer a character for sale at `price` battle tokens, replacing any earlier price
+    ListCharacterForSale {
+        character_id: String,
+        price: Amount,
+    },
+
+    /// Withdraw a character from sale
+    DelistCharacter {
+        character_id: String,
+    },
+
+    /// Buy a character listed on `seller_chain`; `price` is escrowed until the seller answers
+    /// and must match the listing
+    BuyCharacter {
+        seller_chain: ChainId,
+        character_id: String,
+        price: Amount,
+    },
+
+    /// Ask the owner of `to_chain` to be friends; only friends can invite each other to parties
+    SendFriendRequest {
+        to_chain: ChainId,
+    },
+
+    /// Accept the friend request `from_chain` sent
+    AcceptFriend {
+        from_chain: ChainId,
+    },
+
+    /// Invite a friend into this chain's party, founding the party with this chain as its
+    /// leader if it has none
+    InviteToParty {
+        to_chain: ChainId,
+    },
+
+    /// Join the party led from `leader_chain`, which invited this chain
+    AcceptPartyInvite {
+        leader_chain: ChainId,
+    },
+
+    /// Leave the party; a leader leaving disbands it
+    LeaveParty,
     
 
     
@@ -227,6 +1369,14 @@ pub enum Operation {
         amount: Amount 
     },
     
+    /// Bet from the caller's player chain: `amount` is held out of its balance and sent to
+    /// the lobby, which places it on the market `market_id` or refuses it back
+    PlaceBetOnMarket {
+        market_id: u64,
+        predicted_winner: ChainId,
+        amount: Amount,
+    },
+
     /// Close market (stop accepting bets)
     CloseMarket { 
         market_id: u64 
@@ -238,17 +1388,23 @@ pub enum Operation {
         winner_chain: ChainId 
     },
     
-    /// Claim winnings from settled market
+    /// Claim winnings from a settled market, or the stake back from a voided one
     ClaimWinnings { 
         market_id: u64 
     },
     
     // ========== TOKEN OPERATIONS ==========
-    /// Transfer battle tokens between accounts
+    /// Transfer battle tokens from the caller's player chain to another registered player
     TransferTokens { 
         to: AccountOwner, 
         amount: Amount 
     },
+
+    /// Create battle tokens on a registered player's chain (lobby, treasury only)
+    MintTokens {
+        to: AccountOwner,
+        amount: Amount,
+    },
 }
 
 /// Cross-chain messages between different chain types
@@ -257,44 +1413,104 @@ pub enum Message {
     // ===== LOBBY → BATTLE =====
     /// Initialize new battle chain with participants
     InitializeBattle {
-        player1: BattleParticipant,
-        player2: BattleParticipant,
+        player1: Box<BattleParticipant>,
+        player2: Box<BattleParticipant>,
+        lobby_chain_id: ChainId,
+        platform_fee_bps: u16,
+        treasury_owner: AccountOwner,
+        rules: BattleRules,
+        rules_version: u32,
+    },
+
+    /// Initialize a team battle chain with both sides, each fighter in its seat
+    InitializeTeamBattle {
+        teams: [Vec<BattleParticipant>; 2],
         lobby_chain_id: ChainId,
         platform_fee_bps: u16,
         treasury_owner: AccountOwner,
+        rules: BattleRules,
+        rules_version: u32,
+    },
+
+    /// Initialize a practice battle: `player` against the bot, which fights a copy of the
+    /// player's character at `difficulty`
+    InitializePracticeBattle {
+        player: Box<BattleParticipant>,
+        difficulty: practice::PracticeDifficulty,
+        lobby_chain_id: ChainId,
+        treasury_owner: AccountOwner,
+        rules: BattleRules,
+        rules_version: u32,
     },
     
     // ===== BATTLE → PLAYER =====
-    /// Send battle result to player chain
+    /// The battle's own account of it to each fighter's player chain, alongside the lobby's
+    /// `UpdatePlayerStats`. Whichever arrives second completes the battle's history record;
+    /// payouts and XP are only ever credited from the lobby's
     BattleResult {
-        winner: AccountOwner,
-        loser: AccountOwner,
-        winner_payout: Amount,
-        xp_gained: u64,
-        battle_stats: CombatStats,
         battle_chain: ChainId,
+        character_id: String,
+        opponent: AccountOwner,
+        stake: Amount,
+        payout: Amount,
+        rounds_played: u8,
+        battle_stats: CombatStats,
+        end_reason: BattleEndReason,
     },
     
     // ===== BATTLE → LOBBY =====
-    /// Notify lobby of battle completion for leaderboard
+    /// Notify lobby of battle completion for leaderboard. A `Draw` end reason has no winner:
+    /// `winner` and `loser` are then just player 1 and player 2, and every result a refund
     BattleCompleted {
         winner: AccountOwner,
         loser: AccountOwner,
         rounds_played: u8,
         total_stake: Amount,
         battle_stats: (CombatStats, CombatStats), // (winner_stats, loser_stats)
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        /// Both fighters' results, so one message settles the battle. Empty when the battle
+        /// reports them separately as `BattleResultWithElo`
+        results: Vec<FighterResult>,
     },
     
-    /// Battle result with ELO changes for lobby processing
+    /// Notify lobby of a team battle's completion, with every fighter's result. Team battles are
+    /// unrated, so the lobby relays the results as they are
+    TeamBattleCompleted {
+        rounds_played: u8,
+        total_stake: Amount,
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        results: Vec<FighterResult>,
+    },
+
+    /// Notify lobby that a practice battle ended, with the player's result. Practice is
+    /// unrated and unstaked, so only the XP reaches the player
+    PracticeBattleCompleted {
+        result: FighterResult,
+    },
+
+    /// Both players agreed with the outcome, or one of them contested it
+    ResultAttestation {
+        attestation: Attestation,
+    },
+
+    /// One fighter's result with ELO changes for lobby processing. The lobby accepts it in any
+    /// order relative to `BattleCompleted`, at most once per fighter
     BattleResultWithElo {
         player: AccountOwner,
+        character_id: String,
         opponent: AccountOwner,
         won: bool,
         payout: Amount,
         xp_gained: u64,
         elo_change: i32,
         battle_stats: CombatStats,
+        rounds_played: u8,
         battle_chain: ChainId,
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        item_drop: Option<ItemDrop>,
     },
     
     // ===== PLAYER → LOBBY =====
@@ -304,14 +1520,19 @@ pub enum Message {
         player_chain: ChainId,
         character_snapshot: CharacterSnapshot,
         stake: Amount,
+        mode: QueueMode,
+        best_of: u8,
+        /// Leader chain of the player's party, for the team queue to keep on one side
+        party: Option<ChainId>,
     },
     
-    /// Request to create private battle
+    /// Request to create private battle, joinable with the secret behind `invite`
     RequestCreatePrivateBattle {
         player: AccountOwner,
         player_chain: ChainId,
         character_snapshot: CharacterSnapshot,
         stake: Amount,
+        invite: [u8; 32],
     },
     
     /// Request to join private battle by ID
@@ -319,12 +1540,33 @@ pub enum Message {
         player: AccountOwner,
         player_chain: ChainId,
         battle_id: u64,
+        invite_secret: [u8; 32],
         character_snapshot: CharacterSnapshot,
         stake: Amount,
     },
+
+    /// Request to enter a tournament, with the entry fee the player chain took
+    RequestJoinTournament {
+        player: AccountOwner,
+        player_chain: ChainId,
+        tournament_id: u64,
+        character_snapshot: CharacterSnapshot,
+        entry_fee: Amount,
+        /// Leader chain of the player's party, checked by party tournaments
+        party: Option<ChainId>,
+    },
+
+    /// Request a practice battle against the bot
+    RequestPracticeBattle {
+        player: AccountOwner,
+        player_chain: ChainId,
+        character_snapshot: CharacterSnapshot,
+        difficulty: practice::PracticeDifficulty,
+    },
     
     // ===== BATTLE → PREDICTION =====
-    /// Notify prediction market that battle started
+    /// Notify the lobby or a prediction chain that the battle's first turn is in, closing
+    /// betting on it
     BattleStarted {
         battle_chain: ChainId,
     },
@@ -360,29 +1602,234 @@ pub enum Message {
     /// Update player stats after battle with ELO
     UpdatePlayerStats {
         player: AccountOwner,
+        character_id: String,
         won: bool,
+        payout: Amount,
         xp_gained: u64,
         elo_change: i32,
         battle_chain: ChainId,
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        item_drop: Option<ItemDrop>,
+        combat_stats: CombatStats,
+        win_streak: u64,
     },
     
     // ===== PLAYER → LOBBY =====
+    /// Confirm that a battle payout was credited on the winner's chain
+    PayoutReceipt {
+        battle_chain: ChainId,
+        player: AccountOwner,
+        amount: Amount,
+    },
+    
     /// Response with player stats
     PlayerStatsResponse {
         player: AccountOwner,
         stats: PlayerGlobalStats,
     },
+
+    /// `player` minted a character; its first snapshot, for the lobby's roster
+    CharacterMinted {
+        player: AccountOwner,
+        snapshot: CharacterSnapshot,
+    },
+
+    /// A character levelled up, or arrived on `player`'s chain after a transfer; its new base
+    /// snapshot, for the lobby's roster
+    CharacterUpdated {
+        player: AccountOwner,
+        snapshot: CharacterSnapshot,
+    },
+
+    /// A character left the sending chain for `to_chain`, which takes it over in the lobby's
+    /// roster once it reports the character
+    CharacterMoved {
+        character_id: String,
+        to_chain: ChainId,
+    },
+
+    /// Move tokens the sending chain already debited from `from` to `to`'s chain
+    RequestTokenTransfer {
+        from: AccountOwner,
+        to: AccountOwner,
+        amount: Amount,
+    },
+
+    /// Bet `amount`, which the sending chain holds out of `bettor`'s balance, on
+    /// `predicted_winner` in the market `market_id`. Answered with `BetPlaced` or `BetRefused`
+    RequestPlaceBet {
+        bettor: AccountOwner,
+        market_id: u64,
+        predicted_winner: ChainId,
+        amount: Amount,
+    },
+
+    /// `player`'s chain debited `amount` to mint a character or forge an item, for the treasury
+    MintFeePaid {
+        player: AccountOwner,
+        amount: Amount,
+    },
+
+    /// `player`'s chain debited `paid` to insure their win streak; the lobby keeps its price
+    /// and credits back the rest, or all of it when insurance is refused
+    StreakInsurancePaid {
+        player: AccountOwner,
+        paid: Amount,
+    },
+
+    /// `player` completed `quest_id` on `day`; the lobby mints its reward once
+    QuestRewardClaimed {
+        player: AccountOwner,
+        day: u64,
+        quest_id: String,
+    },
+
+    /// A character lost a life in a ranked defeat or was revived; `revive_fee` is what its
+    /// chain debited for a revival, for the treasury
+    CharacterLivesChanged {
+        player: AccountOwner,
+        character_id: String,
+        lives_remaining: u8,
+        revive_fee: Amount,
+    },
     
+    // ===== PLAYER → PLAYER =====
+    /// A character handed to `to_owner`; `price` is what the receiving chain escrowed for it,
+    /// zero for gifts. Sent tracked, so a refusing chain bounces the character back
+    CharacterTransferred {
+        character: CharacterRecord,
+        to_owner: AccountOwner,
+        price: Amount,
+    },
+
+    /// Offer to buy a listed character, paid from the buyer chain's escrow
+    PurchaseCharacter {
+        buyer: AccountOwner,
+        character_id: String,
+        price: Amount,
+    },
+
+    /// The seller chain did not sell; the buyer chain releases its escrow
+    PurchaseRefused {
+        character_id: String,
+    },
+
+    /// `from` asks to be friends with the receiving chain's owner
+    FriendRequest {
+        from: AccountOwner,
+    },
+
+    /// `friend` accepted a friend request the receiving chain sent
+    FriendAccepted {
+        friend: AccountOwner,
+    },
+
+    /// The sending chain's party leader invites the receiving chain in
+    PartyInvite,
+
+    /// `member` accepted the receiving leader chain's invite
+    JoinParty {
+        member: AccountOwner,
+    },
+
+    /// The party as its leader now has it, or `None` once the receiving chain is out of it
+    PartyUpdated {
+        party: Option<Party>,
+    },
+
+    /// The sending chain left the receiving chain's party
+    PartyLeft,
+
     // ===== LOBBY → PLAYER =====
     /// Notify player that private battle was created
     PrivateBattleCreated {
         battle_id: u64,
     },
 
+    /// Notify player that a queued character was matched into a battle for `stake`; any
+    /// locked stake beyond it is unlocked
+    BattleMatched {
+        battle_chain: ChainId,
+        character_id: String,
+        stake: Amount,
+    },
+
+    /// Final summary of a battle the receiving chain subscribed to
+    BattleSummaryNotification {
+        summary: BattleResultSummary,
+    },
+
+    /// A character is out of a tournament: refused, refunded, eliminated or placed.
+    /// `payout` is a refunded fee or a prize, credited to the chain's balance
+    TournamentExited {
+        tournament_id: u64,
+        character_id: String,
+        payout: Amount,
+    },
+
+    /// A queued character was matched into a series: `stake` of its locked stake goes into
+    /// the series pot and the rest is unlocked. The character stays held between games
+    SeriesStarted {
+        series_id: u64,
+        character_id: String,
+        stake: Amount,
+    },
+
+    /// A series is over: the character is released and `payout` from the pot is credited
+    SeriesEnded {
+        series_id: u64,
+        character_id: String,
+        payout: Amount,
+    },
+
+    /// Confirm that the lobby queued a character
+    QueueJoined {
+        character_id: String,
+    },
+
+    /// Notify player that their queue entry was removed, unlocking its stake
+    QueueLeft {
+        character_id: String,
+    },
+
+    /// A practice battle ended: the character is released and credited `xp_gained`. Practice
+    /// counts toward no stats
+    PracticeBattleEnded {
+        battle_chain: ChainId,
+        character_id: String,
+        won: bool,
+        xp_gained: u64,
+    },
+
+    /// The lobby placed the chain's bet on `market_id`; the held amount now sits in the market
+    BetPlaced {
+        market_id: u64,
+    },
+
+    /// The lobby refused the chain's bet on `market_id`; the held amount is credited back
+    BetRefused {
+        market_id: u64,
+    },
+
+    /// Add tokens to `owner`'s balance: minted, transferred to them, or a refused transfer
+    /// returned to its sender
+    CreditTokens {
+        owner: AccountOwner,
+        amount: Amount,
+    },
+
     /// Initialize player chain with lobby reference
     InitializePlayerChain {
         lobby_chain_id: ChainId,
         owner: AccountOwner,
+        max_concurrent_battles: u8,
+        /// Lobby's ranked thresholds, for failing fast before a request is sent
+        ranked_gates: RankedGates,
+        /// Lobby's progression gates, for failing fast like `ranked_gates`
+        progression: ProgressionGates,
+        leveling: leveling::LevelingConfig,
+        minting: minting::MintTerms,
     },
     
     /// Instantiate chain with specific variant
@@ -393,47 +1840,80 @@ pub enum Message {
     },
 }
 
-impl CharacterClass {
+impl std::str::FromStr for CharacterClass {
+    type Err = ();
+
     /// Parse from string
-    pub fn from_str(s: &str) -> Option<Self> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "warrior" => Some(CharacterClass::Warrior),
-            "assassin" => Some(CharacterClass::Assassin),
-            "mage" => Some(CharacterClass::Mage),
-            "tank" => Some(CharacterClass::Tank),
-            "trickster" => Some(CharacterClass::Trickster),
-            _ => None,
+            "warrior" => Ok(CharacterClass::Warrior),
+            "assassin" => Ok(CharacterClass::Assassin),
+            "mage" => Ok(CharacterClass::Mage),
+            "tank" => Ok(CharacterClass::Tank),
+            "trickster" => Ok(CharacterClass::Trickster),
+            _ => Err(()),
         }
     }
 }
 
 impl Stance {
+    pub const ALL: [Stance; 5] = [
+        Stance::Balanced,
+        Stance::Aggressive,
+        Stance::Defensive,
+        Stance::Berserker,
+        Stance::Counter,
+    ];
+}
+
+impl std::str::FromStr for Stance {
+    type Err = ();
+
     /// Parse from string
-    pub fn from_str(s: &str) -> Option<Self> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "balanced" => Some(Stance::Balanced),
-            "aggressive" => Some(Stance::Aggressive),
-            "defensive" => Some(Stance::Defensive),
-            "berserker" => Some(Stance::Berserker),
-            "counter" => Some(Stance::Counter),
-            _ => None,
+            "balanced" => Ok(Stance::Balanced),
+            "aggressive" => Ok(Stance::Aggressive),
+            "defensive" => Ok(Stance::Defensive),
+            "berserker" => Ok(Stance::Berserker),
+            "counter" => Ok(Stance::Counter),
+            _ => Err(()),
         }
     }
 }
 
 impl CharacterClass {
-    /// Get base stats (HP, min_dmg, max_dmg, crit_bps)
-    pub fn base_stats(&self) -> (u32, u16, u16, u16) {
+    pub const ALL: [CharacterClass; 5] = [
+        CharacterClass::Warrior,
+        CharacterClass::Assassin,
+        CharacterClass::Mage,
+        CharacterClass::Tank,
+        CharacterClass::Trickster,
+    ];
+
+    /// Get base stats (HP, min_dmg, max_dmg, crit chance)
+    pub fn base_stats(&self) -> (u32, u16, u16, Bps) {
+        match self {
+            CharacterClass::Warrior => (120, 8, 15, Bps(1500)),   // 15% crit
+            CharacterClass::Assassin => (90, 12, 20, Bps(3500)),  // 35% crit
+            CharacterClass::Mage => (80, 10, 18, Bps(2000)),      // 20% crit
+            CharacterClass::Tank => (150, 6, 12, Bps(1000)),      // 10% crit
+            CharacterClass::Trickster => (100, 8, 16, Bps(2500)), // 25% crit
+        }
+    }
+
+    /// The class's special ability
+    pub fn special(&self) -> SpecialAbility {
         match self {
-            CharacterClass::Warrior => (120, 8, 15, 1500),   // 15% crit
-            CharacterClass::Assassin => (90, 12, 20, 3500),  // 35% crit
-            CharacterClass::Mage => (80, 10, 18, 2000),      // 20% crit
-            CharacterClass::Tank => (150, 6, 12, 1000),      // 10% crit
-            CharacterClass::Trickster => (100, 8, 16, 2500), // 25% crit
+            CharacterClass::Warrior => SpecialAbility::ArmorPierce,
+            CharacterClass::Assassin => SpecialAbility::Assassinate,
+            CharacterClass::Mage => SpecialAbility::Ignite,
+            CharacterClass::Tank => SpecialAbility::Bulwark,
+            CharacterClass::Trickster => SpecialAbility::Vanish,
         }
     }
 
-    /// Special ability cooldown
+    /// Special ability cooldown under the default rules
     pub fn special_cooldown(&self) -> u8 {
         match self {
             CharacterClass::Warrior => 3,
@@ -445,6 +1925,18 @@ impl CharacterClass {
     }
 }
 
+/// Stances a class may fight with when class-locked stances are enabled
+pub fn allowed_stances(class: CharacterClass) -> &'static [Stance] {
+    use Stance::*;
+    match class {
+        CharacterClass::Warrior => &[Balanced, Aggressive, Defensive, Berserker, Counter],
+        CharacterClass::Assassin => &[Balanced, Aggressive, Berserker, Counter],
+        CharacterClass::Mage => &[Balanced, Defensive, Counter],
+        CharacterClass::Tank => &[Balanced, Aggressive, Defensive, Counter],
+        CharacterClass::Trickster => &[Balanced, Aggressive, Counter],
+    }
+}
+
 impl BattleParticipant {
     pub fn new(owner: AccountOwner, chain: ChainId, character: CharacterSnapshot, stake: Amount) -> Self {
         Self {
@@ -454,6 +1946,7 @@ impl BattleParticipant {
             stake,
             current_hp: character.hp_max,
             combo_stack: 0,
+            longest_combo: 0,
             special_cooldown: 0,
             turns_submitted: [None, None, None],
         }
@@ -501,6 +1994,7 @@ impl CombatStats {
             crits: 0,
             dodges: 0,
             highest_crit: 0,
+            longest_combo: 0,
         }
     }
 }
@@ -509,30 +2003,31 @@ impl CombatStats {
 pub const FP_SCALE: u128 = 1_000_000; // 1e6 for fixed-point arithmetic
 pub const MAX_COMBO_STACK: u8 = 5;
 
-/// Helper: multiply two fixed-point values
-pub fn mul_fp(a: u128, b: u128) -> u128 {
-    (a * b) / FP_SCALE
+/// Turns each fighter submits per round under the default rules
+pub const TURNS_PER_ROUND: u8 = 3;
+
+/// Product of two fixed-point values, or `None` when it overflows
+pub fn mul_fp_checked(a: u128, b: u128) -> Option<u128> {
+    a.checked_mul(b).map(|product| product / FP_SCALE)
 }
 
-/// Helper: convert fixed-point to u64
-pub fn fp_to_u64(value: u128) -> u64 {
-    (value / FP_SCALE) as u64
+/// Product of two fixed-point values; one too large to hold saturates at `u128::MAX`
+pub fn mul_fp(a: u128, b: u128) -> u128 {
+    mul_fp_checked(a, b).unwrap_or(u128::MAX)
 }
 
-/// Generate random value from seed and tag
-pub fn derive_random_u64(seed: &[u8; 32], tag: u8) -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+/// `value` scaled by `bps` basis points, rounded down; a product too large to hold saturates
+/// at `u128::MAX`
+pub fn apply_bps(value: u128, bps: u128) -> u128 {
+    value.checked_mul(bps).map_or(u128::MAX, |product| product / fees::BPS_DENOMINATOR)
+}
 
-    let mut hasher = DefaultHasher::new();
-    seed.hash(&mut hasher);
-    tag.hash(&mut hasher);
-    hasher.finish()
+/// [`apply_bps`] for damage, HP and effect magnitudes, saturating at `u32::MAX`
+pub fn apply_bps_u32(value: u32, bps: u32) -> u32 {
+    u32::try_from(apply_bps(value.into(), bps.into())).unwrap_or(u32::MAX)
 }
 
-/// Generate random value in range [min, max]
-pub fn random_in_range(seed: &[u8; 32], tag: u8, min: u64, max: u64) -> u64 {
-    let raw = derive_random_u64(seed, tag);
-    let range = max - min + 1;
-    min + (raw % range)
+/// Whole part of a fixed-point value, saturating at `u64::MAX`
+pub fn fp_to_u64(value: u128) -> u64 {
+    u64::try_from(value / FP_SCALE).unwrap_or(u64::MAX)
 }
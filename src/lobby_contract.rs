@@ -1,10 +1,65 @@
 use linera_sdk::{
-    linera_base_types::{Amount, AccountOwner, ChainId},
+    linera_base_types::{Amount, AccountOwner, ChainId, TimeDelta, Timestamp},
     ContractRuntime,
 };
 
-use majorules::{Operation, Message};
-use crate::state::LobbyState;
+use majorules::{
+    bracket::{self, BracketError, Entrant},
+    counters::{self, checked_accumulate, Counter},
+    elo,
+    fees::{Dust, FeeBreakdown, ProRataSplit},
+    fixtures::{self, FixturePlayer, FixtureStage, FixtureWorld},
+    matchmaking::{self, QueueTerms, Seeker},
+    odds,
+    practice::PracticeDifficulty,
+    quests,
+    rewards::XpFactors,
+    schedule::{ScheduleError, ScheduleLimits, SchedulePhase, TournamentSchedule},
+    series::{self, SeriesOutcome, SeriesScore},
+    throttle::{RejectionKey, RejectionVerdict},
+    time, invite_digest, Attestation, BattleEndReason, BattleResultSummary, CharacterSnapshot, FighterResult, Operation, OperationResponse,
+    Message, ProgressionGates, QueueMode,
+    STARTING_LIVES, TEAM_SIZE,
+};
+use crate::state::{
+    record_overflow, record_rejection, BattleStandings, CrankRecord, CreationKind, DustEntry, HeldRelease, LeaderboardEntry, LobbyState,
+    NotableStreak, PendingNotification, PendingSettlement, PlayerQueueEntry, QueueStats, RosterEntry, Season, SeasonReward, SeriesMetadata, SeriesStatus, Subscriber,
+    Tournament, TournamentMatch, TournamentStatus, MAX_TOURNAMENT_ENTRANTS,
+};
+
+/// Deferred steps a single lobby operation or message may take on top of its own work
+pub const MAINTENANCE_BUDGET: u32 = 32;
+
+/// Observers a single battle accepts
+pub const MAX_BATTLE_SUBSCRIBERS: usize = 8;
+
+/// Most maintenance steps a single `Crank` may take
+pub const MAX_CRANK_WORK: u32 = 128;
+
+/// Bounty per maintenance step a crank performs
+pub const CRANK_BOUNTY_PER_STEP: Amount = Amount::from_millis(1);
+
+/// Most bounty a single crank earns, however much it does
+pub const CRANK_BOUNTY_CAP: Amount = Amount::from_millis(20);
+
+/// Most bounty paid across all cranks in a day
+pub const CRANK_DAILY_BOUNTY_CAP: Amount = Amount::from_millis(200);
+
+/// How long a market stays open waiting for its battle before maintenance closes it
+pub const MARKET_OPEN_LIMIT: TimeDelta = TimeDelta::from_secs(60 * 60);
+
+/// How long a closed market waits for its battle's result before maintenance voids it,
+/// refunding every stake
+pub const MARKET_RESULT_LIMIT: TimeDelta = TimeDelta::from_secs(24 * 60 * 60);
+
+/// Highest platform fee the treasury or admin may set
+pub const MAX_PLATFORM_FEE_BPS: u16 = 2_000;
+
+/// Players the leaderboard keeps, best rated first
+pub const LEADERBOARD_SIZE: usize = 100;
+
+/// How long after completion a battle may still deliver fighter results
+pub const RESULT_GRACE_PERIOD: TimeDelta = TimeDelta::from_secs(24 * 60 * 60);
 
 pub struct LobbyContract;
 
@@ -13,72 +68,370 @@ impl LobbyContract {
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
         operation: Operation,
-    ) {
+    ) -> OperationResponse {
+        let Some(caller) = runtime.authenticated_signer() else {
+            return OperationResponse::rejected("unauthenticated");
+        };
         match operation {
             Operation::Increment { value } => {
                 state.value.set(state.value.get() + value);
             }
 
             Operation::CreatePlayerChain => {
-                let caller = runtime.authenticated_signer()
-                    .expect("Operation must be authenticated");
-                
-                // Create single-owner player chain with proper instantiation
-                let player_chain_id = runtime.open_chain(
-                    linera_sdk::linera_base_types::ChainOwnership::single(caller),
-                    linera_sdk::linera_base_types::ApplicationPermissions::default(),
-                    Amount::ZERO,
-                );
-                
-                // Initialize as Player chain via instantiation argument
-                let init_arg = majorules::InitializationArgument {
-                    variant: majorules::ChainVariant::Player,
-                    treasury_owner: None,
-                    platform_fee_bps: None,
+                // Owners keep one player chain; asking again returns it rather than orphaning it
+                if let Some(chain_id) = Self::get_player_chain(&caller, state).await {
+                    return OperationResponse::PlayerChain { chain_id };
+                }
+                let Some(chain_id) = Self::open_player_chain(state, runtime, caller).await else {
+                    Self::reject(state, runtime, "CreatePlayerChain", "player_chain_cap", caller).await;
+                    return OperationResponse::rejected("player_chain_cap");
                 };
-                
-                runtime.prepare_message(majorules::Message::InstantiateChain {
-                    variant: init_arg.variant.clone(),
-                    treasury_owner: init_arg.treasury_owner,
-                    platform_fee_bps: init_arg.platform_fee_bps,
-                }).with_authentication().send_to(player_chain_id);
-
-                // Register player's chain ID
-                state.character_registry.insert(
-                    &caller.to_string(), 
-                    crate::state::CharacterRegistryEntry {
-                        character_id: String::new(),
-                        owner: caller,
-                        owner_chain: player_chain_id,
-                        class: crate::state::CharacterClass::Warrior,
-                        level: 1,
-                        created_at: runtime.system_time(),
-                        total_battles: 0,
-                        wins: 0,
-                        losses: 0,
-                        is_alive: true,
-                        lives_remaining: 3,
+                Self::register_player(state, crate::state::CharacterRegistryEntry {
+                    character_id: String::new(),
+                    owner: caller,
+                    owner_chain: chain_id,
+                    class: crate::state::CharacterClass::Warrior,
+                    level: 1,
+                    created_at: runtime.system_time(),
+                    total_battles: 0,
+                    wins: 0,
+                    losses: 0,
+                    is_alive: true,
+                    lives_remaining: STARTING_LIVES,
+                });
+                return OperationResponse::PlayerChain { chain_id };
+            }
+
+            Operation::ReplacePlayerChain => {
+                let Ok(Some(mut entry)) = state.character_registry.get(&caller.to_string()).await else {
+                    Self::reject(state, runtime, "ReplacePlayerChain", "no_player_chain", caller).await;
+                    return OperationResponse::rejected("no_player_chain");
+                };
+                // Stakes and results in flight still go to the old chain
+                if Self::player_chain_in_use(state, caller).await {
+                    Self::reject(state, runtime, "ReplacePlayerChain", "player_chain_in_use", caller).await;
+                    return OperationResponse::rejected("player_chain_in_use");
+                }
+                let Some(chain_id) = Self::open_player_chain(state, runtime, caller).await else {
+                    Self::reject(state, runtime, "ReplacePlayerChain", "player_chain_cap", caller).await;
+                    return OperationResponse::rejected("player_chain_cap");
+                };
+                entry.owner_chain = chain_id;
+                Self::register_player(state, entry);
+                return OperationResponse::PlayerChain { chain_id };
+            }
+
+            Operation::UpdateBattleRules { rules } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "UpdateBattleRules", caller).await {
+                    return rejected;
+                }
+                if let Err(reason) = rules.validate() {
+                    Self::reject(state, runtime, "UpdateBattleRules", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+                state.battle_rules.set(rules);
+                state.rules_version.set(state.rules_version.get() + 1);
+            }
+
+            Operation::SetCreationCaps { player_chains, private_battles, queue_joins } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetCreationCaps", caller).await {
+                    return rejected;
+                }
+                state.creation_caps.set(crate::state::CreationCaps {
+                    player_chains,
+                    private_battles,
+                    queue_joins,
+                });
+            }
+
+            Operation::SetRankedGates { gates } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetRankedGates", caller).await {
+                    return rejected;
+                }
+                state.ranked_gates.set(gates);
+            }
+
+            Operation::SetProgressionGates { gates } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetProgressionGates", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().progression = gates;
+            }
+
+            Operation::SetEloConfig { config } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetEloConfig", caller).await {
+                    return rejected;
+                }
+                state.elo_config.set(config);
+            }
+
+            Operation::SetLevelingConfig { config } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetLevelingConfig", caller).await {
+                    return rejected;
+                }
+                state.leveling_config.set(config);
+            }
+
+            Operation::SetMintTerms { terms } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetMintTerms", caller).await {
+                    return rejected;
+                }
+                state.mint_terms.set(terms);
+            }
+
+            Operation::UpdatePlatformFee { platform_fee_bps } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "UpdatePlatformFee", caller).await {
+                    return rejected;
+                }
+                if platform_fee_bps > MAX_PLATFORM_FEE_BPS {
+                    Self::reject(state, runtime, "UpdatePlatformFee", "fee_too_high", caller).await;
+                    return OperationResponse::rejected("fee_too_high");
+                }
+                state.config.get_mut().platform_fee_bps = platform_fee_bps;
+            }
+
+            Operation::UpdateMatchmakingConfig { config } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "UpdateMatchmakingConfig", caller).await {
+                    return rejected;
+                }
+                if let Err(reason) = config.validate() {
+                    Self::reject(state, runtime, "UpdateMatchmakingConfig", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+                state.config.get_mut().matchmaking = config;
+                Self::attempt_elo_matchmaking(state, runtime).await;
+            }
+
+            Operation::SetQueueTerms { mode, terms } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetQueueTerms", caller).await {
+                    return rejected;
+                }
+                if let Err(reason) = terms.validate() {
+                    Self::reject(state, runtime, "SetQueueTerms", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+                state.queue_terms.insert(&mode, terms).expect("Failed to set queue terms");
+                Self::attempt_elo_matchmaking(state, runtime).await;
+            }
+
+            Operation::UpdateXpCurve { curve } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "UpdateXpCurve", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().xp_curve = curve;
+            }
+
+            Operation::UpdateStreakTerms { terms } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "UpdateStreakTerms", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().streaks = terms;
+            }
+
+            Operation::SetMarketPricing { pricing } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetMarketPricing", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().market_pricing = pricing;
+            }
+
+            Operation::SetBettingLimits { limits } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetBettingLimits", caller).await {
+                    return rejected;
+                }
+                if let Err(reason) = limits.validate() {
+                    Self::reject(state, runtime, "SetBettingLimits", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+                state.config.get_mut().betting_limits = limits;
+            }
+
+            Operation::PauseMatchmaking { paused } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "PauseMatchmaking", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().matchmaking_paused = paused;
+                Self::attempt_elo_matchmaking(state, runtime).await;
+            }
+
+            Operation::SetTreasuryOwner { owner } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "SetTreasuryOwner", caller).await {
+                    return rejected;
+                }
+                state.treasury_owner.set(Some(owner));
+            }
+
+            Operation::SetLobbyAdmin { admin } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "SetLobbyAdmin", caller).await {
+                    return rejected;
+                }
+                state.config.get_mut().admin = admin;
+            }
+
+            Operation::FundCommunityPool { amount } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "FundCommunityPool", caller).await {
+                    return rejected;
+                }
+                state.community_pool.set(state.community_pool.get().saturating_add(amount));
+            }
+
+            Operation::StartSeason { name, terms } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "StartSeason", caller).await {
+                    return rejected;
+                }
+                let reason = if state.current_season.get().is_some() {
+                    Some("season_running")
+                } else {
+                    terms.validate().err()
+                };
+                if let Some(reason) = reason {
+                    Self::reject(state, runtime, "StartSeason", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+
+                let season_id = *state.season_count.get() + 1;
+                state.season_count.set(season_id);
+                state.current_season.set(Some(Season {
+                    season_id,
+                    name,
+                    terms,
+                    started_at: runtime.system_time(),
+                    ended_at: None,
+                    standings: Vec::new(),
+                    rewards: Vec::new(),
+                }));
+            }
+
+            Operation::EndSeason => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "EndSeason", caller).await {
+                    return rejected;
+                }
+                let Some(season) = state.current_season.get().clone() else {
+                    Self::reject(state, runtime, "EndSeason", "no_season", caller).await;
+                    return OperationResponse::rejected("no_season");
+                };
+                Self::end_season(state, runtime, season).await;
+            }
+
+            Operation::MintTokens { to, amount } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "MintTokens", caller).await {
+                    return rejected;
+                }
+                let Some(player_chain) = Self::get_player_chain(&to, state).await else {
+                    Self::reject(state, runtime, "MintTokens", "unregistered", caller).await;
+                    return OperationResponse::rejected("unregistered");
+                };
+                let mut supply = *state.token_supply.get();
+                if let Err(reason) = supply.mint(amount) {
+                    Self::reject(state, runtime, "MintTokens", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+                state.token_supply.set(supply);
+                runtime.prepare_message(Message::CreditTokens { owner: to, amount })
+                    .with_authentication()
+                    .send_to(player_chain);
+            }
+
+            Operation::Crank { max_work } => {
+                let work = Self::run_maintenance(state, runtime, max_work.min(MAX_CRANK_WORK)).await;
+                Self::pay_crank_bounty(state, runtime, caller, work).await;
+            }
+
+            Operation::CreateTournament { name, terms, start_time, registration_closes_at, party } => {
+                if let Some(rejected) = Self::require_treasury(state, runtime, "CreateTournament", caller).await {
+                    return rejected;
+                }
+                let now = runtime.system_time();
+                let schedule = TournamentSchedule::new(now, start_time, registration_closes_at, &ScheduleLimits::default());
+                let reason = if !(2..=MAX_TOURNAMENT_ENTRANTS).contains(&terms.max_entrants) {
+                    Some("invalid_max_entrants")
+                } else if terms.runner_up_share_bps > 10_000 {
+                    Some("invalid_prize_split")
+                } else {
+                    match schedule {
+                        Err(ScheduleError::StartTooSoon) => Some("start_too_soon"),
+                        Err(ScheduleError::StartTooFar) => Some("start_too_far"),
+                        Err(ScheduleError::RegistrationClosesAfterStart) => Some("registration_closes_after_start"),
+                        Ok(_) => None,
                     }
-                ).expect("Failed to register player chain");
+                };
+                let (None, Ok(schedule)) = (reason, schedule) else {
+                    Self::reject(state, runtime, "CreateTournament", reason.unwrap_or_default(), caller).await;
+                    return OperationResponse::rejected(reason.unwrap_or_default());
+                };
 
-                // Initialize player chain with lobby reference
-                let lobby_chain_id = runtime.chain_id();
-                runtime.prepare_message(Message::InitializePlayerChain {
-                    lobby_chain_id,
-                    owner: caller,
-                }).with_authentication().send_to(player_chain_id);
+                let tournament_id = *state.tournament_count.get() + 1;
+                state.tournament_count.set(tournament_id);
+                state.tournaments.insert(&tournament_id, Tournament {
+                    tournament_id,
+                    name,
+                    terms,
+                    schedule,
+                    status: TournamentStatus::Registration,
+                    entrants: 0,
+                    prize_pool: Amount::ZERO,
+                    round: 0,
+                    matches: Vec::new(),
+                    champion: None,
+                    runner_up: None,
+                    created_at: now,
+                    party,
+                }).expect("Failed to create tournament");
+                state.tournament_starts.insert(&tournament_id, start_time)
+                    .expect("Failed to schedule tournament");
+            }
+
+            Operation::SetCreationExemption { owner, exempt } => {
+                if let Some(rejected) = Self::require_admin(state, runtime, "SetCreationExemption", caller).await {
+                    return rejected;
+                }
+                if exempt {
+                    state.creation_exemptions.insert(&owner, ())
+                        .expect("Failed to add creation exemption");
+                } else {
+                    state.creation_exemptions.remove(&owner)
+                        .expect("Failed to remove creation exemption");
+                }
             }
 
             Operation::LeaveQueue => {
-                let caller = runtime.authenticated_signer()
-                    .expect("Operation must be authenticated");
-                
-                // Remove from queue
-                state.waiting_players.remove(&caller).ok();
-                
-                // Decrement counter
-                if *state.value.get() > 0 {
-                    state.value.set(state.value.get() - 1);
+                // Remove the caller's characters from the queue and release them and their stakes
+                // on the player chain. Entries already matched are gone, and their stakes stay
+                // with the battle
+                let entries = state.queued_entries(caller).await.expect("Failed to read queue");
+                let mut held = Vec::new();
+                state.held_releases.for_each_index_value(|(owner, character_id), release| {
+                    if owner == caller {
+                        held.push((character_id, release.player_chain));
+                    }
+                    Ok(())
+                }).await.expect("Failed to read held releases");
+                if entries.is_empty() && held.is_empty() {
+                    Self::reject(state, runtime, "LeaveQueue", "not_queued", caller).await;
+                    return OperationResponse::rejected("not_queued");
+                }
+                if !entries.is_empty() {
+                    state.queue_exits.insert(&caller, runtime.system_time())
+                        .expect("Failed to record queue exit");
+                }
+                for entry in entries {
+                    state.dequeue(&(caller, entry.character_id.clone())).await.expect("Failed to leave queue");
+                    runtime.prepare_message(Message::QueueLeft {
+                        character_id: entry.character_id,
+                    }).with_authentication().send_to(entry.player_chain);
+                }
+                for (character_id, player_chain) in held {
+                    state.held_releases.remove(&(caller, character_id.clone())).ok();
+                    runtime.prepare_message(Message::QueueLeft { character_id })
+                        .with_authentication()
+                        .send_to(player_chain);
+                }
+            }
+
+            Operation::SubscribeToBattle { battle_chain } => {
+                if let Err(reason) = Self::subscribe_to_battle(state, caller, battle_chain).await {
+                    Self::reject(state, runtime, "SubscribeToBattle", reason, caller).await;
+                    return OperationResponse::rejected(reason);
                 }
             }
 
@@ -93,20 +446,47 @@ impl LobbyContract {
             }
             
             Operation::PlaceBet { market_id, predicted_winner, amount } => {
-                let caller = runtime.authenticated_signer()
-                    .expect("Operation must be authenticated");
-                    
-                Self::place_bet(state, runtime, caller, market_id, predicted_winner, amount).await;
+                // Markets are addressed externally by their encoded id
+                let market_id = state.id_codec.get().decode(market_id);
+                let now = runtime.system_time();
+                // Late and oversized bets are answered, so bettors learn why their stake stayed home
+                if let Err(reason) = Self::place_bet(state, runtime, caller, market_id, predicted_winner, amount, now).await {
+                    Self::reject(state, runtime, "PlaceBet", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
             }
             
             Operation::CloseMarket { market_id } => {
+                let market_id = state.id_codec.get().decode(market_id);
                 Self::close_market(state, runtime, market_id).await;
             }
 
+            Operation::ClaimWinnings { market_id } => {
+                let market_id = state.id_codec.get().decode(market_id);
+                if let Err(reason) = Self::claim_winnings(state, runtime, caller, market_id).await {
+                    Self::reject(state, runtime, "ClaimWinnings", reason, caller).await;
+                    return OperationResponse::rejected(reason);
+                }
+            }
+
+            Operation::GenerateFixtures { seed, players, battles, markets } => {
+                if !runtime.application_parameters().dev_fixtures {
+                    Self::reject(state, runtime, "GenerateFixtures", "fixtures_disabled", caller).await;
+                    return OperationResponse::rejected("fixtures_disabled");
+                }
+                if let Some(rejected) = Self::require_treasury(state, runtime, "GenerateFixtures", caller).await {
+                    return rejected;
+                }
+                let max_rounds = state.battle_rules.get().max_rounds;
+                let world = FixtureWorld::plan(seed, players, battles, markets, max_rounds, runtime.system_time());
+                Self::generate_fixtures(state, runtime, caller, world).await;
+            }
+
             _ => {
                 // Ignore operations not relevant to lobby
             }
         }
+        OperationResponse::Done
     }
 
     pub async fn execute_message(
@@ -115,7 +495,7 @@ impl LobbyContract {
         message: Message,
     ) {
         match message {
-            Message::RequestJoinQueue { player, player_chain, character_snapshot, stake } => {
+            Message::RequestJoinQueue { player, player_chain, character_snapshot, stake, mode, best_of, party } => {
                 // Verify message comes from the player's chain
                 let sender_chain = runtime.message_origin_chain_id()
                     .expect("Message must have origin");
@@ -123,473 +503,5449 @@ impl LobbyContract {
                     return; // Reject unauthorized requests
                 }
 
-                // Check if already in queue
-                if state.waiting_players.contains_key(&player).await.unwrap_or(false) {
-                    return; // Already in queue
+                // Every refusal releases the character, unlocking its stake, except a repeat of
+                // the entry already queued, which must stay held. An owner's other characters
+                // queue alongside it, each with its own entry
+                let seat = (player, character_snapshot.nft_id.clone());
+                let queued = state.waiting_players.get(&seat).await.expect("Failed to read queue");
+                if queued.as_ref().is_some_and(|entry| entry.player_chain == player_chain) {
+                    Self::reject(state, runtime, "RequestJoinQueue", "already_queued", player).await;
+                    return;
+                }
+                // A player who just left waits out the cooldown, and one player chain queues one
+                // owner at a time, so owners cannot churn the queue until they meet their own accounts
+                let now = runtime.system_time();
+                let terms = Self::queue_terms(state, mode).await;
+                let config = terms.matchmaking_or(state.config.get().matchmaking);
+                let cooldown = TimeDelta::from_secs(config.rejoin_cooldown_secs);
+                let cooling_down = state.queue_exits.get(&player).await
+                    .expect("Failed to read queue exits")
+                    .is_some_and(|left_at| time::delta_or_zero(now, left_at) < cooldown);
+                let taken = Self::held_elsewhere(state, &character_snapshot.nft_id, player_chain).await;
+                let (mut chain_queued, mut queue_size) = (false, 0);
+                state.waiting_players.for_each_index_value(|_, entry| {
+                    chain_queued |= entry.player_chain == player_chain && entry.player != player;
+                    queue_size += usize::from(entry.mode == mode);
+                    Ok(())
+                }).await.expect("Failed to read queue");
+                let refusal = if queued.is_some() {
+                    Some("already_queued")
+                } else if stake <= Amount::ZERO {
+                    Some("invalid_stake")
+                } else if stake < terms.min_stake {
+                    Some("stake_below_queue_minimum")
+                } else if let Err(reason) = series::validate_best_of(best_of) {
+                    Some(reason)
+                } else if mode == QueueMode::Teams && best_of > 1 {
+                    Some("series_not_in_team_queue")
+                } else if !character_snapshot.within_equipment_bounds() {
+                    // Snapshot modifiers must be reachable with equipment
+                    Some("snapshot_out_of_bounds")
+                } else if taken {
+                    Some("character_id_taken")
+                } else if cooling_down {
+                    Some("rejoin_cooldown")
+                } else if chain_queued {
+                    Some("player_chain_in_queue")
+                } else if queue_size >= config.max_queue_size as usize {
+                    Some("queue_full")
+                } else {
+                    None
+                };
+                if let Some(reason) = refusal {
+                    let verdict = Self::reject(state, runtime, "RequestJoinQueue", reason, player).await;
+                    Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+                    return;
+                }
+
+                // Ranked entry is gated on the character's level and the account's battle count
+                if mode == QueueMode::Ranked {
+                    let account_battles = match state.character_registry.get(&player.to_string()).await {
+                        Ok(Some(entry)) => entry.total_battles,
+                        _ => 0,
+                    };
+                    if let Err(reason) = state.ranked_gates.get().check(character_snapshot.level, account_battles) {
+                        let verdict = Self::reject(state, runtime, "RequestJoinQueue", &reason, player).await;
+                        Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+                        return;
+                    }
+                }
+
+                // Staked and ranked entry waits until the account has won practice or casual
+                // battles, or brought a character up to level
+                if ProgressionGates::applies(mode, stake) {
+                    let wins = state.progression_wins.get(&player).await
+                        .expect("Failed to read progression wins")
+                        .unwrap_or(0);
+                    if let Err(reason) = state.config.get().progression.check(character_snapshot.level, wins) {
+                        let verdict = Self::reject(state, runtime, "RequestJoinQueue", &reason, player).await;
+                        Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+                        return;
+                    }
+                }
+
+                // Enforce the daily queue join limit
+                if !Self::consume_allowance(state, runtime, player, CreationKind::QueueJoin).await {
+                    let verdict = Self::reject(state, runtime, "RequestJoinQueue", "queue_join_cap", player).await;
+                    Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+                    return;
                 }
 
-                // Validate stake
-                if stake <= Amount::ZERO {
-                    return; // Invalid stake
+                // Unrated players queue at the initial rating until their chain reports theirs
+                if !state.ratings.contains_key(&player).await.unwrap_or(true) {
+                    runtime.prepare_message(Message::RequestPlayerStats { player })
+                        .with_authentication()
+                        .send_to(player_chain);
                 }
 
                 // Player chain provides character data
-                let now = runtime.system_time();
                 let queue_entry = crate::state::PlayerQueueEntry {
                     player,
                     player_chain,
                     character_id: character_snapshot.nft_id.clone(),
-                    character_snapshot: crate::state::CharacterSnapshot {
-                        nft_id: character_snapshot.nft_id,
-                        class: match character_snapshot.class {
-                            majorules::CharacterClass::Warrior => crate::state::CharacterClass::Warrior,
-                            majorules::CharacterClass::Mage => crate::state::CharacterClass::Mage,
-                            _ => crate::state::CharacterClass::Warrior,
-                        },
-                        level: character_snapshot.level,
-                        hp_max: character_snapshot.hp_max,
-                        min_damage: character_snapshot.min_damage,
-                        max_damage: character_snapshot.max_damage,
-                        crit_chance: character_snapshot.crit_chance,
-                        crit_multiplier: character_snapshot.crit_multiplier,
-                        dodge_chance: character_snapshot.dodge_chance,
-                        defense: character_snapshot.defense,
-                        attack_bps: character_snapshot.attack_bps,
-                        defense_bps: character_snapshot.defense_bps,
-                        crit_bps: character_snapshot.crit_bps,
-                    },
+                    character_snapshot: character_snapshot.into(),
                     stake,
                     joined_at: now,
+                    mode,
+                    best_of,
+                    elo_rating: Self::rating(state, player).await,
+                    party: party.filter(|_| mode == QueueMode::Teams),
                 };
 
-                state.waiting_players.insert(&player, queue_entry)
-                    .expect("Failed to add player to queue");
+                let character_id = queue_entry.character_id.clone();
+                state.enqueue(queue_entry).await.expect("Failed to add player to queue");
+                state.queue_exits.remove(&player).expect("Failed to clear queue exit");
+                Self::update_queue_stats(state, mode, |stats| stats.joins = stats.joins.saturating_add(1)).await;
+                runtime.prepare_message(Message::QueueJoined { character_id })
+                    .with_authentication()
+                    .send_to(player_chain);
+                Self::attempt_elo_matchmaking(state, runtime).await;
+            }
 
-                // Check for ELO-based matchmaking
-                let queue_count = state.waiting_players.count().await.unwrap_or(0);
-                if queue_count >= 2 {
-                    Self::attempt_elo_matchmaking(state, runtime).await;
+            Message::RequestJoinTournament { player, player_chain, tournament_id, character_snapshot, entry_fee, party } => {
+                if runtime.message_origin_chain_id() != Some(player_chain) {
+                    return;
+                }
+                let character_id = character_snapshot.nft_id.clone();
+                let taken = Self::held_elsewhere(state, &character_id, player_chain).await;
+                let id = state.id_codec.get().decode(tournament_id);
+                let now = runtime.system_time();
+                let tournament = state.tournaments.get(&id).await.expect("Failed to read tournament");
+                let mut entrants = state.tournament_entrants.get(&id).await
+                    .expect("Failed to read tournament entrants")
+                    .unwrap_or_default();
+                let reason = match &tournament {
+                    None => Some("unknown_tournament"),
+                    Some(tournament) if tournament.status != TournamentStatus::Registration
+                        || !tournament.schedule.accepts_joins(now) => Some("registration_closed"),
+                    Some(tournament) if tournament.entrants >= tournament.terms.max_entrants => Some("tournament_full"),
+                    Some(tournament) if tournament.terms.entry_fee != entry_fee => Some("wrong_entry_fee"),
+                    Some(tournament) if tournament.party.is_some() && tournament.party != party => Some("not_in_party"),
+                    Some(_) if entrants.iter().any(|entrant| entrant.player == player) => Some("already_entered"),
+                    Some(_) if !character_snapshot.within_equipment_bounds() => Some("snapshot_out_of_bounds"),
+                    Some(_) if taken => Some("character_id_taken"),
+                    Some(_) => None,
+                };
+                let (None, Some(mut tournament)) = (reason, tournament) else {
+                    // Refunds go out even while rejections are throttled
+                    Self::reject(state, runtime, "RequestJoinTournament", reason.unwrap_or_default(), player).await;
+                    runtime.prepare_message(Message::TournamentExited { tournament_id, character_id, payout: entry_fee })
+                        .with_authentication()
+                        .send_to(player_chain);
+                    return;
+                };
+
+                entrants.push(PlayerQueueEntry {
+                    player,
+                    player_chain,
+                    character_id,
+                    character_snapshot: character_snapshot.into(),
+                    stake: Amount::ZERO,
+                    joined_at: now,
+                    mode: QueueMode::Casual,
+                    best_of: 1,
+                    elo_rating: Self::rating(state, player).await,
+                    party,
+                });
+                state.tournament_entrants.insert(&id, entrants)
+                    .expect("Failed to register tournament entrant");
+                tournament.entrants += 1;
+                tournament.prize_pool = tournament.prize_pool.saturating_add(entry_fee);
+                state.tournaments.insert(&id, tournament)
+                    .expect("Failed to update tournament");
+            }
+
+            Message::RequestCreatePrivateBattle { player, player_chain, character_snapshot, stake, invite } => {
+                if runtime.message_origin_chain_id() != Some(player_chain) {
+                    return;
+                }
+                let Some(host) = Self::admit_private_entry(
+                    state, runtime, "RequestCreatePrivateBattle", player, player_chain, character_snapshot, stake,
+                ).await else {
+                    return;
+                };
+                if !Self::consume_allowance(state, runtime, player, CreationKind::PrivateBattle).await {
+                    let verdict = Self::reject(state, runtime, "RequestCreatePrivateBattle", "private_battle_cap", player).await;
+                    Self::release_rejected_join(state, runtime, verdict, player, player_chain, host.character_id);
+                    return;
+                }
+
+                let id = *state.private_battle_count.get() + 1;
+                state.private_battle_count.set(id);
+                state.pending_private_battles.insert(&id, host)
+                    .expect("Failed to record private battle");
+                state.private_battle_invites.insert(&id, invite)
+                    .expect("Failed to record private battle invite");
+                runtime.prepare_message(Message::PrivateBattleCreated { battle_id: state.id_codec.get().encode(id) })
+                    .with_authentication()
+                    .send_to(player_chain);
+            }
+
+            Message::RequestJoinPrivateBattle { player, player_chain, battle_id, invite_secret, character_snapshot, stake } => {
+                if runtime.message_origin_chain_id() != Some(player_chain) {
+                    return;
+                }
+                let Some(guest) = Self::admit_private_entry(
+                    state, runtime, "RequestJoinPrivateBattle", player, player_chain, character_snapshot, stake,
+                ).await else {
+                    return;
+                };
+
+                // A wrong secret is refused like an unknown id, so ids cannot be probed
+                let id = state.id_codec.get().decode(battle_id);
+                let invited = state.private_battle_invites.get(&id).await.ok().flatten() == Some(invite_digest(invite_secret));
+                let refusal = match state.pending_private_battles.get(&id).await.ok().flatten() {
+                    Some(_) if !invited => Err("unknown_battle"),
+                    None => Err("unknown_battle"),
+                    Some(host) if host.player == player || host.player_chain == player_chain => Err("own_battle"),
+                    Some(host) if host.stake != stake => Err("stake_mismatch"),
+                    Some(host) => Ok(host),
+                };
+                match refusal {
+                    Ok(host) => {
+                        state.pending_private_battles.remove(&id).expect("Failed to close private battle");
+                        state.private_battle_invites.remove(&id).expect("Failed to close private battle");
+                        Self::create_battle_chain(state, runtime, host, guest).await;
+                    }
+                    Err(reason) => {
+                        let verdict = Self::reject(state, runtime, "RequestJoinPrivateBattle", reason, player).await;
+                        Self::release_rejected_join(state, runtime, verdict, player, player_chain, guest.character_id);
+                    }
+                }
+            }
+
+            Message::RequestPracticeBattle { player, player_chain, character_snapshot, difficulty } => {
+                if runtime.message_origin_chain_id() != Some(player_chain) {
+                    return;
+                }
+                // Each practice battle opens a chain, so it counts against the private battle cap
+                let reason = if !character_snapshot.within_equipment_bounds() {
+                    Some("snapshot_out_of_bounds")
+                } else if Self::held_elsewhere(state, &character_snapshot.nft_id, player_chain).await {
+                    Some("character_id_taken")
+                } else if !Self::consume_allowance(state, runtime, player, CreationKind::PrivateBattle).await {
+                    Some("practice_battle_cap")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    let verdict = Self::reject(state, runtime, "RequestPracticeBattle", reason, player).await;
+                    Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+                    return;
                 }
+                Self::create_practice_battle_chain(state, runtime, player, player_chain, character_snapshot, difficulty).await;
             }
 
-            Message::BattleResultWithElo { player, opponent: _, won, payout: _, xp_gained, elo_change, battle_stats: _, battle_chain } => {
+            Message::BattleResultWithElo {
+                player, character_id, opponent, won, payout, xp_gained, elo_change: _, battle_stats, rounds_played, battle_chain: _, rules_digest,
+                end_reason, item_drop,
+            } => {
                 // Verify message comes from a valid battle chain
                 let sender_chain = runtime.message_origin_chain_id()
                     .expect("Message must have origin");
                 
-                // Check if this battle chain exists in our active battles
-                if !state.active_battles.contains_key(&sender_chain).await.unwrap_or(false) {
+                // The completion may already have arrived, so recently completed battles count too
+                let Some((fighters, mode)) = Self::known_fighters(state, runtime, sender_chain).await else {
                     return; // Reject unauthorized battle results
+                };
+                if !Self::fought(fighters, player, opponent) {
+                    return; // A battle chain only reports on its own fighters
                 }
                 
-                // Forward ELO update directly to player chain (lobby doesn't store stats)
-                if let Some(player_chain) = Self::get_player_chain(&player, state).await {
-                    runtime.prepare_message(Message::UpdatePlayerStats {
-                        player,
-                        won,
-                        xp_gained,
-                        elo_change,
-                        battle_chain,
-                    }).with_authentication().send_to(player_chain);
+                let (winner, loser) = if won { (player, opponent) } else { (opponent, player) };
+                let ((winner_change, loser_change), standings) =
+                    Self::rate_battle(state, runtime, sender_chain, winner, loser, end_reason, mode).await;
+                let (elo_change, win_streak) = if won {
+                    (winner_change, standings.streaks_after.0)
+                } else {
+                    (loser_change, standings.streaks_after.1)
+                };
+                let mut result = FighterResult {
+                    player, character_id, won, payout, xp_gained, elo_change, item_drop, combat_stats: battle_stats, win_streak,
+                };
+                result.xp_gained = Self::scaled_xp(state, &result, winner, standings, rounds_played);
+                Self::relay_result(state, runtime, sender_chain, rules_digest, end_reason, result).await;
+            }
+            
+            Message::PayoutReceipt { battle_chain, player, amount } => {
+                // Only the winner's registered player chain may confirm a payout
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                
+                match state.completed_battles.get(&battle_chain).await {
+                    Ok(Some(record)) if record.winner == player => {}
+                    _ => return, // Unknown battle or not the winner
                 }
+                
+                state.payout_receipts.insert(&battle_chain, crate::state::PayoutReceiptRecord {
+                    player,
+                    amount,
+                    received_at: runtime.system_time(),
+                }).expect("Failed to store payout receipt");
             }
             
-            Message::BattleCompleted { winner, loser, rounds_played, total_stake, battle_stats: _ } => {
+            Message::BattleCompleted { winner, loser, rounds_played, total_stake, battle_stats: _, rules_digest, end_reason, results } => {
                 let sender_chain = runtime.message_origin_chain_id()
                     .expect("Message must have origin");
+
+                let Some((fighters, mode)) = Self::known_fighters(state, runtime, sender_chain).await else {
+                    return;
+                };
+                if !Self::fought(fighters, winner, loser) {
+                    return;
+                }
+                let ((winner_change, loser_change), standings) =
+                    Self::rate_battle(state, runtime, sender_chain, winner, loser, end_reason, mode).await;
+                for mut result in results.into_iter().filter(|result| result.player == winner || result.player == loser) {
+                    (result.elo_change, result.win_streak) = if result.player == winner {
+                        (winner_change, standings.streaks_after.0)
+                    } else {
+                        (loser_change, standings.streaks_after.1)
+                    };
+                    result.xp_gained = Self::scaled_xp(state, &result, winner, standings, rounds_played);
+                    Self::relay_result(state, runtime, sender_chain, rules_digest, end_reason, result).await;
+                }
                     
                 // Handle battle completion separately from prediction market
-                Self::handle_battle_completion(state, runtime, sender_chain, winner, loser, rounds_played, total_stake).await;
+                let now = runtime.system_time();
+                Self::handle_battle_completion(
+                    state, runtime, sender_chain, winner, rounds_played, total_stake, rules_digest, end_reason, now,
+                ).await;
+            }
+
+            Message::TeamBattleCompleted { rounds_played, total_stake, rules_digest, end_reason, results } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Ok(Some(sides)) = state.team_battles.get(&sender_chain).await else {
+                    return;
+                };
+                // The winning side's first seat stands in for it, as in the battle itself
+                let won_by = |side: &Vec<AccountOwner>| results.iter().any(|result| result.won && side.contains(&result.player));
+                let winner = if won_by(&sides[1]) { sides[1][0] } else { sides[0][0] };
+                for result in results.into_iter().filter(|result| sides.iter().flatten().any(|&member| member == result.player)) {
+                    Self::relay_result(state, runtime, sender_chain, rules_digest, end_reason, result).await;
+                }
+
+                let now = runtime.system_time();
+                Self::handle_battle_completion(
+                    state, runtime, sender_chain, winner, rounds_played, total_stake, rules_digest, end_reason, now,
+                ).await;
+                // The completion covers the first seats; their teammates are archived and counted here
+                let drawn = end_reason == BattleEndReason::Draw;
+                for (side, members) in sides.iter().enumerate() {
+                    Self::count_fighters(state, &members[1..], false).await;
+                    for &member in &members[1..] {
+                        let mut battles = state.completed_by_owner.get(&member).await
+                            .expect("Failed to read player archive index")
+                            .unwrap_or_default();
+                        battles.push((now, sender_chain));
+                        state.completed_by_owner.insert(&member, battles)
+                            .expect("Failed to index completed battle by player");
+                        if let Ok(Some(mut entry)) = state.character_registry.get(&member.to_string()).await {
+                            entry.total_battles += 1;
+                            if !drawn {
+                                if sides[side][0] == winner { entry.wins += 1 } else { entry.losses += 1 }
+                            }
+                            state.character_registry.insert(&member.to_string(), entry)
+                                .expect("Failed to update registry battle counts");
+                        }
+                    }
+                }
+                state.team_battles.remove(&sender_chain).expect("Failed to close team battle");
             }
 
 
 
-            Message::PlayerStatsResponse { player, stats } => {
-                // Use player stats for matchmaking (don't store permanently)
-                // This is used temporarily for ELO-based matchmaking
+            Message::PracticeBattleCompleted { result } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Ok(Some((player, player_chain))) = state.practice_battles.get(&sender_chain).await else {
+                    return;
+                };
+                if result.player != player {
+                    return;
+                }
+                state.practice_battles.remove(&sender_chain).expect("Failed to close practice battle");
+                Self::count_fighters(state, &[player], false).await;
+                if result.won {
+                    Self::count_progression_win(state, player).await;
+                }
+                runtime.prepare_message(Message::PracticeBattleEnded {
+                    battle_chain: sender_chain,
+                    character_id: result.character_id,
+                    won: result.won,
+                    xp_gained: result.xp_gained,
+                }).with_authentication().send_to(player_chain);
             }
 
-            _ => {
-                // Ignore other message types
+            Message::BattleStarted { battle_chain } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                // Only a live battle closes betting, and only on itself
+                if sender_chain != battle_chain || !state.active_battles.contains_key(&battle_chain).await.unwrap_or(false) {
+                    return;
+                }
+                if let Ok(Some(market_id)) = state.battle_to_market.get(&battle_chain).await {
+                    Self::close_market(state, runtime, market_id).await;
+                }
             }
-        }
-    }
 
-    async fn get_player_chain(player: &AccountOwner, state: &LobbyState) -> Option<ChainId> {
-        if let Ok(Some(entry)) = state.character_registry.get(&player.to_string()).await {
-            Some(entry.owner_chain)
-        } else {
-            None
-        }
-    }
+            Message::ResultAttestation { attestation } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                // Only completed battles attest, each about itself
+                let Ok(Some(mut record)) = state.completed_battles.get(&sender_chain).await else {
+                    return;
+                };
+                if record.attestation == Attestation::Disputed {
+                    return;
+                }
+                record.attestation = attestation;
+                state.completed_battles.insert(&sender_chain, record)
+                    .expect("Failed to record attestation");
+                if attestation == Attestation::Disputed {
+                    state.review_queue.insert(&sender_chain, ())
+                        .expect("Failed to flag battle for review");
+                }
+            }
 
-    async fn create_battle_chain(
-        state: &mut LobbyState,
-        runtime: &mut ContractRuntime<crate::MajorulesContract>,
-        player1: crate::state::PlayerQueueEntry,
-        player2: crate::state::PlayerQueueEntry,
-    ) {
-        use linera_sdk::linera_base_types::{ChainOwnership, ApplicationPermissions};
+            Message::PlayerStatsResponse { player, stats } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                // A chain's rating may lag changes still in flight, so it only seeds the cache
+                if !state.ratings.contains_key(&player).await.unwrap_or(true) {
+                    state.ratings.insert(&player, stats.elo_rating)
+                        .expect("Failed to cache rating");
+                }
 
-        // Create multi-owner battle chain with proper instantiation
-        let battle_chain_id = runtime.open_chain(
-            ChainOwnership::multiple(
-                vec![
-                    (player1.player, 1u64),
-                    (player2.player, 1u64),
-                ].into_iter(),
-                10, // multi_leader_rounds
-                Default::default(), // timeout_config
-            ),
-            ApplicationPermissions::default(),
-            Amount::ZERO,
-        );
-        
-        // Initialize as Battle chain via instantiation argument
-        let init_arg = majorules::InitializationArgument {
-            variant: majorules::ChainVariant::Battle,
-            treasury_owner: Some(state.treasury_owner.get().unwrap()),
-            platform_fee_bps: Some(*state.platform_fee_bps.get()),
-        };
-        
-        runtime.prepare_message(majorules::Message::InstantiateChain {
-            variant: init_arg.variant.clone(),
-            treasury_owner: init_arg.treasury_owner,
-            platform_fee_bps: init_arg.platform_fee_bps,
-        }).with_authentication().send_to(battle_chain_id);
+                let entries = state.queued_entries(player).await.expect("Failed to read queue");
+                if !entries.is_empty() {
+                    let rating = Self::rating(state, player).await;
+                    for mut entry in entries {
+                        entry.elo_rating = rating;
+                        state.enqueue(entry).await.expect("Failed to update queued rating");
+                    }
+                    Self::attempt_elo_matchmaking(state, runtime).await;
+                }
+            }
 
-        // Send initialization message to battle chain
-        let participant1 = majorules::BattleParticipant::new(
-            player1.player,
-            player1.player_chain,
-            majorules::CharacterSnapshot {
-                nft_id: player1.character_snapshot.nft_id,
-                class: match player1.character_snapshot.class {
-                    crate::state::CharacterClass::Warrior => majorules::CharacterClass::Warrior,
-                    crate::state::CharacterClass::Mage => majorules::CharacterClass::Mage,
-                    _ => majorules::CharacterClass::Warrior,
-                },
-                level: player1.character_snapshot.level,
-                hp_max: player1.character_snapshot.hp_max,
-                min_damage: player1.character_snapshot.min_damage,
-                max_damage: player1.character_snapshot.max_damage,
-                crit_chance: player1.character_snapshot.crit_chance,
-                crit_multiplier: player1.character_snapshot.crit_multiplier,
-                dodge_chance: player1.character_snapshot.dodge_chance,
-                defense: player1.character_snapshot.defense,
-                attack_bps: player1.character_snapshot.attack_bps,
-                defense_bps: player1.character_snapshot.defense_bps,
-                crit_bps: player1.character_snapshot.crit_bps,
-            },
-            player1.stake,
-        );
+            Message::CharacterMinted { player, snapshot } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                // Players pick character ids, so the first chain to report one keeps it
+                if state.roster.contains_key(&snapshot.nft_id).await.expect("Failed to read roster") {
+                    Self::reject(state, runtime, "CharacterMinted", "duplicate_character_id", player).await;
+                    return;
+                }
+                state.roster.insert(&snapshot.nft_id, RosterEntry {
+                    character_id: snapshot.nft_id.clone(),
+                    owner: player,
+                    owner_chain: sender_chain,
+                    class: snapshot.class.into(),
+                    level: snapshot.level,
+                    lives_remaining: STARTING_LIVES,
+                    minted_at: runtime.system_time(),
+                    moving_to: None,
+                }).expect("Failed to add character to roster");
+            }
 
-        let participant2 = majorules::BattleParticipant::new(
-            player2.player,
-            player2.player_chain,
-            majorules::CharacterSnapshot {
-                nft_id: player2.character_snapshot.nft_id,
-                class: match player2.character_snapshot.class {
-                    crate::state::CharacterClass::Warrior => majorules::CharacterClass::Warrior,
-                    crate::state::CharacterClass::Mage => majorules::CharacterClass::Mage,
-                    _ => majorules::CharacterClass::Warrior,
-                },
-                level: player2.character_snapshot.level,
-                hp_max: player2.character_snapshot.hp_max,
-                min_damage: player2.character_snapshot.min_damage,
-                max_damage: player2.character_snapshot.max_damage,
-                crit_chance: player2.character_snapshot.crit_chance,
-                crit_multiplier: player2.character_snapshot.crit_multiplier,
-                dodge_chance: player2.character_snapshot.dodge_chance,
-                defense: player2.character_snapshot.defense,
-                attack_bps: player2.character_snapshot.attack_bps,
-                defense_bps: player2.character_snapshot.defense_bps,
-                crit_bps: player2.character_snapshot.crit_bps,
-            },
-            player2.stake,
-        );
+            Message::CharacterUpdated { player, snapshot } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Ok(Some(mut entry)) = state.character_registry.get(&player.to_string()).await else {
+                    return;
+                };
+                if entry.owner_chain != sender_chain {
+                    return;
+                }
+                // Levels only grow; a reordered older snapshot changes nothing
+                entry.level = entry.level.max(snapshot.level);
+                state.character_registry.insert(&player.to_string(), entry)
+                    .expect("Failed to update registry level");
 
-        let lobby_chain_id = runtime.chain_id();
-        let platform_fee_bps = *state.platform_fee_bps.get();
-        let treasury_owner = state.treasury_owner.get().unwrap();
-        
-        runtime.prepare_message(Message::InitializeBattle {
-            player1: participant1,
-            player2: participant2,
-            lobby_chain_id,
-            platform_fee_bps,
-            treasury_owner,
-        }).with_authentication().send_to(battle_chain_id);
+                let Some(mut character) = state.roster.get(&snapshot.nft_id).await.expect("Failed to read roster") else {
+                    return;
+                };
+                if character.moving_to == Some(sender_chain) {
+                    (character.owner, character.owner_chain) = (player, sender_chain);
+                } else if character.owner_chain != sender_chain {
+                    // Reported ahead of its `CharacterMoved`; the chain's next report takes it over
+                    return;
+                }
+                // The holder reporting it again means any transfer bounced back
+                character.moving_to = None;
+                character.level = character.level.max(snapshot.level);
+                state.roster.insert(&snapshot.nft_id, character).expect("Failed to update roster");
+            }
 
-        // Track active battle
-        let battle_metadata = crate::state::BattleMetadata {
-            battle_chain: battle_chain_id,
-            player1: player1.player,
-            player2: player2.player,
-            total_stake: player1.stake.saturating_add(player2.stake),
-            created_at: runtime.system_time(),
-            status: crate::state::BattleStatus::InProgress,
-            has_prediction_market: true,
-        };
+            Message::CharacterMoved { character_id, to_chain } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(mut character) = state.roster.get(&character_id).await.expect("Failed to read roster") else {
+                    return;
+                };
+                if character.owner_chain != sender_chain {
+                    return;
+                }
+                character.moving_to = Some(to_chain);
+                state.roster.insert(&character_id, character).expect("Failed to update roster");
+            }
 
-        state.active_battles.insert(&battle_chain_id, battle_metadata)
-            .expect("Failed to track battle");
-            
-        // Create prediction market separately
-        let market_id = Self::create_prediction_market_in_lobby(state, runtime, battle_chain_id, player1.player_chain, player2.player_chain).await;
-        
-        // Link battle to market for tracking
-        state.battle_to_market.insert(&battle_chain_id, market_id)
-            .expect("Failed to link battle to market");
+            Message::RequestTokenTransfer { from, to, amount } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&from, state).await != Some(sender_chain) {
+                    return; // Only the sender's own chain holds their tokens
+                }
+                // Tokens nobody can receive go back to the chain that debited them
+                let (owner, chain) = match Self::get_player_chain(&to, state).await {
+                    Some(chain) => (to, chain),
+                    None => {
+                        Self::reject(state, runtime, "TransferTokens", "unregistered", from).await;
+                        (from, sender_chain)
+                    }
+                };
+                runtime.prepare_message(Message::CreditTokens { owner, amount })
+                    .with_authentication()
+                    .send_to(chain);
+            }
+
+            Message::RequestPlaceBet { bettor, market_id, predicted_winner, amount } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                // Every answer goes back to the chain holding the amount, so it never stays held
+                let placed = if Self::get_player_chain(&bettor, state).await != Some(sender_chain) {
+                    Err("not_player_chain")
+                } else {
+                    let internal_id = state.id_codec.get().decode(market_id);
+                    let now = runtime.system_time();
+                    Self::place_bet(state, runtime, bettor, internal_id, predicted_winner, amount, now).await
+                };
+                let answer = match placed {
+                    Ok(()) => Message::BetPlaced { market_id },
+                    Err(reason) => {
+                        Self::reject(state, runtime, "PlaceBetOnMarket", reason, bettor).await;
+                        Message::BetRefused { market_id }
+                    }
+                };
+                runtime.prepare_message(answer)
+                    .with_authentication()
+                    .send_to(sender_chain);
+            }
+
+            Message::MintFeePaid { player, amount } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                Self::collect_character_fee(state, runtime, player, amount).await;
+            }
+
+            Message::StreakInsurancePaid { player, paid } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                let price = state.config.get().streaks.insurance_price;
+                let refusal = if state.streak_insurance.contains_key(&player).await.unwrap_or(true) {
+                    Some("already_insured")
+                } else if paid < price {
+                    Some("price_exceeded")
+                } else {
+                    None
+                };
+                let refund = match refusal {
+                    Some(reason) => {
+                        Self::reject(state, runtime, "BuyStreakInsurance", reason, player).await;
+                        paid
+                    }
+                    None => {
+                        state.streak_insurance.insert(&player, ()).expect("Failed to insure streak");
+                        let revenue = *state.total_platform_revenue.get();
+                        let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, price).await;
+                        state.total_platform_revenue.set(revenue);
+                        Self::burn(state, runtime, price).await;
+                        paid.saturating_sub(price)
+                    }
+                };
+                if refund > Amount::ZERO {
+                    runtime.prepare_message(Message::CreditTokens { owner: player, amount: refund })
+                        .with_authentication()
+                        .send_to(sender_chain);
+                }
+            }
+
+            Message::QuestRewardClaimed { player, day, quest_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Self::get_player_chain(&player, state).await != Some(sender_chain) {
+                    return;
+                }
+                // The lobby prices the reward itself, and pays each quest once a day
+                let Some(quest) = quests::daily_quest(&quest_id) else {
+                    return;
+                };
+                let key = (day, player, quest_id);
+                if day > time::bucket_day(runtime.system_time())
+                    || state.quest_claims.contains_key(&key).await.unwrap_or(true)
+                {
+                    return;
+                }
+                let mut supply = *state.token_supply.get();
+                if supply.mint(quest.reward).is_err() {
+                    return;
+                }
+                state.token_supply.set(supply);
+                state.quest_claims.insert(&key, quest.reward).expect("Failed to record quest claim");
+                runtime.prepare_message(Message::CreditTokens { owner: player, amount: quest.reward })
+                    .with_authentication()
+                    .send_to(sender_chain);
+            }
+
+            Message::CharacterLivesChanged { player, character_id, lives_remaining, revive_fee } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Ok(Some(mut entry)) = state.character_registry.get(&player.to_string()).await else {
+                    return;
+                };
+                if entry.owner_chain != sender_chain {
+                    return;
+                }
+                if let Some(mut character) = state.roster.get(&character_id).await.expect("Failed to read roster") {
+                    if character.owner_chain == sender_chain {
+                        character.lives_remaining = lives_remaining;
+                        state.roster.insert(&character_id, character).expect("Failed to update roster lives");
+                    }
+                }
+                // The registry follows the character whose lives changed last
+                entry.character_id = character_id;
+                entry.lives_remaining = lives_remaining;
+                entry.is_alive = lives_remaining > 0;
+                state.character_registry.insert(&player.to_string(), entry)
+                    .expect("Failed to update registry lives");
+                if revive_fee > Amount::ZERO {
+                    Self::collect_character_fee(state, runtime, player, revive_fee).await;
+                }
+            }
+
+            _ => {
+                // Ignore other message types
+            }
+        }
     }
-    
-    /// Attempt ELO-based matchmaking by requesting player stats
-    async fn attempt_elo_matchmaking(
+
+    /// Book a mint or revive fee `player`'s chain already debited as treasury revenue
+    async fn collect_character_fee(
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: AccountOwner,
+        amount: Amount,
     ) {
-        // For now, use simple level-based matching from character snapshots
-        // In full implementation, would request ELO from player chains first
-        let mut players_with_level = Vec::new();
-        
-        state.waiting_players.for_each_index_value(|owner, entry| {
-            let level = entry.character_snapshot.level;
-            players_with_level.push((owner.clone(), entry.into_owned(), level));
-            Ok(())
-        }).await.unwrap_or(());
-        
-        // Sort by character level as ELO proxy
-        players_with_level.sort_by_key(|(_, _, level)| *level);
-        
-        // Find best match pairs (closest levels)
-        for i in 0..players_with_level.len() {
-            for j in (i + 1)..players_with_level.len() {
-                let (_, _, level1) = &players_with_level[i];
-                let (_, _, level2) = &players_with_level[j];
-                
-                // Match players within 10 levels for fair games
-                let level_diff = if level1 > level2 { level1 - level2 } else { level2 - level1 };
-                
-                if level_diff <= 10 {
-                    let (player1_owner, player1_entry, _) = players_with_level[i].clone();
-                    let (player2_owner, player2_entry, _) = players_with_level[j].clone();
-                    
-                    // Remove both players from queue
-                    state.waiting_players.remove(&player1_owner).ok();
-                    state.waiting_players.remove(&player2_owner).ok();
-                    
-                    // Create battle
-                    Self::create_battle_chain(state, runtime, player1_entry, player2_entry).await;
-                    return; // Match found, exit
-                }
+        let paid = state.mint_fee_ledger.get(&player).await.ok().flatten().unwrap_or_default();
+        state.mint_fee_ledger.insert(&player, paid.saturating_add(amount))
+            .expect("Failed to record character fee");
+        let revenue = *state.total_platform_revenue.get();
+        let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, amount).await;
+        state.total_platform_revenue.set(revenue);
+        Self::burn(state, runtime, amount).await;
+    }
+
+    /// Take a collected `fee` out of circulation
+    async fn burn(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, fee: Amount) {
+        let mut supply = *state.token_supply.get();
+        supply.burned = Self::accumulate(state, runtime, counters::TOKENS_BURNED, supply.burned, fee).await;
+        state.token_supply.set(supply);
+    }
+
+    /// The two fighters of `battle_chain` and the queue that matched them, if it may report
+    /// results: the lobby opened it, and it is active or completed less than
+    /// `RESULT_GRACE_PERIOD` ago
+    async fn known_fighters(
+        state: &LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        battle_chain: ChainId,
+    ) -> Option<((AccountOwner, AccountOwner), QueueMode)> {
+        if let Ok(Some(battle)) = state.active_battles.get(&battle_chain).await {
+            return Some(((battle.player1, battle.player2), battle.mode));
+        }
+        match state.completed_battles.get(&battle_chain).await {
+            Ok(Some(record)) if time::delta_or_zero(runtime.system_time(), record.completed_at) < RESULT_GRACE_PERIOD => {
+                Some(((record.player1, record.player2), record.mode))
             }
+            _ => None,
         }
-        
-        // If no close level match found and queue has been waiting too long, match anyway
-        if players_with_level.len() >= 2 {
-            let now = runtime.system_time();
-            let oldest_wait = players_with_level.iter()
-                .map(|(_, entry, _)| now.delta_since(entry.joined_at).as_micros() / 1_000_000)
-                .max()
-                .unwrap_or(0);
-            
-            // After 60 seconds, match regardless of level difference
-            if oldest_wait >= 60 {
-                let (player1_owner, player1_entry, _) = players_with_level[0].clone();
-                let (player2_owner, player2_entry, _) = players_with_level[1].clone();
-                
-                state.waiting_players.remove(&player1_owner).ok();
-                state.waiting_players.remove(&player2_owner).ok();
-                
-                Self::create_battle_chain(state, runtime, player1_entry, player2_entry).await;
+    }
+
+    /// Whether `a` and `b` are the two `fighters`, in either order
+    fn fought(fighters: (AccountOwner, AccountOwner), a: AccountOwner, b: AccountOwner) -> bool {
+        fighters == (a, b) || fighters == (b, a)
+    }
+
+    /// Rating changes for `battle_chain`'s winner and loser, computed from the cached ratings
+    /// the first time either result arrives and applied to the cache then, along with both
+    /// fighters' standings. Endings without a contested winner leave ratings and streaks alone,
+    /// and only battles matched in a rated queue move ratings
+    async fn rate_battle(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        battle_chain: ChainId,
+        winner: AccountOwner,
+        loser: AccountOwner,
+        end_reason: BattleEndReason,
+        mode: QueueMode,
+    ) -> ((i32, i32), BattleStandings) {
+        if let Some(changes) = state.rated_battles.get(&battle_chain).await.expect("Failed to read rated battles") {
+            let standings = state.battle_standings.get(&battle_chain).await
+                .expect("Failed to read battle standings")
+                .unwrap_or_default();
+            return (changes, standings);
+        }
+        let mut standings = BattleStandings::default();
+        for (standing, player) in [(&mut standings.before.0, winner), (&mut standings.before.1, loser)] {
+            standing.rating = Self::rating(state, player).await;
+            standing.streak = state.win_streaks.get(&player).await.expect("Failed to read win streak").unwrap_or(0);
+        }
+        standings.streaks_after = (standings.before.0.streak, standings.before.1.streak);
+        let mut changes = (0, 0);
+        if end_reason.settles_market() {
+            if mode.is_rated() {
+                changes = elo::rating_changes(standings.before.0.rating, standings.before.1.rating, *state.elo_config.get());
+                Self::set_rating(state, winner, elo::apply(standings.before.0.rating, changes.0));
+                Self::set_rating(state, loser, elo::apply(standings.before.1.rating, changes.1));
+            }
+            standings.streaks_after = (
+                standings.before.0.streak.saturating_add(1),
+                Self::break_streak(state, loser, standings.before.1.streak).await,
+            );
+            state.win_streaks.insert(&winner, standings.streaks_after.0).expect("Failed to extend win streak");
+            state.win_streaks.insert(&loser, standings.streaks_after.1).expect("Failed to reset win streak");
+            Self::reward_streak(state, runtime, battle_chain, winner, standings).await;
+            if mode == QueueMode::Casual {
+                Self::count_progression_win(state, winner).await;
             }
         }
+        state.rated_battles.insert(&battle_chain, changes).expect("Failed to record rated battle");
+        state.battle_standings.insert(&battle_chain, standings).expect("Failed to record battle standings");
+        (changes, standings)
     }
-    
-    /// Create prediction market in lobby for battle
-    async fn create_prediction_market_in_lobby(
+
+    /// Count a practice or casual win toward `player`'s progression gates
+    async fn count_progression_win(state: &mut LobbyState, player: AccountOwner) {
+        let wins = state.progression_wins.get(&player).await
+            .expect("Failed to read progression wins")
+            .unwrap_or(0);
+        state.progression_wins.insert(&player, wins.saturating_add(1))
+            .expect("Failed to count progression win");
+    }
+
+    /// Streak `loser` keeps after a defeat: none, unless insurance covers it, which it uses up
+    async fn break_streak(state: &mut LobbyState, loser: AccountOwner, streak: u64) -> u64 {
+        if streak == 0 || !state.streak_insurance.contains_key(&loser).await.unwrap_or(false) {
+            return 0;
+        }
+        state.streak_insurance.remove(&loser).expect("Failed to use up streak insurance");
+        streak
+    }
+
+    /// Mint `winner`'s streak bonus and announce the streak if it became notable
+    async fn reward_streak(
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
         battle_chain: ChainId,
-        player1_chain: ChainId,
-        player2_chain: ChainId,
+        winner: AccountOwner,
+        standings: BattleStandings,
+    ) {
+        let terms = state.config.get().streaks;
+        let bonus = terms.token_bonus(standings.before.0.streak);
+        if bonus > Amount::ZERO {
+            if let Some(player_chain) = Self::get_player_chain(&winner, state).await {
+                let mut supply = *state.token_supply.get();
+                if supply.mint(bonus).is_ok() {
+                    state.token_supply.set(supply);
+                    runtime.prepare_message(Message::CreditTokens { owner: winner, amount: bonus })
+                        .with_authentication()
+                        .send_to(player_chain);
+                }
+            }
+        }
+        let streak = standings.streaks_after.0;
+        if terms.is_notable(streak) {
+            state.notable_streaks.push(NotableStreak { player: winner, streak, battle_chain, reached_at: runtime.system_time() });
+        }
+    }
+
+    /// `result`'s base XP scaled by the lobby's XP curve
+    fn scaled_xp(
+        state: &LobbyState,
+        result: &FighterResult,
+        winner: AccountOwner,
+        standings: BattleStandings,
+        rounds_played: u8,
     ) -> u64 {
-        // Generate unique market ID
-        let current_market_count = state.market_count.get();
-        let market_id = current_market_count + 1;
-        state.market_count.set(market_id);
-        
-        // Create market with separate lifecycle from battle
-        let market = crate::state::Market {
-            market_id,
-            battle_chain,
-            player1_chain,
-            player2_chain,
-            status: crate::state::MarketStatus::Open,
-            total_pool: Amount::ZERO,
-            player1_pool: Amount::ZERO,
-            player2_pool: Amount::ZERO,
-            winner_chain: None,
-            created_at: runtime.system_time(),
-            closed_at: None,
-            settled_at: None,
+        let (winner_standing, loser_standing) = standings.before;
+        let (standing, opponent) = if result.player == winner {
+            (winner_standing, loser_standing)
+        } else {
+            (loser_standing, winner_standing)
         };
-        
-        // Store market separately from battle tracking
-        state.prediction_markets.insert(&market_id, market)
-            .expect("Failed to create prediction market");
-            
-        market_id
+        state.config.get().xp_curve.scale(result.xp_gained, &XpFactors {
+            won: result.won,
+            standing,
+            opponent,
+            rounds_played,
+            damage_dealt: result.combat_stats.damage_dealt,
+        })
     }
-    
-    /// Place bet on battle outcome
-    async fn place_bet(
+
+    /// Cached rating of `player`, or the initial rating for accounts not rated yet. A rating not
+    /// written in the running season reads as its soft reset
+    async fn rating(state: &LobbyState, player: AccountOwner) -> u64 {
+        let rating = state.ratings.get(&player).await
+            .expect("Failed to read rating")
+            .unwrap_or(elo::INITIAL_RATING);
+        let Some(season) = state.current_season.get() else {
+            return rating;
+        };
+        let rated_in = state.rating_seasons.get(&player).await.expect("Failed to read rating season");
+        if rated_in == Some(season.season_id) {
+            rating
+        } else {
+            season.terms.soft_reset(rating)
+        }
+    }
+
+    /// Cache `player`'s new rating, as of the running season
+    fn set_rating(state: &mut LobbyState, player: AccountOwner, rating: u64) {
+        state.ratings.insert(&player, rating).expect("Failed to update rating");
+        if let Some(season) = state.current_season.get() {
+            let season_id = season.season_id;
+            state.rating_seasons.insert(&player, season_id).expect("Failed to stamp rating season");
+        }
+    }
+
+    /// Count a completed battle in both fighters' records for the running season
+    async fn record_season_battle(
         state: &mut LobbyState,
-        runtime: &mut ContractRuntime<crate::MajorulesContract>,
-        bettor: AccountOwner,
-        market_id: u64,
-        predicted_winner: ChainId,
-        amount: Amount,
+        players: [AccountOwner; 2],
+        winner: AccountOwner,
+        drawn: bool,
     ) {
-        // Get market and validate
-        if let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await {
-            if market.status != crate::state::MarketStatus::Open {
-                return; // Market closed
+        let Some(mut season) = state.current_season.get().clone() else {
+            return;
+        };
+        for player in players {
+            let key = (season.season_id, player);
+            let mut stats = state.season_stats.get(&key).await
+                .expect("Failed to read season stats")
+                .unwrap_or_default();
+            stats.battles += 1;
+            if drawn {
+                stats.draws += 1;
+            } else if player == winner {
+                stats.wins += 1;
+            } else {
+                stats.losses += 1;
             }
-            
-            // Create bet
-            let bet = crate::state::Bet {
-                bettor,
-                market_id,
-                predicted_winner,
-                amount,
-                odds_at_bet: 10000, // 1:1 odds for simplicity
-                placed_at: runtime.system_time(),
-                claimed: false,
+            stats.rating = Self::rating(state, player).await;
+            state.season_stats.insert(&key, stats).expect("Failed to update season stats");
+            season.place(player, stats);
+        }
+        state.current_season.set(Some(season));
+    }
+
+    /// Close `season`: freeze its standings, mint each rewarded rank's share to the player's
+    /// chain and archive it. Players without a chain, or rewards the supply cannot take, go unpaid
+    async fn end_season(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        mut season: Season,
+    ) {
+        let rewards = season.terms.rewards(season.standings.len());
+        for (standing, amount) in season.standings.clone().into_iter().zip(rewards) {
+            let Some(player_chain) = Self::get_player_chain(&standing.player, state).await else {
+                continue;
             };
-            
-            // Update market pools
-            market.total_pool = market.total_pool.saturating_add(amount);
-            if predicted_winner == market.player1_chain {
-                market.player1_pool = market.player1_pool.saturating_add(amount);
-            } else {
-                market.player2_pool = market.player2_pool.saturating_add(amount);
+            let mut supply = *state.token_supply.get();
+            if supply.mint(amount).is_err() {
+                continue;
             }
-            
-            // Store bet and update market
-            state.bets.insert(&(market_id, bettor), bet)
-                .expect("Failed to place bet");
-            state.prediction_markets.insert(&market_id, market)
-                .expect("Failed to update market");
-                
-            // Update total volume
-            let current_volume = state.total_betting_volume.get();
-            state.total_betting_volume.set(current_volume.saturating_add(amount));
+            state.token_supply.set(supply);
+            runtime.prepare_message(Message::CreditTokens { owner: standing.player, amount })
+                .with_authentication()
+                .send_to(player_chain);
+            season.rewards.push(SeasonReward { rank: standing.rank, player: standing.player, amount });
         }
+
+        season.ended_at = Some(runtime.system_time());
+        let id = season.season_id;
+        state.seasons.insert(&id, season).expect("Failed to archive season");
+        state.current_season.set(None);
     }
-    
-    /// Handle battle completion with separate tracking
-    async fn handle_battle_completion(
+
+    /// Forward a fighter's result to their player chain, once per battle and fighter
+    /// (the lobby doesn't store stats)
+    async fn relay_result(
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
         battle_chain: ChainId,
-        winner: AccountOwner,
-        _loser: AccountOwner,
-        rounds_played: u8,
-        total_stake: Amount,
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        result: FighterResult,
     ) {
-        // Get battle metadata before removing
-        if let Ok(Some(battle_metadata)) = state.active_battles.get(&battle_chain).await {
-            // Update platform revenue
-            let platform_fee_bps = state.platform_fee_bps.get();
-            let total_attos = u128::from(total_stake);
-            let fee_attos = total_attos.saturating_mul(*platform_fee_bps as u128) / 10000;
-            let platform_fee = Amount::from_attos(fee_attos);
-            
-            let current_revenue = state.total_platform_revenue.get();
-            state.total_platform_revenue.set(current_revenue.saturating_add(platform_fee));
-            
-            // Get prediction market info if exists
-            let (market_id, betting_volume) = if let Ok(Some(market_id)) = state.battle_to_market.get(&battle_chain).await {
-                let volume = if let Ok(Some(market)) = state.prediction_markets.get(&market_id).await {
-                    market.total_pool
-                } else {
-                    Amount::ZERO
-                };
-                (Some(market_id), volume)
-            } else {
-                (None, Amount::ZERO)
-            };
-            
-            // Create completed battle record
-            let completed_record = crate::state::CompletedBattleRecord {
+        let key = (battle_chain, result.player);
+        if state.relayed_results.contains_key(&key).await.unwrap_or(true) {
+            return;
+        }
+        state.relayed_results.insert(&key, ()).expect("Failed to record relayed result");
+
+        if let Some(player_chain) = Self::get_player_chain(&result.player, state).await {
+            runtime.prepare_message(Message::UpdatePlayerStats {
+                player: result.player,
+                character_id: result.character_id,
+                won: result.won,
+                payout: result.payout,
+                xp_gained: result.xp_gained,
+                elo_change: result.elo_change,
                 battle_chain,
-                player1: battle_metadata.player1,
-                player2: battle_metadata.player2,
-                winner,
-                total_stake,
-                rounds_played,
-                created_at: battle_metadata.created_at,
-                completed_at: runtime.system_time(),
-                prediction_market_id: market_id,
-                total_betting_volume: betting_volume,
-            };
-            
-            // Move from active to completed
-            state.completed_battles.insert(&battle_chain, completed_record)
-                .expect("Failed to record completed battle");
-            state.active_battles.remove(&battle_chain).ok();
-            
-            // Handle prediction market settlement separately
-            if let Some(market_id) = market_id {
-                Self::settle_prediction_market(state, runtime, market_id, winner).await;
-            }
+                rules_digest,
+                end_reason,
+                item_drop: result.item_drop,
+                combat_stats: result.combat_stats,
+                win_streak: result.win_streak,
+            }).with_authentication().send_to(player_chain);
         }
     }
-    
-    /// Settle prediction market separately from battle
-    async fn settle_prediction_market(
+
+    /// Count one more daily `kind` action for `owner`, returning false once the cap is reached.
+    /// Exempt owners are never counted.
+    async fn consume_allowance(
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
-        market_id: u64,
-        winner: AccountOwner,
+        owner: AccountOwner,
+        kind: CreationKind,
+    ) -> bool {
+        if state.creation_exemptions.contains_key(&owner).await.unwrap_or(false) {
+            return true;
+        }
+
+        let key = (time::bucket_day(runtime.system_time()), owner);
+        let mut counts = state.creation_counts.get(&key).await
+            .expect("Failed to read creation counts")
+            .unwrap_or_default();
+        if counts.count(kind) >= state.creation_caps.get().cap(kind) {
+            return false;
+        }
+        counts.record(kind);
+        state.creation_counts.insert(&key, counts)
+            .expect("Failed to update creation counts");
+        true
+    }
+
+    /// `value + delta` for a lifetime counter, recording a pin instead of saturating silently
+    async fn accumulate<T: Counter>(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        counter: &str,
+        mut value: T,
+        delta: T,
+    ) -> T {
+        if checked_accumulate(&mut value, delta) {
+            record_overflow(&mut state.counter_overflows, counter, runtime.system_time()).await;
+        }
+        value
+    }
+
+    /// Log a rejection, coalescing repeats; see `majorules::throttle`
+    async fn reject(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        reason: &str,
+        signer: AccountOwner,
+    ) -> RejectionVerdict {
+        let key = RejectionKey::new(operation, reason, signer);
+        record_rejection(&mut state.rejections, key, runtime.system_time()).await
+    }
+
+    /// The entry a private battle request brings to the lobby, or `None` once the request was
+    /// rejected and the character released
+    #[allow(clippy::too_many_arguments)]
+    async fn admit_private_entry(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        player: AccountOwner,
+        player_chain: ChainId,
+        character_snapshot: CharacterSnapshot,
+        stake: Amount,
+    ) -> Option<crate::state::PlayerQueueEntry> {
+        let reason = if stake <= Amount::ZERO {
+            Some("invalid_stake")
+        } else if !character_snapshot.within_equipment_bounds() {
+            Some("snapshot_out_of_bounds")
+        } else if Self::held_elsewhere(state, &character_snapshot.nft_id, player_chain).await {
+            Some("character_id_taken")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            let verdict = Self::reject(state, runtime, operation, reason, player).await;
+            Self::release_rejected_join(state, runtime, verdict, player, player_chain, character_snapshot.nft_id);
+            return None;
+        }
+        Some(crate::state::PlayerQueueEntry {
+            player,
+            player_chain,
+            character_id: character_snapshot.nft_id.clone(),
+            character_snapshot: character_snapshot.into(),
+            stake,
+            joined_at: runtime.system_time(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+            elo_rating: Self::rating(state, player).await,
+            party: None,
+        })
+    }
+
+    /// Whether the roster gives `character_id` to a chain other than `player_chain`: the id
+    /// was minted first elsewhere, or the character has since moved on
+    async fn held_elsewhere(state: &LobbyState, character_id: &str, player_chain: ChainId) -> bool {
+        state.roster.get(character_id).await
+            .expect("Failed to read roster")
+            .is_some_and(|entry| entry.owner_chain != player_chain)
+    }
+
+    /// Release the character of a refused queue request,
+    /// holding the release back while the rejection is throttled
+    fn release_rejected_join(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        verdict: RejectionVerdict,
+        player: AccountOwner,
+        player_chain: ChainId,
+        character_id: String,
     ) {
-        if let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await {
-            // Determine winner chain from battle result
-            // Find winner chain by comparing with battle participants
-            let winner_chain = if let Ok(Some(battle)) = state.active_battles.get(&market.battle_chain).await {
-                if winner == battle.player1 {
-                    market.player1_chain
-                } else {
-                    market.player2_chain
-                }
+        if verdict.send_feedback() {
+            runtime.prepare_message(Message::QueueLeft { character_id })
+                .with_authentication().send_to(player_chain);
+        } else {
+            state.held_releases.insert(&(player, character_id.clone()), HeldRelease { player_chain, character_id })
+                .expect("Failed to hold queue release");
+        }
+    }
+
+    /// Register `caller`'s player chain to receive the summary of an active battle, or say why not
+    async fn subscribe_to_battle(state: &mut LobbyState, caller: AccountOwner, battle_chain: ChainId) -> Result<(), &'static str> {
+        let Some(chain) = Self::get_player_chain(&caller, state).await else {
+            return Err("unregistered");
+        };
+        if !state.active_battles.contains_key(&battle_chain).await.unwrap_or(false) {
+            return Err("unknown_battle");
+        }
+
+        let mut subscribers = state.battle_subscribers.get(&battle_chain).await.ok().flatten().unwrap_or_default();
+        if subscribers.iter().any(|subscriber| subscriber.owner == caller) {
+            return Err("already_subscribed");
+        }
+        if subscribers.len() >= MAX_BATTLE_SUBSCRIBERS {
+            return Err("subscriber_cap");
+        }
+        subscribers.push(Subscriber { owner: caller, chain });
+        state.battle_subscribers.insert(&battle_chain, subscribers)
+            .expect("Failed to record battle subscriber");
+        Ok(())
+    }
+
+    /// Reject `operation` unless the treasury signed it
+    async fn require_treasury(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        caller: AccountOwner,
+    ) -> Option<OperationResponse> {
+        if Some(caller) == *state.treasury_owner.get() {
+            return None;
+        }
+        Self::reject(state, runtime, operation, "unauthorized", caller).await;
+        Some(OperationResponse::rejected("unauthorized"))
+    }
+
+    /// Settings, unlike funds, may also be managed by the lobby admin
+    async fn require_admin(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        caller: AccountOwner,
+    ) -> Option<OperationResponse> {
+        if Some(caller) == state.config.get().admin {
+            return None;
+        }
+        Self::require_treasury(state, runtime, operation, caller).await
+    }
+
+    /// Open and initialize a player chain owned by `owner`, drawing on their daily allowance;
+    /// `None` once the allowance is spent
+    async fn open_player_chain(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        owner: AccountOwner,
+    ) -> Option<ChainId> {
+        if !Self::consume_allowance(state, runtime, owner, CreationKind::PlayerChain).await {
+            return None;
+        }
+
+        // Create single-owner player chain with proper instantiation
+        let player_chain_id = runtime.open_chain(
+            linera_sdk::linera_base_types::ChainOwnership::single(owner),
+            linera_sdk::linera_base_types::ApplicationPermissions::default(),
+            Amount::ZERO,
+        );
+
+        // Initialize as Player chain via instantiation argument
+        let init_arg = majorules::InitializationArgument {
+            variant: majorules::ChainVariant::Player,
+            treasury_owner: None,
+            platform_fee_bps: None,
+            max_concurrent_battles: None,
+            public_bettors: None,
+            obfuscated_ids: None,
+            market_dust_to: None,
+            matchmaking: None,
+        };
+
+        runtime.prepare_message(majorules::Message::InstantiateChain {
+            variant: init_arg.variant,
+            treasury_owner: init_arg.treasury_owner,
+            platform_fee_bps: init_arg.platform_fee_bps,
+        }).with_authentication().send_to(player_chain_id);
+
+        // Initialize player chain with lobby reference
+        let lobby_chain_id = runtime.chain_id();
+        runtime.prepare_message(Message::InitializePlayerChain {
+            lobby_chain_id,
+            owner,
+            max_concurrent_battles: state.config.get().max_concurrent_battles,
+            ranked_gates: *state.ranked_gates.get(),
+            progression: state.config.get().progression,
+            leveling: *state.leveling_config.get(),
+            minting: *state.mint_terms.get(),
+        }).with_authentication().send_to(player_chain_id);
+        Some(player_chain_id)
+    }
+
+    /// Whether `owner` is queued or fighting, so their player chain holds a stake or awaits a result
+    async fn player_chain_in_use(state: &LobbyState, owner: AccountOwner) -> bool {
+        state.is_queued(owner).await.expect("Failed to read queue")
+            || state.battles_in_flight.contains_key(&owner).await.expect("Failed to read battles in flight")
+    }
+
+    /// Count `fighters` into a battle that opened, or out of one that ended
+    async fn count_fighters(state: &mut LobbyState, fighters: &[AccountOwner], opened: bool) {
+        for fighter in fighters {
+            let battles = state.battles_in_flight.get(fighter).await
+                .expect("Failed to read battles in flight")
+                .unwrap_or(0);
+            let battles = if opened { battles + 1 } else { battles.saturating_sub(1) };
+            if battles == 0 {
+                state.battles_in_flight.remove(fighter).expect("Failed to update battles in flight");
             } else {
-                market.player1_chain // fallback
-            };
-            
-            market.status = crate::state::MarketStatus::Settled;
-            market.winner_chain = Some(winner_chain);
-            market.settled_at = Some(runtime.system_time());
-            
-            state.prediction_markets.insert(&market_id, market)
-                .expect("Failed to settle market");
-                
-            // TODO: Distribute winnings to bettors
+                state.battles_in_flight.insert(fighter, battles).expect("Failed to update battles in flight");
+            }
         }
     }
-    
-    /// Close market when battle starts
-    async fn close_market(
+
+    fn register_player(state: &mut LobbyState, entry: crate::state::CharacterRegistryEntry) {
+        state.character_registry.insert(&entry.owner.to_string(), entry)
+            .expect("Failed to register player chain");
+    }
+
+    async fn get_player_chain(player: &AccountOwner, state: &LobbyState) -> Option<ChainId> {
+        if let Ok(Some(entry)) = state.character_registry.get(&player.to_string()).await {
+            Some(entry.owner_chain)
+        } else {
+            None
+        }
+    }
+
+    async fn create_battle_chain(
         state: &mut LobbyState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
-        market_id: u64,
-    ) {
-        if let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await {
-            market.status = crate::state::MarketStatus::Closed;
-            market.closed_at = Some(runtime.system_time());
+        player1: crate::state::PlayerQueueEntry,
+        player2: crate::state::PlayerQueueEntry,
+    ) -> ChainId {
+        let battle_chain_id = Self::open_battle_chain(state, runtime, &[player1.player, player2.player]);
+
+        // Move both characters from queue to battle engagement on their player chains
+        for entry in [&player1, &player2] {
+            runtime.prepare_message(Message::BattleMatched {
+                battle_chain: battle_chain_id,
+                character_id: entry.character_id.clone(),
+                stake: entry.stake,
+            }).with_authentication().send_to(entry.player_chain);
+        }
+
+        // Send initialization message to battle chain
+        let participant1 = majorules::BattleParticipant::new(
+            player1.player,
+            player1.player_chain,
+            player1.character_snapshot.clone().into(),
+            player1.stake,
+        );
+
+        let participant2 = majorules::BattleParticipant::new(
+            player2.player,
+            player2.player_chain,
+            player2.character_snapshot.clone().into(),
+            player2.stake,
+        );
+
+        let lobby_chain_id = runtime.chain_id();
+        let platform_fee_bps = state.config.get().platform_fee_bps;
+        let treasury_owner = state.treasury_owner.get().unwrap();
+        
+        runtime.prepare_message(Message::InitializeBattle {
+            player1: Box::new(participant1),
+            player2: Box::new(participant2),
+            lobby_chain_id,
+            platform_fee_bps,
+            treasury_owner,
+            rules: state.battle_rules.get().clone(),
+            rules_version: *state.rules_version.get(),
+        }).with_authentication().send_to(battle_chain_id);
+
+        let now = runtime.system_time();
+        Self::track_new_battle(state, battle_chain_id, &player1, &player2, now).await;
+        battle_chain_id
+    }
+
+    /// Open a battle chain owned by its fighters and instantiate it as a battle
+    fn open_battle_chain(
+        state: &LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        fighters: &[AccountOwner],
+    ) -> ChainId {
+        use linera_sdk::linera_base_types::{ChainOwnership, ApplicationPermissions};
+
+        // Create multi-owner battle chain with proper instantiation
+        let battle_chain_id = runtime.open_chain(
+            ChainOwnership::multiple(
+                fighters.iter().map(|&fighter| (fighter, 1u64)),
+                10, // multi_leader_rounds
+                Default::default(), // timeout_config
+            ),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+        );
+        
+        // Initialize as Battle chain via instantiation argument
+        let init_arg = majorules::InitializationArgument {
+            variant: majorules::ChainVariant::Battle,
+            treasury_owner: Some(state.treasury_owner.get().unwrap()),
+            platform_fee_bps: Some(state.config.get().platform_fee_bps),
+            max_concurrent_battles: None,
+            public_bettors: None,
+            obfuscated_ids: None,
+            market_dust_to: None,
+            matchmaking: None,
+        };
+        
+        runtime.prepare_message(majorules::Message::InstantiateChain {
+            variant: init_arg.variant,
+            treasury_owner: init_arg.treasury_owner,
+            platform_fee_bps: init_arg.platform_fee_bps,
+        }).with_authentication().send_to(battle_chain_id);
+
+        battle_chain_id
+    }
+
+    /// Open a team battle between the drafted `sides`, each fighter engaged from their own
+    /// chain. Team battles are unrated and open no prediction market
+    async fn create_team_battle_chain(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        sides: [Vec<PlayerQueueEntry>; 2],
+    ) -> ChainId {
+        let fighters: Vec<AccountOwner> = sides.iter().flatten().map(|entry| entry.player).collect();
+        let battle_chain_id = Self::open_battle_chain(state, runtime, &fighters);
+        for entry in sides.iter().flatten() {
+            runtime.prepare_message(Message::BattleMatched {
+                battle_chain: battle_chain_id,
+                character_id: entry.character_id.clone(),
+                stake: entry.stake,
+            }).with_authentication().send_to(entry.player_chain);
+        }
+
+        let teams = sides.each_ref().map(|side| {
+            side.iter()
+                .map(|entry| majorules::BattleParticipant::new(entry.player, entry.player_chain, entry.character_snapshot.clone().into(), entry.stake))
+                .collect::<Vec<_>>()
+        });
+        let lobby_chain_id = runtime.chain_id();
+        runtime.prepare_message(Message::InitializeTeamBattle {
+            teams,
+            lobby_chain_id,
+            platform_fee_bps: state.config.get().platform_fee_bps,
+            treasury_owner: state.treasury_owner.get().unwrap(),
+            rules: state.battle_rules.get().clone(),
+            rules_version: *state.rules_version.get(),
+        }).with_authentication().send_to(battle_chain_id);
+
+        let [lead1, lead2] = [&sides[0][0], &sides[1][0]];
+        let battle_metadata = crate::state::BattleMetadata {
+            battle_chain: battle_chain_id,
+            player1: lead1.player,
+            player2: lead2.player,
+            total_stake: sides.iter().flatten().fold(Amount::ZERO, |total, entry| total.saturating_add(entry.stake)),
+            created_at: runtime.system_time(),
+            status: crate::state::BattleStatus::InProgress,
+            has_prediction_market: false,
+            rules_version: *state.rules_version.get(),
+            player1_class: lead1.character_snapshot.class,
+            player2_class: lead2.character_snapshot.class,
+            platform_fee_bps: state.config.get().platform_fee_bps,
+            mode: QueueMode::Teams,
+        };
+        state.active_battles.insert(&battle_chain_id, battle_metadata)
+            .expect("Failed to track battle");
+        let members = sides.map(|side| side.into_iter().map(|entry| entry.player).collect::<Vec<_>>());
+        Self::count_fighters(state, &members.concat(), true).await;
+        state.team_battles.insert(&battle_chain_id, members)
+            .expect("Failed to track team battle");
+        battle_chain_id
+    }
+
+    /// Open a practice battle between `player`'s character and the bot. Practice is unrated,
+    /// unstaked and opens no prediction market, so it stays out of `active_battles`
+    async fn create_practice_battle_chain(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: AccountOwner,
+        player_chain: ChainId,
+        character_snapshot: CharacterSnapshot,
+        difficulty: PracticeDifficulty,
+    ) -> ChainId {
+        let battle_chain_id = Self::open_battle_chain(state, runtime, &[player]);
+        runtime.prepare_message(Message::BattleMatched {
+            battle_chain: battle_chain_id,
+            character_id: character_snapshot.nft_id.clone(),
+            stake: Amount::ZERO,
+        }).with_authentication().send_to(player_chain);
+
+        let participant = majorules::BattleParticipant::new(player, player_chain, character_snapshot, Amount::ZERO);
+        let lobby_chain_id = runtime.chain_id();
+        runtime.prepare_message(Message::InitializePracticeBattle {
+            player: Box::new(participant),
+            difficulty,
+            lobby_chain_id,
+            treasury_owner: state.treasury_owner.get().unwrap(),
+            rules: state.battle_rules.get().clone(),
+            rules_version: *state.rules_version.get(),
+        }).with_authentication().send_to(battle_chain_id);
+
+        state.practice_battles.insert(&battle_chain_id, (player, player_chain))
+            .expect("Failed to track practice battle");
+        Self::count_fighters(state, &[player], true).await;
+        battle_chain_id
+    }
+
+    /// Track a battle opened at `opened_at` as active and open its prediction market,
+    /// returning the market id
+    async fn track_new_battle(
+        state: &mut LobbyState,
+        battle_chain_id: ChainId,
+        player1: &crate::state::PlayerQueueEntry,
+        player2: &crate::state::PlayerQueueEntry,
+        opened_at: Timestamp,
+    ) -> u64 {
+        // Track active battle
+        let battle_metadata = crate::state::BattleMetadata {
+            battle_chain: battle_chain_id,
+            player1: player1.player,
+            player2: player2.player,
+            total_stake: player1.stake.saturating_add(player2.stake),
+            created_at: opened_at,
+            status: crate::state::BattleStatus::InProgress,
+            has_prediction_market: true,
+            rules_version: *state.rules_version.get(),
+            player1_class: player1.character_snapshot.class,
+            player2_class: player2.character_snapshot.class,
+            platform_fee_bps: state.config.get().platform_fee_bps,
+            mode: player1.mode,
+        };
+
+        state.active_battles.insert(&battle_chain_id, battle_metadata)
+            .expect("Failed to track battle");
+        Self::count_fighters(state, &[player1.player, player2.player], true).await;
             
-            state.prediction_markets.insert(&market_id, market)
-                .expect("Failed to close market");
+        // Create prediction market separately
+        let public_bettors = state.config.get().public_bettors;
+        let market_id = Self::create_prediction_market_in_lobby(
+            state, battle_chain_id, player1.player_chain, player2.player_chain, public_bettors, opened_at,
+        ).await;
+        
+        // Link battle to market for tracking
+        state.battle_to_market.insert(&battle_chain_id, market_id)
+            .expect("Failed to link battle to market");
+        market_id
+    }
+    
+    /// Pair the two queued players `matchmaking::best_pair` picks by rating, wait and stake,
+    /// never an account against itself nor across queues. Both fight for the smaller stake.
+    /// Returns whether a pair was matched
+    async fn attempt_elo_matchmaking(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    ) -> bool {
+        if state.config.get().matchmaking_paused {
+            return false;
+        }
+        // First come, first considered
+        let entries = state.queue_in_order().await.expect("Failed to read queue");
+
+        // Ranked players avoid opponents they met too often lately
+        let mut recent = Vec::with_capacity(entries.len());
+        for entry in &entries {
+            let pairings = if entry.mode == QueueMode::Ranked {
+                state.recent_pairings.get(&entry.player).await
+                    .expect("Failed to read recent pairings")
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            recent.push(pairings);
+        }
+
+        let now = runtime.system_time();
+        let shared = state.config.get().matchmaking;
+        let seekers: Vec<_> = entries.iter()
+            .map(|entry| Seeker {
+                rating: entry.elo_rating,
+                stake: entry.stake,
+                waited: time::delta_or_zero(now, entry.joined_at),
+            })
+            .collect();
+        // Each queue pairs its own entries by its own settings; the team queue drafts instead
+        let mut pair = None;
+        for mode in QueueMode::ALL.into_iter().filter(|&mode| mode != QueueMode::Teams) {
+            let config = Self::queue_terms(state, mode).await.matchmaking_or(shared);
+            pair = matchmaking::best_pair(&seekers, &config, |i, j| {
+                let (entry1, entry2) = (&entries[i], &entries[j]);
+                entry1.mode == mode
+                    && entry2.mode == mode
+                    && entry1.player != entry2.player
+                    && entry1.player_chain != entry2.player_chain
+                    && entry1.best_of == entry2.best_of
+                    && (mode != QueueMode::Ranked || config.ranked_rematch_allowed(&recent[i], entry2.player, now))
+            });
+            if pair.is_some() {
+                break;
+            }
         }
+        let Some((i, j)) = pair else {
+            return Self::form_teams(state, runtime, &entries, now).await;
+        };
+
+        for (entry, opponent) in [(&entries[i], entries[j].player), (&entries[j], entries[i].player)] {
+            let mut pairings = state.recent_pairings.get(&entry.player).await
+                .expect("Failed to read recent pairings")
+                .unwrap_or_default();
+            matchmaking::record_pairing(&mut pairings, opponent, now);
+            state.recent_pairings.insert(&entry.player, pairings)
+                .expect("Failed to record pairing");
+        }
+
+        let (mut player1, mut player2) = (entries[i].clone(), entries[j].clone());
+        state.dequeue(&(player1.player, player1.character_id.clone())).await.expect("Failed to dequeue matched player");
+        state.dequeue(&(player2.player, player2.character_id.clone())).await.expect("Failed to dequeue matched player");
+        let stake = matchmaking::matched_stake(player1.stake, player2.stake);
+        (player1.stake, player2.stake) = (stake, stake);
+        let waited = seekers[i].waited.as_micros().saturating_add(seekers[j].waited.as_micros()) / 1_000_000;
+        Self::update_queue_stats(state, player1.mode, |stats| {
+            stats.matches = stats.matches.saturating_add(1);
+            stats.matched_stake = stats.matched_stake.saturating_add(stake).saturating_add(stake);
+            stats.total_wait_secs = stats.total_wait_secs.saturating_add(waited);
+        }).await;
+        if player1.best_of > 1 {
+            Self::start_series(state, runtime, player1, player2).await;
+        } else {
+            Self::create_battle_chain(state, runtime, player1, player2).await;
+        }
+        true
+    }
+
+    /// Draft the longest-waiting `2 * TEAM_SIZE` team-queue entries, one per account and player
+    /// chain, into two sides by rating. Everyone fights for the smallest of their stakes.
+    /// Returns whether teams were formed
+    async fn form_teams(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        entries: &[PlayerQueueEntry],
+        now: Timestamp,
+    ) -> bool {
+        let mut drafted: Vec<&PlayerQueueEntry> = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.mode == QueueMode::Teams) {
+            if drafted.iter().all(|other| other.player != entry.player && other.player_chain != entry.player_chain) {
+                drafted.push(entry);
+            }
+        }
+        // Party members queued together stay together; whole groups are taken in queue order
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (index, entry) in drafted.iter().enumerate() {
+            match groups.iter_mut().find(|group| entry.party.is_some() && drafted[group[0]].party == entry.party) {
+                Some(group) => group.push(index),
+                None => groups.push(vec![index]),
+            }
+        }
+        let mut seats = 0;
+        groups.retain(|group| {
+            let fits = group.len() <= TEAM_SIZE && seats + group.len() <= 2 * TEAM_SIZE;
+            seats += if fits { group.len() } else { 0 };
+            fits
+        });
+        let ratings: Vec<u64> = drafted.iter().map(|entry| entry.elo_rating).collect();
+        let Some(sides) = matchmaking::draft_teams(&groups, &ratings, TEAM_SIZE) else {
+            return false;
+        };
+        let stake = groups.iter().flatten().map(|&index| drafted[index].stake).min().unwrap_or(Amount::ZERO);
+        let sides = sides.map(|side| {
+            side.into_iter().map(|index| PlayerQueueEntry { stake, ..drafted[index].clone() }).collect::<Vec<_>>()
+        });
+        let drafted: Vec<&PlayerQueueEntry> = groups.iter().flatten().map(|&index| drafted[index]).collect();
+        let waited = drafted.iter().fold(0u64, |total, entry| total.saturating_add(time::delta_or_zero(now, entry.joined_at).as_micros())) / 1_000_000;
+        for entry in &drafted {
+            state.dequeue(&(entry.player, entry.character_id.clone())).await.expect("Failed to dequeue drafted player");
+        }
+        Self::update_queue_stats(state, QueueMode::Teams, |stats| {
+            stats.matches = stats.matches.saturating_add(1);
+            let matched = Amount::from_attos(u128::from(stake).saturating_mul(2 * TEAM_SIZE as u128));
+            stats.matched_stake = stats.matched_stake.saturating_add(matched);
+            stats.total_wait_secs = stats.total_wait_secs.saturating_add(waited);
+        }).await;
+        Self::create_team_battle_chain(state, runtime, sides).await;
+        true
+    }
+
+    /// Terms `mode`'s queue runs by
+    async fn queue_terms(state: &LobbyState, mode: QueueMode) -> QueueTerms {
+        state.queue_terms.get(&mode).await
+            .expect("Failed to read queue terms")
+            .unwrap_or_else(|| QueueTerms::default_for(mode))
+    }
+
+    async fn update_queue_stats(state: &mut LobbyState, mode: QueueMode, update: impl FnOnce(&mut QueueStats)) {
+        let mut stats = state.queue_stats.get(&mode).await
+            .expect("Failed to read queue stats")
+            .unwrap_or_default();
+        update(&mut stats);
+        state.queue_stats.insert(&mode, stats).expect("Failed to update queue stats");
+    }
+
+    /// Open a series between two matched players: their stakes go into the series pot, and
+    /// its first game starts
+    async fn start_series(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player1: PlayerQueueEntry,
+        player2: PlayerQueueEntry,
+    ) {
+        let series_id = *state.series_count.get() + 1;
+        state.series_count.set(series_id);
+        // Sent ahead of the first `BattleMatched`, so the pot takes the stake before the game
+        // could unlock it
+        for entry in [&player1, &player2] {
+            runtime.prepare_message(Message::SeriesStarted {
+                series_id: state.id_codec.get().encode(series_id),
+                character_id: entry.character_id.clone(),
+                stake: entry.stake,
+            }).with_authentication().send_to(entry.player_chain);
+        }
+
+        let mut series = SeriesMetadata {
+            series_id,
+            mode: player1.mode,
+            player1: player1.player,
+            player2: player2.player,
+            stake: player1.stake,
+            score: SeriesScore::new(player1.best_of),
+            games: Vec::new(),
+            status: SeriesStatus::InProgress,
+            winner: None,
+            platform_fee_bps: state.config.get().platform_fee_bps,
+            created_at: runtime.system_time(),
+            decided_at: None,
+            entries: [player1, player2],
+        };
+        Self::play_series_game(state, runtime, &mut series).await;
+        state.series.insert(&series_id, series).expect("Failed to create series");
+    }
+
+    /// Open the next game of a series; the pot holds the stakes, so the game carries none
+    async fn play_series_game(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        series: &mut SeriesMetadata,
+    ) {
+        let [mut player1, mut player2] = series.entries.clone();
+        (player1.stake, player2.stake) = (Amount::ZERO, Amount::ZERO);
+        let battle_chain = Self::create_battle_chain(state, runtime, player1, player2).await;
+        series.games.push(battle_chain);
+        state.series_battles.insert(&battle_chain, series.series_id)
+            .expect("Failed to link series game");
+    }
+
+    /// Count a finished game toward its series, then open the next game or settle the pot
+    async fn advance_series(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        series_id: u64,
+        winner: AccountOwner,
+        drawn: bool,
+    ) {
+        let Ok(Some(mut series)) = state.series.get(&series_id).await else {
+            return;
+        };
+        match series.score.record((!drawn).then_some(winner == series.player1)) {
+            SeriesOutcome::Undecided => Self::play_series_game(state, runtime, &mut series).await,
+            outcome => Self::settle_series(state, runtime, &mut series, outcome).await,
+        }
+        state.series.insert(&series_id, series).expect("Failed to update series");
+    }
+
+    /// Pay out the pot of a finished series net of the platform fee: all of it to the winner,
+    /// or each stake's share back when the series is drawn. The fee is booked against the
+    /// deciding game
+    async fn settle_series(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        series: &mut SeriesMetadata,
+        outcome: SeriesOutcome,
+    ) {
+        let pot = series.stake.saturating_add(series.stake);
+        let breakdown = FeeBreakdown::compute(pot, series.platform_fee_bps);
+        let revenue = *state.total_platform_revenue.get();
+        let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, breakdown.platform_fee).await;
+        state.total_platform_revenue.set(revenue);
+        if let Some(&deciding_game) = series.games.last() {
+            state.treasury_ledger.insert(&deciding_game, breakdown.platform_fee)
+                .expect("Failed to record treasury ledger entry");
+        }
+        Self::burn(state, runtime, breakdown.platform_fee).await;
+
+        let payouts = match outcome {
+            SeriesOutcome::Won { player1: true } => (breakdown.winner_payout, Amount::ZERO),
+            SeriesOutcome::Won { player1: false } => (Amount::ZERO, breakdown.winner_payout),
+            // Player 2's refund takes the rounding, as in a drawn battle
+            SeriesOutcome::Drawn | SeriesOutcome::Undecided => {
+                let refund = FeeBreakdown::compute(series.stake, series.platform_fee_bps).winner_payout.min(breakdown.winner_payout);
+                (refund, breakdown.winner_payout.saturating_sub(refund))
+            }
+        };
+        series.status = match outcome {
+            SeriesOutcome::Won { .. } => SeriesStatus::Decided,
+            _ => SeriesStatus::Drawn,
+        };
+        series.winner = match outcome {
+            SeriesOutcome::Won { player1 } => Some(if player1 { series.player1 } else { series.player2 }),
+            _ => None,
+        };
+        series.decided_at = Some(runtime.system_time());
+
+        let series_id = state.id_codec.get().encode(series.series_id);
+        for (entry, payout) in series.entries.iter().zip([payouts.0, payouts.1]) {
+            runtime.prepare_message(Message::SeriesEnded {
+                series_id,
+                character_id: entry.character_id.clone(),
+                payout,
+            }).with_authentication().send_to(entry.player_chain);
+        }
+    }
+    
+    /// Create prediction market in lobby for battle, opened at `opened_at`
+    async fn create_prediction_market_in_lobby(
+        state: &mut LobbyState,
+        battle_chain: ChainId,
+        player1_chain: ChainId,
+        player2_chain: ChainId,
+        public_bettors: bool,
+        opened_at: Timestamp,
+    ) -> u64 {
+        // Generate unique market ID
+        let current_market_count = state.market_count.get();
+        let market_id = current_market_count + 1;
+        state.market_count.set(market_id);
+        
+        // Create market with separate lifecycle from battle
+        let market = crate::state::Market {
+            market_id,
+            battle_chain,
+            player1_chain,
+            player2_chain,
+            status: crate::state::MarketStatus::Open,
+            total_pool: Amount::ZERO,
+            player1_pool: Amount::ZERO,
+            player2_pool: Amount::ZERO,
+            winner_chain: None,
+            created_at: opened_at,
+            closed_at: None,
+            settled_at: None,
+            public_bettors,
+            pricing: state.config.get().market_pricing,
+            player1_locked: Amount::ZERO,
+            player2_locked: Amount::ZERO,
+        };
+        
+        // Store market separately from battle tracking
+        state.prediction_markets.insert(&market_id, market)
+            .expect("Failed to create prediction market");
+        let deadline = time::deadline_after(opened_at, MARKET_OPEN_LIMIT);
+        state.market_deadlines.insert(&market_id, deadline)
+            .expect("Failed to track market deadline");
+            
+        market_id
+    }
+    
+    /// Place bet on battle outcome at `placed_at`, locking the odds the pools give it with its
+    /// own stake counted. A bettor holds one bet per market and may only add to it on the same
+    /// side; the odds of a topped-up bet blend by stake. Bets after the market closed, or past
+    /// the betting limits, are refused with the reason
+    #[allow(clippy::too_many_arguments)]
+    async fn place_bet(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        bettor: AccountOwner,
+        market_id: u64,
+        predicted_winner: ChainId,
+        amount: Amount,
+        placed_at: Timestamp,
+    ) -> Result<(), &'static str> {
+        let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await else {
+            return Err("unknown_market");
+        };
+        if !market.accepts_bets() {
+            return Err("market_closed");
+        }
+        if amount == Amount::ZERO {
+            return Err("zero_amount");
+        }
+        let (side_pool, side_locked) = match predicted_winner {
+            winner if winner == market.player1_chain => (&mut market.player1_pool, &mut market.player1_locked),
+            winner if winner == market.player2_chain => (&mut market.player2_pool, &mut market.player2_locked),
+            _ => return Err("unknown_side"),
+        };
+        let previous = state.bets.get(&(market_id, bettor)).await.expect("Failed to read bet");
+        if previous.as_ref().is_some_and(|bet| bet.predicted_winner != predicted_winner) {
+            return Err("opposite_side");
+        }
+
+        // Update market pools; a pool that cannot hold the bet cannot pay it out either
+        let staked = previous.as_ref().map_or(Amount::ZERO, |bet| bet.amount);
+        let (Ok(total_pool), Ok(new_side_pool), Ok(new_staked)) =
+            (market.total_pool.try_add(amount), side_pool.try_add(amount), staked.try_add(amount))
+        else {
+            return Err("pool_overflow");
+        };
+        state.config.get().betting_limits.check(new_staked, total_pool)?;
+        let quote = odds::implied_odds(new_side_pool, total_pool, state.config.get().platform_fee_bps)
+            .unwrap_or(odds::EVEN_ODDS);
+        *side_pool = new_side_pool;
+        *side_locked = side_locked.saturating_add(odds::locked_payout(amount, quote));
+        market.total_pool = total_pool;
+
+        let bet = match previous {
+            Some(bet) => crate::state::Bet {
+                amount: new_staked,
+                odds_at_bet: odds::blend(staked, bet.odds_at_bet, amount, quote),
+                ..bet
+            },
+            None => crate::state::Bet {
+                bettor,
+                market_id,
+                predicted_winner,
+                amount,
+                odds_at_bet: quote,
+                placed_at,
+                claimed: false,
+                payout: None,
+            },
+        };
+
+        // Store bet and update market
+        if !state.bets.contains_key(&(market_id, bettor)).await.unwrap_or(false) {
+            let mut bettors = state.market_bettors.get(&market_id).await.ok().flatten().unwrap_or_default();
+            bettors.push(bettor);
+            state.market_bettors.insert(&market_id, bettors)
+                .expect("Failed to record bettor");
+        }
+        state.bets.insert(&(market_id, bettor), bet)
+            .expect("Failed to place bet");
+        state.prediction_markets.insert(&market_id, market)
+            .expect("Failed to update market");
+
+        // Update total volume
+        let volume = *state.total_betting_volume.get();
+        let volume = Self::accumulate(state, runtime, counters::BETTING_VOLUME, volume, amount).await;
+        state.total_betting_volume.set(volume);
+        Ok(())
+    }
+    
+    /// Handle battle completion at `completed_at` with separate tracking
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_battle_completion(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        battle_chain: ChainId,
+        winner: AccountOwner,
+        rounds_played: u8,
+        total_stake: Amount,
+        rules_digest: u64,
+        end_reason: BattleEndReason,
+        completed_at: Timestamp,
+    ) {
+        // Get battle metadata before removing
+        if let Ok(Some(battle_metadata)) = state.active_battles.get(&battle_chain).await {
+            // Update platform revenue and the per-battle treasury ledger
+            let breakdown = FeeBreakdown::compute(total_stake, battle_metadata.platform_fee_bps);
+            
+            let revenue = *state.total_platform_revenue.get();
+            let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, breakdown.platform_fee).await;
+            state.total_platform_revenue.set(revenue);
+            state.treasury_ledger.insert(&battle_chain, breakdown.platform_fee)
+                .expect("Failed to record treasury ledger entry");
+            Self::burn(state, runtime, breakdown.platform_fee).await;
+            
+            // Get prediction market info if exists
+            let (market_id, betting_volume) = if let Ok(Some(market_id)) = state.battle_to_market.get(&battle_chain).await {
+                let volume = if let Ok(Some(market)) = state.prediction_markets.get(&market_id).await {
+                    market.total_pool
+                } else {
+                    Amount::ZERO
+                };
+                (Some(market_id), volume)
+            } else {
+                (None, Amount::ZERO)
+            };
+            
+            // Create completed battle record
+            let completed_record = crate::state::CompletedBattleRecord {
+                battle_chain,
+                player1: battle_metadata.player1,
+                player2: battle_metadata.player2,
+                winner,
+                total_stake,
+                platform_fee_bps: breakdown.platform_fee_bps,
+                platform_fee: breakdown.platform_fee,
+                winner_payout: breakdown.winner_payout,
+                rounds_played,
+                created_at: battle_metadata.created_at,
+                completed_at,
+                prediction_market_id: market_id,
+                total_betting_volume: betting_volume,
+                rules_version: battle_metadata.rules_version,
+                rules_digest,
+                attestation: Attestation::Unattested,
+                player1_class: battle_metadata.player1_class,
+                player2_class: battle_metadata.player2_class,
+                end_reason,
+                mode: battle_metadata.mode,
+            };
+            
+            // Move from active to completed, indexed by player and by day for the archive
+            state.completed_battles.insert(&battle_chain, completed_record)
+                .expect("Failed to record completed battle");
+            state.active_battles.remove(&battle_chain).ok();
+            Self::count_fighters(state, &[battle_metadata.player1, battle_metadata.player2], false).await;
+            for player in [battle_metadata.player1, battle_metadata.player2] {
+                let mut battles = state.completed_by_owner.get(&player).await
+                    .expect("Failed to read player archive index")
+                    .unwrap_or_default();
+                battles.push((completed_at, battle_chain));
+                state.completed_by_owner.insert(&player, battles)
+                    .expect("Failed to index completed battle by player");
+            }
+            let day = time::bucket_day(completed_at);
+            let mut battles = state.completed_by_day.get(&day).await
+                .expect("Failed to read daily archive index")
+                .unwrap_or_default();
+            battles.push((completed_at, battle_chain));
+            state.completed_by_day.insert(&day, battles)
+                .expect("Failed to index completed battle by day");
+
+            // Account battle counts back the ranked gates; draws count as neither win nor loss
+            let drawn = end_reason == BattleEndReason::Draw;
+            for player in [battle_metadata.player1, battle_metadata.player2] {
+                if let Ok(Some(mut entry)) = state.character_registry.get(&player.to_string()).await {
+                    entry.total_battles += 1;
+                    if !drawn {
+                        if player == winner { entry.wins += 1 } else { entry.losses += 1 }
+                    }
+                    state.character_registry.insert(&player.to_string(), entry)
+                        .expect("Failed to update registry battle counts");
+                }
+            }
+            Self::record_season_battle(state, [battle_metadata.player1, battle_metadata.player2], winner, drawn).await;
+
+            let mut ratings = Vec::new();
+            for player in [battle_metadata.player1, battle_metadata.player2] {
+                ratings.push((player, Self::rating(state, player).await));
+            }
+            Self::refresh_leaderboard(state, &ratings).await;
+
+            // Subscriptions end with the battle; their summaries go out through the outbox
+            if let Ok(Some(subscribers)) = state.battle_subscribers.get(&battle_chain).await {
+                state.battle_subscribers.remove(&battle_chain).ok();
+                let summary = BattleResultSummary {
+                    battle_chain,
+                    winner,
+                    rounds_played,
+                    total_stake,
+                    rules_digest,
+                    end_reason,
+                    completed_at,
+                };
+                // Reversed so popping from the back serves subscribers in order
+                let recipients = subscribers.into_iter().rev().map(|subscriber| subscriber.chain).collect();
+                state.notification_outbox.insert(&battle_chain, PendingNotification { summary, recipients })
+                    .expect("Failed to queue battle summaries");
+            }
+            
+            // Settle the market now, or void it for endings without a contested winner;
+            // payouts are deferred to maintenance
+            if let Some(market_id) = market_id {
+                let player1_won = end_reason.settles_market().then_some(winner == battle_metadata.player1);
+                Self::settle_prediction_market(state, runtime, market_id, player1_won, completed_at).await;
+            }
+
+            // Brackets need someone to go through, so a drawn match sends its nominal winner
+            if let Ok(Some(tournament_id)) = state.tournament_battles.get(&battle_chain).await {
+                state.tournament_battles.remove(&battle_chain).ok();
+                Self::advance_tournament(state, runtime, tournament_id, battle_chain, winner).await;
+            }
+            if let Ok(Some(series_id)) = state.series_battles.get(&battle_chain).await {
+                state.series_battles.remove(&battle_chain).ok();
+                Self::advance_series(state, runtime, series_id, winner, drawn).await;
+            }
+        }
+    }
+    
+    /// Settle prediction market separately from battle and queue its payouts. Winners share
+    /// the pool pari-mutuel after the platform fee on the losing side. Without a winner the
+    /// market is void: every stake is a winning share, so each bettor gets theirs back. A
+    /// market already settled or voided stays as it is
+    async fn settle_prediction_market(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+        player1_won: Option<bool>,
+        settled_at: Timestamp,
+    ) {
+        if let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await {
+            if market.settled_at.is_some() {
+                return;
+            }
+            let (winner_chain, status) = match player1_won {
+                Some(true) => (Some(market.player1_chain), crate::state::MarketStatus::Settled),
+                Some(false) => (Some(market.player2_chain), crate::state::MarketStatus::Settled),
+                None => (None, crate::state::MarketStatus::Cancelled),
+            };
+            let split = match winner_chain {
+                Some(winner) => {
+                    let platform_fee_bps = state.config.get().platform_fee_bps;
+                    let (_, fee) = market.payout_pool(winner, platform_fee_bps);
+                    if fee > Amount::ZERO {
+                        state.market_fee_ledger.insert(&market_id, fee)
+                            .expect("Failed to record market fee");
+                    }
+                    // Fixed odds that promised less than the pool leave the rest to the platform
+                    let (split, surplus) = market.winners_split(winner, platform_fee_bps);
+                    let kept = fee.saturating_add(surplus);
+                    if kept > Amount::ZERO {
+                        let revenue = *state.total_platform_revenue.get();
+                        let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, kept).await;
+                        state.total_platform_revenue.set(revenue);
+                        Self::burn(state, runtime, kept).await;
+                    }
+                    split
+                }
+                None => ProRataSplit::new(market.total_pool, market.total_pool),
+            };
+            
+            market.status = status;
+            market.winner_chain = winner_chain;
+            market.settled_at = Some(settled_at);
+            
+            state.prediction_markets.insert(&market_id, market)
+                .expect("Failed to settle market");
+            state.market_deadlines.remove(&market_id).expect("Failed to clear market deadline");
+            state.result_deadlines.remove(&market_id).expect("Failed to clear result deadline");
+            state.pending_settlements.insert(&market_id, PendingSettlement { winner_chain, next_bettor: 0, split })
+                .expect("Failed to queue market payouts");
+        }
+    }
+
+    /// Drain deferred work, taking at most `budget` steps: one per notification sent, bet paid out,
+    /// overdue market closed or voided, stale queue entry released, queued pair matched or
+    /// tournament started or cancelled.
+    /// Runs after every lobby operation and message so heavy completions amortize across blocks.
+    pub async fn run_maintenance(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let mut work = Self::flush_notifications(state, runtime, budget).await;
+
+        let mut market_ids = Vec::new();
+        state.pending_settlements.for_each_index(|market_id| {
+            market_ids.push(market_id);
+            Ok(())
+        }).await.expect("Failed to list pending settlements");
+
+        for market_id in market_ids {
+            if work >= budget {
+                break;
+            }
+            work += Self::pay_out_market(state, runtime, market_id, budget - work).await;
+        }
+        if work < budget {
+            work += Self::close_overdue_markets(state, runtime, budget - work).await;
+        }
+        if work < budget {
+            work += Self::void_stalled_markets(state, runtime, budget - work).await;
+        }
+        if work < budget {
+            work += Self::expire_queue_entries(state, runtime, budget - work).await;
+        }
+        if work < budget {
+            work += Self::sweep_queue(state, runtime, budget - work).await;
+        }
+        if work < budget {
+            work += Self::expire_private_battles(state, runtime, budget - work).await;
+        }
+        if work < budget {
+            work += Self::start_due_tournaments(state, runtime, budget - work).await;
+        }
+        work
+    }
+
+    /// Close up to `budget` markets whose battle did not start before their deadline
+    async fn close_overdue_markets(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let now = runtime.system_time();
+        let mut overdue = Vec::new();
+        state.market_deadlines.for_each_index_value_while(|market_id, deadline| {
+            if *deadline <= now {
+                overdue.push(market_id);
+            }
+            Ok(overdue.len() < budget as usize)
+        }).await.expect("Failed to list market deadlines");
+
+        for &market_id in &overdue {
+            Self::close_market(state, runtime, market_id).await;
+        }
+        overdue.len() as u32
+    }
+
+    /// Void up to `budget` closed markets whose battle never delivered a result in time, so
+    /// every bettor can claim their stake back. The battle is given up on too: it leaves the
+    /// active battles and stops counting against its fighters, so their player chains can be
+    /// replaced again, and a result arriving after all is not rated
+    async fn void_stalled_markets(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let now = runtime.system_time();
+        let mut stalled = Vec::new();
+        state.result_deadlines.for_each_index_value_while(|market_id, deadline| {
+            if *deadline <= now {
+                stalled.push(market_id);
+            }
+            Ok(stalled.len() < budget as usize)
+        }).await.expect("Failed to list result deadlines");
+
+        for &market_id in &stalled {
+            Self::settle_prediction_market(state, runtime, market_id, None, now).await;
+            let Ok(Some(market)) = state.prediction_markets.get(&market_id).await else {
+                continue;
+            };
+            Self::abandon_battle(state, market.battle_chain).await;
+        }
+        stalled.len() as u32
+    }
+
+    /// Stop tracking a battle that will not report back, counting its fighters, teammates
+    /// included, out of it
+    async fn abandon_battle(state: &mut LobbyState, battle_chain: ChainId) {
+        let Ok(Some(battle)) = state.active_battles.get(&battle_chain).await else {
+            return;
+        };
+        state.active_battles.remove(&battle_chain).expect("Failed to abandon battle");
+        let mut fighters = vec![battle.player1, battle.player2];
+        if let Some(sides) = state.team_battles.get(&battle_chain).await.expect("Failed to read team battle") {
+            fighters = sides.concat();
+            state.team_battles.remove(&battle_chain).expect("Failed to abandon team battle");
+        }
+        Self::count_fighters(state, &fighters, false).await;
+    }
+
+    /// Draw the bracket of up to `budget` tournaments whose start time came with enough
+    /// entrants, and cancel those whose grace period ran out without them
+    async fn start_due_tournaments(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let now = runtime.system_time();
+        let mut due = Vec::new();
+        state.tournament_starts.for_each_index_value(|tournament_id, start_time| {
+            if *start_time <= now {
+                due.push(tournament_id);
+            }
+            Ok(())
+        }).await.expect("Failed to list tournament starts");
+
+        let mut work = 0;
+        for tournament_id in due {
+            if work >= budget {
+                break;
+            }
+            let Some(mut tournament) = state.tournaments.get(&tournament_id).await.expect("Failed to read tournament") else {
+                // A start left behind by a tournament that is gone has nothing to start
+                state.tournament_starts.remove(&tournament_id).expect("Failed to unschedule tournament");
+                continue;
+            };
+            let entrants = state.tournament_entrants.get(&tournament_id).await
+                .expect("Failed to read tournament entrants")
+                .unwrap_or_default();
+            match tournament.schedule.phase(now, tournament.entrants, &ScheduleLimits::default()) {
+                SchedulePhase::ReadyToStart => {
+                    let seeded = entrants.iter()
+                        .map(|entrant| Entrant { owner: entrant.player, level: entrant.character_snapshot.level })
+                        .collect();
+                    match bracket::round_one(seeded, tournament.terms.options, tournament_id, tournament.schedule.start_time) {
+                        Ok(pairings) => {
+                            tournament.status = TournamentStatus::InProgress;
+                            tournament.round = 1;
+                            tournament.matches = pairings.into_iter()
+                                .map(|pairing| TournamentMatch {
+                                    player1: pairing.high,
+                                    player2: pairing.low,
+                                    battle_chain: None,
+                                    // A bye advances its top seed unplayed
+                                    winner: pairing.low.is_none().then_some(pairing.high),
+                                })
+                                .collect();
+                            Self::play_round(state, runtime, &mut tournament, &entrants).await;
+                        }
+                        Err(BracketError::TooFewEntrants | BracketError::NotPowerOfTwo { .. }) => {
+                            Self::cancel_tournament(runtime, &mut tournament, &entrants);
+                        }
+                    }
+                }
+                SchedulePhase::Expired => Self::cancel_tournament(runtime, &mut tournament, &entrants),
+                _ => continue,
+            }
+            state.tournament_starts.remove(&tournament_id).expect("Failed to unschedule tournament");
+            state.tournaments.insert(&tournament_id, tournament).expect("Failed to update tournament");
+            work += 1;
+        }
+        work
+    }
+
+    /// Open a battle chain for every match of the current round that has two players
+    async fn play_round(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        tournament: &mut Tournament,
+        entrants: &[PlayerQueueEntry],
+    ) {
+        let entry = |owner: AccountOwner| {
+            entrants.iter().find(|entrant| entrant.player == owner).cloned().expect("Bracket players are entrants")
+        };
+        for round_match in &mut tournament.matches {
+            let (Some(player2), None) = (round_match.player2, round_match.battle_chain) else {
+                continue;
+            };
+            let battle_chain = Self::create_battle_chain(state, runtime, entry(round_match.player1), entry(player2)).await;
+            round_match.battle_chain = Some(battle_chain);
+            state.tournament_battles.insert(&battle_chain, tournament.tournament_id)
+                .expect("Failed to link tournament battle");
+        }
+    }
+
+    /// Record the winner of a tournament match, send the loser home, and once the round is
+    /// decided pair its winners for the next one or crown the champion
+    async fn advance_tournament(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        tournament_id: u64,
+        battle_chain: ChainId,
+        winner: AccountOwner,
+    ) {
+        let Ok(Some(mut tournament)) = state.tournaments.get(&tournament_id).await else {
+            return;
+        };
+        let entrants = state.tournament_entrants.get(&tournament_id).await
+            .expect("Failed to read tournament entrants")
+            .unwrap_or_default();
+        let Some(round_match) = tournament.matches.iter_mut().find(|round_match| round_match.battle_chain == Some(battle_chain)) else {
+            return;
+        };
+        round_match.winner = Some(winner);
+        let loser = if winner == round_match.player1 { round_match.player2 } else { Some(round_match.player1) };
+        let is_final = tournament.matches.len() == 1;
+
+        if is_final {
+            tournament.status = TournamentStatus::Completed;
+            tournament.champion = Some(winner);
+            tournament.runner_up = loser;
+            let breakdown = FeeBreakdown::compute(tournament.prize_pool, state.config.get().platform_fee_bps);
+            let revenue = *state.total_platform_revenue.get();
+            let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, breakdown.platform_fee).await;
+            state.total_platform_revenue.set(revenue);
+            let runner_up_prize = Amount::from_attos(
+                u128::from(breakdown.winner_payout) * tournament.terms.runner_up_share_bps as u128 / 10_000,
+            );
+            let champion_prize = breakdown.winner_payout.saturating_sub(runner_up_prize);
+            Self::exit_tournament(state, runtime, tournament_id, &entrants, winner, champion_prize);
+            if let Some(loser) = loser {
+                Self::exit_tournament(state, runtime, tournament_id, &entrants, loser, runner_up_prize);
+            }
+        } else {
+            if let Some(loser) = loser {
+                Self::exit_tournament(state, runtime, tournament_id, &entrants, loser, Amount::ZERO);
+            }
+            if tournament.round_decided() {
+                tournament.round += 1;
+                tournament.matches = tournament.matches.chunks(2)
+                    .map(|pair| TournamentMatch {
+                        player1: pair[0].winner.expect("Decided rounds have winners"),
+                        player2: pair.get(1).and_then(|next| next.winner),
+                        battle_chain: None,
+                        winner: None,
+                    })
+                    .collect();
+                Self::play_round(state, runtime, &mut tournament, &entrants).await;
+            }
+        }
+        state.tournaments.insert(&tournament_id, tournament).expect("Failed to update tournament");
+    }
+
+    /// Refund every entrant of a tournament that will not be played
+    fn cancel_tournament(
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        tournament: &mut Tournament,
+        entrants: &[PlayerQueueEntry],
+    ) {
+        tournament.status = TournamentStatus::Cancelled;
+        for entrant in entrants {
+            runtime.prepare_message(Message::TournamentExited {
+                tournament_id: tournament.tournament_id,
+                character_id: entrant.character_id.clone(),
+                payout: tournament.terms.entry_fee,
+            }).with_authentication().send_to(entrant.player_chain);
+        }
+    }
+
+    /// Tell `player`'s chain its character is out of the tournament, paying `payout`
+    fn exit_tournament(
+        state: &LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        tournament_id: u64,
+        entrants: &[PlayerQueueEntry],
+        player: AccountOwner,
+        payout: Amount,
+    ) {
+        let Some(entrant) = entrants.iter().find(|entrant| entrant.player == player) else {
+            return;
+        };
+        runtime.prepare_message(Message::TournamentExited {
+            tournament_id: state.id_codec.get().encode(tournament_id),
+            character_id: entrant.character_id.clone(),
+            payout,
+        }).with_authentication().send_to(entrant.player_chain);
+    }
+
+    /// Release up to `budget` queue entries that waited longer than [`crate::state::QUEUE_ENTRY_TTL`]
+    async fn expire_queue_entries(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        // The queue order holds the oldest entries first, so the expired ones lead it
+        let now = runtime.system_time();
+        let mut expired = Vec::new();
+        state.queue_order.for_each_index_while(|key| {
+            let stale = time::delta_or_zero(now, key.joined_at) >= crate::state::QUEUE_ENTRY_TTL;
+            if stale {
+                expired.push((key.player, key.character_id));
+            }
+            Ok(stale && expired.len() < budget as usize)
+        }).await.expect("Failed to list queued players");
+
+        let work = expired.len() as u32;
+        for seat in expired {
+            let entry = state.dequeue(&seat).await.expect("Failed to expire queue entry");
+            if let Some(entry) = entry {
+                runtime.prepare_message(Message::QueueLeft { character_id: entry.character_id })
+                    .with_authentication()
+                    .send_to(entry.player_chain);
+            }
+        }
+        work
+    }
+
+    /// Match up to `budget` queued pairs that waiting has brought within each other's windows.
+    /// Windows and wait credits only grow every `widen_every_secs`, so the queue is swept at
+    /// most as often as the fastest-widening queue's; joins still match on arrival
+    async fn sweep_queue(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let now = runtime.system_time();
+        if now < *state.matchmaking_sweep_at.get() {
+            return 0;
+        }
+        let mut work = 0;
+        while work < budget {
+            if !Self::attempt_elo_matchmaking(state, runtime).await {
+                // The queue whose windows widen most often sets the pace
+                let shared = state.config.get().matchmaking;
+                let mut period = u64::MAX;
+                for mode in QueueMode::ALL {
+                    period = period.min(Self::queue_terms(state, mode).await.matchmaking_or(shared).widen_every_secs);
+                }
+                state.matchmaking_sweep_at.set(time::deadline_after(now, TimeDelta::from_secs(period.max(1))));
+                break;
+            }
+            work += 1;
+        }
+        work
+    }
+
+    /// Release up to `budget` private battles nobody joined within the queue entry TTL
+    async fn expire_private_battles(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let now = runtime.system_time();
+        let mut expired = Vec::new();
+        state.pending_private_battles.for_each_index_value_while(|id, host| {
+            if host.expired(now) {
+                expired.push((id, host.player_chain, host.character_id.clone()));
+            }
+            Ok(expired.len() < budget as usize)
+        }).await.expect("Failed to list private battles");
+
+        let work = expired.len() as u32;
+        for (id, player_chain, character_id) in expired {
+            state.pending_private_battles.remove(&id).expect("Failed to expire private battle");
+            state.private_battle_invites.remove(&id).expect("Failed to expire private battle");
+            runtime.prepare_message(Message::QueueLeft { character_id })
+                .with_authentication()
+                .send_to(player_chain);
+        }
+        work
+    }
+
+    /// Pay `cranker` for `work` maintenance steps out of the community pool, within the
+    /// per-crank and daily caps, and record the crank
+    async fn pay_crank_bounty(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        cranker: AccountOwner,
+        work: u32,
+    ) {
+        let now = runtime.system_time();
+        let day = time::bucket_day(now);
+        let paid_today = state.crank_payouts.get(&day).await
+            .expect("Failed to read crank payouts")
+            .unwrap_or_default();
+        let bounty = CRANK_BOUNTY_PER_STEP.saturating_mul(work as u128)
+            .min(CRANK_BOUNTY_CAP)
+            .min(CRANK_DAILY_BOUNTY_CAP.saturating_sub(paid_today))
+            .min(*state.community_pool.get());
+
+        if bounty > Amount::ZERO {
+            state.community_pool.set(state.community_pool.get().saturating_sub(bounty));
+            state.crank_payouts.insert(&day, paid_today.saturating_add(bounty))
+                .expect("Failed to record crank payouts");
+            let earned = state.crank_rewards.get(&cranker).await
+                .expect("Failed to read crank rewards")
+                .unwrap_or_default();
+            state.crank_rewards.insert(&cranker, earned.saturating_add(bounty))
+                .expect("Failed to credit crank bounty");
+        }
+
+        let mut stats = state.crank_stats.get().clone();
+        stats.cranks += 1;
+        stats.work_done += work as u64;
+        stats.bounty_paid = stats.bounty_paid.saturating_add(bounty);
+        state.crank_stats.set(stats);
+        // Idle cranks only count towards the totals, so spamming them cannot grow the log
+        if work > 0 {
+            state.crank_log.push(CrankRecord { cranker, work, bounty, cranked_at: now });
+        }
+    }
+
+    /// Send up to `budget` queued battle summaries
+    async fn flush_notifications(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        budget: u32,
+    ) -> u32 {
+        let mut battles = Vec::new();
+        state.notification_outbox.for_each_index(|battle_chain| {
+            battles.push(battle_chain);
+            Ok(())
+        }).await.expect("Failed to list queued notifications");
+
+        let mut work = 0;
+        for battle_chain in battles {
+            let Ok(Some(mut pending)) = state.notification_outbox.get(&battle_chain).await else {
+                continue;
+            };
+            while work < budget {
+                let Some(recipient) = pending.recipients.pop() else {
+                    break;
+                };
+                runtime.prepare_message(Message::BattleSummaryNotification { summary: pending.summary.clone() })
+                    .with_authentication()
+                    .send_to(recipient);
+                work += 1;
+            }
+            if pending.recipients.is_empty() {
+                state.notification_outbox.remove(&battle_chain).expect("Failed to clear battle summaries");
+            } else {
+                state.notification_outbox.insert(&battle_chain, pending).expect("Failed to track battle summaries");
+                break;
+            }
+        }
+        work
+    }
+
+    /// Compute parimutuel payouts for up to `budget` bets of a settled market
+    async fn pay_out_market(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+        budget: u32,
+    ) -> u32 {
+        let Ok(Some(mut pending)) = state.pending_settlements.get(&market_id).await else {
+            return 0;
+        };
+        let bettors = state.market_bettors.get(&market_id).await.ok().flatten().unwrap_or_default();
+        let Ok(Some(market)) = state.prediction_markets.get(&market_id).await else {
+            return 0;
+        };
+
+        let mut work = 0;
+        while work < budget && (pending.next_bettor as usize) < bettors.len() {
+            let key = (market_id, bettors[pending.next_bettor as usize]);
+            if let Ok(Some(mut bet)) = state.bets.get(&key).await {
+                let payout = if pending.winner_chain.is_none_or(|winner| bet.predicted_winner == winner) {
+                    pending.split.share(pending.next_bettor, market.payout_weight(&bet))
+                } else {
+                    Amount::ZERO
+                };
+                if pending.winner_chain.is_none() {
+                    // A void split hands every bet its own stake
+                    let refunded = state.market_refunds.get(&market_id).await
+                        .expect("Failed to read market refunds")
+                        .unwrap_or_default();
+                    state.market_refunds.insert(&market_id, refunded.saturating_add(payout))
+                        .expect("Failed to record market refund");
+                }
+                bet.payout = Some(payout);
+                state.bets.insert(&key, bet).expect("Failed to record payout");
+            }
+            pending.next_bettor += 1;
+            work += 1;
+        }
+
+        if pending.next_bettor as usize >= bettors.len() {
+            let dust = pending.split.finish(*state.market_rounding.get());
+            Self::route_dust(state, runtime, market_id, &bettors, dust).await;
+            state.pending_settlements.remove(&market_id).expect("Failed to finish market payouts");
+        } else {
+            state.pending_settlements.insert(&market_id, pending).expect("Failed to track market payouts");
+        }
+        work
+    }
+    
+    /// Credit a finished market's rounding remainder to its bettor or the platform and ledger it
+    async fn route_dust(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+        bettors: &[AccountOwner],
+        dust: Dust,
+    ) {
+        if dust.amount == Amount::ZERO {
+            return;
+        }
+        let recipient = dust.recipient.map(|index| bettors[index as usize]);
+        match recipient {
+            Some(bettor) => {
+                if let Ok(Some(mut bet)) = state.bets.get(&(market_id, bettor)).await {
+                    bet.payout = Some(bet.payout.unwrap_or_default().saturating_add(dust.amount));
+                    state.bets.insert(&(market_id, bettor), bet).expect("Failed to record payout");
+                }
+            }
+            None => {
+                let revenue = *state.total_platform_revenue.get();
+                let revenue = Self::accumulate(state, runtime, counters::PLATFORM_REVENUE, revenue, dust.amount).await;
+                state.total_platform_revenue.set(revenue);
+            }
+        }
+        state.dust_ledger.insert(&market_id, DustEntry { amount: dust.amount, recipient })
+            .expect("Failed to record market dust");
+    }
+
+    /// Send `bettor`'s share of a market whose payouts maintenance has finished to their
+    /// player chain, once: their winnings, or their stake back from a voided market
+    async fn claim_winnings(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        bettor: AccountOwner,
+        market_id: u64,
+    ) -> Result<(), &'static str> {
+        let Ok(Some(mut bet)) = state.bets.get(&(market_id, bettor)).await else {
+            return Err("no_bet");
+        };
+        if bet.claimed {
+            return Err("already_claimed");
+        }
+        // Dust lands on a payout only once the whole market is paid out
+        let pending = state.pending_settlements.contains_key(&market_id).await
+            .expect("Failed to read pending settlements");
+        let Some(amount) = bet.payout.filter(|_| !pending) else {
+            return Err("not_settled");
+        };
+        if amount == Amount::ZERO {
+            return Err("nothing_to_claim");
+        }
+        let Some(player_chain) = Self::get_player_chain(&bettor, state).await else {
+            return Err("no_player_chain");
+        };
+
+        bet.claimed = true;
+        state.bets.insert(&(market_id, bettor), bet)
+            .expect("Failed to record claim");
+        runtime.prepare_message(Message::DistributeWinnings {
+            bettor,
+            amount,
+            market_id: state.id_codec.get().encode(market_id),
+        }).with_authentication().send_to(player_chain);
+        Ok(())
+    }
+
+    /// Close market when battle starts; it is voided if the battle's result does not follow
+    /// within `MARKET_RESULT_LIMIT`
+    async fn close_market(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+    ) {
+        if let Ok(Some(mut market)) = state.prediction_markets.get(&market_id).await {
+            if !market.accepts_bets() {
+                return;
+            }
+            let now = runtime.system_time();
+            market.status = crate::state::MarketStatus::Closed;
+            market.closed_at = Some(now);
+            
+            state.prediction_markets.insert(&market_id, market)
+                .expect("Failed to close market");
+            state.market_deadlines.remove(&market_id).expect("Failed to clear market deadline");
+            state.result_deadlines.insert(&market_id, time::deadline_after(now, MARKET_RESULT_LIMIT))
+                .expect("Failed to track result deadline");
+        }
+    }
+
+    /// Apply a planned fixture world through the same paths live traffic takes: registration,
+    /// battle tracking, betting, closing and completion. A world already applied is rejected
+    async fn generate_fixtures(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        caller: AccountOwner,
+        world: FixtureWorld,
+    ) {
+        if let Some(first) = world.players.first() {
+            if state.character_registry.contains_key(&first.owner.to_string()).await.unwrap_or(true) {
+                Self::reject(state, runtime, "GenerateFixtures", "already_generated", caller).await;
+                return;
+            }
+        }
+
+        // Players joined before the oldest battle they could have fought
+        let joined_at = runtime.system_time().saturating_sub_micros(fixtures::FIXTURE_DAYS * time::MICROS_PER_DAY);
+        for player in &world.players {
+            Self::register_player(state, crate::state::CharacterRegistryEntry {
+                character_id: player.name.clone(),
+                owner: player.owner,
+                owner_chain: player.chain,
+                class: player.snapshot.class.into(),
+                level: player.snapshot.level,
+                created_at: joined_at,
+                total_battles: 0,
+                wins: 0,
+                losses: 0,
+                is_alive: true,
+                lives_remaining: STARTING_LIVES,
+            });
+        }
+
+        let rules_digest = state.battle_rules.get().digest();
+        for battle in world.battles {
+            let (player1, player2) = (&world.players[battle.player1], &world.players[battle.player2]);
+            let fighter = |player: &FixturePlayer| crate::state::PlayerQueueEntry {
+                player: player.owner,
+                player_chain: player.chain,
+                character_id: player.name.clone(),
+                character_snapshot: player.snapshot.clone().into(),
+                stake: battle.stake,
+                joined_at: battle.opened_at,
+                mode: QueueMode::Ranked,
+                best_of: 1,
+                elo_rating: player.elo,
+                party: None,
+            };
+            let market_id = Self::track_new_battle(state, battle.chain, &fighter(player1), &fighter(player2), battle.opened_at).await;
+
+            for bet in &battle.bets {
+                let predicted_winner = if bet.on_player1 { player1.chain } else { player2.chain };
+                let bettor = world.players[bet.bettor].owner;
+                if let Err(reason) = Self::place_bet(state, runtime, bettor, market_id, predicted_winner, bet.amount, bet.placed_at).await {
+                    Self::reject(state, runtime, "PlaceBet", reason, bettor).await;
+                }
+            }
+
+            match battle.stage {
+                FixtureStage::Open => {}
+                FixtureStage::Closed => Self::close_market(state, runtime, market_id).await,
+                FixtureStage::Completed(outcome) => {
+                    let winner = if outcome.player1_won { player1.owner } else { player2.owner };
+                    Self::handle_battle_completion(
+                        state,
+                        runtime,
+                        battle.chain,
+                        winner,
+                        outcome.rounds_played,
+                        battle.stake.saturating_add(battle.stake),
+                        rules_digest,
+                        outcome.end_reason,
+                        outcome.completed_at,
+                    ).await;
+                }
+            }
+        }
+
+        let ratings: Vec<_> = world.players.iter().map(|player| (player.owner, player.elo)).collect();
+        for &(player, rating) in &ratings {
+            state.ratings.insert(&player, rating).expect("Failed to seed rating");
+        }
+        Self::refresh_leaderboard(state, &ratings).await;
+    }
+
+    /// Rebuild the leaderboard entries of `ratings`' players from their registry records and
+    /// won battles, then rank everyone by rating, most wins breaking ties, keeping the top
+    /// [`LEADERBOARD_SIZE`]
+    async fn refresh_leaderboard(state: &mut LobbyState, ratings: &[(AccountOwner, u64)]) {
+        let mut leaderboard: Vec<LeaderboardEntry> = state.leaderboard.get().iter()
+            .filter(|entry| ratings.iter().all(|(player, _)| *player != entry.player))
+            .cloned()
+            .collect();
+
+        for &(player, elo_rating) in ratings {
+            let Ok(Some(registered)) = state.character_registry.get(&player.to_string()).await else {
+                continue;
+            };
+            let mut total_earnings = Amount::ZERO;
+            let battles = state.completed_by_owner.get(&player).await
+                .expect("Failed to read player archive index")
+                .unwrap_or_default();
+            for (_, battle_chain) in battles {
+                if let Ok(Some(record)) = state.completed_battles.get(&battle_chain).await {
+                    if record.winner == player {
+                        total_earnings = total_earnings.saturating_add(record.winner_payout);
+                    }
+                }
+            }
+            let win_rate = if registered.total_battles == 0 {
+                0.0
+            } else {
+                registered.wins as f64 / registered.total_battles as f64
+            };
+            leaderboard.push(LeaderboardEntry {
+                rank: 0,
+                player,
+                elo_rating,
+                total_battles: registered.total_battles,
+                wins: registered.wins,
+                losses: registered.losses,
+                win_rate,
+                total_earnings,
+                win_streak: state.win_streaks.get(&player).await
+                    .expect("Failed to read win streak")
+                    .unwrap_or(0),
+            });
+        }
+
+        leaderboard.sort_by(|a, b| b.elo_rating.cmp(&a.elo_rating).then(b.wins.cmp(&a.wins)));
+        leaderboard.truncate(LEADERBOARD_SIZE);
+        for (index, entry) in leaderboard.iter_mut().enumerate() {
+            entry.rank = index as u64 + 1;
+        }
+        state.leaderboard.set(leaderboard);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        linera_base_types::{
+            AccountOwner, Amount, ApplicationPermissions, ChainId, ChainOwnership, CryptoHash, TimeDelta, Timestamp,
+        },
+        util::BlockingWait,
+        views::View,
+        ContractRuntime,
+    };
+    use majorules::{
+        betting::BettingLimits,
+        bracket::{BracketOptions, Seeding},
+        counters::{self, CounterOverflow},
+        elo::EloConfig,
+        fees::{DustDestination, RoundingPolicy},
+        leveling::LevelingConfig,
+        matchmaking::{MatchmakingConfig, QueueTerms},
+        minting::{max_trait_bps, MintTerms},
+        odds::MarketPricing,
+        practice::PracticeDifficulty,
+        rewards::{StreakTerms, XpCurve},
+        season::SeasonTerms,
+        idcodec::IdCodec, throttle::RejectionKey, time::MICROS_PER_DAY, invite_digest, Attestation, BattleRules, Bps, CharacterClass,
+        BattleEndReason, CharacterSnapshot, CombatStats, FighterResult, ItemRarity, Message, Operation, OperationResponse, Parameters,
+        ProgressionGates, QueueMode, RankedGates, TiebreakBy, TournamentTerms, STARTING_LIVES,
+    };
+
+    use super::{
+        LobbyContract, CRANK_BOUNTY_CAP, CRANK_BOUNTY_PER_STEP, CRANK_DAILY_BOUNTY_CAP, LEADERBOARD_SIZE, MAINTENANCE_BUDGET,
+        MARKET_OPEN_LIMIT, MARKET_RESULT_LIMIT, MAX_BATTLE_SUBSCRIBERS, MAX_PLATFORM_FEE_BPS, RESULT_GRACE_PERIOD,
+    };
+    use crate::{
+        actions::{lobby_actions, maintenance_backlog, CanLeaveQueue, CanPlaceBet, LobbyAction},
+        player_contract::PlayerContract,
+        state::{
+            BattleMetadata, BattleStatus, CharacterRegistryEntry, RosterEntry, CreationCaps, LobbyState, MarketStatus, PlayerState, QueueStats, SeriesStatus, TournamentStatus,
+            QUEUE_ENTRY_TTL,
+        },
+    };
+
+    fn owner(name: &str) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(name))
+    }
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    fn setup() -> (LobbyState, ContractRuntime<crate::MajorulesContract>) {
+        let runtime = ContractRuntime::new()
+            .with_chain_id(chain("lobby"))
+            .with_authenticated_signer(owner("treasury"))
+            .with_system_time(Timestamp::from(0));
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        state.treasury_owner.set(Some(owner("treasury")));
+        (state, runtime)
+    }
+
+    fn operate(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, signer: &str, operation: Operation) -> OperationResponse {
+        runtime.set_authenticated_signer(Some(owner(signer)));
+        LobbyContract::execute_operation(state, runtime, operation).blocking_wait()
+    }
+
+    /// Open the signer's `index`th player chain: the first is created, later ones replace it
+    fn create_player_chain(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, signer: &str, index: u32) {
+        let chain_id = chain(&format!("{signer}-{index}"));
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::single(owner(signer)),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain_id,
+        );
+        let operation = if index == 0 { Operation::CreatePlayerChain } else { Operation::ReplacePlayerChain };
+        assert_eq!(operate(state, runtime, signer, operation), OperationResponse::PlayerChain { chain_id });
+    }
+
+    fn snapshot(player: &str) -> CharacterSnapshot {
+        CharacterSnapshot {
+            nft_id: format!("{player}-character"),
+            class: CharacterClass::Warrior,
+            level: 1,
+            hp_max: 100,
+            min_damage: 10,
+            max_damage: 20,
+            crit_chance: Bps(1000),
+            crit_multiplier: 15000,
+            dodge_chance: Bps(500),
+            defense: 5,
+            attack_bps: 0,
+            defense_bps: 0,
+            crit_bps: 0,
+        }
+    }
+
+    fn request_join_queue(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str) {
+        request_join_queue_with(state, runtime, player, snapshot(player));
+    }
+
+    fn request_join_queue_with(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        character_snapshot: CharacterSnapshot,
+    ) {
+        request_join_queue_in(state, runtime, player, character_snapshot, QueueMode::Casual);
+    }
+
+    fn request_join_queue_in(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        character_snapshot: CharacterSnapshot,
+        mode: QueueMode,
+    ) {
+        request_join_queue_in_party(state, runtime, player, character_snapshot, mode, None);
+    }
+
+    fn request_join_queue_in_party(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        character_snapshot: CharacterSnapshot,
+        mode: QueueMode,
+        party: Option<&str>,
+    ) {
+        runtime.set_message_origin_chain_id(chain(player));
+        LobbyContract::execute_message(state, runtime, Message::RequestJoinQueue {
+            player: owner(player),
+            player_chain: chain(player),
+            character_snapshot,
+            stake: Amount::from_tokens(1),
+            mode,
+            best_of: 1,
+            party: party.map(chain),
+        }).blocking_wait();
+    }
+
+    #[test]
+    fn player_chain_creation_is_capped_per_day() {
+        let (mut state, mut runtime) = setup();
+
+        for index in 0..2 {
+            create_player_chain(&mut state, &mut runtime, "alice", index);
+        }
+        let response = operate(&mut state, &mut runtime, "alice", Operation::ReplacePlayerChain);
+        assert_eq!(response, OperationResponse::rejected("player_chain_cap"));
+        assert_eq!(LobbyContract::get_player_chain(&owner("alice"), &state).blocking_wait(), Some(chain("alice-1")));
+    }
+
+    #[test]
+    fn owners_keep_their_player_chain_until_they_replace_it() {
+        let (mut state, mut runtime) = setup();
+        let response = operate(&mut state, &mut runtime, "alice", Operation::ReplacePlayerChain);
+        assert_eq!(response, OperationResponse::rejected("no_player_chain"));
+
+        // Asking again opens nothing and returns the registered chain
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        let response = operate(&mut state, &mut runtime, "alice", Operation::CreatePlayerChain);
+        assert_eq!(response, OperationResponse::PlayerChain { chain_id: chain("alice-0") });
+        assert_eq!(state.creation_counts.get(&(0, owner("alice"))).blocking_wait().unwrap().unwrap().player_chains, 1);
+
+        // A queued owner's chain holds their stake, so it cannot be replaced yet
+        request_join_queue(&mut state, &mut runtime, "alice");
+        let response = operate(&mut state, &mut runtime, "alice", Operation::ReplacePlayerChain);
+        assert_eq!(response, OperationResponse::rejected("player_chain_in_use"));
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+
+        let registered = |state: &LobbyState| state.character_registry.get(&owner("alice").to_string()).blocking_wait().unwrap().unwrap();
+        state.character_registry.insert(&owner("alice").to_string(), CharacterRegistryEntry { total_battles: 12, ..registered(&state) }).unwrap();
+        create_player_chain(&mut state, &mut runtime, "alice", 1);
+        let entry = registered(&state);
+        assert_eq!((entry.owner_chain, entry.total_battles), (chain("alice-1"), 12));
+    }
+
+    #[test]
+    fn unsigned_operations_are_refused() {
+        let (mut state, mut runtime) = setup();
+        runtime.set_authenticated_signer(None);
+
+        let response = LobbyContract::execute_operation(&mut state, &mut runtime, Operation::CreatePlayerChain).blocking_wait();
+
+        assert_eq!(response, OperationResponse::rejected("unauthenticated"));
+        assert!(state.creation_counts.get(&(0, owner("treasury"))).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn player_chain_cap_resets_next_day_and_spares_exempt_owners() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetCreationExemption {
+            owner: owner("bot"),
+            exempt: true,
+        });
+
+        for index in 0..5 {
+            create_player_chain(&mut state, &mut runtime, "bot", index);
+        }
+        for index in 0..2 {
+            create_player_chain(&mut state, &mut runtime, "alice", index);
+        }
+
+        runtime.set_system_time(Timestamp::from(MICROS_PER_DAY));
+        create_player_chain(&mut state, &mut runtime, "alice", 2);
+
+        assert_eq!(state.creation_counts.get(&(1, owner("alice"))).blocking_wait().unwrap().unwrap().player_chains, 1);
+        assert!(state.creation_counts.get(&(0, owner("bot"))).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn only_treasury_manages_exemptions() {
+        let (mut state, mut runtime) = setup();
+
+        let response = operate(&mut state, &mut runtime, "alice", Operation::SetCreationExemption {
+            owner: owner("alice"),
+            exempt: true,
+        });
+        assert_eq!(response, OperationResponse::rejected("unauthorized"));
+        assert!(!state.creation_exemptions.contains_key(&owner("alice")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn queue_joins_over_cap_are_rejected_and_released() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetCreationCaps {
+            player_chains: 2,
+            private_battles: 20,
+            queue_joins: 1,
+        });
+
+        request_join_queue(&mut state, &mut runtime, "alice");
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(30)));
+        request_join_queue(&mut state, &mut runtime, "alice");
+
+        assert!(!state.is_queued(owner("alice")).blocking_wait().unwrap());
+        let capped = RejectionKey::new("RequestJoinQueue", "queue_join_cap", owner("alice"));
+        assert!(state.rejections.contains_key(&capped).blocking_wait().unwrap());
+        let released = runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain("alice"))
+            .filter(|request| matches!(request.message, Message::QueueLeft { .. }))
+            .count();
+        assert_eq!(released, 2);
+    }
+
+    #[test]
+    fn queue_entry_and_exit_are_confirmed_to_the_player_chain_once() {
+        let (mut state, mut runtime) = setup();
+        let sent_to_alice = |runtime: &mut ContractRuntime<crate::MajorulesContract>, left: bool| runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain("alice"))
+            .filter(|request| match request.message {
+                Message::QueueJoined { .. } => !left,
+                Message::QueueLeft { .. } => left,
+                _ => false,
+            })
+            .count();
+
+        request_join_queue(&mut state, &mut runtime, "alice");
+        assert_eq!(sent_to_alice(&mut runtime, false), 1);
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        assert_eq!(sent_to_alice(&mut runtime, true), 1);
+        assert!(state.rejections.contains_key(&RejectionKey::new("LeaveQueue", "not_queued", owner("alice"))).blocking_wait().unwrap());
+
+        // A refused entry is released too, so its stake unlocks
+        runtime.set_message_origin_chain_id(chain("alice"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestJoinQueue {
+            player: owner("alice"),
+            player_chain: chain("alice"),
+            character_snapshot: snapshot("alice"),
+            stake: Amount::ZERO,
+            mode: QueueMode::Casual,
+            best_of: 1,
+            party: None,
+        }).blocking_wait();
+        assert_eq!((sent_to_alice(&mut runtime, false), sent_to_alice(&mut runtime, true)), (1, 2));
+    }
+
+    #[test]
+    fn repeated_rejections_are_coalesced_and_feedback_throttled() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetCreationCaps {
+            player_chains: 2,
+            private_battles: 20,
+            queue_joins: 0,
+        });
+        let releases = |runtime: &mut ContractRuntime<crate::MajorulesContract>| runtime.created_send_message_requests().iter()
+            .filter(|request| matches!(request.message, Message::QueueLeft { .. }))
+            .count();
+
+        for second in 0..50 {
+            runtime.set_system_time(Timestamp::from(second * 1_000_000));
+            request_join_queue(&mut state, &mut runtime, "alice");
+        }
+
+        let capped = RejectionKey::new("RequestJoinQueue", "queue_join_cap", owner("alice"));
+        assert_eq!(state.rejections.get(&capped).blocking_wait().unwrap().unwrap().count, 50);
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 1);
+        assert_eq!(releases(&mut runtime), 1);
+
+        // The withheld release goes out once the player leaves the queue
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        assert_eq!(releases(&mut runtime), 2);
+        assert_eq!(state.held_releases.count().blocking_wait().unwrap(), 0);
+
+        state.creation_caps.set(CreationCaps { queue_joins: 1, ..CreationCaps::default() });
+        request_join_queue(&mut state, &mut runtime, "alice");
+        request_join_queue(&mut state, &mut runtime, "alice");
+        let queued = RejectionKey::new("RequestJoinQueue", "already_queued", owner("alice"));
+        assert_eq!(state.rejections.get(&queued).blocking_wait().unwrap().unwrap().count, 1);
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn snapshots_beyond_equipment_bounds_are_rejected() {
+        let (mut state, mut runtime) = setup();
+        let max = ItemRarity::Epic.max_bonus_bps() + max_trait_bps(ItemRarity::Epic);
+
+        request_join_queue_with(&mut state, &mut runtime, "alice", CharacterSnapshot {
+            attack_bps: max,
+            defense_bps: max,
+            crit_bps: max,
+            ..snapshot("alice")
+        });
+        request_join_queue_with(&mut state, &mut runtime, "bob", CharacterSnapshot {
+            attack_bps: max + 1,
+            ..snapshot("bob")
+        });
+
+        assert!(state.is_queued(owner("alice")).blocking_wait().unwrap());
+        assert!(!state.is_queued(owner("bob")).blocking_wait().unwrap());
+        let rejected = RejectionKey::new("RequestJoinQueue", "snapshot_out_of_bounds", owner("bob"));
+        assert!(state.rejections.contains_key(&rejected).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn queued_players_pair_by_rating_as_their_windows_widen() {
+        let (mut state, mut runtime) = setup();
+        for (player, rating) in [("alice", 1200), ("bob", 1500), ("carol", 1900)] {
+            state.ratings.insert(&owner(player), rating).unwrap();
+        }
+        let battles = |state: &LobbyState| state.active_battles.indices().blocking_wait().unwrap();
+
+        request_join_queue(&mut state, &mut runtime, "alice");
+        request_join_queue(&mut state, &mut runtime, "bob");
+        assert!(battles(&state).is_empty());
+
+        // A minute on, bob accepts a 400-point gap; alice is the closer of the two candidates
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(60)));
+        expect_match_chain(&mut runtime, "alice", "bob", "first");
+        request_join_queue(&mut state, &mut runtime, "carol");
+        assert_eq!(battles(&state), [chain("first")]);
+        assert!(state.is_queued(owner("carol")).blocking_wait().unwrap());
+
+        // Unrated dave queues at the initial rating until their chain reports one
+        create_player_chain(&mut state, &mut runtime, "dave", 0);
+        runtime.set_message_origin_chain_id(chain("dave-0"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestJoinQueue {
+            player: owner("dave"),
+            player_chain: chain("dave-0"),
+            character_snapshot: snapshot("dave"),
+            stake: Amount::from_tokens(1),
+            mode: QueueMode::Casual,
+            best_of: 1,
+            party: None,
+        }).blocking_wait();
+        assert!(runtime.created_send_message_requests().iter().any(|request| {
+            request.destination == chain("dave-0") && matches!(request.message, Message::RequestPlayerStats { .. })
+        }));
+        assert_eq!(battles(&state).len(), 1);
+
+        expect_match_chain(&mut runtime, "carol", "dave", "second");
+        let stats = majorules::PlayerGlobalStats { elo_rating: 1850, ..Default::default() };
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::PlayerStatsResponse { player: owner("dave"), stats })
+            .blocking_wait();
+        assert_eq!(battles(&state), [chain("first"), chain("second")]);
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 0);
+    }
+
+    #[test]
+    fn maintenance_matches_pairs_that_waited_into_range() {
+        let (mut state, mut runtime) = setup();
+        for (player, rating) in [("alice", 1200), ("bob", 1500)] {
+            state.ratings.insert(&owner(player), rating).unwrap();
+        }
+        request_join_queue(&mut state, &mut runtime, "alice");
+        request_join_queue(&mut state, &mut runtime, "bob");
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+
+        // The sweep that found nothing waits out a widening step before looking again
+        assert_eq!(*state.matchmaking_sweep_at.get(), Timestamp::from(0).saturating_add(TimeDelta::from_secs(10)));
+
+        // A minute on, both accept the 300-point gap without anyone else joining
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(60)));
+        expect_match_chain(&mut runtime, "alice", "bob", "swept");
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 1);
+        assert!(state.active_battles.contains_key(&chain("swept")).blocking_wait().unwrap());
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 0);
+    }
+
+    #[test]
+    fn ranked_queue_is_gated_on_level_and_account_battles() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        let ranked = |level| CharacterSnapshot { level, ..snapshot("alice") };
+        let rejected = |state: &LobbyState, reason: &str| {
+            let key = RejectionKey::new("RequestJoinQueue", reason, owner("alice"));
+            state.rejections.contains_key(&key).blocking_wait().unwrap()
+        };
+
+        request_join_queue_in(&mut state, &mut runtime, "alice", ranked(1), QueueMode::Ranked);
+        assert!(rejected(&state, "ranked_level_below_5"));
+        request_join_queue_in(&mut state, &mut runtime, "alice", ranked(5), QueueMode::Ranked);
+        assert!(rejected(&state, "ranked_battles_below_10"));
+        assert!(!state.is_queued(owner("alice")).blocking_wait().unwrap());
+
+        let mut entry = state.character_registry.get(&owner("alice").to_string()).blocking_wait().unwrap().unwrap();
+        entry.total_battles = 10;
+        state.character_registry.insert(&owner("alice").to_string(), entry).unwrap();
+        request_join_queue_in(&mut state, &mut runtime, "alice", ranked(5), QueueMode::Ranked);
+
+        // A fresh casual player is never gated, and never paired with a ranked one
+        request_join_queue(&mut state, &mut runtime, "bob");
+        for player in ["alice", "bob"] {
+            assert!(state.is_queued(owner(player)).blocking_wait().unwrap());
+        }
+
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        operate(&mut state, &mut runtime, "treasury", Operation::SetRankedGates {
+            gates: RankedGates { min_ranked_level: 1, min_account_battles: 0 },
+        });
+        request_join_queue_in(&mut state, &mut runtime, "carol", snapshot("carol"), QueueMode::Ranked);
+        assert!(state.is_queued(owner("carol")).blocking_wait().unwrap());
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn staked_queues_wait_for_practice_wins_or_character_level() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::PauseMatchmaking { paused: true });
+        operate(&mut state, &mut runtime, "treasury", Operation::SetProgressionGates {
+            gates: ProgressionGates { min_wins: 1, min_level: 3 },
+        });
+
+        // A fresh account with a level 1 character is refused a staked entry
+        request_join_queue(&mut state, &mut runtime, "alice");
+        let gated = RejectionKey::new("RequestJoinQueue", "progression_wins_below_1_or_level_below_3", owner("alice"));
+        assert!(state.rejections.contains_key(&gated).blocking_wait().unwrap());
+        assert!(!state.is_queued(owner("alice")).blocking_wait().unwrap());
+
+        // Either a leveled character or a practice win clears the gates
+        request_join_queue_with(&mut state, &mut runtime, "bob", CharacterSnapshot { level: 3, ..snapshot("bob") });
+        assert!(state.is_queued(owner("bob")).blocking_wait().unwrap());
+        state.practice_battles.insert(&chain("practice"), (owner("alice"), chain("alice"))).unwrap();
+        runtime.set_message_origin_chain_id(chain("practice"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::PracticeBattleCompleted {
+            result: FighterResult {
+                player: owner("alice"),
+                character_id: "alice-character".to_string(),
+                won: true,
+                payout: Amount::ZERO,
+                xp_gained: 5,
+                elo_change: 0,
+                item_drop: None,
+                combat_stats: CombatStats::default(),
+                win_streak: 0,
+            },
+        }).blocking_wait();
+        request_join_queue(&mut state, &mut runtime, "alice");
+        assert!(state.is_queued(owner("alice")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn full_queues_refuse_and_release_new_joins() {
+        let (mut state, mut runtime) = setup();
+        let config = MatchmakingConfig { max_queue_size: 2, ..MatchmakingConfig::default() };
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateMatchmakingConfig { config });
+        operate(&mut state, &mut runtime, "treasury", Operation::PauseMatchmaking { paused: true });
+
+        for player in ["alice", "bob", "carol"] {
+            request_join_queue(&mut state, &mut runtime, player);
+        }
+
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 2);
+        let full = RejectionKey::new("RequestJoinQueue", "queue_full", owner("carol"));
+        assert!(state.rejections.contains_key(&full).blocking_wait().unwrap());
+        assert!(runtime.created_send_message_requests().iter().any(|request| request.destination == chain("carol")
+            && matches!(&request.message, Message::QueueLeft { character_id } if character_id == "carol-character")));
+    }
+
+    #[test]
+    fn the_queue_reads_back_in_join_order_through_leaves_and_rating_updates() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::PauseMatchmaking { paused: true });
+        create_player_chain(&mut state, &mut runtime, "carol", 0);
+        for (secs, player) in [(30, "carol"), (10, "alice"), (20, "bob"), (40, "dave")] {
+            runtime.set_system_time(Timestamp::from(secs * 1_000_000));
+            let player_chain = if player == "carol" { chain("carol-0") } else { chain(player) };
+            runtime.set_message_origin_chain_id(player_chain);
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestJoinQueue {
+                player: owner(player),
+                player_chain,
+                character_snapshot: snapshot(player),
+                stake: Amount::from_tokens(1),
+                mode: QueueMode::Casual,
+                best_of: 1,
+                party: None,
+            }).blocking_wait();
+        }
+        let order = |state: &LobbyState| {
+            let by_index: Vec<_> = state.queue_order.indices().blocking_wait().unwrap().into_iter().map(|key| key.player).collect();
+            let entries: Vec<_> = state.queue_in_order().blocking_wait().unwrap().into_iter().map(|entry| entry.player).collect();
+            assert_eq!(by_index, entries);
+            entries
+        };
+        assert_eq!(order(&state), ["alice", "bob", "carol", "dave"].map(owner));
+
+        operate(&mut state, &mut runtime, "bob", Operation::LeaveQueue);
+        runtime.set_message_origin_chain_id(chain("carol-0"));
+        let stats = majorules::PlayerGlobalStats { elo_rating: 1500, ..Default::default() };
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::PlayerStatsResponse { player: owner("carol"), stats })
+            .blocking_wait();
+        assert_eq!(order(&state), ["alice", "carol", "dave"].map(owner));
+        let carol = state.waiting_players.get(&(owner("carol"), "carol-character".to_string())).blocking_wait().unwrap().unwrap();
+        assert_eq!(carol.elo_rating, 1500);
+    }
+
+    #[test]
+    fn queues_pair_only_their_own_entries_by_their_own_terms() {
+        let (mut state, mut runtime) = setup();
+        request_join_queue_in(&mut state, &mut runtime, "alice", snapshot("alice"), QueueMode::HighStakes);
+        let below = RejectionKey::new("RequestJoinQueue", "stake_below_queue_minimum", owner("alice"));
+        assert!(state.rejections.contains_key(&below).blocking_wait().unwrap());
+        assert_eq!(released(&mut runtime, "alice"), 1);
+
+        let invalid = MatchmakingConfig { max_queue_size: 1, ..MatchmakingConfig::default() };
+        let terms = QueueTerms { min_stake: Amount::from_tokens(1), matchmaking: Some(invalid) };
+        let response = operate(&mut state, &mut runtime, "treasury", Operation::SetQueueTerms { mode: QueueMode::HighStakes, terms });
+        assert_eq!(response, OperationResponse::rejected("invalid_queue_size"));
+        let terms = QueueTerms { min_stake: Amount::from_tokens(1), matchmaking: None };
+        operate(&mut state, &mut runtime, "treasury", Operation::SetQueueTerms { mode: QueueMode::HighStakes, terms });
+
+        // Casual bob and high-stakes carol never meet; dave joining casual pairs with bob
+        request_join_queue(&mut state, &mut runtime, "bob");
+        request_join_queue_in(&mut state, &mut runtime, "carol", snapshot("carol"), QueueMode::HighStakes);
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 2);
+        expect_match_chain(&mut runtime, "bob", "dave", "casual");
+        request_join_queue(&mut state, &mut runtime, "dave");
+        assert_eq!(state.waiting_players.indices().blocking_wait().unwrap(), [(owner("carol"), "carol-character".to_string())]);
+
+        let stats = |state: &LobbyState, mode| state.queue_stats.get(&mode).blocking_wait().unwrap().unwrap_or_default();
+        assert_eq!(stats(&state, QueueMode::Casual), QueueStats {
+            joins: 2,
+            matches: 1,
+            matched_stake: Amount::from_tokens(2),
+            total_wait_secs: 0,
+        });
+        assert_eq!(stats(&state, QueueMode::HighStakes), QueueStats { joins: 1, ..QueueStats::default() });
+
+        // Casual results count toward streaks but leave ratings alone
+        finish_match(&mut state, &mut runtime, "casual", "bob", "dave");
+        assert_eq!(state.rated_battles.get(&chain("casual")).blocking_wait().unwrap(), Some((0, 0)));
+        assert_eq!(state.ratings.get(&owner("bob")).blocking_wait().unwrap(), None);
+        assert_eq!(state.win_streaks.get(&owner("bob")).blocking_wait().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn team_queue_drafts_sides_by_rating_and_relays_every_fighters_result() {
+        let (mut state, mut runtime) = setup();
+        for (player, rating) in [("alice", 1_500), ("bob", 1_400), ("carol", 1_300), ("dave", 1_200)] {
+            create_player_chain(&mut state, &mut runtime, player, 0);
+            state.ratings.insert(&owner(player), rating).unwrap();
+        }
+
+        // Team battles are single games
+        runtime.set_message_origin_chain_id(chain("bob"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestJoinQueue {
+            player: owner("bob"),
+            player_chain: chain("bob"),
+            character_snapshot: snapshot("bob"),
+            stake: Amount::from_tokens(1),
+            mode: QueueMode::Teams,
+            best_of: 3,
+            party: None,
+        }).blocking_wait();
+        let series = RejectionKey::new("RequestJoinQueue", "series_not_in_team_queue", owner("bob"));
+        assert!(state.rejections.contains_key(&series).blocking_wait().unwrap());
+
+        // Nobody pairs off in the team queue; the fourth fighter completes the draft, strongest
+        // first and snaking: alice and dave against bob and carol
+        for player in ["alice", "bob", "carol"] {
+            request_join_queue_in(&mut state, &mut runtime, player, snapshot(player), QueueMode::Teams);
+        }
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 3);
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::multiple(["alice", "dave", "bob", "carol"].map(|player| (owner(player), 1)), 10, Default::default()),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain("team"),
+        );
+        request_join_queue_in(&mut state, &mut runtime, "dave", snapshot("dave"), QueueMode::Teams);
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 0);
+        let drafted = [["alice", "dave"].map(owner).to_vec(), ["bob", "carol"].map(owner).to_vec()];
+        assert_eq!(state.team_battles.get(&chain("team")).blocking_wait().unwrap(), Some(drafted.clone()));
+        let initialized = runtime.created_send_message_requests().iter().any(|request| {
+            request.destination == chain("team") && matches!(&request.message, Message::InitializeTeamBattle { teams, .. }
+                if teams.each_ref().map(|side| side.iter().map(|fighter| fighter.owner).collect::<Vec<_>>()) == drafted)
+        });
+        assert!(initialized);
+        let battle = state.active_battles.get(&chain("team")).blocking_wait().unwrap().unwrap();
+        assert_eq!((battle.player1, battle.player2, battle.mode), (owner("alice"), owner("bob"), QueueMode::Teams));
+        assert!(!battle.has_prediction_market);
+        let stats = state.queue_stats.get(&QueueMode::Teams).blocking_wait().unwrap().unwrap();
+        assert_eq!((stats.matches, stats.matched_stake), (1, Amount::from_tokens(4)));
+        let in_flight = |state: &LobbyState| ["alice", "bob", "carol", "dave"]
+            .map(|player| state.battles_in_flight.contains_key(&owner(player)).blocking_wait().unwrap());
+        assert_eq!(in_flight(&state), [true; 4]);
+
+        // Results go to the drafted fighters only, and the winning side's first seat wins the record
+        let result = |player: &str, won: bool| FighterResult {
+            player: owner(player),
+            character_id: format!("{player}-character"),
+            won,
+            payout: if won { Amount::from_tokens(2) } else { Amount::ZERO },
+            xp_gained: 10,
+            elo_change: 0,
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        };
+        runtime.set_message_origin_chain_id(chain("team"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::TeamBattleCompleted {
+            rounds_played: 2,
+            total_stake: Amount::from_tokens(4),
+            rules_digest: 7,
+            end_reason: BattleEndReason::Knockout,
+            results: vec![result("alice", false), result("dave", false), result("bob", true), result("carol", true), result("erin", true)],
+        }).blocking_wait();
+        assert_eq!(in_flight(&state), [false; 4]);
+        let requests = runtime.created_send_message_requests();
+        for player in ["alice", "bob", "carol", "dave", "erin"] {
+            let relayed = requests.iter()
+                .filter(|request| request.destination == chain(&format!("{player}-0")))
+                .filter(|request| matches!(request.message, Message::UpdatePlayerStats { .. }))
+                .count();
+            assert_eq!(relayed, usize::from(player != "erin"), "{player}");
+        }
+        let record = state.completed_battles.get(&chain("team")).blocking_wait().unwrap().unwrap();
+        assert_eq!(record.winner, owner("bob"));
+        let archived = state.completed_by_owner.get(&owner("carol")).blocking_wait().unwrap().unwrap_or_default();
+        assert_eq!(archived.iter().map(|(_, battle)| *battle).collect::<Vec<_>>(), [chain("team")]);
+        assert!(!state.team_battles.contains_key(&chain("team")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn practice_battles_pit_a_player_against_the_bot_and_relay_only_xp() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+
+        // The practice chain is the player's alone, and opens no market
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::multiple([(owner("alice"), 1)], 10, Default::default()),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain("practice"),
+        );
+        runtime.set_message_origin_chain_id(chain("alice-0"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestPracticeBattle {
+            player: owner("alice"),
+            player_chain: chain("alice-0"),
+            character_snapshot: snapshot("alice"),
+            difficulty: PracticeDifficulty::Normal,
+        }).blocking_wait();
+        let requests = runtime.created_send_message_requests();
+        assert!(requests.iter().any(|request| request.destination == chain("alice-0")
+            && matches!(&request.message, Message::BattleMatched { battle_chain, stake, .. } if *battle_chain == chain("practice") && *stake == Amount::ZERO)));
+        assert!(requests.iter().any(|request| request.destination == chain("practice")
+            && matches!(&request.message, Message::InitializePracticeBattle { player, difficulty: PracticeDifficulty::Normal, .. } if player.owner == owner("alice"))));
+        assert!(!state.active_battles.contains_key(&chain("practice")).blocking_wait().unwrap());
+        assert!(state.battle_to_market.get(&chain("practice")).blocking_wait().unwrap().is_none());
+        drop(requests);
+        // The practice result goes to the player chain, so it stays in place until then
+        let response = operate(&mut state, &mut runtime, "alice", Operation::ReplacePlayerChain);
+        assert_eq!(response, OperationResponse::rejected("player_chain_in_use"));
+
+        // Only the practice chain reports, on its own player, once; the player hears of XP alone
+        let result = |player: &str| FighterResult {
+            player: owner(player),
+            character_id: format!("{player}-character"),
+            won: true,
+            payout: Amount::ZERO,
+            xp_gained: 12,
+            elo_change: 0,
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        };
+        for (origin, player) in [("elsewhere", "alice"), ("practice", "bob"), ("practice", "alice"), ("practice", "alice")] {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::PracticeBattleCompleted { result: result(player) }).blocking_wait();
+        }
+        let ended: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain("alice-0"))
+            .filter_map(|request| match &request.message {
+                Message::PracticeBattleEnded { battle_chain, won, xp_gained, .. } => Some((*battle_chain, *won, *xp_gained)),
+                Message::UpdatePlayerStats { .. } => panic!("Practice counts toward no stats"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ended, [(chain("practice"), true, 12)]);
+        assert!(!state.practice_battles.contains_key(&chain("practice")).blocking_wait().unwrap());
+        assert!(!state.battles_in_flight.contains_key(&owner("alice")).blocking_wait().unwrap());
+        assert!(state.ratings.get(&owner("alice")).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn parties_draft_onto_one_side_and_enter_only_their_own_tournaments() {
+        let (mut state, mut runtime) = setup();
+        for (player, rating) in [("alice", 1_500), ("bob", 1_400), ("carol", 1_300), ("dave", 1_200)] {
+            create_player_chain(&mut state, &mut runtime, player, 0);
+            state.ratings.insert(&owner(player), rating).unwrap();
+        }
+
+        // Only the party a tournament is for may enter it; everyone else is refunded
+        operate(&mut state, &mut runtime, "treasury", Operation::CreateTournament {
+            name: "Alice's party".to_string(),
+            terms: tournament_terms(8),
+            start_time: Timestamp::from(0).saturating_add(TimeDelta::from_secs(600)),
+            registration_closes_at: None,
+            party: Some(chain("alice")),
+        });
+        for (player, party) in [("bob", Some("alice")), ("carol", None), ("dave", Some("dave"))] {
+            join_tournament_in(&mut state, &mut runtime, player, 1, 2, party);
+        }
+        assert_eq!(state.tournaments.get(&1).blocking_wait().unwrap().unwrap().entrants, 1);
+        assert_eq!(tournament_exits(&mut runtime), [(chain("carol"), Amount::from_tokens(2)), (chain("dave"), Amount::from_tokens(2))]);
+        let refused = RejectionKey::new("RequestJoinTournament", "not_in_party", owner("carol"));
+        assert!(state.rejections.contains_key(&refused).blocking_wait().unwrap());
+
+        // Alice and bob queued as a party, so the two strongest face the solos together
+        // instead of being split by the draft
+        for (player, party) in [("alice", Some("alice")), ("carol", None), ("bob", Some("alice"))] {
+            request_join_queue_in_party(&mut state, &mut runtime, player, snapshot(player), QueueMode::Teams, party);
+        }
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::multiple(["alice", "bob", "carol", "dave"].map(|player| (owner(player), 1)), 10, Default::default()),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain("team"),
+        );
+        request_join_queue_in(&mut state, &mut runtime, "dave", snapshot("dave"), QueueMode::Teams);
+        let drafted = [["alice", "bob"].map(owner).to_vec(), ["carol", "dave"].map(owner).to_vec()];
+        assert_eq!(state.team_battles.get(&chain("team")).blocking_wait().unwrap(), Some(drafted));
+    }
+
+    #[test]
+    fn ranked_rematches_queue_churn_and_shared_chains_are_held_off() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetRankedGates {
+            gates: RankedGates { min_ranked_level: 1, min_account_battles: 0 },
+        });
+        let config = MatchmakingConfig { rematch_window_secs: 600, ..MatchmakingConfig::default() };
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateMatchmakingConfig { config });
+        let at = |secs| Timestamp::from(0).saturating_add(TimeDelta::from_secs(secs));
+        let queued = |state: &LobbyState, player: &str| state.is_queued(owner(player)).blocking_wait().unwrap();
+        let rejected = |state: &LobbyState, player: &str, reason: &str| {
+            let key = RejectionKey::new("RequestJoinQueue", reason, owner(player));
+            state.rejections.contains_key(&key).blocking_wait().unwrap()
+        };
+
+        // Two ranked meetings within the window are allowed; the third waits for the first to age out
+        for (secs, battle) in [(0, "first"), (60, "second")] {
+            runtime.set_system_time(at(secs));
+            expect_match_chain(&mut runtime, "alice", "bob", battle);
+            for player in ["alice", "bob"] {
+                request_join_queue_in(&mut state, &mut runtime, player, snapshot(player), QueueMode::Ranked);
+            }
+        }
+        runtime.set_system_time(at(120));
+        for player in ["alice", "bob"] {
+            request_join_queue_in(&mut state, &mut runtime, player, snapshot(player), QueueMode::Ranked);
+        }
+        assert!(queued(&state, "alice") && queued(&state, "bob"));
+        let pairings = state.recent_pairings.get(&owner("alice")).blocking_wait().unwrap().unwrap();
+        assert_eq!(pairings.iter().map(|pairing| pairing.opponent).collect::<Vec<_>>(), [owner("bob"); 2]);
+
+        runtime.set_system_time(at(600));
+        expect_match_chain(&mut runtime, "alice", "bob", "third");
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateMatchmakingConfig { config });
+        assert_eq!(state.active_battles.count().blocking_wait().unwrap(), 3);
+
+        // A second owner cannot queue from a player chain already in the queue
+        request_join_queue(&mut state, &mut runtime, "carol");
+        runtime.set_message_origin_chain_id(chain("carol"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestJoinQueue {
+            player: owner("mallory"),
+            player_chain: chain("carol"),
+            character_snapshot: snapshot("mallory"),
+            stake: Amount::from_tokens(1),
+            mode: QueueMode::Casual,
+            best_of: 1,
+            party: None,
+        }).blocking_wait();
+        assert!(rejected(&state, "mallory", "player_chain_in_queue"));
+
+        // Leaving holds off rejoining for the cooldown
+        operate(&mut state, &mut runtime, "carol", Operation::LeaveQueue);
+        request_join_queue(&mut state, &mut runtime, "carol");
+        assert!(rejected(&state, "carol", "rejoin_cooldown") && !queued(&state, "carol"));
+        runtime.set_system_time(at(630));
+        request_join_queue(&mut state, &mut runtime, "carol");
+        assert!(queued(&state, "carol"));
+        assert!(state.queue_exits.get(&owner("carol")).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn owners_queue_several_characters_that_never_meet_each_other() {
+        let (mut state, mut runtime) = setup();
+        let second = CharacterSnapshot { nft_id: "alice-second".to_string(), ..snapshot("alice") };
+        request_join_queue(&mut state, &mut runtime, "alice");
+        runtime.set_system_time(Timestamp::from(1_000_000));
+        request_join_queue_with(&mut state, &mut runtime, "alice", second.clone());
+        request_join_queue_with(&mut state, &mut runtime, "alice", second);
+        let queued = |state: &LobbyState| {
+            let mut queued: Vec<_> = state.queued_entries(owner("alice")).blocking_wait().unwrap()
+                .into_iter()
+                .map(|entry| entry.character_id)
+                .collect();
+            queued.sort();
+            queued
+        };
+        assert_eq!(queued(&state), ["alice-character", "alice-second"]);
+        assert_eq!(state.active_battles.count().blocking_wait().unwrap(), 0);
+        let repeat = RejectionKey::new("RequestJoinQueue", "already_queued", owner("alice"));
+        assert!(state.rejections.contains_key(&repeat).blocking_wait().unwrap());
+
+        // Each character meets another owner, the first queued first
+        expect_match_chain(&mut runtime, "alice", "bob", "first");
+        request_join_queue(&mut state, &mut runtime, "bob");
+        assert_eq!(queued(&state), ["alice-second"]);
+        expect_match_chain(&mut runtime, "alice", "carol", "second");
+        request_join_queue(&mut state, &mut runtime, "carol");
+        assert!(queued(&state).is_empty());
+        assert_eq!(state.active_battles.count().blocking_wait().unwrap(), 2);
+
+        // Leaving releases every character still queued
+        request_join_queue(&mut state, &mut runtime, "dave");
+        request_join_queue_with(&mut state, &mut runtime, "dave", CharacterSnapshot { nft_id: "dave-second".to_string(), ..snapshot("dave") });
+        assert_eq!(operate(&mut state, &mut runtime, "dave", Operation::LeaveQueue), OperationResponse::Done);
+        let mut released: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain("dave"))
+            .filter_map(|request| match &request.message {
+                Message::QueueLeft { character_id } => Some(character_id.clone()),
+                _ => None,
+            })
+            .collect();
+        released.sort();
+        assert_eq!(released, ["dave-character", "dave-second"]);
+        assert!(!state.is_queued(owner("dave")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn leveled_characters_update_the_registry_from_their_own_chain() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetLevelingConfig { config: LevelingConfig { level_cap: 20 } });
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        assert!(runtime.created_send_message_requests().iter().any(|request| matches!(
+            request.message,
+            Message::InitializePlayerChain { leveling: LevelingConfig { level_cap: 20 }, .. }
+        )));
+        let leveled = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, from: &str, level| {
+            runtime.set_message_origin_chain_id(chain(from));
+            LobbyContract::execute_message(state, runtime, Message::CharacterUpdated {
+                player: owner("alice"),
+                snapshot: CharacterSnapshot { level, ..snapshot("alice") },
+            }).blocking_wait();
+            state.character_registry.get(&owner("alice").to_string()).blocking_wait().unwrap().unwrap().level
+        };
+
+        assert_eq!(leveled(&mut state, &mut runtime, "mallory", 9), 1);
+        assert_eq!(leveled(&mut state, &mut runtime, "alice-0", 4), 4);
+        assert_eq!(leveled(&mut state, &mut runtime, "alice-0", 3), 4);
+    }
+
+    #[test]
+    fn the_roster_keeps_character_ids_unique_and_follows_transfers() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        let deliver = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, from: &str, message| {
+            runtime.set_message_origin_chain_id(chain(from));
+            LobbyContract::execute_message(state, runtime, message).blocking_wait();
+            state.roster.get("alice-character").blocking_wait().unwrap()
+                .map(|entry| (entry.owner, entry.owner_chain, entry.level, entry.moving_to))
+        };
+        let minted = |player: &str| Message::CharacterMinted { player: owner(player), snapshot: snapshot("alice") };
+        let updated = |player: &str, level| Message::CharacterUpdated {
+            player: owner(player),
+            snapshot: CharacterSnapshot { level, ..snapshot("alice") },
+        };
+        let moved = || Message::CharacterMoved { character_id: "alice-character".to_string(), to_chain: chain("bob-0") };
+
+        // Only a player's own chain registers their characters, and an id only once
+        assert_eq!(deliver(&mut state, &mut runtime, "mallory", minted("alice")), None);
+        let alice_holds = Some((owner("alice"), chain("alice-0"), 1, None));
+        assert_eq!(deliver(&mut state, &mut runtime, "alice-0", minted("alice")), alice_holds);
+        assert_eq!(deliver(&mut state, &mut runtime, "bob-0", minted("bob")), alice_holds);
+        let duplicate = RejectionKey::new("CharacterMinted", "duplicate_character_id", owner("bob"));
+        assert!(state.rejections.contains_key(&duplicate).blocking_wait().unwrap());
+
+        // Bob's chain takes the character over only once alice's says it is on its way
+        assert_eq!(deliver(&mut state, &mut runtime, "bob-0", updated("bob", 2)), alice_holds);
+        assert_eq!(deliver(&mut state, &mut runtime, "bob-0", moved()), alice_holds);
+        let moving = Some((owner("alice"), chain("alice-0"), 1, Some(chain("bob-0"))));
+        assert_eq!(deliver(&mut state, &mut runtime, "alice-0", moved()), moving);
+        let bob_holds = Some((owner("bob"), chain("bob-0"), 2, None));
+        assert_eq!(deliver(&mut state, &mut runtime, "bob-0", updated("bob", 2)), bob_holds);
+        assert_eq!(deliver(&mut state, &mut runtime, "alice-0", updated("alice", 5)), bob_holds);
+    }
+
+    #[test]
+    fn characters_the_roster_gives_to_another_chain_cannot_fight() {
+        let (mut state, mut runtime) = setup();
+        state.roster.insert("alice-character", RosterEntry {
+            character_id: "alice-character".to_string(),
+            owner: owner("alice"),
+            owner_chain: chain("alice"),
+            class: CharacterClass::Warrior.into(),
+            level: 1,
+            lives_remaining: STARTING_LIVES,
+            minted_at: runtime.system_time(),
+            moving_to: None,
+        }).unwrap();
+
+        // Bob's chain minted the same id later
+        request_join_queue_with(&mut state, &mut runtime, "bob", snapshot("alice"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::RequestCreatePrivateBattle {
+            player: owner("bob"),
+            player_chain: chain("bob"),
+            character_snapshot: snapshot("alice"),
+            stake: Amount::from_tokens(1),
+            invite: invite_digest(INVITE),
+        }).blocking_wait();
+        for operation in ["RequestJoinQueue", "RequestCreatePrivateBattle"] {
+            assert!(state.rejections.contains_key(&RejectionKey::new(operation, "character_id_taken", owner("bob"))).blocking_wait().unwrap());
+        }
+        assert_eq!(released(&mut runtime, "bob"), 2);
+
+        request_join_queue(&mut state, &mut runtime, "alice");
+        assert!(state.is_queued(owner("alice")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn bets_resolve_obfuscated_market_ids() {
+        let (mut state, mut runtime) = setup();
+        let codec = IdCodec::obfuscated(0xDEC0DE);
+        state.id_codec.set(codec);
+        let market_id = LobbyContract::create_prediction_market_in_lobby(
+            &mut state, chain("battle"), chain("alice"), chain("bob"), false, runtime.system_time(),
+        ).blocking_wait();
+        assert_eq!(market_id, 1);
+
+        operate(&mut state, &mut runtime, "carol", Operation::PlaceBet {
+            market_id: codec.encode(market_id),
+            predicted_winner: chain("alice"),
+            amount: Amount::from_tokens(3),
+        });
+
+        let bet = state.bets.get(&(market_id, owner("carol"))).blocking_wait().unwrap().unwrap();
+        assert_eq!(bet.amount, Amount::from_tokens(3));
+        let market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+        assert_eq!(market.player1_pool, Amount::from_tokens(3));
+    }
+
+    #[test]
+    fn player_chains_bet_by_message_and_hear_back_either_way() {
+        let (mut state, mut runtime) = setup();
+        let codec = IdCodec::obfuscated(0xDEC0DE);
+        state.id_codec.set(codec);
+        create_player_chain(&mut state, &mut runtime, "carol", 0);
+        let market_id = LobbyContract::create_prediction_market_in_lobby(
+            &mut state, chain("battle"), chain("alice"), chain("bob"), false, runtime.system_time(),
+        ).blocking_wait();
+        let bet = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, origin: &str, winner: &str| {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(state, runtime, Message::RequestPlaceBet {
+                bettor: owner("carol"),
+                market_id: codec.encode(market_id),
+                predicted_winner: chain(winner),
+                amount: Amount::from_tokens(3),
+            }).blocking_wait();
+        };
+
+        bet(&mut state, &mut runtime, "carol-0", "alice");
+        bet(&mut state, &mut runtime, "carol-0", "bob");
+        bet(&mut state, &mut runtime, "mallory", "alice");
+        let answers: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::BetPlaced { market_id } => Some((request.destination, true, market_id)),
+                Message::BetRefused { market_id } => Some((request.destination, false, market_id)),
+                _ => None,
+            })
+            .collect();
+        let encoded = codec.encode(market_id);
+        assert_eq!(answers, [(chain("carol-0"), true, encoded), (chain("carol-0"), false, encoded), (chain("mallory"), false, encoded)]);
+        for reason in ["opposite_side", "not_player_chain"] {
+            assert!(state.rejections.contains_key(&RejectionKey::new("PlaceBetOnMarket", reason, owner("carol"))).blocking_wait().unwrap());
+        }
+        let placed = state.bets.get(&(market_id, owner("carol"))).blocking_wait().unwrap().unwrap();
+        assert_eq!((placed.predicted_winner, placed.amount), (chain("alice"), Amount::from_tokens(3)));
+    }
+
+    /// Track a battle the way `create_battle_chain` does
+    fn track_battle(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str) {
+        state.active_battles.insert(&chain(battle), BattleMetadata {
+            battle_chain: chain(battle),
+            player1: owner("alice"),
+            player2: owner("bob"),
+            total_stake: Amount::from_tokens(2),
+            created_at: runtime.system_time(),
+            status: BattleStatus::InProgress,
+            has_prediction_market: false,
+            rules_version: *state.rules_version.get(),
+            player1_class: CharacterClass::Warrior.into(),
+            player2_class: CharacterClass::Warrior.into(),
+            platform_fee_bps: state.config.get().platform_fee_bps,
+            mode: QueueMode::Ranked,
+        }).unwrap();
+        LobbyContract::count_fighters(state, &[owner("alice"), owner("bob")], true).blocking_wait();
+    }
+
+    /// Track a battle and complete it under `rules`
+    fn run_battle(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, rules: &BattleRules) {
+        track_battle(state, runtime, battle);
+        runtime.set_message_origin_chain_id(chain(battle));
+        LobbyContract::execute_message(state, runtime, Message::BattleCompleted {
+            winner: owner("alice"),
+            loser: owner("bob"),
+            rounds_played: rules.max_rounds,
+            total_stake: Amount::from_tokens(2),
+            battle_stats: (CombatStats::default(), CombatStats::default()),
+            rules_digest: rules.digest(),
+            end_reason: BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::HpPercent },
+            results: vec![],
+        }).blocking_wait();
+    }
+
+    /// Open a market on `battle` with `count` bettors alternating sides: one token on alice, three on bob
+    fn busy_market(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, count: u32) -> u64 {
+        let market_id = LobbyContract::create_prediction_market_in_lobby(
+            state, chain(battle), chain("alice"), chain("bob"), false, runtime.system_time(),
+        ).blocking_wait();
+        state.battle_to_market.insert(&chain(battle), market_id).unwrap();
+        for index in 0..count {
+            let (side, tokens) = if index % 2 == 0 { ("alice", 1) } else { ("bob", 3) };
+            let now = runtime.system_time();
+            LobbyContract::place_bet(
+                state, runtime, owner(&format!("bettor-{index}")), market_id, chain(side), Amount::from_tokens(tokens), now,
+            ).blocking_wait().unwrap();
+        }
+        market_id
+    }
+
+    fn payouts(state: &LobbyState, market_id: u64, count: u32) -> Vec<Option<Amount>> {
+        (0..count)
+            .map(|index| {
+                let bet = state.bets.get(&(market_id, owner(&format!("bettor-{index}")))).blocking_wait().unwrap().unwrap();
+                bet.payout
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_market_payouts_are_deferred_across_maintenance_passes() {
+        let (mut state, mut runtime) = setup();
+        let market_id = busy_market(&mut state, &mut runtime, "big", 80);
+
+        run_battle(&mut state, &mut runtime, "big", &BattleRules::default());
+
+        // The core completion ran synchronously
+        let market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+        assert_eq!((market.status, market.winner_chain), (MarketStatus::Settled, Some(chain("alice"))));
+        let record = state.completed_battles.get(&chain("big")).blocking_wait().unwrap().unwrap();
+        assert_eq!(record.prediction_market_id, Some(market_id));
+        assert!(payouts(&state, market_id, 80).iter().all(Option::is_none));
+
+        let passes: Vec<_> = (0..4).map(|_| LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait()).collect();
+        assert_eq!(passes, [32, 32, 16, 0]);
+        assert!(!state.pending_settlements.contains_key(&market_id).blocking_wait().unwrap());
+
+        // 40 tokens backed alice out of a 160 token pool
+        let paid = payouts(&state, market_id, 80);
+        assert!(paid.iter().step_by(2).all(|payout| *payout == Some(Amount::from_tokens(4))));
+        assert!(paid.iter().skip(1).step_by(2).all(|payout| *payout == Some(Amount::ZERO)));
+    }
+
+    #[test]
+    fn delayed_maintenance_loses_no_payouts() {
+        let (mut state, mut runtime) = setup();
+        let first = busy_market(&mut state, &mut runtime, "first", 40);
+        let second = busy_market(&mut state, &mut runtime, "second", 40);
+
+        run_battle(&mut state, &mut runtime, "first", &BattleRules::default());
+        run_battle(&mut state, &mut runtime, "second", &BattleRules::default());
+        assert_eq!(state.pending_settlements.count().blocking_wait().unwrap(), 2);
+
+        while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+
+        assert_eq!(state.pending_settlements.count().blocking_wait().unwrap(), 0);
+        for market_id in [first, second] {
+            assert!(payouts(&state, market_id, 40).iter().all(Option::is_some));
+        }
+    }
+
+    #[test]
+    fn winnings_are_claimed_once_to_the_bettor_chain() {
+        let (mut state, mut runtime) = setup();
+        state.config.get_mut().platform_fee_bps = 1000;
+        let market_id = busy_market(&mut state, &mut runtime, "claimed", 4);
+        let external_id = state.id_codec.get().encode(market_id);
+        for bettor in ["bettor-0", "bettor-1"] {
+            create_player_chain(&mut state, &mut runtime, bettor, 0);
+        }
+        let claim = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, bettor: &str| {
+            operate(state, runtime, bettor, Operation::ClaimWinnings { market_id: external_id });
+        };
+        let refused = |state: &LobbyState, bettor: &str, reason: &str| {
+            state.rejections.contains_key(&RejectionKey::new("ClaimWinnings", reason, owner(bettor))).blocking_wait().unwrap()
+        };
+
+        claim(&mut state, &mut runtime, "bettor-0");
+        run_battle(&mut state, &mut runtime, "claimed", &BattleRules::default());
+        claim(&mut state, &mut runtime, "bettor-2");
+        assert!(refused(&state, "bettor-0", "not_settled") && refused(&state, "bettor-2", "not_settled"));
+        while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+
+        // Alice's backers keep their 2 tokens and split bob's 6 less the 10% fee
+        assert_eq!(state.market_fee_ledger.get(&market_id).blocking_wait().unwrap(), Some(Amount::from_millis(600)));
+        assert_eq!(*state.total_platform_revenue.get(), Amount::from_millis(800));
+        for bettor in ["bettor-0", "bettor-0", "bettor-1", "bettor-2"] {
+            claim(&mut state, &mut runtime, bettor);
+        }
+        let sent: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::DistributeWinnings { bettor, amount, market_id } => Some((request.destination, bettor, amount, market_id)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent, [(chain("bettor-0-0"), owner("bettor-0"), Amount::from_millis(3700), external_id)]);
+        assert!(refused(&state, "bettor-0", "already_claimed"));
+        assert!(refused(&state, "bettor-1", "nothing_to_claim"));
+        assert!(refused(&state, "bettor-2", "no_player_chain"));
+    }
+
+    #[test]
+    fn fixed_odds_markets_pay_the_locked_odds_as_far_as_the_pool_goes() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetMarketPricing { pricing: MarketPricing::FixedOdds });
+        state.market_rounding.set(RoundingPolicy { dust_to: DustDestination::Platform });
+        fn open_market(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, bets: [(&str, &str, u128); 3]) -> u64 {
+            let market_id = LobbyContract::create_prediction_market_in_lobby(
+                state, chain(battle), chain("alice"), chain("bob"), false, runtime.system_time(),
+            ).blocking_wait();
+            state.battle_to_market.insert(&chain(battle), market_id).unwrap();
+            for (bettor, side, tokens) in bets {
+                let now = runtime.system_time();
+                LobbyContract::place_bet(state, runtime, owner(bettor), market_id, chain(side), Amount::from_tokens(tokens), now)
+                    .blocking_wait().unwrap();
+            }
+            market_id
+        }
+        let bet = |state: &LobbyState, market_id: u64, bettor: &str| state.bets.get(&(market_id, owner(bettor))).blocking_wait().unwrap().unwrap();
+
+        // Alice's backers were promised 1 and 2.5 tokens of a 5-token pool
+        let cheap = open_market(&mut state, &mut runtime, "cheap", [("carol", "alice", 1), ("dave", "bob", 3), ("erin", "alice", 1)]);
+        assert_eq!(["carol", "dave", "erin"].map(|bettor| bet(&state, cheap, bettor).odds_at_bet), [10_000, 13_333, 25_000]);
+        // Backing alice at 4.0 and then 1.75 promised 9.25 tokens of a 7-token pool
+        let rich = open_market(&mut state, &mut runtime, "rich", [("dave", "bob", 3), ("carol", "alice", 1), ("erin", "alice", 3)]);
+        assert_eq!(state.prediction_markets.get(&rich).blocking_wait().unwrap().unwrap().player1_locked, Amount::from_millis(9_250));
+        let market_id = state.id_codec.get().encode(rich);
+        operate(&mut state, &mut runtime, "dave", Operation::PlaceBet {
+            market_id,
+            predicted_winner: chain("alice"),
+            amount: Amount::from_tokens(1),
+        });
+        assert!(state.rejections.contains_key(&RejectionKey::new("PlaceBet", "opposite_side", owner("dave"))).blocking_wait().unwrap());
+
+        run_battle(&mut state, &mut runtime, "cheap", &BattleRules::default());
+        run_battle(&mut state, &mut runtime, "rich", &BattleRules::default());
+        while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+
+        let payout = |state: &LobbyState, market_id: u64, bettor: &str| bet(state, market_id, bettor).payout;
+        assert_eq!(payout(&state, cheap, "carol"), Some(Amount::from_tokens(1)));
+        assert_eq!(payout(&state, cheap, "erin"), Some(Amount::from_millis(2_500)));
+        // The short pool pays every promise at 7/9.25, the last atto of rounding to the platform
+        assert_eq!(payout(&state, rich, "carol"), Some(Amount::from_attos(3_027_027_027_027_027_027)));
+        assert_eq!(payout(&state, rich, "erin"), Some(Amount::from_attos(3_972_972_972_972_972_972)));
+        assert_eq!(payout(&state, rich, "dave"), Some(Amount::ZERO));
+        assert_eq!(*state.total_platform_revenue.get(), Amount::from_attos(1_500_000_000_000_000_001));
+    }
+
+    #[test]
+    fn markets_whose_battle_never_completes_are_voided_and_refunded() {
+        let (mut state, mut runtime) = setup();
+        let market_id = busy_market(&mut state, &mut runtime, "stalled", 2);
+        let external_id = state.id_codec.get().encode(market_id);
+        create_player_chain(&mut state, &mut runtime, "bettor-1", 0);
+        track_battle(&mut state, &mut runtime, "stalled");
+        operate(&mut state, &mut runtime, "carol", Operation::CloseMarket { market_id: external_id });
+        let market = |state: &LobbyState| state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+        assert_eq!(market(&state).status, MarketStatus::Closed);
+
+        runtime.set_system_time(Timestamp::from(MARKET_RESULT_LIMIT.as_micros() - 1));
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+
+        // Once the result is overdue every bettor is owed exactly their stake
+        runtime.set_system_time(Timestamp::from(0).saturating_add(MARKET_RESULT_LIMIT));
+        assert_eq!(maintenance_backlog(&state, runtime.system_time()).blocking_wait().unwrap(), 1);
+        while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+        assert_eq!((market(&state).status, market(&state).winner_chain), (MarketStatus::Cancelled, None));
+        assert_eq!(payouts(&state, market_id, 2), [Some(Amount::from_tokens(1)), Some(Amount::from_tokens(3))]);
+        assert_eq!(state.market_refunds.get(&market_id).blocking_wait().unwrap(), Some(Amount::from_tokens(4)));
+        assert!(!state.result_deadlines.contains_key(&market_id).blocking_wait().unwrap());
+
+        // The battle is given up on, so its fighters may replace their player chains again
+        assert!(!state.active_battles.contains_key(&chain("stalled")).blocking_wait().unwrap());
+        for player in ["alice", "bob"] {
+            assert!(!state.battles_in_flight.contains_key(&owner(player)).blocking_wait().unwrap());
+        }
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "alice", 1);
+
+        operate(&mut state, &mut runtime, "bettor-1", Operation::ClaimWinnings { market_id: external_id });
+        assert!(runtime.created_send_message_requests().iter().any(|request| request.destination == chain("bettor-1-0")
+            && matches!(request.message, Message::DistributeWinnings { amount, .. } if amount == Amount::from_tokens(3))));
+
+        // A result arriving after all is neither recorded nor rated, and leaves the refunds as they are
+        runtime.set_message_origin_chain_id(chain("stalled"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::BattleCompleted {
+            winner: owner("alice"),
+            loser: owner("bob"),
+            rounds_played: 3,
+            total_stake: Amount::from_tokens(2),
+            battle_stats: (CombatStats::default(), CombatStats::default()),
+            rules_digest: BattleRules::default().digest(),
+            end_reason: BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::HpPercent },
+            results: vec![],
+        }).blocking_wait();
+        assert!(!state.completed_battles.contains_key(&chain("stalled")).blocking_wait().unwrap());
+        assert_eq!(market(&state).status, MarketStatus::Cancelled);
+        assert!(!state.pending_settlements.contains_key(&market_id).blocking_wait().unwrap());
+        assert_eq!(payouts(&state, market_id, 2), [Some(Amount::from_tokens(1)), Some(Amount::from_tokens(3))]);
+    }
+
+    #[test]
+    fn tokens_are_minted_and_moved_only_between_registered_chains() {
+        let (mut state, mut runtime) = setup();
+        for player in ["alice", "bob"] {
+            create_player_chain(&mut state, &mut runtime, player, 0);
+        }
+        let credits = |runtime: &mut ContractRuntime<crate::MajorulesContract>| -> Vec<_> {
+            runtime.created_send_message_requests().iter()
+                .filter_map(|request| match request.message {
+                    Message::CreditTokens { owner, amount } => Some((request.destination, owner, amount)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let transfer = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, origin: &str, to: &str| {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(state, runtime, Message::RequestTokenTransfer {
+                from: owner("alice"),
+                to: owner(to),
+                amount: Amount::from_tokens(2),
+            }).blocking_wait();
+        };
+
+        operate(&mut state, &mut runtime, "treasury", Operation::MintTokens { to: owner("alice"), amount: Amount::from_tokens(5) });
+        operate(&mut state, &mut runtime, "treasury", Operation::MintTokens { to: owner("carol"), amount: Amount::from_tokens(5) });
+        operate(&mut state, &mut runtime, "treasury", Operation::MintTokens { to: owner("bob"), amount: Amount::ZERO });
+        assert_eq!(state.token_supply.get().minted, Amount::from_tokens(5));
+        assert!(state.rejections.contains_key(&RejectionKey::new("MintTokens", "unregistered", owner("treasury"))).blocking_wait().unwrap());
+        assert!(state.rejections.contains_key(&RejectionKey::new("MintTokens", "zero_amount", owner("treasury"))).blocking_wait().unwrap());
+
+        // Transfers reach the recipient's chain, or return to the sender's when nobody can take them
+        transfer(&mut state, &mut runtime, "alice-0", "bob");
+        transfer(&mut state, &mut runtime, "bob-0", "bob");
+        transfer(&mut state, &mut runtime, "alice-0", "carol");
+        assert_eq!(credits(&mut runtime), [
+            (chain("alice-0"), owner("alice"), Amount::from_tokens(5)),
+            (chain("bob-0"), owner("bob"), Amount::from_tokens(2)),
+            (chain("alice-0"), owner("alice"), Amount::from_tokens(2)),
+        ]);
+    }
+
+    #[test]
+    fn mint_terms_reach_new_chains_and_their_fees_reach_the_treasury() {
+        let (mut state, mut runtime) = setup();
+        let terms = MintTerms { cost: Amount::from_tokens(3), free_mints: 2, roster_cap: 4, cooldown_secs: 600, item_cost: Amount::from_tokens(1) };
+        operate(&mut state, &mut runtime, "treasury", Operation::SetMintTerms { terms });
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        assert!(runtime.created_send_message_requests().iter()
+            .any(|request| matches!(request.message, Message::InitializePlayerChain { minting, .. } if minting == terms)));
+
+        for origin in ["alice-0", "mallory-0"] {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::MintFeePaid {
+                player: owner("alice"),
+                amount: Amount::from_tokens(3),
+            }).blocking_wait();
+        }
+        assert_eq!(state.mint_fee_ledger.get(&owner("alice")).blocking_wait().unwrap(), Some(Amount::from_tokens(3)));
+        assert_eq!(*state.total_platform_revenue.get(), Amount::from_tokens(3));
+        assert_eq!(state.token_supply.get().burned, Amount::from_tokens(3));
+    }
+
+    #[test]
+    fn quest_rewards_are_minted_once_per_day_for_the_player_chain() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        runtime.set_system_time(Timestamp::from(majorules::time::MICROS_PER_DAY));
+        for (origin, day, quest_id) in [
+            ("alice-0", 1, "win-3"),
+            ("alice-0", 1, "win-3"),
+            ("mallory-0", 1, "crit-5"),
+            ("alice-0", 2, "crit-5"),
+            ("alice-0", 1, "daily-login"),
+            ("alice-0", 0, "crit-5"),
+        ] {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::QuestRewardClaimed {
+                player: owner("alice"),
+                day,
+                quest_id: quest_id.to_string(),
+            }).blocking_wait();
+        }
+
+        let credits: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::CreditTokens { amount, .. } => Some((request.destination, amount)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(credits, [(chain("alice-0"), Amount::from_tokens(3)), (chain("alice-0"), Amount::from_tokens(2))]);
+        assert_eq!(state.token_supply.get().minted, Amount::from_tokens(5));
+    }
+
+    #[test]
+    fn the_registry_follows_lives_reported_by_player_chains() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        let report = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, origin: &str, lives: u8, fee: u128| {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(state, runtime, Message::CharacterLivesChanged {
+                player: owner("alice"),
+                character_id: "hero".to_string(),
+                lives_remaining: lives,
+                revive_fee: Amount::from_tokens(fee),
+            }).blocking_wait();
+        };
+
+        report(&mut state, &mut runtime, "alice-0", 0, 0);
+        report(&mut state, &mut runtime, "mallory-0", 3, 0);
+        let entry = state.character_registry.get(&owner("alice").to_string()).blocking_wait().unwrap().unwrap();
+        assert_eq!((entry.character_id.as_str(), entry.lives_remaining, entry.is_alive), ("hero", 0, false));
+
+        report(&mut state, &mut runtime, "alice-0", STARTING_LIVES, 4);
+        let entry = state.character_registry.get(&owner("alice").to_string()).blocking_wait().unwrap().unwrap();
+        assert!(entry.is_alive && entry.lives_remaining == STARTING_LIVES);
+        assert_eq!(state.mint_fee_ledger.get(&owner("alice")).blocking_wait().unwrap(), Some(Amount::from_tokens(4)));
+        assert_eq!(state.token_supply.get().burned, Amount::from_tokens(4));
+    }
+
+    #[test]
+    fn seasons_soft_reset_ratings_and_reward_their_top_players() {
+        let (mut state, mut runtime) = setup();
+        for player in ["alice", "bob"] {
+            create_player_chain(&mut state, &mut runtime, player, 0);
+        }
+        state.ratings.insert(&owner("alice"), 1600).unwrap();
+        let terms = SeasonTerms { reward_pool: Amount::from_tokens(100), payout_bps: vec![7_000, 3_000], rating_carry_bps: 5_000 };
+        operate(&mut state, &mut runtime, "treasury", Operation::StartSeason { name: "One".to_string(), terms: terms.clone() });
+        operate(&mut state, &mut runtime, "treasury", Operation::StartSeason { name: "Two".to_string(), terms });
+        assert!(state.rejections.contains_key(&RejectionKey::new("StartSeason", "season_running", owner("treasury"))).blocking_wait().unwrap());
+        assert_eq!(LobbyContract::rating(&state, owner("alice")).blocking_wait(), 1400);
+
+        run_battle(&mut state, &mut runtime, "seasonal", &BattleRules::default());
+        let alice = state.season_stats.get(&(1, owner("alice"))).blocking_wait().unwrap().unwrap();
+        let bob = state.season_stats.get(&(1, owner("bob"))).blocking_wait().unwrap().unwrap();
+        assert_eq!((alice.battles, alice.wins, bob.losses), (1, 1, 1));
+        assert!(alice.rating > 1400 && alice.rating < 1600);
+        assert_eq!(LobbyContract::rating(&state, owner("alice")).blocking_wait(), alice.rating);
+        let standings = state.current_season.get().as_ref().unwrap().standings.clone();
+        assert_eq!(standings.iter().map(|standing| (standing.rank, standing.player)).collect::<Vec<_>>(), [
+            (1, owner("alice")),
+            (2, owner("bob")),
+        ]);
+
+        operate(&mut state, &mut runtime, "treasury", Operation::EndSeason);
+        let season = state.seasons.get(&1).blocking_wait().unwrap().unwrap();
+        assert!(state.current_season.get().is_none() && season.ended_at.is_some());
+        assert_eq!(season.rewards.iter().map(|reward| (reward.player, reward.amount)).collect::<Vec<_>>(), [
+            (owner("alice"), Amount::from_tokens(70)),
+            (owner("bob"), Amount::from_tokens(30)),
+        ]);
+        assert_eq!(state.token_supply.get().minted, Amount::from_tokens(100));
+        assert!(runtime.created_send_message_requests().iter().any(|request| request.destination == chain("alice-0")
+            && matches!(request.message, Message::CreditTokens { amount, .. } if amount == Amount::from_tokens(70))));
+
+        operate(&mut state, &mut runtime, "treasury", Operation::EndSeason);
+        assert!(state.rejections.contains_key(&RejectionKey::new("EndSeason", "no_season", owner("treasury"))).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn the_lobby_admin_tunes_fees_and_matchmaking() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetLobbyAdmin { admin: Some(owner("admin")) });
+        operate(&mut state, &mut runtime, "admin", Operation::UpdatePlatformFee { platform_fee_bps: 1_000 });
+        operate(&mut state, &mut runtime, "admin", Operation::UpdatePlatformFee { platform_fee_bps: MAX_PLATFORM_FEE_BPS + 1 });
+        assert_eq!(state.config.get().platform_fee_bps, 1_000);
+        assert!(state.rejections.contains_key(&RejectionKey::new("UpdatePlatformFee", "fee_too_high", owner("admin"))).blocking_wait().unwrap());
+
+        // A paused lobby keeps queueing and pairs once resumed
+        operate(&mut state, &mut runtime, "admin", Operation::PauseMatchmaking { paused: true });
+        request_join_queue(&mut state, &mut runtime, "alice");
+        request_join_queue(&mut state, &mut runtime, "bob");
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 2);
+        expect_match_chain(&mut runtime, "alice", "bob", "resumed");
+        operate(&mut state, &mut runtime, "admin", Operation::PauseMatchmaking { paused: false });
+        assert_eq!(state.waiting_players.count().blocking_wait().unwrap(), 0);
+
+        // Battles settle at the fee they were opened with
+        operate(&mut state, &mut runtime, "admin", Operation::UpdatePlatformFee { platform_fee_bps: 0 });
+        runtime.set_message_origin_chain_id(chain("resumed"));
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::BattleCompleted {
+            winner: owner("alice"),
+            loser: owner("bob"),
+            rounds_played: 3,
+            total_stake: Amount::from_tokens(2),
+            battle_stats: (CombatStats::default(), CombatStats::default()),
+            rules_digest: BattleRules::default().digest(),
+            end_reason: BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::HpPercent },
+            results: vec![],
+        }).blocking_wait();
+        let record = state.completed_battles.get(&chain("resumed")).blocking_wait().unwrap().unwrap();
+        assert_eq!((record.platform_fee_bps, record.platform_fee), (1_000, Amount::from_millis(200)));
+    }
+
+    #[test]
+    fn completions_count_only_from_opened_chains_about_their_own_fighters() {
+        let (mut state, mut runtime) = setup();
+        track_battle(&mut state, &mut runtime, "opened");
+        let complete = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, origin: &str, loser: &str| {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(state, runtime, Message::BattleCompleted {
+                winner: owner("alice"),
+                loser: owner(loser),
+                rounds_played: 3,
+                total_stake: Amount::from_tokens(2),
+                battle_stats: (CombatStats::default(), CombatStats::default()),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                results: vec![fighter_result("alice"), fighter_result("mallory")],
+            }).blocking_wait();
+        };
+
+        complete(&mut state, &mut runtime, "forged", "bob");
+        complete(&mut state, &mut runtime, "opened", "mallory");
+        assert!(state.completed_battles.get(&chain("forged")).blocking_wait().unwrap().is_none());
+        assert!(state.active_battles.contains_key(&chain("opened")).blocking_wait().unwrap());
+
+        complete(&mut state, &mut runtime, "opened", "bob");
+        assert!(state.completed_battles.get(&chain("opened")).blocking_wait().unwrap().is_some());
+        let relayed = state.relayed_results.indices().blocking_wait().unwrap();
+        assert_eq!(relayed, [(chain("opened"), owner("alice"))]);
+    }
+
+    #[test]
+    fn the_lobby_admin_cannot_move_the_treasury() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::SetLobbyAdmin { admin: Some(owner("admin")) });
+        let response = operate(&mut state, &mut runtime, "admin", Operation::SetTreasuryOwner { owner: owner("admin") });
+        assert_eq!(response, OperationResponse::rejected("unauthorized"));
+        assert_eq!(*state.treasury_owner.get(), Some(owner("treasury")));
+    }
+
+    #[test]
+    fn only_treasury_mints_tokens() {
+        let (mut state, mut runtime) = setup();
+        let response = operate(&mut state, &mut runtime, "alice", Operation::MintTokens { to: owner("alice"), amount: Amount::from_tokens(5) });
+        assert_eq!(response, OperationResponse::rejected("unauthorized"));
+        assert!(state.rejections.contains_key(&RejectionKey::new("MintTokens", "unauthorized", owner("alice"))).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn cranks_are_paid_per_step_within_caps() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::FundCommunityPool { amount: Amount::from_tokens(1) });
+        let stale = busy_market(&mut state, &mut runtime, "stale", 0);
+        request_join_queue(&mut state, &mut runtime, "alice");
+        let mut now = MARKET_OPEN_LIMIT.as_micros();
+        runtime.set_system_time(Timestamp::from(now));
+        let earned = |state: &LobbyState| state.crank_rewards.get(&owner("carol")).blocking_wait().unwrap().unwrap_or_default();
+
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 10 });
+        let market = state.prediction_markets.get(&stale).blocking_wait().unwrap().unwrap();
+        assert_eq!(market.status, MarketStatus::Closed);
+        assert!(!state.is_queued(owner("alice")).blocking_wait().unwrap());
+        assert!(runtime.created_send_message_requests().iter()
+            .any(|request| request.destination == chain("alice") && matches!(request.message, Message::QueueLeft { .. })));
+        assert_eq!(earned(&state), CRANK_BOUNTY_PER_STEP.saturating_mul(2));
+
+        // Nothing left to do pays nothing and leaves no audit entry
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 10 });
+        assert_eq!(earned(&state), CRANK_BOUNTY_PER_STEP.saturating_mul(2));
+        assert_eq!((state.crank_stats.get().cranks, state.crank_stats.get().work_done), (2, 2));
+        assert_eq!(state.crank_log.count(), 1);
+
+        // A busy crank is capped, and so is the day's total
+        let open_markets = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, batch: &str, count: u32| {
+            for index in 0..count {
+                busy_market(state, runtime, &format!("{batch}-{index}"), 0);
+            }
+        };
+        open_markets(&mut state, &mut runtime, "busy", 25);
+        now += MARKET_OPEN_LIMIT.as_micros();
+        runtime.set_system_time(Timestamp::from(now));
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 100 });
+        assert_eq!(state.crank_log.get(1).blocking_wait().unwrap().unwrap().work, 25);
+        assert_eq!(earned(&state), CRANK_BOUNTY_PER_STEP.saturating_mul(2).saturating_add(CRANK_BOUNTY_CAP));
+
+        state.crank_payouts.insert(&0, CRANK_DAILY_BOUNTY_CAP.saturating_sub(CRANK_BOUNTY_PER_STEP)).unwrap();
+        open_markets(&mut state, &mut runtime, "late", 3);
+        now += MARKET_OPEN_LIMIT.as_micros();
+        runtime.set_system_time(Timestamp::from(now));
+        let before = earned(&state);
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 100 });
+        assert_eq!(earned(&state), before.saturating_add(CRANK_BOUNTY_PER_STEP));
+        assert_eq!(state.crank_payouts.get(&0).blocking_wait().unwrap(), Some(CRANK_DAILY_BOUNTY_CAP));
+        assert_eq!(state.crank_stats.get().bounty_paid, earned(&state));
+        assert_eq!(*state.community_pool.get(), Amount::from_tokens(1).saturating_sub(earned(&state)));
+    }
+
+    fn lobby_hints(state: &LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str) -> Vec<LobbyAction> {
+        lobby_actions(state, owner(player), runtime.system_time()).blocking_wait().unwrap()
+    }
+
+    #[test]
+    fn hints_match_the_operations_a_lobby_takes() {
+        let (mut state, mut runtime) = setup();
+        assert!(lobby_hints(&state, &mut runtime, "carol").is_empty());
+
+        // An open market takes bets until it is closed
+        let market_id = busy_market(&mut state, &mut runtime, "hinted", 5);
+        let external_id = state.id_codec.get().encode(market_id);
+        let bet_hint = LobbyAction::CanPlaceBet(CanPlaceBet {
+            market_id: external_id,
+            player1_chain: chain("alice"),
+            player2_chain: chain("bob"),
+        });
+        assert_eq!(lobby_hints(&state, &mut runtime, "carol"), [bet_hint]);
+        let bet = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, bettor: &str| {
+            operate(state, runtime, bettor, Operation::PlaceBet {
+                market_id: external_id,
+                predicted_winner: chain("alice"),
+                amount: Amount::from_tokens(1),
+            });
+            state.bets.contains_key(&(market_id, owner(bettor))).blocking_wait().unwrap()
+        };
+        assert!(bet(&mut state, &mut runtime, "carol"));
+
+        operate(&mut state, &mut runtime, "treasury", Operation::CloseMarket { market_id: external_id });
+        assert!(lobby_hints(&state, &mut runtime, "dave").is_empty());
+        assert!(!bet(&mut state, &mut runtime, "dave"));
+
+        // Deferred payouts are offered to a crank, estimated at exactly the work it does
+        run_battle(&mut state, &mut runtime, "hinted", &BattleRules::default());
+        let hints = lobby_hints(&state, &mut runtime, "carol");
+        let [LobbyAction::CanCrank(crank)] = hints.as_slice() else {
+            panic!("Expected a crank hint after the battle completed, got {hints:?}");
+        };
+        let before = state.crank_stats.get().work_done;
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 100 });
+        assert_eq!(state.crank_stats.get().work_done - before, u64::from(crank.estimated_work));
+        assert!(lobby_hints(&state, &mut runtime, "carol").is_empty());
+        operate(&mut state, &mut runtime, "carol", Operation::Crank { max_work: 100 });
+        assert_eq!(state.crank_stats.get().work_done - before, u64::from(crank.estimated_work));
+
+        // A queued character can be taken back out, once
+        request_join_queue(&mut state, &mut runtime, "alice");
+        let leave_hint = LobbyAction::CanLeaveQueue(CanLeaveQueue { character_id: "alice-character".to_string() });
+        assert_eq!(lobby_hints(&state, &mut runtime, "alice"), [leave_hint]);
+        operate(&mut state, &mut runtime, "alice", Operation::LeaveQueue);
+        let released = runtime.created_send_message_requests().iter()
+            .filter(|request| matches!(request.message, Message::QueueLeft { .. }))
+            .count();
+        assert_eq!(released, 1);
+        assert!(lobby_hints(&state, &mut runtime, "alice").is_empty());
+    }
+
+    /// Secret the private battles in these tests are opened with
+    const INVITE: [u8; 32] = [7; 32];
+
+    fn request_private_battle(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, join: Option<u64>, tokens: u128) {
+        request_private_battle_with(state, runtime, player, join, INVITE, tokens);
+    }
+
+    /// Open a private battle behind `secret`, or join battle `join` with it
+    fn request_private_battle_with(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        join: Option<u64>,
+        secret: [u8; 32],
+        tokens: u128,
+    ) {
+        runtime.set_message_origin_chain_id(chain(player));
+        let (player, player_chain, character_snapshot, stake) = (owner(player), chain(player), snapshot(player), Amount::from_tokens(tokens));
+        let message = match join {
+            None => Message::RequestCreatePrivateBattle { player, player_chain, character_snapshot, stake, invite: invite_digest(secret) },
+            Some(battle_id) => {
+                Message::RequestJoinPrivateBattle { player, player_chain, battle_id, invite_secret: secret, character_snapshot, stake }
+            }
+        };
+        LobbyContract::execute_message(state, runtime, message).blocking_wait();
+    }
+
+    fn released(runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str) -> usize {
+        runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == chain(player) && matches!(request.message, Message::QueueLeft { .. }))
+            .count()
+    }
+
+    #[test]
+    fn private_battles_start_when_the_invited_opponent_joins() {
+        let (mut state, mut runtime) = setup();
+        let codec = IdCodec::obfuscated(0xBA771E);
+        state.id_codec.set(codec);
+
+        request_private_battle(&mut state, &mut runtime, "alice", None, 2);
+        let battle_id = codec.encode(1);
+        assert!(runtime.created_send_message_requests().iter().any(|request| {
+            request.destination == chain("alice") && matches!(request.message, Message::PrivateBattleCreated { battle_id: id } if id == battle_id)
+        }));
+
+        // Refused joins release the joining character; without the secret the battle is unknown
+        for (player, id, secret, tokens, reason) in [
+            ("alice", battle_id, INVITE, 2, "own_battle"),
+            ("bob", battle_id, INVITE, 1, "stake_mismatch"),
+            ("carol", codec.encode(9), INVITE, 2, "unknown_battle"),
+            ("dave", battle_id, [8; 32], 2, "unknown_battle"),
+        ] {
+            request_private_battle_with(&mut state, &mut runtime, player, Some(id), secret, tokens);
+            let key = RejectionKey::new("RequestJoinPrivateBattle", reason, owner(player));
+            assert!(state.rejections.contains_key(&key).blocking_wait().unwrap(), "{reason}");
+            assert_eq!(released(&mut runtime, player), 1);
+        }
+        assert!(state.pending_private_battles.contains_key(&1).blocking_wait().unwrap());
+
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::multiple([(owner("alice"), 1), (owner("bob"), 1)], 10, Default::default()),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain("private"),
+        );
+        request_private_battle(&mut state, &mut runtime, "bob", Some(battle_id), 2);
+
+        assert!(!state.pending_private_battles.contains_key(&1).blocking_wait().unwrap());
+        assert!(!state.private_battle_invites.contains_key(&1).blocking_wait().unwrap());
+        let battle = state.active_battles.get(&chain("private")).blocking_wait().unwrap().unwrap();
+        assert_eq!((battle.player1, battle.player2, battle.total_stake), (owner("alice"), owner("bob"), Amount::from_tokens(4)));
+        let matched = runtime.created_send_message_requests().iter()
+            .filter(|request| matches!(request.message, Message::BattleMatched { battle_chain, .. } if battle_chain == chain("private")))
+            .count();
+        assert_eq!(matched, 2);
+    }
+
+    #[test]
+    fn unjoined_private_battles_expire_with_queue_entries() {
+        let (mut state, mut runtime) = setup();
+        request_private_battle(&mut state, &mut runtime, "alice", None, 2);
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+
+        runtime.set_system_time(Timestamp::from(0).saturating_add(QUEUE_ENTRY_TTL).saturating_add(TimeDelta::from_secs(1)));
+        let backlog = maintenance_backlog(&state, runtime.system_time()).blocking_wait().unwrap();
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 1);
+        assert_eq!(backlog, 1);
+        assert_eq!(released(&mut runtime, "alice"), 1);
+        assert!(!state.pending_private_battles.contains_key(&1).blocking_wait().unwrap());
+        assert!(!state.private_battle_invites.contains_key(&1).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn market_rounding_dust_is_routed_and_ledgered() {
+        let policies = [
+            (DustDestination::Platform, None),
+            (DustDestination::LargestShare, Some(1)),
+            (DustDestination::FirstClaimer, Some(0)),
+        ];
+        for (dust_to, recipient) in policies {
+            let (mut state, mut runtime) = setup();
+            state.market_rounding.set(RoundingPolicy { dust_to });
+            let market_id = LobbyContract::create_prediction_market_in_lobby(
+                &mut state, chain("odd"), chain("alice"), chain("bob"), false, runtime.system_time(),
+            ).blocking_wait();
+            state.battle_to_market.insert(&chain("odd"), market_id).unwrap();
+            // Thirds of a pool one atto over four tokens leave one atto behind
+            let bets = [
+                ("alice", Amount::from_tokens(1)),
+                ("alice", Amount::from_tokens(2)),
+                ("bob", Amount::from_attos(1_000_000_000_000_000_001)),
+            ];
+            for (index, (side, amount)) in bets.into_iter().enumerate() {
+                let bettor = owner(&format!("bettor-{index}"));
+                let now = runtime.system_time();
+                LobbyContract::place_bet(&mut state, &mut runtime, bettor, market_id, chain(side), amount, now).blocking_wait().unwrap();
+            }
+            run_battle(&mut state, &mut runtime, "odd", &BattleRules::default());
+            let revenue_before = *state.total_platform_revenue.get();
+
+            while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+
+            let one_atto = Amount::from_attos(1);
+            let entry = state.dust_ledger.get(&market_id).blocking_wait().unwrap().unwrap();
+            assert_eq!((entry.amount, entry.recipient), (one_atto, recipient.map(|index| owner(&format!("bettor-{index}")))));
+            let platform_dust = state.total_platform_revenue.get().saturating_sub(revenue_before);
+            assert_eq!(platform_dust, if recipient.is_none() { one_atto } else { Amount::ZERO });
+
+            let paid = payouts(&state, market_id, 3).into_iter()
+                .fold(Amount::ZERO, |total, payout| total.saturating_add(payout.unwrap()));
+            let pool = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap().total_pool;
+            assert_eq!(paid.saturating_add(platform_dust), pool);
+        }
+    }
+
+    fn summaries_sent(runtime: &mut ContractRuntime<crate::MajorulesContract>) -> Vec<ChainId> {
+        runtime.created_send_message_requests().iter()
+            .filter(|request| matches!(request.message, Message::BattleSummaryNotification { .. }))
+            .map(|request| request.destination)
+            .collect()
+    }
+
+    #[test]
+    fn subscribers_receive_the_summary_once_via_the_outbox() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "carol", 0);
+        create_player_chain(&mut state, &mut runtime, "dave", 0);
+        track_battle(&mut state, &mut runtime, "final");
+        for observer in ["carol", "dave"] {
+            operate(&mut state, &mut runtime, observer, Operation::SubscribeToBattle { battle_chain: chain("final") });
+        }
+
+        run_battle(&mut state, &mut runtime, "final", &BattleRules::default());
+        assert!(summaries_sent(&mut runtime).is_empty());
+
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 2);
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+        assert_eq!(summaries_sent(&mut runtime), [chain("carol-0"), chain("dave-0")]);
+        let summary = runtime.created_send_message_requests().iter()
+            .find_map(|request| match &request.message {
+                Message::BattleSummaryNotification { summary } => Some(summary.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!((summary.battle_chain, summary.winner), (chain("final"), owner("alice")));
+    }
+
+    #[test]
+    fn battle_subscriptions_are_capped() {
+        let (mut state, mut runtime) = setup();
+        track_battle(&mut state, &mut runtime, "popular");
+        for index in 0..=MAX_BATTLE_SUBSCRIBERS {
+            let observer = format!("observer-{index}");
+            create_player_chain(&mut state, &mut runtime, &observer, 0);
+            operate(&mut state, &mut runtime, &observer, Operation::SubscribeToBattle { battle_chain: chain("popular") });
+        }
+
+        let subscribers = state.battle_subscribers.get(&chain("popular")).blocking_wait().unwrap().unwrap();
+        assert_eq!(subscribers.len(), MAX_BATTLE_SUBSCRIBERS);
+        let excess = format!("observer-{MAX_BATTLE_SUBSCRIBERS}");
+        let capped = RejectionKey::new("SubscribeToBattle", "subscriber_cap", owner(&excess));
+        assert!(state.rejections.contains_key(&capped).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn subscriptions_end_with_the_battle() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "carol", 0);
+        track_battle(&mut state, &mut runtime, "short");
+        operate(&mut state, &mut runtime, "carol", Operation::SubscribeToBattle { battle_chain: chain("short") });
+
+        run_battle(&mut state, &mut runtime, "short", &BattleRules::default());
+        assert!(!state.battle_subscribers.contains_key(&chain("short")).blocking_wait().unwrap());
+
+        operate(&mut state, &mut runtime, "carol", Operation::SubscribeToBattle { battle_chain: chain("short") });
+        let late = RejectionKey::new("SubscribeToBattle", "unknown_battle", owner("carol"));
+        assert!(state.rejections.contains_key(&late).blocking_wait().unwrap());
+        assert!(!state.battle_subscribers.contains_key(&chain("short")).blocking_wait().unwrap());
+    }
+
+    fn report_attestation(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, attestation: Attestation) {
+        runtime.set_message_origin_chain_id(chain(battle));
+        LobbyContract::execute_message(state, runtime, Message::ResultAttestation { attestation }).blocking_wait();
+    }
+
+    #[test]
+    fn attestations_update_completed_records_and_flag_disputes() {
+        let (mut state, mut runtime) = setup();
+        run_battle(&mut state, &mut runtime, "agreed", &BattleRules::default());
+        run_battle(&mut state, &mut runtime, "contested", &BattleRules::default());
+
+        report_attestation(&mut state, &mut runtime, "agreed", Attestation::Attested);
+        report_attestation(&mut state, &mut runtime, "contested", Attestation::Disputed);
+        // A dispute is not overturned by a later report
+        report_attestation(&mut state, &mut runtime, "contested", Attestation::Attested);
+        // Chains without a completed record cannot attest
+        report_attestation(&mut state, &mut runtime, "unknown", Attestation::Disputed);
+
+        let attestation = |battle: &str| state.completed_battles.get(&chain(battle)).blocking_wait().unwrap().unwrap().attestation;
+        assert_eq!(attestation("agreed"), Attestation::Attested);
+        assert_eq!(attestation("contested"), Attestation::Disputed);
+        assert_eq!(state.review_queue.indices().blocking_wait().unwrap(), [chain("contested")]);
+    }
+
+    #[test]
+    fn completed_battles_keep_the_rules_they_ran_under() {
+        let (mut state, mut runtime) = setup();
+        state.rules_version.set(1);
+        let original = BattleRules::default();
+        run_battle(&mut state, &mut runtime, "first", &original);
+
+        let boosted = BattleRules { max_rounds: 5, xp_boost_bps: 20_000, ..BattleRules::default() };
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateBattleRules { rules: boosted.clone() });
+        assert_eq!(*state.rules_version.get(), 2);
+        // Formats no battle could be fought under are refused and leave the rules alone
+        for (broken, reason) in [
+            (BattleRules { turns_per_round: 0, ..boosted.clone() }, "invalid_turns_per_round"),
+            (BattleRules { reveal_window_secs: 0, ..boosted.clone() }, "zero_timeout"),
+            (BattleRules { max_rounds: 200, sudden_death_rounds: 100, ..boosted.clone() }, "too_many_rounds"),
+        ] {
+            let response = operate(&mut state, &mut runtime, "treasury", Operation::UpdateBattleRules { rules: broken });
+            assert_eq!(response, OperationResponse::rejected(reason));
+        }
+        assert_eq!(*state.battle_rules.get(), boosted);
+        run_battle(&mut state, &mut runtime, "second", &boosted);
+
+        let first = state.completed_battles.get(&chain("first")).blocking_wait().unwrap().unwrap();
+        let second = state.completed_battles.get(&chain("second")).blocking_wait().unwrap().unwrap();
+        assert_eq!((first.rules_version, first.rules_digest), (1, original.digest()));
+        assert_eq!((second.rules_version, second.rules_digest), (2, boosted.digest()));
+        assert_ne!(first.rules_digest, second.rules_digest);
+
+        // Both battles are indexed for the archive under each player and the day they ended
+        let keys = vec![(first.completed_at, chain("first")), (second.completed_at, chain("second"))];
+        for player in ["alice", "bob"] {
+            assert_eq!(state.completed_by_owner.get(&owner(player)).blocking_wait().unwrap(), Some(keys.clone()));
+        }
+        assert_eq!(state.completed_by_day.get(&0).blocking_wait().unwrap(), Some(keys));
+    }
+
+    #[test]
+    fn saturated_revenue_is_recorded_as_an_overflow() {
+        let (mut state, mut runtime) = setup();
+        state.config.get_mut().platform_fee_bps = 500;
+        let rules = BattleRules::default();
+        run_battle(&mut state, &mut runtime, "exact", &rules);
+        assert!(state.counter_overflows.indices().blocking_wait().unwrap().is_empty());
+
+        state.total_platform_revenue.set(Amount::MAX.saturating_sub(Amount::from_attos(1)));
+        runtime.set_system_time(Timestamp::from(3 * MICROS_PER_DAY));
+        run_battle(&mut state, &mut runtime, "pinned", &rules);
+        runtime.set_system_time(Timestamp::from(5 * MICROS_PER_DAY));
+        run_battle(&mut state, &mut runtime, "still-pinned", &rules);
+
+        assert_eq!(*state.total_platform_revenue.get(), Amount::MAX);
+        let overflow = state.counter_overflows.get(counters::PLATFORM_REVENUE).blocking_wait().unwrap().unwrap();
+        assert_eq!(overflow, CounterOverflow { first_day: 3, last_day: 5, events: 2 });
+        // Per-battle ledger entries stay exact
+        let fee = state.treasury_ledger.get(&chain("pinned")).blocking_wait().unwrap().unwrap();
+        assert_eq!(fee, Amount::from_millis(100));
+    }
+
+    #[test]
+    fn bets_that_would_overflow_a_pool_are_rejected() {
+        let (mut state, mut runtime) = setup();
+        let market_id = busy_market(&mut state, &mut runtime, "battle", 0);
+        let mut market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+        market.total_pool = Amount::MAX;
+        state.prediction_markets.insert(&market_id, market).unwrap();
+
+        let placed = LobbyContract::place_bet(
+            &mut state, &mut runtime, owner("whale"), market_id, chain("alice"), Amount::from_tokens(1), Timestamp::from(0),
+        ).blocking_wait();
+
+        assert_eq!(placed, Err("pool_overflow"));
+
+        assert!(state.bets.get(&(market_id, owner("whale"))).blocking_wait().unwrap().is_none());
+        let market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+        assert_eq!((market.total_pool, market.player1_pool), (Amount::MAX, Amount::ZERO));
+    }
+
+    #[test]
+    fn bets_past_the_limits_or_the_battle_start_are_refused() {
+        let (mut state, mut runtime) = setup();
+        let limits = BettingLimits { max_stake_per_bettor: Amount::from_tokens(5), max_market_pool: Amount::from_tokens(8) };
+        operate(&mut state, &mut runtime, "treasury", Operation::SetBettingLimits { limits });
+        // Four tokens are already in
+        let market_id = busy_market(&mut state, &mut runtime, "battle", 2);
+        let external_id = state.id_codec.get().encode(market_id);
+        let bet = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, bettor: &str, tokens: u128| {
+            operate(state, runtime, bettor, Operation::PlaceBet {
+                market_id: external_id,
+                predicted_winner: chain("alice"),
+                amount: Amount::from_tokens(tokens),
+            })
+        };
+
+        // Top-ups count towards the bettor's cap, and every bet towards the market's
+        assert_eq!(bet(&mut state, &mut runtime, "carol", 3), OperationResponse::Done);
+        assert_eq!(bet(&mut state, &mut runtime, "carol", 3), OperationResponse::rejected("bet_limit_exceeded"));
+        assert_eq!(bet(&mut state, &mut runtime, "carol", 1), OperationResponse::Done);
+        assert_eq!(bet(&mut state, &mut runtime, "dave", 1), OperationResponse::rejected("market_limit_reached"));
+        assert_eq!(bet(&mut state, &mut runtime, "dave", 0), OperationResponse::rejected("zero_amount"));
+        assert!(state.bets.get(&(market_id, owner("dave"))).blocking_wait().unwrap().is_none());
+        assert_eq!(state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap().total_pool, Amount::from_tokens(8));
+
+        // Limits that admit no bet at all are refused
+        operate(&mut state, &mut runtime, "treasury", Operation::SetBettingLimits {
+            limits: BettingLimits { max_stake_per_bettor: Amount::ZERO, ..limits },
+        });
+        assert_eq!(state.config.get().betting_limits, limits);
+        operate(&mut state, &mut runtime, "treasury", Operation::SetBettingLimits { limits: BettingLimits::default() });
+
+        // Only the battle itself closes betting when its first turn is in
+        track_battle(&mut state, &mut runtime, "battle");
+        for origin in ["mallory", "battle"] {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::BattleStarted { battle_chain: chain("battle") })
+                .blocking_wait();
+            let market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+            assert_eq!(market.accepts_bets(), origin == "mallory");
+        }
+        assert_eq!(bet(&mut state, &mut runtime, "erin", 1), OperationResponse::rejected("market_closed"));
+        assert!(state.rejections.contains_key(&RejectionKey::new("PlaceBet", "market_closed", owner("erin"))).blocking_wait().unwrap());
+        assert!(state.bets.get(&(market_id, owner("erin"))).blocking_wait().unwrap().is_none());
+    }
+
+    /// Alice beats bob; results are identical however they are delivered
+    fn fighter_result(player: &str) -> FighterResult {
+        let won = player == "alice";
+        FighterResult {
+            player: owner(player),
+            character_id: "hero".to_string(),
+            won,
+            payout: if won { Amount::from_tokens(2) } else { Amount::ZERO },
+            xp_gained: if won { 150 } else { 50 },
+            elo_change: if won { 16 } else { -16 },
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        }
+    }
+
+    /// Message `index` of a battle reporting separately: alice's result, bob's result, then the completion.
+    /// Index 3 is the consolidated completion carrying both results
+    fn battle_message(index: usize) -> Message {
+        let separate = |result: FighterResult, opponent: &str| Message::BattleResultWithElo {
+            player: result.player,
+            character_id: result.character_id,
+            opponent: owner(opponent),
+            won: result.won,
+            payout: result.payout,
+            xp_gained: result.xp_gained,
+            elo_change: result.elo_change,
+            battle_stats: result.combat_stats,
+            rounds_played: 3,
+            battle_chain: chain("battle"),
+            rules_digest: 7,
+            end_reason: BattleEndReason::Knockout,
+            item_drop: result.item_drop,
+        };
+        match index {
+            0 => separate(fighter_result("alice"), "bob"),
+            1 => separate(fighter_result("bob"), "alice"),
+            2 => completion(BattleEndReason::Knockout, vec![]),
+            _ => completion(BattleEndReason::Knockout, vec![fighter_result("alice"), fighter_result("bob")]),
+        }
+    }
+
+    fn completion(end_reason: BattleEndReason, results: Vec<FighterResult>) -> Message {
+        Message::BattleCompleted {
+            winner: owner("alice"),
+            loser: owner("bob"),
+            rounds_played: 3,
+            total_stake: Amount::from_tokens(2),
+            battle_stats: (CombatStats::default(), CombatStats::default()),
+            rules_digest: 7,
+            end_reason,
+            results,
+        }
+    }
+
+    #[test]
+    fn battles_are_rated_once_from_cached_ratings() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        operate(&mut state, &mut runtime, "treasury", Operation::SetEloConfig { config: EloConfig { k_factor: 40 } });
+
+        // Only a player's own chain seeds their rating, and only once
+        for (origin, elo_rating) in [("mallory", 2000), ("bob-0", 1600), ("bob-0", 1800)] {
+            runtime.set_message_origin_chain_id(chain(origin));
+            let stats = majorules::PlayerGlobalStats { elo_rating, ..Default::default() };
+            LobbyContract::execute_message(&mut state, &mut runtime, Message::PlayerStatsResponse { player: owner("bob"), stats })
+                .blocking_wait();
+        }
+        assert_eq!(state.ratings.get(&owner("bob")).blocking_wait().unwrap(), Some(1600));
+
+        // Unrated alice upsets bob, reported both ways
+        track_battle(&mut state, &mut runtime, "rated");
+        runtime.set_message_origin_chain_id(chain("rated"));
+        let results = vec![fighter_result("alice"), fighter_result("bob")];
+        LobbyContract::execute_message(&mut state, &mut runtime, completion(BattleEndReason::Knockout, results)).blocking_wait();
+        let alice = fighter_result("alice");
+        LobbyContract::execute_message(&mut state, &mut runtime, Message::BattleResultWithElo {
+            player: alice.player, character_id: alice.character_id, opponent: owner("bob"), won: true, payout: alice.payout,
+            xp_gained: alice.xp_gained, elo_change: 0, battle_stats: CombatStats::default(), rounds_played: 3, battle_chain: chain("rated"),
+            rules_digest: 7, end_reason: BattleEndReason::Knockout, item_drop: None,
+        }).blocking_wait();
+
+        let changes: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::UpdatePlayerStats { elo_change, .. } => Some((request.destination, elo_change)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(changes, [(chain("alice-0"), 36), (chain("bob-0"), -36)]);
+        let rating = |state: &LobbyState, player: &str| state.ratings.get(&owner(player)).blocking_wait().unwrap();
+        assert_eq!((rating(&state, "alice"), rating(&state, "bob")), (Some(1236), Some(1564)));
+
+        // Draws leave ratings alone
+        track_battle(&mut state, &mut runtime, "drawn");
+        runtime.set_message_origin_chain_id(chain("drawn"));
+        LobbyContract::execute_message(&mut state, &mut runtime, completion(BattleEndReason::Draw, vec![])).blocking_wait();
+        assert_eq!(state.rated_battles.get(&chain("drawn")).blocking_wait().unwrap(), Some((0, 0)));
+        assert_eq!((rating(&state, "alice"), rating(&state, "bob")), (Some(1236), Some(1564)));
+    }
+
+    #[test]
+    fn xp_grows_with_upsets_streaks_and_battle_length() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        state.ratings.insert(&owner("bob"), 1600).unwrap();
+        let fight = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str| {
+            track_battle(state, runtime, battle);
+            runtime.set_message_origin_chain_id(chain(battle));
+            let results = vec![fighter_result("alice"), fighter_result("bob")];
+            LobbyContract::execute_message(state, runtime, completion(BattleEndReason::Knockout, results)).blocking_wait();
+        };
+
+        // Three rounds are worth 600 bps to both; alice's 400-point upset adds 4 000
+        fight(&mut state, &mut runtime, "upset");
+        // Still 342 points under bob, alice now brings a one-win streak
+        fight(&mut state, &mut runtime, "again");
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateXpCurve {
+            curve: XpCurve { max_bonus_bps: 0, ..XpCurve::default() },
+        });
+        fight(&mut state, &mut runtime, "flat");
+
+        let xp: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::UpdatePlayerStats { xp_gained, .. } => Some((request.destination, xp_gained)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(xp, [
+            (chain("alice-0"), 219), (chain("bob-0"), 53),
+            (chain("alice-0"), 211), (chain("bob-0"), 53),
+            (chain("alice-0"), 150), (chain("bob-0"), 50),
+        ]);
+        assert_eq!(state.win_streaks.get(&owner("alice")).blocking_wait().unwrap(), Some(3));
+        assert_eq!(state.win_streaks.get(&owner("bob")).blocking_wait().unwrap(), Some(0));
+    }
+
+    #[test]
+    fn streaks_mint_bonuses_and_insurance_survives_one_defeat() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        operate(&mut state, &mut runtime, "treasury", Operation::UpdateStreakTerms {
+            terms: StreakTerms { notable_every: 2, ..StreakTerms::default() },
+        });
+        let fight = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, winner: &str| {
+            track_battle(state, runtime, battle);
+            runtime.set_message_origin_chain_id(chain(battle));
+            let loser = if winner == "alice" { "bob" } else { "alice" };
+            LobbyContract::execute_message(state, runtime, Message::BattleCompleted {
+                winner: owner(winner),
+                loser: owner(loser),
+                rounds_played: 3,
+                total_stake: Amount::from_tokens(2),
+                battle_stats: (CombatStats::default(), CombatStats::default()),
+                rules_digest: 7,
+                end_reason: BattleEndReason::Knockout,
+                results: vec![],
+            }).blocking_wait();
+        };
+        let insure = |state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, origin: &str| {
+            runtime.set_message_origin_chain_id(chain(origin));
+            LobbyContract::execute_message(state, runtime, Message::StreakInsurancePaid {
+                player: owner("alice"),
+                paid: Amount::from_tokens(3),
+            }).blocking_wait();
+        };
+        let streak = |state: &LobbyState| state.win_streaks.get(&owner("alice")).blocking_wait().unwrap();
+
+        fight(&mut state, &mut runtime, "first", "alice");
+        fight(&mut state, &mut runtime, "second", "alice");
+        let notable = state.notable_streaks.read(..).blocking_wait().unwrap();
+        assert_eq!(notable.iter().map(|entry| (entry.player, entry.streak, entry.battle_chain)).collect::<Vec<_>>(), [
+            (owner("alice"), 2, chain("second")),
+        ]);
+
+        // Insurance costs 2 tokens out of the 3 offered, and is held once at a time
+        insure(&mut state, &mut runtime, "mallory-0");
+        insure(&mut state, &mut runtime, "alice-0");
+        insure(&mut state, &mut runtime, "alice-0");
+        assert!(state.rejections.contains_key(&RejectionKey::new("BuyStreakInsurance", "already_insured", owner("alice"))).blocking_wait().unwrap());
+        assert_eq!(state.token_supply.get().burned, Amount::from_tokens(2));
+
+        fight(&mut state, &mut runtime, "insured", "bob");
+        assert_eq!(streak(&state), Some(2));
+        fight(&mut state, &mut runtime, "broken", "bob");
+        assert_eq!(streak(&state), Some(0));
+
+        let credits: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::CreditTokens { amount, .. } => Some((request.destination, amount)),
+                _ => None,
+            })
+            .collect();
+        // Alice's second win, the change and the refused payment, then bob's second win
+        assert_eq!(credits, [
+            (chain("alice-0"), Amount::from_millis(100)),
+            (chain("alice-0"), Amount::from_tokens(1)),
+            (chain("alice-0"), Amount::from_tokens(3)),
+            (chain("bob-0"), Amount::from_millis(100)),
+        ]);
+    }
+
+    #[test]
+    fn completed_battles_keep_the_leaderboard_ranked_and_capped() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        run_battle(&mut state, &mut runtime, "first", &BattleRules::default());
+
+        let standings = |state: &LobbyState| -> Vec<_> {
+            state.leaderboard.get().iter().map(|entry| (entry.rank, entry.player, entry.elo_rating, entry.wins)).collect()
+        };
+        assert_eq!(standings(&state), [(1, owner("alice"), 1216, 1), (2, owner("bob"), 1184, 0)]);
+
+        // A crowd rated above both leaves room for alice only
+        let mut crowd = Vec::new();
+        for index in 0..LEADERBOARD_SIZE - 1 {
+            let name = format!("crowd-{index}");
+            create_player_chain(&mut state, &mut runtime, &name, 0);
+            crowd.push((owner(&name), 1500));
+        }
+        LobbyContract::refresh_leaderboard(&mut state, &crowd).blocking_wait();
+        let standings = standings(&state);
+        assert_eq!(standings.len(), LEADERBOARD_SIZE);
+        assert_eq!(standings.last(), Some(&(LEADERBOARD_SIZE as u64, owner("alice"), 1216, 1)));
+    }
+
+    /// Apply the stat updates a player chain received, each twice, and describe its resulting state
+    fn player_chain_state(player: &str, updates: &[Message]) -> String {
+        let state = player_chain(player, updates);
+        let hero = state.characters.get("hero").blocking_wait().unwrap().unwrap();
+        format!("{:?} xp={}", state.player_stats.get(), hero.xp)
+    }
+
+    /// A player chain with one character, after receiving `updates` twice each
+    fn player_chain(player: &str, updates: &[Message]) -> PlayerState {
+        let mut runtime = ContractRuntime::new()
+            .with_chain_id(chain(&format!("{player}-0")))
+            .with_authenticated_signer(owner(player))
+            .with_system_time(Timestamp::from(0));
+        let mut state = PlayerState::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        runtime.set_message_origin_chain_id(chain("lobby"));
+        PlayerContract::execute_message(&mut state, &mut runtime, Message::InitializePlayerChain {
+            lobby_chain_id: chain("lobby"),
+            owner: owner(player),
+            max_concurrent_battles: 1,
+            ranked_gates: RankedGates::default(),
+            progression: ProgressionGates::default(),
+            leveling: LevelingConfig::default(),
+            minting: MintTerms::default(),
+        }).blocking_wait();
+        PlayerContract::execute_operation(&mut state, &mut runtime, Operation::MintCharacter {
+            character_id: "hero".to_string(),
+            class: "warrior".to_string(),
+        }).blocking_wait();
+        PlayerContract::execute_message(&mut state, &mut runtime, Message::BattleMatched {
+            battle_chain: chain("battle"),
+            character_id: "hero".to_string(),
+            stake: Amount::ZERO,
+        }).blocking_wait();
+
+        for update in updates.iter().chain(updates) {
+            let update = serde_json::from_value(serde_json::to_value(update).unwrap()).unwrap();
+            PlayerContract::execute_message(&mut state, &mut runtime, update).blocking_wait();
+        }
+        state
+    }
+
+    #[test]
+    fn battle_results_settle_identically_in_any_order() {
+        // Deliver `order` twice over and describe the final lobby and player chain state
+        let settle = |order: &[usize]| {
+            let (mut state, mut runtime) = setup();
+            create_player_chain(&mut state, &mut runtime, "alice", 0);
+            create_player_chain(&mut state, &mut runtime, "bob", 0);
+            let market_id = busy_market(&mut state, &mut runtime, "battle", 4);
+            track_battle(&mut state, &mut runtime, "battle");
+            let sent_before = runtime.created_send_message_requests().len();
+
+            runtime.set_message_origin_chain_id(chain("battle"));
+            for &index in order.iter().chain(order) {
+                LobbyContract::execute_message(&mut state, &mut runtime, battle_message(index)).blocking_wait();
+            }
+            LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait();
+
+            let requests = runtime.created_send_message_requests();
+            let updates_for = |player: &str| {
+                let updates: Vec<_> = requests[sent_before..].iter()
+                    .filter(|request| request.destination == chain(&format!("{player}-0")))
+                    .filter(|request| matches!(request.message, Message::UpdatePlayerStats { .. }))
+                    .map(|request| serde_json::from_value(serde_json::to_value(&request.message).unwrap()).unwrap())
+                    .collect();
+                assert_eq!(updates.len(), 1, "{player} should be updated exactly once");
+                updates
+            };
+            let registry = |player: &str| {
+                let entry = state.character_registry.get(&owner(player).to_string()).blocking_wait().unwrap().unwrap();
+                (entry.total_battles, entry.wins, entry.losses)
+            };
+            (
+                format!("{:?}", state.completed_battles.get(&chain("battle")).blocking_wait().unwrap()),
+                state.active_battles.contains_key(&chain("battle")).blocking_wait().unwrap(),
+                state.relayed_results.indices().blocking_wait().unwrap(),
+                (registry("alice"), registry("bob")),
+                payouts(&state, market_id, 4),
+                player_chain_state("alice", &updates_for("alice")),
+                player_chain_state("bob", &updates_for("bob")),
+            )
+        };
+
+        let consolidated = settle(&[3]);
+        assert!(!consolidated.1);
+        assert_eq!(consolidated.3, ((1, 1, 0), (1, 0, 1)));
+        for order in [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+            assert_eq!(settle(&order), consolidated, "order {order:?}");
+        }
+    }
+
+    #[test]
+    fn end_reasons_reach_the_record_summaries_histories_and_market() {
+        let reasons = [
+            BattleEndReason::Knockout,
+            BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::Damage },
+            BattleEndReason::Forfeit,
+            BattleEndReason::Draw,
+            BattleEndReason::NoContest,
+        ];
+        for end_reason in reasons {
+            let (mut state, mut runtime) = setup();
+            create_player_chain(&mut state, &mut runtime, "alice", 0);
+            create_player_chain(&mut state, &mut runtime, "bob", 0);
+            create_player_chain(&mut state, &mut runtime, "carol", 0);
+            let market_id = busy_market(&mut state, &mut runtime, "battle", 4);
+            track_battle(&mut state, &mut runtime, "battle");
+            operate(&mut state, &mut runtime, "carol", Operation::SubscribeToBattle { battle_chain: chain("battle") });
+
+            runtime.set_message_origin_chain_id(chain("battle"));
+            let results = vec![fighter_result("alice"), fighter_result("bob")];
+            LobbyContract::execute_message(&mut state, &mut runtime, completion(end_reason, results)).blocking_wait();
+            LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait();
+
+            let record = state.completed_battles.get(&chain("battle")).blocking_wait().unwrap().unwrap();
+            assert_eq!(record.end_reason, end_reason);
+
+            let requests = runtime.created_send_message_requests();
+            let summaries: Vec<_> = requests.iter()
+                .filter_map(|request| match &request.message {
+                    Message::BattleSummaryNotification { summary } => Some(summary.end_reason),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(summaries, [end_reason]);
+
+            for player in ["alice", "bob"] {
+                let updates: Vec<_> = requests.iter()
+                    .filter(|request| request.destination == chain(&format!("{player}-0")))
+                    .filter(|request| matches!(request.message, Message::UpdatePlayerStats { .. }))
+                    .map(|request| serde_json::from_value(serde_json::to_value(&request.message).unwrap()).unwrap())
+                    .collect();
+                let history = player_chain(player, &updates).battle_history.get(&chain("battle")).blocking_wait().unwrap().unwrap();
+                assert_eq!(history.end_reason, end_reason);
+            }
+            drop(requests);
+
+            // Alice's backers win unless the ending voids the market and refunds every stake
+            let market = state.prediction_markets.get(&market_id).blocking_wait().unwrap().unwrap();
+            let stakes = |alice, bob| [Some(Amount::from_tokens(alice)), Some(Amount::from_tokens(bob))].repeat(2);
+            if end_reason.settles_market() {
+                assert_eq!((market.status, market.winner_chain), (MarketStatus::Settled, Some(chain("alice"))));
+                assert_eq!(payouts(&state, market_id, 4), stakes(4, 0));
+            } else {
+                assert_eq!((market.status, market.winner_chain), (MarketStatus::Cancelled, None));
+                assert_eq!(payouts(&state, market_id, 4), stakes(1, 3));
+            }
+        }
+    }
+
+    #[test]
+    fn results_from_unknown_or_long_completed_battles_are_ignored() {
+        let (mut state, mut runtime) = setup();
+        create_player_chain(&mut state, &mut runtime, "alice", 0);
+        create_player_chain(&mut state, &mut runtime, "bob", 0);
+        let updates = |runtime: &mut ContractRuntime<crate::MajorulesContract>| {
+            runtime.created_send_message_requests().iter()
+                .filter(|request| matches!(request.message, Message::UpdatePlayerStats { .. }))
+                .count()
+        };
+
+        runtime.set_message_origin_chain_id(chain("battle"));
+        LobbyContract::execute_message(&mut state, &mut runtime, battle_message(0)).blocking_wait();
+        assert_eq!(updates(&mut runtime), 0);
+
+        track_battle(&mut state, &mut runtime, "battle");
+        LobbyContract::execute_message(&mut state, &mut runtime, battle_message(2)).blocking_wait();
+        runtime.set_system_time(Timestamp::from(0).saturating_add(RESULT_GRACE_PERIOD));
+        LobbyContract::execute_message(&mut state, &mut runtime, battle_message(1)).blocking_wait();
+        assert_eq!(updates(&mut runtime), 0);
+    }
+
+    /// A lobby at day 30 where fixtures are allowed, populated from `seed` with payouts drained
+    fn fixture_lobby(seed: u64) -> (LobbyState, ContractRuntime<crate::MajorulesContract>) {
+        let (mut state, mut runtime) = setup();
+        state.config.get_mut().platform_fee_bps = 500;
+        runtime.set_application_parameters(Parameters { dev_fixtures: true });
+        runtime.set_system_time(Timestamp::from(30 * MICROS_PER_DAY));
+        operate(&mut state, &mut runtime, "treasury", Operation::GenerateFixtures { seed, players: 12, battles: 40, markets: 9 });
+        while LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait() > 0 {}
+        (state, runtime)
+    }
+
+    /// Everything fixtures write, rendered for comparison
+    fn fixture_snapshot(state: &LobbyState) -> String {
+        format!(
+            "{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}",
+            state.character_registry.index_values().blocking_wait().unwrap(),
+            state.completed_battles.index_values().blocking_wait().unwrap(),
+            state.completed_by_day.index_values().blocking_wait().unwrap(),
+            state.active_battles.index_values().blocking_wait().unwrap(),
+            state.prediction_markets.index_values().blocking_wait().unwrap(),
+            state.bets.index_values().blocking_wait().unwrap(),
+            state.leaderboard.get(),
+            state.total_platform_revenue.get(),
+            state.total_betting_volume.get(),
+        )
+    }
+
+    #[test]
+    fn fixtures_from_one_seed_build_identical_state() {
+        let (mut state, mut runtime) = fixture_lobby(7);
+        let (other, _) = fixture_lobby(7);
+        assert_eq!(fixture_snapshot(&state), fixture_snapshot(&other));
+        assert_ne!(fixture_snapshot(&state), fixture_snapshot(&fixture_lobby(8).0));
+
+        // Applying the same world twice would double count it
+        let before = fixture_snapshot(&state);
+        operate(&mut state, &mut runtime, "treasury", Operation::GenerateFixtures { seed: 7, players: 12, battles: 40, markets: 9 });
+        assert_eq!(fixture_snapshot(&state), before);
+        let repeated = RejectionKey::new("GenerateFixtures", "already_generated", owner("treasury"));
+        assert!(state.rejections.get(&repeated).blocking_wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn fixtures_hold_the_revenue_and_leaderboard_invariants() {
+        let (state, _) = fixture_lobby(42);
+        let records: Vec<_> = state.completed_battles.index_values().blocking_wait().unwrap()
+            .into_iter().map(|(_, record)| record).collect();
+        assert_eq!(records.len(), 40);
+
+        // Revenue is exactly the ledgered fees plus the dust routed to the platform
+        let fees = state.treasury_ledger.index_values().blocking_wait().unwrap().into_iter()
+            .fold(Amount::ZERO, |total, (_, fee)| total.saturating_add(fee));
+        let platform_dust = state.dust_ledger.index_values().blocking_wait().unwrap().into_iter()
+            .filter(|(_, dust)| dust.recipient.is_none())
+            .fold(Amount::ZERO, |total, (_, dust)| total.saturating_add(dust.amount));
+        let market_fees = state.market_fee_ledger.index_values().blocking_wait().unwrap().into_iter()
+            .fold(Amount::ZERO, |total, (_, fee)| total.saturating_add(fee));
+        assert_eq!(*state.total_platform_revenue.get(), fees.saturating_add(platform_dust).saturating_add(market_fees));
+        assert_eq!(fees, records.iter().fold(Amount::ZERO, |total, record| total.saturating_add(record.platform_fee)));
+        // Every collected fee left circulation
+        assert_eq!(state.token_supply.get().burned, fees.saturating_add(market_fees));
+
+        // Volume is every pool, and each settled pool is paid out in full
+        let markets: Vec<_> = state.prediction_markets.index_values().blocking_wait().unwrap();
+        let volume = markets.iter().fold(Amount::ZERO, |total, (_, market)| total.saturating_add(market.total_pool));
+        assert_eq!(*state.total_betting_volume.get(), volume);
+        let bets = state.bets.index_values().blocking_wait().unwrap();
+        for (market_id, market) in &markets {
+            let paid = bets.iter()
+                .filter(|((id, _), _)| id == market_id)
+                .fold(Amount::ZERO, |total, (_, bet)| total.saturating_add(bet.payout.unwrap_or_default()));
+            let dust = state.dust_ledger.get(market_id).blocking_wait().unwrap()
+                .filter(|dust| dust.recipient.is_none())
+                .map_or(Amount::ZERO, |dust| dust.amount);
+            let fee = state.market_fee_ledger.get(market_id).blocking_wait().unwrap().unwrap_or_default();
+            match market.status {
+                MarketStatus::Settled => assert_eq!(paid.saturating_add(dust).saturating_add(fee), market.total_pool),
+                _ => assert_eq!(paid, Amount::ZERO),
+            }
+        }
+        for status in [MarketStatus::Open, MarketStatus::Closed, MarketStatus::Settled] {
+            assert!(markets.iter().any(|(_, market)| market.status == status), "no {status:?} market");
+        }
+
+        // The leaderboard agrees with the records it was built from
+        let leaderboard = state.leaderboard.get();
+        assert_eq!(leaderboard.len(), 12);
+        assert!(leaderboard.iter().enumerate().all(|(index, entry)| entry.rank == index as u64 + 1));
+        assert!(leaderboard.windows(2).all(|pair| pair[0].elo_rating >= pair[1].elo_rating));
+        assert!(leaderboard.iter().any(|entry| entry.elo_rating != leaderboard[0].elo_rating));
+        let sum = |field: fn(&crate::state::LeaderboardEntry) -> u64| leaderboard.iter().map(field).sum::<u64>();
+        assert_eq!(sum(|entry| entry.wins), 40);
+        assert_eq!(sum(|entry| entry.losses), 40);
+        assert_eq!(sum(|entry| entry.total_battles), 80);
+        let earnings = leaderboard.iter().fold(Amount::ZERO, |total, entry| total.saturating_add(entry.total_earnings));
+        assert_eq!(earnings, records.iter().fold(Amount::ZERO, |total, record| total.saturating_add(record.winner_payout)));
+
+        // Battles span recent days, each indexed under the day it completed
+        let days = state.completed_by_day.index_values().blocking_wait().unwrap();
+        assert!(days.len() > 1 && days.iter().all(|(day, _)| (23..=30).contains(day)));
+        assert_eq!(days.iter().map(|(_, battles)| battles.len()).sum::<usize>(), 40);
+    }
+
+    #[test]
+    fn fixtures_are_rejected_unless_the_parameters_allow_them() {
+        let (mut state, mut runtime) = setup();
+        runtime.set_application_parameters(Parameters::default());
+        operate(&mut state, &mut runtime, "treasury", Operation::GenerateFixtures { seed: 7, players: 12, battles: 40, markets: 9 });
+
+        assert_eq!(state.character_registry.count().blocking_wait().unwrap(), 0);
+        assert_eq!(*state.market_count.get(), 0);
+        let disabled = RejectionKey::new("GenerateFixtures", "fixtures_disabled", owner("treasury"));
+        assert_eq!(state.rejections.get(&disabled).blocking_wait().unwrap().unwrap().count, 1);
+    }
+
+    #[test]
+    fn only_treasury_updates_battle_rules() {
+        let (mut state, mut runtime) = setup();
+        let response = operate(&mut state, &mut runtime, "mallory", Operation::UpdateBattleRules { rules: BattleRules::default() });
+        assert_eq!(response, OperationResponse::rejected("unauthorized"));
+        assert_eq!(*state.rules_version.get(), 0);
+    }
+
+    fn tournament_terms(max_entrants: u32) -> TournamentTerms {
+        TournamentTerms {
+            entry_fee: Amount::from_tokens(2),
+            max_entrants,
+            options: BracketOptions { require_power_of_two: false, seeding: Seeding::ByLevel },
+            runner_up_share_bps: 2_500,
+        }
+    }
+
+    fn create_tournament(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, terms: TournamentTerms) {
+        operate(state, runtime, "treasury", Operation::CreateTournament {
+            name: "Open".to_string(),
+            terms,
+            start_time: Timestamp::from(0).saturating_add(TimeDelta::from_secs(600)),
+            registration_closes_at: None,
+            party: None,
+        });
+    }
+
+    fn join_tournament(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, level: u16, tokens: u128) {
+        join_tournament_in(state, runtime, player, level, tokens, None);
+    }
+
+    fn join_tournament_in(
+        state: &mut LobbyState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: &str,
+        level: u16,
+        tokens: u128,
+        party: Option<&str>,
+    ) {
+        runtime.set_message_origin_chain_id(chain(player));
+        LobbyContract::execute_message(state, runtime, Message::RequestJoinTournament {
+            player: owner(player),
+            player_chain: chain(player),
+            tournament_id: 1,
+            character_snapshot: CharacterSnapshot { level, ..snapshot(player) },
+            entry_fee: Amount::from_tokens(tokens),
+            party: party.map(chain),
+        }).blocking_wait();
+    }
+
+    /// Payouts of the `TournamentExited` messages sent so far, by recipient
+    fn tournament_exits(runtime: &mut ContractRuntime<crate::MajorulesContract>) -> Vec<(ChainId, Amount)> {
+        runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::TournamentExited { payout, .. } => Some((request.destination, payout)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn expect_match_chain(runtime: &mut ContractRuntime<crate::MajorulesContract>, player1: &str, player2: &str, battle: &str) {
+        runtime.add_expected_open_chain_call(
+            ChainOwnership::multiple([(owner(player1), 1), (owner(player2), 1)], 10, Default::default()),
+            ApplicationPermissions::default(),
+            Amount::ZERO,
+            chain(battle),
+        );
+    }
+
+    fn finish_match(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, winner: &str, loser: &str) {
+        runtime.set_message_origin_chain_id(chain(battle));
+        LobbyContract::execute_message(state, runtime, Message::BattleCompleted {
+            winner: owner(winner),
+            loser: owner(loser),
+            rounds_played: 3,
+            total_stake: Amount::ZERO,
+            battle_stats: (CombatStats::default(), CombatStats::default()),
+            rules_digest: 7,
+            end_reason: BattleEndReason::Knockout,
+            results: vec![],
+        }).blocking_wait();
+    }
+
+    #[test]
+    fn tournaments_play_their_bracket_out_and_pay_the_finalists() {
+        let (mut state, mut runtime) = setup();
+        state.config.get_mut().platform_fee_bps = 1_000;
+        create_tournament(&mut state, &mut runtime, tournament_terms(3));
+
+        // Refused entries get their fee straight back
+        for (player, level, tokens, reason) in [
+            ("carol", 1, 2, None),
+            ("alice", 3, 2, None),
+            ("alice", 3, 2, Some("already_entered")),
+            ("dave", 1, 1, Some("wrong_entry_fee")),
+            ("bob", 2, 2, None),
+            ("erin", 1, 2, Some("tournament_full")),
+        ] {
+            join_tournament(&mut state, &mut runtime, player, level, tokens);
+            let Some(reason) = reason else {
+                continue;
+            };
+            let key = RejectionKey::new("RequestJoinTournament", reason, owner(player));
+            assert!(state.rejections.contains_key(&key).blocking_wait().unwrap(), "{reason}");
+        }
+        assert_eq!(tournament_exits(&mut runtime), [
+            (chain("alice"), Amount::from_tokens(2)),
+            (chain("dave"), Amount::from_tokens(1)),
+            (chain("erin"), Amount::from_tokens(2)),
+        ]);
+        let tournament = state.tournaments.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!((tournament.entrants, tournament.prize_pool), (3, Amount::from_tokens(6)));
+
+        // Top seed alice takes the bye; bob and carol fight for the other final spot
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(600)));
+        expect_match_chain(&mut runtime, "bob", "carol", "semi");
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 1);
+        let tournament = state.tournaments.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!((tournament.status, tournament.round), (TournamentStatus::InProgress, 1));
+        assert_eq!(tournament.matches[0].winner, Some(owner("alice")));
+        assert_eq!(tournament.matches[1].battle_chain, Some(chain("semi")));
+
+        expect_match_chain(&mut runtime, "alice", "bob", "final");
+        finish_match(&mut state, &mut runtime, "semi", "bob", "carol");
+        let tournament = state.tournaments.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!(tournament.round, 2);
+        assert_eq!(tournament.matches.len(), 1);
+        assert_eq!(tournament.matches[0].battle_chain, Some(chain("final")));
+
+        // The champion and runner-up split the pool left after the platform fee
+        finish_match(&mut state, &mut runtime, "final", "alice", "bob");
+        let tournament = state.tournaments.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!(tournament.status, TournamentStatus::Completed);
+        assert_eq!((tournament.champion, tournament.runner_up), (Some(owner("alice")), Some(owner("bob"))));
+        assert_eq!(tournament_exits(&mut runtime)[3..], [
+            (chain("carol"), Amount::ZERO),
+            (chain("alice"), Amount::from_millis(4_050)),
+            (chain("bob"), Amount::from_millis(1_350)),
+        ]);
+        assert_eq!(*state.total_platform_revenue.get(), Amount::from_millis(600));
+        assert!(state.tournament_battles.index_values().blocking_wait().unwrap().is_empty());
+    }
+
+    #[test]
+    fn tournaments_without_enough_entrants_are_cancelled_with_refunds() {
+        let (mut state, mut runtime) = setup();
+        operate(&mut state, &mut runtime, "treasury", Operation::CreateTournament {
+            name: "Too soon".to_string(),
+            terms: tournament_terms(8),
+            start_time: Timestamp::from(0),
+            registration_closes_at: None,
+            party: None,
+        });
+        let too_soon = RejectionKey::new("CreateTournament", "start_too_soon", owner("treasury"));
+        assert!(state.rejections.contains_key(&too_soon).blocking_wait().unwrap());
+        create_tournament(&mut state, &mut runtime, tournament_terms(1));
+        let invalid = RejectionKey::new("CreateTournament", "invalid_max_entrants", owner("treasury"));
+        assert!(state.rejections.contains_key(&invalid).blocking_wait().unwrap());
+        assert_eq!(*state.tournament_count.get(), 0);
+
+        create_tournament(&mut state, &mut runtime, tournament_terms(8));
+        join_tournament(&mut state, &mut runtime, "alice", 1, 2);
+
+        // A lone entrant waits out the grace period, then is refunded
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(600)));
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+        runtime.set_system_time(Timestamp::from(0).saturating_add(TimeDelta::from_secs(600 + 3_600)));
+        assert_eq!(maintenance_backlog(&state, runtime.system_time()).blocking_wait().unwrap(), 1);
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 1);
+
+        let tournament = state.tournaments.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!(tournament.status, TournamentStatus::Cancelled);
+        assert_eq!(tournament_exits(&mut runtime), [(chain("alice"), Amount::from_tokens(2))]);
+        assert!(!state.tournament_starts.contains_key(&1).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn starts_left_behind_by_missing_tournaments_are_dropped() {
+        let (mut state, mut runtime) = setup();
+        state.tournament_starts.insert(&7, Timestamp::from(0)).unwrap();
+
+        assert_eq!(LobbyContract::run_maintenance(&mut state, &mut runtime, MAINTENANCE_BUDGET).blocking_wait(), 0);
+        assert!(!state.tournament_starts.contains_key(&7).blocking_wait().unwrap());
+        assert!(state.tournaments.get(&7).blocking_wait().unwrap().is_none());
+    }
+
+    fn request_join_series(state: &mut LobbyState, runtime: &mut ContractRuntime<crate::MajorulesContract>, player: &str, best_of: u8) {
+        runtime.set_message_origin_chain_id(chain(player));
+        LobbyContract::execute_message(state, runtime, Message::RequestJoinQueue {
+            player: owner(player),
+            player_chain: chain(player),
+            character_snapshot: snapshot(player),
+            stake: Amount::from_tokens(2),
+            mode: QueueMode::Casual,
+            best_of,
+            party: None,
+        }).blocking_wait();
+    }
+
+    #[test]
+    fn series_play_game_after_game_and_pay_the_pot_once_decided() {
+        let (mut state, mut runtime) = setup();
+        state.config.get_mut().platform_fee_bps = 1_000;
+        // Stakes taken into the pot and payouts from it, by player chain
+        let series_messages = |runtime: &mut ContractRuntime<crate::MajorulesContract>, started: bool| {
+            let mut sent = runtime.created_send_message_requests().iter()
+                .filter_map(|request| match &request.message {
+                    Message::SeriesStarted { stake, .. } if started => Some((request.destination, *stake)),
+                    Message::SeriesEnded { payout, .. } if !started => Some((request.destination, *payout)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            sent.sort();
+            sent
+        };
+        let sorted = |mut expected: Vec<(ChainId, Amount)>| {
+            expected.sort();
+            expected
+        };
+
+        // Only queues asking for the same length meet
+        request_join_series(&mut state, &mut runtime, "alice", 3);
+        request_join_series(&mut state, &mut runtime, "carol", 1);
+        request_join_series(&mut state, &mut runtime, "dave", 4);
+        assert!(state.rejections.contains_key(&RejectionKey::new("RequestJoinQueue", "invalid_series_length", owner("dave"))).blocking_wait().unwrap());
+        expect_match_chain(&mut runtime, "alice", "bob", "game-1");
+        request_join_series(&mut state, &mut runtime, "bob", 3);
+        assert!(state.is_queued(owner("carol")).blocking_wait().unwrap());
+        let two = Amount::from_tokens(2);
+        assert_eq!(series_messages(&mut runtime, true), sorted(vec![(chain("alice"), two), (chain("bob"), two)]));
+        let game = state.active_battles.get(&chain("game-1")).blocking_wait().unwrap().unwrap();
+        assert_eq!(game.total_stake, Amount::ZERO);
+
+        // Each decided game opens the next until alice has two wins
+        expect_match_chain(&mut runtime, "alice", "bob", "game-2");
+        finish_match(&mut state, &mut runtime, "game-1", "alice", "bob");
+        finish_match(&mut state, &mut runtime, "game-2", "alice", "bob");
+        let series = state.series.get(&1).blocking_wait().unwrap().unwrap();
+        assert_eq!(series.games, [chain("game-1"), chain("game-2")]);
+        assert_eq!((series.status, series.winner, series.score.games_played), (SeriesStatus::Decided, Some(owner("alice")), 2));
+        assert!(state.series_battles.indices().blocking_wait().unwrap().is_empty());
+
+        // The pot pays out once, net of the fee
+        let payouts = sorted(vec![(chain("alice"), Amount::from_millis(3_600)), (chain("bob"), Amount::ZERO)]);
+        assert_eq!(series_messages(&mut runtime, false), payouts);
+        assert_eq!(*state.total_platform_revenue.get(), Amount::from_millis(400));
+        finish_match(&mut state, &mut runtime, "game-2", "alice", "bob");
+        assert_eq!(series_messages(&mut runtime, false), payouts);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,355 @@
+//! Pairing queued players by rating.
+//!
+//! Each waiting player accepts opponents within a rating window that starts narrow and widens
+//! the longer they wait. Stakes must fall in one band: the smaller may only fall short of the
+//! larger by a set tolerance, and a matched pair both put up the smaller stake. Among the pairs
+//! some window allows, the closest ratings win, with differing stakes counted as extra rating
+//! distance and time waited forgiving some of it, so entries that waited longest go first
+//! instead of starving behind a stream of fresh, closely rated joins. The queue holds only so
+//! many players at once.
+//!
+//! Each queue mode is its own pool with its own terms: a minimum stake, and optionally windows
+//! and limits of its own in place of the lobby-wide settings.
+//!
+//! To keep owners from farming rating off accounts they control, a player who leaves the queue
+//! waits out a cooldown before rejoining, and two players meet in ranked only so many times
+//! within a rematch window.
+
+use async_graphql::{InputObject, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, Amount, TimeDelta, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{fees::BPS_DENOMINATOR, time, QueueMode};
+
+/// Most recent pairings kept per player
+pub const RECENT_PAIRINGS: usize = 20;
+
+/// How rating windows open up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "MatchmakingConfigInput")]
+pub struct MatchmakingConfig {
+    /// Rating gap a player accepts as soon as they join
+    pub base_window: u64,
+    /// Points the window grows by every `widen_every_secs` of waiting
+    pub widen_by: u64,
+    pub widen_every_secs: u64,
+    /// Widest the window gets, however long the wait
+    pub max_window: u64,
+    /// Rating points a pair is penalized when one stake is double the other; smaller
+    /// differences cost proportionally less
+    pub stake_weight: u64,
+    /// Most the smaller stake of a pair may fall short of the larger, in basis points of the
+    /// larger; pairs further apart are never matched
+    pub stake_tolerance_bps: u16,
+    /// Seconds a player who left the queue waits before rejoining
+    pub rejoin_cooldown_secs: u64,
+    /// Most times two players meet in ranked within `rematch_window_secs`
+    pub max_ranked_meetings: u32,
+    pub rematch_window_secs: u64,
+    /// Rating points of distance a pair is forgiven for every `widen_every_secs` its
+    /// longer-waiting player has waited
+    pub wait_credit: u64,
+    /// Most players waiting at once; joins past it are refused
+    pub max_queue_size: u32,
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> Self {
+        Self {
+            base_window: 100,
+            widen_by: 50,
+            widen_every_secs: 10,
+            max_window: 800,
+            stake_weight: 200,
+            stake_tolerance_bps: 5_000,
+            rejoin_cooldown_secs: 30,
+            max_ranked_meetings: 2,
+            rematch_window_secs: 3_600,
+            wait_credit: 25,
+            max_queue_size: 256,
+        }
+    }
+}
+
+impl MatchmakingConfig {
+    /// Rating gap a player who has waited `waited` accepts
+    pub fn window(&self, waited: TimeDelta) -> u64 {
+        self.base_window.saturating_add(self.wait_steps(waited).saturating_mul(self.widen_by)).min(self.max_window)
+    }
+
+    /// Rating distance forgiven a pair whose longer-waiting player has waited `waited`
+    pub fn credit(&self, waited: TimeDelta) -> u64 {
+        self.wait_steps(waited).saturating_mul(self.wait_credit)
+    }
+
+    /// Whole `widen_every_secs` periods in `waited`
+    fn wait_steps(&self, waited: TimeDelta) -> u64 {
+        (waited.as_micros() / 1_000_000).checked_div(self.widen_every_secs).unwrap_or(0)
+    }
+
+    /// Whether stakes `a` and `b` fall in one band
+    pub fn stakes_compatible(&self, a: Amount, b: Amount) -> bool {
+        let (low, high) = (u128::from(a.min(b)), u128::from(a.max(b)));
+        let tolerance = self.stake_tolerance_bps as u128;
+        // `high * tolerance / BPS_DENOMINATOR`, without overflowing on large stakes
+        let allowed = high / BPS_DENOMINATOR * tolerance + high % BPS_DENOMINATOR * tolerance / BPS_DENOMINATOR;
+        high - low <= allowed
+    }
+
+    /// Whether a player whose `recent` pairings these are may meet `opponent` in ranked at `now`
+    pub fn ranked_rematch_allowed(&self, recent: &[Pairing], opponent: AccountOwner, now: Timestamp) -> bool {
+        let window = TimeDelta::from_secs(self.rematch_window_secs);
+        let meetings = recent.iter()
+            .filter(|pairing| pairing.opponent == opponent && time::delta_or_zero(now, pairing.matched_at) < window)
+            .count();
+        meetings < self.max_ranked_meetings as usize
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.max_window < self.base_window {
+            return Err("max_window_below_base");
+        }
+        if u128::from(self.stake_tolerance_bps) > BPS_DENOMINATOR {
+            return Err("invalid_stake_tolerance");
+        }
+        if self.max_ranked_meetings == 0 {
+            return Err("invalid_ranked_meetings");
+        }
+        if self.max_queue_size < 2 {
+            return Err("invalid_queue_size");
+        }
+        Ok(())
+    }
+}
+
+/// Smallest stake the high-stakes queue admits unless its terms are changed
+pub const HIGH_STAKES_MIN_STAKE: Amount = Amount::from_tokens(100);
+
+/// What one queue asks of its entries and how it pairs them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "QueueTermsInput")]
+pub struct QueueTerms {
+    /// Smallest stake the queue admits
+    pub min_stake: Amount,
+    /// Windows, waits and limits the queue pairs by instead of the lobby-wide settings
+    pub matchmaking: Option<MatchmakingConfig>,
+}
+
+impl QueueTerms {
+    /// Terms `mode` has until they are set
+    pub fn default_for(mode: QueueMode) -> Self {
+        let min_stake = match mode {
+            QueueMode::HighStakes => HIGH_STAKES_MIN_STAKE,
+            QueueMode::Casual | QueueMode::Ranked | QueueMode::Teams => Amount::ZERO,
+        };
+        Self { min_stake, matchmaking: None }
+    }
+
+    /// Settings the queue pairs by, given the lobby-wide `shared` ones
+    pub fn matchmaking_or(&self, shared: MatchmakingConfig) -> MatchmakingConfig {
+        self.matchmaking.unwrap_or(shared)
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        self.matchmaking.as_ref().map_or(Ok(()), MatchmakingConfig::validate)
+    }
+}
+
+/// What each player of a matched pair puts up: the smaller of their stakes. The larger
+/// staker's excess stays out of the pot
+pub fn matched_stake(a: Amount, b: Amount) -> Amount {
+    a.min(b)
+}
+
+/// A player's earlier match against `opponent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct Pairing {
+    pub opponent: AccountOwner,
+    pub matched_at: Timestamp,
+}
+
+/// Add a match against `opponent` to `recent`, dropping the oldest past `RECENT_PAIRINGS`
+pub fn record_pairing(recent: &mut Vec<Pairing>, opponent: AccountOwner, matched_at: Timestamp) {
+    recent.push(Pairing { opponent, matched_at });
+    let excess = recent.len().saturating_sub(RECENT_PAIRINGS);
+    recent.drain(..excess);
+}
+
+/// A queued player as matchmaking sees them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seeker {
+    pub rating: u64,
+    pub stake: Amount,
+    pub waited: TimeDelta,
+}
+
+/// Indices of the best pair among `seekers` that `compatible` allows, whose stakes share a band
+/// and whose rating gap is within the wider of the two windows, or `None`. `seekers` come
+/// longest-waiting first, and ties go to the pair found first, so to the longest waiter
+pub fn best_pair(
+    seekers: &[Seeker],
+    config: &MatchmakingConfig,
+    compatible: impl Fn(usize, usize) -> bool,
+) -> Option<(usize, usize)> {
+    let mut best: Option<((usize, usize), i128)> = None;
+    for i in 0..seekers.len() {
+        for j in i + 1..seekers.len() {
+            let (a, b) = (&seekers[i], &seekers[j]);
+            let gap = a.rating.abs_diff(b.rating);
+            if gap > config.window(a.waited).max(config.window(b.waited))
+                || !config.stakes_compatible(a.stake, b.stake)
+                || !compatible(i, j)
+            {
+                continue;
+            }
+            let distance = gap.saturating_add(stake_penalty(a.stake, b.stake, config.stake_weight));
+            let distance = i128::from(distance) - i128::from(config.credit(a.waited.max(b.waited)));
+            if best.is_none_or(|(_, shortest)| distance < shortest) {
+                best = Some(((i, j), distance));
+            }
+        }
+    }
+    best.map(|(pair, _)| pair)
+}
+
+/// Split `groups` of fighters into two sides of `team_size`, keeping each group on one side:
+/// parties go first, largest first, each to the emptier side; solo fighters follow strongest
+/// first, each to the side with room and the lower rating total. Returns indices into
+/// `ratings`, strongest first on each side, or `None` when the groups do not fill both sides
+pub fn draft_teams(groups: &[Vec<usize>], ratings: &[u64], team_size: usize) -> Option<[Vec<usize>; 2]> {
+    let mut sides: [Vec<usize>; 2] = [Vec::new(), Vec::new()];
+    let mut parties: Vec<&Vec<usize>> = groups.iter().filter(|group| group.len() > 1).collect();
+    parties.sort_by_key(|party| std::cmp::Reverse(party.len()));
+    for party in parties {
+        let side = usize::from(sides[1].len() < sides[0].len());
+        if sides[side].len() + party.len() <= team_size {
+            sides[side].extend(party);
+        }
+    }
+
+    let mut solos: Vec<usize> = groups.iter().filter(|group| group.len() == 1).map(|group| group[0]).collect();
+    solos.sort_by(|&a, &b| ratings[b].cmp(&ratings[a]).then(a.cmp(&b)));
+    for solo in solos {
+        let total = |side: &Vec<usize>| side.iter().map(|&index| ratings[index]).sum::<u64>();
+        let open = (0..2).filter(|&side| sides[side].len() < team_size);
+        let Some(side) = open.min_by_key(|&side| (total(&sides[side]), sides[side].len(), side)) else {
+            break;
+        };
+        sides[side].push(solo);
+    }
+    for side in &mut sides {
+        side.sort_by(|&a, &b| ratings[b].cmp(&ratings[a]).then(a.cmp(&b)));
+    }
+    sides.iter().all(|side| side.len() == team_size).then_some(sides)
+}
+
+/// `weight` scaled by how far the smaller stake falls short of the larger
+fn stake_penalty(a: Amount, b: Amount, weight: u64) -> u64 {
+    let (low, high) = (u128::from(a.min(b)), u128::from(a.max(b)));
+    if high == 0 {
+        return 0;
+    }
+    // Halving the stake costs the full weight: 1 - low/high, doubled, capped at 1
+    let shortfall_bps = ((high - low) * 20_000 / high).min(10_000);
+    (shortfall_bps * weight as u128 / 10_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{AccountOwner, Amount, CryptoHash, TimeDelta, Timestamp};
+
+    use super::{best_pair, draft_teams, matched_stake, record_pairing, MatchmakingConfig, Seeker, RECENT_PAIRINGS};
+
+    fn seeker(rating: u64, tokens: u128, waited_secs: u64) -> Seeker {
+        Seeker { rating, stake: Amount::from_tokens(tokens), waited: TimeDelta::from_secs(waited_secs) }
+    }
+
+    #[test]
+    fn windows_widen_with_waiting_up_to_the_cap() {
+        let config = MatchmakingConfig::default();
+        assert_eq!([0, 9, 10, 35, 10_000].map(|secs| config.window(TimeDelta::from_secs(secs))), [100, 100, 150, 250, 800]);
+    }
+
+    #[test]
+    fn pairs_need_a_wide_enough_window_and_prefer_close_ratings_and_stakes() {
+        let config = MatchmakingConfig::default();
+        let anyone = |_, _| true;
+        assert_eq!(best_pair(&[seeker(1200, 1, 0), seeker(1400, 1, 0)], &config, anyone), None);
+        // Waiting 20 seconds opens the first player's window to 200
+        assert_eq!(best_pair(&[seeker(1200, 1, 20), seeker(1400, 1, 0)], &config, anyone), Some((0, 1)));
+
+        let queue = [seeker(1200, 1, 0), seeker(1290, 1, 0), seeker(1210, 4, 0), seeker(1250, 1, 0)];
+        assert_eq!(best_pair(&queue, &config, anyone), Some((1, 3)));
+        assert_eq!(best_pair(&queue, &config, |i, j| i != 1 && j != 1), Some((0, 3)));
+        // Without the stake penalty and band the 10-point gap would win
+        let stakeless = MatchmakingConfig { stake_weight: 0, stake_tolerance_bps: 10_000, ..config };
+        assert_eq!(best_pair(&queue, &stakeless, anyone), Some((0, 2)));
+    }
+
+    #[test]
+    fn long_waits_outrank_fresher_closer_pairs() {
+        let config = MatchmakingConfig::default();
+        let anyone = |_, _| true;
+        // A minute in, the oldest entry is forgiven 150 points: its 100-point gap beats a fresh 10
+        let queue = [seeker(1200, 1, 60), seeker(1300, 1, 0), seeker(1400, 1, 0), seeker(1410, 1, 0)];
+        assert_eq!(config.credit(TimeDelta::from_secs(60)), 150);
+        assert_eq!(best_pair(&queue, &config, anyone), Some((0, 1)));
+        assert_eq!(best_pair(&queue, &MatchmakingConfig { wait_credit: 0, ..config }, anyone), Some((2, 3)));
+
+        // Equal pairs go to the entries queued first
+        let queue = [seeker(1500, 1, 30), seeker(1500, 1, 30), seeker(1500, 1, 30), seeker(1500, 1, 30)];
+        assert_eq!(best_pair(&queue, &config, anyone), Some((0, 1)));
+        assert_eq!(MatchmakingConfig { max_queue_size: 1, ..config }.validate(), Err("invalid_queue_size"));
+    }
+
+    #[test]
+    fn stakes_outside_the_band_are_never_paired() {
+        let config = MatchmakingConfig { stake_tolerance_bps: 2_500, ..MatchmakingConfig::default() };
+        assert!(config.stakes_compatible(Amount::from_tokens(4), Amount::from_tokens(3)));
+        assert!(!config.stakes_compatible(Amount::from_tokens(4), Amount::from_millis(2_999)));
+        assert!(config.stakes_compatible(Amount::MAX, Amount::MAX));
+        assert_eq!(best_pair(&[seeker(1200, 4, 0), seeker(1200, 2, 0)], &config, |_, _| true), None);
+
+        let exact = MatchmakingConfig { stake_tolerance_bps: 0, ..config };
+        assert!(!exact.stakes_compatible(Amount::from_tokens(2), Amount::from_attos(1_999_999_999_999_999_999)));
+        assert_eq!(matched_stake(Amount::from_tokens(4), Amount::from_tokens(3)), Amount::from_tokens(3));
+    }
+
+    #[test]
+    fn ranked_pairs_meet_only_so_often_within_the_window() {
+        let config = MatchmakingConfig::default();
+        let (bob, carol) = (AccountOwner::from(CryptoHash::test_hash("bob")), AccountOwner::from(CryptoHash::test_hash("carol")));
+        let at = |secs| Timestamp::from(secs * 1_000_000);
+        let mut recent = Vec::new();
+        record_pairing(&mut recent, bob, at(0));
+        assert!(config.ranked_rematch_allowed(&recent, bob, at(60)));
+        record_pairing(&mut recent, bob, at(60));
+        assert!(!config.ranked_rematch_allowed(&recent, bob, at(120)));
+        assert!(config.ranked_rematch_allowed(&recent, carol, at(120)));
+        // The first meeting falls out of the window an hour on
+        assert!(config.ranked_rematch_allowed(&recent, bob, at(3_600)));
+
+        for secs in 0..RECENT_PAIRINGS as u64 {
+            record_pairing(&mut recent, carol, at(secs));
+        }
+        assert_eq!(recent.len(), RECENT_PAIRINGS);
+        assert!(config.ranked_rematch_allowed(&recent, bob, at(120)));
+        assert_eq!(MatchmakingConfig { max_ranked_meetings: 0, ..config }.validate(), Err("invalid_ranked_meetings"));
+    }
+
+    #[test]
+    fn team_drafts_balance_solos_and_keep_parties_together() {
+        let solos = |count: usize| (0..count).map(|index| vec![index]).collect::<Vec<_>>();
+        assert_eq!(draft_teams(&solos(4), &[1_200, 1_500, 1_100, 1_400], 2), Some([vec![1, 2], vec![3, 0]]));
+        // Equal ratings keep queue order
+        assert_eq!(draft_teams(&solos(4), &[1_000; 4], 2), Some([vec![0, 2], vec![1, 3]]));
+        assert_eq!(draft_teams(&solos(6), &[900, 1_000, 1_100, 1_200, 1_300, 1_400], 3), Some([vec![5, 2, 1], vec![4, 3, 0]]));
+
+        // The two strongest queued as a party, so the solos face them together
+        let ratings = [1_500, 1_100, 1_400, 1_000];
+        assert_eq!(draft_teams(&[vec![0, 2], vec![1], vec![3]], &ratings, 2), Some([vec![0, 2], vec![1, 3]]));
+        assert_eq!(draft_teams(&[vec![0, 1], vec![2, 3]], &ratings, 2), Some([vec![0, 1], vec![2, 3]]));
+        // A party too large for a side is left out
+        assert_eq!(draft_teams(&[vec![0, 1, 2], vec![3]], &ratings, 2), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! State migrations.
+//!
+//! Stored values are BCS, which records no field names: once a struct gains, loses or renames a
+//! field, what an older build wrote no longer decodes as the struct. A change like that bumps the
+//! contract's schema version and keeps the previous shape as a legacy type implementing
+//! [`Upgrade`]. Chains written at an older version are rewritten in place when they load, one
+//! version at a time, before anything reads the changed values.
+
+use std::ops::Range;
+
+use linera_sdk::{
+    bcs,
+    views::{linera_views::ViewError, MapView, View},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A stored shape from an older schema version
+pub trait Upgrade: Clone + Serialize + DeserializeOwned + Send + Sync {
+    /// The shape the following version stores
+    type Next: Clone + Serialize + DeserializeOwned + Send + Sync;
+
+    /// Default the fields the next version added and carry renamed ones over
+    fn upgrade(self) -> Self::Next;
+}
+
+/// Versions a chain written at `from` still steps out of to reach `to`
+pub fn pending(from: u32, to: u32) -> Range<u32> {
+    from..to.max(from)
+}
+
+/// Decode a value an older version wrote as `L` and upgrade it
+pub fn upgrade_bytes<L: Upgrade>(bytes: &[u8]) -> Result<L::Next, bcs::Error> {
+    bcs::from_bytes::<L>(bytes).map(Upgrade::upgrade)
+}
+
+/// Rewrite every value in `map`, stored as `L` by the previous version, in the next shape,
+/// returning how many were rewritten
+pub async fn upgrade_map<I, L>(map: &mut MapView<I, L::Next>) -> Result<usize, ViewError>
+where
+    I: Serialize + DeserializeOwned + Send + Sync + 'static,
+    L: Upgrade + 'static,
+{
+    // Same keys, decoded the way the previous version wrote them
+    let legacy = MapView::<I, L>::load(map.context().clone()).await?;
+    let indices = legacy.indices().await?;
+    for index in &indices {
+        if let Some(value) = legacy.get(index).await? {
+            map.insert(index, value.upgrade())?;
+        }
+    }
+    Ok(indices.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::bcs;
+    use serde::{Deserialize, Serialize};
+
+    use super::{pending, upgrade_bytes, Upgrade};
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Before {
+        wins: u64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct After {
+        wins: u64,
+        draws: u64,
+    }
+
+    impl Upgrade for Before {
+        type Next = After;
+
+        fn upgrade(self) -> After {
+            After { wins: self.wins, draws: 0 }
+        }
+    }
+
+    #[test]
+    fn older_values_decode_through_their_legacy_shape() {
+        let bytes = bcs::to_bytes(&Before { wins: 7 }).unwrap();
+        assert!(bcs::from_bytes::<After>(&bytes).is_err());
+        assert_eq!(upgrade_bytes::<Before>(&bytes).unwrap(), After { wins: 7, draws: 0 });
+
+        assert_eq!(pending(1, 3), 1..3);
+        assert!(pending(3, 3).is_empty());
+        assert!(pending(4, 3).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+//! Character minting.
+//!
+//! A player chain's first mints are free; later ones cost battle tokens, paid to the lobby
+//! treasury. Mints are spaced by a cooldown, rosters are capped, and every character is born
+//! with a rarity whose trait bonuses stack with what its equipment grants. Equipment can be
+//! forged for a flat cost, besides dropping from won battles.
+
+use async_graphql::{InputObject, SimpleObject};
+use linera_sdk::linera_base_types::{Amount, ChainId, TimeDelta, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{fnv1a, splitmix64, ItemRarity, PassiveMods};
+
+/// What minting a character takes on a player chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "MintTermsInput")]
+pub struct MintTerms {
+    /// Battle tokens each mint past the free ones costs
+    pub cost: Amount,
+    /// Mints every chain gets without paying
+    pub free_mints: u32,
+    /// Most characters a chain may hold, minted or received
+    pub roster_cap: u32,
+    /// Least time between two mints on a chain
+    pub cooldown_secs: u64,
+    /// Battle tokens forging an item costs; items are not capped or paced
+    pub item_cost: Amount,
+}
+
+impl Default for MintTerms {
+    fn default() -> Self {
+        Self { cost: Amount::from_tokens(10), free_mints: 1, roster_cap: 10, cooldown_secs: 3600, item_cost: Amount::from_tokens(5) }
+    }
+}
+
+impl MintTerms {
+    /// Cost of the next mint on a chain that minted `minted` characters, holds `roster`, and
+    /// last minted at `last_mint`; or why it may not mint now
+    pub fn check(&self, minted: u64, roster: usize, last_mint: Option<Timestamp>, now: Timestamp) -> Result<Amount, &'static str> {
+        if roster as u64 >= self.roster_cap as u64 {
+            return Err("roster_full");
+        }
+        if last_mint.is_some_and(|last| now < last.saturating_add(TimeDelta::from_secs(self.cooldown_secs))) {
+            return Err("mint_cooldown");
+        }
+        Ok(if minted < self.free_mints as u64 { Amount::ZERO } else { self.cost })
+    }
+}
+
+/// Largest trait bonus a character of `rarity` is born with, per stat
+pub fn max_trait_bps(rarity: ItemRarity) -> i16 {
+    match rarity {
+        ItemRarity::Common => 100,
+        ItemRarity::Rare => 250,
+        ItemRarity::Epic => 500,
+    }
+}
+
+/// Rarity and trait bonuses a character is minted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintTraits {
+    pub rarity: ItemRarity,
+    pub bonuses: PassiveMods,
+}
+
+impl MintTraits {
+    /// Roll the traits of `character_id` minted on `player_chain`; every input yields the same
+    /// result on replay
+    pub fn roll(player_chain: ChainId, character_id: &str, entropy: u64) -> Self {
+        let mut seed = fnv1a(player_chain.to_string().as_bytes()) ^ fnv1a(character_id.as_bytes()).rotate_left(32) ^ entropy;
+        let mut next = || splitmix64(&mut seed);
+
+        let rarity = ItemRarity::from_roll(next() % 10_000);
+        let range = max_trait_bps(rarity) as u64 + 1;
+        let mut trait_bps = || (next() % range) as i16;
+        let bonuses = PassiveMods { attack_bps: trait_bps(), defense_bps: trait_bps(), crit_bps: trait_bps() };
+        Self { rarity, bonuses }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{Amount, ChainId, CryptoHash, Timestamp};
+
+    use super::{max_trait_bps, MintTerms, MintTraits};
+
+    #[test]
+    fn mints_past_the_free_ones_cost_and_wait_out_the_cooldown() {
+        let terms = MintTerms { cost: Amount::from_tokens(5), free_mints: 1, roster_cap: 2, cooldown_secs: 60, item_cost: Amount::ZERO };
+        let minted_at = Some(Timestamp::from(0));
+
+        assert_eq!(terms.check(0, 0, None, Timestamp::from(0)), Ok(Amount::ZERO));
+        assert_eq!(terms.check(1, 1, minted_at, Timestamp::from(59_999_999)), Err("mint_cooldown"));
+        assert_eq!(terms.check(1, 1, minted_at, Timestamp::from(60_000_000)), Ok(Amount::from_tokens(5)));
+        assert_eq!(terms.check(2, 2, minted_at, Timestamp::from(60_000_000)), Err("roster_full"));
+    }
+
+    #[test]
+    fn traits_stay_within_their_rarity_and_replay_identically() {
+        let chain = ChainId(CryptoHash::test_hash("player"));
+        for index in 0..200u64 {
+            let character_id = format!("hero-{index}");
+            let traits = MintTraits::roll(chain, &character_id, index);
+            let max = max_trait_bps(traits.rarity);
+            let bonuses = [traits.bonuses.attack_bps, traits.bonuses.defense_bps, traits.bonuses.crit_bps];
+            assert!(bonuses.iter().all(|bps| (0..=max).contains(bps)));
+            assert_eq!(MintTraits::roll(chain, &character_id, index), traits);
+        }
+    }
+}
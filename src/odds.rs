@@ -0,0 +1,81 @@
+//! Market odds.
+//!
+//! Odds are decimal and in basis points: what a winning bet returns per unit staked, stake
+//! included, so 20 000 doubles the stake. A pari-mutuel market quotes each side the pool after
+//! the platform fee over that side's stakes; every bet locks the quote its own stake makes.
+//! Markets priced at fixed odds pay winners what their locked odds promised, scaled down only
+//! when the pool cannot cover every promise.
+
+use async_graphql::Enum;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::fees::FeeBreakdown;
+
+/// Odds that return exactly the stake
+pub const EVEN_ODDS: u64 = 10_000;
+
+/// How a settled market pays its winners
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum MarketPricing {
+    /// Winners share the final pool by stake
+    #[default]
+    PariMutuel,
+    /// Winners are paid at the odds locked when they bet
+    FixedOdds,
+}
+
+/// Odds a side with `side_pool` staked pays out of `total_pool`, net of `platform_fee_bps` on
+/// the losing side, or `None` while nobody backs the side
+pub fn implied_odds(side_pool: Amount, total_pool: Amount, platform_fee_bps: u16) -> Option<u64> {
+    if side_pool == Amount::ZERO {
+        return None;
+    }
+    let losing_pool = total_pool.saturating_sub(side_pool);
+    let fee = FeeBreakdown::compute(losing_pool, platform_fee_bps).platform_fee;
+    let returned = u128::from(total_pool.saturating_sub(fee));
+    let odds = returned.saturating_mul(EVEN_ODDS as u128) / u128::from(side_pool);
+    Some(u64::try_from(odds).unwrap_or(u64::MAX))
+}
+
+/// Odds of a bet grown from `staked` at `odds` by `added` at `added_odds`, weighted by stake
+pub fn blend(staked: Amount, odds: u64, added: Amount, added_odds: u64) -> u64 {
+    let total = u128::from(staked) + u128::from(added);
+    if total == 0 {
+        return added_odds;
+    }
+    let weighted = u128::from(staked) * odds as u128 + u128::from(added) * added_odds as u128;
+    (weighted / total) as u64
+}
+
+/// What `amount` staked at `odds` returns on a win
+pub fn locked_payout(amount: Amount, odds: u64) -> Amount {
+    Amount::from_attos(u128::from(amount).saturating_mul(odds as u128) / EVEN_ODDS as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::{blend, implied_odds, locked_payout, EVEN_ODDS};
+
+    #[test]
+    fn sides_are_quoted_the_pool_after_fees_over_their_stakes() {
+        let tokens = Amount::from_tokens;
+        assert_eq!(implied_odds(tokens(1), tokens(4), 0), Some(40_000));
+        assert_eq!(implied_odds(tokens(3), tokens(4), 0), Some(13_333));
+        // A 10% fee on bob's 3 tokens leaves alice's backers 3.7
+        assert_eq!(implied_odds(tokens(1), tokens(4), 1_000), Some(37_000));
+        assert_eq!(implied_odds(tokens(2), tokens(2), 1_000), Some(EVEN_ODDS));
+        assert_eq!(implied_odds(Amount::ZERO, tokens(2), 0), None);
+    }
+
+    #[test]
+    fn topped_up_bets_blend_their_odds_by_stake() {
+        let tokens = Amount::from_tokens;
+        assert_eq!(blend(Amount::ZERO, 0, tokens(2), 30_000), 30_000);
+        assert_eq!(blend(tokens(1), 40_000, tokens(3), 20_000), 25_000);
+        assert_eq!(locked_payout(tokens(4), 25_000), tokens(10));
+        assert_eq!(locked_payout(Amount::from_millis(1_500), EVEN_ODDS), Amount::from_millis(1_500));
+    }
+}
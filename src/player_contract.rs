@@ -1,10 +1,11 @@
 use linera_sdk::{
-    linera_base_types::Amount,
+    linera_base_types::{AccountOwner, Amount, ChainId},
+    views::View,
     ContractRuntime,
 };
 
-use majorules::{Operation, Message, CharacterSnapshot, CharacterClass};
-use crate::state::PlayerState;
+use majorules::{counters::{self, checked_accumulate}, leveling::{self, LevelGains}, minting::MintTraits, quests, throttle::RejectionKey, time, BattleEndReason, CombatStats, Operation, OperationResponse, Message, QueueMode, CharacterSnapshot, CharacterClass, ItemDrop, ItemSlot, PassiveMods, Party, PartyMember, MAX_FRIENDS, MAX_PARTY_SIZE, STARTING_LIVES, TEAM_SIZE};
+use crate::state::{record_overflow, record_rejection, CharacterData, EngagementKind, ItemData, PlayerState, QuestProgress};
 
 pub struct PlayerContract;
 
@@ -13,123 +14,152 @@ impl PlayerContract {
         state: &mut PlayerState,
         runtime: &mut ContractRuntime<crate::MajorulesContract>,
         operation: Operation,
-    ) {
-        let caller = runtime.authenticated_signer()
-            .expect("Operation must be authenticated");
+    ) -> OperationResponse {
+        let Some(caller) = runtime.authenticated_signer() else {
+            return OperationResponse::rejected("unauthenticated");
+        };
 
         match operation {
-            Operation::JoinQueue { character_id, stake } => {
-                // Get character data and send to lobby
-                if let Ok(Some(character)) = state.characters.get(&character_id).await {
-                    let lobby_chain_id = state.lobby_chain_id.get().unwrap();
-                    let player_chain_id = runtime.chain_id();
-                    
-                    runtime.prepare_message(Message::RequestJoinQueue {
-                        player: caller,
-                        player_chain: player_chain_id,
-                        character_snapshot: CharacterSnapshot {
-                            nft_id: character.nft_id,
-                            class: match character.class {
-                                crate::state::CharacterClass::Warrior => CharacterClass::Warrior,
-                                crate::state::CharacterClass::Mage => CharacterClass::Mage,
-                                _ => CharacterClass::Warrior,
-                            },
-                            level: character.level,
-                            hp_max: character.hp_max,
-                            min_damage: character.min_damage,
-                            max_damage: character.max_damage,
-                            crit_chance: character.crit_chance,
-                            crit_multiplier: character.crit_multiplier,
-                            dodge_chance: character.dodge_chance,
-                            defense: character.defense,
-                            attack_bps: character.attack_bps,
-                            defense_bps: character.defense_bps,
-                            crit_bps: character.crit_bps,
-                        },
-                        stake,
-                    }).with_authentication().send_to(lobby_chain_id);
+            Operation::JoinQueue { character_id, stake, mode, best_of } => {
+                // The team queue keeps a party on one side, so it must fit on one
+                let party = match (mode, state.party.get()) {
+                    (QueueMode::Teams, Some(party)) if party.members.len() > TEAM_SIZE => {
+                        return Self::reject(state, runtime, "JoinQueue", "party_too_large", caller).await;
+                    }
+                    (QueueMode::Teams, Some(party)) => Some(party.leader),
+                    _ => None,
+                };
+                let Ok(balance) = state.battle_token_balance.get().try_sub(stake) else {
+                    return Self::reject(state, runtime, "JoinQueue", "insufficient_balance", caller).await;
+                };
+                if let Err(reason) = state.progression_check(&character_id, mode, stake).await {
+                    return Self::reject(state, runtime, "JoinQueue", &reason, caller).await;
                 }
+                let (lobby_chain_id, character_snapshot) = match Self::enter_lobby(state, runtime, caller, "JoinQueue", &character_id, mode).await {
+                    Ok(entered) => entered,
+                    Err(refused) => return refused,
+                };
+                // Locked until the lobby matches the entry or releases it
+                state.battle_token_balance.set(balance);
+                state.locked_stakes.insert(&character_id, stake)
+                    .expect("Failed to lock stake");
+                let player_chain = runtime.chain_id();
+                runtime.prepare_message(Message::RequestJoinQueue {
+                    player: caller,
+                    player_chain,
+                    character_snapshot,
+                    stake,
+                    mode,
+                    best_of,
+                    party,
+                }).with_authentication().send_to(lobby_chain_id);
             }
 
-            Operation::CreatePrivateBattle { character_id, stake } => {
-                // Get character data and send to lobby
-                if let Ok(Some(character)) = state.characters.get(&character_id).await {
-                    let lobby_chain_id = state.lobby_chain_id.get().unwrap();
-                    let player_chain_id = runtime.chain_id();
-                    
-                    runtime.prepare_message(Message::RequestCreatePrivateBattle {
-                        player: caller,
-                        player_chain: player_chain_id,
-                        character_snapshot: CharacterSnapshot {
-                            nft_id: character.nft_id,
-                            class: match character.class {
-                                crate::state::CharacterClass::Warrior => CharacterClass::Warrior,
-                                crate::state::CharacterClass::Mage => CharacterClass::Mage,
-                                _ => CharacterClass::Warrior,
-                            },
-                            level: character.level,
-                            hp_max: character.hp_max,
-                            min_damage: character.min_damage,
-                            max_damage: character.max_damage,
-                            crit_chance: character.crit_chance,
-                            crit_multiplier: character.crit_multiplier,
-                            dodge_chance: character.dodge_chance,
-                            defense: character.defense,
-                            attack_bps: character.attack_bps,
-                            defense_bps: character.defense_bps,
-                            crit_bps: character.crit_bps,
-                        },
-                        stake,
-                    }).with_authentication().send_to(lobby_chain_id);
-                }
+            Operation::CreatePrivateBattle { character_id, stake, invite } => {
+                let (lobby_chain_id, character_snapshot) = match Self::enter_lobby(state, runtime, caller, "CreatePrivateBattle", &character_id, QueueMode::Casual).await {
+                    Ok(entered) => entered,
+                    Err(refused) => return refused,
+                };
+                let player_chain = runtime.chain_id();
+                runtime.prepare_message(Message::RequestCreatePrivateBattle {
+                    player: caller,
+                    player_chain,
+                    character_snapshot,
+                    stake,
+                    invite,
+                }).with_authentication().send_to(lobby_chain_id);
             }
 
-            Operation::JoinPrivateBattle { battle_id, character_id, stake } => {
-                // Get character data and send to lobby
-                if let Ok(Some(character)) = state.characters.get(&character_id).await {
-                    let lobby_chain_id = state.lobby_chain_id.get().unwrap();
-                    let player_chain_id = runtime.chain_id();
-                    
-                    runtime.prepare_message(Message::RequestJoinPrivateBattle {
-                        player: caller,
-                        player_chain: player_chain_id,
-                        battle_id,
-                        character_snapshot: CharacterSnapshot {
-                            nft_id: character.nft_id,
-                            class: match character.class {
-                                crate::state::CharacterClass::Warrior => CharacterClass::Warrior,
-                                crate::state::CharacterClass::Mage => CharacterClass::Mage,
-                                _ => CharacterClass::Warrior,
-                            },
-                            level: character.level,
-                            hp_max: character.hp_max,
-                            min_damage: character.min_damage,
-                            max_damage: character.max_damage,
-                            crit_chance: character.crit_chance,
-                            crit_multiplier: character.crit_multiplier,
-                            dodge_chance: character.dodge_chance,
-                            defense: character.defense,
-                            attack_bps: character.attack_bps,
-                            defense_bps: character.defense_bps,
-                            crit_bps: character.crit_bps,
-                        },
-                        stake,
-                    }).with_authentication().send_to(lobby_chain_id);
-                }
+            Operation::JoinPrivateBattle { battle_id, invite_secret, character_id, stake } => {
+                let (lobby_chain_id, character_snapshot) = match Self::enter_lobby(state, runtime, caller, "JoinPrivateBattle", &character_id, QueueMode::Casual).await {
+                    Ok(entered) => entered,
+                    Err(refused) => return refused,
+                };
+                let player_chain = runtime.chain_id();
+                runtime.prepare_message(Message::RequestJoinPrivateBattle {
+                    player: caller,
+                    player_chain,
+                    battle_id,
+                    invite_secret,
+                    character_snapshot,
+                    stake,
+                }).with_authentication().send_to(lobby_chain_id);
+            }
+
+            Operation::StartPracticeBattle { character_id, difficulty } => {
+                let (lobby_chain_id, character_snapshot) = match Self::enter_lobby(state, runtime, caller, "StartPracticeBattle", &character_id, QueueMode::Casual).await {
+                    Ok(entered) => entered,
+                    Err(refused) => return refused,
+                };
+                let player_chain = runtime.chain_id();
+                runtime.prepare_message(Message::RequestPracticeBattle {
+                    player: caller,
+                    player_chain,
+                    character_snapshot,
+                    difficulty,
+                }).with_authentication().send_to(lobby_chain_id);
+            }
+
+            Operation::JoinTournament { tournament_id, character_id, entry_fee } => {
+                let Ok(balance) = state.battle_token_balance.get().try_sub(entry_fee) else {
+                    return Self::reject(state, runtime, "JoinTournament", "insufficient_balance", caller).await;
+                };
+                let (lobby_chain_id, character_snapshot) = match Self::enter_lobby(state, runtime, caller, "JoinTournament", &character_id, QueueMode::Casual).await {
+                    Ok(entered) => entered,
+                    Err(refused) => return refused,
+                };
+                state.battle_token_balance.set(balance);
+                let player_chain = runtime.chain_id();
+                let party = state.party.get().as_ref().map(|party| party.leader);
+                runtime.prepare_message(Message::RequestJoinTournament {
+                    player: caller,
+                    player_chain,
+                    tournament_id,
+                    character_snapshot,
+                    entry_fee,
+                    party,
+                }).with_authentication().send_to(lobby_chain_id);
             }
 
             Operation::MintCharacter { character_id, class } => {
-                let character_class = CharacterClass::from_str(&class).unwrap_or(CharacterClass::Warrior);
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "MintCharacter", "not_owner", caller).await;
+                }
+                if !majorules::valid_character_id(&character_id) {
+                    return Self::reject(state, runtime, "MintCharacter", "invalid_character_id", caller).await;
+                }
+                if state.characters.contains_key(&character_id).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "MintCharacter", "character_exists", caller).await;
+                }
+                let now = runtime.system_time();
+                let roster = state.characters.count().await.expect("Failed to count characters");
+                let cost = match state.mint_terms.get().check(*state.character_count.get(), roster, *state.last_mint_at.get(), now) {
+                    Ok(cost) => cost,
+                    Err(reason) => return Self::reject(state, runtime, "MintCharacter", reason, caller).await,
+                };
+                let Ok(balance) = state.battle_token_balance.get().try_sub(cost) else {
+                    return Self::reject(state, runtime, "MintCharacter", "insufficient_balance", caller).await;
+                };
+                if cost > Amount::ZERO {
+                    let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                        return Self::reject(state, runtime, "MintCharacter", "no_lobby", caller).await;
+                    };
+                    state.battle_token_balance.set(balance);
+                    runtime.prepare_message(Message::MintFeePaid { player: caller, amount: cost })
+                        .with_authentication()
+                        .send_to(lobby_chain_id);
+                }
+                state.character_count.set(state.character_count.get().saturating_add(1));
+                state.last_mint_at.set(Some(now));
+
+                let character_class = class.parse().unwrap_or(CharacterClass::Warrior);
                 let (hp_max, min_damage, max_damage, crit_chance) = character_class.base_stats();
+                let traits = MintTraits::roll(runtime.chain_id(), &character_id, now.micros());
                 
                 let character = crate::state::CharacterData {
                     nft_id: character_id.clone(),
                     owner: caller,
-                    class: match character_class {
-                        CharacterClass::Warrior => crate::state::CharacterClass::Warrior,
-                        CharacterClass::Mage => crate::state::CharacterClass::Mage,
-                        _ => crate::state::CharacterClass::Warrior,
-                    },
+                    class: character_class.into(),
                     level: 1,
                     xp: 0,
                     hp_max,
@@ -137,17 +167,55 @@ impl PlayerContract {
                     max_damage,
                     crit_chance,
                     crit_multiplier: 1500,
-                    dodge_chance: 500,
+                    dodge_chance: majorules::BASE_DODGE_CHANCE,
                     defense: 5,
-                    attack_bps: 0,
-                    defense_bps: 0,
-                    crit_bps: 0,
-                    created_at: runtime.system_time(),
+                    attack_bps: traits.bonuses.attack_bps,
+                    defense_bps: traits.bonuses.defense_bps,
+                    crit_bps: traits.bonuses.crit_bps,
+                    rarity: traits.rarity,
+                    lives_remaining: STARTING_LIVES,
+                    created_at: now,
                     is_active: false,
                 };
 
-                state.characters.insert(&character_id, character)
+                state.characters.insert(&character_id, character.clone())
                     .expect("Failed to mint character");
+                if let Some(lobby_chain_id) = *state.lobby_chain_id.get() {
+                    let snapshot = Self::snapshot(state, character).await;
+                    runtime.prepare_message(Message::CharacterMinted { player: caller, snapshot })
+                        .with_authentication()
+                        .send_to(lobby_chain_id);
+                }
+            }
+
+            Operation::ReviveCharacter { character_id } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "ReviveCharacter", "not_owner", caller).await;
+                }
+                let Ok(Some(mut character)) = state.characters.get(&character_id).await else {
+                    return Self::reject(state, runtime, "ReviveCharacter", "unknown_character", caller).await;
+                };
+                if character.is_alive() {
+                    return Self::reject(state, runtime, "ReviveCharacter", "character_alive", caller).await;
+                }
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return Self::reject(state, runtime, "ReviveCharacter", "no_lobby", caller).await;
+                };
+                let revive_fee = state.mint_terms.get().cost;
+                let Ok(balance) = state.battle_token_balance.get().try_sub(revive_fee) else {
+                    return Self::reject(state, runtime, "ReviveCharacter", "insufficient_balance", caller).await;
+                };
+
+                state.battle_token_balance.set(balance);
+                character.lives_remaining = STARTING_LIVES;
+                state.characters.insert(&character_id, character)
+                    .expect("Failed to revive character");
+                runtime.prepare_message(Message::CharacterLivesChanged {
+                    player: caller,
+                    character_id,
+                    lives_remaining: STARTING_LIVES,
+                    revive_fee,
+                }).with_authentication().send_to(lobby_chain_id);
             }
 
             Operation::SetActiveCharacter { character_id } => {
@@ -159,10 +227,425 @@ impl PlayerContract {
                 }
             }
 
+            Operation::MintItem { item_id, slot } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "MintItem", "not_owner", caller).await;
+                }
+                if state.items.contains_key(&item_id).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "MintItem", "item_exists", caller).await;
+                }
+                let cost = state.mint_terms.get().item_cost;
+                let Ok(balance) = state.battle_token_balance.get().try_sub(cost) else {
+                    return Self::reject(state, runtime, "MintItem", "insufficient_balance", caller).await;
+                };
+                if cost > Amount::ZERO {
+                    let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                        return Self::reject(state, runtime, "MintItem", "no_lobby", caller).await;
+                    };
+                    state.battle_token_balance.set(balance);
+                    runtime.prepare_message(Message::MintFeePaid { player: caller, amount: cost })
+                        .with_authentication()
+                        .send_to(lobby_chain_id);
+                }
+
+                let now = runtime.system_time();
+                let forged = ItemDrop::forge(runtime.chain_id(), &item_id, slot, now.micros());
+                state.items.insert(&item_id, ItemData {
+                    item_id: forged.item_id,
+                    slot,
+                    bonuses: forged.bonuses,
+                    rarity: forged.rarity,
+                    acquired_at: now,
+                    equipped_on: None,
+                }).expect("Failed to forge item");
+            }
+
+            Operation::BuyStreakInsurance { max_price } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "BuyStreakInsurance", "not_owner", caller).await;
+                }
+                let Ok(balance) = state.battle_token_balance.get().try_sub(max_price) else {
+                    return Self::reject(state, runtime, "BuyStreakInsurance", "insufficient_balance", caller).await;
+                };
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return Self::reject(state, runtime, "BuyStreakInsurance", "no_lobby", caller).await;
+                };
+                // The lobby keeps the price and credits back what is left of `max_price`
+                state.battle_token_balance.set(balance);
+                runtime.prepare_message(Message::StreakInsurancePaid { player: caller, paid: max_price })
+                    .with_authentication()
+                    .send_to(lobby_chain_id);
+            }
+
+            Operation::ClaimQuestReward { quest_id, character_id } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "not_owner", caller).await;
+                }
+                let Some(quest) = quests::daily_quest(&quest_id) else {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "unknown_quest", caller).await;
+                };
+                let day = Self::roll_quest_day(state, runtime);
+                let mut progress = state.quest_progress.get(&quest_id).await
+                    .expect("Failed to read quest progress")
+                    .unwrap_or_default();
+                if progress.claimed {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "quest_claimed", caller).await;
+                }
+                if progress.progress < quest.target {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "quest_incomplete", caller).await;
+                }
+                let Ok(Some(mut character)) = state.characters.get(&character_id).await else {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "unknown_character", caller).await;
+                };
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return Self::reject(state, runtime, "ClaimQuestReward", "no_lobby", caller).await;
+                };
+
+                progress.claimed = true;
+                state.quest_progress.insert(&quest_id, progress)
+                    .expect("Failed to claim quest");
+                if checked_accumulate(&mut character.xp, quest.reward_xp) {
+                    record_overflow(&mut state.counter_overflows, counters::CHARACTER_XP, runtime.system_time()).await;
+                }
+                state.characters.insert(&character_id, character)
+                    .expect("Failed to award quest XP");
+                // Tokens come back as `CreditTokens` once the lobby has minted them
+                runtime.prepare_message(Message::QuestRewardClaimed { player: caller, day, quest_id })
+                    .with_authentication()
+                    .send_to(lobby_chain_id);
+            }
+
+            Operation::EquipItem { character_id, item_id } => {
+                let Ok(Some(mut item)) = state.items.get(&item_id).await else {
+                    return Self::reject(state, runtime, "EquipItem", "unknown_item", caller).await;
+                };
+                if !matches!(state.characters.get(&character_id).await, Ok(Some(character)) if character.owner == caller) {
+                    return Self::reject(state, runtime, "EquipItem", "unknown_character", caller).await;
+                }
+                if item.equipped_on.is_some() {
+                    return Self::reject(state, runtime, "EquipItem", "item_already_equipped", caller).await;
+                }
+                let slot_key = (character_id.clone(), item.slot);
+                if state.equipment.contains_key(&slot_key).await.unwrap_or(false) {
+                    return Self::reject(state, runtime, "EquipItem", "slot_occupied", caller).await;
+                }
+
+                state.equipment.insert(&slot_key, item_id.clone())
+                    .expect("Failed to equip item");
+                item.equipped_on = Some(character_id);
+                state.items.insert(&item_id, item)
+                    .expect("Failed to lock item to character");
+            }
+
+            Operation::UnequipItem { item_id } => {
+                let Ok(Some(mut item)) = state.items.get(&item_id).await else {
+                    return Self::reject(state, runtime, "UnequipItem", "unknown_item", caller).await;
+                };
+                let Some(character_id) = item.equipped_on.take() else {
+                    return Self::reject(state, runtime, "UnequipItem", "item_not_equipped", caller).await;
+                };
+                if !matches!(state.characters.get(&character_id).await, Ok(Some(character)) if character.owner == caller) {
+                    return Self::reject(state, runtime, "UnequipItem", "unknown_character", caller).await;
+                }
+
+                state.equipment.remove(&(character_id, item.slot))
+                    .expect("Failed to unequip item");
+                state.items.insert(&item_id, item)
+                    .expect("Failed to release item");
+            }
+
+            Operation::LevelUpCharacter { character_id, xp_to_spend } => {
+                let Ok(Some(mut character)) = state.characters.get(&character_id).await else {
+                    return Self::reject(state, runtime, "LevelUpCharacter", "unknown_character", caller).await;
+                };
+                if character.owner != caller {
+                    return Self::reject(state, runtime, "LevelUpCharacter", "unknown_character", caller).await;
+                }
+                let budget = xp_to_spend.min(character.xp);
+                let (levels, spent) = match leveling::levels_bought(character.level, budget, *state.leveling_config.get()) {
+                    Ok(bought) => bought,
+                    Err(reason) => return Self::reject(state, runtime, "LevelUpCharacter", reason, caller).await,
+                };
+
+                let gains = LevelGains::of(character.class.into());
+                character.level += levels;
+                character.xp -= spent;
+                character.hp_max = character.hp_max.saturating_add(gains.hp_max * levels as u32);
+                character.min_damage = character.min_damage.saturating_add(gains.min_damage.saturating_mul(levels));
+                character.max_damage = character.max_damage.saturating_add(gains.max_damage.saturating_mul(levels));
+                // Chances stop growing at the cap but never drop below what the character had
+                character.crit_chance = character.crit_chance
+                    .saturating_add(gains.crit_chance.saturating_mul(levels))
+                    .min(leveling::MAX_CHANCE_BPS.max(character.crit_chance));
+                character.dodge_chance = character.dodge_chance
+                    .saturating_add(gains.dodge_chance.saturating_mul(levels))
+                    .min(leveling::MAX_CHANCE_BPS.max(character.dodge_chance));
+                character.defense = character.defense.saturating_add(gains.defense.saturating_mul(levels));
+                state.characters.insert(&character_id, character.clone())
+                    .expect("Failed to level up character");
+
+                if let Some(lobby_chain_id) = *state.lobby_chain_id.get() {
+                    let snapshot = Self::snapshot(state, character).await;
+                    runtime.prepare_message(Message::CharacterUpdated { player: caller, snapshot })
+                        .with_authentication()
+                        .send_to(lobby_chain_id);
+                }
+            }
+
+            Operation::TransferCharacter { character_id, to_owner, to_chain } => {
+                if to_chain == runtime.chain_id() {
+                    return Self::reject(state, runtime, "TransferCharacter", "same_chain", caller).await;
+                }
+                let character = match state.outgoing_check(caller, &character_id).await {
+                    Ok(character) => character,
+                    Err(reason) => return Self::reject(state, runtime, "TransferCharacter", reason, caller).await,
+                };
+                if state.character_listings.contains_key(&character_id).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "TransferCharacter", "character_listed", caller).await;
+                }
+
+                Self::release_character(state, &character_id).await;
+                Self::report_move(state, runtime, character_id, to_chain);
+                runtime.prepare_message(Message::CharacterTransferred {
+                    character: character.record(),
+                    to_owner,
+                    price: Amount::ZERO,
+                }).with_authentication().with_tracking().send_to(to_chain);
+            }
+
+            Operation::ListCharacterForSale { character_id, price } => {
+                if let Err(reason) = state.outgoing_check(caller, &character_id).await {
+                    return Self::reject(state, runtime, "ListCharacterForSale", reason, caller).await;
+                }
+                if price == Amount::ZERO {
+                    return Self::reject(state, runtime, "ListCharacterForSale", "zero_price", caller).await;
+                }
+                state.character_listings.insert(&character_id, price)
+                    .expect("Failed to list character");
+            }
+
+            Operation::DelistCharacter { character_id } => {
+                if !matches!(state.characters.get(&character_id).await, Ok(Some(character)) if character.owner == caller) {
+                    return Self::reject(state, runtime, "DelistCharacter", "unknown_character", caller).await;
+                }
+                if !state.character_listings.contains_key(&character_id).await.unwrap_or(false) {
+                    return Self::reject(state, runtime, "DelistCharacter", "not_listed", caller).await;
+                }
+                state.character_listings.remove(&character_id)
+                    .expect("Failed to delist character");
+            }
+
+            Operation::BuyCharacter { seller_chain, character_id, price } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "BuyCharacter", "not_owner", caller).await;
+                }
+                if seller_chain == runtime.chain_id() {
+                    return Self::reject(state, runtime, "BuyCharacter", "same_chain", caller).await;
+                }
+                if price == Amount::ZERO {
+                    return Self::reject(state, runtime, "BuyCharacter", "zero_price", caller).await;
+                }
+                if state.characters.contains_key(&character_id).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "BuyCharacter", "character_exists", caller).await;
+                }
+                let escrow_key = (seller_chain, character_id.clone());
+                if state.purchase_escrows.contains_key(&escrow_key).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "BuyCharacter", "purchase_pending", caller).await;
+                }
+                let Ok(balance) = state.battle_token_balance.get().try_sub(price) else {
+                    return Self::reject(state, runtime, "BuyCharacter", "insufficient_balance", caller).await;
+                };
+
+                state.battle_token_balance.set(balance);
+                state.purchase_escrows.insert(&escrow_key, price)
+                    .expect("Failed to escrow purchase");
+                runtime.prepare_message(Message::PurchaseCharacter { buyer: caller, character_id, price })
+                    .with_authentication()
+                    .with_tracking()
+                    .send_to(seller_chain);
+            }
+
+            Operation::SendFriendRequest { to_chain } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "SendFriendRequest", "not_owner", caller).await;
+                }
+                let reason = if to_chain == runtime.chain_id() {
+                    Some("same_chain")
+                } else if state.friends.contains_key(&to_chain).await.unwrap_or(true) {
+                    Some("already_friends")
+                } else if state.sent_friend_requests.contains_key(&to_chain).await.unwrap_or(true) {
+                    Some("request_pending")
+                } else if state.friends.count().await.unwrap_or(MAX_FRIENDS) >= MAX_FRIENDS {
+                    Some("friend_list_full")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Self::reject(state, runtime, "SendFriendRequest", reason, caller).await;
+                }
+                state.sent_friend_requests.insert(&to_chain, ())
+                    .expect("Failed to record friend request");
+                runtime.prepare_message(Message::FriendRequest { from: caller })
+                    .with_authentication()
+                    .send_to(to_chain);
+            }
+
+            Operation::AcceptFriend { from_chain } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "AcceptFriend", "not_owner", caller).await;
+                }
+                let Ok(Some(friend)) = state.friend_requests.get(&from_chain).await else {
+                    return Self::reject(state, runtime, "AcceptFriend", "no_friend_request", caller).await;
+                };
+                if state.friends.count().await.unwrap_or(MAX_FRIENDS) >= MAX_FRIENDS {
+                    return Self::reject(state, runtime, "AcceptFriend", "friend_list_full", caller).await;
+                }
+                state.friend_requests.remove(&from_chain).expect("Failed to clear friend request");
+                state.sent_friend_requests.remove(&from_chain).expect("Failed to clear friend request");
+                state.friends.insert(&from_chain, friend).expect("Failed to add friend");
+                runtime.prepare_message(Message::FriendAccepted { friend: caller })
+                    .with_authentication()
+                    .send_to(from_chain);
+            }
+
+            Operation::InviteToParty { to_chain } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "InviteToParty", "not_owner", caller).await;
+                }
+                let this_chain = runtime.chain_id();
+                let party = state.party.get().clone().unwrap_or_else(|| Party {
+                    leader: this_chain,
+                    members: vec![PartyMember { owner: caller, chain: this_chain }],
+                });
+                let reason = if !state.friends.contains_key(&to_chain).await.unwrap_or(false) {
+                    Some("not_a_friend")
+                } else if party.leader != this_chain {
+                    Some("not_party_leader")
+                } else if party.has(to_chain) {
+                    Some("already_in_party")
+                } else if party.members.len() >= MAX_PARTY_SIZE {
+                    Some("party_full")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Self::reject(state, runtime, "InviteToParty", reason, caller).await;
+                }
+                state.party.set(Some(party));
+                state.sent_party_invites.insert(&to_chain, ())
+                    .expect("Failed to record party invite");
+                runtime.prepare_message(Message::PartyInvite)
+                    .with_authentication()
+                    .send_to(to_chain);
+            }
+
+            Operation::AcceptPartyInvite { leader_chain } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "AcceptPartyInvite", "not_owner", caller).await;
+                }
+                if !state.party_invites.contains_key(&leader_chain).await.unwrap_or(false) {
+                    return Self::reject(state, runtime, "AcceptPartyInvite", "no_party_invite", caller).await;
+                }
+                if state.party.get().is_some() {
+                    return Self::reject(state, runtime, "AcceptPartyInvite", "already_in_party", caller).await;
+                }
+                // The party is joined once the leader announces it with this chain in it
+                state.party_invites.remove(&leader_chain).expect("Failed to clear party invite");
+                runtime.prepare_message(Message::JoinParty { member: caller })
+                    .with_authentication()
+                    .send_to(leader_chain);
+            }
+
+            Operation::LeaveParty => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "LeaveParty", "not_owner", caller).await;
+                }
+                let Some(party) = state.party.get().clone() else {
+                    return Self::reject(state, runtime, "LeaveParty", "not_in_party", caller).await;
+                };
+                let this_chain = runtime.chain_id();
+                if party.leader == this_chain {
+                    for member in party.members.iter().filter(|member| member.chain != this_chain) {
+                        runtime.prepare_message(Message::PartyUpdated { party: None })
+                            .with_authentication()
+                            .send_to(member.chain);
+                    }
+                    state.sent_party_invites.clear();
+                } else {
+                    runtime.prepare_message(Message::PartyLeft)
+                        .with_authentication()
+                        .send_to(party.leader);
+                }
+                state.party.set(None);
+            }
+
+            Operation::TransferTokens { to, amount } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "TransferTokens", "not_owner", caller).await;
+                }
+                if to == caller {
+                    return Self::reject(state, runtime, "TransferTokens", "self_transfer", caller).await;
+                }
+                if amount == Amount::ZERO {
+                    return Self::reject(state, runtime, "TransferTokens", "zero_amount", caller).await;
+                }
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return Self::reject(state, runtime, "TransferTokens", "no_lobby", caller).await;
+                };
+                let Ok(balance) = state.battle_token_balance.get().try_sub(amount) else {
+                    return Self::reject(state, runtime, "TransferTokens", "insufficient_balance", caller).await;
+                };
+
+                // Tracked, so tokens the lobby cannot take come back
+                state.battle_token_balance.set(balance);
+                runtime.prepare_message(Message::RequestTokenTransfer { from: caller, to, amount })
+                    .with_authentication()
+                    .with_tracking()
+                    .send_to(lobby_chain_id);
+            }
+
+            Operation::PlaceBetOnMarket { market_id, predicted_winner, amount } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "PlaceBetOnMarket", "not_owner", caller).await;
+                }
+                if amount == Amount::ZERO {
+                    return Self::reject(state, runtime, "PlaceBetOnMarket", "zero_amount", caller).await;
+                }
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return Self::reject(state, runtime, "PlaceBetOnMarket", "no_lobby", caller).await;
+                };
+                if state.pending_bets.contains_key(&market_id).await.unwrap_or(true) {
+                    return Self::reject(state, runtime, "PlaceBetOnMarket", "bet_pending", caller).await;
+                }
+                let Ok(balance) = state.battle_token_balance.get().try_sub(amount) else {
+                    return Self::reject(state, runtime, "PlaceBetOnMarket", "insufficient_balance", caller).await;
+                };
+
+                // Held until the lobby answers; tracked, so a bet the lobby cannot take comes back
+                state.battle_token_balance.set(balance);
+                state.pending_bets.insert(&market_id, amount)
+                    .expect("Failed to hold bet");
+                runtime.prepare_message(Message::RequestPlaceBet { bettor: caller, market_id, predicted_winner, amount })
+                    .with_authentication()
+                    .with_tracking()
+                    .send_to(lobby_chain_id);
+            }
+
+            Operation::SetPreferences { prefs } => {
+                if Some(caller) != *state.owner.get() {
+                    return Self::reject(state, runtime, "SetPreferences", "not_owner", caller).await;
+                }
+                if let Err(reason) = prefs.validate() {
+                    return Self::reject(state, runtime, "SetPreferences", reason, caller).await;
+                }
+                state.preferences.set(prefs);
+            }
+
             _ => {
                 // Ignore operations not relevant to player chain
             }
         }
+        OperationResponse::Done
     }
 
     pub async fn execute_message(
@@ -171,119 +654,2063 @@ impl PlayerContract {
         message: Message,
     ) {
         match message {
-            Message::InitializePlayerChain { lobby_chain_id, owner } => {
+            Message::InitializePlayerChain { lobby_chain_id, owner, max_concurrent_battles, ranked_gates, progression, leveling, minting } => {
                 // Initialize player chain with lobby reference
                 state.lobby_chain_id.set(Some(lobby_chain_id));
                 state.owner.set(Some(owner));
+                state.max_concurrent_battles.set(max_concurrent_battles.max(1));
+                state.ranked_gates.set(ranked_gates);
+                state.progression.set(progression);
+                state.leveling_config.set(leveling);
+                state.mint_terms.set(minting);
             }
 
-            Message::UpdatePlayerStats { player, won, xp_gained, elo_change, battle_chain } => {
-                // Verify message comes from lobby chain (only lobby can update player stats)
+            Message::BattleSummaryNotification { summary } => {
                 let sender_chain = runtime.message_origin_chain_id()
                     .expect("Message must have origin");
-                let lobby_chain_id = state.lobby_chain_id.get().unwrap();
-                
+                if Some(sender_chain) != *state.lobby_chain_id.get() {
+                    return; // Only the lobby relays battle summaries
+                }
+                if state.preferences.get().mute_battle_summaries {
+                    return;
+                }
+                let battle_chain = summary.battle_chain;
+                state.subscribed_results.insert(&battle_chain, summary)
+                    .expect("Failed to store battle summary");
+            }
+
+            Message::BattleMatched { battle_chain, character_id, stake } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return;
+                };
                 if sender_chain != lobby_chain_id {
-                    return; // Reject unauthorized stat updates
+                    return; // Only the lobby matches players
                 }
-                
-                // Update player stats from battle results with ELO
-                if Some(player) == *state.owner.get() {
-                    let mut stats = state.player_stats.get().clone();
-                    
-                    // Apply ELO change
-                    if elo_change >= 0 {
-                        stats.elo_rating = stats.elo_rating.saturating_add(elo_change as u64);
-                    } else {
-                        stats.elo_rating = stats.elo_rating.saturating_sub((-elo_change) as u64);
-                    }
-                    
-                    // Update battle count and win/loss
-                    stats.total_battles += 1;
-                    if won {
-                        stats.wins += 1;
-                        stats.current_streak += 1;
-                        if stats.current_streak > stats.best_streak {
-                            stats.best_streak = stats.current_streak;
-                        }
-                    } else {
-                        stats.losses += 1;
-                        stats.current_streak = 0;
-                    }
-                    
-                    // Update win rate
-                    stats.win_rate = if stats.total_battles > 0 {
-                        stats.wins as f64 / stats.total_battles as f64
-                    } else {
-                        0.0
-                    };
-                    
-                    state.player_stats.set(stats);
+                // Results are only taken for battles the lobby matched a character into
+                state.matched_battles.insert(&battle_chain, character_id.clone())
+                    .expect("Failed to record matched battle");
 
-                    // Add XP to active character
-                    if let Some(character_id) = state.active_character.get() {
-                        if let Ok(Some(mut character)) = state.characters.get(character_id).await {
-                            character.xp += xp_gained;
-                            state.characters.insert(character_id, character)
-                                .expect("Failed to update character XP");
-                        }
-                    }
-                    
-                    // Store battle record for history
-                    let battle_record = crate::state::BattleRecord {
-                        battle_chain,
-                        opponent: player, // This will be corrected by lobby
-                        character_used: state.active_character.get().clone().unwrap_or_default(),
-                        stake: Amount::ZERO, // Will be filled by lobby
-                        result: if won { crate::state::BattleResult::Won } else { crate::state::BattleResult::Lost },
-                        rounds_played: 0, // Will be filled by lobby
-                        xp_gained,
-                        payout: Amount::ZERO, // Will be filled by lobby
-                        combat_stats: crate::state::CombatStats {
-                            damage_dealt: 0,
-                            damage_taken: 0,
-                            crits: 0,
-                            dodges: 0,
-                            highest_crit: 0,
-                        },
-                        completed_at: runtime.system_time(),
-                    };
-                    
-                    state.battle_history.insert(&battle_chain, battle_record)
-                        .expect("Failed to store battle record");
+                // Promote the queue engagement to a battle engagement; a series keeps holding
+                // the character between games
+                let Some(engagement) = state.engagement_with(&character_id, lobby_chain_id).await else {
+                    return;
+                };
+                if engagement.kind == EngagementKind::Series {
+                    // The series keeps holding the character between games; its stake is in the pot
+                    return;
                 }
-            }
+                state.hosted_private_battle.set(None);
+                state.active_engagements.insert(&character_id, crate::state::Engagement {
+                    chain: battle_chain,
+                    kind: crate::state::EngagementKind::Battle,
+                    mode: engagement.mode,
+                    since: runtime.system_time(),
+                    confirmed: true,
+                }).expect("Failed to record battle engagement");
 
-            Message::RequestPlayerStats { player } => {
-                // Send player stats to lobby
-                if Some(player) == *state.owner.get() {
-                    let lobby_chain_id = state.lobby_chain_id.get().unwrap();
-                    let stats = state.player_stats.get().clone();
-                    
-                    runtime.prepare_message(Message::PlayerStatsResponse {
-                        player,
-                        stats: majorules::PlayerGlobalStats {
-                            total_battles: stats.total_battles,
-                            wins: stats.wins,
-                            losses: stats.losses,
-                            win_rate: stats.win_rate,
-                            elo_rating: stats.elo_rating,
-                            total_earnings: stats.total_earnings,
-                            total_damage_dealt: stats.total_damage_dealt,
-                            total_damage_taken: stats.total_damage_taken,
-                            total_crits: stats.total_crits,
-                            total_dodges: stats.total_dodges,
-                            highest_crit: stats.highest_crit,
-                            current_streak: stats.current_streak,
-                            best_streak: stats.best_streak,
-                        },
-                    }).with_authentication().send_to(lobby_chain_id);
+                // The battle holds the matched stake; whatever was locked beyond it comes back
+                let locked = Self::take_locked_stake(state, &character_id).await;
+                let staked = locked.min(stake);
+                if staked > Amount::ZERO {
+                    state.locked_stakes.insert(&character_id, staked)
+                        .expect("Failed to lock battle stake");
                 }
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(locked.saturating_sub(staked)));
             }
 
-            _ => {
-                // Ignore other message types
+            Message::SeriesStarted { character_id, stake, .. } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return;
+                };
+                if sender_chain != lobby_chain_id {
+                    return;
+                }
+
+                let Some(mut engagement) = state.engagement_with(&character_id, lobby_chain_id).await else {
+                    return;
+                };
+                engagement.kind = EngagementKind::Series;
+                engagement.confirmed = true;
+                state.active_engagements.insert(&character_id, engagement)
+                    .expect("Failed to hold character for series");
+                // The pot takes the matched stake; whatever was locked beyond it comes back
+                let locked = Self::take_locked_stake(state, &character_id).await;
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(locked.saturating_sub(stake)));
             }
-        }
+
+            Message::SeriesEnded { character_id, payout, .. } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return;
+                };
+                if sender_chain != lobby_chain_id {
+                    return;
+                }
+
+                match state.engagement_with(&character_id, lobby_chain_id).await {
+                    Some(engagement) if engagement.kind == EngagementKind::Series => {
+                        state.active_engagements.remove(&character_id).ok();
+                    }
+                    _ => return, // Each series ends once
+                }
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(payout));
+            }
+
+            Message::QueueJoined { character_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return;
+                };
+                if sender_chain != lobby_chain_id {
+                    return;
+                }
+
+                if let Some(mut engagement) = state.engagement_with(&character_id, lobby_chain_id).await {
+                    engagement.confirmed = true;
+                    state.active_engagements.insert(&character_id, engagement)
+                        .expect("Failed to confirm queue engagement");
+                }
+            }
+
+            Message::QueueLeft { character_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let Some(lobby_chain_id) = *state.lobby_chain_id.get() else {
+                    return;
+                };
+                if sender_chain != lobby_chain_id {
+                    return;
+                }
+
+                // A release that arrives after a match finds nothing left to unlock
+                if state.engagement_with(&character_id, lobby_chain_id).await.is_some() {
+                    state.active_engagements.remove(&character_id).ok();
+                    state.hosted_private_battle.set(None);
+                    let unlocked = Self::take_locked_stake(state, &character_id).await;
+                    state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(unlocked));
+                }
+            }
+
+            Message::BattleResult { battle_chain, character_id, opponent, stake, rounds_played, .. } => {
+                // Only a battle reports on itself, and only on a character the lobby matched into it
+                if runtime.message_origin_chain_id() != Some(battle_chain) {
+                    return;
+                }
+                match state.battle_history.get(&battle_chain).await.expect("Failed to read battle history") {
+                    Some(mut record) if record.character_used == character_id => {
+                        // The lobby's result came first; complete its record
+                        (record.opponent, record.stake, record.rounds_played) = (opponent, stake, rounds_played);
+                        state.battle_history.insert(&battle_chain, record)
+                            .expect("Failed to complete battle record");
+                    }
+                    None if state.matched_battles.get(&battle_chain).await.ok().flatten().as_ref() == Some(&character_id) => {
+                        state.battle_details.insert(&battle_chain, crate::state::BattleDetails { opponent, stake, rounds_played })
+                            .expect("Failed to store battle details");
+                    }
+                    _ => {}
+                }
+            }
+
+            Message::PracticeBattleEnded { battle_chain, character_id, won, xp_gained } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) != *state.lobby_chain_id.get() {
+                    return;
+                }
+                // Each practice battle ends once, for the character matched into it
+                if state.matched_battles.get(&battle_chain).await.ok().flatten().as_ref() != Some(&character_id) {
+                    return;
+                }
+                state.matched_battles.remove(&battle_chain).expect("Failed to close matched battle");
+                if state.engagement_with(&character_id, battle_chain).await.is_some() {
+                    state.active_engagements.remove(&character_id).ok();
+                }
+
+                // Practice leaves the player's stats alone; the character keeps the XP, and a win
+                // counts toward the progression gates
+                if won {
+                    state.progression_wins.set(state.progression_wins.get().saturating_add(1));
+                }
+                if let Ok(Some(mut character)) = state.characters.get(&character_id).await {
+                    if checked_accumulate(&mut character.xp, xp_gained) {
+                        record_overflow(&mut state.counter_overflows, counters::CHARACTER_XP, runtime.system_time()).await;
+                    }
+                    state.characters.insert(&character_id, character)
+                        .expect("Failed to update character XP");
+                }
+            }
+
+            Message::TournamentExited { character_id, payout, .. } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) != *state.lobby_chain_id.get() {
+                    return;
+                }
+
+                if state.engagement_with(&character_id, sender_chain).await.is_some() {
+                    state.active_engagements.remove(&character_id).ok();
+                }
+                let balance = state.battle_token_balance.get().saturating_add(payout);
+                state.battle_token_balance.set(balance);
+            }
+
+            Message::PrivateBattleCreated { battle_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) != *state.lobby_chain_id.get() {
+                    return;
+                }
+                // Shared with the invited opponent to join
+                state.hosted_private_battle.set(Some(battle_id));
+            }
+
+            Message::DistributeWinnings { bettor, amount, market_id: _ } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) != *state.lobby_chain_id.get() || Some(bettor) != *state.owner.get() {
+                    return;
+                }
+                // The lobby sends each claim once
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(amount));
+            }
+
+            Message::CreditTokens { owner, amount } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) != *state.lobby_chain_id.get() || Some(owner) != *state.owner.get() {
+                    return; // Only the lobby credits tokens, and only to this chain's owner
+                }
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(amount));
+            }
+
+            Message::RequestTokenTransfer { amount, .. } => {
+                // Only ever received here as a bounce of a transfer this chain sent
+                if runtime.message_is_bouncing() == Some(true) {
+                    state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(amount));
+                }
+            }
+
+            Message::CharacterTransferred { character, to_owner, price } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if runtime.message_is_bouncing() == Some(true) {
+                    // The receiving chain refused the character: take it back, and undo the sale
+                    let owner = state.owner.get().expect("Player chain has an owner");
+                    let character_id = character.nft_id.clone();
+                    let character = CharacterData::from_record(character, owner);
+                    state.characters.insert(&character_id, character.clone())
+                        .expect("Failed to restore character");
+                    Self::report_character(state, runtime, owner, character).await;
+                    if price > Amount::ZERO {
+                        state.battle_token_balance.set(state.battle_token_balance.get().saturating_sub(price));
+                        runtime.prepare_message(Message::PurchaseRefused { character_id })
+                            .with_authentication()
+                            .send_to(sender_chain);
+                    }
+                    return;
+                }
+
+                // Panicking refuses the tracked message, which bounces the character back
+                assert_eq!(Some(to_owner), *state.owner.get(), "Character sent to a chain its recipient does not own");
+                assert!(
+                    !state.characters.contains_key(&character.nft_id).await.unwrap_or(true),
+                    "Character id already used on this chain",
+                );
+                let roster = state.characters.count().await.expect("Failed to count characters");
+                assert!((roster as u64) < state.mint_terms.get().roster_cap as u64, "Roster is full");
+                if price > Amount::ZERO {
+                    state.purchase_escrows.remove(&(sender_chain, character.nft_id.clone()))
+                        .expect("Failed to release purchase escrow");
+                }
+                let character_id = character.nft_id.clone();
+                let character = CharacterData::from_record(character, to_owner);
+                state.characters.insert(&character_id, character.clone())
+                    .expect("Failed to receive character");
+                Self::report_character(state, runtime, to_owner, character).await;
+            }
+
+            Message::PurchaseCharacter { buyer, character_id, price } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if runtime.message_is_bouncing() == Some(true) {
+                    return Self::refund_purchase(state, sender_chain, character_id).await;
+                }
+
+                let owner = state.owner.get().expect("Player chain has an owner");
+                let listed = state.character_listings.get(&character_id).await.ok().flatten() == Some(price);
+                let character = match state.outgoing_check(owner, &character_id).await {
+                    Ok(character) if listed => character,
+                    _ => {
+                        runtime.prepare_message(Message::PurchaseRefused { character_id })
+                            .with_authentication()
+                            .send_to(sender_chain);
+                        return;
+                    }
+                };
+
+                Self::release_character(state, &character_id).await;
+                Self::report_move(state, runtime, character_id, sender_chain);
+                state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(price));
+                runtime.prepare_message(Message::CharacterTransferred { character: character.record(), to_owner: buyer, price })
+                    .with_authentication()
+                    .with_tracking()
+                    .send_to(sender_chain);
+            }
+
+            Message::RequestPlaceBet { market_id, .. } => {
+                // Only ever received here as a bounce of a bet this chain sent
+                if runtime.message_is_bouncing() == Some(true) {
+                    let amount = Self::release_bet(state, market_id).await;
+                    state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(amount));
+                }
+            }
+
+            Message::BetPlaced { market_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) == *state.lobby_chain_id.get() {
+                    // The market holds the stake now; winnings arrive as `DistributeWinnings`
+                    Self::release_bet(state, market_id).await;
+                }
+            }
+
+            Message::BetRefused { market_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if Some(sender_chain) == *state.lobby_chain_id.get() {
+                    let amount = Self::release_bet(state, market_id).await;
+                    state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(amount));
+                }
+            }
+
+            Message::PurchaseRefused { character_id } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                Self::refund_purchase(state, sender_chain, character_id).await;
+            }
+
+            Message::FriendRequest { from } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if state.friends.contains_key(&sender_chain).await.unwrap_or(true)
+                    || state.friend_requests.count().await.unwrap_or(MAX_FRIENDS) >= MAX_FRIENDS
+                {
+                    return;
+                }
+                state.friend_requests.insert(&sender_chain, from)
+                    .expect("Failed to record friend request");
+            }
+
+            Message::FriendAccepted { friend } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if !state.sent_friend_requests.contains_key(&sender_chain).await.unwrap_or(false) {
+                    return; // Only chains this one asked become friends
+                }
+                state.sent_friend_requests.remove(&sender_chain).expect("Failed to clear friend request");
+                state.friend_requests.remove(&sender_chain).expect("Failed to clear friend request");
+                state.friends.insert(&sender_chain, friend).expect("Failed to add friend");
+            }
+
+            Message::PartyInvite => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if state.friends.contains_key(&sender_chain).await.unwrap_or(false) {
+                    state.party_invites.insert(&sender_chain, ())
+                        .expect("Failed to record party invite");
+                }
+            }
+
+            Message::JoinParty { member } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if !state.sent_party_invites.contains_key(&sender_chain).await.unwrap_or(false) {
+                    return;
+                }
+                state.sent_party_invites.remove(&sender_chain).expect("Failed to clear party invite");
+                let this_chain = runtime.chain_id();
+                let Some(mut party) = state.party.get().clone() else {
+                    return;
+                };
+                if party.leader != this_chain || party.has(sender_chain) || party.members.len() >= MAX_PARTY_SIZE {
+                    return;
+                }
+                party.members.push(PartyMember { owner: member, chain: sender_chain });
+                Self::announce_party(runtime, &party, this_chain);
+                state.party.set(Some(party));
+            }
+
+            Message::PartyUpdated { party } => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                if state.party.get().as_ref().is_some_and(|current| current.leader != sender_chain) {
+                    return; // Only the leader of the party this chain is in speaks for it
+                }
+                let this_chain = runtime.chain_id();
+                state.party.set(party.filter(|party| party.leader == sender_chain && party.has(this_chain)));
+            }
+
+            Message::PartyLeft => {
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let this_chain = runtime.chain_id();
+                let Some(mut party) = state.party.get().clone() else {
+                    return;
+                };
+                if party.leader != this_chain || !party.has(sender_chain) {
+                    return;
+                }
+                party.members.retain(|member| member.chain != sender_chain);
+                Self::announce_party(runtime, &party, this_chain);
+                state.party.set(Some(party));
+            }
+
+            Message::UpdatePlayerStats {
+                player, character_id, won, payout, xp_gained, elo_change, battle_chain, rules_digest, end_reason, item_drop,
+                combat_stats, win_streak,
+            } => {
+                // Verify message comes from lobby chain (only lobby can update player stats)
+                let sender_chain = runtime.message_origin_chain_id()
+                    .expect("Message must have origin");
+                let lobby_chain_id = state.lobby_chain_id.get().unwrap();
+                
+                if sender_chain != lobby_chain_id {
+                    return; // Reject unauthorized stat updates
+                }
+                // Only the battle this character was matched into reports on it
+                if state.matched_battles.get(&battle_chain).await.ok().flatten().as_ref() != Some(&character_id) {
+                    return;
+                }
+
+                // Each battle counts once, however often its result is delivered
+                if state.battle_history.contains_key(&battle_chain).await.unwrap_or(true) {
+                    return;
+                }
+                
+                // Update player stats from battle results with ELO
+                if Some(player) == *state.owner.get() {
+                    let mut stats = state.player_stats.get().clone();
+                    
+                    // Apply ELO change
+                    if elo_change >= 0 {
+                        stats.elo_rating = stats.elo_rating.saturating_add(elo_change as u64);
+                    } else {
+                        stats.elo_rating = stats.elo_rating.saturating_sub((-elo_change) as u64);
+                    }
+                    
+                    // Update battle count and win/loss; a draw's refund is not earnings. The lobby
+                    // keeps streaks, insured ones included
+                    stats.total_battles += 1;
+                    stats.total_damage_dealt = stats.total_damage_dealt.saturating_add(combat_stats.damage_dealt);
+                    stats.total_damage_taken = stats.total_damage_taken.saturating_add(combat_stats.damage_taken);
+                    stats.total_crits = stats.total_crits.saturating_add(combat_stats.crits);
+                    stats.total_dodges = stats.total_dodges.saturating_add(combat_stats.dodges);
+                    stats.highest_crit = stats.highest_crit.max(combat_stats.highest_crit);
+                    let drawn = end_reason == BattleEndReason::Draw;
+                    if drawn {
+                        stats.draws += 1;
+                    } else if won {
+                        stats.wins += 1;
+                        if checked_accumulate(&mut stats.total_earnings, payout) {
+                            record_overflow(&mut state.counter_overflows, counters::EARNINGS, runtime.system_time()).await;
+                        }
+                    } else {
+                        stats.losses += 1;
+                    }
+                    stats.current_streak = win_streak;
+                    stats.best_streak = stats.best_streak.max(win_streak);
+                    
+                    // Update win rate
+                    stats.win_rate_bps = if stats.total_battles > 0 {
+                        stats.wins * 10_000 / stats.total_battles
+                    } else {
+                        0
+                    };
+                    
+                    let totals = stats.lifetime_totals();
+                    state.player_stats.set(stats);
+                    Self::advance_quests(state, runtime, won && !drawn, &combat_stats).await;
+                    Self::unlock_achievements(state, runtime, &totals).await;
+
+                    // Add XP to the character that fought this battle; a ranked defeat also costs it a life,
+                    // and a casual win counts toward the progression gates
+                    // A series game's character stays held by the series
+                    let engagement = state.active_engagements.get(&character_id).await
+                        .expect("Failed to read engagements")
+                        .filter(|engagement| engagement.chain == battle_chain || engagement.kind == EngagementKind::Series);
+                    let mode = engagement.as_ref().map(|engagement| engagement.mode);
+                    let ranked = mode == Some(QueueMode::Ranked);
+                    if won && !drawn && mode == Some(QueueMode::Casual) {
+                        state.progression_wins.set(state.progression_wins.get().saturating_add(1));
+                    }
+                    if let Ok(Some(mut character)) = state.characters.get(&character_id).await {
+                        if checked_accumulate(&mut character.xp, xp_gained) {
+                            record_overflow(&mut state.counter_overflows, counters::CHARACTER_XP, runtime.system_time()).await;
+                        }
+                        if ranked && !won && !drawn && character.is_alive() {
+                            character.lives_remaining -= 1;
+                            runtime.prepare_message(Message::CharacterLivesChanged {
+                                player,
+                                character_id: character_id.clone(),
+                                lives_remaining: character.lives_remaining,
+                                revive_fee: Amount::ZERO,
+                            }).with_authentication().send_to(lobby_chain_id);
+                        }
+                        state.characters.insert(&character_id, character)
+                            .expect("Failed to update character XP");
+                    }
+
+                    // Store any item the battle dropped
+                    if let Some(drop) = item_drop {
+                        if !state.items.contains_key(&drop.item_id).await.unwrap_or(true) {
+                            state.items.insert(&drop.item_id.clone(), ItemData {
+                                item_id: drop.item_id,
+                                slot: drop.slot,
+                                bonuses: drop.bonuses,
+                                rarity: drop.rarity,
+                                acquired_at: runtime.system_time(),
+                                equipped_on: None,
+                            }).expect("Failed to store dropped item");
+                        }
+                    }
+
+                    // Release the character's battle engagement; its stake went into the pot,
+                    // and the payout is what comes back of it
+                    if engagement.is_some_and(|engagement| engagement.chain == battle_chain) {
+                        state.active_engagements.remove(&character_id).ok();
+                        Self::take_locked_stake(state, &character_id).await;
+                    }
+                    state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(payout));
+                    
+                    // Store battle record for history, with the battle's own report if it came first;
+                    // otherwise the report fills in the opponent, stake and rounds when it arrives
+                    let details = state.battle_details.get(&battle_chain).await
+                        .expect("Failed to read battle details");
+                    if details.is_some() {
+                        state.battle_details.remove(&battle_chain).expect("Failed to clear battle details");
+                    }
+                    let (opponent, stake, rounds_played) = details
+                        .map_or((player, Amount::ZERO, 0), |details| (details.opponent, details.stake, details.rounds_played));
+                    let battle_record = crate::state::BattleRecord {
+                        battle_chain,
+                        opponent,
+                        character_used: character_id,
+                        stake,
+                        result: match (drawn, won) {
+                            (true, _) => crate::state::BattleResult::Draw,
+                            (false, true) => crate::state::BattleResult::Won,
+                            (false, false) => crate::state::BattleResult::Lost,
+                        },
+                        rounds_played,
+                        xp_gained,
+                        payout,
+                        combat_stats: crate::state::CombatStats {
+                            damage_dealt: combat_stats.damage_dealt,
+                            damage_taken: combat_stats.damage_taken,
+                            crits: combat_stats.crits,
+                            dodges: combat_stats.dodges,
+                            highest_crit: combat_stats.highest_crit,
+                            longest_combo: combat_stats.longest_combo,
+                        },
+                        completed_at: runtime.system_time(),
+                        rules_digest,
+                        end_reason,
+                    };
+                    
+                    state.battle_history.insert(&battle_chain, battle_record)
+                        .expect("Failed to store battle record");
+                    state.battle_history_order.push(battle_chain);
+                    state.matched_battles.remove(&battle_chain).expect("Failed to close matched battle");
+                    
+                    // Echo a receipt so the lobby can audit the payout
+                    if won && payout > Amount::ZERO {
+                        runtime.prepare_message(Message::PayoutReceipt {
+                            battle_chain,
+                            player,
+                            amount: payout,
+                        }).with_authentication().send_to(lobby_chain_id);
+                    }
+                }
+            }
+
+            Message::RequestPlayerStats { player } => {
+                // Send player stats to lobby
+                if Some(player) == *state.owner.get() {
+                    let lobby_chain_id = state.lobby_chain_id.get().unwrap();
+                    let stats = state.player_stats.get().clone();
+                    
+                    runtime.prepare_message(Message::PlayerStatsResponse {
+                        player,
+                        stats: majorules::PlayerGlobalStats {
+                            total_battles: stats.total_battles,
+                            wins: stats.wins,
+                            losses: stats.losses,
+                            draws: stats.draws,
+                            win_rate_bps: stats.win_rate_bps,
+                            elo_rating: stats.elo_rating,
+                            total_earnings: stats.total_earnings,
+                            total_damage_dealt: stats.total_damage_dealt,
+                            total_damage_taken: stats.total_damage_taken,
+                            total_crits: stats.total_crits,
+                            total_dodges: stats.total_dodges,
+                            highest_crit: stats.highest_crit,
+                            current_streak: stats.current_streak,
+                            best_streak: stats.best_streak,
+                        },
+                    }).with_authentication().send_to(lobby_chain_id);
+                }
+            }
+
+            _ => {
+                // Ignore other message types
+            }
+        }
+    }
+
+    /// Log a rejected player operation and answer it with the reason
+    async fn reject(
+        state: &mut PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        reason: &str,
+        caller: AccountOwner,
+    ) -> OperationResponse {
+        let key = RejectionKey::new(operation, reason, caller);
+        record_rejection(&mut state.rejections, key, runtime.system_time()).await;
+        OperationResponse::rejected(reason)
+    }
+
+    /// Take a character off this chain: delist it, free its items and stop it being active
+    /// Tell the lobby's roster this chain now holds `character`
+    async fn report_character(
+        state: &PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        player: AccountOwner,
+        character: CharacterData,
+    ) {
+        if let Some(lobby_chain_id) = *state.lobby_chain_id.get() {
+            let snapshot = Self::snapshot(state, character).await;
+            runtime.prepare_message(Message::CharacterUpdated { player, snapshot })
+                .with_authentication()
+                .send_to(lobby_chain_id);
+        }
+    }
+
+    /// Tell the lobby's roster `character_id` is on its way to `to_chain`
+    fn report_move(state: &PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, character_id: String, to_chain: ChainId) {
+        if let Some(lobby_chain_id) = *state.lobby_chain_id.get() {
+            runtime.prepare_message(Message::CharacterMoved { character_id, to_chain })
+                .with_authentication()
+                .send_to(lobby_chain_id);
+        }
+    }
+
+    /// Send `party` as it now stands to every member but its leader
+    fn announce_party(runtime: &mut ContractRuntime<crate::MajorulesContract>, party: &Party, leader_chain: ChainId) {
+        for member in party.members.iter().filter(|member| member.chain != leader_chain) {
+            runtime.prepare_message(Message::PartyUpdated { party: Some(party.clone()) })
+                .with_authentication()
+                .send_to(member.chain);
+        }
+    }
+
+    async fn release_character(state: &mut PlayerState, character_id: &str) {
+        state.characters.remove(character_id)
+            .expect("Failed to remove character");
+        state.character_listings.remove(character_id)
+            .expect("Failed to delist character");
+        for slot in [ItemSlot::Weapon, ItemSlot::Armor, ItemSlot::Trinket] {
+            let slot_key = (character_id.to_string(), slot);
+            let Ok(Some(item_id)) = state.equipment.get(&slot_key).await else {
+                continue;
+            };
+            state.equipment.remove(&slot_key)
+                .expect("Failed to unequip item");
+            if let Ok(Some(mut item)) = state.items.get(&item_id).await {
+                item.equipped_on = None;
+                state.items.insert(&item_id, item)
+                    .expect("Failed to release item");
+            }
+        }
+        if state.active_character.get().as_deref() == Some(character_id) {
+            state.active_character.set(None);
+        }
+    }
+
+    /// Return the payment escrowed for buying `character_id` from `seller_chain`
+    async fn refund_purchase(state: &mut PlayerState, seller_chain: ChainId, character_id: String) {
+        let escrow_key = (seller_chain, character_id);
+        let Ok(Some(price)) = state.purchase_escrows.get(&escrow_key).await else {
+            return;
+        };
+        state.purchase_escrows.remove(&escrow_key)
+            .expect("Failed to release purchase escrow");
+        state.battle_token_balance.set(state.battle_token_balance.get().saturating_add(price));
+    }
+
+    /// Stop holding the bet sent on `market_id`, returning how much was held
+    async fn release_bet(state: &mut PlayerState, market_id: u64) -> Amount {
+        let Ok(Some(amount)) = state.pending_bets.get(&market_id).await else {
+            return Amount::ZERO;
+        };
+        state.pending_bets.remove(&market_id)
+            .expect("Failed to release bet");
+        amount
+    }
+
+    /// Day quests count toward now, starting their progress over once the day has turned
+    fn roll_quest_day(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>) -> u64 {
+        let today = time::bucket_day(runtime.system_time());
+        if *state.quest_day.get() != today {
+            state.quest_progress.clear();
+            state.quest_day.set(today);
+        }
+        today
+    }
+
+    /// Count a battle toward today's quests
+    async fn advance_quests(
+        state: &mut PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        won: bool,
+        combat_stats: &CombatStats,
+    ) {
+        Self::roll_quest_day(state, runtime);
+        for quest in &quests::DAILY_QUESTS {
+            let gained = quest.goal.progress(won, combat_stats);
+            if gained == 0 {
+                continue;
+            }
+            let mut progress: QuestProgress = state.quest_progress.get(quest.quest_id).await
+                .expect("Failed to read quest progress")
+                .unwrap_or_default();
+            progress.progress = progress.progress.saturating_add(gained).min(quest.target);
+            state.quest_progress.insert(quest.quest_id, progress)
+                .expect("Failed to update quest progress");
+        }
+    }
+
+    /// Unlock the achievements `totals` reach for the first time
+    async fn unlock_achievements(
+        state: &mut PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        totals: &quests::LifetimeTotals,
+    ) {
+        for achievement in quests::ACHIEVEMENTS.iter().filter(|achievement| achievement.reached(totals)) {
+            if !state.achievements.contains_key(achievement.achievement_id).await.unwrap_or(true) {
+                state.achievements.insert(achievement.achievement_id, runtime.system_time())
+                    .expect("Failed to unlock achievement");
+            }
+        }
+    }
+
+    /// Release the stake locked for `character_id`, returning how much it was
+    async fn take_locked_stake(state: &mut PlayerState, character_id: &str) -> Amount {
+        let Ok(Some(locked)) = state.locked_stakes.get(character_id).await else {
+            return Amount::ZERO;
+        };
+        state.locked_stakes.remove(character_id)
+            .expect("Failed to release locked stake");
+        locked
+    }
+
+    /// Hold a character for a request to the lobby, returning the lobby and the character's
+    /// snapshot, or reject `operation` with the reason the character cannot go
+    async fn enter_lobby(
+        state: &mut PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        caller: AccountOwner,
+        operation: &str,
+        character_id: &str,
+        mode: QueueMode,
+    ) -> Result<(ChainId, CharacterSnapshot), OperationResponse> {
+        let (lobby_chain_id, character) = match state.queue_check(character_id, mode).await {
+            Ok(queueable) => queueable,
+            Err(reason) => return Err(Self::reject(state, runtime, operation, &reason, caller).await),
+        };
+        state.active_engagements.insert(character_id, crate::state::Engagement {
+            chain: lobby_chain_id,
+            kind: crate::state::EngagementKind::Queue,
+            mode,
+            since: runtime.system_time(),
+            confirmed: false,
+        }).expect("Failed to record queue engagement");
+        Ok((lobby_chain_id, Self::snapshot(state, character).await))
+    }
+
+    /// Battle snapshot of a character with the bonuses of its equipped items folded in
+    async fn snapshot(state: &PlayerState, character: CharacterData) -> CharacterSnapshot {
+        let mut mods = PassiveMods {
+            attack_bps: character.attack_bps,
+            defense_bps: character.defense_bps,
+            crit_bps: character.crit_bps,
+        };
+        for slot in [ItemSlot::Weapon, ItemSlot::Armor, ItemSlot::Trinket] {
+            let Ok(Some(item_id)) = state.equipment.get(&(character.nft_id.clone(), slot)).await else {
+                continue;
+            };
+            if let Ok(Some(item)) = state.items.get(&item_id).await {
+                mods = mods.saturating_add(item.bonuses);
+            }
+        }
+
+        CharacterSnapshot {
+            nft_id: character.nft_id,
+            class: character.class.into(),
+            level: character.level,
+            hp_max: character.hp_max,
+            min_damage: character.min_damage,
+            max_damage: character.max_damage,
+            crit_chance: character.crit_chance,
+            crit_multiplier: character.crit_multiplier,
+            dodge_chance: character.dodge_chance,
+            defense: character.defense,
+            attack_bps: mods.attack_bps,
+            defense_bps: mods.defense_bps,
+            crit_bps: mods.crit_bps,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+        util::BlockingWait,
+        views::View,
+        ContractRuntime,
+    };
+    use majorules::{
+        leveling::{LevelGains, LevelingConfig}, minting::{max_trait_bps, MintTerms}, practice::PracticeDifficulty, throttle::RejectionKey, BattleEndReason, BattleResultSummary, CharacterClass,
+        CombatStats, ItemDrop, ItemRarity, ItemSlot, Message, Operation, OperationResponse, PassiveMods, PlayerPreferences, PreferenceEntry,
+        ProgressionGates, QueueMode, RankedGates, MAX_CHARACTER_ID_LEN, MAX_PREFERENCE_ENTRY_LEN, STARTING_LIVES,
+    };
+
+    use super::PlayerContract;
+    use crate::{
+        actions::{player_actions, PlayerAction},
+        state::{EngagementKind, PlayerState},
+    };
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    fn setup(max_concurrent_battles: u8) -> (PlayerState, ContractRuntime<crate::MajorulesContract>) {
+        setup_as("owner", "player", max_concurrent_battles)
+    }
+
+    fn setup_as(owner: &str, chain_name: &str, max_concurrent_battles: u8) -> (PlayerState, ContractRuntime<crate::MajorulesContract>) {
+        let owner = AccountOwner::from(CryptoHash::test_hash(owner));
+        let mut runtime = ContractRuntime::new()
+            .with_chain_id(chain(chain_name))
+            .with_authenticated_signer(owner)
+            .with_system_time(Timestamp::from(0));
+        let mut state = PlayerState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+
+        deliver(&mut state, &mut runtime, Message::InitializePlayerChain {
+            lobby_chain_id: chain("lobby"),
+            owner,
+            max_concurrent_battles,
+            ranked_gates: RankedGates::default(),
+            progression: ProgressionGates::default(),
+            leveling: LevelingConfig::default(),
+            minting: MintTerms { cost: Amount::ZERO, free_mints: 0, roster_cap: 10, cooldown_secs: 0, item_cost: Amount::ZERO },
+        });
+        for character_id in ["a", "b", "c"] {
+            operate(&mut state, &mut runtime, Operation::MintCharacter {
+                character_id: character_id.to_string(),
+                class: "warrior".to_string(),
+            });
+        }
+        (state, runtime)
+    }
+
+    fn operate(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, operation: Operation) -> OperationResponse {
+        PlayerContract::execute_operation(state, runtime, operation).blocking_wait()
+    }
+
+    fn deliver(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, message: Message) {
+        runtime.set_message_origin_chain_id(chain("lobby"));
+        PlayerContract::execute_message(state, runtime, message).blocking_wait();
+    }
+
+    fn deliver_from(
+        state: &mut PlayerState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        origin: &str,
+        bouncing: bool,
+        message: Message,
+    ) {
+        runtime.set_message_origin_chain_id(chain(origin));
+        runtime.set_message_is_bouncing(Some(bouncing));
+        PlayerContract::execute_message(state, runtime, message).blocking_wait();
+        runtime.set_message_is_bouncing(None);
+    }
+
+    /// Messages sent so far, with where they went and whether they were tracked
+    fn sent(runtime: &mut ContractRuntime<crate::MajorulesContract>) -> Vec<(ChainId, bool, Message)> {
+        runtime.created_send_message_requests().iter()
+            .map(|request| {
+                let message = serde_json::from_value(serde_json::to_value(&request.message).unwrap()).unwrap();
+                (request.destination, request.is_tracked, message)
+            })
+            .collect()
+    }
+
+    fn queue_and_match(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, character_id: &str) {
+        operate(state, runtime, Operation::JoinQueue {
+            character_id: character_id.to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+        });
+        deliver(state, runtime, Message::BattleMatched {
+            battle_chain: chain(character_id),
+            character_id: character_id.to_string(),
+            stake: Amount::ZERO,
+        });
+    }
+
+    fn join_requests(runtime: &mut ContractRuntime<crate::MajorulesContract>) -> usize {
+        runtime.created_send_message_requests().iter()
+            .filter(|request| matches!(request.message, Message::RequestJoinQueue { .. }))
+            .count()
+    }
+
+    #[test]
+    fn concurrent_battles_credit_their_own_characters() {
+        let (mut state, mut runtime) = setup(2);
+
+        queue_and_match(&mut state, &mut runtime, "a");
+        queue_and_match(&mut state, &mut runtime, "b");
+
+        for id in ["a", "b"] {
+            let engagement = state.active_engagements.get(id).blocking_wait().unwrap().unwrap();
+            assert_eq!((engagement.chain, engagement.kind), (chain(id), EngagementKind::Battle));
+        }
+
+        let player = state.owner.get().unwrap();
+        for (id, won, xp_gained) in [("b", false, 50), ("a", true, 150)] {
+            deliver(&mut state, &mut runtime, Message::UpdatePlayerStats {
+                player,
+                character_id: id.to_string(),
+                won,
+                payout: Default::default(),
+                xp_gained,
+                elo_change: 0,
+                battle_chain: chain(id),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                item_drop: None,
+                combat_stats: CombatStats::default(),
+                win_streak: 0,
+            });
+        }
+
+        let a = state.characters.get("a").blocking_wait().unwrap().unwrap();
+        let b = state.characters.get("b").blocking_wait().unwrap().unwrap();
+        assert_eq!((a.xp, b.xp), (150, 50));
+        assert_eq!(state.active_engagements.count().blocking_wait().unwrap(), 0);
+        assert_eq!((state.player_stats.get().wins, state.player_stats.get().losses), (1, 1));
+    }
+
+    #[test]
+    fn battle_reports_complete_history_records_in_either_order() {
+        let (mut state, mut runtime) = setup(2);
+        let player = state.owner.get().unwrap();
+        let rival = AccountOwner::from(CryptoHash::test_hash("rival"));
+        queue_and_match(&mut state, &mut runtime, "a");
+        queue_and_match(&mut state, &mut runtime, "b");
+        let report = |id: &str| Message::BattleResult {
+            battle_chain: chain(id),
+            character_id: id.to_string(),
+            opponent: rival,
+            stake: Amount::from_tokens(2),
+            payout: Amount::from_tokens(9),
+            rounds_played: 3,
+            battle_stats: CombatStats::default(),
+            end_reason: BattleEndReason::Knockout,
+        };
+        let update = |id: &str| Message::UpdatePlayerStats {
+            player,
+            character_id: id.to_string(),
+            won: true,
+            payout: Amount::from_tokens(3),
+            xp_gained: 10,
+            elo_change: 0,
+            battle_chain: chain(id),
+            rules_digest: 0,
+            end_reason: BattleEndReason::Knockout,
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        };
+
+        // Only the battle itself reports on itself
+        deliver(&mut state, &mut runtime, report("a"));
+        assert!(state.battle_details.get(&chain("a")).blocking_wait().unwrap().is_none());
+        deliver_from(&mut state, &mut runtime, "a", false, report("a"));
+        deliver(&mut state, &mut runtime, update("a"));
+        deliver(&mut state, &mut runtime, update("b"));
+        deliver_from(&mut state, &mut runtime, "b", false, report("b"));
+
+        // The lobby's payout is the one recorded and credited
+        for id in ["a", "b"] {
+            let record = state.battle_history.get(&chain(id)).blocking_wait().unwrap().unwrap();
+            assert_eq!((record.opponent, record.stake, record.rounds_played, record.payout), (rival, Amount::from_tokens(2), 3, Amount::from_tokens(3)));
+        }
+        assert_eq!(state.battle_details.count().blocking_wait().unwrap(), 0);
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(6));
+    }
+
+    #[test]
+    fn results_count_only_for_the_battle_a_character_was_matched_into() {
+        let (mut state, mut runtime) = setup(1);
+        let player = state.owner.get().unwrap();
+        queue_and_match(&mut state, &mut runtime, "a");
+        let result = |character_id: &str, battle: &str| Message::UpdatePlayerStats {
+            player,
+            character_id: character_id.to_string(),
+            won: true,
+            payout: Default::default(),
+            xp_gained: 100,
+            elo_change: 0,
+            battle_chain: chain(battle),
+            rules_digest: 0,
+            end_reason: BattleEndReason::Knockout,
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        };
+
+        deliver(&mut state, &mut runtime, result("a", "unmatched"));
+        deliver(&mut state, &mut runtime, result("b", "a"));
+        deliver_from(&mut state, &mut runtime, "a", false, result("a", "a"));
+        assert_eq!(state.player_stats.get().total_battles, 0);
+
+        deliver(&mut state, &mut runtime, result("a", "a"));
+        assert_eq!(state.player_stats.get().total_battles, 1);
+        assert!(!state.matched_battles.contains_key(&chain("a")).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn queue_stakes_stay_locked_until_released_or_settled() {
+        let (mut state, mut runtime) = setup(1);
+        let player = state.owner.get().unwrap();
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner: player, amount: Amount::from_tokens(10) });
+        let join = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, tokens| {
+            let stake = Amount::from_tokens(tokens);
+            operate(state, runtime, Operation::JoinQueue { character_id: "a".to_string(), stake, mode: QueueMode::Casual, best_of: 1 });
+        };
+        let locked = |state: &PlayerState, character_id| state.locked_stakes.get(character_id).blocking_wait().unwrap();
+
+        join(&mut state, &mut runtime, 11);
+        assert!(state.rejections.contains_key(&RejectionKey::new("JoinQueue", "insufficient_balance", player)).blocking_wait().unwrap());
+        join(&mut state, &mut runtime, 4);
+        assert_eq!((*state.battle_token_balance.get(), locked(&state, "a")), (Amount::from_tokens(6), Some(Amount::from_tokens(4))));
+        deliver(&mut state, &mut runtime, Message::QueueJoined { character_id: "a".to_string() });
+        assert!(state.active_engagements.get("a").blocking_wait().unwrap().unwrap().confirmed);
+
+        // Leaving unlocks the stake once, however often the release arrives
+        for _ in 0..2 {
+            deliver(&mut state, &mut runtime, Message::QueueLeft { character_id: "a".to_string() });
+        }
+        assert_eq!((*state.battle_token_balance.get(), locked(&state, "a")), (Amount::from_tokens(10), None));
+
+        // A match keeps the matched stake locked and unlocks the rest; a late release changes nothing
+        join(&mut state, &mut runtime, 4);
+        deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain("battle"), character_id: "a".to_string(), stake: Amount::from_tokens(3) });
+        deliver(&mut state, &mut runtime, Message::QueueLeft { character_id: "a".to_string() });
+        assert_eq!((*state.battle_token_balance.get(), locked(&state, "a")), (Amount::from_tokens(7), Some(Amount::from_tokens(3))));
+
+        deliver(&mut state, &mut runtime, Message::UpdatePlayerStats {
+            player,
+            character_id: "a".to_string(),
+            won: true,
+            payout: Amount::from_tokens(5),
+            xp_gained: 0,
+            elo_change: 0,
+            battle_chain: chain("battle"),
+            rules_digest: 0,
+            end_reason: BattleEndReason::Knockout,
+            item_drop: None,
+            combat_stats: CombatStats::default(),
+            win_streak: 0,
+        });
+        assert_eq!((*state.battle_token_balance.get(), locked(&state, "a")), (Amount::from_tokens(12), None));
+    }
+
+    #[test]
+    fn series_hold_the_character_between_games_and_pay_out_of_the_pot() {
+        let (mut state, mut runtime) = setup(1);
+        let player = state.owner.get().unwrap();
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner: player, amount: Amount::from_tokens(10) });
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Amount::from_tokens(3),
+            mode: QueueMode::Casual,
+            best_of: 3,
+        });
+
+        // The pot takes the matched stake and the rest is unlocked
+        deliver(&mut state, &mut runtime, Message::SeriesStarted { series_id: 1, character_id: "a".to_string(), stake: Amount::from_tokens(2) });
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(8));
+        assert!(state.locked_stakes.indices().blocking_wait().unwrap().is_empty());
+
+        for game in ["game-1", "game-2"] {
+            deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain(game), character_id: "a".to_string(), stake: Amount::ZERO });
+            deliver(&mut state, &mut runtime, Message::UpdatePlayerStats {
+                player,
+                character_id: "a".to_string(),
+                won: true,
+                payout: Amount::ZERO,
+                xp_gained: 0,
+                elo_change: 0,
+                battle_chain: chain(game),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                item_drop: None,
+                combat_stats: CombatStats::default(),
+                win_streak: 0,
+            });
+            let held = state.active_engagements.get("a").blocking_wait().unwrap().unwrap();
+            assert_eq!((held.chain, held.kind), (chain("lobby"), EngagementKind::Series));
+        }
+        assert_eq!(state.player_stats.get().wins, 2);
+
+        for _ in 0..2 {
+            deliver(&mut state, &mut runtime, Message::SeriesEnded { series_id: 1, character_id: "a".to_string(), payout: Amount::from_millis(3_600) });
+        }
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_millis(11_600));
+        assert!(!state.is_engaged("a").blocking_wait());
+    }
+
+    #[test]
+    fn draws_are_counted_apart_and_keep_the_streak() {
+        let (mut state, mut runtime) = setup(1);
+        let player = state.owner.get().unwrap();
+        for (battle, end_reason) in [("first", BattleEndReason::Knockout), ("second", BattleEndReason::Draw)] {
+            deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain(battle), character_id: "a".to_string(), stake: Amount::ZERO });
+            deliver(&mut state, &mut runtime, Message::UpdatePlayerStats {
+                player,
+                character_id: "a".to_string(),
+                won: true,
+                payout: Amount::from_tokens(1),
+                xp_gained: 0,
+                elo_change: 0,
+                battle_chain: chain(battle),
+                rules_digest: 0,
+                end_reason,
+                item_drop: None,
+                combat_stats: CombatStats::default(),
+                // The lobby leaves a streak as it was after a draw
+                win_streak: 1,
+            });
+        }
+
+        let stats = state.player_stats.get();
+        assert_eq!((stats.total_battles, stats.wins, stats.losses, stats.draws), (2, 1, 0, 1));
+        assert_eq!((stats.current_streak, stats.total_earnings), (1, Amount::from_tokens(1)));
+        let record = state.battle_history.get(&chain("second")).blocking_wait().unwrap().unwrap();
+        assert_eq!(record.result, crate::state::BattleResult::Draw);
+    }
+
+    #[test]
+    fn engagements_are_capped_and_exclusive_per_character() {
+        let (mut state, mut runtime) = setup(2);
+
+        queue_and_match(&mut state, &mut runtime, "a");
+
+        // The same character cannot queue while it is fighting
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+        });
+        assert_eq!(state.active_engagements.get("a").blocking_wait().unwrap().unwrap().chain, chain("a"));
+
+        queue_and_match(&mut state, &mut runtime, "b");
+
+        // A third concurrent engagement exceeds the cap
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "c".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+        });
+        assert!(!state.is_engaged("c").blocking_wait());
+        assert_eq!(join_requests(&mut runtime), 2);
+    }
+
+    #[test]
+    fn characters_queue_at_once_with_their_own_stakes() {
+        let (mut state, mut runtime) = setup(2);
+        let player = state.owner.get().unwrap();
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner: player, amount: Amount::from_tokens(10) });
+        for (id, tokens) in [("a", 2), ("b", 3)] {
+            operate(&mut state, &mut runtime, Operation::JoinQueue {
+                character_id: id.to_string(),
+                stake: Amount::from_tokens(tokens),
+                mode: QueueMode::Casual,
+                best_of: 1,
+            });
+        }
+        assert_eq!(join_requests(&mut runtime), 2);
+
+        // The lobby queues each character with its own entry, so both hold their stakes
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(5));
+        for (id, tokens) in [("a", 2), ("b", 3)] {
+            assert_eq!(state.locked_stakes.get(id).blocking_wait().unwrap(), Some(Amount::from_tokens(tokens)));
+            assert_eq!(state.active_engagements.get(id).blocking_wait().unwrap().unwrap().chain, chain("lobby"));
+        }
+
+        // Releasing one leaves the other queued
+        deliver(&mut state, &mut runtime, Message::QueueLeft { character_id: "b".to_string() });
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(8));
+        assert!(state.is_engaged("a").blocking_wait() && !state.is_engaged("b").blocking_wait());
+    }
+
+    #[test]
+    fn ranked_gates_are_checked_before_engaging() {
+        let (mut state, mut runtime) = setup(2);
+
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Ranked,
+            best_of: 1,
+        });
+        assert!(!state.is_engaged("a").blocking_wait());
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 1);
+        assert_eq!(join_requests(&mut runtime), 0);
+
+        state.ranked_gates.set(RankedGates { min_ranked_level: 1, min_account_battles: 0 });
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Ranked,
+            best_of: 1,
+        });
+        assert_eq!(join_requests(&mut runtime), 1);
+    }
+
+    #[test]
+    fn staked_joins_are_gated_on_progression_until_a_practice_win() {
+        let (mut state, mut runtime) = setup(2);
+        state.battle_token_balance.set(Amount::from_tokens(5));
+        state.progression.set(ProgressionGates { min_wins: 1, min_level: 3 });
+        let join = |stake| Operation::JoinQueue { character_id: "a".to_string(), stake, mode: QueueMode::Casual, best_of: 1 };
+
+        let response = operate(&mut state, &mut runtime, join(Amount::from_tokens(1)));
+        assert_eq!(response, OperationResponse::rejected("progression_wins_below_1_or_level_below_3"));
+        assert_eq!((join_requests(&mut runtime), *state.battle_token_balance.get()), (0, Amount::from_tokens(5)));
+        operate(&mut state, &mut runtime, join(Amount::ZERO));
+        assert_eq!(join_requests(&mut runtime), 1);
+
+        // A practice win opens staked queues
+        deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain("practice"), character_id: "a".to_string(), stake: Amount::ZERO });
+        deliver(&mut state, &mut runtime, Message::PracticeBattleEnded { battle_chain: chain("practice"), character_id: "a".to_string(), won: true, xp_gained: 0 });
+        assert_eq!(*state.progression_wins.get(), 1);
+        operate(&mut state, &mut runtime, join(Amount::from_tokens(1)));
+        assert_eq!(join_requests(&mut runtime), 2);
+    }
+
+    #[test]
+    fn xp_buys_levels_with_class_gains_up_to_the_cap() {
+        let (mut state, mut runtime) = setup(1);
+        state.leveling_config.set(LevelingConfig { level_cap: 4 });
+        let mut character = state.characters.get("a").blocking_wait().unwrap().unwrap();
+        character.xp = 1_500;
+        let minted = character.clone();
+        state.characters.insert("a", character).unwrap();
+        let level_up = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, xp_to_spend| {
+            operate(state, runtime, Operation::LevelUpCharacter { character_id: "a".to_string(), xp_to_spend });
+        };
+        let rejected = |state: &PlayerState, reason: &str| {
+            let key = RejectionKey::new("LevelUpCharacter", reason, state.owner.get().unwrap());
+            state.rejections.contains_key(&key).blocking_wait().unwrap()
+        };
+
+        // 100 + 300 reach level 3; the other 50 stay unspent
+        level_up(&mut state, &mut runtime, 450);
+        let character = state.characters.get("a").blocking_wait().unwrap().unwrap();
+        let gains = LevelGains::of(CharacterClass::Warrior);
+        assert_eq!((character.level, character.xp), (3, 1_100));
+        assert_eq!(character.hp_max, minted.hp_max + 2 * gains.hp_max);
+        assert_eq!(character.max_damage, minted.max_damage + 2 * gains.max_damage);
+        assert_eq!(character.crit_chance, minted.crit_chance.saturating_add(gains.crit_chance.saturating_mul(2)));
+        assert_eq!(character.defense, minted.defense + 2 * gains.defense);
+        let leveled: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match &request.message {
+                Message::CharacterUpdated { snapshot, .. } => Some((request.destination, snapshot.level, snapshot.hp_max)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(leveled, [(chain("lobby"), 3, character.hp_max)]);
+
+        // Spending more than the character has only spends what it has
+        level_up(&mut state, &mut runtime, 10_000);
+        let character = state.characters.get("a").blocking_wait().unwrap().unwrap();
+        assert_eq!((character.level, character.xp), (4, 500));
+
+        level_up(&mut state, &mut runtime, 500);
+        assert!(rejected(&state, "level_cap_reached"));
+        let response = operate(&mut state, &mut runtime, Operation::LevelUpCharacter { character_id: "b".to_string(), xp_to_spend: 100 });
+        assert!(rejected(&state, "insufficient_xp"));
+        // Refusals answer the signer with the reason they were logged under
+        assert_eq!(response, OperationResponse::rejected("insufficient_xp"));
+        let response = operate(&mut state, &mut runtime, Operation::LevelUpCharacter { character_id: "z".to_string(), xp_to_spend: 100 });
+        assert_eq!(response, OperationResponse::rejected("unknown_character"));
+        assert!(rejected(&state, "unknown_character"));
+    }
+
+    #[test]
+    fn characters_change_hands_with_their_full_record() {
+        let (mut alice, mut alice_runtime) = setup(1);
+        let (mut bob, mut bob_runtime) = setup_as("bob", "bob-chain", 1);
+        let bob_owner = bob.owner.get().unwrap();
+        bob.characters.remove("a").unwrap();
+        operate(&mut alice, &mut alice_runtime, Operation::SetActiveCharacter { character_id: "a".to_string() });
+        let mut character = alice.characters.get("a").blocking_wait().unwrap().unwrap();
+        character.xp = 321;
+        alice.characters.insert("a", character.clone()).unwrap();
+
+        operate(&mut alice, &mut alice_runtime, Operation::TransferCharacter {
+            character_id: "a".to_string(),
+            to_owner: bob_owner,
+            to_chain: chain("bob-chain"),
+        });
+        assert!(!alice.characters.contains_key("a").blocking_wait().unwrap());
+        assert_eq!(*alice.active_character.get(), None);
+        let (to_lobby, to_bob): (Vec<_>, Vec<_>) = sent(&mut alice_runtime).into_iter()
+            .partition(|(destination, _, _)| *destination == chain("lobby"));
+        let [(destination, tracked, transfer)] = <[_; 1]>::try_from(to_bob).unwrap();
+        assert_eq!((destination, tracked), (chain("bob-chain"), true));
+        // The lobby's roster hands the character over once bob's chain reports holding it
+        assert!(matches!(to_lobby.last(), Some((_, _, Message::CharacterMoved { character_id, to_chain }))
+            if character_id == "a" && *to_chain == chain("bob-chain")));
+
+        deliver_from(&mut bob, &mut bob_runtime, "player", false, transfer);
+        let received = bob.characters.get("a").blocking_wait().unwrap().unwrap();
+        assert_eq!(received.owner, bob_owner);
+        assert_eq!(received.record(), character.record());
+        assert!(matches!(sent(&mut bob_runtime).pop(), Some((destination, _, Message::CharacterUpdated { player, snapshot }))
+            if destination == chain("lobby") && player == bob_owner && snapshot.nft_id == "a"));
+
+        // Characters in battle stay put; a refused transfer comes back as it left
+        queue_and_match(&mut alice, &mut alice_runtime, "b");
+        let transfer_b = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>| {
+            operate(state, runtime, Operation::TransferCharacter {
+                character_id: "b".to_string(),
+                to_owner: bob_owner,
+                to_chain: chain("bob-chain"),
+            });
+        };
+        transfer_b(&mut alice, &mut alice_runtime);
+        let key = RejectionKey::new("TransferCharacter", "character_engaged", alice.owner.get().unwrap());
+        assert!(alice.rejections.contains_key(&key).blocking_wait().unwrap());
+
+        alice.active_engagements.remove("b").unwrap();
+        transfer_b(&mut alice, &mut alice_runtime);
+        let (_, _, bounced) = sent(&mut alice_runtime).pop().unwrap();
+        deliver_from(&mut alice, &mut alice_runtime, "bob-chain", true, bounced);
+        assert!(alice.characters.contains_key("b").blocking_wait().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Character sent to a chain its recipient does not own")]
+    fn characters_for_another_account_are_refused() {
+        let (mut alice, mut alice_runtime) = setup(1);
+        let (mut bob, mut bob_runtime) = setup_as("bob", "bob-chain", 1);
+        operate(&mut alice, &mut alice_runtime, Operation::TransferCharacter {
+            character_id: "a".to_string(),
+            to_owner: AccountOwner::from(CryptoHash::test_hash("mallory")),
+            to_chain: chain("bob-chain"),
+        });
+        let (_, _, transfer) = sent(&mut alice_runtime).pop().unwrap();
+        deliver_from(&mut bob, &mut bob_runtime, "player", false, transfer);
+    }
+
+    #[test]
+    fn sales_escrow_payment_until_the_character_arrives() {
+        let (mut seller, mut seller_runtime) = setup(1);
+        let (mut buyer, mut buyer_runtime) = setup_as("bob", "bob-chain", 1);
+        buyer.battle_token_balance.set(Amount::from_tokens(10));
+        for (id, tokens) in [("a", 5), ("b", 4)] {
+            operate(&mut seller, &mut seller_runtime, Operation::ListCharacterForSale {
+                character_id: id.to_string(),
+                price: Amount::from_tokens(tokens),
+            });
+        }
+        // Both chains minted the same ids; the buyer's own would block the purchases
+        for id in ["a", "b"] {
+            buyer.characters.remove(id).unwrap();
+        }
+        let buy = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, id: &str, tokens| {
+            operate(state, runtime, Operation::BuyCharacter {
+                seller_chain: chain("player"),
+                character_id: id.to_string(),
+                price: Amount::from_tokens(tokens),
+            });
+            sent(runtime).pop().map(|(_, _, message)| message)
+        };
+
+        let offer = buy(&mut buyer, &mut buyer_runtime, "a", 5).unwrap();
+        assert_eq!(*buyer.battle_token_balance.get(), Amount::from_tokens(5));
+        deliver_from(&mut seller, &mut seller_runtime, "bob-chain", false, offer);
+        assert_eq!(*seller.battle_token_balance.get(), Amount::from_tokens(5));
+        assert!(!seller.characters.contains_key("a").blocking_wait().unwrap());
+        assert!(!seller.character_listings.contains_key("a").blocking_wait().unwrap());
+
+        let (_, tracked, delivery) = sent(&mut seller_runtime).pop().unwrap();
+        assert!(tracked);
+        deliver_from(&mut buyer, &mut buyer_runtime, "player", false, delivery);
+        assert!(buyer.characters.contains_key("a").blocking_wait().unwrap());
+        assert_eq!(buyer.purchase_escrows.count().blocking_wait().unwrap(), 0);
+
+        // An offer below the asking price is refused and the escrow refunded
+        let lowball = buy(&mut buyer, &mut buyer_runtime, "b", 3).unwrap();
+        assert_eq!(*buyer.battle_token_balance.get(), Amount::from_tokens(2));
+        deliver_from(&mut seller, &mut seller_runtime, "bob-chain", false, lowball);
+        let (_, _, refusal) = sent(&mut seller_runtime).pop().unwrap();
+        assert!(matches!(refusal, Message::PurchaseRefused { .. }));
+        deliver_from(&mut buyer, &mut buyer_runtime, "player", false, refusal);
+        assert_eq!(*buyer.battle_token_balance.get(), Amount::from_tokens(5));
+        assert!(seller.characters.contains_key("b").blocking_wait().unwrap());
+
+        buy(&mut buyer, &mut buyer_runtime, "b", 6);
+        let key = RejectionKey::new("BuyCharacter", "insufficient_balance", buyer.owner.get().unwrap());
+        assert!(buyer.rejections.contains_key(&key).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn tournament_entries_hold_the_fee_and_character_until_the_lobby_lets_go() {
+        let (mut state, mut runtime) = setup(1);
+        state.battle_token_balance.set(Amount::from_tokens(3));
+        let enter = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, id: &str| {
+            operate(state, runtime, Operation::JoinTournament {
+                tournament_id: 1,
+                character_id: id.to_string(),
+                entry_fee: Amount::from_tokens(2),
+            });
+        };
+
+        enter(&mut state, &mut runtime, "a");
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(1));
+        assert!(matches!(sent(&mut runtime).pop(), Some((destination, _, Message::RequestJoinTournament { .. })) if destination == chain("lobby")));
+        enter(&mut state, &mut runtime, "b");
+        let key = RejectionKey::new("JoinTournament", "insufficient_balance", state.owner.get().unwrap());
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+        assert!(state.is_engaged("a").blocking_wait());
+
+        // Only the lobby pays out, and paying out frees the character
+        let exit = || Message::TournamentExited { tournament_id: 1, character_id: "a".to_string(), payout: Amount::from_tokens(4) };
+        deliver_from(&mut state, &mut runtime, "mallory", false, exit());
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(1));
+        deliver(&mut state, &mut runtime, exit());
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(5));
+        assert!(!state.is_engaged("a").blocking_wait());
+    }
+
+    /// Deliver the last message `runtime` sent to `state`, as coming from the chain `origin`
+    fn forward(
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        origin: &str,
+        state: &mut PlayerState,
+        to_runtime: &mut ContractRuntime<crate::MajorulesContract>,
+    ) {
+        let (_, _, message) = sent(runtime).pop().unwrap();
+        deliver_from(state, to_runtime, origin, false, message);
+    }
+
+    #[test]
+    fn friends_form_parties_that_queue_for_teams_together() {
+        let (mut alice, mut alice_runtime) = setup_as("alice", "alice-chain", 2);
+        let (mut bob, mut bob_runtime) = setup_as("bob", "bob-chain", 2);
+        let (alice_owner, bob_owner) = (alice.owner.get().unwrap(), bob.owner.get().unwrap());
+        let rejected = |state: &PlayerState, operation: &str, reason: &str| {
+            let key = RejectionKey::new(operation, reason, state.owner.get().unwrap());
+            state.rejections.contains_key(&key).blocking_wait().unwrap()
+        };
+
+        // Parties are for friends, and friendship takes both sides
+        operate(&mut alice, &mut alice_runtime, Operation::InviteToParty { to_chain: chain("bob-chain") });
+        assert!(rejected(&alice, "InviteToParty", "not_a_friend"));
+        operate(&mut alice, &mut alice_runtime, Operation::SendFriendRequest { to_chain: chain("bob-chain") });
+        forward(&mut alice_runtime, "alice-chain", &mut bob, &mut bob_runtime);
+        operate(&mut alice, &mut alice_runtime, Operation::SendFriendRequest { to_chain: chain("bob-chain") });
+        assert!(rejected(&alice, "SendFriendRequest", "request_pending"));
+        assert_eq!(bob.friend_requests.get(&chain("alice-chain")).blocking_wait().unwrap(), Some(alice_owner));
+        operate(&mut bob, &mut bob_runtime, Operation::AcceptFriend { from_chain: chain("alice-chain") });
+        forward(&mut bob_runtime, "bob-chain", &mut alice, &mut alice_runtime);
+        assert_eq!(alice.friends.get(&chain("bob-chain")).blocking_wait().unwrap(), Some(bob_owner));
+        assert_eq!(bob.friends.get(&chain("alice-chain")).blocking_wait().unwrap(), Some(alice_owner));
+        assert!(bob.friend_requests.indices().blocking_wait().unwrap().is_empty());
+
+        // Inviting founds alice's party; bob is in once she announces it with him
+        operate(&mut alice, &mut alice_runtime, Operation::InviteToParty { to_chain: chain("bob-chain") });
+        forward(&mut alice_runtime, "alice-chain", &mut bob, &mut bob_runtime);
+        operate(&mut bob, &mut bob_runtime, Operation::AcceptPartyInvite { leader_chain: chain("alice-chain") });
+        forward(&mut bob_runtime, "bob-chain", &mut alice, &mut alice_runtime);
+        forward(&mut alice_runtime, "alice-chain", &mut bob, &mut bob_runtime);
+        let party = alice.party.get().clone().unwrap();
+        assert_eq!(party.leader, chain("alice-chain"));
+        assert_eq!(party.members.iter().map(|member| (member.owner, member.chain)).collect::<Vec<_>>(), [
+            (alice_owner, chain("alice-chain")),
+            (bob_owner, chain("bob-chain")),
+        ]);
+        assert_eq!(*bob.party.get(), Some(party.clone()));
+        deliver_from(&mut bob, &mut bob_runtime, "mallory", false, Message::PartyUpdated { party: None });
+        assert_eq!(*bob.party.get(), Some(party));
+        operate(&mut bob, &mut bob_runtime, Operation::InviteToParty { to_chain: chain("alice-chain") });
+        assert!(rejected(&bob, "InviteToParty", "not_party_leader"));
+
+        // The team queue hears of the party; other queues do not
+        let join = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, mode: QueueMode| {
+            operate(state, runtime, Operation::JoinQueue { character_id: "a".to_string(), stake: Amount::ZERO, mode, best_of: 1 });
+            match sent(runtime).pop() {
+                Some((_, _, Message::RequestJoinQueue { party, .. })) => party,
+                other => panic!("expected a queue request, got {other:?}"),
+            }
+        };
+        assert_eq!(join(&mut bob, &mut bob_runtime, QueueMode::Teams), Some(chain("alice-chain")));
+        deliver(&mut bob, &mut bob_runtime, Message::QueueLeft { character_id: "a".to_string() });
+        assert_eq!(join(&mut bob, &mut bob_runtime, QueueMode::Casual), None);
+
+        // A member leaving tells the leader; the leader leaving disbands what is left
+        operate(&mut bob, &mut bob_runtime, Operation::LeaveParty);
+        assert_eq!(*bob.party.get(), None);
+        forward(&mut bob_runtime, "bob-chain", &mut alice, &mut alice_runtime);
+        assert_eq!(alice.party.get().as_ref().map(|party| party.members.len()), Some(1));
+        operate(&mut alice, &mut alice_runtime, Operation::LeaveParty);
+        assert_eq!(*alice.party.get(), None);
+        operate(&mut alice, &mut alice_runtime, Operation::LeaveParty);
+        assert!(rejected(&alice, "LeaveParty", "not_in_party"));
+    }
+
+    fn queue_hint(state: &PlayerState, character_id: &str, mode: QueueMode) -> Option<String> {
+        let owner = state.owner.get().unwrap();
+        player_actions(state, owner).blocking_wait().unwrap().into_iter()
+            .map(|action| match action {
+                PlayerAction::CanQueue(hint) => hint,
+            })
+            .find(|hint| hint.character_id == character_id && hint.mode == mode)
+            .expect("Every character is hinted in every mode")
+            .blocked_by
+    }
+
+    #[test]
+    fn queue_hints_match_the_joins_a_player_chain_takes() {
+        let (mut state, mut runtime) = setup(2);
+        let join = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, mode: QueueMode| {
+            let before = join_requests(runtime);
+            operate(state, runtime, Operation::JoinQueue { character_id: "a".to_string(), stake: Default::default(), mode, best_of: 1 });
+            join_requests(runtime) > before
+        };
+        assert!(player_actions(&state, AccountOwner::from(CryptoHash::test_hash("stranger"))).blocking_wait().unwrap().is_empty());
+
+        assert_eq!(queue_hint(&state, "a", QueueMode::Ranked).as_deref(), Some("ranked_level_below_5"));
+        assert!(!join(&mut state, &mut runtime, QueueMode::Ranked));
+        let key = RejectionKey::new("JoinQueue", "ranked_level_below_5", state.owner.get().unwrap());
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+
+        assert_eq!(queue_hint(&state, "a", QueueMode::Casual), None);
+        assert!(join(&mut state, &mut runtime, QueueMode::Casual));
+
+        // The queued character is held; the others may still queue
+        assert_eq!(queue_hint(&state, "a", QueueMode::Casual).as_deref(), Some("character_engaged"));
+        assert_eq!(queue_hint(&state, "b", QueueMode::Casual), None);
+        assert!(!join(&mut state, &mut runtime, QueueMode::Casual));
+        let key = RejectionKey::new("JoinQueue", "character_engaged", state.owner.get().unwrap());
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn private_battles_hold_the_character_until_matched() {
+        let (mut state, mut runtime) = setup(2);
+
+        operate(&mut state, &mut runtime, Operation::CreatePrivateBattle { character_id: "a".to_string(), stake: Amount::from_tokens(1), invite: [0; 32] });
+        let engagement = state.active_engagements.get("a").blocking_wait().unwrap().unwrap();
+        assert_eq!(engagement.chain, chain("lobby"));
+        assert!(runtime.created_send_message_requests().iter()
+            .any(|request| matches!(request.message, Message::RequestCreatePrivateBattle { .. })));
+
+        // The held character cannot also join someone else's battle
+        operate(&mut state, &mut runtime, Operation::JoinPrivateBattle { battle_id: 7, invite_secret: [0; 32], character_id: "a".to_string(), stake: Amount::from_tokens(1) });
+        let key = RejectionKey::new("JoinPrivateBattle", "character_engaged", state.owner.get().unwrap());
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+
+        deliver(&mut state, &mut runtime, Message::PrivateBattleCreated { battle_id: 42 });
+        assert_eq!(*state.hosted_private_battle.get(), Some(42));
+        deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain("private"), character_id: "a".to_string(), stake: Amount::ZERO });
+        assert_eq!(*state.hosted_private_battle.get(), None);
+        assert_eq!(state.active_engagements.get("a").blocking_wait().unwrap().unwrap().chain, chain("private"));
+    }
+
+    #[test]
+    fn winnings_from_the_lobby_credit_the_owner() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        let winnings = |bettor| Message::DistributeWinnings { bettor, amount: Amount::from_tokens(2), market_id: 1 };
+
+        deliver(&mut state, &mut runtime, winnings(owner));
+        deliver(&mut state, &mut runtime, winnings(AccountOwner::from(CryptoHash::test_hash("other"))));
+        runtime.set_message_origin_chain_id(chain("mallory"));
+        PlayerContract::execute_message(&mut state, &mut runtime, winnings(owner)).blocking_wait();
+
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(2));
+    }
+
+    #[test]
+    fn token_transfers_debit_up_front_and_come_back_if_bounced() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        let bob = AccountOwner::from(CryptoHash::test_hash("bob"));
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner, amount: Amount::from_tokens(5) });
+        deliver_from(&mut state, &mut runtime, "mallory", false, Message::CreditTokens { owner, amount: Amount::from_tokens(5) });
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(5));
+
+        operate(&mut state, &mut runtime, Operation::TransferTokens { to: bob, amount: Amount::from_tokens(3) });
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(2));
+        let (destination, tracked, request) = sent(&mut runtime).pop().unwrap();
+        assert_eq!(destination, chain("lobby"));
+        assert!(tracked);
+        assert!(matches!(request, Message::RequestTokenTransfer { from, to, .. } if from == owner && to == bob));
+
+        operate(&mut state, &mut runtime, Operation::TransferTokens { to: bob, amount: Amount::from_tokens(3) });
+        let key = RejectionKey::new("TransferTokens", "insufficient_balance", owner);
+        assert!(state.rejections.contains_key(&key).blocking_wait().unwrap());
+
+        deliver_from(&mut state, &mut runtime, "lobby", true, request);
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(5));
+    }
+
+    #[test]
+    fn bets_stay_held_until_the_lobby_places_or_refuses_them() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner, amount: Amount::from_tokens(10) });
+        let bet = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, market_id: u64| {
+            operate(state, runtime, Operation::PlaceBetOnMarket { market_id, predicted_winner: chain("alice"), amount: Amount::from_tokens(3) });
+        };
+        let held = |state: &PlayerState, market_id: u64| state.pending_bets.get(&market_id).blocking_wait().unwrap();
+
+        bet(&mut state, &mut runtime, 1);
+        bet(&mut state, &mut runtime, 1);
+        assert!(state.rejections.contains_key(&RejectionKey::new("PlaceBetOnMarket", "bet_pending", owner)).blocking_wait().unwrap());
+        let (destination, tracked, request) = sent(&mut runtime).pop().unwrap();
+        assert!(destination == chain("lobby") && tracked);
+        assert!(matches!(request, Message::RequestPlaceBet { bettor, market_id: 1, .. } if bettor == owner));
+        assert_eq!((*state.battle_token_balance.get(), held(&state, 1)), (Amount::from_tokens(7), Some(Amount::from_tokens(3))));
+
+        // Only the lobby answers; a placed bet stays spent
+        deliver_from(&mut state, &mut runtime, "mallory", false, Message::BetRefused { market_id: 1 });
+        assert_eq!(held(&state, 1), Some(Amount::from_tokens(3)));
+        deliver(&mut state, &mut runtime, Message::BetPlaced { market_id: 1 });
+        assert_eq!((*state.battle_token_balance.get(), held(&state, 1)), (Amount::from_tokens(7), None));
+
+        // Refused and bounced bets come back, once
+        bet(&mut state, &mut runtime, 2);
+        bet(&mut state, &mut runtime, 3);
+        deliver(&mut state, &mut runtime, Message::BetRefused { market_id: 2 });
+        deliver(&mut state, &mut runtime, Message::BetRefused { market_id: 2 });
+        let (_, _, request) = sent(&mut runtime).pop().unwrap();
+        deliver_from(&mut state, &mut runtime, "lobby", true, request);
+        assert_eq!((*state.battle_token_balance.get(), held(&state, 2), held(&state, 3)), (Amount::from_tokens(7), None, None));
+    }
+
+    fn mint(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, character_id: &str) {
+        operate(state, runtime, Operation::MintCharacter {
+            character_id: character_id.to_string(),
+            class: "mage".to_string(),
+        });
+    }
+
+    #[test]
+    fn minting_is_capped_paced_and_paid_for() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        state.mint_terms.set(MintTerms { cost: Amount::from_tokens(5), free_mints: 3, roster_cap: 4, cooldown_secs: 60, item_cost: Amount::ZERO });
+        let rejected = |state: &PlayerState, reason: &str| {
+            state.rejections.contains_key(&RejectionKey::new("MintCharacter", reason, owner)).blocking_wait().unwrap()
+        };
+
+        let long = "x".repeat(MAX_CHARACTER_ID_LEN + 1);
+        for character_id in ["", "two words", long.as_str()] {
+            mint(&mut state, &mut runtime, character_id);
+            assert!(!state.characters.contains_key(character_id).blocking_wait().unwrap());
+        }
+        assert!(rejected(&state, "invalid_character_id"));
+        mint(&mut state, &mut runtime, "d");
+        assert!(rejected(&state, "mint_cooldown"));
+        runtime.set_system_time(Timestamp::from(60_000_000));
+        mint(&mut state, &mut runtime, "a");
+        mint(&mut state, &mut runtime, "d");
+        assert!(rejected(&state, "character_exists") && rejected(&state, "insufficient_balance"));
+
+        state.battle_token_balance.set(Amount::from_tokens(7));
+        mint(&mut state, &mut runtime, "d");
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(2));
+        assert!(runtime.created_send_message_requests().iter().any(|request| request.destination == chain("lobby")
+            && matches!(request.message, Message::MintFeePaid { amount, .. } if amount == Amount::from_tokens(5))));
+        assert!(runtime.created_send_message_requests().iter().any(|request| request.destination == chain("lobby")
+            && matches!(&request.message, Message::CharacterMinted { player, snapshot } if *player == owner && snapshot.nft_id == "d")));
+        let minted = state.characters.get("d").blocking_wait().unwrap().unwrap();
+        let traits = [minted.attack_bps, minted.defense_bps, minted.crit_bps];
+        assert!(traits.iter().all(|bps| (0..=max_trait_bps(minted.rarity)).contains(bps)));
+
+        runtime.set_system_time(Timestamp::from(120_000_000));
+        mint(&mut state, &mut runtime, "e");
+        assert!(rejected(&state, "roster_full"));
+        assert_eq!(*state.character_count.get(), 4);
+    }
+
+    #[test]
+    fn every_class_survives_minting_and_snapshots() {
+        let (mut state, mut runtime) = setup(1);
+
+        for class in CharacterClass::ALL {
+            let character_id = format!("{class:?}");
+            operate(&mut state, &mut runtime, Operation::MintCharacter {
+                character_id: character_id.clone(),
+                class: character_id.to_lowercase(),
+            });
+            let character = state.characters.get(&character_id).blocking_wait().unwrap().unwrap();
+            assert_eq!(CharacterClass::from(character.class), class);
+            assert_eq!(PlayerContract::snapshot(&state, character).blocking_wait().class, class);
+        }
+    }
+
+    #[test]
+    fn ranked_defeats_cost_lives_until_the_character_is_revived() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        state.ranked_gates.set(RankedGates { min_ranked_level: 1, min_account_battles: 0 });
+        let fight = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, mode: QueueMode| {
+            operate(state, runtime, Operation::JoinQueue { character_id: "a".to_string(), stake: Default::default(), mode, best_of: 1 });
+            deliver(state, runtime, Message::BattleMatched { battle_chain: chain(battle), character_id: "a".to_string(), stake: Amount::ZERO });
+            deliver(state, runtime, Message::UpdatePlayerStats {
+                player: owner,
+                character_id: "a".to_string(),
+                won: false,
+                payout: Amount::ZERO,
+                xp_gained: 0,
+                elo_change: 0,
+                battle_chain: chain(battle),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                item_drop: None,
+                combat_stats: CombatStats::default(),
+                win_streak: 0,
+            });
+        };
+        let lives = |state: &PlayerState| state.characters.get("a").blocking_wait().unwrap().unwrap().lives_remaining;
+
+        fight(&mut state, &mut runtime, "casual", QueueMode::Casual);
+        assert_eq!(lives(&state), STARTING_LIVES);
+        for battle in ["r1", "r2", "r3"] {
+            fight(&mut state, &mut runtime, battle, QueueMode::Ranked);
+        }
+        assert_eq!(lives(&state), 0);
+        let reported: Vec<_> = runtime.created_send_message_requests().iter()
+            .filter_map(|request| match request.message {
+                Message::CharacterLivesChanged { lives_remaining, .. } => Some(lives_remaining),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reported, [2, 1, 0]);
+
+        // A dead character stays out of battles until paid for again
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+        });
+        assert!(state.rejections.contains_key(&RejectionKey::new("JoinQueue", "character_dead", owner)).blocking_wait().unwrap());
+        state.mint_terms.set(MintTerms { cost: Amount::from_tokens(4), ..MintTerms::default() });
+        operate(&mut state, &mut runtime, Operation::ReviveCharacter { character_id: "a".to_string() });
+        assert!(state.rejections.contains_key(&RejectionKey::new("ReviveCharacter", "insufficient_balance", owner)).blocking_wait().unwrap());
+
+        state.battle_token_balance.set(Amount::from_tokens(5));
+        operate(&mut state, &mut runtime, Operation::ReviveCharacter { character_id: "a".to_string() });
+        assert_eq!((lives(&state), *state.battle_token_balance.get()), (STARTING_LIVES, Amount::from_tokens(1)));
+        assert!(runtime.created_send_message_requests().iter().any(|request| matches!(
+            request.message,
+            Message::CharacterLivesChanged { lives_remaining: STARTING_LIVES, revive_fee, .. } if revive_fee == Amount::from_tokens(4)
+        )));
+    }
+
+    fn weapon(item_id: &str, rarity: ItemRarity) -> ItemDrop {
+        ItemDrop {
+            item_id: item_id.to_string(),
+            slot: ItemSlot::Weapon,
+            rarity,
+            bonuses: PassiveMods { attack_bps: rarity.max_bonus_bps(), ..PassiveMods::default() },
+        }
+    }
+
+    fn equip(state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, character_id: &str, item_id: &str) {
+        operate(state, runtime, Operation::EquipItem {
+            character_id: character_id.to_string(),
+            item_id: item_id.to_string(),
+        });
+    }
+
+    #[test]
+    fn dropped_items_are_equipped_into_snapshots() {
+        let (mut state, mut runtime) = setup(2);
+        let player = state.owner.get().unwrap();
+        for (battle, drop) in [("first", weapon("sword", ItemRarity::Epic)), ("second", weapon("axe", ItemRarity::Rare))] {
+            deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain(battle), character_id: "a".to_string(), stake: Amount::ZERO });
+            deliver(&mut state, &mut runtime, Message::UpdatePlayerStats {
+                player,
+                character_id: "a".to_string(),
+                won: true,
+                payout: Default::default(),
+                xp_gained: 150,
+                elo_change: 0,
+                battle_chain: chain(battle),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                item_drop: Some(drop),
+                combat_stats: CombatStats::default(),
+                win_streak: 0,
+            });
+        }
+        assert_eq!(state.items.count().blocking_wait().unwrap(), 2);
+
+        equip(&mut state, &mut runtime, "a", "sword");
+        // One weapon per character, and an equipped item is locked to its character
+        equip(&mut state, &mut runtime, "a", "axe");
+        equip(&mut state, &mut runtime, "b", "sword");
+
+        let sword = state.items.get("sword").blocking_wait().unwrap().unwrap();
+        let axe = state.items.get("axe").blocking_wait().unwrap().unwrap();
+        assert_eq!((sword.equipped_on.as_deref(), axe.equipped_on), (Some("a"), None));
+        assert!(state.equipment.get(&("b".to_string(), ItemSlot::Weapon)).blocking_wait().unwrap().is_none());
+        assert_eq!(state.rejections.count().blocking_wait().unwrap(), 2);
+
+        operate(&mut state, &mut runtime, Operation::JoinQueue {
+            character_id: "a".to_string(),
+            stake: Default::default(),
+            mode: QueueMode::Casual,
+            best_of: 1,
+        });
+        let snapshot = runtime.created_send_message_requests().iter()
+            .find_map(|request| match &request.message {
+                Message::RequestJoinQueue { character_snapshot, .. } => Some(character_snapshot.clone()),
+                _ => None,
+            })
+            .expect("JoinQueue should reach the lobby");
+        let character = state.characters.get("a").blocking_wait().unwrap().unwrap();
+        assert_eq!(snapshot.attack_bps, character.attack_bps + ItemRarity::Epic.max_bonus_bps());
+        assert!(snapshot.within_equipment_bounds());
+
+        operate(&mut state, &mut runtime, Operation::UnequipItem { item_id: "sword".to_string() });
+        equip(&mut state, &mut runtime, "b", "sword");
+        let sword = state.items.get("sword").blocking_wait().unwrap().unwrap();
+        assert_eq!(sword.equipped_on.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn forged_items_cost_the_item_price_and_roll_within_their_rarity() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        state.mint_terms.set(MintTerms { item_cost: Amount::from_tokens(2), ..MintTerms::default() });
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner, amount: Amount::from_tokens(3) });
+        let forge = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, item_id: &str| {
+            operate(state, runtime, Operation::MintItem { item_id: item_id.to_string(), slot: ItemSlot::Armor });
+        };
+
+        forge(&mut state, &mut runtime, "plate");
+        forge(&mut state, &mut runtime, "plate");
+        forge(&mut state, &mut runtime, "mail");
+        assert!(state.rejections.contains_key(&RejectionKey::new("MintItem", "item_exists", owner)).blocking_wait().unwrap());
+        assert!(state.rejections.contains_key(&RejectionKey::new("MintItem", "insufficient_balance", owner)).blocking_wait().unwrap());
+        assert_eq!(*state.battle_token_balance.get(), Amount::from_tokens(1));
+        assert!(sent(&mut runtime).iter().any(|(destination, _, message)| *destination == chain("lobby")
+            && matches!(message, Message::MintFeePaid { amount, .. } if *amount == Amount::from_tokens(2))));
+
+        let plate = state.items.get("plate").blocking_wait().unwrap().unwrap();
+        assert_eq!((plate.slot, plate.bonuses.attack_bps, plate.bonuses.crit_bps), (ItemSlot::Armor, 0, 0));
+        assert!((1..=plate.rarity.max_bonus_bps()).contains(&plate.bonuses.defense_bps));
+        equip(&mut state, &mut runtime, "a", "plate");
+        assert_eq!(state.equipment.get(&("a".to_string(), ItemSlot::Armor)).blocking_wait().unwrap().as_deref(), Some("plate"));
+    }
+
+    #[test]
+    fn only_lobby_relayed_summaries_are_stored() {
+        let (mut state, mut runtime) = setup(1);
+        let summary = BattleResultSummary {
+            battle_chain: chain("final"),
+            winner: AccountOwner::from(CryptoHash::test_hash("winner")),
+            rounds_played: 3,
+            total_stake: Amount::from_tokens(2),
+            rules_digest: 7,
+            end_reason: BattleEndReason::Knockout,
+            completed_at: Timestamp::from(0),
+        };
+
+        runtime.set_message_origin_chain_id(chain("mallory"));
+        PlayerContract::execute_message(&mut state, &mut runtime, Message::BattleSummaryNotification {
+            summary: summary.clone(),
+        }).blocking_wait();
+        assert!(state.subscribed_results.get(&chain("final")).blocking_wait().unwrap().is_none());
+
+        deliver(&mut state, &mut runtime, Message::BattleSummaryNotification { summary: summary.clone() });
+        assert_eq!(state.subscribed_results.get(&chain("final")).blocking_wait().unwrap(), Some(summary.clone()));
+
+        // Muting summaries stops new ones from being stored
+        operate(&mut state, &mut runtime, Operation::SetPreferences {
+            prefs: PlayerPreferences { mute_battle_summaries: true, ..PlayerPreferences::default() },
+        });
+        let muted = BattleResultSummary { battle_chain: chain("muted"), ..summary };
+        deliver(&mut state, &mut runtime, Message::BattleSummaryNotification { summary: muted });
+        assert!(state.subscribed_results.get(&chain("muted")).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn preferences_round_trip_within_the_size_cap() {
+        let (mut state, mut runtime) = setup(1);
+        let entry = |key: &str, value: &str| PreferenceEntry { key: key.to_string(), value: value.to_string() };
+        let prefs = PlayerPreferences {
+            preferred_stance: Some("counter".to_string()),
+            auto_accept_rematch: true,
+            extra: vec![entry("theme", "dark"), entry("locale", "pt-BR")],
+            ..PlayerPreferences::default()
+        };
+
+        operate(&mut state, &mut runtime, Operation::SetPreferences { prefs: prefs.clone() });
+        assert_eq!(*state.preferences.get(), prefs);
+
+        // Oversized, overlong or unparseable preferences leave the stored ones untouched
+        let oversized = PlayerPreferences {
+            extra: (0..20).map(|index| entry(&format!("key-{index}"), &"x".repeat(MAX_PREFERENCE_ENTRY_LEN))).collect(),
+            ..PlayerPreferences::default()
+        };
+        let overlong = PlayerPreferences { extra: vec![entry("note", &"x".repeat(MAX_PREFERENCE_ENTRY_LEN + 1))], ..prefs.clone() };
+        let bad_stance = PlayerPreferences { preferred_stance: Some("reckless".to_string()), ..prefs.clone() };
+        for prefs in [oversized, overlong, bad_stance] {
+            operate(&mut state, &mut runtime, Operation::SetPreferences { prefs });
+        }
+        assert_eq!(*state.preferences.get(), prefs);
+
+        let owner = state.owner.get().unwrap();
+        for reason in ["preferences_too_large", "preference_entry_too_long", "invalid_stance"] {
+            let key = RejectionKey::new("SetPreferences", reason, owner);
+            assert!(state.rejections.contains_key(&key).blocking_wait().unwrap(), "{reason}");
+        }
+    }
+
+    #[test]
+    fn daily_quests_pay_once_when_complete_and_start_over_the_next_day() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        let win = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, battle: &str, crits: u64| {
+            deliver(state, runtime, Message::BattleMatched { battle_chain: chain(battle), character_id: "a".to_string(), stake: Amount::ZERO });
+            deliver(state, runtime, Message::UpdatePlayerStats {
+                player: owner,
+                character_id: "a".to_string(),
+                won: true,
+                payout: Amount::ZERO,
+                xp_gained: 0,
+                elo_change: 0,
+                battle_chain: chain(battle),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+                item_drop: None,
+                combat_stats: CombatStats { damage_dealt: 400, crits, ..CombatStats::default() },
+                win_streak: 0,
+            });
+        };
+        let claim = |state: &mut PlayerState, runtime: &mut ContractRuntime<crate::MajorulesContract>, quest_id: &str| {
+            operate(state, runtime, Operation::ClaimQuestReward { quest_id: quest_id.to_string(), character_id: "a".to_string() });
+        };
+        let claims = |runtime: &mut ContractRuntime<crate::MajorulesContract>| -> Vec<String> {
+            sent(runtime).into_iter()
+                .filter_map(|(_, _, message)| match message {
+                    Message::QuestRewardClaimed { quest_id, day: 0, .. } => Some(quest_id),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        win(&mut state, &mut runtime, "first", 2);
+        win(&mut state, &mut runtime, "second", 2);
+        claim(&mut state, &mut runtime, "win-3");
+        assert!(state.rejections.contains_key(&RejectionKey::new("ClaimQuestReward", "quest_incomplete", owner)).blocking_wait().unwrap());
+
+        win(&mut state, &mut runtime, "third", 2);
+        claim(&mut state, &mut runtime, "win-3");
+        claim(&mut state, &mut runtime, "win-3");
+        claim(&mut state, &mut runtime, "crit-5");
+        claim(&mut state, &mut runtime, "damage-1000");
+        claim(&mut state, &mut runtime, "daily-login");
+        for reason in ["quest_claimed", "unknown_quest"] {
+            assert!(state.rejections.contains_key(&RejectionKey::new("ClaimQuestReward", reason, owner)).blocking_wait().unwrap());
+        }
+        assert_eq!(claims(&mut runtime), ["win-3", "crit-5", "damage-1000"]);
+        assert_eq!(state.characters.get("a").blocking_wait().unwrap().unwrap().xp, 100 + 60 + 60);
+
+        // Lifetime totals keep counting, and achievements unlock once
+        let stats = state.player_stats.get();
+        assert_eq!((stats.total_crits, stats.total_damage_dealt), (6, 1_200));
+        let first_blood = state.achievements.get("first-blood").blocking_wait().unwrap();
+        assert_eq!(first_blood, Some(Timestamp::from(0)));
+        assert!(state.achievements.get("veteran").blocking_wait().unwrap().is_none());
+
+        runtime.set_system_time(Timestamp::from(majorules::time::MICROS_PER_DAY));
+        claim(&mut state, &mut runtime, "crit-5");
+        win(&mut state, &mut runtime, "fourth", 0);
+        let progress = state.quest_progress.get("win-3").blocking_wait().unwrap().unwrap();
+        assert_eq!((*state.quest_day.get(), progress.progress, progress.claimed), (1, 1, false));
+        assert_eq!(state.achievements.get("first-blood").blocking_wait().unwrap(), first_blood);
+    }
+
+    #[test]
+    fn streak_insurance_is_paid_from_the_balance_up_to_its_limit() {
+        let (mut state, mut runtime) = setup(1);
+        let owner = state.owner.get().unwrap();
+        deliver(&mut state, &mut runtime, Message::CreditTokens { owner, amount: Amount::from_tokens(3) });
+        operate(&mut state, &mut runtime, Operation::BuyStreakInsurance { max_price: Amount::from_tokens(4) });
+        assert!(state.rejections.contains_key(&RejectionKey::new("BuyStreakInsurance", "insufficient_balance", owner)).blocking_wait().unwrap());
+
+        operate(&mut state, &mut runtime, Operation::BuyStreakInsurance { max_price: Amount::from_tokens(3) });
+        assert_eq!(*state.battle_token_balance.get(), Amount::ZERO);
+        assert!(sent(&mut runtime).iter().any(|(destination, _, message)| *destination == chain("lobby")
+            && matches!(message, Message::StreakInsurancePaid { paid, .. } if *paid == Amount::from_tokens(3))));
+    }
+
+    #[test]
+    fn practice_battles_credit_xp_and_release_the_character_without_touching_stats() {
+        let (mut state, mut runtime) = setup(1);
+        operate(&mut state, &mut runtime, Operation::StartPracticeBattle { character_id: "a".to_string(), difficulty: PracticeDifficulty::Easy });
+        assert!(sent(&mut runtime).iter().any(|(destination, _, message)| *destination == chain("lobby")
+            && matches!(message, Message::RequestPracticeBattle { character_snapshot, difficulty: PracticeDifficulty::Easy, .. } if character_snapshot.nft_id == "a")));
+        deliver(&mut state, &mut runtime, Message::BattleMatched { battle_chain: chain("practice"), character_id: "a".to_string(), stake: Amount::ZERO });
+        let engagement = state.active_engagements.get("a").blocking_wait().unwrap().unwrap();
+        assert_eq!((engagement.chain, engagement.kind), (chain("practice"), EngagementKind::Battle));
+
+        // Only the lobby ends a practice battle, and only once
+        let ended = || Message::PracticeBattleEnded { battle_chain: chain("practice"), character_id: "a".to_string(), won: true, xp_gained: 12 };
+        deliver_from(&mut state, &mut runtime, "practice", false, ended());
+        assert!(state.is_engaged("a").blocking_wait());
+        deliver(&mut state, &mut runtime, ended());
+        deliver(&mut state, &mut runtime, ended());
+        assert!(!state.is_engaged("a").blocking_wait());
+        assert_eq!(state.characters.get("a").blocking_wait().unwrap().unwrap().xp, 12);
+        let stats = state.player_stats.get();
+        assert_eq!((stats.total_battles, stats.wins), (0, 0));
+        assert!(state.battle_history.get(&chain("practice")).blocking_wait().unwrap().is_none());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,132 @@
+//! Practice battles against a bot.
+//!
+//! New players have no one to fight, so a player may pit a character against a bot fighting a
+//! copy of it. Nothing is staked and nothing is rated: the battle pays only a share of the XP a
+//! regular battle would, larger the harder the bot.
+//!
+//! The bot picks its turn the moment its opponent submits one. Its roll is drawn from the
+//! turn's block and the opponent's commit data, as the turn's own seed is, so the bot's choice
+//! cannot be known before the turn is in. Its difficulty decides how much of the fight it
+//! weighs when it picks.
+
+use async_graphql::Enum;
+use linera_sdk::linera_base_types::{AccountOwner, BcsHashable, CryptoHash};
+use serde::{Deserialize, Serialize};
+
+use crate::{fees::BPS_DENOMINATOR, Stance};
+
+/// Every stance, for a bot whose battle does not lock stances to classes
+pub const STANCES: [Stance; 5] = [Stance::Balanced, Stance::Aggressive, Stance::Defensive, Stance::Berserker, Stance::Counter];
+
+/// How hard the bot plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum PracticeDifficulty {
+    /// Random stances, never a special
+    Easy,
+    /// Random stances, a special on about half the turns it is ready
+    Normal,
+    /// Turtles when low, presses a low opponent, specials whenever ready
+    Hard,
+}
+
+impl PracticeDifficulty {
+    /// Share of a regular battle's XP a practice battle awards, in basis points
+    pub fn xp_bps(self) -> u16 {
+        match self {
+            PracticeDifficulty::Easy => 2_500,
+            PracticeDifficulty::Normal => 3_500,
+            PracticeDifficulty::Hard => 5_000,
+        }
+    }
+
+    /// `xp` a regular battle would award, scaled down for practice
+    pub fn scaled_xp(self, xp: u64) -> u64 {
+        (xp as u128 * self.xp_bps() as u128 / BPS_DENOMINATOR) as u64
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PracticeBot;
+
+impl BcsHashable<'_> for PracticeBot {}
+
+/// The account the bot fights as. No one holds a key for it, so it never signs an operation
+pub fn practice_bot() -> AccountOwner {
+    AccountOwner::Address32(CryptoHash::new(&PracticeBot))
+}
+
+/// What the bot weighs when it picks a turn
+#[derive(Debug, Clone, Copy)]
+pub struct BotView<'a> {
+    /// Stances the bot's class may take
+    pub stances: &'a [Stance],
+    pub hp: u32,
+    pub hp_max: u32,
+    pub opponent_hp: u32,
+    pub opponent_hp_max: u32,
+    /// Whether the special is off cooldown
+    pub special_ready: bool,
+}
+
+/// The bot's stance and whether it uses its special, for a turn that rolled `roll`
+pub fn bot_choice(difficulty: PracticeDifficulty, roll: u64, view: &BotView) -> (Stance, bool) {
+    let random = match view.stances {
+        [] => Stance::Balanced,
+        stances => stances[(roll % stances.len() as u64) as usize],
+    };
+    let allowed = |stance: Stance| view.stances.contains(&stance);
+    match difficulty {
+        PracticeDifficulty::Easy => (random, false),
+        PracticeDifficulty::Normal => (random, view.special_ready && (roll >> 32) & 1 == 0),
+        PracticeDifficulty::Hard => {
+            let stance = if view.hp.saturating_mul(3) <= view.hp_max && allowed(Stance::Defensive) {
+                Stance::Defensive
+            } else if view.opponent_hp.saturating_mul(3) <= view.opponent_hp_max {
+                [Stance::Berserker, Stance::Aggressive].into_iter().find(|&stance| allowed(stance)).unwrap_or(random)
+            } else {
+                random
+            };
+            (stance, view.special_ready)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bot_choice, practice_bot, BotView, PracticeDifficulty};
+    use crate::{allowed_stances, CharacterClass, Stance};
+
+    fn view(stances: &[Stance], hp: u32, opponent_hp: u32) -> BotView<'_> {
+        BotView { stances, hp, hp_max: 90, opponent_hp, opponent_hp_max: 90, special_ready: true }
+    }
+
+    #[test]
+    fn bots_keep_to_their_class_and_play_harder_by_difficulty() {
+        let mage = allowed_stances(CharacterClass::Mage);
+        for roll in 0..64 {
+            for difficulty in [PracticeDifficulty::Easy, PracticeDifficulty::Normal, PracticeDifficulty::Hard] {
+                let (stance, _) = bot_choice(difficulty, roll << 29, &view(mage, 90, 90));
+                assert!(mage.contains(&stance));
+            }
+            assert!(!bot_choice(PracticeDifficulty::Easy, roll, &view(mage, 90, 90)).1);
+        }
+        let specials = (0..64u64).filter(|roll| bot_choice(PracticeDifficulty::Normal, roll << 32, &view(mage, 90, 90)).1).count();
+        assert_eq!(specials, 32);
+
+        let warrior = allowed_stances(CharacterClass::Warrior);
+        assert_eq!(bot_choice(PracticeDifficulty::Hard, 1, &view(warrior, 30, 90)), (Stance::Defensive, true));
+        assert_eq!(bot_choice(PracticeDifficulty::Hard, 1, &view(warrior, 90, 30)).0, Stance::Berserker);
+        // A class without a finishing stance falls back to aggression, and one without a
+        // defensive stance never turtles
+        let trickster = allowed_stances(CharacterClass::Trickster);
+        assert_eq!(bot_choice(PracticeDifficulty::Hard, 0, &view(trickster, 90, 30)).0, Stance::Aggressive);
+        assert_eq!(bot_choice(PracticeDifficulty::Hard, 0, &view(trickster, 30, 90)).0, trickster[0]);
+    }
+
+    #[test]
+    fn practice_pays_a_share_of_xp_to_a_fixed_bot() {
+        assert_eq!(PracticeDifficulty::Easy.scaled_xp(100), 25);
+        assert_eq!(PracticeDifficulty::Hard.scaled_xp(100), 50);
+        assert_eq!(practice_bot(), practice_bot());
+    }
+}
@@ -0,0 +1,589 @@
+use linera_sdk::{
+    linera_base_types::{Amount, AccountOwner, ChainId},
+    ContractRuntime,
+};
+
+use majorules::{
+    counters::{self, checked_accumulate, Counter},
+    odds,
+    throttle::RejectionKey,
+    Operation, OperationResponse, Message,
+};
+use crate::state::{
+    record_overflow, record_rejection, Bet, BettingLeaderboardEntry, BettorStats, Market, MarketStatus, PredictionState,
+};
+
+/// Bettors the betting leaderboard keeps
+pub const BETTING_LEADERBOARD_SIZE: usize = 100;
+
+/// Markets on their own chain. The lobby, or the treasury, opens one per battle; the battle
+/// closes it when it starts and settles it when it ends. Bettors claim their own winnings.
+pub struct PredictionContract;
+
+impl PredictionContract {
+    pub async fn execute_operation(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: Operation,
+    ) -> OperationResponse {
+        let Some(caller) = runtime.authenticated_signer() else {
+            return OperationResponse::rejected("unauthenticated");
+        };
+        match operation {
+            Operation::CreateMarket { battle_chain, player1_chain, player2_chain } => {
+                if !state.is_treasury(caller) {
+                    return Self::reject(state, runtime, "CreateMarket", "not_treasury", caller).await;
+                }
+                if !Self::create_market(state, runtime, battle_chain, player1_chain, player2_chain).await {
+                    return Self::reject(state, runtime, "CreateMarket", "duplicate_market", caller).await;
+                }
+            }
+
+            Operation::PlaceBet { market_id, predicted_winner, amount } => {
+                if let Err(reason) = Self::place_bet(state, runtime, caller, market_id, predicted_winner, amount).await {
+                    return Self::reject(state, runtime, "PlaceBet", reason, caller).await;
+                }
+            }
+
+            Operation::CloseMarket { market_id } => {
+                if !state.is_treasury(caller) {
+                    return Self::reject(state, runtime, "CloseMarket", "not_treasury", caller).await;
+                }
+                Self::close_market(state, runtime, market_id).await;
+            }
+
+            // Manual settlement for battles whose chain never reports back
+            Operation::SettleMarket { market_id, winner_chain } => {
+                if !state.is_treasury(caller) {
+                    return Self::reject(state, runtime, "SettleMarket", "not_treasury", caller).await;
+                }
+                if !Self::settle_market(state, runtime, market_id, Some(winner_chain)).await {
+                    return Self::reject(state, runtime, "SettleMarket", "not_settleable", caller).await;
+                }
+            }
+
+            Operation::ClaimWinnings { market_id } => {
+                if let Err(reason) = Self::claim_winnings(state, caller, market_id).await {
+                    return Self::reject(state, runtime, "ClaimWinnings", reason, caller).await;
+                }
+            }
+
+            _ => {
+                // Ignore operations not relevant to prediction markets
+            }
+        }
+        OperationResponse::Done
+    }
+
+    pub async fn execute_message(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        message: Message,
+    ) {
+        let sender_chain = runtime.message_origin_chain_id()
+            .expect("Message must have origin");
+        match message {
+            Message::CreatePredictionMarket { battle_chain, player1_chain, player2_chain } => {
+                if *state.lobby_chain_id.get() != Some(sender_chain) {
+                    return; // Only the lobby opening battles opens their markets
+                }
+                Self::create_market(state, runtime, battle_chain, player1_chain, player2_chain).await;
+            }
+
+            // Battles report only about themselves
+            Message::BattleStarted { battle_chain } => {
+                if sender_chain != battle_chain {
+                    return;
+                }
+                if let Ok(Some(market_id)) = state.battle_to_market.get(&battle_chain).await {
+                    Self::close_market(state, runtime, market_id).await;
+                }
+            }
+
+            Message::BattleEnded { battle_chain, winner_chain } => {
+                if sender_chain != battle_chain {
+                    return;
+                }
+                if let Ok(Some(market_id)) = state.battle_to_market.get(&battle_chain).await {
+                    Self::settle_market(state, runtime, market_id, Some(winner_chain)).await;
+                }
+            }
+
+            _ => {
+                // Ignore messages not relevant to prediction markets
+            }
+        }
+    }
+
+    /// Open a market on `battle_chain`; `false` when the battle already has one
+    async fn create_market(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        battle_chain: ChainId,
+        player1_chain: ChainId,
+        player2_chain: ChainId,
+    ) -> bool {
+        if state.battle_to_market.contains_key(&battle_chain).await.unwrap_or(true) {
+            return false;
+        }
+        let market_id = *state.market_count.get() + 1;
+        state.market_count.set(market_id);
+
+        let market = Market {
+            market_id,
+            battle_chain,
+            player1_chain,
+            player2_chain,
+            status: MarketStatus::Open,
+            total_pool: Amount::ZERO,
+            player1_pool: Amount::ZERO,
+            player2_pool: Amount::ZERO,
+            winner_chain: None,
+            created_at: runtime.system_time(),
+            closed_at: None,
+            settled_at: None,
+            public_bettors: *state.public_bettors.get(),
+            pricing: Default::default(),
+            player1_locked: Amount::ZERO,
+            player2_locked: Amount::ZERO,
+        };
+        state.markets.insert(&market_id, market)
+            .expect("Failed to create market");
+        state.battle_to_market.insert(&battle_chain, market_id)
+            .expect("Failed to index market by battle");
+        true
+    }
+
+    /// Stake `amount` on `predicted_winner`. A bettor holds one bet per market and may only
+    /// add to it on the same side; the odds of a topped-up bet blend by stake
+    async fn place_bet(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        bettor: AccountOwner,
+        market_id: u64,
+        predicted_winner: ChainId,
+        amount: Amount,
+    ) -> Result<(), &'static str> {
+        let Ok(Some(mut market)) = state.markets.get(&market_id).await else {
+            return Err("unknown_market");
+        };
+        if !market.accepts_bets() {
+            return Err("market_closed");
+        }
+        if amount == Amount::ZERO {
+            return Err("zero_amount");
+        }
+        let (side_pool, side_locked) = match predicted_winner {
+            winner if winner == market.player1_chain => (&mut market.player1_pool, &mut market.player1_locked),
+            winner if winner == market.player2_chain => (&mut market.player2_pool, &mut market.player2_locked),
+            _ => return Err("unknown_side"),
+        };
+
+        let key = (market_id, bettor);
+        let previous = state.bets.get(&key).await.expect("Failed to read bet");
+        if previous.as_ref().is_some_and(|bet| bet.predicted_winner != predicted_winner) {
+            return Err("opposite_side");
+        }
+        let staked = previous.as_ref().map_or(Amount::ZERO, |bet| bet.amount);
+        // A pool that cannot hold the bet cannot pay it out either
+        let (Ok(total_pool), Ok(new_side_pool), Ok(new_staked)) =
+            (market.total_pool.try_add(amount), side_pool.try_add(amount), staked.try_add(amount))
+        else {
+            return Err("pool_overflow");
+        };
+        let quote = odds::implied_odds(new_side_pool, total_pool, *state.platform_fee_bps.get()).unwrap_or(odds::EVEN_ODDS);
+        *side_pool = new_side_pool;
+        *side_locked = side_locked.saturating_add(odds::locked_payout(amount, quote));
+        market.total_pool = total_pool;
+
+        let placed_at = runtime.system_time();
+        let first_bet = previous.is_none();
+        let bet = match previous {
+            Some(bet) => Bet { amount: new_staked, odds_at_bet: odds::blend(staked, bet.odds_at_bet, amount, quote), ..bet },
+            None => Bet {
+                bettor,
+                market_id,
+                predicted_winner,
+                amount: new_staked,
+                odds_at_bet: quote,
+                placed_at,
+                claimed: false,
+                payout: None,
+            },
+        };
+        state.bets.insert(&key, bet).expect("Failed to place bet");
+        state.markets.insert(&market_id, market).expect("Failed to update market");
+
+        let mut stats = state.bettor_stats.get(&bettor).await.expect("Failed to read bettor stats").unwrap_or_default();
+        if first_bet {
+            stats.total_bets = stats.total_bets.saturating_add(1);
+            let mut bettors = state.market_bettors.get(&market_id).await.expect("Failed to read bettors").unwrap_or_default();
+            bettors.push(bettor);
+            state.market_bettors.insert(&market_id, bettors).expect("Failed to record bettor");
+        }
+        stats.total_wagered = stats.total_wagered.saturating_add(amount);
+        state.bettor_stats.insert(&bettor, stats).expect("Failed to record bettor stats");
+        let total = *state.total_volume.get();
+        let total = Self::accumulate(state, runtime, counters::PREDICTION_VOLUME, total, amount).await;
+        state.total_volume.set(total);
+        Ok(())
+    }
+
+    /// Stop taking bets on an open market
+    async fn close_market(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+    ) {
+        if let Ok(Some(mut market)) = state.markets.get(&market_id).await {
+            if market.status != MarketStatus::Open {
+                return;
+            }
+            market.status = MarketStatus::Closed;
+            market.closed_at = Some(runtime.system_time());
+            state.markets.insert(&market_id, market)
+                .expect("Failed to close market");
+        }
+    }
+
+    /// Settle an unsettled market on `winner_chain` and take the platform fee. A winner that is
+    /// neither side, or that nobody backed, voids the market so every stake is refunded.
+    /// `false` when the market is unknown or already settled
+    async fn settle_market(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        market_id: u64,
+        winner_chain: Option<ChainId>,
+    ) -> bool {
+        let Ok(Some(mut market)) = state.markets.get(&market_id).await else {
+            return false;
+        };
+        if !matches!(market.status, MarketStatus::Open | MarketStatus::Closed) {
+            return false;
+        }
+        let now = runtime.system_time();
+        let winner_chain = winner_chain.filter(|winner| market.winning_pool(*winner) > Amount::ZERO);
+        market.status = if winner_chain.is_some() { MarketStatus::Settled } else { MarketStatus::Cancelled };
+        market.winner_chain = winner_chain;
+        market.closed_at.get_or_insert(now);
+        market.settled_at = Some(now);
+
+        if let Some(winner) = winner_chain {
+            let (_, fee) = market.payout_pool(winner, *state.platform_fee_bps.get());
+            let collected = *state.total_fees_collected.get();
+            let collected = Self::accumulate(state, runtime, counters::PREDICTION_FEES, collected, fee).await;
+            state.total_fees_collected.set(collected);
+            Self::record_outcomes(state, &market).await;
+        }
+        state.markets.insert(&market_id, market)
+            .expect("Failed to settle market");
+        true
+    }
+
+    /// Count every bet of a market settled on a winner in its bettor's record, then rerank
+    /// those bettors on the betting leaderboard
+    async fn record_outcomes(state: &mut PredictionState, market: &Market) {
+        let bettors = state.market_bettors.get(&market.market_id).await
+            .expect("Failed to read bettors")
+            .unwrap_or_default();
+        let platform_fee_bps = *state.platform_fee_bps.get();
+        let mut updated = Vec::with_capacity(bettors.len());
+        for bettor in bettors {
+            let Ok(Some(bet)) = state.bets.get(&(market.market_id, bettor)).await else {
+                continue;
+            };
+            let mut stats = state.bettor_stats.get(&bettor).await.expect("Failed to read bettor stats").unwrap_or_default();
+            stats.settle(bet.amount, Self::winning_share(market, &bet, platform_fee_bps));
+            state.bettor_stats.insert(&bettor, stats).expect("Failed to record bettor stats");
+            updated.push((bettor, stats));
+        }
+        Self::refresh_betting_leaderboard(state, &updated);
+    }
+
+    /// Replace the leaderboard entries of `updated`'s bettors, then rank everyone by profit,
+    /// win rate and then volume breaking ties, keeping the top [`BETTING_LEADERBOARD_SIZE`]
+    fn refresh_betting_leaderboard(state: &mut PredictionState, updated: &[(AccountOwner, BettorStats)]) {
+        let mut leaderboard: Vec<BettingLeaderboardEntry> = state.betting_leaderboard.get().iter()
+            .filter(|entry| updated.iter().all(|(bettor, _)| *bettor != entry.bettor))
+            .cloned()
+            .collect();
+        leaderboard.extend(updated.iter().map(|(bettor, stats)| BettingLeaderboardEntry {
+            rank: 0,
+            bettor: *bettor,
+            total_bets: stats.total_bets,
+            total_wagered: stats.total_wagered,
+            total_winnings: stats.total_winnings,
+            profit: stats.profit(),
+            win_rate: stats.win_rate(),
+        }));
+
+        leaderboard.sort_by(|a, b| {
+            b.profit.cmp(&a.profit)
+                .then(b.win_rate.total_cmp(&a.win_rate))
+                .then(b.total_wagered.cmp(&a.total_wagered))
+        });
+        leaderboard.truncate(BETTING_LEADERBOARD_SIZE);
+        for (index, entry) in leaderboard.iter_mut().enumerate() {
+            entry.rank = index as u64 + 1;
+        }
+        state.betting_leaderboard.set(leaderboard);
+    }
+
+    /// Record the payout owed on `bettor`'s bet in a settled or voided market
+    async fn claim_winnings(
+        state: &mut PredictionState,
+        bettor: AccountOwner,
+        market_id: u64,
+    ) -> Result<Amount, &'static str> {
+        let Ok(Some(market)) = state.markets.get(&market_id).await else {
+            return Err("unknown_market");
+        };
+        let Ok(Some(mut bet)) = state.bets.get(&(market_id, bettor)).await else {
+            return Err("no_bet");
+        };
+        if bet.claimed {
+            return Err("already_claimed");
+        }
+        let payout = match market.status {
+            MarketStatus::Settled => Self::winning_share(&market, &bet, *state.platform_fee_bps.get()),
+            MarketStatus::Cancelled => bet.amount,
+            MarketStatus::Open | MarketStatus::Closed => return Err("not_settled"),
+        };
+        if payout == Amount::ZERO {
+            return Err("nothing_to_claim");
+        }
+        bet.claimed = true;
+        bet.payout = Some(payout);
+        state.bets.insert(&(market_id, bettor), bet)
+            .expect("Failed to record claim");
+        Ok(payout)
+    }
+
+    /// Share of the pool after the fee for a bet on the winner, rounded down; see
+    /// `Market::winners_split`. The rounding remainder stays with the chain
+    fn winning_share(market: &Market, bet: &Bet, platform_fee_bps: u16) -> Amount {
+        let Some(winner) = market.winner_chain.filter(|winner| *winner == bet.predicted_winner) else {
+            return Amount::ZERO;
+        };
+        let (mut split, _) = market.winners_split(winner, platform_fee_bps);
+        split.share(0, market.payout_weight(bet))
+    }
+
+    /// `value + delta` for a lifetime counter, recording a pin instead of saturating silently
+    async fn accumulate<T: Counter>(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        counter: &str,
+        mut value: T,
+        delta: T,
+    ) -> T {
+        if checked_accumulate(&mut value, delta) {
+            record_overflow(&mut state.counter_overflows, counter, runtime.system_time()).await;
+        }
+        value
+    }
+
+    /// Log a rejection, coalescing repeats (see `majorules::throttle`), and answer it with the reason
+    async fn reject(
+        state: &mut PredictionState,
+        runtime: &mut ContractRuntime<crate::MajorulesContract>,
+        operation: &str,
+        reason: &str,
+        signer: AccountOwner,
+    ) -> OperationResponse {
+        let key = RejectionKey::new(operation, reason, signer);
+        record_rejection(&mut state.rejections, key, runtime.system_time()).await;
+        OperationResponse::rejected(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+        util::BlockingWait,
+        views::View,
+        ContractRuntime,
+    };
+    use majorules::{throttle::RejectionKey, Message, Operation};
+
+    use super::PredictionContract;
+    use crate::state::{BettorStats, MarketStatus, PredictionState};
+
+    fn owner(name: &str) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(name))
+    }
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    fn setup() -> (PredictionState, ContractRuntime<crate::MajorulesContract>) {
+        let runtime = ContractRuntime::new()
+            .with_chain_id(chain("prediction"))
+            .with_authenticated_signer(owner("treasury"))
+            .with_system_time(Timestamp::from(0));
+        let mut state = PredictionState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        state.treasury_owner.set(Some(owner("treasury")));
+        state.lobby_chain_id.set(Some(chain("lobby")));
+        state.platform_fee_bps.set(1000);
+        (state, runtime)
+    }
+
+    fn operate(state: &mut PredictionState, runtime: &mut ContractRuntime<crate::MajorulesContract>, signer: &str, operation: Operation) {
+        runtime.set_authenticated_signer(Some(owner(signer)));
+        PredictionContract::execute_operation(state, runtime, operation).blocking_wait();
+    }
+
+    fn deliver(state: &mut PredictionState, runtime: &mut ContractRuntime<crate::MajorulesContract>, from: &str, message: Message) {
+        runtime.set_message_origin_chain_id(chain(from));
+        PredictionContract::execute_message(state, runtime, message).blocking_wait();
+    }
+
+    fn bet(state: &mut PredictionState, runtime: &mut ContractRuntime<crate::MajorulesContract>, bettor: &str, side: &str, tokens: u128) {
+        operate(state, runtime, bettor, Operation::PlaceBet {
+            market_id: 1,
+            predicted_winner: chain(side),
+            amount: Amount::from_tokens(tokens),
+        });
+    }
+
+    fn rejected(state: &PredictionState, operation: &str, reason: &str, signer: &str) -> bool {
+        state.rejections.contains_key(&RejectionKey::new(operation, reason, owner(signer))).blocking_wait().unwrap()
+    }
+
+    fn payout(state: &PredictionState, bettor: &str) -> Option<Amount> {
+        state.bets.get(&(1, owner(bettor))).blocking_wait().unwrap().and_then(|bet| bet.payout)
+    }
+
+    fn market_status(state: &PredictionState) -> MarketStatus {
+        state.markets.get(&1).blocking_wait().unwrap().unwrap().status
+    }
+
+    #[test]
+    fn battles_close_and_settle_the_markets_the_lobby_opens() {
+        let (mut state, mut runtime) = setup();
+        let open = || Message::CreatePredictionMarket { battle_chain: chain("battle"), player1_chain: chain("alice"), player2_chain: chain("bob") };
+        deliver(&mut state, &mut runtime, "mallory", open());
+        assert_eq!(*state.market_count.get(), 0);
+        deliver(&mut state, &mut runtime, "lobby", open());
+        deliver(&mut state, &mut runtime, "lobby", open());
+        assert_eq!(*state.market_count.get(), 1);
+
+        bet(&mut state, &mut runtime, "carol", "alice", 2);
+        bet(&mut state, &mut runtime, "dave", "bob", 1);
+        bet(&mut state, &mut runtime, "erin", "alice", 1);
+        bet(&mut state, &mut runtime, "carol", "bob", 1);
+        assert!(rejected(&state, "PlaceBet", "opposite_side", "carol"));
+        bet(&mut state, &mut runtime, "frank", "mallory", 1);
+        assert!(rejected(&state, "PlaceBet", "unknown_side", "frank"));
+        assert_eq!(*state.total_volume.get(), Amount::from_tokens(4));
+        // Each bet locked the odds its own stake made, net of the 10% fee on the other side
+        let odds = |bettor| state.bets.get(&(1, owner(bettor))).blocking_wait().unwrap().unwrap().odds_at_bet;
+        assert_eq!([odds("carol"), odds("dave"), odds("erin")], [10_000, 28_000, 13_000]);
+
+        // Only the battle itself reports on its market
+        deliver(&mut state, &mut runtime, "mallory", Message::BattleStarted { battle_chain: chain("battle") });
+        assert_eq!(market_status(&state), MarketStatus::Open);
+        deliver(&mut state, &mut runtime, "battle", Message::BattleStarted { battle_chain: chain("battle") });
+        assert_eq!(market_status(&state), MarketStatus::Closed);
+        bet(&mut state, &mut runtime, "erin", "alice", 1);
+        assert!(rejected(&state, "PlaceBet", "market_closed", "erin"));
+
+        operate(&mut state, &mut runtime, "carol", Operation::ClaimWinnings { market_id: 1 });
+        assert!(rejected(&state, "ClaimWinnings", "not_settled", "carol"));
+        deliver(&mut state, &mut runtime, "battle", Message::BattleEnded { battle_chain: chain("battle"), winner_chain: chain("alice") });
+        assert_eq!(market_status(&state), MarketStatus::Settled);
+        assert_eq!(*state.total_fees_collected.get(), Amount::from_millis(100));
+
+        // 3.9 tokens after the fee on bob's side, split 2:1 between the bettors on alice
+        for bettor in ["carol", "dave", "erin"] {
+            operate(&mut state, &mut runtime, bettor, Operation::ClaimWinnings { market_id: 1 });
+        }
+        assert_eq!(payout(&state, "carol"), Some(Amount::from_millis(2600)));
+        assert_eq!(payout(&state, "erin"), Some(Amount::from_millis(1300)));
+        assert_eq!(payout(&state, "dave"), None);
+        assert!(rejected(&state, "ClaimWinnings", "nothing_to_claim", "dave"));
+        operate(&mut state, &mut runtime, "carol", Operation::ClaimWinnings { market_id: 1 });
+        assert!(rejected(&state, "ClaimWinnings", "already_claimed", "carol"));
+    }
+
+    #[test]
+    fn settled_markets_fold_into_bettor_records_and_the_leaderboard() {
+        let (mut state, mut runtime) = setup();
+        for battle in ["battle", "rematch"] {
+            operate(&mut state, &mut runtime, "treasury", Operation::CreateMarket {
+                battle_chain: chain(battle),
+                player1_chain: chain("alice"),
+                player2_chain: chain("bob"),
+            });
+        }
+        bet(&mut state, &mut runtime, "carol", "alice", 2);
+        bet(&mut state, &mut runtime, "dave", "bob", 1);
+        bet(&mut state, &mut runtime, "erin", "alice", 1);
+        for (bettor, side, tokens) in [("dave", "alice", 1), ("dave", "alice", 2), ("carol", "bob", 1)] {
+            operate(&mut state, &mut runtime, bettor, Operation::PlaceBet {
+                market_id: 2,
+                predicted_winner: chain(side),
+                amount: Amount::from_tokens(tokens),
+            });
+        }
+        let stats = |state: &PredictionState, bettor| state.bettor_stats.get(&owner(bettor)).blocking_wait().unwrap().unwrap();
+        let standings = |state: &PredictionState| {
+            state.betting_leaderboard.get().iter().map(|entry| (entry.rank, entry.bettor, entry.profit)).collect::<Vec<_>>()
+        };
+        // The top-up added to dave's bet on the rematch rather than counting as another
+        assert_eq!(stats(&state, "dave"), BettorStats { total_bets: 2, total_wagered: Amount::from_tokens(4), ..BettorStats::default() });
+        assert!(state.betting_leaderboard.get().is_empty());
+
+        // Carol and erin split 3.9 tokens 2:1
+        deliver(&mut state, &mut runtime, "battle", Message::BattleEnded { battle_chain: chain("battle"), winner_chain: chain("alice") });
+        assert_eq!(standings(&state), [
+            (1, owner("carol"), Amount::from_millis(600)),
+            (2, owner("erin"), Amount::from_millis(300)),
+            (3, owner("dave"), Amount::ZERO),
+        ]);
+
+        // Dave's 3 tokens take 3.9, ending a tenth behind overall and carol four tenths; with no
+        // profit and one win in two each, the larger volume ranks first
+        deliver(&mut state, &mut runtime, "rematch", Message::BattleEnded { battle_chain: chain("rematch"), winner_chain: chain("alice") });
+        assert_eq!(stats(&state, "dave"), BettorStats {
+            total_bets: 2,
+            total_wagered: Amount::from_tokens(4),
+            decided_bets: 2,
+            decided_wagered: Amount::from_tokens(4),
+            won_bets: 1,
+            total_winnings: Amount::from_millis(3_900),
+        });
+        assert_eq!(stats(&state, "carol").win_rate(), 0.5);
+        assert_eq!(standings(&state), [
+            (1, owner("erin"), Amount::from_millis(300)),
+            (2, owner("dave"), Amount::ZERO),
+            (3, owner("carol"), Amount::ZERO),
+        ]);
+    }
+
+    #[test]
+    fn treasury_settlement_on_an_unbacked_winner_refunds_every_stake() {
+        let (mut state, mut runtime) = setup();
+        let create = || Operation::CreateMarket { battle_chain: chain("battle"), player1_chain: chain("alice"), player2_chain: chain("bob") };
+        operate(&mut state, &mut runtime, "carol", create());
+        assert!(rejected(&state, "CreateMarket", "not_treasury", "carol"));
+        operate(&mut state, &mut runtime, "treasury", create());
+        bet(&mut state, &mut runtime, "carol", "alice", 2);
+        bet(&mut state, &mut runtime, "carol", "alice", 1);
+
+        operate(&mut state, &mut runtime, "carol", Operation::SettleMarket { market_id: 1, winner_chain: chain("alice") });
+        assert!(rejected(&state, "SettleMarket", "not_treasury", "carol"));
+        operate(&mut state, &mut runtime, "treasury", Operation::SettleMarket { market_id: 1, winner_chain: chain("bob") });
+        assert_eq!(market_status(&state), MarketStatus::Cancelled);
+        assert_eq!(*state.total_fees_collected.get(), Amount::ZERO);
+
+        operate(&mut state, &mut runtime, "carol", Operation::ClaimWinnings { market_id: 1 });
+        assert_eq!(payout(&state, "carol"), Some(Amount::from_tokens(3)));
+        operate(&mut state, &mut runtime, "treasury", Operation::SettleMarket { market_id: 1, winner_chain: chain("alice") });
+        assert!(rejected(&state, "SettleMarket", "not_settleable", "treasury"));
+    }
+}
@@ -0,0 +1,128 @@
+//! Daily quests and achievements.
+//!
+//! Quests count the battles a player chain fights during one day and start over the next;
+//! each pays its token and XP reward once, when claimed. Achievements unlock once and for good
+//! on a chain's lifetime totals.
+
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::CombatStats;
+
+/// What a quest or achievement counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum QuestGoal {
+    FightBattles,
+    WinBattles,
+    LandCrits,
+    DealDamage,
+}
+
+impl QuestGoal {
+    /// What one battle, `won` or not, adds toward the goal
+    pub fn progress(self, won: bool, stats: &CombatStats) -> u64 {
+        match self {
+            Self::FightBattles => 1,
+            Self::WinBattles => won as u64,
+            Self::LandCrits => stats.crits,
+            Self::DealDamage => stats.damage_dealt,
+        }
+    }
+}
+
+/// A daily quest and what completing it pays
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct Quest {
+    pub quest_id: &'static str,
+    pub goal: QuestGoal,
+    pub target: u64,
+    /// Battle tokens minted by the lobby for the claim
+    pub reward: Amount,
+    /// XP for the character the claim names
+    pub reward_xp: u64,
+}
+
+/// Quests offered every day
+pub const DAILY_QUESTS: [Quest; 3] = [
+    Quest { quest_id: "win-3", goal: QuestGoal::WinBattles, target: 3, reward: Amount::from_tokens(3), reward_xp: 100 },
+    Quest { quest_id: "crit-5", goal: QuestGoal::LandCrits, target: 5, reward: Amount::from_tokens(2), reward_xp: 60 },
+    Quest { quest_id: "damage-1000", goal: QuestGoal::DealDamage, target: 1_000, reward: Amount::from_tokens(2), reward_xp: 60 },
+];
+
+/// The daily quest `quest_id`, if there is one
+pub fn daily_quest(quest_id: &str) -> Option<&'static Quest> {
+    DAILY_QUESTS.iter().find(|quest| quest.quest_id == quest_id)
+}
+
+/// Most battle tokens one player can claim from quests in a day
+pub fn daily_reward_cap() -> Amount {
+    DAILY_QUESTS.iter().fold(Amount::ZERO, |cap, quest| cap.saturating_add(quest.reward))
+}
+
+/// A one-time milestone on lifetime totals
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct Achievement {
+    pub achievement_id: &'static str,
+    pub goal: QuestGoal,
+    pub threshold: u64,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 5] = [
+    Achievement { achievement_id: "first-blood", goal: QuestGoal::WinBattles, threshold: 1 },
+    Achievement { achievement_id: "veteran", goal: QuestGoal::FightBattles, threshold: 100 },
+    Achievement { achievement_id: "champion", goal: QuestGoal::WinBattles, threshold: 50 },
+    Achievement { achievement_id: "sharpshooter", goal: QuestGoal::LandCrits, threshold: 100 },
+    Achievement { achievement_id: "devastator", goal: QuestGoal::DealDamage, threshold: 100_000 },
+];
+
+/// Lifetime totals achievements are measured against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LifetimeTotals {
+    pub battles: u64,
+    pub wins: u64,
+    pub crits: u64,
+    pub damage_dealt: u64,
+}
+
+impl Achievement {
+    pub fn reached(&self, totals: &LifetimeTotals) -> bool {
+        let total = match self.goal {
+            QuestGoal::FightBattles => totals.battles,
+            QuestGoal::WinBattles => totals.wins,
+            QuestGoal::LandCrits => totals.crits,
+            QuestGoal::DealDamage => totals.damage_dealt,
+        };
+        total >= self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::{daily_quest, daily_reward_cap, LifetimeTotals, QuestGoal, ACHIEVEMENTS};
+    use crate::CombatStats;
+
+    #[test]
+    fn battles_advance_the_goals_they_count() {
+        let stats = CombatStats { damage_dealt: 420, crits: 2, ..CombatStats::default() };
+        assert_eq!(QuestGoal::WinBattles.progress(false, &stats), 0);
+        assert_eq!(QuestGoal::WinBattles.progress(true, &stats), 1);
+        assert_eq!(QuestGoal::LandCrits.progress(false, &stats), 2);
+        assert_eq!(QuestGoal::DealDamage.progress(true, &stats), 420);
+        assert_eq!(daily_quest("crit-5").map(|quest| quest.target), Some(5));
+        assert_eq!(daily_quest("unknown"), None);
+        assert_eq!(daily_reward_cap(), Amount::from_tokens(7));
+    }
+
+    #[test]
+    fn achievements_unlock_at_their_threshold() {
+        let totals = LifetimeTotals { battles: 100, wins: 1, crits: 99, damage_dealt: 0 };
+        let reached: Vec<_> = ACHIEVEMENTS.iter()
+            .filter(|achievement| achievement.reached(&totals))
+            .map(|achievement| achievement.achievement_id)
+            .collect();
+        assert_eq!(reached, ["first-blood", "veteran"]);
+    }
+}
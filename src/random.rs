@@ -1,25 +1,129 @@
-use std::sync::{Mutex, OnceLock};
-use rand::{rngs::StdRng, Rng, SeedableRng};
+//! Deterministic randomness for contract logic.
+//!
+//! Every roll is drawn from a seed hashed out of on-chain inputs, so all validators agree on
+//! it. A turn's seed covers both fighters' submissions, so no roll of that turn can be known
+//! before both have submitted.
 
-static RNG: OnceLock<Mutex<StdRng>> = OnceLock::new();
+use linera_sdk::linera_base_types::{BcsHashable, BlockHeight, ChainId, CryptoHash};
+use serde::{Deserialize, Serialize};
 
-fn custom_getrandom(buf: &mut [u8]) -> Result<(), getrandom::Error> {
-    let seed = [0u8; 32];
-    RNG.get_or_init(|| Mutex::new(StdRng::from_seed(seed)))
-        .lock()
-        .expect("failed to get RNG lock")
-        .fill(buf);
-    Ok(())
+/// Everything a turn's rolls depend on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TurnEntropy {
+    battle_chain: ChainId,
+    block_height: BlockHeight,
+    round: u8,
+    turn: u8,
+    commits: [Vec<u8>; 2],
 }
 
-getrandom::register_custom_getrandom!(custom_getrandom);
+impl BcsHashable<'_> for TurnEntropy {}
 
+/// A single draw from a seed, distinguished by its tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Draw {
+    seed: [u8; 32],
+    tag: u8,
+}
+
+impl BcsHashable<'_> for Draw {}
+
+/// Seed for one turn of a battle, from the block executing it and both fighters' commit data
+pub fn turn_seed(battle_chain: ChainId, block_height: BlockHeight, round: u8, turn: u8, commits: [Vec<u8>; 2]) -> [u8; 32] {
+    let entropy = TurnEntropy { battle_chain, block_height, round, turn, commits };
+    CryptoHash::new(&entropy).into()
+}
+
+/// Independent value for each `tag` drawn from `seed`
+pub fn derive_random_u64(seed: &[u8; 32], tag: u8) -> u64 {
+    let [word, ..] = <[u64; 4]>::from(CryptoHash::new(&Draw { seed: *seed, tag }));
+    word
+}
+
+/// Value in `[min, max]` for `tag`, without modulo bias; `min` when the range is empty
+pub fn random_in_range(seed: &[u8; 32], tag: u8, min: u64, max: u64) -> u64 {
+    if max <= min {
+        return min;
+    }
+    let raw = derive_random_u64(seed, tag) as u128;
+    match (max - min).checked_add(1) {
+        Some(span) => min + ((raw * span as u128) >> 64) as u64,
+        None => raw as u64,
+    }
+}
+
+/// What a roll decides during an attack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Roll {
+    Damage,
+    Crit,
+    Dodge,
+    Counter,
+}
+
+impl Roll {
+    const KINDS: u8 = 4;
+}
+
+/// The rolls one fighter's attack draws from a turn's seed; each fighter has its own tags
+#[derive(Debug, Clone, Copy)]
+pub struct AttackRolls {
+    seed: [u8; 32],
+    attacker: u8,
+}
+
+impl AttackRolls {
+    /// Rolls for the attack by fighter `attacker` (0 or 1) in the turn seeded with `seed`
+    pub fn new(seed: [u8; 32], attacker: u8) -> Self {
+        Self { seed, attacker }
+    }
+
+    pub fn roll(&self, roll: Roll, min: u64, max: u64) -> u64 {
+        random_in_range(&self.seed, self.attacker * Roll::KINDS + roll as u8, min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{BlockHeight, ChainId, CryptoHash};
+
+    use super::{random_in_range, turn_seed, AttackRolls, Roll};
+
+    fn seed(chain: &str, height: u64, commits: [&[u8]; 2]) -> [u8; 32] {
+        let chain = ChainId(CryptoHash::test_hash(chain));
+        turn_seed(chain, BlockHeight(height), 1, 0, commits.map(<[u8]>::to_vec))
+    }
+
+    #[test]
+    fn seeds_depend_on_every_input() {
+        let base = seed("battle", 7, [b"a", b"b"]);
+        assert_eq!(base, seed("battle", 7, [b"a", b"b"]));
+        for other in [seed("other", 7, [b"a", b"b"]), seed("battle", 8, [b"a", b"b"]), seed("battle", 7, [b"a", b"c"]), seed("battle", 7, [b"b", b"a"])] {
+            assert_ne!(base, other);
+        }
+    }
 
-pub fn random_value(min: u64, max: u64) -> u64 {
-    let seed = [0u8; 32]; // Use timestamp in production
-    let mut rng = RNG.get_or_init(|| Mutex::new(StdRng::from_seed(seed)))
-        .lock()
-        .expect("failed to get RNG lock");
+    #[test]
+    fn ranges_are_inclusive_and_cover_every_value() {
+        let seed = seed("battle", 1, [b"a", b"b"]);
+        let mut seen = [false; 6];
+        for tag in 0..=u8::MAX {
+            let value = random_in_range(&seed, tag, 10, 15);
+            assert!((10..=15).contains(&value));
+            seen[(value - 10) as usize] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(random_in_range(&seed, 0, 9, 9), 9);
+        assert_eq!(random_in_range(&seed, 0, 9, 3), 9);
+        random_in_range(&seed, 0, 0, u64::MAX);
+    }
 
-    rng.gen_range(min..=max)
+    #[test]
+    fn fighters_draw_independent_rolls() {
+        let seed = seed("battle", 1, [b"a", b"b"]);
+        let (first, second) = (AttackRolls::new(seed, 0), AttackRolls::new(seed, 1));
+        let draws = |rolls: AttackRolls| [Roll::Damage, Roll::Crit, Roll::Dodge, Roll::Counter].map(|roll| rolls.roll(roll, 0, u64::MAX));
+        assert_ne!(draws(first), draws(second));
+        assert_eq!(draws(first), draws(AttackRolls::new(seed, 0)));
+    }
 }
@@ -0,0 +1,167 @@
+//! XP scaled to how a battle went.
+//!
+//! Battle chains award the base XP of their rules; the lobby, which knows both fighters'
+//! ratings and streaks, scales it before relaying the result, so player chains credit it as is.
+//! Every bonus is in basis points of the base XP, and together they stay under one cap.
+//!
+//! The lobby keeps win streaks too: a win extending a streak also mints a token bonus, and
+//! streak insurance bought beforehand keeps a streak through one defeat.
+
+use async_graphql::{InputObject, SimpleObject};
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::MAX_XP_PER_BATTLE;
+
+/// How far XP grows with the opponent, the battle's length, the damage dealt and the streak
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "XpCurveInput")]
+pub struct XpCurve {
+    /// Bonus to a winner for every 100 rating points the loser stood above them
+    pub upset_bps_per_100: u32,
+    /// Bonus for every round played
+    pub round_bps: u32,
+    /// Bonus for every 100 damage dealt
+    pub damage_bps_per_100: u32,
+    /// Bonus to a winner for every win of the streak they brought into the battle
+    pub streak_bps: u32,
+    /// Most all bonuses together add
+    pub max_bonus_bps: u32,
+}
+
+impl Default for XpCurve {
+    fn default() -> Self {
+        Self {
+            upset_bps_per_100: 1_000,
+            round_bps: 200,
+            damage_bps_per_100: 100,
+            streak_bps: 500,
+            max_bonus_bps: 10_000,
+        }
+    }
+}
+
+/// What win streaks are worth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "StreakTermsInput")]
+pub struct StreakTerms {
+    /// Battle tokens minted to a winner for every win of the streak they extend
+    pub token_bonus_per_win: Amount,
+    /// Most tokens one win's bonus mints
+    pub max_token_bonus: Amount,
+    /// Price of insurance that keeps a streak through one defeat
+    pub insurance_price: Amount,
+    /// Streaks are announced every time they reach a multiple of this length; 0 announces none
+    pub notable_every: u64,
+}
+
+impl Default for StreakTerms {
+    fn default() -> Self {
+        Self {
+            token_bonus_per_win: Amount::from_millis(100),
+            max_token_bonus: Amount::from_tokens(1),
+            insurance_price: Amount::from_tokens(2),
+            notable_every: 5,
+        }
+    }
+}
+
+impl StreakTerms {
+    /// Tokens minted for a win extending a streak of `streak` wins
+    pub fn token_bonus(&self, streak: u64) -> Amount {
+        Amount::from_attos(u128::from(self.token_bonus_per_win).saturating_mul(streak as u128)).min(self.max_token_bonus)
+    }
+
+    /// Whether a streak of `streak` wins is announced
+    pub fn is_notable(&self, streak: u64) -> bool {
+        self.notable_every > 0 && streak > 0 && streak % self.notable_every == 0
+    }
+}
+
+/// Where a fighter stood going into a battle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub rating: u64,
+    /// Wins in a row
+    pub streak: u64,
+}
+
+/// What one fighter's XP is scaled by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XpFactors {
+    pub won: bool,
+    pub standing: Standing,
+    pub opponent: Standing,
+    pub rounds_played: u8,
+    pub damage_dealt: u64,
+}
+
+impl XpCurve {
+    /// Bonus the factors earn, in basis points of the base XP
+    pub fn bonus_bps(&self, factors: &XpFactors) -> u64 {
+        let mut bonus = (factors.rounds_played as u64).saturating_mul(self.round_bps as u64)
+            .saturating_add((factors.damage_dealt / 100).saturating_mul(self.damage_bps_per_100 as u64));
+        if factors.won {
+            let gap = factors.opponent.rating.saturating_sub(factors.standing.rating);
+            bonus = bonus
+                .saturating_add((gap / 100).saturating_mul(self.upset_bps_per_100 as u64))
+                .saturating_add(factors.standing.streak.saturating_mul(self.streak_bps as u64));
+        }
+        bonus.min(self.max_bonus_bps as u64)
+    }
+
+    /// `base` XP with the bonuses applied, capped at `MAX_XP_PER_BATTLE`
+    pub fn scale(&self, base: u64, factors: &XpFactors) -> u64 {
+        let scaled = base as u128 * (10_000 + self.bonus_bps(factors) as u128) / 10_000;
+        scaled.min(MAX_XP_PER_BATTLE as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::{Standing, StreakTerms, XpCurve, XpFactors};
+    use crate::MAX_XP_PER_BATTLE;
+
+    fn factors(won: bool, rating: u64, opponent_rating: u64) -> XpFactors {
+        XpFactors {
+            won,
+            standing: Standing { rating, streak: 2 },
+            opponent: Standing { rating: opponent_rating, streak: 0 },
+            rounds_played: 5,
+            damage_dealt: 250,
+        }
+    }
+
+    #[test]
+    fn upsets_and_streaks_pay_winners_only() {
+        let curve = XpCurve::default();
+        // 5 rounds, and 250 damage counted in whole hundreds: 1 000 + 200
+        assert_eq!(curve.bonus_bps(&factors(false, 1200, 1500)), 1_200);
+        // Plus 300 points of upset and a streak of 2: 3 000 + 1 000
+        assert_eq!(curve.bonus_bps(&factors(true, 1200, 1500)), 5_200);
+        // Beating a weaker opponent is no upset
+        assert_eq!(curve.bonus_bps(&factors(true, 1500, 1200)), 2_200);
+        assert_eq!(curve.scale(150, &factors(true, 1200, 1500)), 228);
+    }
+
+    #[test]
+    fn bonuses_stay_under_their_cap() {
+        let curve = XpCurve { max_bonus_bps: 5_000, ..XpCurve::default() };
+        assert_eq!(curve.bonus_bps(&factors(true, 0, 5_000)), 5_000);
+        assert_eq!(curve.scale(100, &factors(true, 0, 5_000)), 150);
+        assert_eq!(curve.scale(MAX_XP_PER_BATTLE, &factors(true, 0, 5_000)), MAX_XP_PER_BATTLE);
+        assert_eq!(XpCurve { max_bonus_bps: 0, ..curve }.scale(150, &factors(true, 0, 5_000)), 150);
+    }
+
+    #[test]
+    fn streak_bonuses_escalate_to_their_cap() {
+        let terms = StreakTerms::default();
+        assert_eq!(terms.token_bonus(0), Amount::ZERO);
+        assert_eq!(terms.token_bonus(4), Amount::from_millis(400));
+        assert_eq!(terms.token_bonus(25), Amount::from_tokens(1));
+        assert_eq!([4, 5, 6, 10].map(|streak| terms.is_notable(streak)), [false, true, false, true]);
+        assert!(!StreakTerms { notable_every: 0, ..terms }.is_notable(5));
+    }
+}
@@ -0,0 +1,151 @@
+//! Tournament registration and start timing. All checks take the acting chain's block time.
+
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::{TimeDelta, Timestamp};
+use serde::{Deserialize, Serialize};
+
+/// Bounds on how a tournament may be scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleLimits {
+    /// Shortest allowed gap between creation and `start_time`
+    pub min_lead: TimeDelta,
+    /// Furthest ahead `start_time` may be set
+    pub max_horizon: TimeDelta,
+    /// How long past `start_time` an under-filled tournament waits before it is cancelled
+    pub grace: TimeDelta,
+    pub min_participants: u32,
+}
+
+impl Default for ScheduleLimits {
+    fn default() -> Self {
+        Self {
+            min_lead: TimeDelta::from_secs(5 * 60),
+            max_horizon: TimeDelta::from_secs(30 * 24 * 3600),
+            grace: TimeDelta::from_secs(3600),
+            min_participants: 2,
+        }
+    }
+}
+
+/// When a tournament stops taking entrants and starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct TournamentSchedule {
+    pub start_time: Timestamp,
+    /// Joins are refused from this moment; never later than `start_time`
+    pub registration_closes_at: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+    StartTooSoon,
+    StartTooFar,
+    RegistrationClosesAfterStart,
+}
+
+/// What a tournament should do at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum SchedulePhase {
+    Registration,
+    /// Registration closed, waiting for `start_time`
+    Closed,
+    /// At or past `start_time` with enough entrants
+    ReadyToStart,
+    /// Past `start_time` without enough entrants, still within the grace period
+    AwaitingEntrants,
+    /// Grace period over without enough entrants: cancel and refund
+    Expired,
+}
+
+impl TournamentSchedule {
+    /// Validate a schedule at creation time `now`.
+    /// `registration_closes_at` defaults to `start_time`.
+    pub fn new(
+        now: Timestamp,
+        start_time: Timestamp,
+        registration_closes_at: Option<Timestamp>,
+        limits: &ScheduleLimits,
+    ) -> Result<Self, ScheduleError> {
+        if start_time < now.saturating_add(limits.min_lead) {
+            return Err(ScheduleError::StartTooSoon);
+        }
+        if start_time > now.saturating_add(limits.max_horizon) {
+            return Err(ScheduleError::StartTooFar);
+        }
+        let registration_closes_at = registration_closes_at.unwrap_or(start_time);
+        if registration_closes_at > start_time {
+            return Err(ScheduleError::RegistrationClosesAfterStart);
+        }
+        Ok(Self { start_time, registration_closes_at })
+    }
+
+    pub fn accepts_joins(&self, now: Timestamp) -> bool {
+        now < self.registration_closes_at
+    }
+
+    pub fn phase(&self, now: Timestamp, entrants: u32, limits: &ScheduleLimits) -> SchedulePhase {
+        if now < self.registration_closes_at {
+            SchedulePhase::Registration
+        } else if now < self.start_time {
+            SchedulePhase::Closed
+        } else if entrants >= limits.min_participants {
+            SchedulePhase::ReadyToStart
+        } else if now < self.start_time.saturating_add(limits.grace) {
+            SchedulePhase::AwaitingEntrants
+        } else {
+            SchedulePhase::Expired
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{TimeDelta, Timestamp};
+
+    use super::{ScheduleError, ScheduleLimits, SchedulePhase, TournamentSchedule};
+
+    fn at_secs(secs: u64) -> Timestamp {
+        Timestamp::from(secs * 1_000_000)
+    }
+
+    #[test]
+    fn start_time_must_fall_within_the_allowed_window() {
+        let limits = ScheduleLimits::default();
+        let now = at_secs(10_000);
+
+        assert_eq!(TournamentSchedule::new(now, at_secs(9_000), None, &limits), Err(ScheduleError::StartTooSoon));
+        assert_eq!(TournamentSchedule::new(now, at_secs(10_060), None, &limits), Err(ScheduleError::StartTooSoon));
+        assert_eq!(
+            TournamentSchedule::new(now, now.saturating_add(limits.max_horizon).saturating_add(TimeDelta::from_secs(1)), None, &limits),
+            Err(ScheduleError::StartTooFar),
+        );
+        assert_eq!(
+            TournamentSchedule::new(now, at_secs(20_000), Some(at_secs(20_001)), &limits),
+            Err(ScheduleError::RegistrationClosesAfterStart),
+        );
+
+        let schedule = TournamentSchedule::new(now, at_secs(20_000), None, &limits).unwrap();
+        assert_eq!(schedule.registration_closes_at, at_secs(20_000));
+    }
+
+    #[test]
+    fn joins_close_at_the_registration_deadline() {
+        let limits = ScheduleLimits::default();
+        let schedule = TournamentSchedule::new(at_secs(0), at_secs(2_000), Some(at_secs(1_500)), &limits).unwrap();
+
+        assert!(schedule.accepts_joins(at_secs(1_499)));
+        assert!(!schedule.accepts_joins(at_secs(1_500)));
+        assert_eq!(schedule.phase(at_secs(1_700), 8, &limits), SchedulePhase::Closed);
+    }
+
+    #[test]
+    fn starts_at_the_boundary_or_expires_after_the_grace_period() {
+        let limits = ScheduleLimits::default();
+        let schedule = TournamentSchedule::new(at_secs(0), at_secs(2_000), None, &limits).unwrap();
+
+        assert_eq!(schedule.phase(at_secs(1_999), 4, &limits), SchedulePhase::Registration);
+        assert_eq!(schedule.phase(at_secs(2_000), 4, &limits), SchedulePhase::ReadyToStart);
+        assert_eq!(schedule.phase(at_secs(2_000), 1, &limits), SchedulePhase::AwaitingEntrants);
+        assert_eq!(schedule.phase(at_secs(2_000 + 3_599), 1, &limits), SchedulePhase::AwaitingEntrants);
+        assert_eq!(schedule.phase(at_secs(2_000 + 3_600), 1, &limits), SchedulePhase::Expired);
+    }
+}
@@ -0,0 +1,96 @@
+//! Ranked seasons.
+//!
+//! A season pulls every rating part of the way back to the initial rating, so a new season
+//! starts close without wiping out skill, and pays a reward pool to its best rated players
+//! when it ends.
+
+use async_graphql::{InputObject, SimpleObject};
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::{elo::INITIAL_RATING, fees::BPS_DENOMINATOR};
+
+/// Most ranks a season rewards
+pub const MAX_REWARDED_RANKS: usize = 10;
+
+/// How a season resets ratings and rewards its top players
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "SeasonTermsInput")]
+pub struct SeasonTerms {
+    /// Battle tokens minted for the rewards when the season ends
+    pub reward_pool: Amount,
+    /// Share of the pool for each final rank, best first; what no one places for stays unminted
+    pub payout_bps: Vec<u16>,
+    /// Share of its distance from the initial rating a rating keeps into the season
+    pub rating_carry_bps: u16,
+}
+
+impl SeasonTerms {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.payout_bps.len() > MAX_REWARDED_RANKS {
+            return Err("too_many_rewarded_ranks");
+        }
+        if self.payout_bps.iter().map(|&bps| u32::from(bps)).sum::<u32>() > BPS_DENOMINATOR as u32 {
+            return Err("invalid_payout_split");
+        }
+        if u128::from(self.rating_carry_bps) > BPS_DENOMINATOR {
+            return Err("invalid_rating_carry");
+        }
+        Ok(())
+    }
+
+    /// Reward of each of the first `placed` ranks
+    pub fn rewards(&self, placed: usize) -> Vec<Amount> {
+        self.payout_bps.iter()
+            .take(placed)
+            .map(|&bps| Amount::from_attos(u128::from(self.reward_pool).saturating_mul(bps as u128) / BPS_DENOMINATOR))
+            .collect()
+    }
+
+    /// `rating` carried into the season: the initial rating plus the carried share of its
+    /// distance from it, rounded toward the initial rating
+    pub fn soft_reset(&self, rating: u64) -> u64 {
+        let carried = |distance: u64| distance.saturating_mul(self.rating_carry_bps as u64) / BPS_DENOMINATOR as u64;
+        if rating >= INITIAL_RATING {
+            INITIAL_RATING + carried(rating - INITIAL_RATING)
+        } else {
+            INITIAL_RATING - carried(INITIAL_RATING - rating)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::{SeasonTerms, MAX_REWARDED_RANKS};
+    use crate::elo::INITIAL_RATING;
+
+    fn terms(payout_bps: Vec<u16>, rating_carry_bps: u16) -> SeasonTerms {
+        SeasonTerms { reward_pool: Amount::from_tokens(100), payout_bps, rating_carry_bps }
+    }
+
+    #[test]
+    fn ratings_move_toward_the_initial_rating() {
+        let half = terms(vec![], 5_000);
+        assert_eq!(half.soft_reset(INITIAL_RATING + 301), INITIAL_RATING + 150);
+        assert_eq!(half.soft_reset(INITIAL_RATING - 301), INITIAL_RATING - 150);
+        assert_eq!(terms(vec![], 10_000).soft_reset(1750), 1750);
+        assert_eq!(terms(vec![], 0).soft_reset(1750), INITIAL_RATING);
+    }
+
+    #[test]
+    fn rewards_cover_the_ranks_that_placed() {
+        let terms = terms(vec![5_000, 3_000, 2_000], 5_000);
+        assert_eq!(terms.rewards(5), [Amount::from_tokens(50), Amount::from_tokens(30), Amount::from_tokens(20)]);
+        assert_eq!(terms.rewards(1), [Amount::from_tokens(50)]);
+    }
+
+    #[test]
+    fn invalid_terms_are_named() {
+        assert_eq!(terms(vec![6_000, 5_000], 0).validate(), Err("invalid_payout_split"));
+        assert_eq!(terms(vec![1; MAX_REWARDED_RANKS + 1], 0).validate(), Err("too_many_rewarded_ranks"));
+        assert_eq!(terms(vec![], 10_001).validate(), Err("invalid_rating_carry"));
+        assert_eq!(terms(vec![10_000], 10_000).validate(), Ok(()));
+    }
+}
@@ -0,0 +1,96 @@
+//! Best-of-N match series.
+//!
+//! A series plays two matched players against each other on one fresh battle chain per game
+//! until one side has won a majority of its games. Games carry no stake of their own: both
+//! stakes sit in the series pot until the series is decided. Drawn games are replayed, and a
+//! series still undecided after twice its length is drawn.
+
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+
+/// Longest series a queue may ask for
+pub const MAX_BEST_OF: u8 = 7;
+
+/// Whether a queue may ask for series of `best_of` games; one game is a single battle
+pub fn validate_best_of(best_of: u8) -> Result<(), &'static str> {
+    if best_of % 2 == 0 || best_of > MAX_BEST_OF {
+        return Err("invalid_series_length");
+    }
+    Ok(())
+}
+
+/// Where a series stands after a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesOutcome {
+    Undecided,
+    Won { player1: bool },
+    Drawn,
+}
+
+/// Running score of a series
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct SeriesScore {
+    pub best_of: u8,
+    pub player1_wins: u8,
+    pub player2_wins: u8,
+    /// Games finished, drawn ones included
+    pub games_played: u8,
+}
+
+impl SeriesScore {
+    pub fn new(best_of: u8) -> Self {
+        Self { best_of, player1_wins: 0, player2_wins: 0, games_played: 0 }
+    }
+
+    /// Games a side must win to take the series
+    pub fn wins_needed(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+
+    /// Count a finished game, `None` for a draw, and say where the series stands
+    pub fn record(&mut self, player1_won: Option<bool>) -> SeriesOutcome {
+        self.games_played = self.games_played.saturating_add(1);
+        match player1_won {
+            Some(true) => self.player1_wins += 1,
+            Some(false) => self.player2_wins += 1,
+            None => {}
+        }
+        if self.player1_wins >= self.wins_needed() {
+            SeriesOutcome::Won { player1: true }
+        } else if self.player2_wins >= self.wins_needed() {
+            SeriesOutcome::Won { player1: false }
+        } else if self.games_played >= self.best_of.saturating_mul(2) {
+            SeriesOutcome::Drawn
+        } else {
+            SeriesOutcome::Undecided
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_best_of, SeriesOutcome, SeriesScore};
+
+    #[test]
+    fn series_are_decided_by_a_majority_of_games() {
+        let mut score = SeriesScore::new(3);
+        assert_eq!(score.record(Some(true)), SeriesOutcome::Undecided);
+        assert_eq!(score.record(None), SeriesOutcome::Undecided);
+        assert_eq!(score.record(Some(false)), SeriesOutcome::Undecided);
+        assert_eq!(score.record(Some(false)), SeriesOutcome::Won { player1: false });
+        assert_eq!((score.player1_wins, score.player2_wins, score.games_played), (1, 2, 4));
+
+        let mut drawn = SeriesScore::new(1);
+        assert_eq!(drawn.record(None), SeriesOutcome::Undecided);
+        assert_eq!(drawn.record(None), SeriesOutcome::Drawn);
+    }
+
+    #[test]
+    fn series_lengths_are_odd_and_bounded() {
+        assert_eq!(validate_best_of(1), Ok(()));
+        assert_eq!(validate_best_of(5), Ok(()));
+        assert_eq!(validate_best_of(0), Err("invalid_series_length"));
+        assert_eq!(validate_best_of(4), Err("invalid_series_length"));
+        assert_eq!(validate_best_of(9), Err("invalid_series_length"));
+    }
+}
@@ -1,66 +1,1840 @@
 #![cfg_attr(target_arch = "wasm32", no_main)]
 
+mod actions;
+#[allow(dead_code)] // Shared with the contract, which uses the combat helpers
 mod state;
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
-use async_graphql::{EmptySubscription, Object, Schema};
+use async_graphql::{
+    parser::types::{OperationType, Selection},
+    EmptySubscription, InputObject, Name, Object, Schema, SimpleObject, Subscription, Value,
+};
+use futures::{stream, Stream, StreamExt as _};
 use linera_sdk::{
-    graphql::GraphQLMutationRoot, linera_base_types::WithServiceAbi, views::View, Service,
-    ServiceRuntime,
+    graphql::GraphQLMutationRoot,
+    linera_base_types::{AccountOwner, Amount, ChainId, Timestamp, WithServiceAbi},
+    views::{MapView, View},
+    Service, ServiceRuntime,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use majorules::{
+    cooldown::cooldown_schedule,
+    counters::{self, checked_accumulate},
+    fees::{self, FeeBreakdown, PayoutRecords, PayoutVerdict},
+    matchmaking::{MatchmakingConfig, Pairing, QueueTerms},
+    practice::PracticeDifficulty,
+    quests::{self, Achievement, Quest},
+    time, invite_digest, BattleEndReason, BattleEvent, BattleRules, ChainVariant, Operation, Party, PlayerPreferences, QueueMode, ResultKind,
 };
 
-use majorules::Operation;
+use self::actions::{BattleAction, LobbyAction, PlayerAction};
+use self::state::{
+    special_cooldown_of, special_plan_start, ActiveEffect, Bet, BettingLeaderboardEntry, BettorStats, BattleRecord, BattleResult, BattleState, BattleStatus, CharacterClass, CharacterRegistryEntry, CompletedBattleRecord,
+    CharacterData, CharacterSnapshot, CombatAction, CrankRecord, CrankStats, CreationKind, ItemData, LeaderboardEntry, LobbyConfig, LobbyState, Market, NotableStreak, PlayerGlobalStats, PlayerState,
+    PredictionState, QuestProgress, QueueStats, RosterEntry, RoundResult, Season, SeasonStats, SeriesMetadata, Stance, TimingInfo, Tournament, MetadataView,
+};
+
+/// Most of a market's bets a single `marketDepth` query will read
+const MARKET_DEPTH_SCAN_CAP: u32 = 1_000;
+
+/// Number of anonymized top positions reported per side
+const TOP_POSITIONS: usize = 5;
+
+/// Most entries `storageReport` reads from any one view
+const STORAGE_SCAN_CAP: u32 = 1_000;
+
+/// Entries per view serialized by default to estimate sizes
+const STORAGE_SAMPLE_SIZE: u32 = 20;
+
+/// Most ids accepted by a single batch lookup
+const MAX_BATCH_IDS: usize = 50;
+
+/// Most completed-battle records one `completedBattles` page reads
+const ARCHIVE_SCAN_CAP: usize = 1_000;
+
+/// Most day buckets one `completedBattles` page visits
+const ARCHIVE_DAY_CAP: u64 = 366;
+
+/// Largest `completedBattles` page
+const ARCHIVE_PAGE_LIMIT: u32 = 100;
+
+/// Query complexity budget; batch lookups count once per requested id
+const MAX_QUERY_COMPLEXITY: usize = 2_000;
+
+pub struct MajorulesService {
+    state: ChainState,
+    runtime: Arc<ServiceRuntime<Self>>,
+}
+
+/// State loaded for the chain's variant
+enum ChainState {
+    Lobby(Arc<LobbyState>),
+    Battle(Arc<BattleState>),
+    Player(Arc<PlayerState>),
+    Prediction(Arc<PredictionState>),
+}
+
+linera_sdk::service!(MajorulesService);
+
+impl WithServiceAbi for MajorulesService {
+    type Abi = majorules::MajorulesAbi;
+}
+
+impl Service for MajorulesService {
+    type Parameters = majorules::Parameters;
+
+    async fn new(runtime: ServiceRuntime<Self>) -> Self {
+        let context = runtime.root_view_storage_context();
+        let header = MetadataView::load(context.clone()).await.expect("Failed to load state");
+        let state = match header.metadata.get().variant {
+            Some(ChainVariant::Battle) => ChainState::Battle(Arc::new(BattleState::load(context).await.expect("Failed to load state"))),
+            Some(ChainVariant::Player) => ChainState::Player(Arc::new(PlayerState::load(context).await.expect("Failed to load state"))),
+            Some(ChainVariant::Prediction) => {
+                ChainState::Prediction(Arc::new(PredictionState::load(context).await.expect("Failed to load state")))
+            }
+            _ => ChainState::Lobby(Arc::new(LobbyState::load(context).await.expect("Failed to load state"))),
+        };
+        MajorulesService {
+            state,
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    async fn handle_query(&self, query: Self::Query) -> Self::QueryResponse {
+        let mutation_root = Operation::mutation_root(self.runtime.clone());
+        match &self.state {
+            ChainState::Lobby(state) => {
+                let query_root = QueryRoot { state: state.clone(), runtime: self.runtime.clone() };
+                Schema::build(query_root, mutation_root, EmptySubscription)
+                    .limit_complexity(MAX_QUERY_COMPLEXITY)
+                    .finish()
+                    .execute(query)
+                    .await
+            }
+            ChainState::Battle(battle) => {
+                let query_root = BattleQueryRoot { battle: battle.clone(), runtime: self.runtime.clone() };
+                let subscription_root = BattleSubscriptionRoot { battle: battle.clone() };
+                let schema = Schema::build(query_root, mutation_root, subscription_root)
+                    .limit_complexity(MAX_QUERY_COMPLEXITY)
+                    .finish();
+                match subscription_fields(&query) {
+                    // One request gets one response, so a subscription answers with every item
+                    // it has up to this block
+                    Some(fields) => merge_subscription(schema.execute_stream(query).collect().await, fields),
+                    None => schema.execute(query).await,
+                }
+            }
+            ChainState::Player(player) => {
+                Schema::build(PlayerQueryRoot { player: player.clone(), runtime: self.runtime.clone() }, mutation_root, EmptySubscription)
+                    .limit_complexity(MAX_QUERY_COMPLEXITY)
+                    .finish()
+                    .execute(query)
+                    .await
+            }
+            ChainState::Prediction(prediction) => {
+                Schema::build(PredictionQueryRoot { prediction: prediction.clone() }, mutation_root, EmptySubscription)
+                    .limit_complexity(MAX_QUERY_COMPLEXITY)
+                    .finish()
+                    .execute(query)
+                    .await
+            }
+        }
+    }
+}
+
+struct QueryRoot {
+    state: Arc<LobbyState>,
+    runtime: Arc<ServiceRuntime<MajorulesService>>,
+}
+
+#[Object]
+impl QueryRoot {
+    async fn value(&self) -> &u64 {
+        self.state.value.get()
+    }
+
+    /// Recompute a completed battle's payout and check it against the
+    /// battle record, the winner's receipt and the treasury ledger
+    async fn verify_payout(&self, battle_chain: ChainId) -> async_graphql::Result<Option<PayoutVerdict>> {
+        let Some(record) = self.state.completed_battles.get(&battle_chain).await? else {
+            return Ok(None);
+        };
+        let receipt = self.state.payout_receipts.get(&battle_chain).await?;
+        let treasury_entry = self.state.treasury_ledger.get(&battle_chain).await?;
+
+        Ok(Some(fees::verify_payout(PayoutRecords {
+            recorded: FeeBreakdown {
+                total_stake: record.total_stake,
+                platform_fee_bps: record.platform_fee_bps,
+                platform_fee: record.platform_fee,
+                winner_payout: record.winner_payout,
+            },
+            receipt: receipt.map(|receipt| receipt.amount),
+            treasury_entry,
+            attestation: record.attestation,
+        })))
+    }
+
+    /// Prediction market by external id
+    async fn market(&self, id: u64) -> async_graphql::Result<Option<MarketEntry>> {
+        self.market_entry(id).await
+    }
+
+    /// Prediction markets by external id, in request order with nulls for misses
+    #[graphql(complexity = "ids.len() * child_complexity")]
+    async fn markets_by_ids(&self, ids: Vec<u64>) -> async_graphql::Result<Vec<Option<MarketEntry>>> {
+        check_batch_size(&ids)?;
+        let mut markets = Vec::with_capacity(ids.len());
+        for id in ids {
+            markets.push(self.market_entry(id).await?);
+        }
+        Ok(markets)
+    }
+
+    /// Stakes refunded from voided market `id`; zero for markets settled with a winner
+    async fn market_refunds(&self, id: u64) -> async_graphql::Result<Amount> {
+        let sequence = self.state.id_codec.get().decode(id);
+        Ok(self.state.market_refunds.get(&sequence).await?.unwrap_or_default())
+    }
+
+    /// Tournament by external id
+    async fn tournament(&self, id: u64) -> async_graphql::Result<Option<TournamentEntry>> {
+        let codec = self.state.id_codec.get();
+        let tournament = self.state.tournaments.get(&codec.decode(id)).await?;
+        Ok(tournament.map(|tournament| TournamentEntry { tournament_id: id, tournament }))
+    }
+
+    /// Most recently created tournaments first, at most `limit` (default 20)
+    async fn tournaments(&self, limit: Option<u32>) -> async_graphql::Result<Vec<TournamentEntry>> {
+        let codec = self.state.id_codec.get();
+        let newest = *self.state.tournament_count.get();
+        let mut tournaments = Vec::new();
+        for sequence in (1..=newest).rev().take(limit.unwrap_or(20) as usize) {
+            if let Some(tournament) = self.state.tournaments.get(&sequence).await? {
+                tournaments.push(TournamentEntry { tournament_id: codec.encode(sequence), tournament });
+            }
+        }
+        Ok(tournaments)
+    }
+
+    /// Series by external id, with its running score and games
+    async fn series(&self, id: u64) -> async_graphql::Result<Option<SeriesEntry>> {
+        let series = self.state.series.get(&self.state.id_codec.get().decode(id)).await?;
+        Ok(series.map(|series| SeriesEntry { series_id: id, series }))
+    }
+
+    /// The running season, with its live standings
+    async fn current_season(&self) -> Option<&Season> {
+        self.state.current_season.get().as_ref()
+    }
+
+    /// Season by id, running or ended
+    async fn season(&self, id: u32) -> async_graphql::Result<Option<Season>> {
+        match self.state.current_season.get() {
+            Some(season) if season.season_id == id => Ok(Some(season.clone())),
+            _ => Ok(self.state.seasons.get(&id).await?),
+        }
+    }
+
+    /// `owner`'s record in season `id`; lifetime stats live on their player chain
+    async fn season_stats(&self, id: u32, owner: AccountOwner) -> async_graphql::Result<Option<SeasonStats>> {
+        Ok(self.state.season_stats.get(&(id, owner)).await?)
+    }
+
+    /// Active or completed battle by chain
+    async fn battle(&self, chain: ChainId) -> async_graphql::Result<Option<BattleSummary>> {
+        self.battle_summary(chain).await
+    }
+
+    /// Active or completed battles by chain, in request order with nulls for misses
+    #[graphql(complexity = "chains.len() * child_complexity")]
+    async fn battles_by_chains(&self, chains: Vec<ChainId>) -> async_graphql::Result<Vec<Option<BattleSummary>>> {
+        check_batch_size(&chains)?;
+        let mut battles = Vec::with_capacity(chains.len());
+        for chain in chains {
+            battles.push(self.battle_summary(chain).await?);
+        }
+        Ok(battles)
+    }
+
+    /// Page through the completed-battle archive in (completedAt, battleChain) order. `player` and
+    /// `from` narrow the scan through the per-owner and per-day indexes; without either the records
+    /// themselves are scanned up to a bound. `approximate` is set whenever a bound cut the scan short
+    async fn completed_battles(
+        &self,
+        filter: Option<ArchiveFilter>,
+        cursor: Option<String>,
+        limit: Option<u32>,
+    ) -> async_graphql::Result<ArchivePage> {
+        let filter = filter.unwrap_or_default();
+        let after = cursor.as_deref().map(parse_archive_cursor).transpose()?;
+        let limit = limit.unwrap_or(ARCHIVE_PAGE_LIMIT).min(ARCHIVE_PAGE_LIMIT) as usize;
+
+        let mut approximate = false;
+        let mut scanned = BTreeMap::new();
+        let mut keys = if let Some(player) = filter.player {
+            self.state.completed_by_owner.get(&player).await?.unwrap_or_default()
+        } else if let Some(from) = filter.from {
+            let first = time::bucket_day(from).max(after.map_or(0, |(at, _)| time::bucket_day(at)));
+            let mut last = time::bucket_day(filter.to.unwrap_or_else(|| self.runtime.system_time()));
+            if last.saturating_sub(first) >= ARCHIVE_DAY_CAP {
+                approximate = true;
+                last = first + ARCHIVE_DAY_CAP - 1;
+            }
+            let mut keys = Vec::new();
+            for day in first..=last {
+                keys.extend(self.state.completed_by_day.get(&day).await?.unwrap_or_default());
+            }
+            keys
+        } else {
+            let mut seen = 0;
+            self.state.completed_battles.for_each_index_value_while(|battle_chain, record| {
+                seen += 1;
+                if seen > ARCHIVE_SCAN_CAP {
+                    return Ok(false);
+                }
+                scanned.insert((record.completed_at, battle_chain), record.into_owned());
+                Ok(true)
+            }).await?;
+            approximate = seen > ARCHIVE_SCAN_CAP;
+            scanned.keys().copied().collect()
+        };
+        keys.retain(|key| {
+            after.is_none_or(|after| *key > after)
+                && filter.from.is_none_or(|from| key.0 >= from)
+                && filter.to.is_none_or(|to| key.0 < to)
+        });
+        keys.sort();
+
+        let (mut battles, mut reads, mut last_examined, mut more) = (Vec::new(), 0, None, false);
+        for key in keys {
+            if battles.len() == limit {
+                more = true;
+                break;
+            }
+            let record = match scanned.remove(&key) {
+                Some(record) => record,
+                None => {
+                    if reads == ARCHIVE_SCAN_CAP {
+                        (approximate, more) = (true, true);
+                        break;
+                    }
+                    reads += 1;
+                    let Some(record) = self.state.completed_battles.get(&key.1).await? else {
+                        continue;
+                    };
+                    record
+                }
+            };
+            last_examined = Some(key);
+            if filter.matches(&record) {
+                battles.push(ArchivedBattle::from(record));
+            }
+        }
+        Ok(ArchivePage {
+            battles,
+            next_cursor: last_examined.filter(|_| more).map(|(at, battle_chain)| format!("{}:{battle_chain}", at.micros())),
+            approximate,
+        })
+    }
+
+    /// Registry row for a player
+    async fn player(&self, owner: AccountOwner) -> async_graphql::Result<Option<CharacterRegistryEntry>> {
+        Ok(self.state.character_registry.get(&owner.to_string()).await?)
+    }
+
+    /// Registry rows by owner, in request order with nulls for misses
+    #[graphql(complexity = "owners.len() * child_complexity")]
+    async fn players_by_owners(&self, owners: Vec<AccountOwner>) -> async_graphql::Result<Vec<Option<CharacterRegistryEntry>>> {
+        check_batch_size(&owners)?;
+        let mut players = Vec::with_capacity(owners.len());
+        for owner in owners {
+            players.push(self.state.character_registry.get(&owner.to_string()).await?);
+        }
+        Ok(players)
+    }
+
+    /// A minted character by id, with the chain that holds it
+    async fn character(&self, character_id: String) -> async_graphql::Result<Option<RosterEntry>> {
+        Ok(self.state.roster.get(&character_id).await?)
+    }
+
+    /// A private battle still waiting for its invited opponent, who must match its stake. Ids
+    /// are opaque, not secret, so only those holding the invite secret get an answer
+    async fn private_battle(&self, battle_id: u64, invite_secret: [u8; 32]) -> async_graphql::Result<Option<PrivateBattle>> {
+        let sequence = self.state.id_codec.get().decode(battle_id);
+        if self.state.private_battle_invites.get(&sequence).await? != Some(invite_digest(invite_secret)) {
+            return Ok(None);
+        }
+        let host = self.state.pending_private_battles.get(&sequence).await?;
+        Ok(host.map(|host| PrivateBattle {
+            battle_id,
+            host: host.player,
+            character_id: host.character_id,
+            class: host.character_snapshot.class,
+            level: host.character_snapshot.level,
+            stake: host.stake,
+            created_at: host.joined_at,
+        }))
+    }
+
+    /// Players waiting for a match, longest waiting first, optionally only those in `mode`
+    async fn queue(&self, mode: Option<QueueMode>) -> async_graphql::Result<Vec<QueueEntry>> {
+        Ok(self.state.queue_in_order().await?
+            .into_iter()
+            .filter(|entry| mode.is_none_or(|mode| mode == entry.mode))
+            .map(|entry| QueueEntry {
+                player: entry.player,
+                player_chain: entry.player_chain,
+                class: entry.character_snapshot.class,
+                level: entry.character_snapshot.level,
+                character_id: entry.character_id,
+                elo_rating: entry.elo_rating,
+                stake: entry.stake,
+                mode: entry.mode,
+                joined_at: entry.joined_at,
+            })
+            .collect())
+    }
+
+    /// Every queue's terms, with how many players wait in it and what it has matched so far
+    async fn queues(&self) -> async_graphql::Result<Vec<QueueSummary>> {
+        let mut waiting = [0u32; QueueMode::ALL.len()];
+        self.state.waiting_players.for_each_index_value(|_, entry| {
+            if let Some(index) = QueueMode::ALL.iter().position(|mode| *mode == entry.mode) {
+                waiting[index] += 1;
+            }
+            Ok(())
+        }).await?;
+        let shared = self.state.config.get().matchmaking;
+        let mut queues = Vec::with_capacity(QueueMode::ALL.len());
+        for (mode, waiting) in QueueMode::ALL.into_iter().zip(waiting) {
+            let terms = self.state.queue_terms.get(&mode).await?.unwrap_or_else(|| QueueTerms::default_for(mode));
+            queues.push(QueueSummary {
+                mode,
+                min_stake: terms.min_stake,
+                matchmaking: terms.matchmaking_or(shared),
+                waiting,
+                stats: self.state.queue_stats.get(&mode).await?.unwrap_or_default(),
+            });
+        }
+        Ok(queues)
+    }
+
+    /// `limit` leaderboard rows (all by default) from rank `offset + 1`, best ranked first
+    async fn leaderboard(&self, limit: Option<u32>, offset: Option<u32>) -> Vec<LeaderboardEntry> {
+        let entries = self.state.leaderboard.get();
+        entries.iter()
+            .skip(offset.unwrap_or(0) as usize)
+            .take(limit.map_or(entries.len(), |limit| limit as usize))
+            .cloned()
+            .collect()
+    }
+
+    /// Rank of `owner` on the leaderboard; `None` outside the top players it keeps
+    async fn player_rank(&self, owner: AccountOwner) -> Option<u64> {
+        self.state.leaderboard.get().iter().find(|entry| entry.player == owner).map(|entry| entry.rank)
+    }
+
+    /// The `limit` (ten by default) streaks most recently announced, newest first
+    async fn notable_streaks(&self, limit: Option<u32>) -> async_graphql::Result<Vec<NotableStreak>> {
+        let count = self.state.notable_streaks.count();
+        let limit = (limit.unwrap_or(10) as usize).min(MAX_BATCH_IDS);
+        let mut recent = self.state.notable_streaks.read(count.saturating_sub(limit)..count).await?;
+        recent.reverse();
+        Ok(recent)
+    }
+
+    /// `owner`'s win streak, and whether insurance will keep it through their next defeat
+    async fn win_streak(&self, owner: AccountOwner) -> async_graphql::Result<WinStreak> {
+        Ok(WinStreak {
+            streak: self.state.win_streaks.get(&owner).await?.unwrap_or(0),
+            insured: self.state.streak_insurance.contains_key(&owner).await?,
+        })
+    }
+
+    /// `owner`'s latest queue matches, oldest first; ranked matchmaking avoids opponents met
+    /// too often within the rematch window
+    async fn recent_pairings(&self, owner: AccountOwner) -> async_graphql::Result<Vec<Pairing>> {
+        Ok(self.state.recent_pairings.get(&owner).await?.unwrap_or_default())
+    }
+
+    /// Practice and casual wins `owner` has counted toward the progression gates
+    async fn progression_wins(&self, owner: AccountOwner) -> async_graphql::Result<u64> {
+        Ok(self.state.progression_wins.get(&owner).await?.unwrap_or(0))
+    }
+
+    /// Client-facing game configuration for battles created from now on
+    async fn game_config(&self) -> GameConfig {
+        let class_locked_stances = self.state.battle_rules.get().class_locked_stances;
+        let classes = majorules::CharacterClass::ALL.into_iter()
+            .map(|class| {
+                let allowed: &[majorules::Stance] = if class_locked_stances {
+                    majorules::allowed_stances(class)
+                } else {
+                    &majorules::Stance::ALL
+                };
+                ClassStances {
+                    class: class.into(),
+                    allowed_stances: allowed.iter().map(|stance| (*stance).into()).collect(),
+                }
+            })
+            .collect();
+        GameConfig {
+            class_locked_stances,
+            classes,
+            ranked_gates: *self.state.ranked_gates.get(),
+            progression: self.state.config.get().progression,
+            leveling: *self.state.leveling_config.get(),
+            minting: *self.state.mint_terms.get(),
+        }
+    }
+
+    /// Fee, matchmaking and admin settings, with the treasury that can change them
+    async fn lobby_config(&self) -> LobbyConfigEntry {
+        LobbyConfigEntry { config: *self.state.config.get(), treasury_owner: *self.state.treasury_owner.get() }
+    }
+
+    /// Rules the lobby stamps on battles it creates from now on
+    async fn battle_rules(&self) -> CurrentBattleRules {
+        let rules = self.state.battle_rules.get().clone();
+        CurrentBattleRules {
+            version: *self.state.rules_version.get(),
+            digest: rules.digest(),
+            rules,
+        }
+    }
+
+    /// Approximate size of the lobby's major views, for planning pruning.
+    /// Reads at most `scan_limit` entries and serializes at most `sample_size` of them per view
+    async fn storage_report(&self, scan_limit: Option<u32>, sample_size: Option<u32>) -> async_graphql::Result<Vec<ViewUsage>> {
+        let scan_limit = scan_limit.unwrap_or(STORAGE_SCAN_CAP).min(STORAGE_SCAN_CAP);
+        let sample_size = sample_size.unwrap_or(STORAGE_SAMPLE_SIZE).min(scan_limit);
+        let bounds = (scan_limit, sample_size);
+        let state = &self.state;
+
+        Ok(vec![
+            view_usage("waitingPlayers", &state.waiting_players, bounds, Some("until matched, left or expired")).await?,
+            view_usage("activeBattles", &state.active_battles, bounds, Some("until the battle completes")).await?,
+            view_usage("completedBattles", &state.completed_battles, bounds, None).await?,
+            view_usage("completedByOwner", &state.completed_by_owner, bounds, None).await?,
+            view_usage("completedByDay", &state.completed_by_day, bounds, None).await?,
+            view_usage("payoutReceipts", &state.payout_receipts, bounds, None).await?,
+            view_usage("relayedResults", &state.relayed_results, bounds, Some("two entries per completed battle")).await?,
+            view_usage("characterRegistry", &state.character_registry, bounds, None).await?,
+            view_usage("creationCounts", &state.creation_counts, bounds, None).await?,
+            view_usage("rejections", &state.rejections, bounds, Some("one entry per key, reset each window")).await?,
+            view_usage("treasuryLedger", &state.treasury_ledger, bounds, None).await?,
+            view_usage("dustLedger", &state.dust_ledger, bounds, None).await?,
+            view_usage("predictionMarkets", &state.prediction_markets, bounds, None).await?,
+            view_usage("bets", &state.bets, bounds, None).await?,
+            view_usage("marketBettors", &state.market_bettors, bounds, None).await?,
+            view_usage("pendingSettlements", &state.pending_settlements, bounds, Some("until payouts are drained")).await?,
+            view_usage("notificationOutbox", &state.notification_outbox, bounds, Some("until summaries are sent")).await?,
+            view_usage("marketDeadlines", &state.market_deadlines, bounds, Some("until the market closes")).await?,
+            view_usage("resultDeadlines", &state.result_deadlines, bounds, Some("until the market settles or is voided")).await?,
+            view_usage("crankPayouts", &state.crank_payouts, bounds, None).await?,
+            view_usage("crankRewards", &state.crank_rewards, bounds, None).await?,
+            view_usage("counterOverflows", &state.counter_overflows, bounds, Some("one entry per counter")).await?,
+            view_usage("battleSubscribers", &state.battle_subscribers, bounds, Some("until the battle completes")).await?,
+        ])
+    }
+
+    /// Completed battles a participant contested, awaiting operator review
+    async fn battles_for_review(&self) -> async_graphql::Result<Vec<ChainId>> {
+        Ok(self.state.review_queue.indices().await?)
+    }
+
+    /// Active battles whose summaries `owner` will receive
+    async fn battle_subscriptions(&self, owner: AccountOwner) -> async_graphql::Result<Vec<ChainId>> {
+        let mut battles = Vec::new();
+        self.state.battle_subscribers.for_each_index_value(|battle_chain, subscribers| {
+            if subscribers.iter().any(|subscriber| subscriber.owner == owner) {
+                battles.push(battle_chain);
+            }
+            Ok(())
+        }).await?;
+        Ok(battles)
+    }
+
+    /// What `owner` can do on the lobby right now: leave the queue, bet on open markets, crank
+    async fn available_actions(&self, owner: AccountOwner) -> async_graphql::Result<Vec<LobbyAction>> {
+        Ok(actions::lobby_actions(&self.state, owner, self.runtime.system_time()).await?)
+    }
+
+    /// Deferred lobby work still waiting for maintenance passes
+    async fn maintenance_backlog(&self) -> async_graphql::Result<MaintenanceBacklog> {
+        let mut pending = Vec::new();
+        self.state.pending_settlements.for_each_index_value(|market_id, settlement| {
+            pending.push((market_id, settlement.next_bettor));
+            Ok(())
+        }).await?;
+
+        let mut pending_payouts = 0;
+        for (market_id, next_bettor) in &pending {
+            let bettors = self.state.market_bettors.get(market_id).await?.unwrap_or_default();
+            pending_payouts += (bettors.len() as u64).saturating_sub(*next_bettor as u64);
+        }
+        Ok(MaintenanceBacklog {
+            settlement_markets: pending.len() as u64,
+            pending_payouts,
+        })
+    }
+
+    /// Crank totals, the pool bounties come from, and the most recent productive cranks
+    async fn crank_activity(&self, limit: Option<u32>) -> async_graphql::Result<CrankActivity> {
+        let count = self.state.crank_log.count();
+        let limit = (limit.unwrap_or(10) as usize).min(MAX_BATCH_IDS);
+        let mut recent = self.state.crank_log.read(count.saturating_sub(limit)..count).await?;
+        recent.reverse();
+        Ok(CrankActivity {
+            stats: self.state.crank_stats.get().clone(),
+            community_pool: *self.state.community_pool.get(),
+            recent,
+        })
+    }
+
+    /// Battle tokens minted and burned so far
+    async fn token_supply(&self) -> TokenSupplyReport {
+        let supply = *self.state.token_supply.get();
+        TokenSupplyReport { minted: supply.minted, burned: supply.burned, circulating: supply.circulating() }
+    }
+
+    /// Platform fees from battles completed on `day`, with lifetime revenue. Figures that a
+    /// saturated counter feeds into are reported as unreliable rather than at face value
+    async fn revenue_report(&self, day: u64) -> async_graphql::Result<RevenueReport> {
+        let battles = self.state.completed_by_day.get(&day).await?.unwrap_or_default();
+        let mut platform_fees = Amount::ZERO;
+        let mut fees_overflowed = false;
+        for (_, battle_chain) in &battles {
+            let fee = self.state.treasury_ledger.get(battle_chain).await?.unwrap_or_default();
+            fees_overflowed |= checked_accumulate(&mut platform_fees, fee);
+        }
+
+        let mut overflowed_counters = Vec::new();
+        self.state.counter_overflows.for_each_index_value(|counter, overflow| {
+            if overflow.affects(day) {
+                overflowed_counters.push(counter);
+            }
+            Ok(())
+        }).await?;
+        let reliable = !fees_overflowed && !overflowed_counters.iter().any(|counter| counter == counters::PLATFORM_REVENUE);
+
+        Ok(RevenueReport {
+            day,
+            battles: battles.len() as u64,
+            platform_fees,
+            lifetime_revenue: *self.state.total_platform_revenue.get(),
+            reliable,
+            overflowed_counters,
+        })
+    }
+
+    /// Creations `owner` may still perform today under the daily limits
+    async fn creation_allowance(&self, owner: AccountOwner) -> async_graphql::Result<CreationAllowance> {
+        let day = majorules::time::bucket_day(self.runtime.system_time());
+        let exempt = self.state.creation_exemptions.contains_key(&owner).await?;
+        let counts = self.state.creation_counts.get(&(day, owner)).await?.unwrap_or_default();
+        let caps = self.state.creation_caps.get();
+        let remaining = |kind| caps.cap(kind).saturating_sub(counts.count(kind));
+
+        Ok(CreationAllowance {
+            day,
+            exempt,
+            player_chains: remaining(CreationKind::PlayerChain),
+            private_battles: remaining(CreationKind::PrivateBattle),
+            queue_joins: remaining(CreationKind::QueueJoin),
+        })
+    }
+
+    /// Aggregate bet depth for a market. Bettor identities are only listed
+    /// when the market was created with public bettors
+    async fn market_depth(
+        &self,
+        market_id: u64,
+        viewer: Option<AccountOwner>,
+        #[graphql(desc = "Inclusive upper bounds of the position size buckets")]
+        bucket_bounds: Option<Vec<Amount>>,
+        scan_limit: Option<u32>,
+    ) -> async_graphql::Result<Option<MarketDepth>> {
+        let sequence = self.state.id_codec.get().decode(market_id);
+        let Some(market) = self.state.prediction_markets.get(&sequence).await? else {
+            return Ok(None);
+        };
+        let mut bucket_bounds = bucket_bounds.unwrap_or_else(|| {
+            vec![Amount::from_tokens(1), Amount::from_tokens(10), Amount::from_tokens(100)]
+        });
+        bucket_bounds.sort();
+        let scan_limit = scan_limit.unwrap_or(MARKET_DEPTH_SCAN_CAP).min(MARKET_DEPTH_SCAN_CAP);
+
+        // Only this market's bets are read, through its bettor list
+        let bettors = self.state.market_bettors.get(&sequence).await?.unwrap_or_default();
+        let approximate = bettors.len() > scan_limit as usize;
+        let keys: Vec<_> = bettors.into_iter().take(scan_limit as usize).map(|bettor| (sequence, bettor)).collect();
+        let scanned = keys.len() as u32;
+        let (player1_bets, player2_bets): (Vec<_>, Vec<_>) = self.state.bets.multi_get(&keys).await?
+            .into_iter()
+            .flatten()
+            .partition(|bet| bet.predicted_winner == market.player1_chain);
+
+        let viewer_position = viewer.and_then(|viewer| {
+            player1_bets.iter().chain(&player2_bets)
+                .find(|bet| bet.bettor == viewer)
+                .map(|bet| ViewerPosition { predicted_winner: bet.predicted_winner, amount: bet.amount })
+        });
+
+        Ok(Some(MarketDepth {
+            market_id,
+            approximate,
+            scanned,
+            player1: SideDepth::new(market.player1_chain, market.player1_pool, player1_bets, &bucket_bounds, market.public_bettors),
+            player2: SideDepth::new(market.player2_chain, market.player2_pool, player2_bets, &bucket_bounds, market.public_bettors),
+            viewer_position,
+        }))
+    }
+}
+
+impl QueryRoot {
+    async fn market_entry(&self, id: u64) -> async_graphql::Result<Option<MarketEntry>> {
+        let sequence = self.state.id_codec.get().decode(id);
+        let market = self.state.prediction_markets.get(&sequence).await?;
+        let platform_fee_bps = self.state.config.get().platform_fee_bps;
+        Ok(market.map(|market| MarketEntry::new(id, market, platform_fee_bps)))
+    }
+
+    async fn battle_summary(&self, chain: ChainId) -> async_graphql::Result<Option<BattleSummary>> {
+        if let Some(battle) = self.state.active_battles.get(&chain).await? {
+            return Ok(Some(BattleSummary {
+                battle_chain: battle.battle_chain,
+                player1: battle.player1,
+                player2: battle.player2,
+                total_stake: battle.total_stake,
+                status: battle.status,
+                created_at: battle.created_at,
+                winner: None,
+                completed_at: None,
+                rules_version: battle.rules_version,
+                rules_digest: None,
+                end_reason: None,
+            }));
+        }
+        let completed = self.state.completed_battles.get(&chain).await?;
+        Ok(completed.map(|battle| {
+            let drawn = battle.end_reason == BattleEndReason::Draw;
+            BattleSummary {
+                battle_chain: battle.battle_chain,
+                player1: battle.player1,
+                player2: battle.player2,
+                total_stake: battle.total_stake,
+                status: if drawn { BattleStatus::Draw } else { BattleStatus::Completed },
+                created_at: battle.created_at,
+                winner: (!drawn).then_some(battle.winner),
+                completed_at: Some(battle.completed_at),
+                rules_version: battle.rules_version,
+                rules_digest: Some(battle.rules_digest),
+                end_reason: Some(battle.end_reason),
+            }
+        }))
+    }
+}
+
+/// Queries served by battle chains
+struct BattleQueryRoot {
+    battle: Arc<BattleState>,
+    runtime: Arc<ServiceRuntime<MajorulesService>>,
+}
+
+#[Object]
+impl BattleQueryRoot {
+    async fn status(&self) -> BattleStatus {
+        *self.battle.status.get()
+    }
+
+    /// Round being fought, from 1
+    async fn current_round(&self) -> u8 {
+        *self.battle.current_round.get()
+    }
+
+    async fn max_rounds(&self) -> u8 {
+        *self.battle.max_rounds.get()
+    }
+
+    /// The round's phase and deadline with the chain's time when they were written, for
+    /// countdowns corrected for clock skew; `None` before the battle starts and once it ends
+    async fn timing(&self) -> Option<TimingInfo> {
+        self.battle.timing.get().clone()
+    }
+
+    /// The bot's difficulty when this is a practice battle, with the bot as player 2
+    async fn practice_difficulty(&self) -> Option<PracticeDifficulty> {
+        *self.battle.practice.get()
+    }
+
+    /// Both fighters' live combat state, player 1 first
+    async fn fighters(&self) -> Vec<Fighter> {
+        [self.battle.player1.get(), self.battle.player2.get()]
+            .into_iter()
+            .flatten()
+            .map(|participant| Fighter {
+                owner: participant.owner,
+                chain: participant.chain,
+                character_id: participant.character.nft_id.clone(),
+                class: participant.character.class,
+                level: participant.character.level,
+                hp: participant.current_hp,
+                hp_max: participant.character.hp_max,
+                special_cooldown: participant.special_cooldown,
+                effects: participant.effects.clone(),
+                stake: participant.stake,
+            })
+            .collect()
+    }
+
+    /// `owner`'s combo, or `None` when `owner` is not fighting here. Only the fighter's own
+    /// client should ask: a combo tells the opponent when to turtle
+    async fn my_combo(&self, owner: AccountOwner) -> Option<MyCombo> {
+        [self.battle.player1.get(), self.battle.player2.get()]
+            .into_iter()
+            .flatten()
+            .find(|participant| participant.owner == owner)
+            .map(|participant| MyCombo {
+                combo_stack: participant.combo_stack,
+                longest_combo: participant.longest_combo,
+                next_hit_bps: participant.combo_multiplier_bps(),
+            })
+    }
+
+    /// Resolved rounds in order, with every combat action
+    async fn round_results(&self) -> async_graphql::Result<Vec<RoundResult>> {
+        let mut results = Vec::new();
+        self.battle.round_results.for_each_index_value(|_, result| {
+            results.push(result.into_owned());
+            Ok(())
+        }).await?;
+        Ok(results)
+    }
+
+    /// What a client needs to re-simulate the fight and check every number: the rules, both
+    /// fighters as they started and each executed turn. `battle_round` narrows the turns to
+    /// one round
+    async fn replay(&self, battle_round: Option<u8>) -> async_graphql::Result<BattleReplay> {
+        let battle = &self.battle;
+        let mut turns = Vec::new();
+        battle.replay_turns.for_each_index_value(|(round, turn), replayed| {
+            if battle_round.is_none_or(|wanted| wanted == round) {
+                let [(player1_stance, player1_special), (player2_stance, player2_special)] = replayed.choices;
+                turns.push(ReplayedTurn {
+                    round,
+                    turn,
+                    seed: replayed.seed.iter().map(|byte| format!("{byte:02x}")).collect(),
+                    player1_stance,
+                    player1_special,
+                    player2_stance,
+                    player2_special,
+                    actions: replayed.into_owned().actions,
+                });
+            }
+            Ok(())
+        }).await?;
+        Ok(BattleReplay {
+            rules_version: *battle.rules_version.get(),
+            rules: battle.rules.get().clone(),
+            fighters: battle.opening_fighters.get().iter().map(|participant| OpeningFighter {
+                owner: participant.owner,
+                chain: participant.chain,
+                character: participant.character.clone(),
+                hp: participant.current_hp,
+                special_cooldown: participant.special_cooldown,
+            }).collect(),
+            turns,
+        })
+    }
+
+    /// Turns `owner` can submit and whether they can attest, as of now
+    async fn available_actions(&self, owner: AccountOwner) -> Vec<BattleAction> {
+        actions::battle_actions(&self.battle, owner, self.runtime.system_time()).await
+    }
+
+    /// Winner and end reason, set once the battle is over
+    async fn outcome(&self) -> Option<BattleOutcome> {
+        let end_reason = (*self.battle.end_reason.get())?;
+        Some(BattleOutcome { winner: *self.battle.winner.get(), end_reason, completed_at: *self.battle.completed_at.get() })
+    }
+
+    /// Check a plan of special uses against `owner`'s live cooldown on this battle chain,
+    /// together with the specials it already queued this round. Rounds and turns are absolute
+    async fn special_planner(
+        &self,
+        owner: AccountOwner,
+        planned_uses: Vec<PlannedUse>,
+    ) -> async_graphql::Result<Vec<ScheduledSpecial>> {
+        let battle = &self.battle;
+        if *battle.status.get() != BattleStatus::InProgress {
+            return Err("Battle is not in progress".into());
+        }
+        let (Some((start, mut uses)), Some(cooldown)) =
+            (special_plan_start(battle, owner).await, special_cooldown_of(battle, owner))
+        else {
+            return Err(format!("{owner} is not fighting in this battle").into());
+        };
+        uses.extend(planned_uses.into_iter().map(|planned| (planned.round, planned.turn)));
+        uses.sort();
+
+        let last_round = battle.max_rounds.get().saturating_add(battle.rules.get().sudden_death_rounds);
+        let rounds_remaining = last_round.saturating_add(1).saturating_sub(start.round);
+        let schedule = cooldown_schedule(cooldown, start, battle.turns_per_round(), rounds_remaining, &uses)
+            .map_err(|error| error.to_string())?;
+        Ok(schedule
+            .into_iter()
+            .map(|(round, turn, cooldown_after)| ScheduledSpecial { round, turn, cooldown_after })
+            .collect())
+    }
+}
+
+/// Live updates served by battle chains, read off the events the battle published on its
+/// stream. Every item carries its event's index on the stream: subscribe again with `after` set
+/// to the last one seen to get only what happened since
+struct BattleSubscriptionRoot {
+    battle: Arc<BattleState>,
+}
+
+#[Subscription]
+impl BattleSubscriptionRoot {
+    /// Both fighters' HP after each executed turn, player 1 first
+    async fn hp_changes(&self, after: Option<u32>) -> async_graphql::Result<impl Stream<Item = HpChange>> {
+        let events = events_after(&self.battle, after).await?;
+        Ok(stream::iter(events.into_iter().filter_map(|(event_index, event)| match event {
+            BattleEvent::TurnResolved { round, turn, player1_hp, player2_hp, .. } => {
+                Some(HpChange { event_index, round, turn, player1_hp, player2_hp })
+            }
+            _ => None,
+        })))
+    }
+
+    /// Each round as both fighters closed it
+    async fn round_results(&self, after: Option<u32>) -> async_graphql::Result<impl Stream<Item = RoundEnd>> {
+        let mut rounds = Vec::new();
+        for (event_index, event) in events_after(&self.battle, after).await? {
+            if let BattleEvent::RoundEnded { round, .. } = event {
+                if let Some(result) = self.battle.round_results.get(&round).await? {
+                    rounds.push(RoundEnd { event_index, result });
+                }
+            }
+        }
+        Ok(stream::iter(rounds))
+    }
+
+    /// The winner announcement, once the battle is over
+    async fn outcome(&self, after: Option<u32>) -> async_graphql::Result<impl Stream<Item = BattleFinish>> {
+        let events = events_after(&self.battle, after).await?;
+        Ok(stream::iter(events.into_iter().filter_map(|(event_index, event)| match event {
+            BattleEvent::BattleFinished { winner, end_reason, rounds_played } => {
+                Some(BattleFinish { event_index, winner, end_reason, rounds_played })
+            }
+            _ => None,
+        })))
+    }
+}
+
+/// The events in `battle`'s log past stream index `after`, oldest first, each with its index
+async fn events_after(battle: &BattleState, after: Option<u32>) -> async_graphql::Result<Vec<(u32, BattleEvent)>> {
+    Ok(battle.battle_log.elements().await?
+        .into_iter()
+        .filter(|(event_index, _)| after.is_none_or(|after| *event_index > after))
+        .collect())
+}
+
+/// Response keys of `request`'s root fields, when it is a subscription
+fn subscription_fields(request: &async_graphql::Request) -> Option<Vec<Name>> {
+    let document = async_graphql::parser::parse_query(&request.query).ok()?;
+    let (_, operation) = document.operations.iter().find(|(name, _)| {
+        request.operation_name.as_deref().is_none_or(|wanted| name.is_some_and(|name| name.as_str() == wanted))
+    })?;
+    if operation.node.ty != OperationType::Subscription {
+        return None;
+    }
+    Some(operation.node.selection_set.node.items
+        .iter()
+        .filter_map(|selection| match &selection.node {
+            Selection::Field(field) => Some(field.node.response_key().node.clone()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Fold a subscription's `responses` into one, listing each of `fields`' items in order
+fn merge_subscription(responses: Vec<async_graphql::Response>, fields: Vec<Name>) -> async_graphql::Response {
+    let mut items: Vec<(Name, Vec<Value>)> = fields.into_iter().map(|field| (field, Vec::new())).collect();
+    let mut errors = Vec::new();
+    for response in responses {
+        errors.extend(response.errors);
+        let Value::Object(data) = response.data else { continue };
+        for (field, item) in data {
+            match items.iter_mut().find(|(name, _)| *name == field) {
+                Some((_, list)) => list.push(item),
+                None => items.push((field, vec![item])),
+            }
+        }
+    }
+    let data = Value::Object(items.into_iter().map(|(field, list)| (field, Value::List(list))).collect());
+    let mut response = async_graphql::Response::new(data);
+    response.errors = errors;
+    response
+}
+
+struct PlayerQueryRoot {
+    player: Arc<PlayerState>,
+    runtime: Arc<ServiceRuntime<MajorulesService>>,
+}
+
+#[Object]
+impl PlayerQueryRoot {
+    /// Whether each of `owner`'s characters can queue, per mode, and why not
+    async fn available_actions(&self, owner: AccountOwner) -> async_graphql::Result<Vec<PlayerAction>> {
+        Ok(actions::player_actions(&self.player, owner).await?)
+    }
+
+    /// Id of the private battle this chain is hosting, to share with the invited opponent
+    async fn hosted_private_battle(&self) -> Option<u64> {
+        *self.player.hosted_private_battle.get()
+    }
+
+    /// Characters minted on this chain, in id order
+    async fn characters(&self) -> async_graphql::Result<Vec<CharacterData>> {
+        let mut characters = Vec::new();
+        self.player.characters.for_each_index_value(|_, character| {
+            characters.push(character.into_owned());
+            Ok(())
+        }).await?;
+        Ok(characters)
+    }
+
+    /// Items this chain owns, in id order, with the character each is equipped on
+    async fn items(&self) -> async_graphql::Result<Vec<ItemData>> {
+        let mut items = Vec::new();
+        self.player.items.for_each_index_value(|_, item| {
+            items.push(item.into_owned());
+            Ok(())
+        }).await?;
+        Ok(items)
+    }
+
+    /// Characters this chain has up for sale, with their asking price
+    async fn listings(&self) -> async_graphql::Result<Vec<CharacterListing>> {
+        let mut listings = Vec::new();
+        self.player.character_listings.for_each_index_value(|character_id, price| {
+            listings.push(CharacterListing { character_id, price: *price });
+            Ok(())
+        }).await?;
+        Ok(listings)
+    }
+
+    /// Befriended player chains, with their owners
+    async fn friends(&self) -> async_graphql::Result<Vec<Friend>> {
+        let mut friends = Vec::new();
+        self.player.friends.for_each_index_value(|chain, owner| {
+            friends.push(Friend { chain, owner: *owner });
+            Ok(())
+        }).await?;
+        Ok(friends)
+    }
+
+    /// Friend requests awaiting an answer, to accept with `AcceptFriend`
+    async fn friend_requests(&self) -> async_graphql::Result<Vec<Friend>> {
+        let mut requests = Vec::new();
+        self.player.friend_requests.for_each_index_value(|chain, owner| {
+            requests.push(Friend { chain, owner: *owner });
+            Ok(())
+        }).await?;
+        Ok(requests)
+    }
+
+    /// Party this chain is in, as its leader last announced it
+    async fn party(&self) -> &Option<Party> {
+        self.player.party.get()
+    }
+
+    /// Leader chains of the parties inviting this chain, to accept with `AcceptPartyInvite`
+    async fn party_invites(&self) -> async_graphql::Result<Vec<ChainId>> {
+        Ok(self.player.party_invites.indices().await?)
+    }
+
+    /// Battle tokens this chain holds, not counting purchase escrows, locked stakes or bets
+    /// awaiting the lobby
+    async fn token_balance(&self) -> Amount {
+        *self.player.battle_token_balance.get()
+    }
+
+    /// Battle tokens locked as stakes of queue entries and battles in progress
+    async fn locked_stake(&self) -> async_graphql::Result<Amount> {
+        let mut locked = Amount::ZERO;
+        self.player.locked_stakes.for_each_index_value(|_, stake| {
+            locked = locked.saturating_add(*stake);
+            Ok(())
+        }).await?;
+        Ok(locked)
+    }
+
+    async fn active_character(&self) -> Option<&String> {
+        self.player.active_character.get().as_ref()
+    }
+
+    /// Lifetime battle statistics
+    async fn stats(&self) -> &PlayerGlobalStats {
+        self.player.player_stats.get()
+    }
+
+    /// Today's quests with this chain's progress toward each; progress starts over every day
+    async fn quests(&self) -> async_graphql::Result<Vec<QuestStatus>> {
+        let today = *self.player.quest_day.get() == time::bucket_day(self.runtime.system_time());
+        let mut statuses = Vec::new();
+        for quest in quests::DAILY_QUESTS {
+            let progress = if today {
+                self.player.quest_progress.get(quest.quest_id).await?.unwrap_or_default()
+            } else {
+                QuestProgress::default()
+            };
+            statuses.push(QuestStatus { quest, progress: progress.progress, claimed: progress.claimed });
+        }
+        Ok(statuses)
+    }
+
+    /// Every achievement, with when this chain unlocked it if it has
+    async fn achievements(&self) -> async_graphql::Result<Vec<AchievementStatus>> {
+        let mut statuses = Vec::new();
+        for achievement in quests::ACHIEVEMENTS {
+            let unlocked_at = self.player.achievements.get(achievement.achievement_id).await?;
+            statuses.push(AchievementStatus { achievement, unlocked_at });
+        }
+        Ok(statuses)
+    }
+
+    /// Client settings stored with `SetPreferences`
+    async fn preferences(&self) -> &PlayerPreferences {
+        self.player.preferences.get()
+    }
+
+    /// Battles fought from this chain, newest first: `limit` (ten by default) from `offset`,
+    /// counting only battles with `result_filter` when one is given
+    async fn battle_history(
+        &self,
+        limit: Option<u32>,
+        offset: Option<u32>,
+        result_filter: Option<BattleResult>,
+    ) -> async_graphql::Result<Vec<HistoryEntry>> {
+        let limit = (limit.unwrap_or(10) as usize).min(MAX_BATCH_IDS);
+        let mut skip = offset.unwrap_or(0) as usize;
+        let mut history = Vec::new();
+        let mut end = self.player.battle_history_order.count();
+        while end > 0 && history.len() < limit {
+            let start = end.saturating_sub(MAX_BATCH_IDS);
+            for battle_chain in self.player.battle_history_order.read(start..end).await?.into_iter().rev() {
+                let Some(record) = self.player.battle_history.get(&battle_chain).await? else {
+                    continue;
+                };
+                if result_filter.is_some_and(|result| result != record.result) {
+                    continue;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                history.push(HistoryEntry::from(record));
+                if history.len() == limit {
+                    break;
+                }
+            }
+            end = start;
+        }
+        Ok(history)
+    }
+
+    /// Everything this chain recorded about one battle it fought
+    async fn battle_detail(&self, battle_chain: ChainId) -> async_graphql::Result<Option<BattleRecord>> {
+        Ok(self.player.battle_history.get(&battle_chain).await?)
+    }
+
+    /// Account overview for this player chain
+    async fn profile(&self) -> async_graphql::Result<PlayerProfile> {
+        let stats = self.player.player_stats.get();
+        Ok(PlayerProfile {
+            owner: *self.player.owner.get(),
+            lobby_chain: *self.player.lobby_chain_id.get(),
+            characters: self.player.characters.count().await? as u64,
+            total_battles: stats.total_battles,
+            wins: stats.wins,
+            losses: stats.losses,
+            draws: stats.draws,
+            current_streak: stats.current_streak,
+            best_streak: stats.best_streak,
+            preferences: self.player.preferences.get().clone(),
+            overflowed_counters: self.player.counter_overflows.indices().await?,
+        })
+    }
+}
+
+struct PredictionQueryRoot {
+    prediction: Arc<PredictionState>,
+}
+
+impl PredictionQueryRoot {
+    /// Prediction chains number their markets themselves and expose them unencoded
+    fn entry(&self, market: Market) -> MarketEntry {
+        MarketEntry::new(market.market_id, market, *self.prediction.platform_fee_bps.get())
+    }
+}
+
+#[Object]
+impl PredictionQueryRoot {
+    async fn market(&self, id: u64) -> async_graphql::Result<Option<MarketEntry>> {
+        Ok(self.prediction.markets.get(&id).await?.map(|market| self.entry(market)))
+    }
+
+    /// Markets in creation order, `limit` (ten by default) from `offset`
+    async fn markets(&self, limit: Option<u32>, offset: Option<u32>) -> async_graphql::Result<Vec<MarketEntry>> {
+        let limit = (limit.unwrap_or(10) as usize).min(MAX_BATCH_IDS);
+        let mut markets = Vec::new();
+        let mut skip = offset.unwrap_or(0);
+        self.prediction.markets.for_each_index_value_while(|_, market| {
+            if skip > 0 {
+                skip -= 1;
+            } else {
+                markets.push(self.entry(market.into_owned()));
+            }
+            Ok(markets.len() < limit)
+        }).await?;
+        Ok(markets)
+    }
+
+    /// Market opened for `battle_chain`, if any
+    async fn market_for_battle(&self, battle_chain: ChainId) -> async_graphql::Result<Option<MarketEntry>> {
+        let Some(id) = self.prediction.battle_to_market.get(&battle_chain).await? else {
+            return Ok(None);
+        };
+        Ok(self.prediction.markets.get(&id).await?.map(|market| self.entry(market)))
+    }
+
+    /// `owner`'s bet on market `id`
+    async fn bet(&self, id: u64, owner: AccountOwner) -> async_graphql::Result<Option<Bet>> {
+        Ok(self.prediction.bets.get(&(id, owner)).await?)
+    }
+
+    async fn total_volume(&self) -> Amount {
+        *self.prediction.total_volume.get()
+    }
+
+    async fn total_fees_collected(&self) -> Amount {
+        *self.prediction.total_fees_collected.get()
+    }
+
+    async fn platform_fee_bps(&self) -> u16 {
+        *self.prediction.platform_fee_bps.get()
+    }
+
+    /// Top `limit` bettors (all it keeps by default), most profitable first
+    async fn betting_leaderboard(&self, limit: Option<u32>) -> Vec<BettingLeaderboardEntry> {
+        let entries = self.prediction.betting_leaderboard.get();
+        entries.iter().take(limit.map_or(entries.len(), |limit| limit as usize)).cloned().collect()
+    }
+
+    /// `owner`'s betting record on this chain, once they bet here
+    async fn bettor_stats(&self, owner: AccountOwner) -> async_graphql::Result<Option<BettorStats>> {
+        Ok(self.prediction.bettor_stats.get(&owner).await?)
+    }
+}
+
+/// Decode a `completedBattles` cursor, `<completedAt micros>:<battle chain>`
+fn parse_archive_cursor(cursor: &str) -> async_graphql::Result<(Timestamp, ChainId)> {
+    let invalid = || async_graphql::Error::new(format!("Invalid archive cursor {cursor:?}"));
+    let (micros, battle_chain) = cursor.split_once(':').ok_or_else(invalid)?;
+    let micros = micros.parse::<u64>().map_err(|_| invalid())?;
+    let battle_chain = battle_chain.parse::<ChainId>().map_err(|_| invalid())?;
+    Ok((Timestamp::from(micros), battle_chain))
+}
+
+fn check_batch_size<T>(ids: &[T]) -> async_graphql::Result<()> {
+    if ids.len() > MAX_BATCH_IDS {
+        return Err(format!("At most {MAX_BATCH_IDS} ids can be looked up at once").into());
+    }
+    Ok(())
+}
+
+/// Prediction market under its external id, with the odds each side pays at the current pools
+#[derive(SimpleObject)]
+struct MarketEntry {
+    market_id: u64,
+    #[graphql(flatten)]
+    market: Market,
+    /// Decimal odds in basis points; null while nobody backs the side
+    player1_odds: Option<u64>,
+    player2_odds: Option<u64>,
+}
+
+/// Tournament under its external id
+#[derive(SimpleObject)]
+struct TournamentEntry {
+    tournament_id: u64,
+    #[graphql(flatten)]
+    tournament: Tournament,
+}
+
+/// Series under its external id
+#[derive(SimpleObject)]
+struct SeriesEntry {
+    series_id: u64,
+    #[graphql(flatten)]
+    series: SeriesMetadata,
+}
+
+impl MarketEntry {
+    fn new(market_id: u64, market: Market, platform_fee_bps: u16) -> Self {
+        let player1_odds = market.odds(market.player1_chain, platform_fee_bps);
+        let player2_odds = market.odds(market.player2_chain, platform_fee_bps);
+        Self { market_id, market, player1_odds, player2_odds }
+    }
+}
+
+/// Private battle open to its invited opponent
+#[derive(SimpleObject)]
+struct PrivateBattle {
+    battle_id: u64,
+    host: AccountOwner,
+    character_id: String,
+    class: CharacterClass,
+    level: u16,
+    stake: Amount,
+    created_at: Timestamp,
+}
+
+/// Battle as tracked by the lobby, whether still running or completed
+#[derive(SimpleObject)]
+struct BattleSummary {
+    battle_chain: ChainId,
+    player1: AccountOwner,
+    player2: AccountOwner,
+    total_stake: Amount,
+    status: BattleStatus,
+    created_at: Timestamp,
+    winner: Option<AccountOwner>,
+    completed_at: Option<Timestamp>,
+    rules_version: u32,
+    /// Digest of the rules the battle ran under, reported once it completes
+    rules_digest: Option<u64>,
+    end_reason: Option<BattleEndReason>,
+}
+
+/// Archive filters; all given ones must match. `from` is inclusive and `to` exclusive
+#[derive(Default, InputObject)]
+struct ArchiveFilter {
+    /// Either side of the battle
+    player: Option<AccountOwner>,
+    /// Either side's class
+    class: Option<CharacterClass>,
+    min_stake: Option<Amount>,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    result_kind: Option<ResultKind>,
+}
+
+impl ArchiveFilter {
+    fn matches(&self, record: &CompletedBattleRecord) -> bool {
+        self.player.is_none_or(|player| record.player1 == player || record.player2 == player)
+            && self.class.is_none_or(|class| record.player1_class == class || record.player2_class == class)
+            && self.min_stake.is_none_or(|min_stake| record.total_stake >= min_stake)
+            && self.result_kind.is_none_or(|kind| record.end_reason.kind() == kind)
+    }
+}
+
+/// Compact completed-battle record; action logs stay on the battle chain
+#[derive(SimpleObject)]
+struct ArchivedBattle {
+    battle_chain: ChainId,
+    player1: AccountOwner,
+    player2: AccountOwner,
+    player1_class: CharacterClass,
+    player2_class: CharacterClass,
+    winner: AccountOwner,
+    total_stake: Amount,
+    rounds_played: u8,
+    end_reason: BattleEndReason,
+    rules_digest: u64,
+    created_at: Timestamp,
+    completed_at: Timestamp,
+}
+
+impl From<CompletedBattleRecord> for ArchivedBattle {
+    fn from(record: CompletedBattleRecord) -> Self {
+        ArchivedBattle {
+            battle_chain: record.battle_chain,
+            player1: record.player1,
+            player2: record.player2,
+            player1_class: record.player1_class,
+            player2_class: record.player2_class,
+            winner: record.winner,
+            total_stake: record.total_stake,
+            rounds_played: record.rounds_played,
+            end_reason: record.end_reason,
+            rules_digest: record.rules_digest,
+            created_at: record.created_at,
+            completed_at: record.completed_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct ArchivePage {
+    battles: Vec<ArchivedBattle>,
+    /// Pass back as `cursor` to continue; null once the archive is exhausted
+    next_cursor: Option<String>,
+    approximate: bool,
+}
+
+#[derive(SimpleObject)]
+struct PlayerProfile {
+    owner: Option<AccountOwner>,
+    lobby_chain: Option<ChainId>,
+    characters: u64,
+    total_battles: u64,
+    wins: u64,
+    losses: u64,
+    draws: u64,
+    current_streak: u64,
+    best_streak: u64,
+    preferences: PlayerPreferences,
+    /// Lifetime counters pinned at their maximum, such as `total_earnings`
+    overflowed_counters: Vec<String>,
+}
+
+/// Player waiting in the lobby queue
+#[derive(SimpleObject)]
+struct QueueEntry {
+    player: AccountOwner,
+    player_chain: ChainId,
+    character_id: String,
+    class: CharacterClass,
+    level: u16,
+    /// Rating matchmaking pairs the player by
+    elo_rating: u64,
+    stake: Amount,
+    mode: QueueMode,
+    joined_at: Timestamp,
+}
+
+/// One queue's terms and activity
+#[derive(SimpleObject)]
+struct QueueSummary {
+    mode: QueueMode,
+    min_stake: Amount,
+    /// Settings the queue pairs by, its own or the lobby-wide ones
+    matchmaking: MatchmakingConfig,
+    waiting: u32,
+    stats: QueueStats,
+}
+
+#[derive(SimpleObject)]
+struct WinStreak {
+    streak: u64,
+    insured: bool,
+}
+
+/// A daily quest and how far this chain is toward it
+#[derive(SimpleObject)]
+struct QuestStatus {
+    #[graphql(flatten)]
+    quest: Quest,
+    progress: u64,
+    claimed: bool,
+}
+
+#[derive(SimpleObject)]
+struct AchievementStatus {
+    #[graphql(flatten)]
+    achievement: Achievement,
+    unlocked_at: Option<Timestamp>,
+}
+
+/// Another player chain and the account that owns it
+#[derive(SimpleObject)]
+struct Friend {
+    chain: ChainId,
+    owner: AccountOwner,
+}
+
+#[derive(SimpleObject)]
+struct CharacterListing {
+    character_id: String,
+    price: Amount,
+}
+
+/// One side of a battle as it stands. Combos are left out: see `myCombo`
+#[derive(SimpleObject)]
+struct Fighter {
+    owner: AccountOwner,
+    chain: ChainId,
+    character_id: String,
+    class: CharacterClass,
+    level: u16,
+    hp: u32,
+    hp_max: u32,
+    special_cooldown: u8,
+    effects: Vec<ActiveEffect>,
+    stake: Amount,
+}
+
+/// A battle from its start, for re-simulating it turn by turn
+#[derive(SimpleObject)]
+struct BattleReplay {
+    rules_version: u32,
+    rules: BattleRules,
+    /// Player 1 first
+    fighters: Vec<OpeningFighter>,
+    /// In round and turn order
+    turns: Vec<ReplayedTurn>,
+}
+
+/// One side of a battle as it started
+#[derive(SimpleObject)]
+struct OpeningFighter {
+    owner: AccountOwner,
+    chain: ChainId,
+    character: CharacterSnapshot,
+    hp: u32,
+    special_cooldown: u8,
+}
+
+/// One executed turn: the seed both attacks rolled from, as hex, each fighter's choice, and the
+/// actions the chain resolved. Player 1 rolls from stream 0 of the seed, player 2 from stream 1
+#[derive(SimpleObject)]
+struct ReplayedTurn {
+    round: u8,
+    turn: u8,
+    seed: String,
+    player1_stance: Stance,
+    player1_special: bool,
+    player2_stance: Stance,
+    player2_special: bool,
+    actions: Vec<CombatAction>,
+}
+
+/// Both fighters' HP after an executed turn, from the battle's `TurnResolved` event
+#[derive(SimpleObject)]
+struct HpChange {
+    event_index: u32,
+    round: u8,
+    turn: u8,
+    player1_hp: u32,
+    player2_hp: u32,
+}
+
+/// A round both fighters closed, from the battle's `RoundEnded` event
+#[derive(SimpleObject)]
+struct RoundEnd {
+    event_index: u32,
+    result: RoundResult,
+}
+
+/// The battle's `BattleFinished` event
+#[derive(SimpleObject)]
+struct BattleFinish {
+    event_index: u32,
+    /// Unset for a draw
+    winner: Option<AccountOwner>,
+    end_reason: BattleEndReason,
+    rounds_played: u8,
+}
+
+/// How a finished battle ended, as its battle chain decided
+#[derive(SimpleObject)]
+struct BattleOutcome {
+    /// Unset for a draw
+    winner: Option<AccountOwner>,
+    end_reason: BattleEndReason,
+    completed_at: Option<Timestamp>,
+}
+
+/// One battle in a player chain's history
+#[derive(SimpleObject)]
+struct HistoryEntry {
+    battle_chain: ChainId,
+    character_id: String,
+    won: bool,
+    result: BattleResult,
+    xp_gained: u64,
+    payout: Amount,
+    end_reason: BattleEndReason,
+    completed_at: Timestamp,
+}
+
+impl From<BattleRecord> for HistoryEntry {
+    fn from(record: BattleRecord) -> Self {
+        Self {
+            battle_chain: record.battle_chain,
+            character_id: record.character_used,
+            won: record.result == BattleResult::Won,
+            result: record.result,
+            xp_gained: record.xp_gained,
+            payout: record.payout,
+            end_reason: record.end_reason,
+            completed_at: record.completed_at,
+        }
+    }
+}
+
+/// Battle configuration clients need to offer valid choices
+#[derive(SimpleObject)]
+struct GameConfig {
+    class_locked_stances: bool,
+    classes: Vec<ClassStances>,
+    /// Requirements for joining the ranked queue
+    ranked_gates: majorules::RankedGates,
+    /// What the account must show before staked or ranked queues take it
+    progression: majorules::ProgressionGates,
+    /// Level cap for player chains created from now on
+    leveling: majorules::leveling::LevelingConfig,
+    /// Mint cost, roster cap and cooldown for player chains created from now on
+    minting: majorules::minting::MintTerms,
+}
+
+#[derive(SimpleObject)]
+struct LobbyConfigEntry {
+    #[graphql(flatten)]
+    config: LobbyConfig,
+    treasury_owner: Option<AccountOwner>,
+}
+
+#[derive(SimpleObject)]
+struct ClassStances {
+    class: CharacterClass,
+    allowed_stances: Vec<Stance>,
+}
+
+/// Pending deferred work per maintenance phase
+#[derive(SimpleObject)]
+struct MaintenanceBacklog {
+    /// Settled markets with payouts outstanding
+    settlement_markets: u64,
+    /// Bets across those markets still awaiting a payout
+    pending_payouts: u64,
+}
+
+#[derive(SimpleObject)]
+struct CrankActivity {
+    stats: CrankStats,
+    community_pool: Amount,
+    /// Newest first
+    recent: Vec<CrankRecord>,
+}
+
+/// Rules new battles are created with
+#[derive(SimpleObject)]
+struct CurrentBattleRules {
+    version: u32,
+    digest: u64,
+    #[graphql(flatten)]
+    rules: BattleRules,
+}
+
+/// A turn on which the planner should spend a special
+#[derive(InputObject)]
+struct PlannedUse {
+    round: u8,
+    turn: u8,
+}
+
+/// One fighter's combo
+#[derive(SimpleObject)]
+struct MyCombo {
+    combo_stack: u8,
+    /// Highest stack reached this battle
+    longest_combo: u8,
+    /// Damage multiplier the fighter's next attack gets, in basis points
+    next_hit_bps: u32,
+}
+
+/// A planned special that fits the cooldown rules
+#[derive(SimpleObject)]
+struct ScheduledSpecial {
+    round: u8,
+    turn: u8,
+    /// Cooldown left at the fighter's following turn; 0 means the special is ready again
+    cooldown_after: u8,
+}
+
+/// Revenue attributed to one day of completed battles
+#[derive(SimpleObject)]
+struct RevenueReport {
+    day: u64,
+    battles: u64,
+    /// Treasury fees of the battles completed that day
+    platform_fees: Amount,
+    lifetime_revenue: Amount,
+    /// False when `platformFees` or `lifetimeRevenue` include a saturated sum
+    reliable: bool,
+    /// Counters pinned at their maximum on or before `day`
+    overflowed_counters: Vec<String>,
+}
+
+/// Lobby's battle token ledger
+#[derive(SimpleObject)]
+struct TokenSupplyReport {
+    minted: Amount,
+    /// Fees collected from battles and markets
+    burned: Amount,
+    circulating: Amount,
+}
+
+/// Remaining daily creation allowances for an owner
+#[derive(SimpleObject)]
+struct CreationAllowance {
+    day: u64,
+    /// Exempt owners are not limited
+    exempt: bool,
+    player_chains: u32,
+    private_battles: u32,
+    queue_joins: u32,
+}
+
+/// Estimated footprint of one map view
+#[derive(SimpleObject)]
+struct ViewUsage {
+    name: String,
+    /// Entries read, which is the full count unless `approximate`
+    entries: u32,
+    /// Set when the scan cap was reached before every entry was read
+    approximate: bool,
+    /// Entries whose values were serialized for the size estimate
+    sampled: u32,
+    avg_entry_bytes: u64,
+    /// `entries` times the average sampled size
+    estimated_bytes: u64,
+    /// Largest sampled value, by key
+    largest: Option<LargestEntry>,
+    /// How long entries are kept; `None` when they are kept forever
+    retention: Option<String>,
+}
+
+#[derive(SimpleObject)]
+struct LargestEntry {
+    key: String,
+    bytes: u64,
+}
 
-use self::state::LobbyState;
+/// Count a map's entries up to `scan_limit`, serializing the first `sample_size` values
+async fn view_usage<K, V>(
+    name: &str,
+    map: &MapView<K, V>,
+    (scan_limit, sample_size): (u32, u32),
+    retention: Option<&str>,
+) -> async_graphql::Result<ViewUsage>
+where
+    K: Serialize + DeserializeOwned + Send + Sync + std::fmt::Debug + 'static,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    let mut entries = 0;
+    let mut approximate = false;
+    let mut sampled_bytes = 0;
+    let mut largest: Option<LargestEntry> = None;
+    map.for_each_index_value_while(|key, value| {
+        if entries == scan_limit {
+            approximate = true;
+            return Ok(false);
+        }
+        if entries < sample_size {
+            let bytes = linera_sdk::bcs::to_bytes(value.as_ref()).map(|bytes| bytes.len() as u64).unwrap_or(0);
+            sampled_bytes += bytes;
+            if largest.as_ref().is_none_or(|entry| bytes > entry.bytes) {
+                largest = Some(LargestEntry { key: format!("{key:?}"), bytes });
+            }
+        }
+        entries += 1;
+        Ok(true)
+    }).await?;
 
-pub struct MajorulesService {
-    state: LobbyState,
-    runtime: Arc<ServiceRuntime<Self>>,
+    let sampled = entries.min(sample_size);
+    let avg_entry_bytes = if sampled > 0 { sampled_bytes / sampled as u64 } else { 0 };
+    Ok(ViewUsage {
+        name: name.to_string(),
+        entries,
+        approximate,
+        sampled,
+        avg_entry_bytes,
+        estimated_bytes: avg_entry_bytes * entries as u64,
+        largest,
+        retention: retention.map(str::to_string),
+    })
 }
 
-linera_sdk::service!(MajorulesService);
+/// Aggregated view of a prediction market's bets
+#[derive(SimpleObject)]
+struct MarketDepth {
+    market_id: u64,
+    /// Set when the scan cap was reached before every bet was read
+    approximate: bool,
+    scanned: u32,
+    player1: SideDepth,
+    player2: SideDepth,
+    viewer_position: Option<ViewerPosition>,
+}
 
-impl WithServiceAbi for MajorulesService {
-    type Abi = majorules::MajorulesAbi;
+/// Bets backing one side of a market
+#[derive(SimpleObject)]
+struct SideDepth {
+    predicted_winner: ChainId,
+    bettor_count: u32,
+    total_pool: Amount,
+    largest_position: Amount,
+    top_positions: Vec<Amount>,
+    histogram: Vec<PositionBucket>,
+    /// Only populated for markets with public bettors
+    bettors: Option<Vec<BettorPosition>>,
 }
 
-impl Service for MajorulesService {
-    type Parameters = ();
+impl SideDepth {
+    fn new(predicted_winner: ChainId, total_pool: Amount, mut bets: Vec<Bet>, bucket_bounds: &[Amount], public_bettors: bool) -> Self {
+        bets.sort_by(|a, b| b.amount.cmp(&a.amount));
 
-    async fn new(runtime: ServiceRuntime<Self>) -> Self {
-        let state = LobbyState::load(runtime.root_view_storage_context())
-            .await
-            .expect("Failed to load state");
-        MajorulesService {
-            state,
-            runtime: Arc::new(runtime),
+        let mut histogram: Vec<PositionBucket> = bucket_bounds.iter()
+            .map(|bound| PositionBucket { upper_bound: Some(*bound), count: 0 })
+            .chain([PositionBucket { upper_bound: None, count: 0 }])
+            .collect();
+        for bet in &bets {
+            let index = bucket_bounds.iter().position(|bound| bet.amount <= *bound).unwrap_or(bucket_bounds.len());
+            histogram[index].count += 1;
         }
-    }
 
-    async fn handle_query(&self, query: Self::Query) -> Self::QueryResponse {
-        Schema::build(
-            QueryRoot {
-                value: *self.state.value.get(),
-            },
-            Operation::mutation_root(self.runtime.clone()),
-            EmptySubscription,
-        )
-        .finish()
-        .execute(query)
-        .await
+        SideDepth {
+            predicted_winner,
+            bettor_count: bets.len() as u32,
+            total_pool,
+            largest_position: bets.first().map_or(Amount::ZERO, |bet| bet.amount),
+            top_positions: bets.iter().take(TOP_POSITIONS).map(|bet| bet.amount).collect(),
+            histogram,
+            bettors: public_bettors.then(|| {
+                bets.iter().map(|bet| BettorPosition { bettor: bet.bettor, amount: bet.amount }).collect()
+            }),
+        }
     }
 }
 
-struct QueryRoot {
-    value: u64,
+/// Count of positions up to `upper_bound` (unbounded for the last bucket)
+#[derive(SimpleObject)]
+struct PositionBucket {
+    upper_bound: Option<Amount>,
+    count: u32,
 }
 
-#[Object]
-impl QueryRoot {
-    async fn value(&self) -> &u64 {
-        &self.value
-    }
+#[derive(SimpleObject)]
+struct BettorPosition {
+    bettor: AccountOwner,
+    amount: Amount,
+}
+
+#[derive(SimpleObject)]
+struct ViewerPosition {
+    predicted_winner: ChainId,
+    amount: Amount,
 }
 
 #[cfg(test)]
@@ -69,21 +1843,40 @@ mod tests {
 
     use async_graphql::{Request, Response, Value};
     use futures::FutureExt as _;
-    use linera_sdk::{util::BlockingWait, views::View, Service, ServiceRuntime};
+    use linera_sdk::{
+        linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+        util::BlockingWait,
+        views::View,
+        Service, ServiceRuntime,
+    };
+    use majorules::{
+        counters::{self, CounterOverflow},
+        fees::{FeeBreakdown, ProRataSplit},
+        idcodec::IdCodec,
+        odds::MarketPricing,
+        time::MICROS_PER_DAY,
+        invite_digest, Attestation, BattleEndReason, BattleEvent, BattleRules, Bps, ItemRarity, PlayerPreferences, PreferenceEntry, QueueMode, ResultKind, TiebreakBy,
+    };
     use serde_json::json;
 
-    use super::{MajorulesService, MajorulesState};
+    use super::{ChainState, LobbyState, MajorulesService};
+    use crate::state::{
+        BattleMetadata, BattleParticipant, BattlePhase, BattleRecord, BattleResult, BattleState, BattleStatus, Bet, BettingLeaderboardEntry,
+        BettorStats, CharacterClass, CharacterData,
+        CharacterRegistryEntry, CharacterSnapshot, CombatAction, CombatStats, CompletedBattleRecord, CreationCounts, LeaderboardEntry, Market, MarketStatus,
+        PayoutReceiptRecord, PendingSettlement, PlayerGlobalStats, PlayerQueueEntry, PlayerState, PredictionState, QueueStats, RoundResult, TimingInfo,
+    };
 
     #[test]
     fn query() {
         let value = 60u64;
         let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
-        let mut state = MajorulesState::load(runtime.root_view_storage_context())
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
             .blocking_wait()
             .expect("Failed to read from mock key value store");
         state.value.set(value);
 
-        let service = MajorulesService { state, runtime };
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
         let request = Request::new("{ value }");
 
         let response = service
@@ -95,4 +1888,1176 @@ mod tests {
 
         assert_eq!(response, expected)
     }
+
+    fn battle_chain() -> ChainId {
+        ChainId(CryptoHash::test_hash("battle"))
+    }
+
+    /// Lobby state after a settled 10-token battle at a 5% platform fee
+    fn settled_battle_state(runtime: &ServiceRuntime<MajorulesService>) -> LobbyState {
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let winner = AccountOwner::from(CryptoHash::test_hash("winner"));
+        let loser = AccountOwner::from(CryptoHash::test_hash("loser"));
+        let breakdown = FeeBreakdown::compute(Amount::from_tokens(10), 500);
+
+        state.completed_battles.insert(&battle_chain(), CompletedBattleRecord {
+            battle_chain: battle_chain(),
+            player1: winner,
+            player2: loser,
+            winner,
+            total_stake: breakdown.total_stake,
+            platform_fee_bps: breakdown.platform_fee_bps,
+            platform_fee: breakdown.platform_fee,
+            winner_payout: breakdown.winner_payout,
+            rounds_played: 3,
+            created_at: Timestamp::from(0),
+            completed_at: Timestamp::from(1),
+            prediction_market_id: None,
+            total_betting_volume: Amount::ZERO,
+            rules_version: 1,
+            rules_digest: BattleRules::default().digest(),
+            attestation: Attestation::Unattested,
+            player1_class: CharacterClass::Warrior,
+            player2_class: CharacterClass::Mage,
+            end_reason: BattleEndReason::Knockout,
+            mode: QueueMode::Casual,
+        }).unwrap();
+        state.treasury_ledger.insert(&battle_chain(), breakdown.platform_fee).unwrap();
+        state.payout_receipts.insert(&battle_chain(), PayoutReceiptRecord {
+            player: winner,
+            amount: breakdown.winner_payout,
+            received_at: Timestamp::from(2),
+        }).unwrap();
+        state
+    }
+
+    fn verify_payout(state: LobbyState, runtime: Arc<ServiceRuntime<MajorulesService>>) -> serde_json::Value {
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+        let request = Request::new(format!(
+            "{{ verifyPayout(battleChain: \"{}\") {{ status diffs {{ field expected actual }} missing }} }}",
+            battle_chain()
+        ));
+        let response = service
+            .handle_query(request)
+            .now_or_never()
+            .expect("Query should not await anything");
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()["verifyPayout"].clone()
+    }
+
+    #[test]
+    fn verify_payout_matches_settled_battle() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = settled_battle_state(&runtime);
+
+        let verdict = verify_payout(state, runtime);
+
+        assert_eq!(verdict, json!({"status": "MATCH", "diffs": [], "missing": []}));
+    }
+
+    #[test]
+    fn verify_payout_without_receipt_is_incomplete() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = settled_battle_state(&runtime);
+        state.payout_receipts.remove(&battle_chain()).unwrap();
+
+        let verdict = verify_payout(state, runtime);
+
+        assert_eq!(verdict, json!({"status": "INCOMPLETE", "diffs": [], "missing": ["payoutReceipt"]}));
+    }
+
+    #[test]
+    fn verify_payout_reports_tampered_ledger() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = settled_battle_state(&runtime);
+        state.treasury_ledger.insert(&battle_chain(), Amount::from_millis(400)).unwrap();
+
+        let verdict = verify_payout(state, runtime);
+
+        assert_eq!(verdict, json!({
+            "status": "MISMATCH",
+            "diffs": [{
+                "field": "treasuryLedger",
+                "expected": Amount::from_millis(500),
+                "actual": Amount::from_millis(400),
+            }],
+            "missing": [],
+        }));
+    }
+
+    fn player_chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    fn bettor(name: &str) -> AccountOwner {
+        AccountOwner::from(CryptoHash::test_hash(name))
+    }
+
+    /// Lobby state with market 1 holding three bets and market 2 holding one
+    fn market_state(runtime: &ServiceRuntime<MajorulesService>, public_bettors: bool) -> LobbyState {
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let bets = [
+            (1, "alice", "player1", Amount::from_tokens(5)),
+            (1, "bob", "player1", Amount::from_tokens(20)),
+            (1, "carol", "player2", Amount::from_millis(500)),
+            (2, "dave", "player1", Amount::from_tokens(7)),
+        ];
+        for market_id in [1, 2] {
+            let pool = |side| bets.iter()
+                .filter(|(market, _, chain, _)| *market == market_id && *chain == side)
+                .fold(Amount::ZERO, |total, (_, _, _, amount)| total.saturating_add(*amount));
+            state.prediction_markets.insert(&market_id, Market {
+                market_id,
+                battle_chain: battle_chain(),
+                player1_chain: player_chain("player1"),
+                player2_chain: player_chain("player2"),
+                status: MarketStatus::Open,
+                total_pool: pool("player1").saturating_add(pool("player2")),
+                player1_pool: pool("player1"),
+                player2_pool: pool("player2"),
+                winner_chain: None,
+                created_at: Timestamp::from(0),
+                closed_at: None,
+                settled_at: None,
+                public_bettors,
+                pricing: MarketPricing::PariMutuel,
+                player1_locked: Amount::ZERO,
+                player2_locked: Amount::ZERO,
+            }).unwrap();
+        }
+        for (market_id, name, side, amount) in bets {
+            state.market_bettors.get_mut_or_default(&market_id).blocking_wait().unwrap().push(bettor(name));
+            state.bets.insert(&(market_id, bettor(name)), Bet {
+                bettor: bettor(name),
+                market_id,
+                predicted_winner: player_chain(side),
+                amount,
+                odds_at_bet: 10000,
+                placed_at: Timestamp::from(0),
+                claimed: false,
+                payout: None,
+            }).unwrap();
+        }
+        state
+    }
+
+    fn market_depth(state: LobbyState, runtime: Arc<ServiceRuntime<MajorulesService>>, arguments: &str) -> serde_json::Value {
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+        let request = Request::new(format!(
+            "{{ marketDepth(marketId: 1{arguments}) {{ approximate \
+                player1 {{ bettorCount totalPool largestPosition topPositions histogram {{ count }} bettors {{ bettor amount }} }} \
+                player2 {{ bettorCount largestPosition histogram {{ count }} }} \
+                viewerPosition {{ amount }} }} }}"
+        ));
+        let response = service.handle_query(request).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()["marketDepth"].clone()
+    }
+
+    #[test]
+    fn market_depth_aggregates_bets_anonymously() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = market_state(&runtime, false);
+        let viewer = format!(", viewer: \"{}\"", bettor("bob"));
+
+        let depth = market_depth(state, runtime, &viewer);
+
+        assert_eq!(depth, json!({
+            "approximate": false,
+            "player1": {
+                "bettorCount": 2,
+                "totalPool": Amount::from_tokens(25),
+                "largestPosition": Amount::from_tokens(20),
+                "topPositions": [Amount::from_tokens(20), Amount::from_tokens(5)],
+                "histogram": [{"count": 0}, {"count": 1}, {"count": 1}, {"count": 0}],
+                "bettors": null,
+            },
+            "player2": {
+                "bettorCount": 1,
+                "largestPosition": Amount::from_millis(500),
+                "histogram": [{"count": 1}, {"count": 0}, {"count": 0}, {"count": 0}],
+            },
+            "viewerPosition": {"amount": Amount::from_tokens(20)},
+        }));
+    }
+
+    #[test]
+    fn market_depth_lists_public_bettors() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = market_state(&runtime, true);
+
+        let depth = market_depth(state, runtime, "");
+
+        assert_eq!(depth["player1"]["bettors"], json!([
+            {"bettor": bettor("bob"), "amount": Amount::from_tokens(20)},
+            {"bettor": bettor("alice"), "amount": Amount::from_tokens(5)},
+        ]));
+        assert_eq!(depth["viewerPosition"], json!(null));
+    }
+
+    #[test]
+    fn market_depth_scan_cap_marks_result_approximate() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = market_state(&runtime, false);
+
+        let depth = market_depth(state, runtime, ", scanLimit: 2");
+
+        assert_eq!(depth["approximate"], json!(true));
+    }
+
+    #[test]
+    fn market_depth_reads_only_its_own_market() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = market_state(&runtime, false);
+        // An earlier market's bets sort ahead of market 1's and would exhaust a global scan
+        for index in 0..5 {
+            let name = format!("early-{index}");
+            state.market_bettors.get_mut_or_default(&0).blocking_wait().unwrap().push(bettor(&name));
+            state.bets.insert(&(0, bettor(&name)), Bet {
+                bettor: bettor(&name),
+                market_id: 0,
+                predicted_winner: player_chain("player1"),
+                amount: Amount::ONE,
+                odds_at_bet: 10000,
+                placed_at: Timestamp::from(0),
+                claimed: false,
+                payout: None,
+            }).unwrap();
+        }
+
+        let depth = market_depth(state, runtime, ", scanLimit: 3");
+
+        assert_eq!(depth["approximate"], json!(false));
+        assert_eq!((depth["player1"]["bettorCount"].clone(), depth["player2"]["bettorCount"].clone()), (json!(2), json!(1)));
+    }
+
+    #[test]
+    fn creation_allowance_reports_remaining_for_today() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new().with_system_time(Timestamp::from(86_400_000_000 * 3)));
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        state.creation_counts.insert(&(3, bettor("alice")), CreationCounts {
+            player_chains: 1,
+            private_battles: 0,
+            queue_joins: 50,
+        }).unwrap();
+        state.creation_counts.insert(&(2, bettor("alice")), CreationCounts {
+            player_chains: 2,
+            private_battles: 20,
+            queue_joins: 50,
+        }).unwrap();
+
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+        let request = Request::new(format!(
+            "{{ creationAllowance(owner: \"{}\") {{ day exempt playerChains privateBattles queueJoins }} }}",
+            bettor("alice")
+        ));
+        let response = service.handle_query(request).blocking_wait();
+
+        assert_eq!(response.data.into_json().unwrap(), json!({"creationAllowance": {
+            "day": 3, "exempt": false, "playerChains": 1, "privateBattles": 20, "queueJoins": 0,
+        }}));
+    }
+
+    #[test]
+    fn market_depth_accepts_obfuscated_ids() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = market_state(&runtime, false);
+        let codec = IdCodec::obfuscated(0xDEC0DE);
+        state.id_codec.set(codec);
+
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+        let request = Request::new(format!(
+            "{{ plain: marketDepth(marketId: 1) {{ marketId }} \
+                encoded: marketDepth(marketId: {}) {{ marketId player1 {{ bettorCount }} }} }}",
+            codec.encode(1)
+        ));
+        let response = service.handle_query(request).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "plain": null,
+            "encoded": {"marketId": codec.encode(1), "player1": {"bettorCount": 2}},
+        }));
+    }
+
+    fn run_query(state: LobbyState, runtime: Arc<ServiceRuntime<MajorulesService>>, query: String) -> Response {
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+        service.handle_query(Request::new(query)).blocking_wait()
+    }
+
+    #[test]
+    fn markets_by_ids_preserve_order_with_nulls() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = market_state(&runtime, false);
+
+        let response = run_query(state, runtime, "{ \
+            batch: marketsByIds(ids: [2, 9, 1]) { marketId status totalPool } \
+            first: market(id: 1) { marketId status totalPool } \
+            second: market(id: 2) { marketId status totalPool } }".to_string());
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+
+        assert_eq!(data["batch"], json!([data["second"], null, data["first"]]));
+        assert_eq!(data["first"]["marketId"], json!(1));
+    }
+
+    #[test]
+    fn battles_and_players_by_id_match_single_lookups() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = settled_battle_state(&runtime);
+        let active_chain = ChainId(CryptoHash::test_hash("active"));
+        state.active_battles.insert(&active_chain, BattleMetadata {
+            battle_chain: active_chain,
+            player1: bettor("alice"),
+            player2: bettor("bob"),
+            total_stake: Amount::from_tokens(4),
+            created_at: Timestamp::from(5),
+            status: BattleStatus::InProgress,
+            has_prediction_market: false,
+            rules_version: 1,
+            player1_class: CharacterClass::Warrior,
+            player2_class: CharacterClass::Mage,
+            platform_fee_bps: 500,
+            mode: QueueMode::Casual,
+        }).unwrap();
+        state.character_registry.insert(&bettor("alice").to_string(), CharacterRegistryEntry {
+            character_id: String::new(),
+            owner: bettor("alice"),
+            owner_chain: player_chain("alice"),
+            class: CharacterClass::Mage,
+            level: 3,
+            created_at: Timestamp::from(0),
+            total_battles: 0,
+            wins: 0,
+            losses: 0,
+            is_alive: true,
+            lives_remaining: 3,
+        }).unwrap();
+
+        let fields = "battleChain status totalStake winner";
+        let response = run_query(state, runtime, format!(
+            "{{ batch: battlesByChains(chains: [\"{active}\", \"{missing}\", \"{completed}\"]) {{ {fields} }} \
+                active: battle(chain: \"{active}\") {{ {fields} }} \
+                completed: battle(chain: \"{completed}\") {{ {fields} }} \
+                players: playersByOwners(owners: [\"{bob}\", \"{alice}\"]) {{ class level }} \
+                alice: player(owner: \"{alice}\") {{ class level }} }}",
+            active = active_chain,
+            missing = player_chain("missing"),
+            completed = battle_chain(),
+            alice = bettor("alice"),
+            bob = bettor("bob"),
+        ));
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+
+        assert_eq!(data["batch"], json!([data["active"], null, data["completed"]]));
+        assert_eq!(data["active"]["status"], json!("IN_PROGRESS"));
+        assert_eq!(data["completed"]["status"], json!("COMPLETED"));
+        assert_eq!(data["players"], json!([null, data["alice"]]));
+        assert_eq!(data["alice"], json!({"class": "MAGE", "level": 3}));
+    }
+
+    #[test]
+    fn battle_summaries_expose_rules_versions() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = settled_battle_state(&runtime);
+        let boosted = BattleRules { max_rounds: 5, xp_boost_bps: 20_000, ..BattleRules::default() };
+        state.battle_rules.set(boosted.clone());
+        state.rules_version.set(2);
+        let active_chain = ChainId(CryptoHash::test_hash("active"));
+        state.active_battles.insert(&active_chain, BattleMetadata {
+            battle_chain: active_chain,
+            player1: bettor("alice"),
+            player2: bettor("bob"),
+            total_stake: Amount::from_tokens(4),
+            created_at: Timestamp::from(5),
+            status: BattleStatus::InProgress,
+            has_prediction_market: false,
+            rules_version: 2,
+            player1_class: CharacterClass::Warrior,
+            player2_class: CharacterClass::Mage,
+            platform_fee_bps: 500,
+            mode: QueueMode::Casual,
+        }).unwrap();
+
+        let response = run_query(state, runtime, format!(
+            "{{ battleRules {{ version digest maxRounds xpBoostBps }} \
+                active: battle(chain: \"{active}\") {{ rulesVersion rulesDigest }} \
+                completed: battle(chain: \"{completed}\") {{ rulesVersion rulesDigest }} }}",
+            active = active_chain,
+            completed = battle_chain(),
+        ));
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+
+        assert_eq!(data["battleRules"], json!({
+            "version": 2,
+            "digest": boosted.digest(),
+            "maxRounds": 5,
+            "xpBoostBps": 20_000,
+        }));
+        assert_eq!(data["active"], json!({"rulesVersion": 2, "rulesDigest": null}));
+        assert_eq!(data["completed"], json!({"rulesVersion": 1, "rulesDigest": BattleRules::default().digest()}));
+    }
+
+    #[test]
+    fn game_config_lists_stances_per_class() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = market_state(&runtime, false);
+        let query = "{ gameConfig { classLockedStances classes { class allowedStances } rankedGates { minRankedLevel } } }";
+
+        state.battle_rules.set(BattleRules::default());
+        let locked = run_query(state, runtime.clone(), query.to_string()).data.into_json().unwrap();
+        let mut state = market_state(&runtime, false);
+        state.battle_rules.set(BattleRules { class_locked_stances: false, ..BattleRules::default() });
+        let casual = run_query(state, runtime, query.to_string()).data.into_json().unwrap();
+
+        assert_eq!(locked["gameConfig"]["classLockedStances"], json!(true));
+        assert_eq!(locked["gameConfig"]["classes"][3], json!({
+            "class": "TANK",
+            "allowedStances": ["BALANCED", "AGGRESSIVE", "DEFENSIVE", "COUNTER"],
+        }));
+        assert_eq!(locked["gameConfig"]["rankedGates"]["minRankedLevel"], json!(5));
+        assert_eq!(casual["gameConfig"]["classLockedStances"], json!(false));
+        assert_eq!(casual["gameConfig"]["classes"][3]["allowedStances"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn maintenance_backlog_counts_pending_payouts() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = market_state(&runtime, false);
+        state.market_bettors.insert(&1, vec![bettor("alice"), bettor("bob"), bettor("carol")]).unwrap();
+        state.market_bettors.insert(&2, vec![bettor("dave")]).unwrap();
+        for (market_id, next_bettor) in [(1, 1), (2, 0)] {
+            state.pending_settlements.insert(&market_id, PendingSettlement {
+                winner_chain: Some(player_chain("player1")),
+                next_bettor,
+                split: ProRataSplit::new(Amount::from_tokens(3), Amount::from_tokens(2)),
+            }).unwrap();
+        }
+
+        let response = run_query(state, runtime, "{ maintenanceBacklog { settlementMarkets pendingPayouts } }".to_string());
+
+        assert_eq!(response.data.into_json().unwrap()["maintenanceBacklog"], json!({
+            "settlementMarkets": 2,
+            "pendingPayouts": 3,
+        }));
+    }
+
+    #[test]
+    fn storage_report_counts_entries_within_its_scan_bounds() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let usage = |runtime: &Arc<ServiceRuntime<MajorulesService>>, arguments: &str| {
+            let mut state = market_state(runtime, false);
+            for name in ["first", "second", "third"] {
+                state.treasury_ledger.insert(&player_chain(name), Amount::from_tokens(1)).unwrap();
+            }
+            let query = format!("{{ storageReport({arguments}) {{ name entries approximate sampled avgEntryBytes largest {{ key bytes }} }} }}");
+            let data = run_query(state, runtime.clone(), query).data.into_json().unwrap();
+            let views = data["storageReport"].as_array().unwrap().clone();
+            move |name: &str| views.iter().find(|view| view["name"] == name).unwrap().clone()
+        };
+
+        let full = usage(&runtime, "sampleSize: 2");
+        assert_eq!((full("bets")["entries"].clone(), full("bets")["sampled"].clone()), (json!(4), json!(2)));
+        assert_eq!(full("predictionMarkets")["entries"], json!(2));
+        assert_eq!(full("treasuryLedger")["entries"], json!(3));
+        assert_eq!(full("completedBattles")["entries"], json!(0));
+        assert!(["bets", "predictionMarkets", "treasuryLedger"].iter().all(|name| full(name)["approximate"] == json!(false)));
+        assert!(full("bets")["avgEntryBytes"].as_u64().unwrap() > 0);
+        assert!(full("bets")["largest"]["key"].is_string());
+        assert!(full("completedBattles")["largest"].is_null());
+
+        let capped = usage(&runtime, "scanLimit: 3");
+        assert_eq!((capped("bets")["entries"].clone(), capped("bets")["approximate"].clone()), (json!(3), json!(true)));
+        assert_eq!(capped("bets")["sampled"], json!(3));
+        assert_eq!((capped("predictionMarkets")["entries"].clone(), capped("predictionMarkets")["approximate"].clone()), (json!(2), json!(false)));
+    }
+
+    #[test]
+    fn batch_lookups_reject_oversized_lists() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let state = market_state(&runtime, false);
+        let ids: Vec<String> = (1..=51).map(|id| id.to_string()).collect();
+
+        let response = run_query(state, runtime, format!("{{ marketsByIds(ids: [{}]) {{ marketId }} }}", ids.join(", ")));
+
+        assert_eq!(response.errors.len(), 1);
+        assert!(response.errors[0].message.contains("At most 50 ids"));
+    }
+
+    #[test]
+    fn leaderboard_pages_and_ranks_players() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = LobbyState::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        let entries = ["alice", "bob", "carol"].into_iter().enumerate()
+            .map(|(index, name)| LeaderboardEntry {
+                rank: index as u64 + 1,
+                player: bettor(name),
+                elo_rating: 1300 - 50 * index as u64,
+                total_battles: 1,
+                wins: 1,
+                losses: 0,
+                win_rate: 1.0,
+                total_earnings: Amount::ZERO,
+                win_streak: 1,
+            })
+            .collect();
+        state.leaderboard.set(entries);
+
+        let query = format!(
+            "{{ leaderboard(limit: 1, offset: 1) {{ rank eloRating }} bob: playerRank(owner: \"{}\") dave: playerRank(owner: \"{}\") }}",
+            bettor("bob"), bettor("dave"),
+        );
+        let response = run_query(state, runtime, query);
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({"leaderboard": [{"rank": 2, "eloRating": 1250}], "bob": 2, "dave": null}),
+        );
+    }
+
+    /// Level 1 warrior owned by `name`
+    fn snapshot(name: &str) -> CharacterSnapshot {
+        CharacterSnapshot {
+            nft_id: format!("{name}-character"),
+            class: CharacterClass::Warrior,
+            level: 1,
+            hp_max: 100,
+            min_damage: 10,
+            max_damage: 20,
+            crit_chance: Bps(1000),
+            crit_multiplier: 15000,
+            dodge_chance: Bps(500),
+            defense: 5,
+            attack_bps: 0,
+            defense_bps: 0,
+            crit_bps: 0,
+        }
+    }
+
+    /// Battle in round 2 of 3 where `alice` attacks first with her special three ticks off
+    fn planner_battle_state(runtime: &ServiceRuntime<MajorulesService>) -> BattleState {
+        let mut battle = BattleState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let fighter = |name: &str| {
+            BattleParticipant::new(bettor(name), player_chain(name), snapshot(name), Amount::from_tokens(1))
+        };
+        battle.player1.set(Some(BattleParticipant { special_cooldown: 3, ..fighter("alice") }));
+        battle.player2.set(Some(fighter("bob")));
+        battle.status.set(BattleStatus::InProgress);
+        battle.current_round.set(2);
+        battle.max_rounds.set(3);
+        battle.rules.set(BattleRules::default());
+        battle
+    }
+
+    #[test]
+    fn battle_chains_serve_status_fighters_and_rounds() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut battle = planner_battle_state(&runtime);
+        battle.player2.set(Some(BattleParticipant {
+            current_hp: 62,
+            ..battle.player2.get().clone().unwrap()
+        }));
+        battle.round_results.insert(&1, RoundResult {
+            round: 1,
+            player1_actions: vec![CombatAction {
+                attacker: bettor("alice"),
+                defender: bettor("bob"),
+                damage: 38,
+                was_crit: true,
+                was_dodged: false,
+                was_countered: false,
+                special_used: false,
+                special: None,
+                absorbed: 0,
+                damage_over_time: false,
+                counter: false,
+                effects: Vec::new(),
+                defender_hp_remaining: 62,
+            }],
+            player2_actions: Vec::new(),
+            player1_hp: 100,
+            player2_hp: 62,
+            player1_effects: Vec::new(),
+            player2_effects: Vec::new(),
+            emotes: Vec::new(),
+        }).unwrap();
+        battle.timing.set(Some(TimingInfo {
+            current_round: 2,
+            phase: BattlePhase::SubmittingTurns,
+            phase_deadline: Some(Timestamp::from(120)),
+            server_time_at_write: Timestamp::from(0),
+        }));
+        let service = MajorulesService { state: ChainState::Battle(Arc::new(battle)), runtime };
+
+        let query = "{ status currentRound maxRounds fighters { owner hp hpMax specialCooldown } \
+            timing { currentRound phase phaseDeadline serverTimeAtWrite } \
+            roundResults { round player1Hp player2Hp player1Actions { damage wasCrit defenderHpRemaining } } }";
+        let response = service.handle_query(Request::new(query)).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "status": "IN_PROGRESS",
+            "currentRound": 2,
+            "maxRounds": 3,
+            "fighters": [
+                {"owner": bettor("alice"), "hp": 100, "hpMax": 100, "specialCooldown": 3},
+                {"owner": bettor("bob"), "hp": 62, "hpMax": 100, "specialCooldown": 0},
+            ],
+            "timing": {
+                "currentRound": 2,
+                "phase": "SUBMITTING_TURNS",
+                "phaseDeadline": Timestamp::from(120),
+                "serverTimeAtWrite": Timestamp::from(0),
+            },
+            "roundResults": [{
+                "round": 1,
+                "player1Hp": 100,
+                "player2Hp": 62,
+                "player1Actions": [{"damage": 38, "wasCrit": true, "defenderHpRemaining": 62}],
+            }],
+        }));
+    }
+
+    #[test]
+    fn combos_are_served_per_fighter_and_left_out_of_fighters() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut battle = planner_battle_state(&runtime);
+        battle.player1.set(Some(BattleParticipant {
+            combo_stack: 2,
+            longest_combo: 3,
+            ..battle.player1.get().clone().unwrap()
+        }));
+        let service = MajorulesService { state: ChainState::Battle(Arc::new(battle)), runtime };
+        let query = |query: String| {
+            let response = service.handle_query(Request::new(query)).blocking_wait();
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()
+        };
+
+        let combo = |owner: &str| query(format!("{{ myCombo(owner: \"{}\") {{ comboStack longestCombo nextHitBps }} }}", bettor(owner)))["myCombo"].clone();
+        assert_eq!(combo("alice"), json!({"comboStack": 2, "longestCombo": 3, "nextHitBps": 11_000}));
+        assert_eq!(combo("bob"), json!({"comboStack": 0, "longestCombo": 0, "nextHitBps": 10_000}));
+        assert_eq!(combo("carol"), json!(null));
+        // The public fighter list has no combo to read
+        let response = service.handle_query(Request::new("{ fighters { comboStack } }")).blocking_wait();
+        assert!(!response.errors.is_empty());
+    }
+
+    #[test]
+    fn battle_subscriptions_follow_the_event_stream() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut battle = planner_battle_state(&runtime);
+        battle.round_results.insert(&1, RoundResult {
+            round: 1,
+            player1_actions: Vec::new(),
+            player2_actions: Vec::new(),
+            player1_hp: 90,
+            player2_hp: 80,
+            player1_effects: Vec::new(),
+            player2_effects: Vec::new(),
+            emotes: Vec::new(),
+        }).unwrap();
+        let events = [
+            BattleEvent::TurnSubmitted { round: 1, turn: 1, player: bettor("alice"), sealed: false },
+            BattleEvent::TurnResolved { round: 1, turn: 1, choices: [(majorules::Stance::Balanced, false); 2], player1_hp: 90, player2_hp: 100 },
+            BattleEvent::TurnResolved { round: 1, turn: 2, choices: [(majorules::Stance::Balanced, false); 2], player1_hp: 90, player2_hp: 80 },
+            BattleEvent::RoundEnded { round: 1, player1_hp: 90, player2_hp: 80 },
+            BattleEvent::BattleFinished { winner: Some(bettor("alice")), end_reason: BattleEndReason::Knockout, rounds_played: 1 },
+        ];
+        // Three older events already left the log
+        for (event_index, event) in (3..).zip(events) {
+            battle.battle_log.push_back((event_index, event));
+        }
+        let service = MajorulesService { state: ChainState::Battle(Arc::new(battle)), runtime };
+        let subscribe = |subscription: &str| {
+            let response = service.handle_query(Request::new(format!("subscription {{ {subscription} }}"))).blocking_wait();
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()
+        };
+
+        assert_eq!(subscribe("hpChanges { eventIndex turn player1Hp player2Hp }"), json!({"hpChanges": [
+            {"eventIndex": 4, "turn": 1, "player1Hp": 90, "player2Hp": 100},
+            {"eventIndex": 5, "turn": 2, "player1Hp": 90, "player2Hp": 80},
+        ]}));
+        assert_eq!(subscribe("hpChanges(after: 4) { eventIndex }"), json!({"hpChanges": [{"eventIndex": 5}]}));
+        assert_eq!(subscribe("hpChanges(after: 5) { eventIndex }"), json!({"hpChanges": []}));
+        assert_eq!(
+            subscribe("roundResults { eventIndex result { round player2Hp } }"),
+            json!({"roundResults": [{"eventIndex": 6, "result": {"round": 1, "player2Hp": 80}}]}),
+        );
+        assert_eq!(
+            subscribe("outcome(after: 6) { eventIndex winner endReason { kind } roundsPlayed }"),
+            json!({"outcome": [{"eventIndex": 7, "winner": bettor("alice"), "endReason": {"kind": "KNOCKOUT"}, "roundsPlayed": 1}]}),
+        );
+        // Queries on the same chain still answer once
+        let response = service.handle_query(Request::new("{ outcome { winner } }")).blocking_wait();
+        assert_eq!(response.data.into_json().unwrap(), json!({"outcome": null}));
+    }
+
+    #[test]
+    fn special_planner_checks_plans_against_the_live_cooldown() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let service = MajorulesService { state: ChainState::Battle(Arc::new(planner_battle_state(&runtime))), runtime };
+        let plan = |uses: &str| {
+            let query = format!(
+                "{{ specialPlanner(owner: \"{}\", plannedUses: [{uses}]) {{ round turn cooldownAfter }} }}",
+                bettor("alice"),
+            );
+            service.handle_query(Request::new(query)).blocking_wait()
+        };
+
+        let accepted = plan("{round: 3, turn: 1}, {round: 2, turn: 2}");
+        assert_eq!(accepted.data.into_json().unwrap()["specialPlanner"], json!([
+            {"round": 2, "turn": 2, "cooldownAfter": 1},
+            {"round": 3, "turn": 1, "cooldownAfter": 1},
+        ]));
+
+        assert_eq!(plan("{round: 2, turn: 1}").errors[0].message, "special is on cooldown at round 2 turn 1 (1 left)");
+        assert_eq!(plan("{round: 4, turn: 0}").errors[0].message, "round 4 turn 0 is past the last round");
+    }
+
+    #[test]
+    fn player_chains_serve_preferences_and_profile() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut player = PlayerState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        player.owner.set(Some(bettor("alice")));
+        player.preferences.set(PlayerPreferences {
+            preferred_stance: Some("defensive".to_string()),
+            mute_battle_summaries: true,
+            extra: vec![PreferenceEntry { key: "theme".to_string(), value: "dark".to_string() }],
+            ..PlayerPreferences::default()
+        });
+        let service = MajorulesService { state: ChainState::Player(Arc::new(player)), runtime };
+
+        let query = "{ preferences { preferredStance muteBattleSummaries extra { key value } } \
+            profile { owner characters preferences { autoAcceptRematch } } }";
+        let response = service.handle_query(Request::new(query)).blocking_wait().data.into_json().unwrap();
+
+        assert_eq!(response["preferences"], json!({
+            "preferredStance": "defensive",
+            "muteBattleSummaries": true,
+            "extra": [{"key": "theme", "value": "dark"}],
+        }));
+        assert_eq!(response["profile"], json!({
+            "owner": bettor("alice"),
+            "characters": 0,
+            "preferences": {"autoAcceptRematch": false},
+        }));
+    }
+
+    #[test]
+    fn player_chains_serve_characters_and_stats() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut player = PlayerState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let character = snapshot("alice");
+        player.characters.insert(&character.nft_id, CharacterData {
+            nft_id: character.nft_id.clone(),
+            owner: bettor("alice"),
+            class: character.class,
+            level: 3,
+            xp: 250,
+            hp_max: character.hp_max,
+            min_damage: character.min_damage,
+            max_damage: character.max_damage,
+            crit_chance: character.crit_chance,
+            crit_multiplier: character.crit_multiplier,
+            dodge_chance: character.dodge_chance,
+            defense: character.defense,
+            attack_bps: 0,
+            defense_bps: 0,
+            crit_bps: 0,
+            rarity: ItemRarity::Rare,
+            lives_remaining: 3,
+            created_at: Timestamp::from(0),
+            is_active: true,
+        }).unwrap();
+        player.active_character.set(Some(character.nft_id.clone()));
+        player.character_listings.insert(&character.nft_id, Amount::from_tokens(5)).unwrap();
+        player.player_stats.set(PlayerGlobalStats { total_battles: 4, wins: 3, losses: 1, ..PlayerGlobalStats::default() });
+        let service = MajorulesService { state: ChainState::Player(Arc::new(player)), runtime };
+
+        let query = "{ characters { nftId class level xp rarity } activeCharacter listings { characterId price } \
+            stats { totalBattles wins losses eloRating } }";
+        let response = service.handle_query(Request::new(query)).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "characters": [{"nftId": "alice-character", "class": "WARRIOR", "level": 3, "xp": 250, "rarity": "RARE"}],
+            "activeCharacter": "alice-character",
+            "listings": [{"characterId": "alice-character", "price": Amount::from_tokens(5)}],
+            "stats": {"totalBattles": 4, "wins": 3, "losses": 1, "eloRating": 1200},
+        }));
+    }
+
+    #[test]
+    fn player_history_pages_newest_first_and_filters_by_result() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut player = PlayerState::load(runtime.root_view_storage_context()).blocking_wait().unwrap();
+        let results = [BattleResult::Won, BattleResult::Lost, BattleResult::Won, BattleResult::Draw, BattleResult::Won];
+        for (index, result) in results.into_iter().enumerate() {
+            let battle_chain = player_chain(&format!("battle-{index}"));
+            player.battle_history.insert(&battle_chain, BattleRecord {
+                battle_chain,
+                opponent: bettor("bob"),
+                character_used: "hero".to_string(),
+                stake: Amount::ZERO,
+                result,
+                rounds_played: 3,
+                xp_gained: 10 * index as u64,
+                payout: Amount::ZERO,
+                combat_stats: CombatStats {
+                    damage_dealt: 0,
+                    damage_taken: 0,
+                    crits: 0,
+                    dodges: 0,
+                    highest_crit: 0,
+                    longest_combo: 0,
+                },
+                completed_at: Timestamp::from(index as u64),
+                rules_digest: 0,
+                end_reason: BattleEndReason::Knockout,
+            }).unwrap();
+            player.battle_history_order.push(battle_chain);
+        }
+        let service = MajorulesService { state: ChainState::Player(Arc::new(player)), runtime };
+        let xp = |query: &str| {
+            let response = service.handle_query(Request::new(query)).blocking_wait();
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()["battleHistory"].as_array().unwrap().iter()
+                .map(|entry| entry["xpGained"].as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(xp("{ battleHistory(limit: 2, offset: 1) { xpGained } }"), [30, 20]);
+        assert_eq!(xp("{ battleHistory(offset: 1, resultFilter: WON) { xpGained } }"), [20, 0]);
+
+        let query = format!("{{ battleDetail(battleChain: \"{}\") {{ result roundsPlayed combatStats {{ crits }} }} }}", player_chain("battle-3"));
+        let response = service.handle_query(Request::new(query)).blocking_wait();
+        assert_eq!(response.data.into_json().unwrap()["battleDetail"], json!({
+            "result": "DRAW",
+            "roundsPlayed": 3,
+            "combatStats": {"crits": 0},
+        }));
+    }
+
+    #[test]
+    fn lobby_queue_lists_waiting_players_longest_waiting_first() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        for (name, joined_at, mode) in [("alice", 30, QueueMode::Casual), ("bob", 10, QueueMode::Ranked), ("carol", 20, QueueMode::Casual)] {
+            state.enqueue(PlayerQueueEntry {
+                player: bettor(name),
+                player_chain: player_chain(name),
+                character_id: format!("{name}-character"),
+                character_snapshot: snapshot(name),
+                stake: Amount::from_tokens(1),
+                joined_at: Timestamp::from(joined_at),
+                mode,
+                best_of: 1,
+                elo_rating: 1200,
+                party: None,
+            }).blocking_wait().unwrap();
+        }
+
+        state.queue_stats.insert(&QueueMode::Casual, QueueStats { joins: 3, matches: 1, ..Default::default() }).unwrap();
+
+        let response = run_query(state, runtime, "{ all: queue { player mode } casual: queue(mode: CASUAL) { player } \
+            queues { mode minStake waiting stats { joins matches } } }".to_string());
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "all": [
+                {"player": bettor("bob"), "mode": "RANKED"},
+                {"player": bettor("carol"), "mode": "CASUAL"},
+                {"player": bettor("alice"), "mode": "CASUAL"},
+            ],
+            "casual": [{"player": bettor("carol")}, {"player": bettor("alice")}],
+            "queues": [
+                {"mode": "CASUAL", "minStake": "0.", "waiting": 2, "stats": {"joins": 3, "matches": 1}},
+                {"mode": "RANKED", "minStake": "0.", "waiting": 1, "stats": {"joins": 0, "matches": 0}},
+                {"mode": "HIGH_STAKES", "minStake": "100.", "waiting": 0, "stats": {"joins": 0, "matches": 0}},
+                {"mode": "TEAMS", "minStake": "0.", "waiting": 0, "stats": {"joins": 0, "matches": 0}},
+            ],
+        }));
+    }
+
+    #[test]
+    fn private_battles_answer_only_to_their_invite_secret() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        state.pending_private_battles.insert(&1, PlayerQueueEntry {
+            player: bettor("alice"),
+            player_chain: player_chain("alice"),
+            character_id: "alice-character".to_string(),
+            character_snapshot: snapshot("alice"),
+            stake: Amount::from_tokens(2),
+            joined_at: Timestamp::from(10),
+            mode: QueueMode::Casual,
+            best_of: 1,
+            elo_rating: 1200,
+            party: None,
+        }).unwrap();
+        state.private_battle_invites.insert(&1, invite_digest([7; 32])).unwrap();
+
+        let query = format!(
+            "{{ invited: privateBattle(battleId: 1, inviteSecret: {:?}) {{ host stake }} \
+            stranger: privateBattle(battleId: 1, inviteSecret: {:?}) {{ host }} }}",
+            [7u8; 32], [8u8; 32],
+        );
+        let response = run_query(state, runtime, query);
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "invited": {"host": bettor("alice"), "stake": "2."},
+            "stranger": null,
+        }));
+    }
+
+    #[test]
+    fn prediction_chains_serve_markets_and_bets() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let mut prediction = PredictionState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let market = Market {
+            market_id: 0,
+            battle_chain: battle_chain(),
+            player1_chain: player_chain("alice"),
+            player2_chain: player_chain("bob"),
+            status: MarketStatus::Open,
+            total_pool: Amount::from_tokens(3),
+            player1_pool: Amount::from_tokens(3),
+            player2_pool: Amount::ZERO,
+            winner_chain: None,
+            created_at: Timestamp::from(0),
+            closed_at: None,
+            settled_at: None,
+            public_bettors: true,
+            pricing: MarketPricing::PariMutuel,
+            player1_locked: Amount::ZERO,
+            player2_locked: Amount::ZERO,
+        };
+        prediction.markets.insert(&0, market.clone()).unwrap();
+        prediction.markets.insert(&1, Market { market_id: 1, status: MarketStatus::Settled, ..market }).unwrap();
+        prediction.battle_to_market.insert(&battle_chain(), 0).unwrap();
+        prediction.bets.insert(&(0, bettor("carol")), Bet {
+            bettor: bettor("carol"),
+            market_id: 0,
+            predicted_winner: player_chain("alice"),
+            amount: Amount::from_tokens(3),
+            odds_at_bet: 10000,
+            placed_at: Timestamp::from(0),
+            claimed: false,
+            payout: None,
+        }).unwrap();
+        prediction.total_volume.set(Amount::from_tokens(3));
+        let stats = BettorStats { total_bets: 1, total_wagered: Amount::from_tokens(3), ..BettorStats::default() };
+        prediction.bettor_stats.insert(&bettor("carol"), stats).unwrap();
+        let ranked = |rank, name: &str| BettingLeaderboardEntry {
+            rank,
+            bettor: bettor(name),
+            total_bets: 1,
+            total_wagered: Amount::from_tokens(1),
+            total_winnings: Amount::ZERO,
+            profit: Amount::ZERO,
+            win_rate: 0.0,
+        };
+        prediction.betting_leaderboard.set(vec![ranked(1, "dave"), ranked(2, "erin")]);
+        let service = MajorulesService { state: ChainState::Prediction(Arc::new(prediction)), runtime };
+
+        let query = format!(
+            "{{ markets(offset: 1) {{ marketId status }} marketForBattle(battleChain: \"{}\") {{ marketId totalPool player1Odds player2Odds }} \
+            bet(id: 0, owner: \"{}\") {{ amount claimed }} missing: bet(id: 1, owner: \"{}\") {{ amount }} totalVolume \
+            bettorStats(owner: \"{}\") {{ totalBets decidedBets }} bettingLeaderboard(limit: 1) {{ rank bettor }} }}",
+            battle_chain(), bettor("carol"), bettor("carol"), bettor("carol"),
+        );
+        let response = service.handle_query(Request::new(query)).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        assert_eq!(response.data.into_json().unwrap(), json!({
+            "markets": [{"marketId": 1, "status": "SETTLED"}],
+            "marketForBattle": {"marketId": 0, "totalPool": Amount::from_tokens(3), "player1Odds": 10_000, "player2Odds": null},
+            "bet": {"amount": Amount::from_tokens(3), "claimed": false},
+            "missing": null,
+            "totalVolume": Amount::from_tokens(3),
+            "bettorStats": {"totalBets": 1, "decidedBets": 0},
+            "bettingLeaderboard": [{"rank": 1, "bettor": bettor("dave")}],
+        }));
+    }
+
+    /// Twelve completed battles over three days, four players and all classes, indexed the way
+    /// the lobby indexes them. Completion times interleave so day order differs from seed order
+    fn archive_state(runtime: &ServiceRuntime<MajorulesService>) -> (LobbyState, Vec<CompletedBattleRecord>) {
+        let mut state = LobbyState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let players = ["alice", "bob", "carol", "dave"];
+        let classes = [
+            CharacterClass::Warrior, CharacterClass::Assassin, CharacterClass::Mage, CharacterClass::Tank, CharacterClass::Trickster,
+        ];
+        let records: Vec<_> = (0..12u64)
+            .map(|index| {
+                let completed_at = Timestamp::from((index % 3) * MICROS_PER_DAY + index * 1_000);
+                CompletedBattleRecord {
+                    battle_chain: player_chain(&format!("archive-{index}")),
+                    player1: bettor(players[index as usize % 4]),
+                    player2: bettor(players[(index as usize + 1) % 4]),
+                    winner: bettor(players[index as usize % 4]),
+                    total_stake: Amount::from_tokens(index as u128 + 1),
+                    platform_fee_bps: 0,
+                    platform_fee: Amount::ZERO,
+                    winner_payout: Amount::from_tokens(index as u128 + 1),
+                    rounds_played: 3,
+                    created_at: Timestamp::from(completed_at.micros().saturating_sub(500)),
+                    completed_at,
+                    prediction_market_id: None,
+                    total_betting_volume: Amount::ZERO,
+                    rules_version: 1,
+                    rules_digest: 7,
+                    attestation: Attestation::Unattested,
+                    player1_class: classes[index as usize % 5],
+                    player2_class: classes[(index as usize + 2) % 5],
+                    end_reason: if index % 3 == 0 {
+                        BattleEndReason::Knockout
+                    } else {
+                        BattleEndReason::MaxRoundsTiebreak { by: TiebreakBy::HpPercent }
+                    },
+                    mode: QueueMode::Ranked,
+                }
+            })
+            .collect();
+
+        for record in &records {
+            let key = (record.completed_at, record.battle_chain);
+            state.completed_battles.insert(&record.battle_chain, record.clone()).unwrap();
+            for player in [record.player1, record.player2] {
+                let mut battles = state.completed_by_owner.get(&player).blocking_wait().unwrap().unwrap_or_default();
+                battles.push(key);
+                state.completed_by_owner.insert(&player, battles).unwrap();
+            }
+            let day = record.completed_at.micros() / MICROS_PER_DAY;
+            let mut battles = state.completed_by_day.get(&day).blocking_wait().unwrap().unwrap_or_default();
+            battles.push(key);
+            state.completed_by_day.insert(&day, battles).unwrap();
+        }
+        (state, records)
+    }
+
+    #[test]
+    fn completed_battles_filter_and_page_through_the_archive() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new().with_system_time(Timestamp::from(3 * MICROS_PER_DAY)));
+        let (state, records) = archive_state(&runtime);
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+
+        // Follow cursors three at a time and return the battle chains in the order served
+        let fetch = |filter: &str| {
+            let (mut chains, mut cursor) = (Vec::new(), None::<String>);
+            loop {
+                let after = cursor.as_ref().map(|cursor| format!(", cursor: \"{cursor}\"")).unwrap_or_default();
+                let query = format!(
+                    "{{ completedBattles(filter: {{ {filter} }}, limit: 3{after}) {{ battles {{ battleChain }} nextCursor approximate }} }}"
+                );
+                let response = service.handle_query(Request::new(query)).blocking_wait();
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+                let page = response.data.into_json().unwrap()["completedBattles"].clone();
+                assert_eq!(page["approximate"], json!(false));
+                chains.extend(page["battles"].as_array().unwrap().iter().map(|battle| battle["battleChain"].clone()));
+                match page["nextCursor"].as_str() {
+                    Some(next) => cursor = Some(next.to_string()),
+                    None => return chains,
+                }
+            }
+        };
+        let expected = |keep: &dyn Fn(&CompletedBattleRecord) -> bool| {
+            let mut matching: Vec<_> = records.iter().filter(|record| keep(record)).collect();
+            matching.sort_by_key(|record| (record.completed_at, record.battle_chain));
+            matching.into_iter().map(|record| json!(record.battle_chain)).collect::<Vec<_>>()
+        };
+        let day = |day: u64| Timestamp::from(day * MICROS_PER_DAY);
+
+        assert_eq!(fetch(""), expected(&|_| true));
+        assert_eq!(
+            fetch(&format!("player: \"{}\"", bettor("alice"))),
+            expected(&|record| record.player1 == bettor("alice") || record.player2 == bettor("alice")),
+        );
+        assert_eq!(
+            fetch("class: TANK"),
+            expected(&|record| record.player1_class == CharacterClass::Tank || record.player2_class == CharacterClass::Tank),
+        );
+        assert_eq!(fetch("minStake: \"6\""), expected(&|record| record.total_stake >= Amount::from_tokens(6)));
+        assert_eq!(
+            fetch(&format!("from: {}, to: {}", MICROS_PER_DAY, 2 * MICROS_PER_DAY)),
+            expected(&|record| record.completed_at >= day(1) && record.completed_at < day(2)),
+        );
+        assert_eq!(
+            fetch(&format!("player: \"{}\", resultKind: KNOCKOUT", bettor("bob"))),
+            expected(&|record| {
+                (record.player1 == bettor("bob") || record.player2 == bettor("bob")) && record.end_reason.kind() == ResultKind::Knockout
+            }),
+        );
+        assert_eq!(
+            fetch(&format!("from: {}, class: MAGE, minStake: \"3\", resultKind: MAX_ROUNDS", MICROS_PER_DAY)),
+            expected(&|record| {
+                record.completed_at >= day(1)
+                    && (record.player1_class == CharacterClass::Mage || record.player2_class == CharacterClass::Mage)
+                    && record.total_stake >= Amount::from_tokens(3)
+                    && record.end_reason.kind() == ResultKind::MaxRounds
+            }),
+        );
+
+        let response = service.handle_query(Request::new("{ completedBattles(cursor: \"soon\") { approximate } }")).blocking_wait();
+        assert_eq!(response.errors[0].message, "Invalid archive cursor \"soon\"");
+    }
+
+    #[test]
+    fn revenue_reports_flag_days_affected_by_saturation() {
+        let runtime = Arc::new(ServiceRuntime::<MajorulesService>::new());
+        let (mut state, records) = archive_state(&runtime);
+        for record in &records {
+            let day = record.completed_at.micros() / MICROS_PER_DAY;
+            let fee = if day == 2 && record.total_stake == Amount::from_tokens(12) { Amount::MAX } else { Amount::from_tokens(1) };
+            state.treasury_ledger.insert(&record.battle_chain, fee).unwrap();
+        }
+        state.total_platform_revenue.set(Amount::MAX);
+        let overflow = CounterOverflow { first_day: 1, last_day: 1, events: 1 };
+        state.counter_overflows.insert(counters::PLATFORM_REVENUE, overflow).unwrap();
+        let service = MajorulesService { state: ChainState::Lobby(Arc::new(state)), runtime };
+
+        let report = |day: u64| {
+            let query = format!("{{ revenueReport(day: {day}) {{ battles platformFees reliable overflowedCounters }} }}");
+            let response = service.handle_query(Request::new(query)).blocking_wait();
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()["revenueReport"].clone()
+        };
+
+        assert_eq!(
+            report(0),
+            json!({ "battles": 4, "platformFees": "4.", "reliable": true, "overflowedCounters": [] }),
+        );
+        // Lifetime revenue pinned on day 1, so that day's report is no longer trustworthy
+        assert_eq!(
+            report(1),
+            json!({ "battles": 4, "platformFees": "4.", "reliable": false, "overflowedCounters": ["total_platform_revenue"] }),
+        );
+        // Day 2's own fees saturate when summed
+        assert_eq!(report(2)["platformFees"], json!(Amount::MAX.to_string()));
+        assert_eq!(report(2)["reliable"], json!(false));
+    }
 }
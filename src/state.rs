@@ -1,11 +1,35 @@
+use async_graphql::{Enum, SimpleObject};
 use linera_sdk::{
-    linera_base_types::{AccountOwner, Amount, ChainId, Timestamp},
-    views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext},
+    linera_base_types::{AccountOwner, Amount, ChainId, TimeDelta, Timestamp},
+    bcs,
+    views::{linera_views, CustomMapView, CustomSerialize, LogView, MapView, QueueView, RegisterView, RootView, ViewStorageContext},
+};
+use majorules::{
+    betting::BettingLimits,
+    cooldown::PlanStart,
+    elo::EloConfig,
+    leveling::LevelingConfig,
+    matchmaking::{MatchmakingConfig, Pairing, QueueTerms},
+    minting::MintTerms,
+    odds::{self, MarketPricing},
+    practice::PracticeDifficulty,
+    quests::LifetimeTotals,
+    rewards::{Standing, StreakTerms, XpCurve},
+    counters::CounterOverflow,
+    fees::{FeeBreakdown, ProRataSplit, RoundingPolicy},
+    idcodec::IdCodec,
+    schedule::TournamentSchedule,
+    season::SeasonTerms,
+    series::SeriesScore,
+    throttle::{RejectionEntry, RejectionKey, RejectionVerdict},
+    tokens::TokenSupply,
+    time, Attestation, BattleEndReason, BattleEvent, BattleResultSummary, BattleRules, Bps, ChainVariant, CharacterRecord, Emote, ItemRarity, ItemSlot, PassiveMods,
+    Party, PlayerPreferences, ProgressionGates, QueueMode, RankedGates, SpecialAbility, TournamentTerms, ATTESTATION_WINDOW, MAX_TEAM_SIZE,
 };
 use serde::{Deserialize, Serialize};
 
 /// Character classes with unique abilities
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
 pub enum CharacterClass {
     Warrior,
     Assassin,
@@ -15,7 +39,7 @@ pub enum CharacterClass {
 }
 
 /// Battle stances with strategic modifiers
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
 pub enum Stance {
     Balanced,
     Aggressive,
@@ -24,18 +48,68 @@ pub enum Stance {
     Counter,
 }
 
+impl From<majorules::CharacterClass> for CharacterClass {
+    fn from(class: majorules::CharacterClass) -> Self {
+        match class {
+            majorules::CharacterClass::Warrior => Self::Warrior,
+            majorules::CharacterClass::Assassin => Self::Assassin,
+            majorules::CharacterClass::Mage => Self::Mage,
+            majorules::CharacterClass::Tank => Self::Tank,
+            majorules::CharacterClass::Trickster => Self::Trickster,
+        }
+    }
+}
+
+impl From<CharacterClass> for majorules::CharacterClass {
+    fn from(class: CharacterClass) -> Self {
+        match class {
+            CharacterClass::Warrior => Self::Warrior,
+            CharacterClass::Assassin => Self::Assassin,
+            CharacterClass::Mage => Self::Mage,
+            CharacterClass::Tank => Self::Tank,
+            CharacterClass::Trickster => Self::Trickster,
+        }
+    }
+}
+
+impl From<majorules::Stance> for Stance {
+    fn from(stance: majorules::Stance) -> Self {
+        match stance {
+            majorules::Stance::Balanced => Self::Balanced,
+            majorules::Stance::Aggressive => Self::Aggressive,
+            majorules::Stance::Defensive => Self::Defensive,
+            majorules::Stance::Berserker => Self::Berserker,
+            majorules::Stance::Counter => Self::Counter,
+        }
+    }
+}
+
+impl From<Stance> for majorules::Stance {
+    fn from(stance: Stance) -> Self {
+        match stance {
+            Stance::Balanced => Self::Balanced,
+            Stance::Aggressive => Self::Aggressive,
+            Stance::Defensive => Self::Defensive,
+            Stance::Berserker => Self::Berserker,
+            Stance::Counter => Self::Counter,
+        }
+    }
+}
+
 /// Battle status
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, Enum)]
 pub enum BattleStatus {
     #[default]
     WaitingForPlayers,
     InProgress,
     Completed,
+    /// Over at the round limit with the fighters dead even; both stakes were refunded
+    Draw,
     Cancelled,
 }
 
 /// Character snapshot for battles
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct CharacterSnapshot {
     pub nft_id: String,
     pub class: CharacterClass,
@@ -43,15 +117,55 @@ pub struct CharacterSnapshot {
     pub hp_max: u32,
     pub min_damage: u16,
     pub max_damage: u16,
-    pub crit_chance: u16,
+    pub crit_chance: Bps,
     pub crit_multiplier: u16,
-    pub dodge_chance: u16,
+    pub dodge_chance: Bps,
     pub defense: u16,
     pub attack_bps: i16,
     pub defense_bps: i16,
     pub crit_bps: i16,
 }
 
+impl From<majorules::CharacterSnapshot> for CharacterSnapshot {
+    fn from(snapshot: majorules::CharacterSnapshot) -> Self {
+        Self {
+            nft_id: snapshot.nft_id,
+            class: snapshot.class.into(),
+            level: snapshot.level,
+            hp_max: snapshot.hp_max,
+            min_damage: snapshot.min_damage,
+            max_damage: snapshot.max_damage,
+            crit_chance: snapshot.crit_chance,
+            crit_multiplier: snapshot.crit_multiplier,
+            dodge_chance: snapshot.dodge_chance,
+            defense: snapshot.defense,
+            attack_bps: snapshot.attack_bps,
+            defense_bps: snapshot.defense_bps,
+            crit_bps: snapshot.crit_bps,
+        }
+    }
+}
+
+impl From<CharacterSnapshot> for majorules::CharacterSnapshot {
+    fn from(snapshot: CharacterSnapshot) -> Self {
+        Self {
+            nft_id: snapshot.nft_id,
+            class: snapshot.class.into(),
+            level: snapshot.level,
+            hp_max: snapshot.hp_max,
+            min_damage: snapshot.min_damage,
+            max_damage: snapshot.max_damage,
+            crit_chance: snapshot.crit_chance,
+            crit_multiplier: snapshot.crit_multiplier,
+            dodge_chance: snapshot.dodge_chance,
+            defense: snapshot.defense,
+            attack_bps: snapshot.attack_bps,
+            defense_bps: snapshot.defense_bps,
+            crit_bps: snapshot.crit_bps,
+        }
+    }
+}
+
 /// Turn submission
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurnSubmission {
@@ -59,6 +173,18 @@ pub struct TurnSubmission {
     pub turn: u8,
     pub stance: Stance,
     pub use_special: bool,
+    /// Salt of the commitment a sealed turn was revealed against
+    pub salt: Option<[u8; 32]>,
+    /// Seat of the enemy a team-battle fighter attacks; 1v1 turns leave it unset
+    pub target: Option<u8>,
+    /// Emote sent with the turn, added to the round result when the turn executes
+    pub emote: Option<Emote>,
+}
+
+impl From<majorules::TurnSubmission> for TurnSubmission {
+    fn from(turn: majorules::TurnSubmission) -> Self {
+        Self { round: turn.round, turn: turn.turn, stance: turn.stance.into(), use_special: turn.use_special, salt: None, target: None, emote: None }
+    }
 }
 
 /// Battle participant data
@@ -70,8 +196,140 @@ pub struct BattleParticipant {
     pub stake: Amount,
     pub current_hp: u32,
     pub combo_stack: u8,
+    /// Highest combo stack reached this battle
+    pub longest_combo: u8,
     pub special_cooldown: u8,
     pub turns_submitted: [Option<TurnSubmission>; 3],
+    /// Status effects on this fighter, at most one of each kind
+    pub effects: Vec<ActiveEffect>,
+}
+
+/// A fighter as the lobby sends it, before any status effect lands
+impl From<majorules::BattleParticipant> for BattleParticipant {
+    fn from(participant: majorules::BattleParticipant) -> Self {
+        Self {
+            owner: participant.owner,
+            chain: participant.chain,
+            character: participant.character.into(),
+            stake: participant.stake,
+            current_hp: participant.current_hp,
+            combo_stack: participant.combo_stack,
+            longest_combo: participant.longest_combo,
+            special_cooldown: participant.special_cooldown,
+            turns_submitted: participant.turns_submitted.map(|turn| turn.map(Into::into)),
+            effects: Vec::new(),
+        }
+    }
+}
+
+/// A lasting effect on a fighter, and what puts it there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum StatusEffect {
+    /// Loses `magnitude` HP at the start of each turn; from a Mage's Ignite
+    Burn,
+    /// Loses `magnitude` HP at the start of each turn; from a landed Berserker crit
+    Bleed,
+    /// Skips their attack for a turn; from a landed Counter
+    Stun,
+    /// Absorbs up to `magnitude` damage before HP; from a Tank's Bulwark
+    Shield,
+    /// `magnitude` basis points of extra dodge chance against the next incoming attack;
+    /// from a Trickster's Vanish
+    Evasion,
+}
+
+impl StatusEffect {
+    /// Whether the effect deals its magnitude as damage when it ticks
+    pub fn damages_over_time(self) -> bool {
+        matches!(self, StatusEffect::Burn | StatusEffect::Bleed)
+    }
+}
+
+/// A status effect on a fighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct ActiveEffect {
+    pub effect: StatusEffect,
+    pub magnitude: u32,
+    /// Turn starts the effect ticks at before it expires
+    pub turns_left: u8,
+    /// Fighter whose attack applied it, who is credited with its damage
+    pub source: AccountOwner,
+}
+
+/// Who fights a battle, for which chain and stake; fixed when the battle is initialized.
+/// The participant registers also carry combat state that changes every turn, this never does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Roster {
+    pub owners: [AccountOwner; 2],
+    pub chains: [ChainId; 2],
+    pub stakes: [Amount; 2],
+}
+
+impl Roster {
+    pub fn of(player1: &BattleParticipant, player2: &BattleParticipant) -> Self {
+        Self {
+            owners: [player1.owner, player2.owner],
+            chains: [player1.chain, player2.chain],
+            stakes: [player1.stake, player2.stake],
+        }
+    }
+
+    /// The other fighter, if `owner` is one of the two
+    pub fn opponent_of(&self, owner: AccountOwner) -> Option<AccountOwner> {
+        match self.owners {
+            [player1, player2] if owner == player1 => Some(player2),
+            [player1, player2] if owner == player2 => Some(player1),
+            _ => None,
+        }
+    }
+
+    /// Whether `player1` and `player2` are still the fighters this roster fixed
+    pub fn matches(&self, player1: &BattleParticipant, player2: &BattleParticipant) -> bool {
+        *self == Self::of(player1, player2)
+    }
+}
+
+/// One fighter's place in a team battle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seat {
+    pub owner: AccountOwner,
+    pub chain: ChainId,
+    pub stake: Amount,
+}
+
+/// Who fights a team battle, side by side and seat by seat; fixed when the battle is
+/// initialized, like `Roster`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamRoster {
+    pub sides: [Vec<Seat>; 2],
+}
+
+impl TeamRoster {
+    pub fn of(teams: &[Vec<BattleParticipant>; 2]) -> Self {
+        let seats = |side: &Vec<BattleParticipant>| {
+            side.iter().map(|fighter| Seat { owner: fighter.owner, chain: fighter.chain, stake: fighter.stake }).collect()
+        };
+        Self { sides: [seats(&teams[0]), seats(&teams[1])] }
+    }
+
+    /// Whether both sides field between one and `MAX_TEAM_SIZE` fighters, no account twice
+    pub fn is_valid(&self) -> bool {
+        let owners: Vec<AccountOwner> = self.sides.iter().flatten().map(|seat| seat.owner).collect();
+        let distinct = owners.iter().enumerate().all(|(index, owner)| !owners[..index].contains(owner));
+        distinct && self.sides.iter().all(|side| (1..=MAX_TEAM_SIZE).contains(&side.len()))
+    }
+
+    /// Side and seat `owner` fights in, if they fight here at all
+    pub fn seat_of(&self, owner: AccountOwner) -> Option<(usize, usize)> {
+        self.sides.iter().enumerate().find_map(|(side, seats)| {
+            seats.iter().position(|seat| seat.owner == owner).map(|seat| (side, seat))
+        })
+    }
+
+    /// Whether `teams` are still the fighters this roster fixed
+    pub fn matches(&self, teams: &[Vec<BattleParticipant>; 2]) -> bool {
+        *self == Self::of(teams)
+    }
 }
 
 impl BattleParticipant {
@@ -84,8 +342,10 @@ impl BattleParticipant {
             character,
             stake,
             combo_stack: 0,
+            longest_combo: 0,
             special_cooldown: 0,
             turns_submitted: [None, None, None],
+            effects: Vec::new(),
         }
     }
 
@@ -126,26 +386,94 @@ impl BattleParticipant {
 
     /// Increase combo stack
     pub fn add_combo(&mut self) {
-        const MAX_COMBO_STACK: u8 = 5;
-        if self.combo_stack < MAX_COMBO_STACK {
+        if self.combo_stack < majorules::MAX_COMBO_STACK {
             self.combo_stack += 1;
         }
+        self.longest_combo = self.longest_combo.max(self.combo_stack);
+    }
+
+    /// Advance the combo after one of this participant's attacks:
+    /// a dodged attack breaks the combo (even if it rolled a crit),
+    /// a landed crit extends it up to the cap, and any other landed hit keeps it
+    pub fn record_hit(&mut self, was_crit: bool, was_dodged: bool) {
+        if was_dodged {
+            self.reset_combo();
+        } else if was_crit {
+            self.add_combo();
+        }
+    }
+
+    /// Damage multiplier in basis points the next attack gets from the combo
+    pub fn combo_multiplier_bps(&self) -> u32 {
+        10_000 + self.combo_stack as u32 * 500
     }
 
     /// Reset combo stack
     pub fn reset_combo(&mut self) {
         self.combo_stack = 0;
     }
+
+    /// The active effect of kind `effect`, if any
+    pub fn effect(&self, effect: StatusEffect) -> Option<&ActiveEffect> {
+        self.effects.iter().find(|active| active.effect == effect)
+    }
+
+    /// Put `effect` on this fighter, replacing any effect of the same kind rather than stacking
+    pub fn apply_effect(&mut self, effect: ActiveEffect) {
+        self.remove_effect(effect.effect);
+        self.effects.push(effect);
+    }
+
+    pub fn remove_effect(&mut self, effect: StatusEffect) {
+        self.effects.retain(|active| active.effect != effect);
+    }
+
+    /// Deal `damage` to this fighter, draining any shield first. Returns what the shield absorbed
+    pub fn absorb_hit(&mut self, damage: u32) -> u32 {
+        let absorbed = self.effects.iter_mut()
+            .find(|active| active.effect == StatusEffect::Shield)
+            .map_or(0, |shield| {
+                let absorbed = damage.min(shield.magnitude);
+                shield.magnitude -= absorbed;
+                absorbed
+            });
+        self.effects.retain(|active| active.effect != StatusEffect::Shield || active.magnitude > 0);
+        self.current_hp = self.current_hp.saturating_sub(damage - absorbed);
+        absorbed
+    }
+
+    /// Start-of-turn tick: count every effect down and drop the expired ones. Returns each
+    /// effect that ticked with the HP it took, damage over time dealing `dot_bps` of its magnitude
+    pub fn tick_effects(&mut self, dot_bps: u32) -> Vec<(ActiveEffect, u32)> {
+        let ticked: Vec<_> = std::mem::take(&mut self.effects)
+            .into_iter()
+            .map(|active| {
+                let damage = if active.effect.damages_over_time() {
+                    majorules::apply_bps_u32(active.magnitude, dot_bps)
+                } else {
+                    0
+                };
+                self.current_hp = self.current_hp.saturating_sub(damage);
+                (active, damage)
+            })
+            .collect();
+        self.effects = ticked.iter()
+            .filter(|(active, _)| active.turns_left > 1)
+            .map(|(active, _)| ActiveEffect { turns_left: active.turns_left - 1, ..*active })
+            .collect();
+        ticked
+    }
 }
 
 /// Combat statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct CombatStats {
     pub damage_dealt: u64,
     pub damage_taken: u64,
     pub crits: u64,
     pub dodges: u64,
     pub highest_crit: u64,
+    pub longest_combo: u8,
 }
 
 /// Queue entry for matchmaking
@@ -157,10 +485,201 @@ pub struct PlayerQueueEntry {
     pub character_snapshot: CharacterSnapshot,
     pub stake: Amount,
     pub joined_at: Timestamp,
+    pub mode: QueueMode,
+    /// Games of the series the player asked for; 1 for a single battle
+    pub best_of: u8,
+    /// Last rating the lobby knew for the player, refreshed when their chain reports one
+    pub elo_rating: u64,
+    /// Leader chain of the player's party, when they queued or entered with one
+    pub party: Option<ChainId>,
+}
+
+/// A queue entry's place in join order. Keys store the join time big-endian ahead of the
+/// owner and character, so the index reads back longest-waiting first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueOrderKey {
+    pub joined_at: Timestamp,
+    pub player: AccountOwner,
+    pub character_id: String,
+}
+
+impl QueueOrderKey {
+    pub fn of(entry: &PlayerQueueEntry) -> Self {
+        Self { joined_at: entry.joined_at, player: entry.player, character_id: entry.character_id.clone() }
+    }
+}
+
+impl CustomSerialize for QueueOrderKey {
+    fn to_custom_bytes(&self) -> Result<Vec<u8>, linera_views::ViewError> {
+        let mut bytes = self.joined_at.micros().to_be_bytes().to_vec();
+        bytes.extend(bcs::to_bytes(&(self.player, &self.character_id))?);
+        Ok(bytes)
+    }
+
+    fn from_custom_bytes(short_key: &[u8]) -> Result<Self, linera_views::ViewError> {
+        let (joined_at, seat) = short_key.split_first_chunk::<8>().ok_or(linera_views::ViewError::PostLoadValuesError)?;
+        let (player, character_id) = bcs::from_bytes(seat)?;
+        Ok(Self { joined_at: Timestamp::from(u64::from_be_bytes(*joined_at)), player, character_id })
+    }
+}
+
+/// How long a queue entry waits for a match before lobby maintenance releases it
+pub const QUEUE_ENTRY_TTL: TimeDelta = TimeDelta::from_secs(30 * 60);
+
+impl PlayerQueueEntry {
+    /// Whether maintenance releases this entry at `now`
+    pub fn expired(&self, now: Timestamp) -> bool {
+        time::delta_or_zero(now, self.joined_at) >= QUEUE_ENTRY_TTL
+    }
+}
+
+/// Most entrants a tournament may take
+pub const MAX_TOURNAMENT_ENTRANTS: u32 = 64;
+
+/// Where a tournament is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum TournamentStatus {
+    Registration,
+    InProgress,
+    Completed,
+    /// Too few entrants, or a bracket its options refused; every fee was refunded
+    Cancelled,
+}
+
+/// One match of the current tournament round; `player2` is `None` when `player1` has a bye
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct TournamentMatch {
+    pub player1: AccountOwner,
+    pub player2: Option<AccountOwner>,
+    /// Set once the match's battle chain is opened
+    pub battle_chain: Option<ChainId>,
+    pub winner: Option<AccountOwner>,
+}
+
+/// Single-elimination tournament run by the lobby
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct Tournament {
+    /// Internal sequence number; exposed through the lobby's id codec
+    #[graphql(skip)]
+    pub tournament_id: u64,
+    pub name: String,
+    pub terms: TournamentTerms,
+    pub schedule: TournamentSchedule,
+    pub status: TournamentStatus,
+    pub entrants: u32,
+    /// Entry fees collected
+    pub prize_pool: Amount,
+    /// Current round, from 1; zero before the bracket is drawn
+    pub round: u32,
+    /// Matches of the current round in bracket order; winners of neighbouring matches meet next
+    pub matches: Vec<TournamentMatch>,
+    pub champion: Option<AccountOwner>,
+    pub runner_up: Option<AccountOwner>,
+    pub created_at: Timestamp,
+    /// Leader chain of the only party whose members may enter; open to everyone when `None`
+    pub party: Option<ChainId>,
+}
+
+impl Tournament {
+    /// Whether every match of the current round has a winner
+    pub fn round_decided(&self) -> bool {
+        self.matches.iter().all(|round_match| round_match.winner.is_some())
+    }
+}
+
+/// Where a series is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum SeriesStatus {
+    InProgress,
+    Decided,
+    /// Undecided after twice its length; each stake was refunded less its share of the fee
+    Drawn,
+}
+
+/// Best-of-N series run by the lobby, one battle chain per game
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct SeriesMetadata {
+    /// Internal sequence number; exposed through the lobby's id codec
+    #[graphql(skip)]
+    pub series_id: u64,
+    pub mode: QueueMode,
+    pub player1: AccountOwner,
+    pub player2: AccountOwner,
+    /// What each player put into the pot
+    pub stake: Amount,
+    pub score: SeriesScore,
+    /// Battle chains of the games so far, in order; the last one is in progress until decided
+    pub games: Vec<ChainId>,
+    pub status: SeriesStatus,
+    pub winner: Option<AccountOwner>,
+    pub platform_fee_bps: u16,
+    pub created_at: Timestamp,
+    pub decided_at: Option<Timestamp>,
+    /// Queue entries both players were matched from; every game is fought with these
+    #[graphql(skip)]
+    pub entries: [PlayerQueueEntry; 2],
+}
+
+/// Players a season's standings keep, best rated first
+pub const SEASON_STANDINGS_SIZE: usize = 100;
+
+/// Ranked season run by the lobby
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct Season {
+    pub season_id: u32,
+    pub name: String,
+    pub terms: SeasonTerms,
+    pub started_at: Timestamp,
+    pub ended_at: Option<Timestamp>,
+    /// Best rated players of the season, refreshed as its battles complete and frozen at its end
+    pub standings: Vec<SeasonStanding>,
+    /// Rewards minted at the season's end, best rank first
+    pub rewards: Vec<SeasonReward>,
+}
+
+impl Season {
+    /// Put `player`'s latest record into the standings, re-ranking them
+    pub fn place(&mut self, player: AccountOwner, stats: SeasonStats) {
+        self.standings.retain(|standing| standing.player != player);
+        self.standings.push(SeasonStanding { rank: 0, player, rating: stats.rating, stats });
+        self.standings.sort_by(|a, b| b.rating.cmp(&a.rating).then(b.stats.wins.cmp(&a.stats.wins)));
+        self.standings.truncate(SEASON_STANDINGS_SIZE);
+        for (index, standing) in self.standings.iter_mut().enumerate() {
+            standing.rank = index as u64 + 1;
+        }
+    }
+}
+
+/// A player's record within one season, apart from their lifetime stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct SeasonStats {
+    pub battles: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+    /// Rating after the player's latest battle of the season
+    pub rating: u64,
+}
+
+/// One row of a season's standings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct SeasonStanding {
+    pub rank: u64,
+    pub player: AccountOwner,
+    pub rating: u64,
+    pub stats: SeasonStats,
+}
+
+/// Tokens a player earned for their final rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct SeasonReward {
+    pub rank: u64,
+    pub player: AccountOwner,
+    pub amount: Amount,
 }
 
 /// Individual combat action
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct CombatAction {
     pub attacker: AccountOwner,
     pub defender: AccountOwner,
@@ -169,17 +688,70 @@ pub struct CombatAction {
     pub was_dodged: bool,
     pub was_countered: bool,
     pub special_used: bool,
+    /// Which special the attack used, for replays
+    pub special: Option<SpecialAbility>,
+    /// Damage the defender's shield took instead of their HP
+    pub absorbed: u32,
+    /// A burn or bleed ticking at the start of a turn rather than an attack
+    pub damage_over_time: bool,
+    /// A Counter-stance defender answering the attack before it, which is `was_countered`
+    pub counter: bool,
+    /// Effects the attack applied to either fighter; for a tick, the effect that ticked, or
+    /// `Stun` when the attacker sat the turn out; for a counter, the `Stun` it left
+    pub effects: Vec<StatusEffect>,
     pub defender_hp_remaining: u32,
 }
 
-/// Round result with all combat actions
+/// One executed turn as a client re-simulates it: the seed both attacks rolled from, the
+/// choices each fighter made, and the actions the chain resolved, to check the replay against
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayTurn {
+    /// Player 1 attacks from roll stream 0 of the seed, player 2 from stream 1
+    pub seed: [u8; 32],
+    /// Stance and whether a special was asked for, player 1 first
+    pub choices: [(Stance, bool); 2],
+    /// Effect ticks first, then the attacks, in the order they landed
+    pub actions: Vec<CombatAction>,
+}
+
+/// Round result with all combat actions
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct RoundResult {
     pub round: u8,
     pub player1_actions: Vec<CombatAction>,
     pub player2_actions: Vec<CombatAction>,
     pub player1_hp: u32,
     pub player2_hp: u32,
+    /// Effects still on each fighter when the round's latest turn ended
+    pub player1_effects: Vec<ActiveEffect>,
+    pub player2_effects: Vec<ActiveEffect>,
+    /// Emotes sent with the round's executed turns, in turn order
+    pub emotes: Vec<TurnEmote>,
+}
+
+/// An emote a fighter sent with one of their turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct TurnEmote {
+    pub turn: u8,
+    pub player: AccountOwner,
+    pub emote: Emote,
+}
+
+/// Phase of the current battle round
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum BattlePhase {
+    SubmittingTurns,
+    AwaitingExecution,
+}
+
+/// Timing snapshot written on every phase transition, carrying the chain's
+/// time at write so clients can correct their countdowns for clock skew
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct TimingInfo {
+    pub current_round: u8,
+    pub phase: BattlePhase,
+    pub phase_deadline: Option<Timestamp>,
+    pub server_time_at_write: Timestamp,
 }
 
 /// Battle metadata for lobby tracking (active battles only)
@@ -192,6 +764,26 @@ pub struct BattleMetadata {
     pub created_at: Timestamp,
     pub status: BattleStatus,
     pub has_prediction_market: bool,
+    pub rules_version: u32,
+    pub player1_class: CharacterClass,
+    pub player2_class: CharacterClass,
+    /// Fee rate the battle chain was opened with; later fee changes do not apply to it
+    pub platform_fee_bps: u16,
+    /// Queue the fighters were matched in; only ranked battles move ratings
+    pub mode: QueueMode,
+}
+
+/// Running totals for one queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct QueueStats {
+    /// Entries the queue admitted
+    pub joins: u64,
+    /// Pairs it matched
+    pub matches: u64,
+    /// Stakes matched players put up, both fighters counted
+    pub matched_stake: Amount,
+    /// Seconds matched players had waited, summed over both fighters of every pair
+    pub total_wait_secs: u64,
 }
 
 /// Completed battle record for historical tracking
@@ -202,20 +794,129 @@ pub struct CompletedBattleRecord {
     pub player2: AccountOwner,
     pub winner: AccountOwner,
     pub total_stake: Amount,
+    pub platform_fee_bps: u16,
+    pub platform_fee: Amount,
+    pub winner_payout: Amount,
     pub rounds_played: u8,
     pub created_at: Timestamp,
     pub completed_at: Timestamp,
     pub prediction_market_id: Option<u64>,
     pub total_betting_volume: Amount,
+    pub rules_version: u32,
+    pub rules_digest: u64,
+    pub attestation: Attestation,
+    pub player1_class: CharacterClass,
+    pub player2_class: CharacterClass,
+    pub end_reason: BattleEndReason,
+    pub mode: QueueMode,
 }
 
-/// Global player statistics
+/// Winner's confirmation that a battle payout was credited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoutReceiptRecord {
+    pub player: AccountOwner,
+    pub amount: Amount,
+    pub received_at: Timestamp,
+}
+
+/// Chains and queue entries created by one owner within one day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreationCounts {
+    pub player_chains: u32,
+    pub private_battles: u32,
+    pub queue_joins: u32,
+}
+
+/// Per-owner daily creation limits
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreationCaps {
+    pub player_chains: u32,
+    pub private_battles: u32,
+    pub queue_joins: u32,
+}
+
+impl Default for CreationCaps {
+    fn default() -> Self {
+        Self {
+            player_chains: 2,
+            private_battles: 20,
+            queue_joins: 50,
+        }
+    }
+}
+
+/// Lobby settings fixed at instantiation that the treasury or the lobby admin may change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct LobbyConfig {
+    /// Fee on battles opened and markets settled from now on
+    pub platform_fee_bps: u16,
+    /// Handed to player chains when they are created
+    pub max_concurrent_battles: u8,
+    /// Whether market depth names bettors
+    pub public_bettors: bool,
+    pub matchmaking: MatchmakingConfig,
+    /// How battle XP scales before results are relayed to player chains
+    pub xp_curve: XpCurve,
+    pub streaks: StreakTerms,
+    /// How markets opened from now on pay their winners
+    pub market_pricing: MarketPricing,
+    /// Caps on bets, checked as each bet is placed
+    pub betting_limits: BettingLimits,
+    /// While set, players still queue but nobody is paired
+    pub matchmaking_paused: bool,
+    /// Manages settings alongside the treasury owner, but not funds
+    pub admin: Option<AccountOwner>,
+    /// What an account must show before staked or ranked queues take it
+    pub progression: ProgressionGates,
+}
+
+/// Daily allowance an action draws from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreationKind {
+    PlayerChain,
+    #[allow(dead_code)] // Private battle creation is not routed through the lobby yet
+    PrivateBattle,
+    QueueJoin,
+}
+
+impl CreationCounts {
+    pub fn count(&self, kind: CreationKind) -> u32 {
+        match kind {
+            CreationKind::PlayerChain => self.player_chains,
+            CreationKind::PrivateBattle => self.private_battles,
+            CreationKind::QueueJoin => self.queue_joins,
+        }
+    }
+
+    pub fn record(&mut self, kind: CreationKind) {
+        match kind {
+            CreationKind::PlayerChain => self.player_chains += 1,
+            CreationKind::PrivateBattle => self.private_battles += 1,
+            CreationKind::QueueJoin => self.queue_joins += 1,
+        }
+    }
+}
+
+impl CreationCaps {
+    pub fn cap(&self, kind: CreationKind) -> u32 {
+        match kind {
+            CreationKind::PlayerChain => self.player_chains,
+            CreationKind::PrivateBattle => self.private_battles,
+            CreationKind::QueueJoin => self.queue_joins,
+        }
+    }
+}
+
+/// Global player statistics
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct PlayerGlobalStats {
     pub total_battles: u64,
     pub wins: u64,
     pub losses: u64,
-    pub win_rate: f64,
+    /// Battles neither fighter won; counted in `total_battles` but not as wins or losses
+    pub draws: u64,
+    /// Wins per 10 000 battles; BCS cannot encode floats
+    pub win_rate_bps: u64,
     pub elo_rating: u64,
     pub total_damage_dealt: u64,
     pub total_damage_taken: u64,
@@ -227,13 +928,26 @@ pub struct PlayerGlobalStats {
     pub best_streak: u64,
 }
 
+impl PlayerGlobalStats {
+    /// Totals achievements are measured against
+    pub fn lifetime_totals(&self) -> LifetimeTotals {
+        LifetimeTotals {
+            battles: self.total_battles,
+            wins: self.wins,
+            crits: self.total_crits,
+            damage_dealt: self.total_damage_dealt,
+        }
+    }
+}
+
 impl Default for PlayerGlobalStats {
     fn default() -> Self {
         Self {
             total_battles: 0,
             wins: 0,
             losses: 0,
-            win_rate: 0.0,
+            draws: 0,
+            win_rate_bps: 0,
             elo_rating: 1200,
             total_damage_dealt: 0,
             total_damage_taken: 0,
@@ -248,7 +962,7 @@ impl Default for PlayerGlobalStats {
 }
 
 /// Character registry entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct CharacterRegistryEntry {
     pub character_id: String,
     pub owner: AccountOwner,
@@ -263,8 +977,22 @@ pub struct CharacterRegistryEntry {
     pub lives_remaining: u8,
 }
 
+/// A minted character as the player chain holding it last reported it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct RosterEntry {
+    pub character_id: String,
+    pub owner: AccountOwner,
+    pub owner_chain: ChainId,
+    pub class: CharacterClass,
+    pub level: u16,
+    pub lives_remaining: u8,
+    pub minted_at: Timestamp,
+    /// Chain the character was handed to, until that chain reports holding it
+    pub moving_to: Option<ChainId>,
+}
+
 /// Leaderboard entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct LeaderboardEntry {
     pub rank: u64,
     pub player: AccountOwner,
@@ -274,6 +1002,26 @@ pub struct LeaderboardEntry {
     pub losses: u64,
     pub win_rate: f64,
     pub total_earnings: Amount,
+    pub win_streak: u64,
+}
+
+/// Both fighters of a rated battle, winner first
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct BattleStandings {
+    /// Where they stood going in
+    pub before: (Standing, Standing),
+    /// Win streaks they came out with
+    pub streaks_after: (u64, u64),
+}
+
+/// A win streak the lobby announced
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct NotableStreak {
+    pub player: AccountOwner,
+    pub streak: u64,
+    /// Battle that extended the streak to this length
+    pub battle_chain: ChainId,
+    pub reached_at: Timestamp,
 }
 
 /// Character NFT data
@@ -287,9 +1035,9 @@ pub struct CharacterNFT {
     pub hp_max: u32,
     pub min_damage: u16,
     pub max_damage: u16,
-    pub crit_chance: u16,
+    pub crit_chance: Bps,
     pub crit_multiplier: u16,
-    pub dodge_chance: u16,
+    pub dodge_chance: Bps,
     pub defense: u16,
     pub rarity: u8,
     pub attack_bps: i16,
@@ -301,7 +1049,7 @@ pub struct CharacterNFT {
 }
 
 /// Battle record for player history
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct BattleRecord {
     pub battle_chain: ChainId,
     pub opponent: AccountOwner,
@@ -313,10 +1061,20 @@ pub struct BattleRecord {
     pub payout: Amount,
     pub combat_stats: CombatStats,
     pub completed_at: Timestamp,
+    pub rules_digest: u64,
+    pub end_reason: BattleEndReason,
+}
+
+/// What a battle chain reported of a battle whose lobby result has yet to arrive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BattleDetails {
+    pub opponent: AccountOwner,
+    pub stake: Amount,
+    pub rounds_played: u8,
 }
 
 /// Battle result
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
 pub enum BattleResult {
     Won,
     Lost,
@@ -324,8 +1082,10 @@ pub enum BattleResult {
 }
 
 /// Prediction market
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct Market {
+    /// Internal sequence number; exposed through the lobby's id codec
+    #[graphql(skip)]
     pub market_id: u64,
     pub battle_chain: ChainId,
     pub player1_chain: ChainId,
@@ -338,10 +1098,16 @@ pub struct Market {
     pub created_at: Timestamp,
     pub closed_at: Option<Timestamp>,
     pub settled_at: Option<Timestamp>,
+    /// Whether individual bettor identities may be published
+    pub public_bettors: bool,
+    pub pricing: MarketPricing,
+    /// What bets on each side return at their locked odds
+    pub player1_locked: Amount,
+    pub player2_locked: Amount,
 }
 
 /// Market status
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
 pub enum MarketStatus {
     Open,
     Closed,
@@ -349,20 +1115,179 @@ pub enum MarketStatus {
     Cancelled,
 }
 
+impl Market {
+    /// Whether `PlaceBet` is taken
+    pub fn accepts_bets(&self) -> bool {
+        self.status == MarketStatus::Open
+    }
+
+    /// Stakes backing `winner`; zero for a chain that is neither side
+    pub fn winning_pool(&self, winner: ChainId) -> Amount {
+        match winner {
+            _ if winner == self.player1_chain => self.player1_pool,
+            _ if winner == self.player2_chain => self.player2_pool,
+            _ => Amount::ZERO,
+        }
+    }
+
+    /// Stakes paid back out when `winner` wins, and the platform fee taken first: winners keep
+    /// their stakes and split the losing side's, net of `platform_fee_bps`
+    pub fn payout_pool(&self, winner: ChainId, platform_fee_bps: u16) -> (Amount, Amount) {
+        let losing_pool = self.total_pool.saturating_sub(self.winning_pool(winner));
+        let fee = FeeBreakdown::compute(losing_pool, platform_fee_bps).platform_fee;
+        (self.total_pool.saturating_sub(fee), fee)
+    }
+
+    /// Current odds on `side`, or `None` while nobody backs it
+    pub fn odds(&self, side: ChainId, platform_fee_bps: u16) -> Option<u64> {
+        odds::implied_odds(self.winning_pool(side), self.total_pool, platform_fee_bps)
+    }
+
+    /// Locked payouts of the bets on `side`; zero for a chain that is neither side
+    pub fn locked_pool(&self, side: ChainId) -> Amount {
+        match side {
+            _ if side == self.player1_chain => self.player1_locked,
+            _ if side == self.player2_chain => self.player2_locked,
+            _ => Amount::ZERO,
+        }
+    }
+
+    /// How `winner`'s backers split the payout pool, and what of it is left to the platform on
+    /// top of the fee. Pari-mutuel markets split the whole pool by stake; fixed-odds markets by
+    /// locked payout, paying each in full unless the pool falls short of them all
+    pub fn winners_split(&self, winner: ChainId, platform_fee_bps: u16) -> (ProRataSplit, Amount) {
+        let (pool, _) = self.payout_pool(winner, platform_fee_bps);
+        match self.pricing {
+            MarketPricing::PariMutuel => (ProRataSplit::new(pool, self.winning_pool(winner)), Amount::ZERO),
+            MarketPricing::FixedOdds => {
+                let promised = self.locked_pool(winner);
+                let paid = pool.min(promised);
+                (ProRataSplit::new(paid, promised), pool.saturating_sub(paid))
+            }
+        }
+    }
+
+    /// What `bet` weighs in the split once the market is settled or voided: its locked payout
+    /// when a fixed-odds market has a winner, its stake otherwise
+    pub fn payout_weight(&self, bet: &Bet) -> Amount {
+        match self.pricing {
+            MarketPricing::FixedOdds if self.winner_chain.is_some() => odds::locked_payout(bet.amount, bet.odds_at_bet),
+            _ => bet.amount,
+        }
+    }
+}
+
 /// Individual bet
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct Bet {
     pub bettor: AccountOwner,
+    /// Internal sequence number, like `Market::market_id`
+    #[graphql(skip)]
     pub market_id: u64,
     pub predicted_winner: ChainId,
     pub amount: Amount,
+    /// Odds locked when the bet was placed, in basis points; see `majorules::odds`
     pub odds_at_bet: u64,
     pub placed_at: Timestamp,
     pub claimed: bool,
+    /// Share of the pool owed once deferred settlement reaches this bet
+    pub payout: Option<Amount>,
+}
+
+/// Observer waiting for a battle's summary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscriber {
+    pub owner: AccountOwner,
+    pub chain: ChainId,
+}
+
+/// Battle summary still to be delivered to some subscribers, drained by lobby maintenance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingNotification {
+    pub summary: BattleResultSummary,
+    pub recipients: Vec<ChainId>,
+}
+
+/// Payout work left for a settled market, drained by lobby maintenance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSettlement {
+    /// `None` for a voided market, which refunds every stake
+    pub winner_chain: Option<ChainId>,
+    /// Index into the market's bettor list of the next bet to pay out
+    pub next_bettor: u32,
+    /// Winning pool shares handed out so far
+    pub split: ProRataSplit,
+}
+
+/// Rounding remainder of a settled market's payouts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct DustEntry {
+    pub amount: Amount,
+    /// Bettor whose payout absorbed the dust; `None` when the platform took it
+    pub recipient: Option<AccountOwner>,
+}
+
+/// One `Crank` call, kept for anyone to audit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct CrankRecord {
+    pub cranker: AccountOwner,
+    /// Maintenance steps the crank performed
+    pub work: u32,
+    pub bounty: Amount,
+    pub cranked_at: Timestamp,
+}
+
+/// Running totals over every crank
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct CrankStats {
+    pub cranks: u64,
+    pub work_done: u64,
+    pub bounty_paid: Amount,
+}
+
+/// A bettor's record on a prediction chain. Outcomes count once a market settles on a
+/// winner; voided markets refund their stakes and decide nothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct BettorStats {
+    /// Markets bet on; topping a bet up does not count again
+    pub total_bets: u64,
+    pub total_wagered: Amount,
+    /// Bets in markets settled on a winner, and the stakes they held
+    pub decided_bets: u64,
+    pub decided_wagered: Amount,
+    pub won_bets: u64,
+    /// What won bets paid out, stakes included
+    pub total_winnings: Amount,
+}
+
+impl BettorStats {
+    /// Count a bet settled in a decided market, paying `payout`
+    pub fn settle(&mut self, staked: Amount, payout: Amount) {
+        self.decided_bets = self.decided_bets.saturating_add(1);
+        self.decided_wagered = self.decided_wagered.saturating_add(staked);
+        if payout > Amount::ZERO {
+            self.won_bets = self.won_bets.saturating_add(1);
+            self.total_winnings = self.total_winnings.saturating_add(payout);
+        }
+    }
+
+    /// Winnings beyond the stakes of decided bets; zero while the bettor is behind
+    pub fn profit(&self) -> Amount {
+        self.total_winnings.saturating_sub(self.decided_wagered)
+    }
+
+    /// Share of decided bets won
+    pub fn win_rate(&self) -> f64 {
+        if self.decided_bets == 0 {
+            0.0
+        } else {
+            self.won_bets as f64 / self.decided_bets as f64
+        }
+    }
 }
 
 /// Betting leaderboard entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct BettingLeaderboardEntry {
     pub rank: u64,
     pub bettor: AccountOwner,
@@ -373,53 +1298,258 @@ pub struct BettingLeaderboardEntry {
     pub win_rate: f64,
 }
 
+/// Layout version of the chain states below; bump it with a migration in `contract.rs`
+/// whenever stored fields change meaning
+pub const STATE_SCHEMA_VERSION: u32 = 1;
+
+/// What a chain records about itself, independent of its variant
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainMetadata {
+    /// Set once, when the chain is instantiated
+    pub variant: Option<ChainVariant>,
+    /// Layout the stored state was written with
+    pub schema_version: u32,
+}
+
+impl ChainMetadata {
+    /// Metadata of a chain instantiated as `variant` by this build
+    pub fn new(variant: ChainVariant) -> Self {
+        Self { variant: Some(variant), schema_version: STATE_SCHEMA_VERSION }
+    }
+
+    /// Whether the chain has been instantiated
+    pub fn is_initialized(&self) -> bool {
+        self.variant.is_some()
+    }
+}
+
+/// The leading field every chain state shares, loaded on its own to tell variants apart
+/// without decoding fields that differ between them
+#[derive(RootView)]
+#[view(context = ViewStorageContext)]
+pub struct MetadataView {
+    pub metadata: RegisterView<ChainMetadata>,
+}
+
 /// Lobby state - matchmaking, leaderboards, and platform management
 #[derive(RootView)]
 #[view(context = ViewStorageContext)]
 pub struct LobbyState {
-    pub variant: RegisterView<String>,
+    pub metadata: RegisterView<ChainMetadata>,
     pub value: RegisterView<u64>,
     
     // === MATCHMAKING & BATTLE TRACKING ===
-    pub waiting_players: MapView<AccountOwner, PlayerQueueEntry>,
+    /// Queue entries by owner and character; an owner queues each of their characters at most
+    /// once. Change the queue through `enqueue` and `dequeue`, which keep `queue_order` in step
+    pub waiting_players: MapView<(AccountOwner, String), PlayerQueueEntry>,
+    /// `waiting_players` in join order
+    pub queue_order: CustomMapView<QueueOrderKey, ()>,
+    /// When maintenance next sweeps the queue for pairs that waiting has made acceptable
+    pub matchmaking_sweep_at: RegisterView<Timestamp>,
+    /// Terms of the queues they were set for; the others keep `QueueTerms::default_for`
+    pub queue_terms: MapView<QueueMode, QueueTerms>,
+    pub queue_stats: MapView<QueueMode, QueueStats>,
     pub active_battles: MapView<ChainId, BattleMetadata>,
+    /// Battles each owner is fighting, practice and team battles included; owners fighting none
+    /// have no entry
+    pub battles_in_flight: MapView<AccountOwner, u32>,
     pub completed_battles: MapView<ChainId, CompletedBattleRecord>,
+    /// Completed battles each owner fought, keyed as the archive sorts them
+    pub completed_by_owner: MapView<AccountOwner, Vec<(Timestamp, ChainId)>>,
+    /// Completed battles by day of completion, keyed as the archive sorts them
+    pub completed_by_day: MapView<u64, Vec<(Timestamp, ChainId)>>,
+    pub payout_receipts: MapView<ChainId, PayoutReceiptRecord>,
+    /// Fighter results already relayed to player chains, per battle and fighter
+    pub relayed_results: MapView<(ChainId, AccountOwner), ()>,
     pub battle_count: RegisterView<u64>,
     
     // === PLAYER MANAGEMENT ===
     pub character_registry: MapView<String, CharacterRegistryEntry>,
+    /// Every character player chains reported minting, by id; the first chain to report an id
+    /// keeps it
+    pub roster: MapView<String, RosterEntry>,
     pub leaderboard: RegisterView<Vec<LeaderboardEntry>>,
+    /// Ratings the lobby rates battles with, seeded from player stats responses
+    pub ratings: MapView<AccountOwner, u64>,
+    /// Winner's and loser's rating changes for each rated battle
+    pub rated_battles: MapView<ChainId, (i32, i32)>,
+    /// Where each rated battle's fighters stood going in and the streaks they came out with
+    pub battle_standings: MapView<ChainId, BattleStandings>,
+    /// Wins in a row of each player; a loss resets it unless insured, a draw leaves it
+    pub win_streaks: MapView<AccountOwner, u64>,
+    /// Players whose streak survives their next defeat
+    pub streak_insurance: MapView<AccountOwner, ()>,
+    /// Streaks that reached a notable length, oldest first
+    pub notable_streaks: LogView<NotableStreak>,
+    pub elo_config: RegisterView<EloConfig>,
+    /// Handed to player chains when they are created
+    pub leveling_config: RegisterView<LevelingConfig>,
+    /// Handed to player chains when they are created
+    pub mint_terms: RegisterView<MintTerms>,
+
+    // === SEASONS ===
+    /// The running season, if any
+    pub current_season: RegisterView<Option<Season>>,
+    /// Ended seasons, by id
+    pub seasons: MapView<u32, Season>,
+    pub season_count: RegisterView<u32>,
+    /// Each player's record per season
+    pub season_stats: MapView<(u32, AccountOwner), SeasonStats>,
+    /// Season each cached rating was last written in; older ratings are soft-reset on read
+    pub rating_seasons: MapView<AccountOwner, u32>,
+    
+    // === ABUSE GUARDS ===
+    pub creation_counts: MapView<(u64, AccountOwner), CreationCounts>,
+    pub creation_caps: RegisterView<CreationCaps>,
+    pub creation_exemptions: MapView<AccountOwner, ()>,
+    /// When each player last left the queue, until they rejoin
+    pub queue_exits: MapView<AccountOwner, Timestamp>,
+    /// Each player's latest queue matches, oldest first
+    pub recent_pairings: MapView<AccountOwner, Vec<Pairing>>,
+    pub rejections: MapView<RejectionKey, RejectionEntry>,
+    /// Queue releases withheld by rejection throttling by owner and character, sent on the
+    /// owner's next `LeaveQueue`
+    pub held_releases: MapView<(AccountOwner, String), HeldRelease>,
+    /// Private battles waiting for their invited opponent, by internal id
+    pub pending_private_battles: MapView<u64, PlayerQueueEntry>,
+    /// Invite digest of each pending private battle, by internal id
+    pub private_battle_invites: MapView<u64, [u8; 32]>,
+    pub private_battle_count: RegisterView<u64>,
+
+    // === TOURNAMENTS ===
+    pub tournaments: MapView<u64, Tournament>,
+    pub tournament_count: RegisterView<u64>,
+    /// Entrants of each tournament in registration order
+    pub tournament_entrants: MapView<u64, Vec<PlayerQueueEntry>>,
+    /// Start time of each tournament still taking entrants, so maintenance can start or cancel it
+    pub tournament_starts: MapView<u64, Timestamp>,
+    /// Tournament each match battle belongs to
+    pub tournament_battles: MapView<ChainId, u64>,
+
+    // === SERIES ===
+    pub series: MapView<u64, SeriesMetadata>,
+    pub series_count: RegisterView<u64>,
+    /// Series each game battle belongs to, until the game completes
+    pub series_battles: MapView<ChainId, u64>,
+
+    // === TEAM BATTLES ===
+    /// Both sides of each active team battle, by seat; its first seats stand as player 1 and 2
+    /// in the battle's metadata
+    pub team_battles: MapView<ChainId, [Vec<AccountOwner>; 2]>,
+    /// Each active practice battle's player and their chain; practice battles are tracked
+    /// apart from `active_battles`, as nobody bets on, spectates or rates them
+    pub practice_battles: MapView<ChainId, (AccountOwner, ChainId)>,
+    /// Practice and casual wins per account, toward the progression gates
+    pub progression_wins: MapView<AccountOwner, u64>,
     
     // === PLATFORM ECONOMICS ===
-    pub platform_fee_bps: RegisterView<u16>,
+    pub config: RegisterView<LobbyConfig>,
     pub treasury_owner: RegisterView<Option<AccountOwner>>,
     pub total_platform_revenue: RegisterView<Amount>,
+    pub treasury_ledger: MapView<ChainId, Amount>,
+    /// Rounding remainder of each settled market and where it went
+    pub dust_ledger: MapView<u64, DustEntry>,
+    /// Platform fee taken from each settled market's losing pool
+    pub market_fee_ledger: MapView<u64, Amount>,
+    /// Stakes refunded from each voided market, once its payouts are worked out
+    pub market_refunds: MapView<u64, Amount>,
+    /// Fees each player has paid to mint and revive characters over their lifetime
+    pub mint_fee_ledger: MapView<AccountOwner, Amount>,
+    /// Quest rewards minted, by day, player and quest; each pays once a day
+    pub quest_claims: MapView<(u64, AccountOwner, String), Amount>,
     pub battle_token_balance: RegisterView<Amount>,
+    /// Battle tokens minted and burned; balances themselves live on player chains
+    pub token_supply: RegisterView<TokenSupply>,
+    /// Funds crank bounties
+    pub community_pool: RegisterView<Amount>,
+    /// Lifetime counters that pinned at their maximum, by counter name
+    pub counter_overflows: MapView<String, CounterOverflow>,
+    
+    // === BATTLE RULES ===
+    pub battle_rules: RegisterView<BattleRules>,
+    pub rules_version: RegisterView<u32>,
+    pub ranked_gates: RegisterView<RankedGates>,
     
     // === PREDICTION MARKETS (SEPARATE TRACKING) ===
     pub prediction_markets: MapView<u64, Market>,
     pub battle_to_market: MapView<ChainId, u64>,
     pub market_count: RegisterView<u64>,
+    pub id_codec: RegisterView<IdCodec>,
     pub bets: MapView<(u64, AccountOwner), Bet>,
+    pub market_rounding: RegisterView<RoundingPolicy>,
+    /// Bettors per market in placement order, so settlement can be paged
+    pub market_bettors: MapView<u64, Vec<AccountOwner>>,
     pub total_betting_volume: RegisterView<Amount>,
     pub betting_leaderboard: RegisterView<Vec<BettingLeaderboardEntry>>,
+
+    // === DEFERRED WORK ===
+    /// Settled markets whose bets still await payouts
+    pub pending_settlements: MapView<u64, PendingSettlement>,
+    /// Result summaries waiting to go out to subscribers, by battle
+    pub notification_outbox: MapView<ChainId, PendingNotification>,
+    /// Open markets by the time maintenance closes them if their battle never starts
+    pub market_deadlines: MapView<u64, Timestamp>,
+    /// Closed markets by the time maintenance voids them if their battle never completes
+    pub result_deadlines: MapView<u64, Timestamp>,
+
+    // === CRANKS ===
+    pub crank_log: LogView<CrankRecord>,
+    pub crank_stats: RegisterView<CrankStats>,
+    /// Bounty paid out per day, to hold cranks under the daily cap
+    pub crank_payouts: MapView<u64, Amount>,
+    /// Bounty earned by each cranker
+    pub crank_rewards: MapView<AccountOwner, Amount>,
+
+    // === OBSERVERS ===
+    /// Subscribers per active battle; dropped once the battle completes
+    pub battle_subscribers: MapView<ChainId, Vec<Subscriber>>,
+
+    // === OPERATOR REVIEW ===
+    /// Completed battles a participant contested
+    pub review_queue: MapView<ChainId, ()>,
 }
 
 /// Battle state - individual combat session between two players
 #[derive(RootView)]
 #[view(context = ViewStorageContext)]
 pub struct BattleState {
-    pub variant: RegisterView<String>,
+    pub metadata: RegisterView<ChainMetadata>,
     pub value: RegisterView<u64>,
     pub player1: RegisterView<Option<BattleParticipant>>,
     pub player2: RegisterView<Option<BattleParticipant>>,
+    /// Write-once at initialization; every battle operation authorizes against it
+    pub roster: RegisterView<Option<Roster>>,
+    /// A team battle's roster, in place of `roster`; write-once like it
+    pub team_roster: RegisterView<Option<TeamRoster>>,
+    /// Both sides of a team battle with their combat state, seat by seat
+    pub teams: RegisterView<[Vec<BattleParticipant>; 2]>,
+    /// Turns of the current round a team battle resolved; they resolve in order
+    pub resolved_turns: RegisterView<u8>,
+    /// The bot's difficulty in a practice battle, where the bot fights as player 2
+    pub practice: RegisterView<Option<PracticeDifficulty>>,
     pub status: RegisterView<BattleStatus>,
     pub current_round: RegisterView<u8>,
     pub max_rounds: RegisterView<u8>,
+    pub rules: RegisterView<BattleRules>,
+    pub rules_version: RegisterView<u32>,
     pub turn_submissions: MapView<(AccountOwner, u8), TurnSubmission>,
+    /// Sealed turns of the current round not yet revealed
+    pub turn_commitments: MapView<(AccountOwner, u8), [u8; 32]>,
+    /// Per turn of the current round, when sealed turns must be revealed by; set once both
+    /// fighters locked the turn in
+    pub reveal_deadlines: MapView<u8, Timestamp>,
     pub winner: RegisterView<Option<AccountOwner>>,
-    pub round_results: RegisterView<Vec<RoundResult>>,
-    pub battle_log: RegisterView<Vec<String>>,
+    /// Set once, wherever the battle ends
+    pub end_reason: RegisterView<Option<BattleEndReason>>,
+    pub round_results: MapView<u8, RoundResult>,
+    /// Both fighters as the battle started, player 1 first; replays simulate from them
+    pub opening_fighters: RegisterView<Vec<BattleParticipant>>,
+    /// Every executed turn, by round and turn
+    pub replay_turns: MapView<(u8, u8), ReplayTurn>,
+    /// The latest events the battle emitted, oldest first, at most `BATTLE_LOG_CAPACITY`, each
+    /// with its index on the battle's stream
+    pub battle_log: QueueView<(u32, BattleEvent)>,
+    pub execute_requests: MapView<(u8, AccountOwner), ()>,
     pub random_counter: RegisterView<u64>,
     pub lobby_chain_id: RegisterView<Option<ChainId>>,
     pub total_stake: RegisterView<Amount>,
@@ -428,10 +1558,203 @@ pub struct BattleState {
     pub started_at: RegisterView<Option<Timestamp>>,
     pub completed_at: RegisterView<Option<Timestamp>>,
     pub round_deadline: RegisterView<Option<Timestamp>>,
+    pub timing: RegisterView<Option<TimingInfo>>,
+    pub rejections: MapView<RejectionKey, RejectionEntry>,
+    /// Each participant's verdict on the finalized outcome
+    pub attestations: MapView<AccountOwner, bool>,
+    /// Whether the lobby heard that the first turn is in, closing betting on the battle
+    pub start_reported: RegisterView<bool>,
+}
+
+impl LobbyState {
+    /// Queue `entry`, replacing the entry its owner queued for the same character
+    pub async fn enqueue(&mut self, entry: PlayerQueueEntry) -> Result<(), linera_views::ViewError> {
+        let seat = (entry.player, entry.character_id.clone());
+        self.dequeue(&seat).await?;
+        self.queue_order.insert(&QueueOrderKey::of(&entry), ())?;
+        self.waiting_players.insert(&seat, entry)
+    }
+
+    /// Take the entry at `seat`, an owner and character, out of the queue and return it
+    pub async fn dequeue(&mut self, seat: &(AccountOwner, String)) -> Result<Option<PlayerQueueEntry>, linera_views::ViewError> {
+        let entry = self.waiting_players.get(seat).await?;
+        if let Some(entry) = &entry {
+            self.queue_order.remove(&QueueOrderKey::of(entry))?;
+            self.waiting_players.remove(seat)?;
+        }
+        Ok(entry)
+    }
+
+    /// Every queue entry, longest-waiting first
+    pub async fn queue_in_order(&self) -> Result<Vec<PlayerQueueEntry>, linera_views::ViewError> {
+        let mut seats = Vec::new();
+        self.queue_order.for_each_index(|key| {
+            seats.push((key.player, key.character_id));
+            Ok(())
+        }).await?;
+        Ok(self.waiting_players.multi_get(&seats).await?.into_iter().flatten().collect())
+    }
+
+    /// `player`'s queue entries, one per queued character
+    pub async fn queued_entries(&self, player: AccountOwner) -> Result<Vec<PlayerQueueEntry>, linera_views::ViewError> {
+        let mut entries = Vec::new();
+        self.waiting_players.for_each_index_value(|(owner, _), entry| {
+            if owner == player {
+                entries.push(entry.into_owned());
+            }
+            Ok(())
+        }).await?;
+        Ok(entries)
+    }
+
+    /// Whether `player` has any character in the queue
+    pub async fn is_queued(&self, player: AccountOwner) -> Result<bool, linera_views::ViewError> {
+        Ok(!self.queued_entries(player).await?.is_empty())
+    }
+}
+
+impl BattleState {
+    /// Whether `caller` may submit or seal `turn` of `round` now, or why not. Checks that
+    /// depend on the submitted stance and special follow in the contract
+    pub async fn turn_check(&self, caller: AccountOwner, round: u8, turn: u8) -> Result<(), &'static str> {
+        self.round_check(round, turn)?;
+        if self.turn_locked(caller, turn).await {
+            return Err("duplicate_turn");
+        }
+        Ok(())
+    }
+
+    /// Turns each fighter submits per round of this battle
+    pub fn turns_per_round(&self) -> u8 {
+        self.rules.get().turns_per_round
+    }
+
+    /// Whether every turn of the current round executed: both fighters' choices are in the
+    /// clear for each, sealed ones revealed
+    pub async fn round_resolved(&self) -> bool {
+        let Some(owners) = self.roster.get().as_ref().map(|roster| roster.owners) else {
+            return false;
+        };
+        for turn in 0..self.turns_per_round() {
+            for owner in owners {
+                if !self.turn_submissions.contains_key(&(owner, turn)).await.unwrap_or(false) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether `owner` submitted or sealed `turn` of the current round
+    pub async fn turn_locked(&self, owner: AccountOwner, turn: u8) -> bool {
+        self.turn_submissions.contains_key(&(owner, turn)).await.unwrap_or(true)
+            || self.turn_commitments.contains_key(&(owner, turn)).await.unwrap_or(true)
+    }
+
+    /// The commitment `caller` would reveal `turn` of `round` against at `now`, or why the
+    /// reveal is refused. Matching the commitment and the stance checks follow in the contract
+    pub async fn reveal_check(&self, caller: AccountOwner, round: u8, turn: u8, now: Timestamp) -> Result<[u8; 32], &'static str> {
+        self.round_check(round, turn)?;
+        let Ok(Some(commitment)) = self.turn_commitments.get(&(caller, turn)).await else {
+            return Err("not_committed");
+        };
+        let Some(opponent) = self.roster.get().as_ref().and_then(|roster| roster.opponent_of(caller)) else {
+            return Err("not_a_participant");
+        };
+        if !self.turn_locked(opponent, turn).await {
+            return Err("awaiting_opponent");
+        }
+        match self.reveal_deadlines.get(&turn).await {
+            Ok(Some(deadline)) if now <= deadline => Ok(commitment),
+            _ => Err("reveal_closed"),
+        }
+    }
+
+    /// The opponent `caller` would win against by forfeit for `turn` of `round` at `now`, or
+    /// why the claim is refused: the caller must have revealed, and the opponent not, in time
+    pub async fn forfeit_check(&self, caller: AccountOwner, round: u8, turn: u8, now: Timestamp) -> Result<AccountOwner, &'static str> {
+        self.round_check(round, turn)?;
+        let Some(opponent) = self.roster.get().as_ref().and_then(|roster| roster.opponent_of(caller)) else {
+            return Err("not_a_participant");
+        };
+        if !self.turn_submissions.contains_key(&(caller, turn)).await.unwrap_or(false) {
+            return Err("not_revealed");
+        }
+        if !self.turn_commitments.contains_key(&(opponent, turn)).await.unwrap_or(false) {
+            return Err("nothing_to_claim");
+        }
+        match self.reveal_deadlines.get(&turn).await {
+            Ok(Some(deadline)) if now > deadline => Ok(opponent),
+            _ => Err("reveal_open"),
+        }
+    }
+
+    /// Whether every fighter of team `side` still standing submitted `turn`
+    pub async fn side_submitted(&self, side: usize, turn: u8) -> bool {
+        for fighter in self.teams.get()[side].iter().filter(|fighter| fighter.current_hp > 0) {
+            if !self.turn_submissions.contains_key(&(fighter.owner, turn)).await.unwrap_or(false) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The team side `caller` would win for by forfeit for `turn` of `round` at `now`, or why
+    /// the claim is refused: `turn` must be the one the battle waits on, the caller's side all
+    /// in and an enemy still standing not, once the round's deadline passed
+    pub async fn team_forfeit_check(&self, caller: AccountOwner, round: u8, turn: u8, now: Timestamp) -> Result<usize, &'static str> {
+        self.round_check(round, turn)?;
+        let Some((side, _)) = self.team_roster.get().as_ref().and_then(|roster| roster.seat_of(caller)) else {
+            return Err("not_a_participant");
+        };
+        if turn != *self.resolved_turns.get() {
+            return Err("turn_not_pending");
+        }
+        if !self.side_submitted(side, turn).await {
+            return Err("not_submitted");
+        }
+        if self.side_submitted(1 - side, turn).await {
+            return Err("nothing_to_claim");
+        }
+        match *self.round_deadline.get() {
+            Some(deadline) if now > deadline => Ok(side),
+            _ => Err("round_open"),
+        }
+    }
+
+    fn round_check(&self, round: u8, turn: u8) -> Result<(), &'static str> {
+        if *self.status.get() != BattleStatus::InProgress {
+            return Err("battle_not_active");
+        }
+        if round != *self.current_round.get() {
+            return Err("wrong_round");
+        }
+        if turn >= self.turns_per_round() {
+            return Err("invalid_turn");
+        }
+        Ok(())
+    }
+
+    /// The opponent `caller` would attest against at `now`, or why the attestation is refused
+    pub async fn attest_check(&self, caller: AccountOwner, now: Timestamp) -> Result<AccountOwner, &'static str> {
+        let (Some(completed_at), BattleStatus::Completed | BattleStatus::Draw) = (*self.completed_at.get(), *self.status.get()) else {
+            return Err("battle_not_completed");
+        };
+        let Some(opponent) = self.roster.get().as_ref().and_then(|roster| roster.opponent_of(caller)) else {
+            return Err("not_a_participant");
+        };
+        if now > completed_at.saturating_add(ATTESTATION_WINDOW) {
+            return Err("attestation_closed");
+        }
+        if self.attestations.contains_key(&caller).await.unwrap_or(false) {
+            return Err("already_attested");
+        }
+        Ok(opponent)
+    }
 }
 
 /// Character data for player chain
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct CharacterData {
     pub nft_id: String,
     pub owner: AccountOwner,
@@ -441,54 +1764,423 @@ pub struct CharacterData {
     pub hp_max: u32,
     pub min_damage: u16,
     pub max_damage: u16,
-    pub crit_chance: u16,
+    pub crit_chance: Bps,
     pub crit_multiplier: u16,
-    pub dodge_chance: u16,
+    pub dodge_chance: Bps,
     pub defense: u16,
     pub attack_bps: i16,
     pub defense_bps: i16,
     pub crit_bps: i16,
+    /// Rolled at mint; the trait bonuses above are its
+    pub rarity: ItemRarity,
+    /// Zero once ranked defeats killed the character, until it is revived
+    pub lives_remaining: u8,
     pub created_at: Timestamp,
     pub is_active: bool,
 }
 
+impl CharacterData {
+    pub fn is_alive(&self) -> bool {
+        self.lives_remaining > 0
+    }
+
+    /// What travels with the character when it changes hands
+    pub fn record(&self) -> CharacterRecord {
+        CharacterRecord {
+            nft_id: self.nft_id.clone(),
+            class: self.class.into(),
+            level: self.level,
+            xp: self.xp,
+            hp_max: self.hp_max,
+            min_damage: self.min_damage,
+            max_damage: self.max_damage,
+            crit_chance: self.crit_chance,
+            crit_multiplier: self.crit_multiplier,
+            dodge_chance: self.dodge_chance,
+            defense: self.defense,
+            attack_bps: self.attack_bps,
+            defense_bps: self.defense_bps,
+            crit_bps: self.crit_bps,
+            rarity: self.rarity,
+            lives_remaining: self.lives_remaining,
+            created_at: self.created_at,
+        }
+    }
+
+    /// A received character, now owned by `owner` and not yet active
+    pub fn from_record(record: CharacterRecord, owner: AccountOwner) -> Self {
+        Self {
+            nft_id: record.nft_id,
+            owner,
+            class: record.class.into(),
+            level: record.level,
+            xp: record.xp,
+            hp_max: record.hp_max,
+            min_damage: record.min_damage,
+            max_damage: record.max_damage,
+            crit_chance: record.crit_chance,
+            crit_multiplier: record.crit_multiplier,
+            dodge_chance: record.dodge_chance,
+            defense: record.defense,
+            attack_bps: record.attack_bps,
+            defense_bps: record.defense_bps,
+            crit_bps: record.crit_bps,
+            rarity: record.rarity,
+            lives_remaining: record.lives_remaining,
+            created_at: record.created_at,
+            is_active: false,
+        }
+    }
+}
+
+/// Equipment item owned by a player chain
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ItemData {
+    pub item_id: String,
+    pub slot: ItemSlot,
+    pub bonuses: PassiveMods,
+    pub rarity: ItemRarity,
+    pub acquired_at: Timestamp,
+    /// Character the item is locked to while equipped
+    pub equipped_on: Option<String>,
+}
+
+/// What a character is currently committed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngagementKind {
+    Queue,
+    Battle,
+    /// Held by the lobby for a series, between and during its games
+    Series,
+}
+
+/// What holds a character: the lobby while queued or in a series, the battle chain once matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Engagement {
+    pub chain: ChainId,
+    pub kind: EngagementKind,
+    /// Queue the character entered; ranked battles cost a life on defeat
+    pub mode: QueueMode,
+    pub since: Timestamp,
+    /// Whether the chain it is engaged with acknowledged it; a queue entry is confirmed by
+    /// `QueueJoined`, a battle by being matched
+    pub confirmed: bool,
+}
+
+/// Progress toward one daily quest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuestProgress {
+    pub progress: u64,
+    pub claimed: bool,
+}
+
+/// Queued character the lobby still owes a `QueueLeft` for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldRelease {
+    pub player_chain: ChainId,
+    pub character_id: String,
+}
+
+/// Note that `counter` pinned at its maximum at `now`
+pub async fn record_overflow(overflows: &mut MapView<String, CounterOverflow>, counter: &str, now: Timestamp) {
+    let previous = overflows.get(counter).await
+        .expect("Failed to read counter overflows");
+    overflows.insert(counter, CounterOverflow::observe(previous, now))
+        .expect("Failed to record counter overflow");
+}
+
+/// Coalesce a rejection into `rejections` and tell the caller whether to send feedback
+pub async fn record_rejection(
+    rejections: &mut MapView<RejectionKey, RejectionEntry>,
+    key: RejectionKey,
+    now: Timestamp,
+) -> RejectionVerdict {
+    let previous = rejections.get(&key).await
+        .expect("Failed to read rejection log");
+    let (entry, verdict) = RejectionEntry::observe(previous, now);
+    if let Some(entry) = entry {
+        rejections.insert(&key, entry)
+            .expect("Failed to update rejection log");
+    }
+    verdict
+}
+
+/// Cooldown `owner`'s special restarts at under `battle`'s rules, or `None` when `owner` is
+/// not fighting in `battle`
+pub fn special_cooldown_of(battle: &BattleState, owner: AccountOwner) -> Option<u8> {
+    [battle.player1.get(), battle.player2.get()]
+        .into_iter()
+        .flatten()
+        .find(|participant| participant.owner == owner)
+        .map(|participant| battle.rules.get().special_cooldown_for(participant.character.class.into()))
+}
+
+/// Where `owner`'s special plan picks up: its live cooldown walked from the first turn this
+/// round still waiting on the opponent, plus the specials it has queued from there.
+/// `None` when `owner` is not fighting in `battle`.
+pub async fn special_plan_start(battle: &BattleState, owner: AccountOwner) -> Option<(PlanStart, Vec<(u8, u8)>)> {
+    let (Some(player1), Some(player2)) = (battle.player1.get(), battle.player2.get()) else {
+        return None;
+    };
+    let attacks_first = player1.owner == owner;
+    let (me, opponent) = match (attacks_first, player2.owner == owner) {
+        (true, _) => (player1, player2),
+        (false, true) => (player2, player1),
+        (false, false) => return None,
+    };
+    let round = *battle.current_round.get();
+
+    let mut next_turn = None;
+    let mut executed_specials = 0;
+    let mut queued = Vec::new();
+    for turn in 0..battle.turns_per_round() {
+        let Some(mine) = battle.turn_submissions.get(&(owner, turn)).await.ok().flatten() else {
+            next_turn.get_or_insert(turn);
+            continue;
+        };
+        if battle.turn_submissions.contains_key(&(opponent.owner, turn)).await.unwrap_or(false) {
+            executed_specials += mine.use_special as u8;
+        } else {
+            next_turn.get_or_insert(turn);
+            if mine.use_special {
+                queued.push((round, turn));
+            }
+        }
+    }
+
+    let start = match next_turn {
+        Some(turn) => PlanStart::live(round, turn, me.special_cooldown, attacks_first, executed_specials),
+        // Every turn of this round has played out
+        None => PlanStart::live(round.saturating_add(1), 0, me.special_cooldown, attacks_first, 0),
+    };
+    Some((start, queued))
+}
+
 /// Player state - NFT characters, inventory, and personal statistics
 #[derive(RootView)]
 #[view(context = ViewStorageContext)]
 pub struct PlayerState {
-    pub variant: RegisterView<String>,
+    pub metadata: RegisterView<ChainMetadata>,
     pub value: RegisterView<u64>,
     pub owner: RegisterView<Option<AccountOwner>>,
     pub lobby_chain_id: RegisterView<Option<ChainId>>,
     pub characters: MapView<String, CharacterData>,
+    pub items: MapView<String, ItemData>,
+    /// Item worn by each character in each slot
+    pub equipment: MapView<(String, ItemSlot), String>,
     pub active_character: RegisterView<Option<String>>,
+    /// Characters ever minted on this chain
     pub character_count: RegisterView<u64>,
+    pub last_mint_at: RegisterView<Option<Timestamp>>,
     pub battle_history: MapView<ChainId, BattleRecord>,
+    /// Battle chains of `battle_history` in the order their results arrived, oldest first
+    pub battle_history_order: LogView<ChainId>,
+    /// Battle chains' own reports of battles whose lobby result is still on its way
+    pub battle_details: MapView<ChainId, BattleDetails>,
     pub player_stats: RegisterView<PlayerGlobalStats>,
     pub battle_token_balance: RegisterView<Amount>,
-    pub locked_stakes: MapView<ChainId, Amount>,
-    pub in_battle: RegisterView<bool>,
-    pub current_battle_chain: RegisterView<Option<ChainId>>,
+    /// Stake held out of the balance for each queued or fighting character, until its entry is
+    /// released or its battle's result arrives
+    pub locked_stakes: MapView<String, Amount>,
+    /// What each engaged character is held by, by character id
+    pub active_engagements: MapView<String, Engagement>,
+    /// Character the lobby matched into each battle chain, until that battle's result arrives
+    pub matched_battles: MapView<ChainId, String>,
+    /// Id of the private battle this chain is hosting, until it starts or is released
+    pub hosted_private_battle: RegisterView<Option<u64>>,
+    /// Asking price of each character listed for sale
+    pub character_listings: MapView<String, Amount>,
+    /// Payment held for each purchase offered to a seller chain, by seller chain and character,
+    /// until the character arrives or the sale is refused
+    pub purchase_escrows: MapView<(ChainId, String), Amount>,
+    /// Amount held for each bet sent to the lobby, by external market id, until the lobby
+    /// places or refuses it
+    pub pending_bets: MapView<u64, Amount>,
+    pub max_concurrent_battles: RegisterView<u8>,
+    /// Lobby's ranked thresholds as of chain creation; the lobby re-checks every request
+    pub ranked_gates: RegisterView<RankedGates>,
+    /// Lobby's progression gates as of chain creation; the lobby re-checks them too
+    pub progression: RegisterView<ProgressionGates>,
+    /// Practice and casual wins of this account, toward the progression gates
+    pub progression_wins: RegisterView<u64>,
+    /// Lobby's leveling config as of chain creation
+    pub leveling_config: RegisterView<LevelingConfig>,
+    /// Lobby's mint terms as of chain creation
+    pub mint_terms: RegisterView<MintTerms>,
     pub last_active: RegisterView<Timestamp>,
+    pub rejections: MapView<RejectionKey, RejectionEntry>,
+    /// Summaries of battles this player subscribed to through the lobby
+    pub subscribed_results: MapView<ChainId, BattleResultSummary>,
+    pub preferences: RegisterView<PlayerPreferences>,
+    /// Lifetime counters that pinned at their maximum, by counter name
+    pub counter_overflows: MapView<String, CounterOverflow>,
+    /// Day `quest_progress` counts, as a `time::bucket_day` bucket
+    pub quest_day: RegisterView<u64>,
+    /// Progress toward each daily quest on `quest_day`
+    pub quest_progress: MapView<String, QuestProgress>,
+    /// When each achievement unlocked
+    pub achievements: MapView<String, Timestamp>,
+    /// Owner of each befriended player chain
+    pub friends: MapView<ChainId, AccountOwner>,
+    /// Friend requests awaiting an answer, with the owner who sent each, by requesting chain
+    pub friend_requests: MapView<ChainId, AccountOwner>,
+    /// Chains this one asked to be friends with, until they accept
+    pub sent_friend_requests: MapView<ChainId, ()>,
+    /// Party this chain is in, as its leader last announced it
+    pub party: RegisterView<Option<Party>>,
+    /// Party invites awaiting an answer, by leader chain
+    pub party_invites: MapView<ChainId, ()>,
+    /// Chains invited into the party this chain leads, until they join
+    pub sent_party_invites: MapView<ChainId, ()>,
+}
+
+impl PlayerState {
+    /// Check that a character may take on a new engagement: it is not already engaged and the
+    /// concurrency cap is not reached
+    pub async fn can_engage(&self, character_id: &str) -> bool {
+        let engaged = self.active_engagements.count().await.unwrap_or(usize::MAX);
+        !self.is_engaged(character_id).await && engaged < *self.max_concurrent_battles.get() as usize
+    }
+
+    /// Whether a character is queued or fighting
+    pub async fn is_engaged(&self, character_id: &str) -> bool {
+        self.active_engagements.contains_key(character_id).await.unwrap_or(true)
+    }
+
+    /// The engagement holding `character_id`, if `chain` holds it
+    pub async fn engagement_with(&self, character_id: &str, chain: ChainId) -> Option<Engagement> {
+        self.active_engagements.get(character_id).await.ok().flatten()
+            .filter(|engagement| engagement.chain == chain)
+    }
+
+    /// The character `owner` may hand to someone else, or why it cannot leave this chain
+    pub async fn outgoing_check(&self, owner: AccountOwner, character_id: &str) -> Result<CharacterData, &'static str> {
+        let Ok(Some(character)) = self.characters.get(character_id).await else {
+            return Err("unknown_character");
+        };
+        if character.owner != owner {
+            return Err("unknown_character");
+        }
+        if self.is_engaged(character_id).await {
+            return Err("character_engaged");
+        }
+        Ok(character)
+    }
+
+    /// The lobby and character `JoinQueue` would queue in `mode`, or why the request is refused.
+    /// Ranked gates are checked against this chain's records; the lobby checks again with its own
+    pub async fn queue_check(&self, character_id: &str, mode: QueueMode) -> Result<(ChainId, CharacterData), String> {
+        let Some(lobby_chain_id) = *self.lobby_chain_id.get() else {
+            return Err("no_lobby".to_string());
+        };
+        let Ok(Some(character)) = self.characters.get(character_id).await else {
+            return Err("unknown_character".to_string());
+        };
+        if !character.is_alive() {
+            return Err("character_dead".to_string());
+        }
+        if !self.can_engage(character_id).await {
+            return Err("character_engaged".to_string());
+        }
+        if mode == QueueMode::Ranked {
+            self.ranked_gates.get().check(character.level, self.player_stats.get().total_battles)?;
+        }
+        Ok((lobby_chain_id, character))
+    }
+
+    /// Whether a `JoinQueue` for `character_id` in `mode` at `stake` clears the progression gates
+    /// on this chain's records, or why not; the lobby checks again with its own
+    pub async fn progression_check(&self, character_id: &str, mode: QueueMode, stake: Amount) -> Result<(), String> {
+        if !ProgressionGates::applies(mode, stake) {
+            return Ok(());
+        }
+        let Ok(Some(character)) = self.characters.get(character_id).await else {
+            return Ok(()); // `queue_check` names the missing character
+        };
+        self.progression.get().check(character.level, *self.progression_wins.get())
+    }
 }
 
 /// Prediction market state - betting on battle outcomes
 #[derive(RootView)]
 #[view(context = ViewStorageContext)]
 pub struct PredictionState {
-    pub variant: RegisterView<String>,
+    pub metadata: RegisterView<ChainMetadata>,
     pub value: RegisterView<u64>,
     pub markets: MapView<u64, Market>,
     pub battle_to_market: MapView<ChainId, u64>,
     pub market_count: RegisterView<u64>,
+    pub public_bettors: RegisterView<bool>,
     pub bets: MapView<(u64, AccountOwner), Bet>,
-    pub user_bet_counts: MapView<AccountOwner, u64>,
-    pub user_volumes: MapView<AccountOwner, Amount>,
+    /// Who bet on each market, in order of their first bet
+    pub market_bettors: MapView<u64, Vec<AccountOwner>>,
+    pub bettor_stats: MapView<AccountOwner, BettorStats>,
     pub total_volume: RegisterView<Amount>,
     pub total_fees_collected: RegisterView<Amount>,
     pub platform_fee_bps: RegisterView<u16>,
     pub treasury_owner: RegisterView<Option<AccountOwner>>,
+    /// Top bettors by profit, reranked as markets settle
     pub betting_leaderboard: RegisterView<Vec<BettingLeaderboardEntry>>,
+    /// Lobby that opened this chain; only it creates markets by message
+    pub lobby_chain_id: RegisterView<Option<ChainId>>,
+    pub rejections: MapView<RejectionKey, RejectionEntry>,
+    /// Lifetime counters that pinned at their maximum, by counter name
+    pub counter_overflows: MapView<String, CounterOverflow>,
+}
+
+impl PredictionState {
+    /// Whether `owner` may administer markets here
+    pub fn is_treasury(&self, owner: AccountOwner) -> bool {
+        *self.treasury_owner.get() == Some(owner)
+    }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::{
+        bcs,
+        linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash},
+    };
+
+    use super::{BattleParticipant, Bps, CharacterClass, CharacterSnapshot};
+
+    #[test]
+    fn character_classes_round_trip_through_the_shared_enum() {
+        for class in majorules::CharacterClass::ALL {
+            let stored = CharacterClass::from(class);
+            assert_eq!(format!("{stored:?}"), format!("{class:?}"));
+            assert_eq!(majorules::CharacterClass::from(stored), class);
+        }
+    }
+
+    #[test]
+    fn snapshots_and_fighters_keep_every_field_across_the_chain_boundary() {
+        let sent = majorules::CharacterSnapshot {
+            nft_id: "alice-1".to_string(),
+            class: majorules::CharacterClass::Trickster,
+            level: 7,
+            hp_max: 140,
+            min_damage: 11,
+            max_damage: 23,
+            crit_chance: Bps(1_200),
+            crit_multiplier: 16_000,
+            dodge_chance: Bps(900),
+            defense: 8,
+            attack_bps: 150,
+            defense_bps: -40,
+            crit_bps: 75,
+        };
+        let stored = CharacterSnapshot::from(sent.clone());
+        let returned = majorules::CharacterSnapshot::from(stored);
+        assert_eq!(bcs::to_bytes(&returned).unwrap(), bcs::to_bytes(&sent).unwrap());
+
+        let owner = AccountOwner::from(CryptoHash::test_hash("alice"));
+        let chain = ChainId(CryptoHash::test_hash("alice"));
+        let fighter = BattleParticipant::from(majorules::BattleParticipant::new(owner, chain, sent, Amount::from_tokens(2)));
+        assert_eq!((fighter.current_hp, fighter.character.dodge_chance, fighter.character.crit_bps), (140, Bps(900), 75));
+        assert_eq!((fighter.stake, fighter.character.class), (Amount::from_tokens(2), CharacterClass::Trickster));
+        assert!(fighter.turns_submitted.iter().all(Option::is_none) && fighter.effects.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use linera_sdk::linera_base_types::{AccountOwner, TimeDelta, Timestamp};
+use serde::{Deserialize, Serialize};
+
+/// Identical rejections within this window share one log entry
+pub const REJECTION_WINDOW: TimeDelta = TimeDelta::from_secs(60);
+
+/// Repeats within one window after which the signer is backed off
+pub const BACKOFF_THRESHOLD: u32 = 100;
+
+/// How long a backed-off rejection is dropped without any bookkeeping
+pub const BACKOFF: TimeDelta = TimeDelta::from_secs(300);
+
+/// What makes two rejections identical
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RejectionKey {
+    /// Operation or message variant that was rejected
+    pub operation: String,
+    pub reason: String,
+    pub signer: AccountOwner,
+}
+
+impl RejectionKey {
+    pub fn new(operation: &str, reason: &str, signer: AccountOwner) -> Self {
+        Self {
+            operation: operation.to_string(),
+            reason: reason.to_string(),
+            signer,
+        }
+    }
+}
+
+/// Coalesced log row for repeated identical rejections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectionEntry {
+    pub window_start: Timestamp,
+    pub last_at: Timestamp,
+    pub count: u32,
+    /// Set once `count` reaches the threshold; rejections are dropped until then
+    pub backoff_until: Option<Timestamp>,
+}
+
+/// How a rejection path should react to a rejection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionVerdict {
+    /// Signer is backed off: do not log or message anything
+    Suppressed,
+    /// Rejection was logged; only the first one in a window may send feedback
+    Logged { send_feedback: bool },
+}
+
+impl RejectionVerdict {
+    pub fn send_feedback(self) -> bool {
+        matches!(self, Self::Logged { send_feedback: true })
+    }
+}
+
+impl RejectionEntry {
+    /// Fold a rejection at `now` into the previous entry for its key.
+    /// Returns the entry to store, or `None` when nothing should be written.
+    pub fn observe(previous: Option<Self>, now: Timestamp) -> (Option<Self>, RejectionVerdict) {
+        if let Some(mut entry) = previous {
+            match entry.backoff_until {
+                Some(until) if now < until => return (None, RejectionVerdict::Suppressed),
+                None if now < entry.window_start.saturating_add(REJECTION_WINDOW) => {
+                    entry.count = entry.count.saturating_add(1);
+                    entry.last_at = now;
+                    if entry.count >= BACKOFF_THRESHOLD {
+                        entry.backoff_until = Some(now.saturating_add(BACKOFF));
+                    }
+                    return (Some(entry), RejectionVerdict::Logged { send_feedback: false });
+                }
+                _ => {}
+            }
+        }
+
+        let entry = Self {
+            window_start: now,
+            last_at: now,
+            count: 1,
+            backoff_until: None,
+        };
+        (Some(entry), RejectionVerdict::Logged { send_feedback: true })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Timestamp;
+
+    use super::{RejectionEntry, RejectionVerdict, BACKOFF, BACKOFF_THRESHOLD, REJECTION_WINDOW};
+
+    /// Observe `times` rejections one millisecond apart, storing entries as a log would
+    fn observe_many(mut entry: Option<RejectionEntry>, start: u64, times: u64) -> (Option<RejectionEntry>, Vec<RejectionVerdict>) {
+        let mut verdicts = Vec::new();
+        for i in 0..times {
+            let (next, verdict) = RejectionEntry::observe(entry.clone(), Timestamp::from(start + i * 1_000));
+            if next.is_some() {
+                entry = next;
+            }
+            verdicts.push(verdict);
+        }
+        (entry, verdicts)
+    }
+
+    #[test]
+    fn identical_rejections_coalesce_within_window() {
+        let (entry, verdicts) = observe_many(None, 0, 50);
+        let entry = entry.unwrap();
+
+        assert_eq!((entry.count, entry.window_start), (50, Timestamp::from(0)));
+        assert_eq!(entry.last_at, Timestamp::from(49_000));
+        assert_eq!(verdicts.iter().filter(|verdict| verdict.send_feedback()).count(), 1);
+        assert!(verdicts[0].send_feedback());
+    }
+
+    #[test]
+    fn new_window_starts_a_fresh_entry() {
+        let (entry, _) = observe_many(None, 0, 10);
+        let later = Timestamp::from(REJECTION_WINDOW.as_micros());
+
+        let (entry, verdict) = RejectionEntry::observe(entry, later);
+
+        assert_eq!(entry.unwrap().count, 1);
+        assert_eq!(verdict, RejectionVerdict::Logged { send_feedback: true });
+    }
+
+    #[test]
+    fn repeat_offenders_are_backed_off() {
+        let (entry, verdicts) = observe_many(None, 0, BACKOFF_THRESHOLD as u64 + 5);
+        let entry = entry.unwrap();
+
+        assert_eq!(entry.count, BACKOFF_THRESHOLD);
+        assert!(verdicts[BACKOFF_THRESHOLD as usize..].iter().all(|verdict| *verdict == RejectionVerdict::Suppressed));
+
+        let until = entry.backoff_until.unwrap();
+        assert_eq!(until, entry.last_at.saturating_add(BACKOFF));
+        let (next, verdict) = RejectionEntry::observe(Some(entry), until);
+        assert_eq!(next.unwrap().count, 1);
+        assert!(verdict.send_feedback());
+    }
+}
@@ -0,0 +1,79 @@
+//! Timestamp helpers for mixing local and remote clocks.
+//!
+//! Chains keep their own clocks, so a timestamp produced on another chain may be ahead
+//! of or behind the local one. Deadlines are always computed from the acting chain's
+//! `system_time()` plus a duration; remote timestamps are only ever clamped and bucketed,
+//! never trusted as deadlines.
+
+use linera_sdk::linera_base_types::{TimeDelta, Timestamp};
+
+/// Microseconds in a day
+pub const MICROS_PER_DAY: u64 = 86_400_000_000;
+
+/// How far ahead of the local clock a remote timestamp may be before it is clamped
+pub const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::from_secs(30);
+
+/// Time elapsed from `earlier` to `later`, or zero when the clocks disagree on the order
+pub fn delta_or_zero(later: Timestamp, earlier: Timestamp) -> TimeDelta {
+    later.delta_since(earlier)
+}
+
+/// Day number since the Unix epoch, used to key daily counters
+pub fn bucket_day(timestamp: Timestamp) -> u64 {
+    timestamp.micros() / MICROS_PER_DAY
+}
+
+/// A remote timestamp as the local chain may use it: values further in the future
+/// than `MAX_CLOCK_SKEW` are pulled back to `local_now`
+pub fn clamp_remote(remote: Timestamp, local_now: Timestamp) -> Timestamp {
+    if remote > local_now.saturating_add(MAX_CLOCK_SKEW) {
+        local_now
+    } else {
+        remote
+    }
+}
+
+/// Local deadline `duration` after the local chain acts
+pub fn deadline_after(local_now: Timestamp, duration: TimeDelta) -> Timestamp {
+    local_now.saturating_add(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::{TimeDelta, Timestamp};
+
+    use super::{bucket_day, clamp_remote, deadline_after, delta_or_zero, MAX_CLOCK_SKEW, MICROS_PER_DAY};
+
+    #[test]
+    fn future_remote_timestamps_give_zero_latency() {
+        let now = Timestamp::from(10_000_000);
+        let sent_at = Timestamp::from(70_000_000);
+
+        assert_eq!(delta_or_zero(now, sent_at), TimeDelta::ZERO);
+        assert_eq!(delta_or_zero(sent_at, now), TimeDelta::from_secs(60));
+    }
+
+    #[test]
+    fn skewed_remote_timestamps_bucket_deterministically() {
+        // Local clock just before midnight, remote clock an hour into the next day
+        let now = Timestamp::from(MICROS_PER_DAY - 1_000_000);
+        let remote = Timestamp::from(MICROS_PER_DAY + 3_600_000_000);
+
+        assert_eq!(bucket_day(clamp_remote(remote, now)), 0);
+        assert_eq!(bucket_day(clamp_remote(remote, now)), bucket_day(now));
+
+        // Within the tolerance the remote value is kept as is
+        let slightly_ahead = now.saturating_add(MAX_CLOCK_SKEW);
+        assert_eq!(clamp_remote(slightly_ahead, now), slightly_ahead);
+        assert_eq!(bucket_day(slightly_ahead), 1);
+    }
+
+    #[test]
+    fn deadlines_follow_the_local_clock() {
+        let now = Timestamp::from(5_000_000);
+        let deadline = deadline_after(now, TimeDelta::from_secs(30));
+
+        assert_eq!(deadline, Timestamp::from(35_000_000));
+        assert_eq!(deadline_after(Timestamp::from(u64::MAX), TimeDelta::from_secs(1)), Timestamp::from(u64::MAX));
+    }
+}
@@ -0,0 +1,59 @@
+//! Battle token supply.
+//!
+//! Balances live on player chains, which only move tokens they hold. The lobby is the one mint
+//! authority and keeps the supply ledger; fees the platform collects leave circulation, so
+//! they count as burned.
+
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Tokens the lobby created and destroyed over its lifetime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct TokenSupply {
+    pub minted: Amount,
+    /// Collected fees; a lifetime counter, see `counters::TOKENS_BURNED`
+    pub burned: Amount,
+}
+
+impl TokenSupply {
+    /// Count `amount` as minted, refusing a mint the ledger could not count exactly
+    pub fn mint(&mut self, amount: Amount) -> Result<(), &'static str> {
+        if amount == Amount::ZERO {
+            return Err("zero_amount");
+        }
+        self.minted = self.minted.try_add(amount).map_err(|_| "supply_overflow")?;
+        Ok(())
+    }
+
+    /// Tokens minted and not burned since
+    pub fn circulating(&self) -> Amount {
+        self.minted.saturating_sub(self.burned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::Amount;
+
+    use super::TokenSupply;
+
+    #[test]
+    fn mints_add_up_and_burns_leave_circulation() {
+        let mut supply = TokenSupply::default();
+        supply.mint(Amount::from_tokens(7)).unwrap();
+        supply.mint(Amount::from_tokens(3)).unwrap();
+        supply.burned = Amount::from_tokens(4);
+
+        assert_eq!(supply.minted, Amount::from_tokens(10));
+        assert_eq!(supply.circulating(), Amount::from_tokens(6));
+    }
+
+    #[test]
+    fn empty_and_unaccountable_mints_are_refused() {
+        let mut supply = TokenSupply { minted: Amount::MAX, burned: Amount::ZERO };
+        assert_eq!(supply.mint(Amount::ZERO), Err("zero_amount"));
+        assert_eq!(supply.mint(Amount::from_attos(1)), Err("supply_overflow"));
+        assert_eq!(supply.minted, Amount::MAX);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Integration tests for the lobby and player chain flows.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::str::FromStr;
+
+use linera_sdk::{
+    linera_base_types::{
+        AccountOwner, Amount, ApplicationId, ApplicationPermissions, ChainDescription, ChainId, ChainOrigin,
+        ChainOwnership, Epoch, InitialChainConfig, TimeDelta,
+    },
+    test::{ActiveChain, QueryOutcome, TestValidator},
+};
+use majorules::{ChainVariant, InitializationArgument, MajorulesAbi, Operation, Parameters, QueueMode};
+
+/// Deploy the application on a new chain acting as the lobby
+async fn lobby() -> (TestValidator, ActiveChain, ApplicationId<MajorulesAbi>) {
+    let (validator, module_id) =
+        TestValidator::with_current_module::<MajorulesAbi, Parameters, InitializationArgument>().await;
+    let mut chain = validator.new_chain().await;
+
+    let argument = InitializationArgument {
+        variant: ChainVariant::Lobby,
+        treasury_owner: Some(chain.public_key().into()),
+        platform_fee_bps: None,
+        max_concurrent_battles: None,
+        public_bettors: None,
+        obfuscated_ids: None,
+        market_dust_to: None,
+        matchmaking: None,
+    };
+    let application_id = chain.create_application(module_id, Parameters::default(), argument, vec![]).await;
+    (validator, chain, application_id)
+}
+
+/// Open a player chain for the lobby chain's owner and drive it with the same key
+async fn create_player_chain(
+    validator: &TestValidator,
+    lobby: &ActiveChain,
+    application_id: ApplicationId<MajorulesAbi>,
+) -> ActiveChain {
+    let owner: AccountOwner = lobby.public_key().into();
+    let certificate = lobby
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::CreatePlayerChain);
+        })
+        .await;
+    let header = &certificate.inner().block().header;
+
+    // Rebuild the description the lobby used when it opened the chain
+    let description = ChainDescription::new(
+        ChainOrigin::Child {
+            parent: lobby.id(),
+            block_height: header.height,
+            chain_index: 0,
+        },
+        InitialChainConfig {
+            ownership: ChainOwnership::single(owner),
+            epoch: Epoch::ZERO,
+            min_active_epoch: Epoch::ZERO,
+            max_active_epoch: Epoch::ZERO,
+            balance: Amount::ZERO,
+            application_permissions: ApplicationPermissions::default(),
+        },
+        header.timestamp,
+    );
+    let registered = query(lobby, application_id, &format!("{{ player(owner: \"{owner}\") {{ ownerChain }} }}")).await;
+    let player_chain_id = ChainId::from_str(registered["player"]["ownerChain"].as_str().unwrap()).unwrap();
+    assert_eq!(description.id(), player_chain_id);
+
+    let player = ActiveChain::new(lobby.key_pair().copy(), description, validator.clone());
+    validator.add_chain(player.clone());
+    player.handle_received_messages().await;
+    player
+}
+
+async fn query(chain: &ActiveChain, application_id: ApplicationId<MajorulesAbi>, query: &str) -> serde_json::Value {
+    let QueryOutcome { response, .. } = chain.graphql_query(application_id, query).await;
+    response
+}
+
+async fn queue_joins_left(lobby: &ActiveChain, application_id: ApplicationId<MajorulesAbi>) -> u64 {
+    let owner: AccountOwner = lobby.public_key().into();
+    let allowance = query(lobby, application_id, &format!("{{ creationAllowance(owner: \"{owner}\") {{ queueJoins }} }}")).await;
+    allowance["creationAllowance"]["queueJoins"].as_u64().unwrap()
+}
+
+/// Creating a player chain registers it on the lobby and counts against the daily cap
+#[tokio::test(flavor = "multi_thread")]
+async fn create_player_chain_registers_the_chain() {
+    let (validator, lobby, application_id) = lobby().await;
+
+    let player = create_player_chain(&validator, &lobby, application_id).await;
+
+    let owner: AccountOwner = lobby.public_key().into();
+    let response = query(&lobby, application_id, &format!(
+        "{{ player(owner: \"{owner}\") {{ ownerChain isAlive }} creationAllowance(owner: \"{owner}\") {{ playerChains }} }}"
+    )).await;
+    assert_eq!(response["player"]["ownerChain"], player.id().to_string());
+    assert_eq!(response["player"]["isAlive"], true);
+    assert_eq!(response["creationAllowance"]["playerChains"], 1);
+}
+
+/// A queue request travels to the lobby, and leaving the queue releases the character
+/// on the player chain so it can queue again
+#[tokio::test(flavor = "multi_thread")]
+async fn join_queue_round_trip() {
+    let (validator, lobby, application_id) = lobby().await;
+    let player = create_player_chain(&validator, &lobby, application_id).await;
+    let join = || Operation::JoinQueue {
+        character_id: "hero".to_string(),
+        stake: Amount::from_tokens(1),
+        mode: QueueMode::Casual,
+        best_of: 1,
+    };
+
+    // Each join locks its stake out of the player chain's balance
+    let owner: AccountOwner = lobby.public_key().into();
+    lobby
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::MintTokens { to: owner, amount: Amount::from_tokens(2) });
+        })
+        .await;
+    player.handle_received_messages().await;
+
+    player
+        .add_block(|block| {
+            block
+                .with_operation(application_id, Operation::MintCharacter {
+                    character_id: "hero".to_string(),
+                    class: "warrior".to_string(),
+                })
+                .with_operation(application_id, join());
+        })
+        .await;
+    lobby.handle_received_messages().await;
+    assert_eq!(queue_joins_left(&lobby, application_id).await, 49);
+
+    lobby
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::LeaveQueue);
+        })
+        .await;
+    player.handle_received_messages().await;
+
+    let rejoin = player
+        .add_block(|block| {
+            block.with_operation(application_id, join());
+        })
+        .await;
+    // A player who left waits out the rejoin cooldown before the lobby takes them back
+    validator.clock().add(TimeDelta::from_secs(30));
+    let after_cooldown = validator.clock().current_time();
+    lobby
+        .add_block(|block| {
+            block.with_messages_from(&rejoin).with_timestamp(after_cooldown);
+        })
+        .await;
+    assert_eq!(queue_joins_left(&lobby, application_id).await, 48);
+}
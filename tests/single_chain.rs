@@ -5,22 +5,31 @@
 
 #![cfg(not(target_arch = "wasm32"))]
 
-use majorules::Operation;
+use majorules::{BattleRules, ChainVariant, InitializationArgument, Operation};
 use linera_sdk::test::{QueryOutcome, TestValidator};
 
-/// Tests setting and incrementing a counter
+/// Tests incrementing the lobby counter
 ///
-/// Creates the application on a `chain`, initializing it with a 10 then add 10 and obtain 20.
-/// which is then checked.
+/// Creates the application on a `chain` as a lobby, which starts the counter at 0, then adds 10
+/// and checks the result.
 #[tokio::test(flavor = "multi_thread")]
 async fn single_chain_test() {
     let (validator, module_id) =
-        TestValidator::with_current_module::<majorules::MajorulesAbi, (), u64>().await;
+        TestValidator::with_current_module::<majorules::MajorulesAbi, majorules::Parameters, InitializationArgument>().await;
     let mut chain = validator.new_chain().await;
 
-    let initial_state = 10u64;
+    let argument = InitializationArgument {
+        variant: ChainVariant::Lobby,
+        treasury_owner: None,
+        platform_fee_bps: None,
+        max_concurrent_battles: None,
+        public_bettors: None,
+        obfuscated_ids: None,
+        market_dust_to: None,
+        matchmaking: None,
+    };
     let application_id = chain
-        .create_application(module_id, (), initial_state, vec![])
+        .create_application(module_id, majorules::Parameters::default(), argument, vec![])
         .await;
 
     let increment = 10u64;
@@ -30,10 +39,50 @@ async fn single_chain_test() {
         })
         .await;
 
-    let final_value = initial_state + increment;
+    let final_value = increment;
     let QueryOutcome { response, .. } =
         chain.graphql_query(application_id, "query { value }").await;
     let state_value = response["value"].as_u64().expect("Failed to get the u64");
 
     assert_eq!(state_value, final_value);
 }
+
+/// Tests that a treasury rules update bumps the version the lobby reports
+#[tokio::test(flavor = "multi_thread")]
+async fn treasury_updates_battle_rules() {
+    let (validator, module_id) =
+        TestValidator::with_current_module::<majorules::MajorulesAbi, majorules::Parameters, InitializationArgument>().await;
+    let mut chain = validator.new_chain().await;
+
+    let argument = InitializationArgument {
+        variant: ChainVariant::Lobby,
+        treasury_owner: Some(chain.public_key().into()),
+        platform_fee_bps: None,
+        max_concurrent_battles: None,
+        public_bettors: None,
+        obfuscated_ids: None,
+        market_dust_to: None,
+        matchmaking: None,
+    };
+    let application_id = chain
+        .create_application(module_id, majorules::Parameters::default(), argument, vec![])
+        .await;
+
+    let rules = BattleRules {
+        max_rounds: 5,
+        ..BattleRules::default()
+    };
+    chain
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::UpdateBattleRules { rules: rules.clone() });
+        })
+        .await;
+
+    let QueryOutcome { response, .. } = chain
+        .graphql_query(application_id, "query { battleRules { version digest maxRounds } }")
+        .await;
+
+    assert_eq!(response["battleRules"]["version"], 2);
+    assert_eq!(response["battleRules"]["digest"], rules.digest());
+    assert_eq!(response["battleRules"]["maxRounds"], 5);
+}